@@ -41,6 +41,6 @@
 
 use cgx::cgx_main;
 
-fn main() -> cgx::SnafuReport<cgx::Error> {
+fn main() -> std::process::ExitCode {
     cgx_main()
 }
@@ -0,0 +1,203 @@
+//! Resolving crates as they were on a past date, for `--as-of`, to reproduce an old environment.
+//!
+//! With `--as-of 2024-06-01`, a crate from crates.io resolves to the newest version matching its
+//! requirement that was published before that date (midnight UTC), going by the publication time
+//! crates.io records for each version.  Other registries don't make those known, so a crate from
+//! one of them resolves as it would without `--as-of`, with a warning.  Whether a version is yanked
+//! is only known as it is now, so a version yanked since the date is still passed over.
+//!
+//! A crate from a git repository or forge with no branch, tag or commit given resolves to the
+//! newest release tag, like `v1.2.3` or `1.2.3`, whose commit was made before the date, or if there
+//! isn't one, to the last commit on the default branch made before the date.  A branch, tag or
+//! commit that's given is used as it is.
+//!
+//! A resolution as of a date is cached apart from the current one for the same crate, and never
+//! taken to be overtaken by a newer release.  The date is recorded in the manifest written by
+//! `--freeze` and the plan written by `--plan`.
+
+use crate::{Result, config::Config, error, http::HttpClient};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use semver::Version;
+use serde::Deserialize;
+use snafu::ResultExt;
+use std::collections::HashMap;
+
+/// The format of an `--as-of` date.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The page size asked for when listing the versions of a crate on crates.io.
+const VERSIONS_PER_PAGE: &str = "100";
+
+/// The parts of a page of the crates.io versions of a crate that are used.
+#[derive(Debug, Deserialize)]
+struct VersionsResponse {
+    versions: Vec<PublishedVersion>,
+
+    #[serde(default)]
+    meta: Option<VersionsMeta>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PublishedVersion {
+    num: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VersionsMeta {
+    /// The query string of the next page, if there is one.
+    #[serde(default)]
+    next_page: Option<String>,
+}
+
+/// Parse an `--as-of` date, like `2024-06-01`.
+pub(crate) fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), DATE_FORMAT).context(error::InvalidAsOfDateSnafu { value })
+}
+
+/// The moment `date` starts, in UTC, before which whatever it's resolved to has to have been
+/// published or committed.
+pub(crate) fn cutoff(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc()
+}
+
+/// When each version of the crate `name` was published to crates.io, by version, or `None` if
+/// crates.io has no such crate.
+pub(crate) fn crates_io_publish_times(
+    config: &Config,
+    http_client: &HttpClient,
+    name: &str,
+) -> Result<Option<HashMap<String, DateTime<Utc>>>> {
+    let url = config
+        .endpoints
+        .crates_io_api
+        .join(&format!("api/v1/crates/{}/versions", name))
+        .expect("BUG: relative URL is valid");
+    let mut page = url.clone();
+    page.query_pairs_mut().append_pair("per_page", VERSIONS_PER_PAGE);
+
+    let mut published = HashMap::new();
+    loop {
+        let Some(body) = http_client.try_download(page.as_str())? else {
+            return Ok(None);
+        };
+        let response: VersionsResponse = serde_json::from_slice(&body).context(error::JsonSnafu)?;
+        published.extend(
+            response
+                .versions
+                .into_iter()
+                .map(|version| (version.num, version.created_at)),
+        );
+
+        match response.meta.and_then(|meta| meta.next_page) {
+            Some(next_page) => {
+                page = url.clone();
+                page.set_query(Some(next_page.trim_start_matches('?')));
+            }
+            None => return Ok(Some(published)),
+        }
+    }
+}
+
+/// The newest of `tags` that names a release, like `v1.2.3` or `1.2.3`, and whose commit was made
+/// before `before`, in seconds since the epoch.  Each tag comes with the time of its commit.
+///
+/// Pre-release tags, like `v2.0.0-rc.1`, are only picked if there are no releases.
+#[cfg_attr(not(feature = "git"), allow(dead_code))]
+pub(crate) fn release_tag(tags: &[(String, i64)], before: i64) -> Option<&str> {
+    tags.iter()
+        .filter(|(_, committed)| *committed < before)
+        .filter_map(|(tag, _)| release_version(tag).map(|version| (tag, version)))
+        .max_by(|(_, a), (_, b)| a.pre.is_empty().cmp(&b.pre.is_empty()).then_with(|| a.cmp(b)))
+        .map(|(tag, _)| tag.as_str())
+}
+
+/// The version the release tag `tag` is for, if it is one.
+#[cfg_attr(not(feature = "git"), allow(dead_code))]
+fn release_version(tag: &str) -> Option<Version> {
+    Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use httpmock::prelude::*;
+    use url::Url;
+
+    #[test]
+    fn dates_are_parsed() {
+        let date = parse_date("2024-06-01").unwrap();
+        assert_eq!(cutoff(date).to_rfc3339(), "2024-06-01T00:00:00+00:00");
+
+        for value in ["2024-13-01", "June 1st", "2024-06-01T12:00:00Z"] {
+            assert_matches!(
+                parse_date(value),
+                Err(error::Error::InvalidAsOfDate { .. }),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn newest_release_tag_before_the_date() {
+        let tags = [
+            ("v1.0.0".to_string(), 100),
+            ("1.1.0".to_string(), 200),
+            ("v2.0.0-rc.1".to_string(), 300),
+            ("nightly".to_string(), 350),
+            ("v2.0.0".to_string(), 400),
+        ];
+
+        assert_eq!(release_tag(&tags, 500), Some("v2.0.0"));
+        assert_eq!(release_tag(&tags, 400), Some("1.1.0"));
+        assert_eq!(release_tag(&tags[2..4], 400), Some("v2.0.0-rc.1"));
+        assert_eq!(release_tag(&tags, 100), None);
+    }
+
+    #[test]
+    fn publish_times_are_read_across_pages() {
+        let server = MockServer::start();
+        let first = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v1/crates/tool/versions")
+                .query_param("per_page", "100")
+                .query_param_missing("seek");
+            then.status(200).header("content-type", "application/json").body(
+                r#"{"versions": [{"num": "2.0.0", "created_at": "2024-07-01T10:00:00.000000+00:00"}],
+                    "meta": {"total": 2, "next_page": "?per_page=100&seek=abc"}}"#,
+            );
+        });
+        let second = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v1/crates/tool/versions")
+                .query_param("seek", "abc");
+            then.status(200).header("content-type", "application/json").body(
+                r#"{"versions": [{"num": "1.0.0", "created_at": "2024-01-15T08:30:00.000000+00:00"}],
+                    "meta": {"total": 2, "next_page": null}}"#,
+            );
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/api/v1/crates/missing/versions");
+            then.status(404);
+        });
+
+        let mut config = Config::default();
+        config.endpoints.crates_io_api = Url::parse(&server.base_url()).unwrap();
+        let http_client = HttpClient::new(&config.http).unwrap();
+
+        let published = crates_io_publish_times(&config, &http_client, "tool")
+            .unwrap()
+            .unwrap();
+        first.assert();
+        second.assert();
+        assert_eq!(published.len(), 2);
+        assert!(published["1.0.0"] < cutoff(parse_date("2024-06-01").unwrap()));
+        assert!(published["2.0.0"] > cutoff(parse_date("2024-06-01").unwrap()));
+
+        assert_eq!(
+            crates_io_publish_times(&config, &http_client, "missing").unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Staging of the auxiliary files some tools need at runtime, next to their cached binaries.
+//!
+//! Some tools expect to find data files such as templates or shell completions relative to the
+//! workspace they were built in, which doesn't exist once the binary is in the cache.  The
+//! [`ToolAssets`] config of such a tool lists the files in its source to keep, which are copied
+//! into a directory beside the binary (see [`staged_dir`]).  The directory's name is derived from
+//! the patterns, so that changing them stages a fresh copy rather than reusing a stale one.
+//!
+//! The source of a crate from a registry or git is immutable, so its assets are only staged once.
+//! Those of a crate in a local directory are staged again every time, since they may have changed.
+
+use crate::{Result, config::ToolAssets, error, helpers::long_path};
+use ignore::{
+    WalkBuilder,
+    overrides::{Override, OverrideBuilder},
+};
+use sha2::{Digest, Sha256};
+use snafu::ResultExt;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::*;
+
+/// The directory that the assets of the tool whose binary is at `bin_path` are staged in.
+///
+/// This is where [`ToolAssets::env_var`] points when the tool is run.
+pub fn staged_dir(bin_path: &Path, assets: &ToolAssets) -> PathBuf {
+    let mut hasher = Sha256::new();
+    for pattern in &assets.include {
+        hasher.update(pattern.as_bytes());
+        hasher.update(b"\n");
+    }
+    let hash = crate::helpers::format_hex_lower(hasher.finalize());
+
+    bin_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(format!("assets-{}", &hash[..16]))
+}
+
+/// A matcher for the files of `tool` selected by `assets`, relative to `root`.
+pub(crate) fn matcher(tool: &str, root: &Path, assets: &ToolAssets) -> Result<Override> {
+    let mut builder = OverrideBuilder::new(root);
+    for pattern in &assets.include {
+        builder
+            .add(pattern)
+            .context(error::InvalidAssetPatternSnafu { tool, pattern })?;
+    }
+
+    builder.build().context(error::InvalidAssetPatternSnafu {
+        tool,
+        pattern: assets.include.join(", "),
+    })
+}
+
+/// Copy the files of `tool` selected by `assets` from its source in `source_dir` into the staged
+/// asset directory for the binary at `bin_path`, and return that directory.
+///
+/// If the assets were already staged they are left as they are, unless `refresh` is set.
+pub(crate) fn stage(
+    tool: &str,
+    source_dir: &Path,
+    bin_path: &Path,
+    assets: &ToolAssets,
+    refresh: bool,
+) -> Result<PathBuf> {
+    let dst = staged_dir(bin_path, assets);
+    if dst.is_dir() && !refresh {
+        debug!("Assets of {} already staged in {}", tool, dst.display());
+        return Ok(dst);
+    }
+
+    let parent = dst.parent().expect("BUG: staged asset dir has no parent");
+    let temp_dir = tempfile::Builder::new()
+        .prefix(".assets-")
+        .tempdir_in(long_path(parent))
+        .map_err(|e| Box::new(e) as _)
+        .context(error::StageAssetsSnafu { tool, dst: &dst })?;
+
+    let copied = copy_matching(tool, source_dir, temp_dir.path(), assets)
+        .map_err(|e| Box::new(e) as _)
+        .context(error::StageAssetsSnafu { tool, dst: &dst })?;
+    if copied == 0 {
+        warn!(
+            "None of the asset patterns of {} ({}) matched any files in {}",
+            tool,
+            assets.include.join(", "),
+            source_dir.display()
+        );
+    }
+
+    if refresh && dst.exists() {
+        fs::remove_dir_all(long_path(&dst))
+            .map_err(|e| Box::new(e) as _)
+            .context(error::StageAssetsSnafu { tool, dst: &dst })?;
+    }
+
+    // Moving the finished directory into place means a concurrent run never sees it half copied
+    let temp_path = temp_dir.keep();
+    if let Err(e) = fs::rename(long_path(&temp_path), long_path(&dst)) {
+        let _ = fs::remove_dir_all(long_path(&temp_path));
+
+        // Another cgx process staging the same assets got there first, which is just as good
+        if !dst.is_dir() {
+            return Err(Box::new(e) as _).context(error::StageAssetsSnafu { tool, dst: &dst });
+        }
+    }
+
+    debug!("Staged {} asset files of {} in {}", copied, tool, dst.display());
+    Ok(dst)
+}
+
+/// Copy the files in `src` that `assets` selects into `dst`, keeping their relative paths.
+///
+/// Returns the number of files copied.
+fn copy_matching(tool: &str, src: &Path, dst: &Path, assets: &ToolAssets) -> Result<usize> {
+    let matcher = matcher(tool, src, assets)?;
+
+    // Like the build directory copy, this skips whatever the crate's .gitignore does, which in a
+    // local checkout keeps the walk out of `target`
+    let walker = WalkBuilder::new(src)
+        .hidden(false)
+        .git_ignore(true)
+        .git_exclude(true)
+        .build();
+
+    let mut copied = 0;
+    for entry in walker {
+        let entry = entry
+            .map_err(|e| Box::new(e) as _)
+            .context(error::CopySourceTreeSnafu { src, dst })?;
+        if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+            continue;
+        }
+
+        let rel_path = entry
+            .path()
+            .strip_prefix(src)
+            .expect("BUG: walked path is outside the walked dir");
+        if !matcher.matched(rel_path, false).is_whitelist() {
+            continue;
+        }
+
+        let dst_path = dst.join(rel_path);
+        if let Some(parent) = dst_path.parent() {
+            fs::create_dir_all(long_path(parent)).context(error::IoSnafu { path: parent })?;
+        }
+        fs::copy(long_path(entry.path()), long_path(&dst_path))
+            .context(error::IoSnafu { path: entry.path() })?;
+        copied += 1;
+    }
+
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn assets(include: &[&str]) -> ToolAssets {
+        ToolAssets {
+            include: include.iter().map(ToString::to_string).collect(),
+            env: None,
+        }
+    }
+
+    /// A crate source with some files worth staging and some that aren't.
+    fn source_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [
+            "Cargo.toml",
+            "src/main.rs",
+            "templates/page.html",
+            "templates/partials/header.html",
+            "completions/tool.bash",
+            "completions/tool.fish",
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, path.to_string_lossy().as_bytes()).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn stages_matching_files_next_to_binary() {
+        let source = source_dir();
+        let bin_dir = tempfile::tempdir().unwrap();
+        let bin_path = bin_dir.path().join("tool");
+        let assets = assets(&["templates/**", "completions/*.bash"]);
+
+        let staged = stage("tool", source.path(), &bin_path, &assets, false).unwrap();
+
+        assert_eq!(staged, staged_dir(&bin_path, &assets));
+        assert_eq!(staged.parent(), Some(bin_dir.path()));
+        assert!(staged.join("templates/page.html").is_file());
+        assert!(staged.join("templates/partials/header.html").is_file());
+        assert!(staged.join("completions/tool.bash").is_file());
+        assert!(!staged.join("completions/tool.fish").exists());
+        assert!(!staged.join("src/main.rs").exists());
+    }
+
+    #[test]
+    fn staged_assets_are_reused_unless_refreshed() {
+        let source = source_dir();
+        let bin_dir = tempfile::tempdir().unwrap();
+        let bin_path = bin_dir.path().join("tool");
+        let assets = assets(&["templates/*.html"]);
+
+        let staged = stage("tool", source.path(), &bin_path, &assets, false).unwrap();
+        fs::write(source.path().join("templates/page.html"), b"changed").unwrap();
+
+        stage("tool", source.path(), &bin_path, &assets, false).unwrap();
+        assert_ne!(fs::read(staged.join("templates/page.html")).unwrap(), b"changed");
+
+        stage("tool", source.path(), &bin_path, &assets, true).unwrap();
+        assert_eq!(fs::read(staged.join("templates/page.html")).unwrap(), b"changed");
+    }
+
+    #[test]
+    fn different_patterns_stage_separately() {
+        let bin_path = Path::new("/cache/bins/tool-1.0.0/abc/def/tool");
+
+        assert_ne!(
+            staged_dir(bin_path, &assets(&["templates/**"])),
+            staged_dir(bin_path, &assets(&["completions/**"]))
+        );
+    }
+
+    #[test]
+    fn invalid_pattern_is_an_error() {
+        let result = matcher("tool", Path::new(""), &assets(&["templates/[*"]));
+
+        assert_matches!(
+            result,
+            Err(error::Error::InvalidAssetPattern { tool, pattern, .. })
+                if tool == "tool" && pattern == "templates/[*"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Hard linking byte-identical binaries in [`Config::bin_dir`] so that they're only stored once.
+//!
+//! Builds of a crate that differ only in options that don't change the output, such as one with
+//! `--locked` and one without when the lockfile resolves the same way, are cached under separate
+//! build keys, and often produce exactly the same binary.  After a binary is stored in the build
+//! cache, it's replaced with a hard link to an identical binary already there, if there is one.
+//!
+//! Only the other builds of the same crate and version, in the same `<crate>-<version>` directory,
+//! are looked at, and only their binaries of the same name and size are read, so nothing has to be
+//! kept about which binaries share contents, and nothing is left to go stale when builds are
+//! removed.  A hard link is its own entry in the directory it's in, so removing one build never
+//! takes away the binary of another, and the space a binary takes is freed once the last build
+//! that shares it is removed.
+//!
+//! Deduplication only ever saves space, so when it can't be done, such as on a filesystem without
+//! hard links, the binary is left as the copy it is, and that's only logged.
+//!
+//! [`Config::bin_dir`]: crate::config::Config::bin_dir
+
+use crate::helpers::long_path;
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::*;
+
+/// Replace `binary`, which was just stored in `bin_dir`, with a hard link to an identical binary of
+/// the same crate and version already there, if there is one.
+pub(crate) fn deduplicate(bin_dir: &Path, binary: &Path) {
+    if let Err(e) = try_deduplicate(bin_dir, binary) {
+        debug!("Failed to deduplicate {}: {}", binary.display(), e);
+    }
+}
+
+fn try_deduplicate(bin_dir: &Path, binary: &Path) -> io::Result<()> {
+    let crate_dir = binary
+        .strip_prefix(bin_dir)
+        .ok()
+        .and_then(|relative| relative.components().next())
+        .map(|crate_dir| bin_dir.join(crate_dir))
+        .ok_or_else(|| io::Error::other("the binary isn't in bin_dir"))?;
+    let file_name = binary
+        .file_name()
+        .ok_or_else(|| io::Error::other("the binary has no file name"))?;
+    let data = fs::read(long_path(binary))?;
+
+    let Some(identical) = find_identical(&crate_dir, binary, file_name, &data) else {
+        return Ok(());
+    };
+
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(".dedup.tmp");
+    let temp_path = binary.with_file_name(temp_name);
+    let _ = fs::remove_file(long_path(&temp_path));
+    fs::hard_link(long_path(&identical), long_path(&temp_path))?;
+    if let Err(e) = fs::rename(long_path(&temp_path), long_path(binary)) {
+        let _ = fs::remove_file(long_path(&temp_path));
+        return Err(e);
+    }
+    debug!(
+        "Hard linked {} to the identical {}",
+        binary.display(),
+        identical.display()
+    );
+
+    Ok(())
+}
+
+/// Another binary named `file_name` in a build under `crate_dir` than `binary`, whose contents are
+/// `data`, if there is one.
+///
+/// Builds are kept in `<crate>-<version>/<source hash>/<build options hash>/`, so the binaries of
+/// the other builds of a crate are two directories down from `crate_dir`.
+fn find_identical(crate_dir: &Path, binary: &Path, file_name: &OsStr, data: &[u8]) -> Option<PathBuf> {
+    fs::read_dir(long_path(crate_dir))
+        .ok()?
+        .flatten()
+        .filter_map(|source_dir| fs::read_dir(long_path(&source_dir.path())).ok())
+        .flatten()
+        .flatten()
+        .map(|build_dir| build_dir.path().join(file_name))
+        .filter(|path| path != binary)
+        .find(|path| {
+            let same_size = fs::metadata(long_path(path))
+                .is_ok_and(|metadata| metadata.is_file() && metadata.len() == data.len() as u64);
+            same_size && fs::read(long_path(path)).is_ok_and(|contents| contents == data)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(bin_dir: &Path, relative_path: &str, contents: &[u8]) -> PathBuf {
+        let path = bin_dir.join(relative_path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        deduplicate(bin_dir, &path);
+        path
+    }
+
+    #[cfg(unix)]
+    fn inode(path: &Path) -> u64 {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(path).unwrap().ino()
+    }
+
+    #[test]
+    fn identical_binaries_are_linked() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let bin_dir = temp_dir.path();
+
+        let locked = store(bin_dir, "tool-1.0.0/src/locked/tool", b"binary");
+        let unlocked = store(bin_dir, "tool-1.0.0/src/unlocked/tool", b"binary");
+        let other = store(bin_dir, "tool-1.0.0/src/release/tool", b"other binary");
+
+        #[cfg(unix)]
+        {
+            assert_eq!(inode(&locked), inode(&unlocked));
+            assert_ne!(inode(&locked), inode(&other));
+        }
+        assert_eq!(fs::read(&unlocked).unwrap(), b"binary");
+        assert_eq!(fs::read_dir(unlocked.parent().unwrap()).unwrap().count(), 1);
+
+        // Removing one of the links leaves the other intact
+        fs::remove_dir_all(locked.parent().unwrap()).unwrap();
+        assert_eq!(fs::read(&unlocked).unwrap(), b"binary");
+    }
+
+    #[test]
+    fn only_identical_binaries_of_the_same_crate_are_linked() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let bin_dir = temp_dir.path();
+
+        let first = store(bin_dir, "tool-1.0.0/src/a/tool", b"binary");
+        let other_version = store(bin_dir, "tool-2.0.0/src/a/tool", b"binary");
+        let other_name = store(bin_dir, "tool-1.0.0/src/b/tool-cli", b"binary");
+
+        // Rebuilt in place with different contents, which mustn't be linked to as the old ones
+        fs::remove_file(&first).unwrap();
+        fs::write(&first, b"rebuilt").unwrap();
+        let second = store(bin_dir, "tool-1.0.0/src/c/tool", b"binary");
+        assert_eq!(fs::read(&first).unwrap(), b"rebuilt");
+        assert_eq!(fs::read(&second).unwrap(), b"binary");
+
+        #[cfg(unix)]
+        {
+            assert_ne!(inode(&first), inode(&second));
+            assert_ne!(inode(&other_version), inode(&second));
+            assert_ne!(inode(&other_name), inode(&second));
+        }
+        #[cfg(not(unix))]
+        let _ = (other_version, other_name);
+    }
+}
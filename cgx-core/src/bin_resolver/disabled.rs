@@ -0,0 +1,58 @@
+//! The [`BinaryResolver`] used without the `prebuilt-binaries` feature, which never finds one.
+
+use super::{BinaryResolver, CachingResolver, ResolvedBinary};
+use crate::{
+    Result,
+    builder::BuildOptions,
+    cache::Cache,
+    config::{Config, UsePrebuiltBinaries},
+    downloader::DownloadedCrate,
+    error,
+    http::HttpClient,
+    messages::PrebuiltBinaryMessage,
+};
+
+/// Create a [`BinaryResolver`] that never finds a pre-built binary, since there are no providers
+/// without the `prebuilt-binaries` feature.
+pub(crate) fn create_resolver(
+    config: Config,
+    cache: Cache,
+    reporter: crate::messages::MessageReporter,
+    _http_client: HttpClient,
+) -> impl BinaryResolver {
+    let prebuilt_binaries = config.prebuilt_binaries.clone();
+    let inner = DisabledBinaryResolver {
+        config,
+        reporter: reporter.clone(),
+    };
+    CachingResolver::new(inner, cache, reporter, prebuilt_binaries)
+}
+
+struct DisabledBinaryResolver {
+    config: Config,
+    reporter: crate::messages::MessageReporter,
+}
+
+impl BinaryResolver for DisabledBinaryResolver {
+    fn resolve(
+        &self,
+        krate: &DownloadedCrate,
+        _build_options: &BuildOptions,
+    ) -> Result<Option<ResolvedBinary>> {
+        if self.config.prebuilt_binaries.use_prebuilt_binaries == UsePrebuiltBinaries::Always {
+            return error::FeatureDisabledSnafu {
+                feature: "prebuilt-binaries",
+                what: "Getting a pre-built binary",
+            }
+            .fail();
+        }
+
+        self.reporter.report(|| {
+            PrebuiltBinaryMessage::no_binary_found(
+                &krate.resolved,
+                vec!["cgx-core was built without the prebuilt-binaries feature".to_string()],
+            )
+        });
+        Ok(None)
+    }
+}
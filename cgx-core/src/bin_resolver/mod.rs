@@ -1,19 +1,34 @@
+//! Pre-built binaries, as an alternative to building a crate from source.
+//!
+//! The providers that find and download them need the `prebuilt-binaries` feature.  Without it,
+//! no pre-built binary is ever found, so every crate is built from source, and
+//! `--prebuilt-binary always` fails with [`error::Error::FeatureDisabled`].
+
+#[cfg(not(feature = "prebuilt-binaries"))]
+mod disabled;
+#[cfg(feature = "prebuilt-binaries")]
+mod prebuilt;
+#[cfg(feature = "prebuilt-binaries")]
 mod providers;
 
+#[cfg(not(feature = "prebuilt-binaries"))]
+pub(crate) use disabled::create_resolver;
+#[cfg(feature = "prebuilt-binaries")]
+pub(crate) use prebuilt::create_resolver;
+
 use crate::{
     Result,
     builder::{BuildOptions, BuildTarget},
     cache::Cache,
-    config::{BinaryProvider, Config, UsePrebuiltBinaries},
+    config::{BinaryProvider, PrebuiltBinariesConfig},
     crate_resolver::ResolvedCrate,
     downloader::DownloadedCrate,
     error,
-    http::HttpClient,
     messages::PrebuiltBinaryMessage,
 };
-use providers::{BinstallProvider, GithubProvider, GitlabProvider, Provider, QuickinstallProvider};
 use serde::{Deserialize, Serialize};
-use snafu::ResultExt;
+use sha2::{Digest, Sha256};
+use strum::Display;
 
 /// A resolved binary means we found, downloaded, and validated a pre-built binary for a crate, so
 /// that we don't have to build it from source.
@@ -29,9 +44,122 @@ pub struct ResolvedBinary {
 
     /// Path to the downloaded binary ready for execution
     pub path: std::path::PathBuf,
+
+    /// SHA256 of the release asset the binary came from: the archive it was in, or the binary
+    /// itself if it was released bare.  Pinned digests are checked against this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact_sha256: Option<String>,
+}
+
+/// How a binary provider failed while looking for a pre-built binary.
+///
+/// This separates failures that say something about the binary from those that only say
+/// something about the provider, which decides whether the outcome may be cached and what the user
+/// can do about it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderFailureKind {
+    /// The provider doesn't have what was asked for.
+    #[strum(to_string = "not found")]
+    NotFound,
+    /// The provider couldn't be reached, or failed in a way that may go away on retry.
+    #[strum(to_string = "transient failure")]
+    Transient,
+    /// The provider refused the request for lack of credentials, or rate-limited anonymous access.
+    #[strum(to_string = "authentication required")]
+    AuthRequired,
+    /// The provider had a binary but it couldn't be used, e.g. its checksum didn't match.
+    #[strum(to_string = "invalid binary")]
+    Invalid,
+    /// The provider had a binary, but not from where
+    /// [`PrebuiltBinariesConfig::trust_prebuilt_only_from`] accepts binaries from.
+    #[strum(to_string = "untrusted origin")]
+    Untrusted,
+}
+
+impl ProviderFailureKind {
+    /// Classify an error returned by a binary provider.
+    pub fn of(error: &error::Error) -> Self {
+        match error {
+            error::Error::HttpStatus { status, .. } => Self::of_status(*status),
+            error::Error::BinaryDownloadHttpError { status, .. } => Self::of_status(status.as_u16()),
+            error::Error::HttpRequest { .. }
+            | error::Error::BinaryDownloadFailed { .. }
+            | error::Error::GithubApiError { .. }
+            | error::Error::QuickinstallApiError { .. } => Self::Transient,
+            // The binary is fine, just not for this system, so as far as it's concerned the
+            // provider doesn't have one
+            error::Error::IncompatibleGlibc { .. } => Self::NotFound,
+            error::Error::UntrustedPrebuiltBinary { .. } => Self::Untrusted,
+            _ => Self::Invalid,
+        }
+    }
+
+    fn of_status(status: u16) -> Self {
+        match status {
+            401 | 403 => Self::AuthRequired,
+            404 | 410 => Self::NotFound,
+            _ => Self::Transient,
+        }
+    }
 }
 
-pub trait BinaryResolver {
+/// A binary provider that failed with an error while looking for a pre-built binary.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ProviderFailure {
+    /// The provider that failed
+    pub provider: BinaryProvider,
+
+    /// The target triple the provider was asked for a binary for
+    pub platform: String,
+
+    /// What kind of failure this was
+    pub kind: ProviderFailureKind,
+
+    /// The error the provider failed with
+    pub message: String,
+}
+
+impl ProviderFailure {
+    #[cfg_attr(not(feature = "prebuilt-binaries"), allow(dead_code))]
+    fn new(provider: BinaryProvider, platform: &str, error: &error::Error) -> Self {
+        Self {
+            provider,
+            platform: platform.to_string(),
+            kind: ProviderFailureKind::of(error),
+            message: error.to_string(),
+        }
+    }
+
+    /// What the user can do about this failure, if anything.
+    pub fn hint(&self) -> Option<&'static str> {
+        match (self.kind, self.provider) {
+            (ProviderFailureKind::AuthRequired, BinaryProvider::GithubReleases) => {
+                Some("set GITHUB_TOKEN to authenticate to the GitHub API")
+            }
+            (ProviderFailureKind::Transient, _) => {
+                Some("check network connectivity and proxy settings, or try again later")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}: {}",
+            self.provider, self.platform, self.kind, self.message
+        )?;
+        if let Some(hint) = self.hint() {
+            write!(f, " ({hint})")?;
+        }
+        Ok(())
+    }
+}
+
+pub trait BinaryResolver: Send + Sync {
     /// Attempt to resolve a pre-built binary for the given crate.
     ///
     /// Returns:
@@ -54,29 +182,16 @@ pub trait BinaryResolver {
     ) -> Result<Option<ResolvedBinary>>;
 }
 
-/// Create the default [`BinaryResolver`] implementation, repecting the given config and using the
-/// provided cache.
-pub(crate) fn create_resolver(
-    config: Config,
-    cache: Cache,
-    reporter: crate::messages::MessageReporter,
-    http_client: HttpClient,
-) -> impl BinaryResolver {
-    let inner = DefaultBinaryResolver::new(config, reporter.clone(), http_client);
-    CachingResolver::new(inner, cache, reporter)
-}
-
-struct DefaultBinaryResolver {
-    config: Config,
-    reporter: crate::messages::MessageReporter,
-    http_client: HttpClient,
-}
-
 /// Check if the build options disqualify the use of pre-built binaries.
 ///
 /// Pre-built binaries can only be used for the default configuration.
-/// Any customization (features, target, profile, etc.) requires building from source.
-fn is_disqualified(build_options: &BuildOptions) -> Option<&'static str> {
+/// Any customization (features, target, profile, etc.) requires building from source, other than
+/// asking for one of the [`PrebuiltBinariesConfig::compatible_profiles`], or for the one target
+/// pre-built binaries are being looked for.
+pub(crate) fn is_disqualified(
+    build_options: &BuildOptions,
+    prebuilt_binaries: &PrebuiltBinariesConfig,
+) -> Option<&'static str> {
     if build_options.build_target != BuildTarget::DefaultBin {
         return Some("explicit --bin or --example specified");
     }
@@ -93,12 +208,16 @@ fn is_disqualified(build_options: &BuildOptions) -> Option<&'static str> {
         return Some("--no-default-features specified");
     }
 
-    if build_options.profile.is_some() {
-        return Some("custom profile specified");
+    if let Some(profile) = &build_options.profile {
+        if !prebuilt_binaries.compatible_profiles.contains(profile) {
+            return Some("custom profile specified");
+        }
     }
 
-    if build_options.target.is_some() {
-        return Some("custom target specified");
+    if let Some(target) = &build_options.target {
+        if prebuilt_binaries.target_preference() != [target.as_str()] {
+            return Some("custom target specified");
+        }
     }
 
     if build_options.toolchain.is_some() {
@@ -108,220 +227,76 @@ fn is_disqualified(build_options: &BuildOptions) -> Option<&'static str> {
     None
 }
 
-impl DefaultBinaryResolver {
-    fn new(config: Config, reporter: crate::messages::MessageReporter, http_client: HttpClient) -> Self {
-        Self {
-            config,
-            reporter,
-            http_client,
-        }
-    }
-
-    /// Relocate a resolved binary from the provider's cache to the `bin_dir` structure.
-    ///
-    /// This ensures all binaries (pre-built and source-built) live in the same directory
-    /// structure, making paths consistent and predictable.
-    fn relocate_to_bin_dir(
-        &self,
-        mut binary: ResolvedBinary,
-        krate: &ResolvedCrate,
-        platform: &str,
-    ) -> Result<ResolvedBinary> {
-        // Compute source hash based on the resolved crate source
-        let source_hash = Self::compute_source_hash(&krate.source);
-
-        // Build target directory: bin_dir/<crate>-<version>/<source-hash>/prebuilt-<provider>-<platform>/
-        let target_dir = self
-            .config
-            .bin_dir
-            .join(format!("{}-{}", krate.name, krate.version))
-            .join(source_hash)
-            .join(format!("prebuilt-{:?}-{}", binary.provider, platform));
-
-        std::fs::create_dir_all(&target_dir).with_context(|_| error::IoSnafu {
-            path: target_dir.clone(),
-        })?;
-
-        let binary_name = binary.path.file_name().ok_or_else(|| error::Error::Io {
-            path: binary.path.clone(),
-            source: std::io::Error::new(std::io::ErrorKind::InvalidInput, "binary path has no filename"),
-        })?;
-
-        let target_path = target_dir.join(binary_name);
-
-        // Copy (don't move) so the provider's cache remains intact
-        std::fs::copy(&binary.path, &target_path).with_context(|_| error::CopyBinarySnafu {
-            src: binary.path.clone(),
-            dst: target_path.clone(),
-        })?;
-
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = std::fs::metadata(&target_path)
-                .with_context(|_| error::IoSnafu {
-                    path: target_path.clone(),
-                })?
-                .permissions();
-            perms.set_mode(0o755);
-            std::fs::set_permissions(&target_path, perms).with_context(|_| error::IoSnafu {
-                path: target_path.clone(),
-            })?;
-        }
-
-        binary.path = target_path;
-        Ok(binary)
-    }
-
-    /// Compute a hash of the source for use in the `bin_dir` structure.
-    fn compute_source_hash(source: &crate::crate_resolver::ResolvedSource) -> String {
-        use crate::{crate_resolver::ResolvedSource, cratespec::RegistrySource};
-        use sha2::{Digest, Sha256};
+/// Compute a hash of the source for use in the `bin_dir` structure, which pre-built binaries are
+/// kept under.
+pub(crate) fn prebuilt_source_hash(source: &crate::crate_resolver::ResolvedSource) -> String {
+    use crate::{crate_resolver::ResolvedSource, cratespec::RegistrySource};
 
-        let mut hasher = Sha256::new();
+    let mut hasher = Sha256::new();
 
-        match source {
-            ResolvedSource::CratesIo => {
-                hasher.update(b"crates-io");
-            }
-            ResolvedSource::Registry { source } => {
-                hasher.update(b"registry:");
-                match source {
-                    RegistrySource::Named(name) => {
-                        hasher.update(b"named:");
-                        hasher.update(name.as_bytes());
-                    }
-                    RegistrySource::IndexUrl(url) => {
-                        hasher.update(b"index:");
-                        hasher.update(url.as_str().as_bytes());
-                    }
-                }
-            }
-            ResolvedSource::Git { repo, commit } => {
-                hasher.update(b"git:");
-                hasher.update(repo.as_bytes());
-                hasher.update(b":");
-                hasher.update(commit.as_bytes());
-            }
-            ResolvedSource::Forge { forge, commit } => {
-                hasher.update(b"forge:");
-                hasher.update(format!("{:?}", forge).as_bytes());
-                hasher.update(b":");
-                hasher.update(commit.as_bytes());
-            }
-            ResolvedSource::LocalDir { path } => {
-                hasher.update(b"local:");
-                hasher.update(path.to_string_lossy().as_bytes());
-            }
+    match source {
+        ResolvedSource::CratesIo => {
+            hasher.update(b"crates-io");
         }
-
-        crate::helpers::format_hex_lower(hasher.finalize())[..16].to_string()
-    }
-}
-
-impl BinaryResolver for DefaultBinaryResolver {
-    fn resolve(
-        &self,
-        krate: &DownloadedCrate,
-        _build_options: &BuildOptions,
-    ) -> Result<Option<ResolvedBinary>> {
-        let resolved = &krate.resolved;
-
-        tracing::debug!(
-            "BinaryResolver::resolve called for {}@{}",
-            resolved.name,
-            resolved.version
-        );
-
-        if self.config.prebuilt_binaries.binary_providers.is_empty() {
-            return error::NoProvidersConfiguredSnafu.fail();
-        }
-
-        // Always use the build target platform for pre-built binaries
-        // If the user overrides this by specifying a custom target, execution is not supposed to
-        // make it to this point.
-        let platform: &'static str = build_context::TARGET;
-
-        let reporter = &self.reporter;
-        let cache_dir = &self.config.cache_dir;
-        let verify = self.config.prebuilt_binaries.verify_checksums;
-
-        for provider_type in &self.config.prebuilt_binaries.binary_providers {
-            reporter.report(|| PrebuiltBinaryMessage::checking_provider(resolved, *provider_type));
-
-            let result = match provider_type {
-                BinaryProvider::Binstall => BinstallProvider::new(
-                    reporter.clone(),
-                    cache_dir.clone(),
-                    verify,
-                    self.http_client.clone(),
-                )
-                .try_resolve(krate, platform),
-                BinaryProvider::GithubReleases => GithubProvider::new(
-                    reporter.clone(),
-                    cache_dir.clone(),
-                    verify,
-                    self.http_client.clone(),
-                )
-                .try_resolve(krate, platform),
-                BinaryProvider::GitlabReleases => GitlabProvider::new(
-                    reporter.clone(),
-                    cache_dir.clone(),
-                    verify,
-                    self.http_client.clone(),
-                )
-                .try_resolve(krate, platform),
-                BinaryProvider::Quickinstall => {
-                    QuickinstallProvider::new(reporter.clone(), cache_dir.clone(), self.http_client.clone())
-                        .try_resolve(krate, platform)
+        ResolvedSource::Registry { source } => {
+            hasher.update(b"registry:");
+            match source {
+                RegistrySource::Named(name) => {
+                    hasher.update(b"named:");
+                    hasher.update(name.as_bytes());
                 }
-            };
-
-            match result {
-                Ok(Some(binary)) => {
-                    let relocated_binary = self.relocate_to_bin_dir(binary, resolved, platform)?;
-                    reporter.report(|| PrebuiltBinaryMessage::resolved(&relocated_binary));
-                    return Ok(Some(relocated_binary));
-                }
-                Ok(None) => continue,
-                Err(e) => {
-                    tracing::debug!("Provider {:?} error: {:?}", provider_type, e);
-                    continue;
+                RegistrySource::IndexUrl(url) => {
+                    hasher.update(b"index:");
+                    hasher.update(url.as_str().as_bytes());
                 }
             }
         }
-
-        if self.config.prebuilt_binaries.use_prebuilt_binaries == UsePrebuiltBinaries::Always {
-            return error::PrebuiltBinaryRequiredSnafu {
-                name: resolved.name.clone(),
-                version: resolved.version.to_string(),
-            }
-            .fail();
+        ResolvedSource::Git { repo, commit } => {
+            hasher.update(b"git:");
+            hasher.update(repo.as_bytes());
+            hasher.update(b":");
+            hasher.update(commit.as_bytes());
+        }
+        ResolvedSource::Forge { forge, commit } => {
+            hasher.update(b"forge:");
+            hasher.update(format!("{:?}", forge).as_bytes());
+            hasher.update(b":");
+            hasher.update(commit.as_bytes());
+        }
+        ResolvedSource::Custom { url, revision } => {
+            hasher.update(b"custom:");
+            hasher.update(url.as_str().as_bytes());
+            hasher.update(b":");
+            hasher.update(revision.as_bytes());
+        }
+        ResolvedSource::LocalDir { path } => {
+            hasher.update(b"local:");
+            hasher.update(path.to_string_lossy().as_bytes());
         }
-
-        self.reporter.report(|| {
-            PrebuiltBinaryMessage::no_binary_found(
-                resolved,
-                vec!["no binary found from any configured provider".to_string()],
-            )
-        });
-
-        Ok(None)
     }
+
+    crate::helpers::format_hex_lower(hasher.finalize())[..16].to_string()
 }
 
 struct CachingResolver<R: BinaryResolver> {
     inner: R,
     cache: Cache,
     reporter: crate::messages::MessageReporter,
+    prebuilt_binaries: PrebuiltBinariesConfig,
 }
 
 impl<R: BinaryResolver> CachingResolver<R> {
-    fn new(inner: R, cache: Cache, reporter: crate::messages::MessageReporter) -> Self {
+    fn new(
+        inner: R,
+        cache: Cache,
+        reporter: crate::messages::MessageReporter,
+        prebuilt_binaries: PrebuiltBinariesConfig,
+    ) -> Self {
         Self {
             inner,
             cache,
             reporter,
+            prebuilt_binaries,
         }
     }
 }
@@ -333,15 +308,38 @@ impl<R: BinaryResolver> BinaryResolver for CachingResolver<R> {
         build_options: &BuildOptions,
     ) -> Result<Option<ResolvedBinary>> {
         // Check build options disqualification BEFORE touching cache
-        if let Some(reason) = is_disqualified(build_options) {
+        if let Some(reason) = is_disqualified(build_options, &self.prebuilt_binaries) {
             self.reporter
                 .report(|| PrebuiltBinaryMessage::disqualified_due_to_customization(reason));
             return Ok(None);
         }
 
         // Delegate to cache (which handles Never mode and caching), keyed on the resolved crate
-        self.cache
-            .get_or_resolve_binary(&krate.resolved, || self.inner.resolve(krate, build_options))
+        let result = self
+            .cache
+            .get_or_resolve_binary(&krate.resolved, || self.inner.resolve(krate, build_options));
+
+        // Providers failing doesn't stop a build from source; it only keeps the outcome out of the
+        // cache so that the providers are asked again next time.
+        match result {
+            Err(error::Error::PrebuiltBinaryUnavailable { failures, .. }) => {
+                for failure in &failures {
+                    if failure.kind == ProviderFailureKind::AuthRequired {
+                        tracing::warn!("Unable to check for a pre-built binary: {failure}");
+                    } else {
+                        tracing::debug!("Unable to check for a pre-built binary: {failure}");
+                    }
+                }
+                self.reporter.report(|| {
+                    PrebuiltBinaryMessage::no_binary_found(
+                        &krate.resolved,
+                        failures.iter().map(ToString::to_string).collect(),
+                    )
+                });
+                Ok(None)
+            }
+            result => result,
+        }
     }
 }
 
@@ -350,11 +348,90 @@ mod tests {
     use super::*;
     use crate::builder::{BuildOptions, BuildTarget};
 
+    /// Test that provider errors are classified by what they say about the binary
+    #[test]
+    fn test_provider_failure_kind_of_http_status() {
+        let status = |status| error::Error::HttpStatus {
+            url: "https://example.com".to_string(),
+            status,
+        };
+
+        assert_eq!(
+            ProviderFailureKind::of(&status(401)),
+            ProviderFailureKind::AuthRequired
+        );
+        assert_eq!(
+            ProviderFailureKind::of(&status(403)),
+            ProviderFailureKind::AuthRequired
+        );
+        assert_eq!(
+            ProviderFailureKind::of(&status(404)),
+            ProviderFailureKind::NotFound
+        );
+        assert_eq!(
+            ProviderFailureKind::of(&status(429)),
+            ProviderFailureKind::Transient
+        );
+        assert_eq!(
+            ProviderFailureKind::of(&status(503)),
+            ProviderFailureKind::Transient
+        );
+    }
+
+    /// Test that errors unrelated to reaching the provider count as an unusable binary
+    #[test]
+    fn test_provider_failure_kind_of_other_errors() {
+        let checksum = error::Error::ChecksumMismatch {
+            expected: "a".to_string(),
+            actual: "b".to_string(),
+        };
+        assert_eq!(ProviderFailureKind::of(&checksum), ProviderFailureKind::Invalid);
+
+        let glibc = error::Error::IncompatibleGlibc {
+            path: "/tmp/tool".into(),
+            required: "2.39".to_string(),
+            available: "2.31".to_string(),
+        };
+        assert_eq!(ProviderFailureKind::of(&glibc), ProviderFailureKind::NotFound);
+
+        let untrusted = error::Error::UntrustedPrebuiltBinary {
+            origin: "quickinstall".to_string(),
+            reason: "it's not the crate's author".to_string(),
+        };
+        assert_eq!(
+            ProviderFailureKind::of(&untrusted),
+            ProviderFailureKind::Untrusted
+        );
+    }
+
+    /// Test that a GitHub authentication failure tells the user how to fix it
+    #[test]
+    fn test_provider_failure_display_includes_hint() {
+        let failure = ProviderFailure::new(
+            BinaryProvider::GithubReleases,
+            "x86_64-unknown-linux-gnu",
+            &error::Error::HttpStatus {
+                url: "https://api.github.com/repos/o/r/releases/tags/v1.0.0".to_string(),
+                status: 403,
+            },
+        );
+
+        assert_eq!(
+            failure.to_string(),
+            "github-releases (x86_64-unknown-linux-gnu): authentication required: HTTP 403 from \
+             https://api.github.com/repos/o/r/releases/tags/v1.0.0 (set GITHUB_TOKEN to authenticate to \
+             the GitHub API)"
+        );
+    }
+
     /// Test that default build options are not disqualified
     #[test]
     fn test_disqualification_default_options_ok() {
         let options = BuildOptions::default();
-        assert_eq!(is_disqualified(&options), None);
+        assert_eq!(
+            is_disqualified(&options, &PrebuiltBinariesConfig::default()),
+            None
+        );
     }
 
     /// Test that explicit --bin flag disqualifies pre-built binaries
@@ -363,7 +440,7 @@ mod tests {
         let mut options = BuildOptions::default();
         options.build_target = BuildTarget::Bin("specific-bin".to_string());
         assert_eq!(
-            is_disqualified(&options),
+            is_disqualified(&options, &PrebuiltBinariesConfig::default()),
             Some("explicit --bin or --example specified")
         );
     }
@@ -374,7 +451,7 @@ mod tests {
         let mut options = BuildOptions::default();
         options.build_target = BuildTarget::Example("my-example".to_string());
         assert_eq!(
-            is_disqualified(&options),
+            is_disqualified(&options, &PrebuiltBinariesConfig::default()),
             Some("explicit --bin or --example specified")
         );
     }
@@ -384,7 +461,10 @@ mod tests {
     fn test_disqualification_custom_features() {
         let mut options = BuildOptions::default();
         options.features = vec!["serde".to_string(), "json".to_string()];
-        assert_eq!(is_disqualified(&options), Some("custom features specified"));
+        assert_eq!(
+            is_disqualified(&options, &PrebuiltBinariesConfig::default()),
+            Some("custom features specified")
+        );
     }
 
     /// Test that --all-features disqualifies pre-built binaries
@@ -392,7 +472,10 @@ mod tests {
     fn test_disqualification_all_features() {
         let mut options = BuildOptions::default();
         options.all_features = true;
-        assert_eq!(is_disqualified(&options), Some("--all-features specified"));
+        assert_eq!(
+            is_disqualified(&options, &PrebuiltBinariesConfig::default()),
+            Some("--all-features specified")
+        );
     }
 
     /// Test that --no-default-features disqualifies pre-built binaries
@@ -400,7 +483,10 @@ mod tests {
     fn test_disqualification_no_default_features() {
         let mut options = BuildOptions::default();
         options.no_default_features = true;
-        assert_eq!(is_disqualified(&options), Some("--no-default-features specified"));
+        assert_eq!(
+            is_disqualified(&options, &PrebuiltBinariesConfig::default()),
+            Some("--no-default-features specified")
+        );
     }
 
     /// Test that custom profile disqualifies pre-built binaries
@@ -408,7 +494,32 @@ mod tests {
     fn test_disqualification_custom_profile() {
         let mut options = BuildOptions::default();
         options.profile = Some("release-with-debug".to_string());
-        assert_eq!(is_disqualified(&options), Some("custom profile specified"));
+        assert_eq!(
+            is_disqualified(&options, &PrebuiltBinariesConfig::default()),
+            Some("custom profile specified")
+        );
+    }
+
+    /// Test that asking for a profile pre-built binaries are compatible with doesn't disqualify them
+    #[test]
+    fn test_disqualification_compatible_profile() {
+        let options = BuildOptions {
+            profile: Some("release".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            is_disqualified(&options, &PrebuiltBinariesConfig::default()),
+            None
+        );
+
+        let prebuilt_binaries = PrebuiltBinariesConfig {
+            compatible_profiles: vec!["dist".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            is_disqualified(&options, &prebuilt_binaries),
+            Some("custom profile specified")
+        );
     }
 
     /// Test that custom target disqualifies pre-built binaries
@@ -416,7 +527,25 @@ mod tests {
     fn test_disqualification_custom_target() {
         let mut options = BuildOptions::default();
         options.target = Some("x86_64-unknown-linux-musl".to_string());
-        assert_eq!(is_disqualified(&options), Some("custom target specified"));
+        assert_eq!(
+            is_disqualified(&options, &PrebuiltBinariesConfig::default()),
+            Some("custom target specified")
+        );
+    }
+
+    /// Test that a target doesn't disqualify pre-built binaries when it's the only one they're looked
+    /// for, as for the platform of a container image
+    #[test]
+    fn test_target_matching_prebuilt_targets_is_not_disqualified() {
+        let options = BuildOptions {
+            target: Some("aarch64-unknown-linux-gnu".to_string()),
+            ..Default::default()
+        };
+        let prebuilt_binaries = PrebuiltBinariesConfig {
+            targets: vec!["aarch64-unknown-linux-gnu".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(is_disqualified(&options, &prebuilt_binaries), None);
     }
 
     /// Test that custom toolchain disqualifies pre-built binaries
@@ -424,6 +553,9 @@ mod tests {
     fn test_disqualification_custom_toolchain() {
         let mut options = BuildOptions::default();
         options.toolchain = Some("nightly".to_string());
-        assert_eq!(is_disqualified(&options), Some("custom toolchain specified"));
+        assert_eq!(
+            is_disqualified(&options, &PrebuiltBinariesConfig::default()),
+            Some("custom toolchain specified")
+        );
     }
 }
@@ -0,0 +1,466 @@
+//! The [`BinaryResolver`] that asks the configured providers for a pre-built binary.
+
+use super::{
+    BinaryResolver, CachingResolver, ProviderFailure, ProviderFailureKind, ResolvedBinary,
+    prebuilt_source_hash,
+    providers::{
+        ArchiveCache, BinaryPicker, BinstallProvider, GithubProvider, GitlabProvider, OriginPolicy, Provider,
+        QuickinstallProvider, ReleaseListingCache,
+    },
+};
+use crate::{
+    Result, binary_format,
+    builder::BuildOptions,
+    cache::Cache,
+    cancel,
+    config::{BinaryProvider, Config, UsePrebuiltBinaries},
+    crate_resolver::ResolvedCrate,
+    downloader::DownloadedCrate,
+    error,
+    http::HttpClient,
+    messages::PrebuiltBinaryMessage,
+};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Create the default [`BinaryResolver`] implementation, repecting the given config and using the
+/// provided cache.
+pub(crate) fn create_resolver(
+    config: Config,
+    cache: Cache,
+    reporter: crate::messages::MessageReporter,
+    http_client: HttpClient,
+) -> impl BinaryResolver {
+    let prebuilt_binaries = config.prebuilt_binaries.clone();
+    let inner = DefaultBinaryResolver::new(config, cache.clone(), reporter.clone(), http_client);
+    CachingResolver::new(inner, cache, reporter, prebuilt_binaries)
+}
+
+struct DefaultBinaryResolver {
+    config: Config,
+    cache: Cache,
+    reporter: crate::messages::MessageReporter,
+    http_client: HttpClient,
+}
+
+impl DefaultBinaryResolver {
+    fn new(
+        config: Config,
+        cache: Cache,
+        reporter: crate::messages::MessageReporter,
+        http_client: HttpClient,
+    ) -> Self {
+        Self {
+            config,
+            cache,
+            reporter,
+            http_client,
+        }
+    }
+
+    /// Relocate a resolved binary from the provider's cache to the `bin_dir` structure.
+    ///
+    /// This ensures all binaries (pre-built and source-built) live in the same directory
+    /// structure, making paths consistent and predictable.  The helpers `picker` installed
+    /// alongside the binary are copied along with it.
+    fn relocate_to_bin_dir(
+        &self,
+        mut binary: ResolvedBinary,
+        krate: &ResolvedCrate,
+        platform: &str,
+        picker: &BinaryPicker,
+    ) -> Result<ResolvedBinary> {
+        // Compute source hash based on the resolved crate source
+        let source_hash = prebuilt_source_hash(&krate.source);
+
+        // Build target directory: bin_dir/<crate>-<version>/<source-hash>/prebuilt-<provider>-<platform>/
+        let target_dir = self
+            .config
+            .bin_dir
+            .join(format!("{}-{}", krate.name, krate.version))
+            .join(source_hash)
+            .join(format!("prebuilt-{:?}-{}", binary.provider, platform));
+
+        let binary_name = binary.path.file_name().ok_or_else(|| error::Error::Io {
+            path: binary.path.clone(),
+            source: std::io::Error::new(std::io::ErrorKind::InvalidInput, "binary path has no filename"),
+        })?;
+
+        let target_path = target_dir.join(binary_name);
+
+        // Copy (don't move) so the provider's cache remains intact
+        crate::helpers::install_executable(&binary.path, &target_path)?;
+        let mut installed = vec![target_path.clone()];
+        let provider_dir = binary.path.parent().unwrap_or(Path::new(""));
+        for helper in picker.installed_helpers(provider_dir) {
+            let helper_path = target_dir.join(helper.file_name().unwrap_or_default());
+            crate::helpers::install_executable(&helper, &helper_path)?;
+            installed.push(helper_path);
+        }
+
+        if self.config.prebuilt_binaries.remove_quarantine {
+            for path in &installed {
+                crate::helpers::remove_quarantine(path);
+            }
+        }
+
+        binary.path = target_path;
+        Ok(binary)
+    }
+
+    /// Try each configured provider in turn for a binary built for `platform`.
+    ///
+    /// Providers that fail with an error, or only have a binary from somewhere
+    /// [`PrebuiltBinariesConfig::trust_prebuilt_only_from`] doesn't accept, are recorded in
+    /// `failures` and skipped.  So are providers that were recently found not to have the binary,
+    /// without being asked again; a provider that doesn't have it now is remembered for next time.
+    fn resolve_for_platform(
+        &self,
+        krate: &DownloadedCrate,
+        platform: &str,
+        picker: &BinaryPicker,
+        http_client: &HttpClient,
+        failures: &mut Vec<ProviderFailure>,
+    ) -> Result<Option<ResolvedBinary>> {
+        let resolved = &krate.resolved;
+        let reporter = &self.reporter;
+        let cache_dir = self.config.binary_cache_root();
+        let verify = self.config.prebuilt_binaries.verify_checksums;
+        let endpoints = &self.config.endpoints;
+        let archives = ArchiveCache::new(&self.config);
+        let releases = ReleaseListingCache::new(&self.config);
+        let origin = OriginPolicy::new(self.config.prebuilt_binaries.trust_prebuilt_only_from, krate)?;
+
+        // A tool with pinned digests only accepts binaries for the targets it pins a digest for
+        let expected_sha256 = match self.config.tool_prebuilt_sha256(&resolved.name) {
+            Some(digests) => {
+                let Some(digest) = digests.get(platform) else {
+                    tracing::debug!(
+                        "No prebuilt binary digest is pinned for {} on {}, skipping this target",
+                        resolved.name,
+                        platform
+                    );
+                    return Ok(None);
+                };
+                Some(digest.as_str())
+            }
+            None => None,
+        };
+
+        for provider_type in &self.config.prebuilt_binaries.binary_providers {
+            cancel::check()?;
+
+            if self.cache.is_binary_missing(resolved, platform, *provider_type) {
+                reporter.report(|| {
+                    PrebuiltBinaryMessage::provider_has_no_binary(
+                        *provider_type,
+                        format!("none found for {} when last checked", platform),
+                    )
+                });
+                continue;
+            }
+
+            reporter.report(|| PrebuiltBinaryMessage::checking_provider(resolved, *provider_type));
+
+            let span = tracing::info_span!(
+                "provider",
+                krate = %resolved.name,
+                version = %resolved.version,
+                provider = <&'static str>::from(*provider_type),
+                target = platform,
+                found = tracing::field::Empty,
+            );
+            let _span = span.enter();
+
+            let result = origin
+                .check_provider(*provider_type)
+                .and_then(|()| match provider_type {
+                    BinaryProvider::Binstall => BinstallProvider::new(
+                        reporter.clone(),
+                        cache_dir.to_path_buf(),
+                        archives.clone(),
+                        verify,
+                        http_client.clone(),
+                        origin.clone(),
+                    )
+                    .try_resolve(krate, platform, picker),
+                    BinaryProvider::GithubReleases => GithubProvider::new(
+                        reporter.clone(),
+                        cache_dir.to_path_buf(),
+                        archives.clone(),
+                        releases.clone(),
+                        verify,
+                        http_client.clone(),
+                        endpoints.github_api.clone(),
+                    )
+                    .try_resolve(krate, platform, picker),
+                    BinaryProvider::GitlabReleases => GitlabProvider::new(
+                        reporter.clone(),
+                        cache_dir.to_path_buf(),
+                        archives.clone(),
+                        verify,
+                        http_client.clone(),
+                        endpoints.gitlab.clone(),
+                        origin.clone(),
+                    )
+                    .try_resolve(krate, platform, picker),
+                    BinaryProvider::Quickinstall => QuickinstallProvider::new(
+                        reporter.clone(),
+                        cache_dir.to_path_buf(),
+                        archives.clone(),
+                        http_client.clone(),
+                        endpoints.quickinstall.clone(),
+                    )
+                    .try_resolve(krate, platform, picker),
+                });
+
+            let result = result
+                .and_then(|binary| match (binary, expected_sha256) {
+                    (Some(binary), Some(expected)) => self
+                        .verify_pinned_digest(&binary, resolved, platform, expected)
+                        .map(|()| Some(binary)),
+                    (binary, _) => Ok(binary),
+                })
+                .and_then(|binary| match binary {
+                    // A binary for the wrong OS or CPU is as unusable as a corrupt one
+                    Some(binary) => binary_format::check(&binary.path, platform).map(|()| Some(binary)),
+                    None => Ok(None),
+                })
+                .and_then(|binary| match binary {
+                    // The glibc of the host only matters if the binary is going to run on it, and
+                    // not in a container image being built for another platform
+                    Some(binary) if self.config.container_target.is_none() => {
+                        binary_format::check_glibc(&binary.path, platform).map(|()| Some(binary))
+                    }
+                    binary => Ok(binary),
+                });
+            span.record("found", matches!(result, Ok(Some(_))));
+
+            match result {
+                Ok(Some(binary)) => {
+                    let relocated_binary = self.relocate_to_bin_dir(binary, resolved, platform, picker)?;
+                    reporter.report(|| PrebuiltBinaryMessage::resolved(&relocated_binary));
+                    return Ok(Some(relocated_binary));
+                }
+                Ok(None) => {
+                    let _ = self.cache.put_missing_binary(resolved, platform, *provider_type);
+                    continue;
+                }
+                // Cancellation isn't something the provider did wrong, so it isn't a failure to
+                // record and move on from
+                Err(e) if cancel::current().is_cancelled() => return Err(e),
+                Err(e) => {
+                    tracing::debug!("Provider {:?} error for {}: {:?}", provider_type, platform, e);
+                    let failure = ProviderFailure::new(*provider_type, platform, &e);
+                    reporter.report(|| PrebuiltBinaryMessage::provider_failed(&failure));
+                    if failure.kind == ProviderFailureKind::NotFound {
+                        let _ = self.cache.put_missing_binary(resolved, platform, *provider_type);
+                    }
+                    failures.push(failure);
+                    continue;
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Report what getting `binary` came to: what was downloaded, how long it took, and how long
+    /// building the crate from source would have taken instead, going by its last build here.
+    fn report_summary(&self, binary: &ResolvedBinary, bytes_downloaded: u64, elapsed: Duration) {
+        let last_build = self
+            .cache
+            .last_build_stats(&binary.krate.name)
+            .map(|(_, stats)| stats);
+
+        let saved = last_build
+            .as_ref()
+            .map(|stats| format!(", saving ~{} of building from source", stats.build_time()))
+            .unwrap_or_default();
+        tracing::info!(
+            "Got {}@{} from {}: downloaded {} bytes in {:.1}s{}",
+            binary.krate.name,
+            binary.krate.version,
+            binary.provider,
+            bytes_downloaded,
+            elapsed.as_secs_f64(),
+            saved
+        );
+        let build_time_saved = last_build.map(|stats| stats.duration);
+
+        self.reporter.report(|| {
+            PrebuiltBinaryMessage::summary(
+                &binary.krate,
+                binary.provider,
+                bytes_downloaded,
+                elapsed,
+                build_time_saved,
+            )
+        });
+    }
+
+    /// Check that `binary` came from the release asset whose digest is pinned for `platform` in the
+    /// tool's config.
+    ///
+    /// The digest pinned is the one `sha256sum` gives for the file on the release page, so it's the
+    /// downloaded asset that's checked, not the binary extracted from it.
+    fn verify_pinned_digest(
+        &self,
+        binary: &ResolvedBinary,
+        krate: &ResolvedCrate,
+        platform: &str,
+        expected: &str,
+    ) -> Result<()> {
+        self.reporter
+            .report(|| PrebuiltBinaryMessage::verifying_checksum(expected));
+
+        let actual = binary.artifact_sha256.as_deref().unwrap_or_default();
+
+        if !actual.eq_ignore_ascii_case(expected) {
+            return error::PinnedDigestMismatchSnafu {
+                name: &krate.name,
+                platform,
+                expected,
+                actual: if actual.is_empty() { "unknown" } else { actual },
+            }
+            .fail();
+        }
+
+        self.reporter.report(PrebuiltBinaryMessage::checksum_verified);
+
+        Ok(())
+    }
+}
+
+impl BinaryResolver for DefaultBinaryResolver {
+    fn resolve(
+        &self,
+        krate: &DownloadedCrate,
+        _build_options: &BuildOptions,
+    ) -> Result<Option<ResolvedBinary>> {
+        let resolved = &krate.resolved;
+
+        tracing::debug!(
+            "BinaryResolver::resolve called for {}@{}",
+            resolved.name,
+            resolved.version
+        );
+
+        if self.config.prebuilt_binaries.binary_providers.is_empty() {
+            return error::NoProvidersConfiguredSnafu.fail();
+        }
+
+        // Pre-built binaries are looked up for the configured targets (by default the ones the host
+        // can run, native first).  If the user overrides this by specifying a custom build target,
+        // execution is not supposed to make it to this point.
+        let platforms = self.config.prebuilt_binaries.target_preference();
+        let picker = BinaryPicker::new(krate, &self.config)?;
+
+        // Downloads are counted for this crate alone, even with other crates resolved at once
+        let http_client = self.http_client.with_own_download_count();
+        let started = Instant::now();
+
+        let mut failures = Vec::new();
+        for platform in &platforms {
+            if let Some(binary) = self.resolve_for_platform(
+                krate,
+                platform,
+                &picker.for_target(platform),
+                &http_client,
+                &mut failures,
+            )? {
+                // Targets the user configured are theirs to order as they like, but falling back
+                // from the native architecture that was detected is worth pointing out, since the
+                // binary will run slower than it could.  Falling back to the other C library isn't.
+                if self.config.prebuilt_binaries.targets.is_empty()
+                    && platform.split('-').next() != platforms[0].split('-').next()
+                {
+                    self.reporter
+                        .report(|| PrebuiltBinaryMessage::emulated_binary(resolved, platform, &platforms[0]));
+                }
+                self.report_summary(&binary, http_client.bytes_downloaded(), started.elapsed());
+                return Ok(Some(binary));
+            }
+        }
+
+        if self.config.prebuilt_binaries.use_prebuilt_binaries == UsePrebuiltBinaries::Always {
+            return error::PrebuiltBinaryRequiredSnafu {
+                name: resolved.name.clone(),
+                version: resolved.version.to_string(),
+                failures,
+            }
+            .fail();
+        }
+
+        // A provider that failed for some reason other than not having the binary might well
+        // have one, so this isn't a definitive "no binary" that is safe to cache.
+        if failures
+            .iter()
+            .any(|failure| failure.kind != ProviderFailureKind::NotFound)
+        {
+            return error::PrebuiltBinaryUnavailableSnafu {
+                name: resolved.name.clone(),
+                version: resolved.version.to_string(),
+                failures,
+            }
+            .fail();
+        }
+
+        self.reporter.report(|| {
+            PrebuiltBinaryMessage::no_binary_found(
+                resolved,
+                vec!["no binary found from any configured provider".to_string()],
+            )
+        });
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use sha2::{Digest, Sha256};
+
+    /// Test that a pinned digest is checked against the downloaded release asset, not the binary
+    /// extracted from it
+    #[test]
+    fn test_pinned_digest_is_of_the_release_asset() {
+        let (temp_dir, config) = crate::config::create_test_env();
+        let reporter = crate::messages::MessageReporter::null();
+        let resolver = DefaultBinaryResolver::new(
+            config.clone(),
+            Cache::new(config.clone(), reporter.clone()),
+            reporter,
+            HttpClient::new(&config.http).unwrap(),
+        );
+
+        let binary_path = temp_dir.path().join("rg");
+        std::fs::write(&binary_path, b"the extracted binary").unwrap();
+        let asset_sha256 = crate::helpers::format_hex_lower(Sha256::digest(b"the release archive"));
+        let binary_sha256 = crate::helpers::format_hex_lower(Sha256::digest(b"the extracted binary"));
+        let krate = ResolvedCrate {
+            name: "ripgrep".to_string(),
+            version: semver::Version::new(14, 1, 1),
+            source: crate::crate_resolver::ResolvedSource::CratesIo,
+        };
+        let binary = ResolvedBinary {
+            krate: krate.clone(),
+            provider: BinaryProvider::GithubReleases,
+            path: binary_path,
+            artifact_sha256: Some(asset_sha256.clone()),
+        };
+        let platform = "x86_64-unknown-linux-gnu";
+
+        resolver
+            .verify_pinned_digest(&binary, &krate, platform, &asset_sha256.to_ascii_uppercase())
+            .unwrap();
+        assert_matches!(
+            resolver.verify_pinned_digest(&binary, &krate, platform, &binary_sha256),
+            Err(error::Error::PinnedDigestMismatch { actual, .. }) if actual == asset_sha256
+        );
+    }
+}
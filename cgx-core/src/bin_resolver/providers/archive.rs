@@ -1,10 +1,21 @@
-use crate::{Result, error};
+use crate::{
+    Result, binary_format, config::Config, downloader::DownloadedCrate, error, helpers::format_hex_lower,
+    http::Download,
+};
 use bzip2::read::BzDecoder;
 use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
 use snafu::ResultExt;
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::*;
 use xz2::read::XzDecoder;
 
+/// How long an extracted archive is kept after it was last used.
+const ARCHIVE_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(in crate::bin_resolver) enum ArchiveFormat {
     Tar,
@@ -17,57 +28,358 @@ pub(in crate::bin_resolver) enum ArchiveFormat {
 }
 
 impl ArchiveFormat {
-    /// Canonical filename for writing downloads to disk.
-    pub(in crate::bin_resolver) fn canonical_filename(&self) -> &'static str {
-        match self {
-            Self::Tar => "archive.tar",
-            Self::TarGz => "archive.tar.gz",
-            Self::TarXz => "archive.tar.xz",
-            Self::TarZst => "archive.tar.zst",
-            Self::TarBz2 => "archive.tar.bz2",
-            Self::Zip => "archive.zip",
-            Self::NakedBinary => {
-                if cfg!(windows) {
-                    "archive.exe"
-                } else {
-                    "archive"
+    /// All (format, suffix) pairs used for candidate filename generation for `platform`.
+    ///
+    /// A naked binary for Windows is a bare `.exe`, and one for WebAssembly a bare `.wasm`; on
+    /// every other platform it has no suffix.
+    pub(in crate::bin_resolver) fn all_formats(platform: &str) -> [(ArchiveFormat, &'static str); 8] {
+        let naked_suffix = if platform.contains("windows") {
+            ".exe"
+        } else if platform.starts_with("wasm") {
+            ".wasm"
+        } else {
+            ""
+        };
+        [
+            (Self::Tar, ".tar"),
+            (Self::TarGz, ".tar.gz"),
+            (Self::TarGz, ".tgz"),
+            (Self::TarXz, ".tar.xz"),
+            (Self::TarZst, ".tar.zst"),
+            (Self::TarBz2, ".tar.bz2"),
+            (Self::Zip, ".zip"),
+            (Self::NakedBinary, naked_suffix),
+        ]
+    }
+}
+
+/// Picks the executables to take from a release archive.
+///
+/// Release archives sometimes hold several executables, such as a tool and helpers it runs.  The
+/// tool's binary is the one named after the crate's bin target, and other executables are only
+/// taken when the tool's config lists them in `helpers`, to be installed alongside the binary.
+///
+/// An archive can also hold the same executable for several targets, or bury it a few directories
+/// deep; see [`find_binary_in_dir`] for how the one for the target is told apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(in crate::bin_resolver) struct BinaryPicker {
+    /// Name of the tool's binary, without any `.exe` suffix.
+    binary_name: String,
+
+    /// Names of the helper executables to install alongside the binary.
+    helpers: Vec<String>,
+
+    /// The target the executables are picked for, once it's known (see [`Self::for_target`]).
+    target: Option<String>,
+}
+
+impl BinaryPicker {
+    pub(in crate::bin_resolver) fn new(krate: &DownloadedCrate, config: &Config) -> Result<Self> {
+        Ok(Self {
+            binary_name: krate.default_binary_name()?,
+            helpers: config
+                .tool_helpers(&krate.resolved.name)
+                .map(<[String]>::to_vec)
+                .unwrap_or_default(),
+            target: None,
+        })
+    }
+
+    /// This picker, picking the executables for `target`.
+    pub(in crate::bin_resolver) fn for_target(&self, target: &str) -> Self {
+        Self {
+            target: Some(target.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// Extract the archive at `archive_path` into `extract_dir`, and install the picked
+    /// executables from it into `final_dir`.
+    ///
+    /// Returns the path of the installed binary.  A helper missing from the archive is an error,
+    /// since the tool is unlikely to work without it.
+    pub(in crate::bin_resolver) fn install_from_archive(
+        &self,
+        archive_path: &Path,
+        format: ArchiveFormat,
+        extract_dir: &Path,
+        final_dir: &Path,
+    ) -> Result<PathBuf> {
+        let binary_path = extract_binary(
+            archive_path,
+            format,
+            &self.binary_name,
+            extract_dir,
+            self.target.as_deref(),
+        )?;
+        self.install(&binary_path, extract_dir, final_dir)
+    }
+
+    /// Install the picked executables from the release asset `download`, downloaded from `url`,
+    /// into `final_dir`.
+    ///
+    /// An archive is extracted by way of `archives`, so that one extracted before is used again.
+    /// A naked binary has nothing to extract, and is installed as it is.
+    pub(in crate::bin_resolver) fn install_from_asset(
+        &self,
+        archives: &ArchiveCache,
+        url: &str,
+        download: &Download,
+        format: ArchiveFormat,
+        final_dir: &Path,
+    ) -> Result<PathBuf> {
+        if format != ArchiveFormat::NakedBinary {
+            let extract_dir = archives.extract(url, download, format)?;
+            return self.install_from_dir(&extract_dir, final_dir);
+        }
+
+        let temp_dir = tempfile::tempdir().with_context(|_| error::TempDirCreationSnafu {
+            parent: std::env::temp_dir(),
+        })?;
+        self.install_from_archive(
+            download.path(),
+            format,
+            &temp_dir.path().join("extracted"),
+            final_dir,
+        )
+    }
+
+    /// Install the picked executables from an archive already extracted into `extract_dir`, as
+    /// returned by [`ArchiveCache::lookup`], into `final_dir`.
+    pub(in crate::bin_resolver) fn install_from_dir(
+        &self,
+        extract_dir: &Path,
+        final_dir: &Path,
+    ) -> Result<PathBuf> {
+        let binary_path = find_binary_in_dir(extract_dir, &self.binary_name, self.target.as_deref())?;
+        self.install(&binary_path, extract_dir, final_dir)
+    }
+
+    fn install(&self, binary_path: &Path, extract_dir: &Path, final_dir: &Path) -> Result<PathBuf> {
+        let helper_paths = self
+            .helpers
+            .iter()
+            .map(|helper| find_binary_in_dir(extract_dir, helper, self.target.as_deref()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let final_path = final_dir.join(installed_file_name(&self.binary_name, binary_path));
+        crate::helpers::install_executable(binary_path, &final_path)?;
+        for (helper, helper_path) in self.helpers.iter().zip(helper_paths) {
+            let helper_file_name = installed_file_name(helper, &helper_path);
+            crate::helpers::install_executable(&helper_path, &final_dir.join(helper_file_name))?;
+        }
+
+        Ok(final_path)
+    }
+
+    /// The helpers that were installed alongside the binary into `final_dir`.
+    pub(in crate::bin_resolver) fn installed_helpers<'a>(
+        &'a self,
+        final_dir: &'a Path,
+    ) -> impl Iterator<Item = PathBuf> + 'a {
+        self.helpers.iter().filter_map(move |helper| {
+            executable_file_names(helper)
+                .into_iter()
+                .map(|file_name| final_dir.join(file_name))
+                .find(|path| path.is_file())
+        })
+    }
+}
+
+/// Release archives that have been extracted, kept by the SHA256 of the archive.
+///
+/// Now and then two crates, or two versions of one, point at the same release asset, such as the
+/// single archive of a monorepo release, and a retry downloads the same asset again.  Each archive
+/// is only extracted once, into `binaries/archives/<SHA256>` in the cache directory, and the URL it
+/// was downloaded from is recorded so that it needn't be downloaded again either.  With
+/// `--refresh=binary` assets are always downloaded again, though an archive with the same digest is
+/// still not extracted twice, since its contents are the same.
+///
+/// An extracted archive is only needed until its binaries are copied into the binary cache, and
+/// then only to spare a download, so whichever haven't been used for [`ARCHIVE_MAX_AGE`] are
+/// removed each time another archive is extracted.
+#[derive(Debug, Clone)]
+pub(in crate::bin_resolver) struct ArchiveCache {
+    dir: PathBuf,
+
+    /// Whether an archive extracted before is used in place of downloading its URL again.
+    reuse_downloads: bool,
+}
+
+impl ArchiveCache {
+    pub(in crate::bin_resolver) fn new(config: &Config) -> Self {
+        Self {
+            dir: config.binary_cache_root().join("binaries").join("archives"),
+            reuse_downloads: !config.refresh.binary,
+        }
+    }
+
+    /// The directory the archive downloaded from `url` was extracted into, if it has been.
+    ///
+    /// If the provider says what the archive's SHA256 is before it's downloaded, that's given as
+    /// `digest`, and an archive with that digest from any URL will do, even with
+    /// `--refresh=binary`, since it's sure to be the same.
+    pub(in crate::bin_resolver) fn lookup(&self, url: &str, digest: Option<&str>) -> Option<PathBuf> {
+        let digest = match digest {
+            Some(digest) => digest.to_ascii_lowercase(),
+            None if self.reuse_downloads => std::fs::read_to_string(self.url_path(url)).ok()?,
+            None => return None,
+        };
+
+        let extract_dir = self.dir.join(digest.trim());
+        if !extract_dir.is_dir() {
+            return None;
+        }
+        Self::mark_used(&extract_dir);
+        Some(extract_dir)
+    }
+
+    /// Extract the archive `download`, downloaded from `url`, unless one with the same digest has
+    /// been already, and return the directory it's extracted into.
+    ///
+    /// The digest is the one worked out as the archive was downloaded, so it isn't hashed again.
+    pub(in crate::bin_resolver) fn extract(
+        &self,
+        url: &str,
+        download: &Download,
+        format: ArchiveFormat,
+    ) -> Result<PathBuf> {
+        let digest = &download.sha256;
+        let extract_dir = self.dir.join(digest);
+
+        if !extract_dir.is_dir() {
+            std::fs::create_dir_all(&self.dir).with_context(|_| error::IoSnafu {
+                path: self.dir.clone(),
+            })?;
+            let temp_dir = tempfile::tempdir_in(&self.dir).with_context(|_| error::TempDirCreationSnafu {
+                parent: self.dir.clone(),
+            })?;
+            // Extracted beside where it goes and then renamed into place, so that a half-extracted
+            // archive is never taken for a whole one.  Another process may have got there first,
+            // which is fine, since it extracted the same archive.
+            let unpacked = temp_dir.path().join("extracted");
+            unpack(download.path(), format, &unpacked)?;
+            if let Err(e) = std::fs::rename(&unpacked, &extract_dir) {
+                if !extract_dir.is_dir() {
+                    return Err(e).context(error::IoSnafu { path: extract_dir });
                 }
             }
+
+            self.prune(ARCHIVE_MAX_AGE, &extract_dir);
         }
+        Self::mark_used(&extract_dir);
+
+        let url_path = self.url_path(url);
+        if let Some(parent) = url_path.parent() {
+            std::fs::create_dir_all(parent).with_context(|_| error::IoSnafu { path: parent })?;
+        }
+        std::fs::write(&url_path, digest).with_context(|_| error::IoSnafu {
+            path: url_path.clone(),
+        })?;
+
+        Ok(extract_dir)
     }
 
-    /// All (format, suffix) pairs used for candidate filename generation.
-    pub(in crate::bin_resolver) fn all_formats() -> &'static [(ArchiveFormat, &'static str)] {
-        #[cfg(windows)]
-        {
-            &[
-                (Self::Tar, ".tar"),
-                (Self::TarGz, ".tar.gz"),
-                (Self::TarGz, ".tgz"),
-                (Self::TarXz, ".tar.xz"),
-                (Self::TarZst, ".tar.zst"),
-                (Self::TarBz2, ".tar.bz2"),
-                (Self::Zip, ".zip"),
-                (Self::NakedBinary, ".exe"),
-            ]
-        }
-        #[cfg(not(windows))]
-        {
-            &[
-                (Self::Tar, ".tar"),
-                (Self::TarGz, ".tar.gz"),
-                (Self::TarGz, ".tgz"),
-                (Self::TarXz, ".tar.xz"),
-                (Self::TarZst, ".tar.zst"),
-                (Self::TarBz2, ".tar.bz2"),
-                (Self::Zip, ".zip"),
-                (Self::NakedBinary, ""),
-            ]
+    /// The SHA256 of the archive that was extracted into `extract_dir`, as returned by
+    /// [`Self::lookup`] or [`Self::extract`].
+    pub(in crate::bin_resolver) fn digest(extract_dir: &Path) -> Option<String> {
+        extract_dir.file_name()?.to_str().map(str::to_string)
+    }
+
+    /// The file recording the digest of the archive last downloaded from `url`.
+    fn url_path(&self, url: &str) -> PathBuf {
+        self.dir
+            .join("urls")
+            .join(format_hex_lower(Sha256::digest(url.as_bytes())))
+    }
+
+    /// The file whose modification time is when the archive in `extract_dir` was last used.
+    ///
+    /// It's beside the extracted archive rather than in it, so that it's never taken for one of
+    /// the archive's files.
+    fn used_path(extract_dir: &Path) -> PathBuf {
+        extract_dir.with_extension("used")
+    }
+
+    /// Record that the archive in `extract_dir` was just used, so that it isn't pruned.
+    fn mark_used(extract_dir: &Path) {
+        let used_path = Self::used_path(extract_dir);
+        if let Err(e) = std::fs::write(&used_path, "") {
+            debug!(
+                "Failed to record the use of the archive in {}: {}",
+                extract_dir.display(),
+                e
+            );
         }
     }
+
+    /// Remove the extracted archives, other than `keep`, that haven't been used for `max_age`, and
+    /// the URLs recorded for them.
+    ///
+    /// Failures are only logged; whatever can't be removed now is tried again next time.
+    fn prune(&self, max_age: Duration, keep: &Path) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+
+        for extract_dir in entries.flatten().map(|entry| entry.path()) {
+            // Only extracted archives are named for their digest; the URLs, the last-used markers
+            // and archives still being extracted are all named otherwise
+            let is_digest = extract_dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit()));
+            if !is_digest || extract_dir == keep {
+                continue;
+            }
+
+            let unused_for = std::fs::metadata(Self::used_path(&extract_dir))
+                .or_else(|_| std::fs::metadata(&extract_dir))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.elapsed().ok());
+            if unused_for.is_none_or(|unused_for| unused_for <= max_age) {
+                continue;
+            }
+
+            debug!(
+                "Removing the archive extracted in {}, unused for over {:?}",
+                extract_dir.display(),
+                max_age
+            );
+            if let Err(e) = std::fs::remove_dir_all(&extract_dir) {
+                warn!(
+                    "Failed to remove the archive extracted in {}: {}",
+                    extract_dir.display(),
+                    e
+                );
+                continue;
+            }
+            let _ = std::fs::remove_file(Self::used_path(&extract_dir));
+        }
+
+        // A URL whose archive is gone would only be looked up in vain
+        let Ok(url_files) = std::fs::read_dir(self.dir.join("urls")) else {
+            return;
+        };
+        for url_path in url_files.flatten().map(|entry| entry.path()) {
+            let Ok(digest) = std::fs::read_to_string(&url_path) else {
+                continue;
+            };
+            if !self.dir.join(digest.trim()).is_dir() {
+                let _ = std::fs::remove_file(&url_path);
+            }
+        }
+    }
+}
+
+/// The file name of the executable `name` on this platform.
+fn exe_file_name(name: &str) -> String {
+    format!("{}{}", name, std::env::consts::EXE_SUFFIX)
 }
 
-/// Extract a binary from an archive or naked binary file.
+/// Extract a binary from an archive or naked binary file, picking the one for `target` if it's
+/// known and the archive has several.
 ///
 /// The caller specifies the [`ArchiveFormat`] explicitly; there is no detection or fallback.
 pub(in crate::bin_resolver) fn extract_binary(
@@ -75,61 +387,43 @@ pub(in crate::bin_resolver) fn extract_binary(
     format: ArchiveFormat,
     expected_binary_name: &str,
     dest_dir: &Path,
+    target: Option<&str>,
 ) -> Result<PathBuf> {
-    match format {
-        ArchiveFormat::Tar => extract_tar(archive_path, expected_binary_name, dest_dir),
-        ArchiveFormat::TarGz => extract_tar_gz(archive_path, expected_binary_name, dest_dir),
-        ArchiveFormat::TarXz => extract_tar_xz(archive_path, expected_binary_name, dest_dir),
-        ArchiveFormat::TarZst => extract_tar_zst(archive_path, expected_binary_name, dest_dir),
-        ArchiveFormat::TarBz2 => extract_tar_bz2(archive_path, expected_binary_name, dest_dir),
-        ArchiveFormat::Zip => extract_zip(archive_path, expected_binary_name, dest_dir),
-        ArchiveFormat::NakedBinary => extract_naked_binary(archive_path, expected_binary_name, dest_dir),
+    if format == ArchiveFormat::NakedBinary {
+        return extract_naked_binary(archive_path, expected_binary_name, dest_dir);
     }
-}
-
-fn extract_tar(archive_path: &Path, binary_name: &str, dest_dir: &Path) -> Result<PathBuf> {
-    let file = std::fs::File::open(archive_path).with_context(|_| error::IoSnafu {
-        path: archive_path.to_path_buf(),
-    })?;
-    extract_tar_archive(file, binary_name, dest_dir)
-}
 
-fn extract_tar_gz(archive_path: &Path, binary_name: &str, dest_dir: &Path) -> Result<PathBuf> {
-    let file = std::fs::File::open(archive_path).with_context(|_| error::IoSnafu {
-        path: archive_path.to_path_buf(),
-    })?;
-    let decoder = GzDecoder::new(file);
-    extract_tar_archive(decoder, binary_name, dest_dir)
-}
-
-fn extract_tar_xz(archive_path: &Path, binary_name: &str, dest_dir: &Path) -> Result<PathBuf> {
-    let file = std::fs::File::open(archive_path).with_context(|_| error::IoSnafu {
-        path: archive_path.to_path_buf(),
-    })?;
-    let decoder = XzDecoder::new(file);
-    extract_tar_archive(decoder, binary_name, dest_dir)
+    unpack(archive_path, format, dest_dir)?;
+    find_binary_in_dir(dest_dir, expected_binary_name, target)
 }
 
-fn extract_tar_zst(archive_path: &Path, binary_name: &str, dest_dir: &Path) -> Result<PathBuf> {
+/// Unpack everything in the archive at `archive_path` into `dest_dir`.
+///
+/// A naked binary isn't an archive, and has to go through [`extract_binary`] to be given its name.
+fn unpack(archive_path: &Path, format: ArchiveFormat, dest_dir: &Path) -> Result<()> {
     let file = std::fs::File::open(archive_path).with_context(|_| error::IoSnafu {
         path: archive_path.to_path_buf(),
     })?;
-    let decoder =
-        zstd::stream::read::Decoder::new(file).map_err(|e| error::Error::ArchiveExtractionFailed {
-            source: Box::new(e) as Box<dyn std::error::Error + Send + Sync>,
-        })?;
-    extract_tar_archive(decoder, binary_name, dest_dir)
-}
 
-fn extract_tar_bz2(archive_path: &Path, binary_name: &str, dest_dir: &Path) -> Result<PathBuf> {
-    let file = std::fs::File::open(archive_path).with_context(|_| error::IoSnafu {
-        path: archive_path.to_path_buf(),
-    })?;
-    let decoder = BzDecoder::new(file);
-    extract_tar_archive(decoder, binary_name, dest_dir)
+    match format {
+        ArchiveFormat::Tar => unpack_tar(file, dest_dir),
+        ArchiveFormat::TarGz => unpack_tar(GzDecoder::new(file), dest_dir),
+        ArchiveFormat::TarXz => unpack_tar(XzDecoder::new(file), dest_dir),
+        ArchiveFormat::TarZst => {
+            let decoder = zstd::stream::read::Decoder::new(file).map_err(|e| {
+                error::Error::ArchiveExtractionFailed {
+                    source: Box::new(e) as Box<dyn std::error::Error + Send + Sync>,
+                }
+            })?;
+            unpack_tar(decoder, dest_dir)
+        }
+        ArchiveFormat::TarBz2 => unpack_tar(BzDecoder::new(file), dest_dir),
+        ArchiveFormat::Zip => unpack_zip(file, dest_dir),
+        ArchiveFormat::NakedBinary => unreachable!("BUG: a naked binary is not unpacked"),
+    }
 }
 
-fn extract_tar_archive<R: std::io::Read>(reader: R, binary_name: &str, dest_dir: &Path) -> Result<PathBuf> {
+fn unpack_tar<R: std::io::Read>(reader: R, dest_dir: &Path) -> Result<()> {
     let mut archive = tar::Archive::new(reader);
 
     std::fs::create_dir_all(dest_dir).with_context(|_| error::IoSnafu {
@@ -140,16 +434,10 @@ fn extract_tar_archive<R: std::io::Read>(reader: R, binary_name: &str, dest_dir:
         .unpack(dest_dir)
         .map_err(|e| error::Error::ArchiveExtractionFailed {
             source: Box::new(e) as Box<dyn std::error::Error + Send + Sync>,
-        })?;
-
-    find_binary_in_dir(dest_dir, binary_name)
+        })
 }
 
-fn extract_zip(archive_path: &Path, binary_name: &str, dest_dir: &Path) -> Result<PathBuf> {
-    let file = std::fs::File::open(archive_path).with_context(|_| error::IoSnafu {
-        path: archive_path.to_path_buf(),
-    })?;
-
+fn unpack_zip(file: std::fs::File, dest_dir: &Path) -> Result<()> {
     let mut archive = zip::ZipArchive::new(file).map_err(|e| error::Error::ArchiveExtractionFailed {
         source: Box::new(e) as Box<dyn std::error::Error + Send + Sync>,
     })?;
@@ -162,9 +450,7 @@ fn extract_zip(archive_path: &Path, binary_name: &str, dest_dir: &Path) -> Resul
         .extract(dest_dir)
         .map_err(|e| error::Error::ArchiveExtractionFailed {
             source: Box::new(e) as Box<dyn std::error::Error + Send + Sync>,
-        })?;
-
-    find_binary_in_dir(dest_dir, binary_name)
+        })
 }
 
 fn extract_naked_binary(archive_path: &Path, binary_name: &str, dest_dir: &Path) -> Result<PathBuf> {
@@ -195,52 +481,245 @@ fn extract_naked_binary(archive_path: &Path, binary_name: &str, dest_dir: &Path)
     Ok(dest_path)
 }
 
-/// Find a binary executable in a directory, searching common locations.
+/// Find a binary executable in a directory, anywhere under it.
+///
+/// Looks for an executable by each of its [`executable_file_names`], in order, and the first name
+/// that any executable has wins.  Archives often put everything in a directory named after the
+/// release, nest it deeper still, or hold a build for each of several targets, so if more than one
+/// executable has that name, they're told apart by, in order:
+/// - whether it's an executable that runs on `target`, going by its header (see
+///   [`binary_format::runs_on`])
+/// - whether a directory it's in names `target`, as in `tool-v1.2.3-x86_64-unknown-linux-gnu/`
+/// - whether it's in one of the usual places: the root, `bin/`, or `target/release/`
+/// - whether it's in a `bin/` directory at all
+///
+/// Executables that are still tied after all that make the binary ambiguous.
+fn find_binary_in_dir(dir: &Path, binary_name: &str, target: Option<&str>) -> Result<PathBuf> {
+    let file_names = executable_file_names(binary_name);
+
+    // The first of the file names that any executable has wins, so that on Windows `tool.exe` is
+    // taken over a `tool.cmd` beside it, the way the shell would pick between them
+    let executables = executables_in_dir(dir);
+    let matches = file_names
+        .iter()
+        .map(|file_name| {
+            executables
+                .iter()
+                .filter(|path| {
+                    path.file_name()
+                        .is_some_and(|name| file_name_matches(name, file_name))
+                })
+                .collect::<Vec<_>>()
+        })
+        .find(|matches| !matches.is_empty())
+        .unwrap_or_default();
+
+    let ranked = matches
+        .iter()
+        .map(|path| (candidate_rank(dir, path, target), *path))
+        .collect::<Vec<_>>();
+    let best = ranked.iter().map(|(rank, _)| *rank).max();
+    let matches = ranked
+        .iter()
+        .filter(|(rank, _)| Some(*rank) == best)
+        .map(|(_, path)| *path)
+        .collect::<Vec<_>>();
+
+    match matches.as_slice() {
+        [path] => Ok(dir.join(path)),
+        [] => {
+            let inventory = if executables.is_empty() {
+                "it has no executables".to_string()
+            } else {
+                format!(
+                    "its executables are {}",
+                    executables
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            };
+            let err = std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!(
+                    "binary '{}' not found in extracted archive; {}",
+                    binary_name, inventory
+                ),
+            );
+            Err(error::Error::ArchiveExtractionFailed {
+                source: Box::new(err) as Box<dyn std::error::Error + Send + Sync>,
+            })
+        }
+        _ => error::AmbiguousArchiveBinarySnafu {
+            binary: binary_name,
+            candidates: matches
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>(),
+        }
+        .fail(),
+    }
+}
+
+/// How likely the executable at `path`, relative to `dir`, is to be the binary for `target`, as
+/// [`find_binary_in_dir`] weighs it, higher being likelier.
+fn candidate_rank(dir: &Path, path: &Path, target: Option<&str>) -> (bool, bool, bool, bool) {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    let dirs = || {
+        parent
+            .components()
+            .filter_map(|component| component.as_os_str().to_str())
+    };
+
+    let runs_on_target = target.is_some_and(|target| binary_format::runs_on(&dir.join(path), target));
+    let in_target_dir = target.is_some_and(|target| dirs().any(|name| name.contains(target)));
+    let in_usual_place = [
+        Path::new(""),
+        Path::new("bin"),
+        &Path::new("target").join("release"),
+    ]
+    .iter()
+    .any(|location| parent == *location);
+    let in_bin_dir = dirs().any(|name| name == "bin");
+
+    (runs_on_target, in_target_dir, in_usual_place, in_bin_dir)
+}
+
+/// The extensions that make a file executable on Windows when `PATHEXT` isn't set.
+const DEFAULT_PATHEXT: &str = ".COM;.EXE;.BAT;.CMD";
+
+/// The file names the executable `name` may have, most preferred first.
+///
+/// On Windows that's `name` with each extension in `PATHEXT`, in order, except that `.exe` always
+/// comes first, since that's what a Rust binary is.  Elsewhere it's just `name`.  Either way,
+/// `name.wasm` comes last, for archives of WebAssembly tools.
+fn executable_file_names(name: &str) -> Vec<String> {
+    let mut file_names = if cfg!(windows) {
+        let pathext = std::env::var("PATHEXT").unwrap_or_else(|_| DEFAULT_PATHEXT.to_string());
+        pathext_file_names(name, &pathext)
+    } else {
+        vec![name.to_string()]
+    };
+    file_names.push(format!("{}.wasm", name));
+
+    file_names
+}
+
+/// The file name the executable `name`, found at `binary_path`, is installed as.
 ///
-/// Looks for:
-/// - `binary_name` or `binary_name.exe` in the root
-/// - `binary_name` or `binary_name.exe` in `bin/`
-/// - `binary_name` or `binary_name.exe` in `target/release/`
-fn find_binary_in_dir(dir: &Path, binary_name: &str) -> Result<PathBuf> {
-    let exe_suffix = std::env::consts::EXE_SUFFIX;
-    let candidates = [
-        dir.join(format!("{}{}", binary_name, exe_suffix)),
-        dir.join(binary_name),
-        dir.join("bin").join(format!("{}{}", binary_name, exe_suffix)),
-        dir.join("bin").join(binary_name),
-        dir.join("target")
-            .join("release")
-            .join(format!("{}{}", binary_name, exe_suffix)),
-        dir.join("target").join("release").join(binary_name),
-    ];
-
-    for candidate in &candidates {
-        if candidate.exists() && is_executable(candidate) {
-            return Ok(candidate.clone());
-        }
-    }
-
-    let err = std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        format!("binary '{}' not found in extracted archive", binary_name),
-    );
-    Err(error::Error::ArchiveExtractionFailed {
-        source: Box::new(err) as Box<dyn std::error::Error + Send + Sync>,
-    })
+/// That's `name` with this platform's executable suffix, except that on Windows an executable
+/// found by another extension in `PATHEXT`, such as a `.cmd` script, keeps it, since that's what
+/// Windows runs it by.
+fn installed_file_name(name: &str, binary_path: &Path) -> String {
+    if cfg!(windows) {
+        let pathext = std::env::var("PATHEXT").unwrap_or_else(|_| DEFAULT_PATHEXT.to_string());
+        if let Some(file_name) = binary_path.file_name().and_then(|file_name| file_name.to_str()) {
+            return pathext_file_name(name, file_name, &pathext);
+        }
+    }
+    exe_file_name(name)
 }
 
-/// Check if a file is executable.
+/// Of `name` with each extension in the `PATHEXT`-style list `pathext`, the one `found` is,
+/// ignoring case, or else `name.exe`.
+fn pathext_file_name(name: &str, found: &str, pathext: &str) -> String {
+    let mut file_names = pathext_file_names(name, pathext);
+    let index = file_names
+        .iter()
+        .position(|file_name| file_name.eq_ignore_ascii_case(found))
+        .unwrap_or(0);
+    file_names.swap_remove(index)
+}
+
+/// `name` with each extension in the `PATHEXT`-style list `pathext`, `.exe` first.
+fn pathext_file_names(name: &str, pathext: &str) -> Vec<String> {
+    let mut extensions = vec![".exe".to_string()];
+    for extension in pathext
+        .split(';')
+        .map(str::trim)
+        .filter(|ext| ext.starts_with('.'))
+    {
+        let extension = extension.to_ascii_lowercase();
+        if !extensions.contains(&extension) {
+            extensions.push(extension);
+        }
+    }
+
+    extensions
+        .into_iter()
+        .map(|extension| format!("{}{}", name, extension))
+        .collect()
+}
+
+/// Whether the file name `actual` is `expected`, ignoring case on Windows as its file systems do.
+fn file_name_matches(actual: &std::ffi::OsStr, expected: &str) -> bool {
+    match actual.to_str() {
+        Some(actual) if cfg!(windows) => actual.eq_ignore_ascii_case(expected),
+        Some(actual) => actual == expected,
+        None => false,
+    }
+}
+
+/// Every executable file in `dir` and its subdirectories, as paths relative to `dir`, sorted.
+///
+/// A symlink to an executable file counts as one, as in a `bin/` of links into the release, but
+/// symlinks to directories aren't followed.
+fn executables_in_dir(dir: &Path) -> Vec<PathBuf> {
+    let mut executables = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => pending.push(path),
+                Ok(file_type)
+                    if (file_type.is_file() || (file_type.is_symlink() && path.is_file()))
+                        && is_executable(&path) =>
+                {
+                    if let Ok(relative) = path.strip_prefix(dir) {
+                        executables.push(relative.to_path_buf());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    executables.sort();
+    executables
+}
+
+/// Check if a file is executable, or a WebAssembly module, which is run by a runtime and so is
+/// rarely marked executable.
 #[cfg(unix)]
 fn is_executable(path: &Path) -> bool {
     use std::os::unix::fs::PermissionsExt;
+    if path.extension().is_some_and(|extension| extension == "wasm") {
+        return true;
+    }
     std::fs::metadata(path)
         .map(|m| m.permissions().mode() & 0o111 != 0)
         .unwrap_or(false)
 }
 
+/// Check if a file is executable, which on Windows is a matter of its extension being in
+/// `PATHEXT`, or a WebAssembly module.
 #[cfg(not(unix))]
-fn is_executable(_path: &Path) -> bool {
-    true
+fn is_executable(path: &Path) -> bool {
+    let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
+        return false;
+    };
+    if extension.eq_ignore_ascii_case("wasm") {
+        return true;
+    }
+    let pathext = std::env::var("PATHEXT").unwrap_or_else(|_| DEFAULT_PATHEXT.to_string());
+    pathext
+        .split(';')
+        .any(|ext| ext.trim().trim_start_matches('.').eq_ignore_ascii_case(extension))
 }
 
 #[cfg(test)]
@@ -251,6 +730,7 @@ mod tests {
     use std::{
         fs,
         io::{Cursor, Write},
+        time::SystemTime,
     };
     use xz2::write::XzEncoder;
     use zip::write::SimpleFileOptions;
@@ -393,6 +873,7 @@ mod tests {
             ArchiveFormat::TarGz,
             "testbin",
             dest_dir.path(),
+            None,
         );
 
         let binary_path = result.unwrap();
@@ -413,6 +894,7 @@ mod tests {
             ArchiveFormat::TarGz,
             "testbin",
             dest_dir.path(),
+            None,
         );
 
         let binary_path = result.unwrap();
@@ -433,6 +915,7 @@ mod tests {
             ArchiveFormat::TarGz,
             "testbin",
             dest_dir.path(),
+            None,
         );
 
         let binary_path = result.unwrap();
@@ -456,6 +939,7 @@ mod tests {
             ArchiveFormat::TarXz,
             "testbin",
             dest_dir.path(),
+            None,
         );
 
         let binary_path = result.unwrap();
@@ -475,6 +959,7 @@ mod tests {
             ArchiveFormat::TarZst,
             "testbin",
             dest_dir.path(),
+            None,
         );
 
         let binary_path = result.unwrap();
@@ -494,6 +979,7 @@ mod tests {
             ArchiveFormat::Zip,
             "testbin",
             dest_dir.path(),
+            None,
         );
 
         let binary_path = result.unwrap();
@@ -513,6 +999,7 @@ mod tests {
             ArchiveFormat::Zip,
             "testbin",
             dest_dir.path(),
+            None,
         );
 
         let binary_path = result.unwrap();
@@ -530,6 +1017,7 @@ mod tests {
             ArchiveFormat::NakedBinary,
             "testbin",
             dest_dir.path(),
+            None,
         );
 
         let binary_path = result.unwrap();
@@ -571,7 +1059,13 @@ mod tests {
         fs::write(&archive_path, &archive_data).unwrap();
 
         let dest_dir = tempfile::tempdir().unwrap();
-        let result = extract_binary(&archive_path, ArchiveFormat::TarGz, "testbin", dest_dir.path());
+        let result = extract_binary(
+            &archive_path,
+            ArchiveFormat::TarGz,
+            "testbin",
+            dest_dir.path(),
+            None,
+        );
 
         assert_matches::assert_matches!(result, Err(Error::ArchiveExtractionFailed { .. }));
     }
@@ -585,7 +1079,13 @@ mod tests {
         fs::write(&archive_path, corrupt_data).unwrap();
 
         let dest_dir = tempfile::tempdir().unwrap();
-        let result = extract_binary(&archive_path, ArchiveFormat::TarGz, "testbin", dest_dir.path());
+        let result = extract_binary(
+            &archive_path,
+            ArchiveFormat::TarGz,
+            "testbin",
+            dest_dir.path(),
+            None,
+        );
 
         assert_matches::assert_matches!(result, Err(Error::ArchiveExtractionFailed { .. }));
     }
@@ -599,7 +1099,13 @@ mod tests {
         fs::write(&archive_path, corrupt_data).unwrap();
 
         let dest_dir = tempfile::tempdir().unwrap();
-        let result = extract_binary(&archive_path, ArchiveFormat::TarXz, "testbin", dest_dir.path());
+        let result = extract_binary(
+            &archive_path,
+            ArchiveFormat::TarXz,
+            "testbin",
+            dest_dir.path(),
+            None,
+        );
 
         assert_matches::assert_matches!(result, Err(Error::ArchiveExtractionFailed { .. }));
     }
@@ -613,7 +1119,13 @@ mod tests {
         fs::write(&archive_path, corrupt_data).unwrap();
 
         let dest_dir = tempfile::tempdir().unwrap();
-        let result = extract_binary(&archive_path, ArchiveFormat::Zip, "testbin", dest_dir.path());
+        let result = extract_binary(
+            &archive_path,
+            ArchiveFormat::Zip,
+            "testbin",
+            dest_dir.path(),
+            None,
+        );
 
         assert_matches::assert_matches!(result, Err(Error::ArchiveExtractionFailed { .. }));
     }
@@ -628,7 +1140,13 @@ mod tests {
         fs::write(&archive_path, truncated).unwrap();
 
         let dest_dir = tempfile::tempdir().unwrap();
-        let result = extract_binary(&archive_path, ArchiveFormat::TarGz, "testbin", dest_dir.path());
+        let result = extract_binary(
+            &archive_path,
+            ArchiveFormat::TarGz,
+            "testbin",
+            dest_dir.path(),
+            None,
+        );
 
         assert_matches::assert_matches!(result, Err(Error::ArchiveExtractionFailed { .. }));
     }
@@ -647,7 +1165,13 @@ mod tests {
         fs::write(&archive_path, &archive_data).unwrap();
 
         let dest_dir = tempfile::tempdir().unwrap();
-        let result = extract_binary(&archive_path, ArchiveFormat::TarGz, "testbin", dest_dir.path());
+        let result = extract_binary(
+            &archive_path,
+            ArchiveFormat::TarGz,
+            "testbin",
+            dest_dir.path(),
+            None,
+        );
 
         assert_matches::assert_matches!(result, Err(Error::ArchiveExtractionFailed { .. }));
     }
@@ -681,7 +1205,13 @@ mod tests {
         fs::write(&archive_path, &archive_data).unwrap();
 
         let dest_dir = tempfile::tempdir().unwrap();
-        let result = extract_binary(&archive_path, ArchiveFormat::TarGz, "testbin", dest_dir.path());
+        let result = extract_binary(
+            &archive_path,
+            ArchiveFormat::TarGz,
+            "testbin",
+            dest_dir.path(),
+            None,
+        );
 
         assert_matches::assert_matches!(result, Err(Error::ArchiveExtractionFailed { .. }));
     }
@@ -705,7 +1235,7 @@ mod tests {
             fs::set_permissions(&binary_path, perms).unwrap();
         }
 
-        let result = find_binary_in_dir(temp_dir.path(), binary_name);
+        let result = find_binary_in_dir(temp_dir.path(), binary_name, None);
         assert!(result.is_ok(), "Should find binary with EXE_SUFFIX");
         assert_eq!(result.unwrap(), binary_path);
     }
@@ -725,11 +1255,22 @@ mod tests {
         perms.set_mode(0o755);
         fs::set_permissions(&binary_path, perms).unwrap();
 
-        let result = find_binary_in_dir(temp_dir.path(), binary_name);
+        let result = find_binary_in_dir(temp_dir.path(), binary_name, None);
         assert!(result.is_ok(), "Should find binary without .exe suffix on Unix");
         assert_eq!(result.unwrap(), binary_path);
     }
 
+    #[test]
+    fn test_find_wasm_binary_not_marked_executable() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let binary_path = temp_dir.path().join("bin").join("testbin.wasm");
+        fs::create_dir_all(binary_path.parent().unwrap()).unwrap();
+        fs::write(&binary_path, b"\0asm\x01\0\0\0").unwrap();
+
+        let result = find_binary_in_dir(temp_dir.path(), "testbin", None);
+        assert_eq!(result.unwrap(), binary_path);
+    }
+
     #[test]
     fn test_find_binary_in_nested_locations() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -750,11 +1291,311 @@ mod tests {
             fs::set_permissions(&binary_path, perms).unwrap();
         }
 
-        let result = find_binary_in_dir(temp_dir.path(), binary_name);
+        let result = find_binary_in_dir(temp_dir.path(), binary_name, None);
         assert!(result.is_ok(), "Should find binary in target/release");
         assert_eq!(result.unwrap(), binary_path);
     }
 
+    /// Build a `.tar.gz` of executables at the given paths.
+    fn create_test_tar_gz_with(paths: &[impl AsRef<Path>]) -> tempfile::NamedTempFile {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for path in paths {
+            let binary_path = temp_dir.path().join(path);
+            fs::create_dir_all(binary_path.parent().unwrap()).unwrap();
+            fs::write(&binary_path, b"#!/bin/sh\necho test").unwrap();
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&binary_path, fs::Permissions::from_mode(0o755)).unwrap();
+            }
+        }
+
+        let archive = tempfile::NamedTempFile::new().unwrap();
+        {
+            let encoder = GzEncoder::new(fs::File::create(archive.path()).unwrap(), Compression::default());
+            let mut tar = tar::Builder::new(encoder);
+            tar.append_dir_all(".", temp_dir.path()).unwrap();
+            tar.into_inner().unwrap().finish().unwrap();
+        }
+
+        archive
+    }
+
+    #[test]
+    fn test_find_binary_in_release_named_dir() {
+        let archive = create_test_tar_gz_with(&["testbin-1.0.0-x86_64-unknown-linux-gnu/testbin"]);
+
+        let dest_dir = tempfile::tempdir().unwrap();
+        let binary_path = extract_binary(
+            archive.path(),
+            ArchiveFormat::TarGz,
+            "testbin",
+            dest_dir.path(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            binary_path,
+            dest_dir
+                .path()
+                .join("testbin-1.0.0-x86_64-unknown-linux-gnu")
+                .join("testbin")
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_ambiguous_binary_lists_candidates() {
+        let archive = create_test_tar_gz_with(&["linux/testbin", "musl/testbin"]);
+
+        let dest_dir = tempfile::tempdir().unwrap();
+        let result = extract_binary(
+            archive.path(),
+            ArchiveFormat::TarGz,
+            "testbin",
+            dest_dir.path(),
+            None,
+        );
+
+        assert_matches::assert_matches!(
+            result,
+            Err(Error::AmbiguousArchiveBinary { candidates, .. })
+                if candidates == ["linux/testbin", "musl/testbin"]
+        );
+    }
+
+    #[test]
+    fn test_binary_in_target_named_dir_is_preferred() {
+        let archive = create_test_tar_gz_with(&[
+            Path::new("testbin-1.0.0-x86_64-unknown-linux-gnu/bin").join(exe_file_name("testbin")),
+            Path::new("testbin-1.0.0-aarch64-unknown-linux-gnu/bin").join(exe_file_name("testbin")),
+        ]);
+
+        let dest_dir = tempfile::tempdir().unwrap();
+        let binary_path = extract_binary(
+            archive.path(),
+            ArchiveFormat::TarGz,
+            "testbin",
+            dest_dir.path(),
+            Some("aarch64-unknown-linux-gnu"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            binary_path,
+            dest_dir
+                .path()
+                .join("testbin-1.0.0-aarch64-unknown-linux-gnu/bin")
+                .join(exe_file_name("testbin"))
+        );
+    }
+
+    /// Test that of several binaries of the same name, the one whose header says it runs on the
+    /// target is picked, wherever it is.
+    #[test]
+    #[cfg(unix)]
+    fn test_binary_that_runs_on_target_is_preferred() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let elf = |machine: u16| {
+            let mut header = vec![0u8; 64];
+            header[..4].copy_from_slice(b"\x7fELF");
+            header[4] = 2;
+            header[5] = 1;
+            header[18..20].copy_from_slice(&machine.to_le_bytes());
+            header
+        };
+        let temp_dir = tempfile::tempdir().unwrap();
+        for (dir, machine) in [("bin", 62), ("linux-arm64", 183)] {
+            let path = temp_dir.path().join(dir).join("testbin");
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, elf(machine)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        assert_eq!(
+            find_binary_in_dir(temp_dir.path(), "testbin", Some("aarch64-unknown-linux-gnu")).unwrap(),
+            temp_dir.path().join("linux-arm64/testbin")
+        );
+        assert_eq!(
+            find_binary_in_dir(temp_dir.path(), "testbin", Some("x86_64-unknown-linux-gnu")).unwrap(),
+            temp_dir.path().join("bin/testbin")
+        );
+        // Without a target to go by, a binary in `bin/` is taken over one elsewhere
+        assert_eq!(
+            find_binary_in_dir(temp_dir.path(), "testbin", None).unwrap(),
+            temp_dir.path().join("bin/testbin")
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_missing_binary_lists_inventory() {
+        let archive = create_test_tar_gz_with(&["tool/other", "tool/helper"]);
+
+        let dest_dir = tempfile::tempdir().unwrap();
+        let error = extract_binary(
+            archive.path(),
+            ArchiveFormat::TarGz,
+            "testbin",
+            dest_dir.path(),
+            None,
+        )
+        .unwrap_err();
+
+        assert!(
+            error.to_string().contains("tool/helper, tool/other"),
+            "unexpected error: {error}"
+        );
+    }
+
+    #[test]
+    fn test_picker_installs_helpers() {
+        let archive = create_test_tar_gz_with(&[
+            Path::new("bin").join(exe_file_name("testbin")),
+            Path::new("bin").join(exe_file_name("testbin-helper")),
+            Path::new("bin").join(exe_file_name("unrelated")),
+        ]);
+        let picker = BinaryPicker {
+            binary_name: "testbin".to_string(),
+            helpers: vec!["testbin-helper".to_string()],
+            target: None,
+        };
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let final_dir = temp_dir.path().join("final");
+        let binary_path = picker
+            .install_from_archive(
+                archive.path(),
+                ArchiveFormat::TarGz,
+                &temp_dir.path().join("extracted"),
+                &final_dir,
+            )
+            .unwrap();
+
+        assert_eq!(binary_path, final_dir.join(exe_file_name("testbin")));
+        assert!(final_dir.join(exe_file_name("testbin-helper")).exists());
+        assert!(!final_dir.join(exe_file_name("unrelated")).exists());
+    }
+
+    fn download_of(data: &[u8]) -> Download {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(data).unwrap();
+        Download::new(file.into_temp_path(), format_hex_lower(Sha256::digest(data)))
+    }
+
+    #[test]
+    fn archive_cache_extracts_each_digest_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            cache_dir: temp_dir.path().join("cache"),
+            ..Config::default()
+        };
+        let archives = ArchiveCache::new(&config);
+        let download = download_of(&create_test_tar_gz("testbin", BinaryLocation::BinDir));
+        let digest = download.sha256.clone();
+        let url = "https://example.com/v1/testbin.tar.gz";
+
+        assert_eq!(archives.lookup(url, None), None);
+        let extract_dir = archives.extract(url, &download, ArchiveFormat::TarGz).unwrap();
+        assert_eq!(
+            extract_dir,
+            config.cache_dir.join("binaries/archives").join(&digest)
+        );
+
+        // Found again by its URL, or by its digest from any URL
+        assert_eq!(archives.lookup(url, None), Some(extract_dir.clone()));
+        assert_eq!(
+            archives.lookup(
+                "https://example.com/v2/testbin.tar.gz",
+                Some(digest.to_uppercase().as_str())
+            ),
+            Some(extract_dir.clone())
+        );
+
+        // The same archive from another URL isn't extracted again
+        fs::write(extract_dir.join("marker"), "").unwrap();
+        let other_url = "https://example.com/v2/testbin.tar.gz";
+        assert_eq!(
+            archives
+                .extract(other_url, &download, ArchiveFormat::TarGz)
+                .unwrap(),
+            extract_dir
+        );
+        assert!(extract_dir.join("marker").exists());
+
+        let picker = BinaryPicker {
+            binary_name: "testbin".to_string(),
+            helpers: Vec::new(),
+            target: None,
+        };
+        let final_dir = temp_dir.path().join("final");
+        let binary_path = picker.install_from_dir(&extract_dir, &final_dir).unwrap();
+        assert_eq!(binary_path, final_dir.join(exe_file_name("testbin")));
+        assert!(extract_dir.join("bin").join(exe_file_name("testbin")).exists());
+
+        // With --refresh=binary a URL is downloaded again, but a known digest is still trusted
+        config.refresh.binary = true;
+        let archives = ArchiveCache::new(&config);
+        assert_eq!(archives.lookup(url, None), None);
+        assert_eq!(archives.lookup(url, Some(digest.as_str())), Some(extract_dir));
+    }
+
+    #[test]
+    fn archive_cache_prunes_only_unused_archives() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            cache_dir: temp_dir.path().join("cache"),
+            ..Config::default()
+        };
+        let archives = ArchiveCache::new(&config);
+        let max_age = Duration::from_secs(24 * 60 * 60);
+
+        let extract = |name: &str, age: Duration| {
+            let download = download_of(&create_test_tar_gz(name, BinaryLocation::BinDir));
+            let url = format!("https://example.com/{}.tar.gz", name);
+            let extract_dir = archives.extract(&url, &download, ArchiveFormat::TarGz).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(ArchiveCache::used_path(&extract_dir))
+                .unwrap()
+                .set_modified(SystemTime::now() - age)
+                .unwrap();
+            (url, extract_dir)
+        };
+        let (recent_url, recent) = extract("recent", Duration::from_secs(60));
+        let (stale_url, stale) = extract("stale", 2 * max_age);
+        let (kept_url, kept) = extract("kept", 2 * max_age);
+
+        archives.prune(max_age, &kept);
+
+        assert_eq!(archives.lookup(&recent_url, None), Some(recent));
+        assert_eq!(archives.lookup(&kept_url, None), Some(kept));
+        assert!(!stale.exists());
+        assert!(!ArchiveCache::used_path(&stale).exists());
+        assert!(!archives.url_path(&stale_url).exists());
+    }
+
+    #[test]
+    fn pathext_names_put_exe_first() {
+        assert_eq!(
+            pathext_file_names("tool", ".COM;.EXE;.BAT; .cmd;;"),
+            vec!["tool.exe", "tool.com", "tool.bat", "tool.cmd"]
+        );
+        assert_eq!(pathext_file_names("tool", ""), vec!["tool.exe"]);
+    }
+
+    #[test]
+    fn pathext_matches_keep_their_extension() {
+        let pathext = ".COM;.EXE;.BAT;.CMD";
+        assert_eq!(pathext_file_name("tool", "TOOL.EXE", pathext), "tool.exe");
+        assert_eq!(pathext_file_name("tool", "tool.CMD", pathext), "tool.cmd");
+        assert_eq!(pathext_file_name("tool", "tool.bat", pathext), "tool.bat");
+        assert_eq!(pathext_file_name("tool", "tool.wasm", pathext), "tool.exe");
+    }
+
     #[test]
     fn archive_format_suffix_consistency() {
         assert_eq!(ArchiveFormat::Tar.suffix(), ".tar");
@@ -763,5 +1604,16 @@ mod tests {
         assert_eq!(ArchiveFormat::TarZst.suffix(), ".tar.zst");
         assert_eq!(ArchiveFormat::TarBz2.suffix(), ".tar.bz2");
         assert_eq!(ArchiveFormat::Zip.suffix(), ".zip");
+
+        let naked = |platform| ArchiveFormat::all_formats(platform)[7];
+        assert_eq!(
+            naked("x86_64-pc-windows-msvc"),
+            (ArchiveFormat::NakedBinary, ".exe")
+        );
+        assert_eq!(
+            naked("x86_64-unknown-linux-gnu"),
+            (ArchiveFormat::NakedBinary, "")
+        );
+        assert_eq!(naked("wasm32-wasip1"), (ArchiveFormat::NakedBinary, ".wasm"));
     }
 }
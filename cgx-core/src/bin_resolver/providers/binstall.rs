@@ -1,24 +1,24 @@
-use super::{ArchiveFormat, Provider};
+use super::{ArchiveCache, ArchiveFormat, BinaryPicker, OriginPolicy, Provider};
 use crate::{
     Result,
     bin_resolver::ResolvedBinary,
     config::BinaryProvider,
-    crate_resolver::ResolvedSource,
     downloader::DownloadedCrate,
     error,
-    http::{Bytes, HttpClient},
+    http::{Bytes, Download, HttpClient},
     messages::PrebuiltBinaryMessage,
 };
 use serde::Deserialize;
-use sha2::{Digest, Sha256};
 use snafu::ResultExt;
 use std::{collections::HashMap, path::PathBuf};
 
 pub(in crate::bin_resolver) struct BinstallProvider {
     reporter: crate::messages::MessageReporter,
     cache_dir: PathBuf,
+    archives: ArchiveCache,
     verify_checksums: bool,
     http_client: HttpClient,
+    origin: OriginPolicy,
 }
 
 #[derive(Debug, Deserialize)]
@@ -128,14 +128,18 @@ impl BinstallProvider {
     pub(in crate::bin_resolver) fn new(
         reporter: crate::messages::MessageReporter,
         cache_dir: PathBuf,
+        archives: ArchiveCache,
         verify_checksums: bool,
         http_client: HttpClient,
+        origin: OriginPolicy,
     ) -> Self {
         Self {
             reporter,
             cache_dir,
+            archives,
             verify_checksums,
             http_client,
+            origin,
         }
     }
 
@@ -167,13 +171,7 @@ impl BinstallProvider {
     /// sources, falls back to the `[package].repository` field in Cargo.toml unfiltered (binstall
     /// templates can point at any host, so no host filtering is applied).
     fn get_repo_url(krate: &DownloadedCrate) -> Result<Option<String>> {
-        match &krate.resolved.source {
-            ResolvedSource::Forge { forge, .. } => Ok(Some(forge.repo_url())),
-            ResolvedSource::CratesIo
-            | ResolvedSource::Registry { .. }
-            | ResolvedSource::Git { .. }
-            | ResolvedSource::LocalDir { .. } => krate.repository_url(),
-        }
+        super::origin::crate_repository(krate)
     }
 
     /// Download a file from the given URL.
@@ -184,7 +182,7 @@ impl BinstallProvider {
         self.http_client.try_download(url)
     }
 
-    fn verify_checksum(&self, data: &[u8], url: &str) -> Result<()> {
+    fn verify_checksum(&self, download: &Download, url: &str) -> Result<()> {
         let checksum_url = format!("{}.sha256", url);
 
         let checksum_data = match self.try_download(&checksum_url)? {
@@ -204,14 +202,10 @@ impl BinstallProvider {
         self.reporter
             .report(|| PrebuiltBinaryMessage::verifying_checksum(expected_hash));
 
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        let actual_hash = crate::helpers::format_hex_lower(hasher.finalize());
-
-        if expected_hash != actual_hash {
+        if expected_hash != download.sha256 {
             return error::ChecksumMismatchSnafu {
                 expected: expected_hash.to_string(),
-                actual: actual_hash,
+                actual: download.sha256.clone(),
             }
             .fail();
         }
@@ -223,7 +217,12 @@ impl BinstallProvider {
 }
 
 impl Provider for BinstallProvider {
-    fn try_resolve(&self, krate: &DownloadedCrate, platform: &str) -> Result<Option<ResolvedBinary>> {
+    fn try_resolve(
+        &self,
+        krate: &DownloadedCrate,
+        platform: &str,
+        picker: &BinaryPicker,
+    ) -> Result<Option<ResolvedBinary>> {
         let resolved = &krate.resolved;
 
         let Some(meta) = Self::read_binstall_metadata(krate, platform)? else {
@@ -255,6 +254,7 @@ impl Provider for BinstallProvider {
 
         let mut last_url = String::new();
         let mut data = None;
+        let mut extracted = None;
 
         for suffix in suffixes {
             let ctx = TemplateContext {
@@ -268,12 +268,23 @@ impl Provider for BinstallProvider {
             };
 
             let url = render_template(pkg_url_template, &ctx);
+            self.origin.check_url(&url)?;
+
+            if let Some(extract_dir) = self.archives.lookup(&url, None) {
+                self.reporter.report(|| {
+                    PrebuiltBinaryMessage::reusing_archive(&url, &extract_dir, BinaryProvider::Binstall)
+                });
+                extracted = Some(extract_dir);
+                break;
+            }
 
             self.reporter
                 .report(|| PrebuiltBinaryMessage::downloading_binary(&url, BinaryProvider::Binstall));
 
-            if let Some(bytes) = self.try_download(&url)? {
-                data = Some(bytes);
+            if let Some(download) =
+                super::download_binary(&self.http_client, &self.reporter, &url, BinaryProvider::Binstall)?
+            {
+                data = Some(download);
                 last_url = url;
                 break;
             }
@@ -281,34 +292,6 @@ impl Provider for BinstallProvider {
             last_url = url;
         }
 
-        let Some(data) = data else {
-            self.reporter.report(|| {
-                PrebuiltBinaryMessage::provider_has_no_binary(
-                    BinaryProvider::Binstall,
-                    format!("download failed: {}", last_url),
-                )
-            });
-            return Ok(None);
-        };
-        let url = last_url;
-
-        if self.verify_checksums {
-            self.verify_checksum(&data, &url)?;
-        }
-
-        let temp_dir = tempfile::tempdir().with_context(|_| error::TempDirCreationSnafu {
-            parent: self.cache_dir.clone(),
-        })?;
-
-        let archive_path = temp_dir.path().join(format.canonical_filename());
-        std::fs::write(&archive_path, &data).with_context(|_| error::IoSnafu {
-            path: archive_path.clone(),
-        })?;
-
-        let binary_name = krate.default_binary_name()?;
-        let extract_dir = temp_dir.path().join("extracted");
-        let binary_path = super::extract_binary(&archive_path, format, &binary_name, &extract_dir)?;
-
         let final_dir = self
             .cache_dir
             .join("binaries")
@@ -317,33 +300,38 @@ impl Provider for BinstallProvider {
             .join(resolved.version.to_string())
             .join(platform);
 
-        std::fs::create_dir_all(&final_dir).with_context(|_| error::IoSnafu {
-            path: final_dir.clone(),
-        })?;
-
-        let final_path = final_dir.join(format!("{}{}", binary_name, std::env::consts::EXE_SUFFIX));
-        std::fs::copy(&binary_path, &final_path).with_context(|_| error::IoSnafu {
-            path: final_path.clone(),
-        })?;
-
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = std::fs::metadata(&final_path)
-                .with_context(|_| error::IoSnafu {
-                    path: final_path.clone(),
-                })?
-                .permissions();
-            perms.set_mode(0o755);
-            std::fs::set_permissions(&final_path, perms).with_context(|_| error::IoSnafu {
-                path: final_path.clone(),
-            })?;
-        }
+        let (final_path, artifact_sha256) = match (extracted, data) {
+            (Some(extract_dir), _) => (
+                picker.install_from_dir(&extract_dir, &final_dir)?,
+                ArchiveCache::digest(&extract_dir),
+            ),
+            (None, Some(data)) => {
+                let url = last_url;
+                if self.verify_checksums {
+                    self.verify_checksum(&data, &url)?;
+                }
+
+                (
+                    picker.install_from_asset(&self.archives, &url, &data, format, &final_dir)?,
+                    Some(data.sha256),
+                )
+            }
+            (None, None) => {
+                self.reporter.report(|| {
+                    PrebuiltBinaryMessage::provider_has_no_binary(
+                        BinaryProvider::Binstall,
+                        format!("download failed: {}", last_url),
+                    )
+                });
+                return Ok(None);
+            }
+        };
 
         Ok(Some(ResolvedBinary {
             krate: resolved.clone(),
             provider: BinaryProvider::Binstall,
             path: final_path,
+            artifact_sha256,
         }))
     }
 }
@@ -481,50 +469,6 @@ mod tests {
         );
     }
 
-    #[test]
-    fn archive_format_canonical_filename_consistency() {
-        assert_eq!(
-            archive_format_from_pkg_fmt(None).unwrap().canonical_filename(),
-            "archive.tar.gz"
-        );
-        assert_eq!(
-            archive_format_from_pkg_fmt(Some("tgz"))
-                .unwrap()
-                .canonical_filename(),
-            "archive.tar.gz"
-        );
-        assert_eq!(
-            archive_format_from_pkg_fmt(Some("txz"))
-                .unwrap()
-                .canonical_filename(),
-            "archive.tar.xz"
-        );
-        assert_eq!(
-            archive_format_from_pkg_fmt(Some("tzstd"))
-                .unwrap()
-                .canonical_filename(),
-            "archive.tar.zst"
-        );
-        assert_eq!(
-            archive_format_from_pkg_fmt(Some("tbz2"))
-                .unwrap()
-                .canonical_filename(),
-            "archive.tar.bz2"
-        );
-        assert_eq!(
-            archive_format_from_pkg_fmt(Some("zip"))
-                .unwrap()
-                .canonical_filename(),
-            "archive.zip"
-        );
-        assert_eq!(
-            archive_format_from_pkg_fmt(Some("bin"))
-                .unwrap()
-                .canonical_filename(),
-            if cfg!(windows) { "archive.exe" } else { "archive" }
-        );
-    }
-
     #[test]
     fn parse_binstall_metadata_from_toml() {
         let toml_content = r#"
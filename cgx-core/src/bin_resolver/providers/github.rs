@@ -1,4 +1,4 @@
-use super::Provider;
+use super::{ArchiveCache, BinaryPicker, Provider, ReleaseAssets, ReleaseListingCache};
 use crate::{
     Result,
     bin_resolver::ResolvedBinary,
@@ -7,19 +7,22 @@ use crate::{
     cratespec::Forge,
     downloader::DownloadedCrate,
     error,
-    http::{ACCEPT, AUTHORIZATION, Bytes, HeaderMap, HeaderValue, HttpClient},
+    http::{ACCEPT, AUTHORIZATION, Bytes, CONTENT_TYPE, Download, HeaderMap, HeaderValue, HttpClient},
     messages::PrebuiltBinaryMessage,
 };
 use serde::Deserialize;
-use sha2::{Digest, Sha256};
 use snafu::ResultExt;
 use std::path::PathBuf;
+use url::Url;
 
 pub(in crate::bin_resolver) struct GithubProvider {
     reporter: crate::messages::MessageReporter,
     cache_dir: PathBuf,
+    archives: ArchiveCache,
+    releases: ReleaseListingCache,
     verify_checksums: bool,
     http_client: HttpClient,
+    api_url: Url,
 }
 
 #[derive(Deserialize)]
@@ -33,18 +36,124 @@ struct ReleaseAsset {
     browser_download_url: String,
 }
 
+/// GraphQL query for the assets of the releases tagged `v{version}` and `{version}`, so that both
+/// tag variants are looked up in a single request.
+const RELEASE_ASSETS_QUERY: &str = r#"query($owner: String!, $repo: String!, $prefixedTag: String!, $tag: String!) {
+  repository(owner: $owner, name: $repo) {
+    prefixed: release(tagName: $prefixedTag) { ...assets }
+    plain: release(tagName: $tag) { ...assets }
+  }
+}
+
+fragment assets on Release {
+  releaseAssets(first: 100) {
+    nodes { name downloadUrl }
+    pageInfo { hasNextPage }
+  }
+}"#;
+
+#[derive(Deserialize)]
+struct GraphqlResponse {
+    data: Option<GraphqlData>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphqlData {
+    repository: Option<GraphqlRepository>,
+}
+
+#[derive(Deserialize)]
+struct GraphqlRepository {
+    prefixed: Option<GraphqlRelease>,
+    plain: Option<GraphqlRelease>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlRelease {
+    release_assets: GraphqlAssetConnection,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlAssetConnection {
+    nodes: Vec<GraphqlAsset>,
+    page_info: GraphqlPageInfo,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlPageInfo {
+    has_next_page: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlAsset {
+    name: String,
+    download_url: String,
+}
+
+#[derive(Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+impl GraphqlResponse {
+    /// The assets of the releases for `v{version}` and `{version}`, in that order, or `None` if the
+    /// response doesn't settle the question.
+    ///
+    /// A missing repository comes back as an error rather than as data, and can't be told apart
+    /// from one the token isn't allowed to see, so that isn't treated as definitive, and neither
+    /// is a release with more assets than fit in one page.
+    fn into_assets(self) -> Option<Vec<(String, String)>> {
+        if !self.errors.is_empty() {
+            let messages = self.errors.into_iter().map(|e| e.message).collect::<Vec<_>>();
+            tracing::debug!("GitHub GraphQL query returned errors: {}", messages.join("; "));
+            return None;
+        }
+
+        let repository = self.data?.repository?;
+        let mut assets = Vec::new();
+        for release in [repository.prefixed, repository.plain].into_iter().flatten() {
+            if release.release_assets.page_info.has_next_page {
+                tracing::debug!("Release has too many assets for one GraphQL page");
+                return None;
+            }
+
+            assets.extend(
+                release
+                    .release_assets
+                    .nodes
+                    .into_iter()
+                    .map(|a| (a.name, a.download_url)),
+            );
+        }
+
+        Some(assets)
+    }
+}
+
 impl GithubProvider {
     pub(in crate::bin_resolver) fn new(
         reporter: crate::messages::MessageReporter,
         cache_dir: PathBuf,
+        archives: ArchiveCache,
+        releases: ReleaseListingCache,
         verify_checksums: bool,
         http_client: HttpClient,
+        api_url: Url,
     ) -> Self {
         Self {
             reporter,
             cache_dir,
+            archives,
+            releases,
             verify_checksums,
             http_client,
+            api_url,
         }
     }
 
@@ -64,7 +173,8 @@ impl GithubProvider {
             | ResolvedSource::CratesIo
             | ResolvedSource::Registry { .. }
             | ResolvedSource::Git { .. }
-            | ResolvedSource::LocalDir { .. } => Ok(krate
+            | ResolvedSource::LocalDir { .. }
+            | ResolvedSource::Custom { .. } => Ok(krate
                 .repository_url()?
                 .filter(|u| u.starts_with("https://github.com/"))),
         }
@@ -85,62 +195,163 @@ impl GithubProvider {
 
     /// Determine the API base URL for a given repository URL.
     ///
-    /// For `github.com`, returns `https://api.github.com`.
+    /// For `github.com`, returns the configured `github_api` (normally `https://api.github.com`).
     /// For GitHub Enterprise (`github.example.com`), returns `https://github.example.com/api/v3`.
-    fn api_base(repo_url: &str) -> Option<String> {
+    fn api_base(repo_url: &str, github_api: &Url) -> Option<String> {
         let host = repo_url.strip_prefix("https://")?.split('/').next()?;
         if host == "github.com" {
-            Some("https://api.github.com".to_string())
+            Some(github_api.as_str().trim_end_matches('/').to_string())
         } else {
             Some(format!("https://{}/api/v3", host))
         }
     }
 
+    /// The GitHub token to authenticate API requests with, if there is one.
+    fn token() -> Option<String> {
+        std::env::var("GITHUB_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+    }
+
+    /// The GraphQL endpoint that goes with the REST API at `api_base`.
+    ///
+    /// On `github.com` that's `https://api.github.com/graphql`, while GitHub Enterprise serves
+    /// REST under `/api/v3` and GraphQL at `/api/graphql`.
+    fn graphql_url(api_base: &str) -> String {
+        match api_base.strip_suffix("/api/v3") {
+            Some(host) => format!("{}/api/graphql", host),
+            None => format!("{}/graphql", api_base),
+        }
+    }
+
+    /// List the assets of the release for `version` with a single GraphQL query, trying both the
+    /// `v{version}` and `{version}` tags at once.
+    ///
+    /// This saves the separate REST request per tag, which adds up for repositories with many
+    /// releases, but GitHub's GraphQL API requires authentication so it needs a `token`.  Returns
+    /// `None` if the query failed or wasn't conclusive, in which case the caller should fall back
+    /// to the REST API, which reports failures in more detail.
+    fn list_release_assets_graphql(
+        &self,
+        api_base: &str,
+        owner: &str,
+        repo: &str,
+        version: &str,
+        token: &str,
+    ) -> Option<Vec<(String, String)>> {
+        let url = Self::graphql_url(api_base);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("bearer {}", token)).ok()?,
+        );
+
+        let body = serde_json::json!({
+            "query": RELEASE_ASSETS_QUERY,
+            "variables": {
+                "owner": owner,
+                "repo": repo,
+                "prefixedTag": format!("v{}", version),
+                "tag": version,
+            },
+        })
+        .to_string();
+
+        let response = match self.http_client.post_with_headers(&url, &headers, &body) {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                tracing::debug!("GitHub GraphQL query to {} returned {}", url, response.status());
+                return None;
+            }
+            Err(e) => {
+                tracing::debug!("GitHub GraphQL query to {} failed: {}", url, e);
+                return None;
+            }
+        };
+
+        let text = response
+            .text()
+            .inspect_err(|e| tracing::debug!("Failed to read GitHub GraphQL response: {}", e))
+            .ok()?;
+        let response: GraphqlResponse = serde_json::from_str(&text)
+            .inspect_err(|e| tracing::debug!("Failed to parse GitHub GraphQL response: {}", e))
+            .ok()?;
+
+        response.into_assets()
+    }
+
     /// List release assets for a given tag from the GitHub Releases API.
     ///
-    /// Returns a vec of `(asset_name, download_url)` pairs.
-    /// On any failure (network, non-200, parse error), returns an empty vec.
+    /// Returns a vec of `(asset_name, download_url)` pairs, which is empty if there is no release
+    /// for the tag.  Any other failure is an error, so that e.g. an API request rejected for lack
+    /// of a token isn't mistaken for the release not existing.
     fn list_release_assets(
         &self,
         api_base: &str,
         owner: &str,
         repo: &str,
         tag: &str,
-    ) -> Vec<(String, String)> {
+    ) -> Result<Vec<(String, String)>> {
         let url = format!("{}/repos/{}/{}/releases/tags/{}", api_base, owner, repo, tag);
 
         let mut headers = HeaderMap::new();
         headers.insert(ACCEPT, HeaderValue::from_static("application/vnd.github+json"));
-        if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+        if let Some(token) = Self::token() {
             if let Ok(auth_value) = HeaderValue::from_str(&format!("token {}", token)) {
                 headers.insert(AUTHORIZATION, auth_value);
             }
         }
 
-        let response = match self.http_client.get_with_headers(&url, &headers) {
-            Ok(r) => r,
-            Err(_) => return Vec::new(),
-        };
+        let response = self.http_client.get_with_headers(&url, &headers)?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
 
         if !response.status().is_success() {
-            return Vec::new();
+            return error::HttpStatusSnafu {
+                url,
+                status: response.status().as_u16(),
+            }
+            .fail();
         }
 
-        let text = match response.text() {
-            Ok(t) => t,
-            Err(_) => return Vec::new(),
-        };
+        let text = response
+            .text()
+            .with_context(|_| error::HttpRequestSnafu { url: url.clone() })?;
 
-        let release: ReleaseResponse = match serde_json::from_str(&text) {
-            Ok(r) => r,
-            Err(_) => return Vec::new(),
-        };
+        let release: ReleaseResponse =
+            serde_json::from_str(&text).map_err(|e| error::Error::GithubApiError { source: Box::new(e) })?;
 
-        release
+        Ok(release
             .assets
             .into_iter()
             .map(|a| (a.name, a.browser_download_url))
-            .collect()
+            .collect())
+    }
+
+    /// List the assets of the releases for `version`, which is empty if there is no release for
+    /// it.
+    ///
+    /// Both the `v{version}` and `{version}` tags are listed, in that order, since a repository
+    /// that has both may only have binaries in one of them.  With a token both can be looked up
+    /// in one GraphQL query.  Otherwise, or if that doesn't work out, they're listed in turn with
+    /// the REST API.
+    fn list_release(&self, api_base: &str, owner: &str, repo: &str, version: &str) -> Result<ReleaseAssets> {
+        let graphql_assets = Self::token()
+            .and_then(|token| self.list_release_assets_graphql(api_base, owner, repo, version, &token));
+        if let Some(assets) = graphql_assets {
+            return Ok(assets);
+        }
+
+        let tags = [format!("v{}", version), version.to_string()];
+        let mut assets = Vec::new();
+        for tag in &tags {
+            assets.extend(self.list_release_assets(api_base, owner, repo, tag)?);
+        }
+        Ok(assets)
     }
 
     /// Download a file from the given URL.
@@ -151,7 +362,7 @@ impl GithubProvider {
         self.http_client.try_download(url)
     }
 
-    fn verify_checksum(&self, data: &[u8], url: &str) -> Result<()> {
+    fn verify_checksum(&self, download: &Download, url: &str) -> Result<()> {
         let checksum_url = format!("{}.sha256", url);
 
         let checksum_data = match self.try_download(&checksum_url)? {
@@ -171,14 +382,10 @@ impl GithubProvider {
         self.reporter
             .report(|| PrebuiltBinaryMessage::verifying_checksum(expected_hash));
 
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        let actual_hash = crate::helpers::format_hex_lower(hasher.finalize());
-
-        if expected_hash != actual_hash {
+        if expected_hash != download.sha256 {
             return error::ChecksumMismatchSnafu {
                 expected: expected_hash.to_string(),
-                actual: actual_hash,
+                actual: download.sha256.clone(),
             }
             .fail();
         }
@@ -190,7 +397,12 @@ impl GithubProvider {
 }
 
 impl Provider for GithubProvider {
-    fn try_resolve(&self, krate: &DownloadedCrate, platform: &str) -> Result<Option<ResolvedBinary>> {
+    fn try_resolve(
+        &self,
+        krate: &DownloadedCrate,
+        platform: &str,
+        picker: &BinaryPicker,
+    ) -> Result<Option<ResolvedBinary>> {
         let repo_url = if let Some(url) = Self::get_repo_url(krate)? {
             url
         } else {
@@ -213,7 +425,7 @@ impl Provider for GithubProvider {
             return Ok(None);
         };
 
-        let Some(api_base) = Self::api_base(&repo_url) else {
+        let Some(api_base) = Self::api_base(&repo_url, &self.api_url) else {
             self.reporter.report(|| {
                 PrebuiltBinaryMessage::provider_has_no_binary(
                     BinaryProvider::GithubReleases,
@@ -225,14 +437,17 @@ impl Provider for GithubProvider {
 
         let version = krate.resolved.version.to_string();
 
-        // Try both v{version} and {version} tags; stop at the first that returns assets.
-        let tags = [format!("v{}", version), version.clone()];
-        let mut assets = Vec::new();
-        for tag in &tags {
-            assets = self.list_release_assets(&api_base, owner, repo, tag);
-            if !assets.is_empty() {
-                break;
-            }
+        let (assets, kept) = self.releases.get_or_list(&repo_url, &version, || {
+            self.list_release(&api_base, owner, repo, &version)
+        })?;
+        if kept {
+            self.reporter.report(|| {
+                PrebuiltBinaryMessage::reusing_release_listing(
+                    &repo_url,
+                    &version,
+                    BinaryProvider::GithubReleases,
+                )
+            });
         }
 
         if assets.is_empty() {
@@ -247,10 +462,12 @@ impl Provider for GithubProvider {
 
         let candidates = super::generate_candidate_filenames(&krate.resolved.name, &version, platform);
 
-        let asset_map: std::collections::HashMap<&str, &str> = assets
-            .iter()
-            .map(|(name, url)| (name.as_str(), url.as_str()))
-            .collect();
+        // An asset named the same in both releases is taken from the `v`-prefixed one, which is
+        // listed first
+        let mut asset_map = std::collections::HashMap::new();
+        for (name, url) in &assets {
+            asset_map.entry(name.as_str()).or_insert(url.as_str());
+        }
 
         let matched = candidates
             .iter()
@@ -259,48 +476,18 @@ impl Provider for GithubProvider {
         let (candidate, download_url) = if let Some(m) = matched {
             m
         } else {
+            let reason = super::msi_only_reason(
+                &krate.resolved.name,
+                platform,
+                assets.iter().map(|(name, _)| name.as_str()),
+            )
+            .unwrap_or_else(|| "no matching asset found in release".to_string());
             self.reporter.report(|| {
-                PrebuiltBinaryMessage::provider_has_no_binary(
-                    BinaryProvider::GithubReleases,
-                    "no matching asset found in release",
-                )
-            });
-            return Ok(None);
-        };
-
-        self.reporter.report(|| {
-            PrebuiltBinaryMessage::downloading_binary(download_url, BinaryProvider::GithubReleases)
-        });
-
-        let data = if let Some(data) = self.try_download(download_url)? {
-            data
-        } else {
-            self.reporter.report(|| {
-                PrebuiltBinaryMessage::provider_has_no_binary(
-                    BinaryProvider::GithubReleases,
-                    format!("failed to download asset: {}", download_url),
-                )
+                PrebuiltBinaryMessage::provider_has_no_binary(BinaryProvider::GithubReleases, reason)
             });
             return Ok(None);
         };
 
-        if self.verify_checksums {
-            self.verify_checksum(&data, download_url)?;
-        }
-
-        let temp_dir = tempfile::tempdir().with_context(|_| error::TempDirCreationSnafu {
-            parent: self.cache_dir.clone(),
-        })?;
-
-        let archive_path = temp_dir.path().join(candidate.format.canonical_filename());
-        std::fs::write(&archive_path, &data).with_context(|_| error::IoSnafu {
-            path: archive_path.clone(),
-        })?;
-
-        let binary_name = krate.default_binary_name()?;
-        let extract_dir = temp_dir.path().join("extracted");
-        let binary_path = super::extract_binary(&archive_path, candidate.format, &binary_name, &extract_dir)?;
-
         let final_dir = self
             .cache_dir
             .join("binaries")
@@ -309,33 +496,62 @@ impl Provider for GithubProvider {
             .join(krate.resolved.version.to_string())
             .join(platform);
 
-        std::fs::create_dir_all(&final_dir).with_context(|_| error::IoSnafu {
-            path: final_dir.clone(),
-        })?;
-
-        let final_path = final_dir.join(format!("{}{}", binary_name, std::env::consts::EXE_SUFFIX));
-        std::fs::copy(&binary_path, &final_path).with_context(|_| error::IoSnafu {
-            path: final_path.clone(),
-        })?;
-
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = std::fs::metadata(&final_path)
-                .with_context(|_| error::IoSnafu {
-                    path: final_path.clone(),
-                })?
-                .permissions();
-            perms.set_mode(0o755);
-            std::fs::set_permissions(&final_path, perms).with_context(|_| error::IoSnafu {
-                path: final_path.clone(),
-            })?;
-        }
+        let (final_path, artifact_sha256) =
+            if let Some(extract_dir) = self.archives.lookup(download_url, None) {
+                self.reporter.report(|| {
+                    PrebuiltBinaryMessage::reusing_archive(
+                        download_url,
+                        &extract_dir,
+                        BinaryProvider::GithubReleases,
+                    )
+                });
+                (
+                    picker.install_from_dir(&extract_dir, &final_dir)?,
+                    ArchiveCache::digest(&extract_dir),
+                )
+            } else {
+                self.reporter.report(|| {
+                    PrebuiltBinaryMessage::downloading_binary(download_url, BinaryProvider::GithubReleases)
+                });
+
+                let data = if let Some(data) = super::download_binary(
+                    &self.http_client,
+                    &self.reporter,
+                    download_url,
+                    BinaryProvider::GithubReleases,
+                )? {
+                    data
+                } else {
+                    self.reporter.report(|| {
+                        PrebuiltBinaryMessage::provider_has_no_binary(
+                            BinaryProvider::GithubReleases,
+                            format!("failed to download asset: {}", download_url),
+                        )
+                    });
+                    return Ok(None);
+                };
+
+                if self.verify_checksums {
+                    self.verify_checksum(&data, download_url)?;
+                }
+
+                (
+                    picker.install_from_asset(
+                        &self.archives,
+                        download_url,
+                        &data,
+                        candidate.format,
+                        &final_dir,
+                    )?,
+                    Some(data.sha256),
+                )
+            };
 
         Ok(Some(ResolvedBinary {
             krate: krate.resolved.clone(),
             provider: BinaryProvider::GithubReleases,
             path: final_path,
+            artifact_sha256,
         }))
     }
 }
@@ -343,10 +559,9 @@ impl Provider for GithubProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{crate_resolver::ResolvedSource, cratespec::Forge};
+    use crate::{config::EndpointsConfig, crate_resolver::ResolvedSource, cratespec::Forge};
     use semver::Version;
     use std::fs;
-    use url::Url;
 
     #[test]
     fn test_parse_owner_repo_standard() {
@@ -372,19 +587,149 @@ mod tests {
     #[test]
     fn test_api_base_github_com() {
         assert_eq!(
-            GithubProvider::api_base("https://github.com/owner/repo"),
+            GithubProvider::api_base(
+                "https://github.com/owner/repo",
+                &EndpointsConfig::default().github_api
+            ),
             Some("https://api.github.com".to_string())
         );
     }
 
+    #[test]
+    fn test_api_base_github_com_override() {
+        let api = Url::parse("http://127.0.0.1:8080/").unwrap();
+        assert_eq!(
+            GithubProvider::api_base("https://github.com/owner/repo", &api),
+            Some("http://127.0.0.1:8080".to_string())
+        );
+    }
+
     #[test]
     fn test_api_base_enterprise() {
         assert_eq!(
-            GithubProvider::api_base("https://github.enterprise.com/owner/repo"),
+            GithubProvider::api_base(
+                "https://github.enterprise.com/owner/repo",
+                &EndpointsConfig::default().github_api
+            ),
             Some("https://github.enterprise.com/api/v3".to_string())
         );
     }
 
+    #[test]
+    fn test_graphql_url() {
+        assert_eq!(
+            GithubProvider::graphql_url("https://api.github.com"),
+            "https://api.github.com/graphql"
+        );
+        assert_eq!(
+            GithubProvider::graphql_url("https://github.enterprise.com/api/v3"),
+            "https://github.enterprise.com/api/graphql"
+        );
+    }
+
+    fn graphql_response(json: serde_json::Value) -> GraphqlResponse {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn release(assets: &[&str], has_next_page: bool) -> serde_json::Value {
+        serde_json::json!({
+            "releaseAssets": {
+                "nodes": assets
+                    .iter()
+                    .map(|name| serde_json::json!({
+                        "name": name,
+                        "downloadUrl": format!("https://github.com/o/r/releases/download/v1.0.0/{name}"),
+                    }))
+                    .collect::<Vec<_>>(),
+                "pageInfo": { "hasNextPage": has_next_page },
+            }
+        })
+    }
+
+    #[test]
+    fn test_graphql_lists_v_prefixed_release_first() {
+        let response = graphql_response(serde_json::json!({
+            "data": { "repository": {
+                "prefixed": release(&["tool-x86_64-unknown-linux-gnu.tar.gz"], false),
+                "plain": release(&["other.tar.gz"], false),
+            }}
+        }));
+
+        let assets = response.into_assets().unwrap();
+        assert_eq!(
+            assets.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(),
+            ["tool-x86_64-unknown-linux-gnu.tar.gz", "other.tar.gz"]
+        );
+    }
+
+    #[test]
+    fn test_graphql_falls_back_to_plain_tag() {
+        let response = graphql_response(serde_json::json!({
+            "data": { "repository": { "prefixed": null, "plain": release(&["tool.zip"], false) } }
+        }));
+
+        assert_eq!(response.into_assets().unwrap()[0].0, "tool.zip");
+    }
+
+    #[test]
+    fn test_graphql_without_release_has_no_assets() {
+        let response = graphql_response(serde_json::json!({
+            "data": { "repository": { "prefixed": null, "plain": null } }
+        }));
+
+        assert_eq!(response.into_assets(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_graphql_inconclusive_responses() {
+        let missing_repo = graphql_response(serde_json::json!({
+            "data": { "repository": null },
+            "errors": [{ "message": "Could not resolve to a Repository with the name 'o/r'." }]
+        }));
+        assert_eq!(missing_repo.into_assets(), None);
+
+        let paged = graphql_response(serde_json::json!({
+            "data": { "repository": { "prefixed": release(&["a.tar.gz"], true), "plain": null } }
+        }));
+        assert_eq!(paged.into_assets(), None);
+    }
+
+    #[test]
+    fn test_graphql_query_is_one_request() {
+        let server = httpmock::MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(httpmock::Method::POST)
+                .path("/graphql")
+                .header("authorization", "bearer secret")
+                .body_includes(r#""prefixedTag":"v1.0.0""#);
+            then.status(200).body(
+                serde_json::json!({
+                    "data": { "repository": {
+                        "prefixed": release(&["tool.tar.gz"], false),
+                        "plain": null,
+                    }}
+                })
+                .to_string(),
+            );
+        });
+
+        let provider = GithubProvider::new(
+            crate::messages::MessageReporter::null(),
+            PathBuf::from("/nonexistent"),
+            ArchiveCache::new(&crate::config::Config::default()),
+            ReleaseListingCache::new(&crate::config::Config::default()),
+            false,
+            HttpClient::new(&crate::config::HttpConfig::default()).unwrap(),
+            Url::parse(&server.base_url()).unwrap(),
+        );
+        let assets = provider
+            .list_release_assets_graphql(&server.base_url(), "o", "r", "1.0.0", "secret")
+            .unwrap();
+
+        assert_eq!(assets.len(), 1);
+        mock.assert_calls(1);
+    }
+
     #[test]
     fn test_get_repo_url_github_forge() {
         let krate = DownloadedCrate {
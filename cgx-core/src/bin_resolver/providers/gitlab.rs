@@ -1,4 +1,4 @@
-use super::{ArchiveFormat, CandidateFilename, Provider};
+use super::{ArchiveCache, ArchiveFormat, BinaryPicker, CandidateFilename, OriginPolicy, Provider};
 use crate::{
     Result,
     bin_resolver::ResolvedBinary,
@@ -7,32 +7,80 @@ use crate::{
     cratespec::Forge,
     downloader::DownloadedCrate,
     error,
-    http::{Bytes, HttpClient},
+    http::{Bytes, Download, HttpClient},
     messages::PrebuiltBinaryMessage,
 };
-use sha2::{Digest, Sha256};
+use serde::{Deserialize, de::DeserializeOwned};
 use snafu::ResultExt;
 use std::path::PathBuf;
+use url::Url;
 
 pub(in crate::bin_resolver) struct GitlabProvider {
     reporter: crate::messages::MessageReporter,
     cache_dir: PathBuf,
+    archives: ArchiveCache,
     verify_checksums: bool,
     http_client: HttpClient,
+    gitlab_url: Url,
+    origin: OriginPolicy,
+}
+
+/// A binary found on GitLab, either as a release asset or in the generic package registry.
+struct FoundAsset {
+    url: String,
+    format: ArchiveFormat,
+    /// The SHA256 the package registry reports for the file.  Release assets have no such
+    /// metadata, and are checked against a `.sha256` file next to them instead.
+    sha256: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Project {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    id: u64,
+    name: String,
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct PackageFile {
+    file_name: String,
+    #[serde(default)]
+    file_sha256: Option<String>,
 }
 
 impl GitlabProvider {
     pub(in crate::bin_resolver) fn new(
         reporter: crate::messages::MessageReporter,
         cache_dir: PathBuf,
+        archives: ArchiveCache,
         verify_checksums: bool,
         http_client: HttpClient,
+        gitlab_url: Url,
+        origin: OriginPolicy,
     ) -> Self {
         Self {
             reporter,
             cache_dir,
+            archives,
             verify_checksums,
             http_client,
+            gitlab_url,
+            origin,
+        }
+    }
+
+    /// Point a `gitlab.com` repository URL at the configured GitLab base URL.
+    ///
+    /// Repositories on other (self-hosted) GitLab instances are left untouched.
+    fn rehost(repo_url: &str, gitlab_url: &Url) -> String {
+        match repo_url.strip_prefix("https://gitlab.com/") {
+            Some(path) => format!("{}/{}", gitlab_url.as_str().trim_end_matches('/'), path),
+            None => repo_url.to_string(),
         }
     }
 
@@ -52,7 +100,8 @@ impl GitlabProvider {
             | ResolvedSource::CratesIo
             | ResolvedSource::Registry { .. }
             | ResolvedSource::Git { .. }
-            | ResolvedSource::LocalDir { .. } => Ok(krate
+            | ResolvedSource::LocalDir { .. }
+            | ResolvedSource::Custom { .. } => Ok(krate
                 .repository_url()?
                 .filter(|u| u.starts_with("https://gitlab.com/"))),
         }
@@ -83,14 +132,138 @@ impl GitlabProvider {
         urls
     }
 
+    /// Split a GitLab repository URL into the base URL of its GitLab instance and the project's
+    /// path within it, e.g. `https://gitlab.com/group/subgroup/repo` into `https://gitlab.com`
+    /// and `group/subgroup/repo`.
+    fn split_project_url(repo_url: &str) -> Option<(String, String)> {
+        let url = Url::parse(repo_url).ok()?;
+        let host = url.host_str()?;
+        let base = match url.port() {
+            Some(port) => format!("{}://{}:{}", url.scheme(), host, port),
+            None => format!("{}://{}", url.scheme(), host),
+        };
+
+        let path = url.path().trim_matches('/').trim_end_matches(".git");
+        if path.is_empty() {
+            return None;
+        }
+        Some((base, path.to_string()))
+    }
+
+    /// GET a GitLab API endpoint and parse its JSON response.
+    ///
+    /// Returns `Ok(None)` if the API says there's nothing there or won't say, which for anonymous
+    /// requests is how GitLab answers for private projects and projects with the package registry
+    /// turned off.
+    fn api_get<T: DeserializeOwned>(&self, url: &str) -> Result<Option<T>> {
+        let response = self.http_client.get(url)?;
+        let status = response.status();
+
+        if status == reqwest::StatusCode::NOT_FOUND
+            || status == reqwest::StatusCode::UNAUTHORIZED
+            || status == reqwest::StatusCode::FORBIDDEN
+        {
+            tracing::debug!("GitLab API request to {} returned {}", url, status);
+            return Ok(None);
+        }
+
+        if !status.is_success() {
+            return error::HttpStatusSnafu {
+                url: url.to_string(),
+                status: status.as_u16(),
+            }
+            .fail();
+        }
+
+        let text = response
+            .text()
+            .with_context(|_| error::HttpRequestSnafu { url: url.to_string() })?;
+        serde_json::from_str(&text).context(error::JsonSnafu).map(Some)
+    }
+
+    /// Look for a binary in the generic package registry of the project at `repo_url`.
+    ///
+    /// The package must be named after the crate and have its version, with or without a `v`
+    /// prefix, and one of its files must be named like the release assets the other providers
+    /// look for.  Besides the file's URL this returns the SHA256 the registry has for it.
+    fn find_generic_package(
+        &self,
+        repo_url: &str,
+        name: &str,
+        version: &str,
+        platform: &str,
+    ) -> Result<Option<FoundAsset>> {
+        let Some((base, project_path)) = Self::split_project_url(repo_url) else {
+            return Ok(None);
+        };
+        let api = format!("{}/api/v4", base);
+
+        let encoded_path = url::form_urlencoded::byte_serialize(project_path.as_bytes()).collect::<String>();
+        let Some(project) = self.api_get::<Project>(&format!("{}/projects/{}", api, encoded_path))? else {
+            return Ok(None);
+        };
+        let project_api = format!("{}/projects/{}", api, project.id);
+
+        // The name filter is a substring match, so the exact name still has to be checked
+        let encoded_name = url::form_urlencoded::byte_serialize(name.as_bytes()).collect::<String>();
+        let packages = self
+            .api_get::<Vec<Package>>(&format!(
+                "{}/packages?package_type=generic&package_name={}&per_page=100",
+                project_api, encoded_name
+            ))?
+            .unwrap_or_default();
+        let tags = [version.to_string(), format!("v{}", version)];
+        let Some(package) = packages
+            .into_iter()
+            .find(|package| package.name == name && tags.contains(&package.version))
+        else {
+            return Ok(None);
+        };
+
+        let files = self
+            .api_get::<Vec<PackageFile>>(&format!(
+                "{}/packages/{}/package_files?per_page=100",
+                project_api, package.id
+            ))?
+            .unwrap_or_default();
+
+        let candidates = super::generate_candidate_filenames(name, version, platform);
+        let Some((candidate, file)) = candidates.iter().find_map(|candidate| {
+            files
+                .iter()
+                .find(|file| file.file_name == candidate.filename)
+                .map(|file| (candidate, file))
+        }) else {
+            return Ok(None);
+        };
+
+        Ok(Some(FoundAsset {
+            url: format!(
+                "{}/packages/generic/{}/{}/{}",
+                project_api, package.name, package.version, file.file_name
+            ),
+            format: candidate.format,
+            sha256: file.file_sha256.clone(),
+        }))
+    }
+
     /// Probe a URL with a HEAD request to check if the asset exists.
     ///
     /// Returns `Ok(true)` if the asset exists (200 response), `Ok(false)` if it doesn't
-    /// (404 or other non-success), or `Err` if a connection/timeout error occurred.
-    /// The error case is used by the caller to bail early when the server is unreachable.
+    /// (404 or other non-success), or `Err` if a connection/timeout error occurred or the server
+    /// demanded authentication.  The caller bails on errors, since they will be the same for all
+    /// the other candidate URLs.
     fn head_probe(&self, url: &str) -> Result<bool> {
         let response = self.http_client.head(url)?;
-        Ok(response.status().is_success())
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            return error::HttpStatusSnafu {
+                url: url.to_string(),
+                status: status.as_u16(),
+            }
+            .fail();
+        }
+        Ok(status.is_success())
     }
 
     /// Download a file from the given URL.
@@ -101,7 +274,7 @@ impl GitlabProvider {
         self.http_client.try_download(url)
     }
 
-    fn verify_checksum(&self, data: &[u8], url: &str) -> Result<()> {
+    fn verify_checksum(&self, download: &Download, url: &str) -> Result<()> {
         let checksum_url = format!("{}.sha256", url);
 
         let checksum_data = match self.try_download(&checksum_url)? {
@@ -118,17 +291,18 @@ impl GitlabProvider {
             .build()
         })?;
 
+        self.verify_sha256(download, expected_hash)
+    }
+
+    /// Check that `download` has the SHA256 `expected_hash`.
+    fn verify_sha256(&self, download: &Download, expected_hash: &str) -> Result<()> {
         self.reporter
             .report(|| PrebuiltBinaryMessage::verifying_checksum(expected_hash));
 
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        let actual_hash = crate::helpers::format_hex_lower(hasher.finalize());
-
-        if expected_hash != actual_hash {
+        if expected_hash != download.sha256 {
             return error::ChecksumMismatchSnafu {
                 expected: expected_hash.to_string(),
-                actual: actual_hash,
+                actual: download.sha256.clone(),
             }
             .fail();
         }
@@ -140,7 +314,12 @@ impl GitlabProvider {
 }
 
 impl Provider for GitlabProvider {
-    fn try_resolve(&self, krate: &DownloadedCrate, platform: &str) -> Result<Option<ResolvedBinary>> {
+    fn try_resolve(
+        &self,
+        krate: &DownloadedCrate,
+        platform: &str,
+        picker: &BinaryPicker,
+    ) -> Result<Option<ResolvedBinary>> {
         let repo_url = if let Some(url) = Self::get_repo_url(krate)? {
             url
         } else {
@@ -153,78 +332,54 @@ impl Provider for GitlabProvider {
             return Ok(None);
         };
 
-        let urls = Self::generate_urls(
-            &repo_url,
-            &krate.resolved.name,
-            &krate.resolved.version.to_string(),
-            platform,
-        );
+        let repo_url = Self::rehost(&repo_url, &self.gitlab_url);
+        let version = krate.resolved.version.to_string();
+        let urls = Self::generate_urls(&repo_url, &krate.resolved.name, &version, platform);
 
         // Probe sequentially with HEAD requests; stop at the first 200.
-        // If we hit a connection/timeout error, bail immediately rather than continuing
-        // to probe all ~160 candidate URLs against a dead server.
+        // If we hit a connection/timeout error or are told to authenticate, bail immediately
+        // rather than continuing to probe all ~160 candidate URLs against a server that will
+        // answer them all the same way.
         let mut found = None;
         for (url, format) in &urls {
             match self.head_probe(url) {
                 Ok(true) => {
-                    found = Some((url.clone(), *format));
+                    found = Some(FoundAsset {
+                        url: url.clone(),
+                        format: *format,
+                        sha256: None,
+                    });
                     break;
                 }
-                Err(e) if HttpClient::is_connection_error(&e) => {
-                    tracing::debug!("GitLab HEAD probe failed with connection error, bailing: {:?}", e);
-                    self.reporter.report(|| {
-                        PrebuiltBinaryMessage::provider_has_no_binary(
-                            BinaryProvider::GitlabReleases,
-                            "server unreachable",
-                        )
-                    });
-                    return Ok(None);
+                Err(e)
+                    if HttpClient::is_connection_error(&e)
+                        || matches!(e, error::Error::HttpStatus { .. }) =>
+                {
+                    tracing::debug!("GitLab HEAD probe failed, bailing: {:?}", e);
+                    return Err(e);
                 }
                 Ok(false) | Err(_) => continue,
             }
         }
-        let Some((url, format)) = found else {
-            self.reporter.report(|| {
-                PrebuiltBinaryMessage::provider_has_no_binary(
-                    BinaryProvider::GitlabReleases,
-                    "no matching release found",
-                )
-            });
-            return Ok(None);
-        };
 
-        self.reporter
-            .report(|| PrebuiltBinaryMessage::downloading_binary(&url, BinaryProvider::GitlabReleases));
+        // Projects that don't attach binaries to releases may publish them as generic packages
+        if found.is_none() {
+            found = self.find_generic_package(&repo_url, &krate.resolved.name, &version, platform)?;
+            if let Some(asset) = &found {
+                self.origin.check_generic_package(&asset.url)?;
+            }
+        }
 
-        let data = if let Some(data) = self.try_download(&url)? {
-            data
-        } else {
+        let Some(FoundAsset { url, format, sha256 }) = found else {
             self.reporter.report(|| {
                 PrebuiltBinaryMessage::provider_has_no_binary(
                     BinaryProvider::GitlabReleases,
-                    format!("failed to download asset: {}", url),
+                    "no matching release or generic package found",
                 )
             });
             return Ok(None);
         };
 
-        if self.verify_checksums {
-            self.verify_checksum(&data, &url)?;
-        }
-
-        let temp_dir = tempfile::tempdir().with_context(|_| error::TempDirCreationSnafu {
-            parent: self.cache_dir.clone(),
-        })?;
-
-        let archive_path = temp_dir.path().join(format.canonical_filename());
-        std::fs::write(&archive_path, &data).with_context(|_| error::IoSnafu {
-            path: archive_path.clone(),
-        })?;
-
-        let binary_name = krate.default_binary_name()?;
-        let extract_dir = temp_dir.path().join("extracted");
-        let binary_path = super::extract_binary(&archive_path, format, &binary_name, &extract_dir)?;
-
         let final_dir = self
             .cache_dir
             .join("binaries")
@@ -233,33 +388,55 @@ impl Provider for GitlabProvider {
             .join(krate.resolved.version.to_string())
             .join(platform);
 
-        std::fs::create_dir_all(&final_dir).with_context(|_| error::IoSnafu {
-            path: final_dir.clone(),
-        })?;
-
-        let final_path = final_dir.join(format!("{}{}", binary_name, std::env::consts::EXE_SUFFIX));
-        std::fs::copy(&binary_path, &final_path).with_context(|_| error::IoSnafu {
-            path: final_path.clone(),
-        })?;
-
-        #[cfg(unix)]
+        let (final_path, artifact_sha256) = if let Some(extract_dir) =
+            self.archives.lookup(&url, sha256.as_deref())
         {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = std::fs::metadata(&final_path)
-                .with_context(|_| error::IoSnafu {
-                    path: final_path.clone(),
-                })?
-                .permissions();
-            perms.set_mode(0o755);
-            std::fs::set_permissions(&final_path, perms).with_context(|_| error::IoSnafu {
-                path: final_path.clone(),
-            })?;
-        }
+            self.reporter.report(|| {
+                PrebuiltBinaryMessage::reusing_archive(&url, &extract_dir, BinaryProvider::GitlabReleases)
+            });
+            (
+                picker.install_from_dir(&extract_dir, &final_dir)?,
+                ArchiveCache::digest(&extract_dir),
+            )
+        } else {
+            self.reporter
+                .report(|| PrebuiltBinaryMessage::downloading_binary(&url, BinaryProvider::GitlabReleases));
+
+            let data = if let Some(data) = super::download_binary(
+                &self.http_client,
+                &self.reporter,
+                &url,
+                BinaryProvider::GitlabReleases,
+            )? {
+                data
+            } else {
+                self.reporter.report(|| {
+                    PrebuiltBinaryMessage::provider_has_no_binary(
+                        BinaryProvider::GitlabReleases,
+                        format!("failed to download asset: {}", url),
+                    )
+                });
+                return Ok(None);
+            };
+
+            if self.verify_checksums {
+                match &sha256 {
+                    Some(expected_hash) => self.verify_sha256(&data, expected_hash)?,
+                    None => self.verify_checksum(&data, &url)?,
+                }
+            }
+
+            (
+                picker.install_from_asset(&self.archives, &url, &data, format, &final_dir)?,
+                Some(data.sha256),
+            )
+        };
 
         Ok(Some(ResolvedBinary {
             krate: krate.resolved.clone(),
             provider: BinaryProvider::GitlabReleases,
             path: final_path,
+            artifact_sha256,
         }))
     }
 }
@@ -272,6 +449,41 @@ mod tests {
     use std::fs;
     use url::Url;
 
+    #[test]
+    fn test_rehost() {
+        let default = crate::config::EndpointsConfig::default().gitlab;
+        assert_eq!(
+            GitlabProvider::rehost("https://gitlab.com/owner/repo", &default),
+            "https://gitlab.com/owner/repo"
+        );
+
+        let mock = Url::parse("http://127.0.0.1:8080").unwrap();
+        assert_eq!(
+            GitlabProvider::rehost("https://gitlab.com/owner/repo", &mock),
+            "http://127.0.0.1:8080/owner/repo"
+        );
+        assert_eq!(
+            GitlabProvider::rehost("https://gitlab.example.com/owner/repo", &mock),
+            "https://gitlab.example.com/owner/repo"
+        );
+    }
+
+    #[test]
+    fn test_split_project_url() {
+        assert_eq!(
+            GitlabProvider::split_project_url("https://gitlab.com/group/subgroup/repo"),
+            Some((
+                "https://gitlab.com".to_string(),
+                "group/subgroup/repo".to_string()
+            ))
+        );
+        assert_eq!(
+            GitlabProvider::split_project_url("http://127.0.0.1:8080/owner/repo.git"),
+            Some(("http://127.0.0.1:8080".to_string(), "owner/repo".to_string()))
+        );
+        assert_eq!(GitlabProvider::split_project_url("https://gitlab.com/"), None);
+    }
+
     #[test]
     fn test_url_generation_includes_version_patterns() {
         let urls = GitlabProvider::generate_urls(
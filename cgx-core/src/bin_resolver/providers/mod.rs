@@ -2,15 +2,26 @@ mod archive;
 mod binstall;
 mod github;
 mod gitlab;
+mod origin;
 mod quickinstall;
+mod releases;
 
-pub(super) use archive::{ArchiveFormat, extract_binary};
+pub(super) use archive::{ArchiveCache, ArchiveFormat, BinaryPicker};
 pub(super) use binstall::BinstallProvider;
 pub(super) use github::GithubProvider;
 pub(super) use gitlab::GitlabProvider;
+pub(super) use origin::OriginPolicy;
 pub(super) use quickinstall::QuickinstallProvider;
+pub(super) use releases::{ReleaseAssets, ReleaseListingCache};
 
-use crate::{Result, bin_resolver::ResolvedBinary, downloader::DownloadedCrate};
+use crate::{
+    Result,
+    bin_resolver::ResolvedBinary,
+    config::BinaryProvider,
+    downloader::DownloadedCrate,
+    http::{Download, HttpClient},
+    messages::{MessageReporter, PrebuiltBinaryMessage},
+};
 
 /// Trait for providers that can resolve pre-built binaries.
 pub(super) trait Provider {
@@ -18,11 +29,29 @@ pub(super) trait Provider {
     ///
     /// All providers receive the full [`DownloadedCrate`], which includes both the resolved
     /// metadata and the path to the downloaded crate source. Providers that only need the
-    /// metadata (like heuristic URL probers) can access it via `krate.resolved`.
+    /// metadata (like heuristic URL probers) can access it via `krate.resolved`.  The `picker`
+    /// says which executables to take from the release archive.
     ///
     /// Returns `Ok(Some(binary))` if found, `Ok(None)` if not available from this provider,
     /// or `Err` if an error occurred during the attempt.
-    fn try_resolve(&self, krate: &DownloadedCrate, platform: &str) -> Result<Option<ResolvedBinary>>;
+    fn try_resolve(
+        &self,
+        krate: &DownloadedCrate,
+        platform: &str,
+        picker: &BinaryPicker,
+    ) -> Result<Option<ResolvedBinary>>;
+}
+
+/// Download the binary or archive at `url` from `provider`, reporting its progress as it goes.
+pub(super) fn download_binary(
+    http_client: &HttpClient,
+    reporter: &MessageReporter,
+    url: &str,
+    provider: BinaryProvider,
+) -> Result<Option<Download>> {
+    http_client.try_download_with_progress(url, &|downloaded, total| {
+        reporter.report(|| PrebuiltBinaryMessage::download_progress(url, downloaded, total, provider));
+    })
 }
 
 /// A candidate release asset filename paired with its known archive format.
@@ -41,11 +70,13 @@ pub(super) fn generate_candidate_filenames(
     version: &str,
     platform: &str,
 ) -> Vec<CandidateFilename> {
-    let formats = ArchiveFormat::all_formats();
+    let formats = ArchiveFormat::all_formats(platform);
     let mut candidates = Vec::new();
 
-    for &(format, suffix) in formats {
-        push_candidate_patterns(&mut candidates, name, version, platform, format, suffix);
+    for platform_name in platform_names(platform) {
+        for &(format, suffix) in &formats {
+            push_candidate_patterns(&mut candidates, name, version, &platform_name, format, suffix);
+        }
     }
 
     // Some projects (e.g. eza) publish Windows release assets with the binary extension baked
@@ -53,14 +84,124 @@ pub(super) fn generate_candidate_filenames(
     // candidates with `{name}.exe` as the name component.
     if platform.contains("windows") {
         let exe_name = format!("{}.exe", name);
-        for &(format, suffix) in formats {
+        for &(format, suffix) in &formats {
             push_candidate_patterns(&mut candidates, &exe_name, version, platform, format, suffix);
         }
+
+        // Failing all of those, a bare `{name}.exe` with no platform in its name at all.  It's
+        // usually for x86_64, which is checked once it's downloaded, along with every other binary.
+        candidates.push(CandidateFilename {
+            filename: exe_name,
+            format: ArchiveFormat::NakedBinary,
+        });
+    }
+
+    // A WebAssembly module runs on any host with a runtime, so it's often published as a bare
+    // `{name}.wasm`
+    if platform.starts_with("wasm") {
+        candidates.push(CandidateFilename {
+            filename: format!("{}.wasm", name),
+            format: ArchiveFormat::NakedBinary,
+        });
     }
 
     candidates
 }
 
+/// The names of the OSes that release assets often call by something other than their target
+/// triple, keyed by the OS part of the triple.
+///
+/// Projects built with Go's naming in mind, or whose CI cross-compiles with tools that follow it,
+/// name BSD and illumos assets like `tool-freebsd-amd64.tar.gz` rather than by the triple.
+/// illumos runs Solaris binaries, so a `solaris` asset is the fallback for it.  WASI tools are
+/// still often published under the target's old name, `wasm32-wasi`.
+const OS_ALIASES: &[(&str, &[&str])] = &[
+    ("unknown-freebsd", &["freebsd"]),
+    ("unknown-netbsd", &["netbsd"]),
+    ("unknown-openbsd", &["openbsd"]),
+    ("unknown-dragonfly", &["dragonfly", "dragonflybsd"]),
+    ("unknown-illumos", &["illumos", "solaris"]),
+    ("pc-solaris", &["solaris"]),
+    ("sun-solaris", &["solaris"]),
+    ("wasip1", &["wasi"]),
+];
+
+/// The names of architectures in release assets named with an [`OS_ALIASES`] entry, keyed by the
+/// architecture part of the triple.
+const ARCH_ALIASES: &[(&str, &[&str])] = &[
+    ("x86_64", &["x86_64", "amd64"]),
+    ("aarch64", &["aarch64", "arm64"]),
+    ("i686", &["i686", "386"]),
+    ("riscv64gc", &["riscv64"]),
+    ("powerpc64", &["ppc64"]),
+    ("sparcv9", &["sparcv9", "sparc64"]),
+];
+
+/// The names release assets might use for `platform`: the target triple itself first, then for
+/// the OSes in [`OS_ALIASES`], each combination of OS and architecture name in the orders and
+/// with the separators that are in common use.
+fn platform_names(platform: &str) -> Vec<String> {
+    let mut names = vec![platform.to_string()];
+
+    let Some((arch, os)) = platform.split_once('-') else {
+        return names;
+    };
+    let Some((_, os_aliases)) = OS_ALIASES.iter().find(|(triple_os, _)| *triple_os == os) else {
+        return names;
+    };
+    let arch_aliases = ARCH_ALIASES
+        .iter()
+        .find(|(triple_arch, _)| *triple_arch == arch)
+        .map_or(&[][..], |(_, aliases)| *aliases);
+
+    for os_alias in *os_aliases {
+        for arch_alias in arch_aliases
+            .iter()
+            .copied()
+            .chain((!arch_aliases.contains(&arch)).then_some(arch))
+        {
+            for name in [
+                format!("{}-{}", os_alias, arch_alias),
+                format!("{}_{}", os_alias, arch_alias),
+                format!("{}-{}", arch_alias, os_alias),
+            ] {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+
+    names
+}
+
+/// Why a release that has no asset for `platform` can't be used, if what it does have for Windows
+/// is an MSI installer.
+///
+/// An MSI installs a tool system-wide rather than being unpacked, so cgx can't use one, but that's
+/// worth saying rather than only that no asset matched.
+pub(super) fn msi_only_reason<'a>(
+    name: &str,
+    platform: &str,
+    asset_names: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    if !platform.contains("windows") {
+        return None;
+    }
+
+    let prefix = name.to_ascii_lowercase();
+    let msi = asset_names.into_iter().find(|asset| {
+        let asset = asset.to_ascii_lowercase();
+        asset.starts_with(&prefix) && asset.ends_with(".msi")
+    })?;
+
+    Some(format!(
+        "the release only has an MSI installer ({}), which cgx can't use; \
+         build from source with `--prebuilt-binary never`",
+        msi
+    ))
+}
+
 fn push_candidate_patterns(
     candidates: &mut Vec<CandidateFilename>,
     name: &str,
@@ -110,3 +251,84 @@ fn push_candidate_patterns(
         format,
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_candidates_end_with_bare_exe() {
+        let windows = generate_candidate_filenames("tool", "1.0.0", "x86_64-pc-windows-msvc");
+        assert!(windows.iter().any(
+            |c| c.filename == "tool-x86_64-pc-windows-msvc.exe" && c.format == ArchiveFormat::NakedBinary
+        ));
+        let last = windows.last().unwrap();
+        assert_eq!(
+            (last.filename.as_str(), last.format),
+            ("tool.exe", ArchiveFormat::NakedBinary)
+        );
+
+        let linux = generate_candidate_filenames("tool", "1.0.0", "x86_64-unknown-linux-gnu");
+        assert!(linux.iter().all(|c| !c.filename.ends_with(".exe")));
+    }
+
+    #[test]
+    fn wasi_candidates_include_the_old_target_name_and_a_bare_wasm() {
+        let wasi = generate_candidate_filenames("tool", "1.0.0", "wasm32-wasip1");
+        for filename in [
+            "tool-wasm32-wasip1.wasm",
+            "tool-wasm32-wasi.wasm",
+            "tool-wasm32-wasi.tar.gz",
+        ] {
+            assert!(wasi.iter().any(|c| c.filename == filename), "{filename}");
+        }
+        let last = wasi.last().unwrap();
+        assert_eq!(
+            (last.filename.as_str(), last.format),
+            ("tool.wasm", ArchiveFormat::NakedBinary)
+        );
+    }
+
+    #[test]
+    fn bsd_and_illumos_candidates_include_os_aliases() {
+        assert_eq!(
+            platform_names("x86_64-unknown-freebsd"),
+            [
+                "x86_64-unknown-freebsd",
+                "freebsd-x86_64",
+                "freebsd_x86_64",
+                "x86_64-freebsd",
+                "freebsd-amd64",
+                "freebsd_amd64",
+                "amd64-freebsd",
+            ]
+        );
+        assert!(platform_names("x86_64-unknown-illumos").contains(&"solaris-amd64".to_string()));
+        assert!(platform_names("sparcv9-sun-solaris").contains(&"solaris-sparc64".to_string()));
+        assert_eq!(platform_names("armv7-unknown-netbsd-eabihf").len(), 1);
+        assert_eq!(
+            platform_names("x86_64-unknown-linux-gnu"),
+            ["x86_64-unknown-linux-gnu"]
+        );
+
+        let netbsd = generate_candidate_filenames("tool", "1.0.0", "aarch64-unknown-netbsd");
+        assert!(
+            netbsd
+                .iter()
+                .any(|c| c.filename == "tool_1.0.0_netbsd_arm64.tar.gz")
+        );
+        assert_eq!(netbsd[0].filename, "tool-aarch64-unknown-netbsd-v1.0.0.tar");
+    }
+
+    #[test]
+    fn msi_only_release_is_explained() {
+        let assets = ["tool-1.0.0-x86_64.msi", "tool-x86_64-unknown-linux-gnu.tar.gz"];
+        assert!(
+            msi_only_reason("tool", "x86_64-pc-windows-msvc", assets)
+                .unwrap()
+                .contains("tool-1.0.0-x86_64.msi")
+        );
+        assert_eq!(msi_only_reason("tool", "x86_64-unknown-linux-gnu", assets), None);
+        assert_eq!(msi_only_reason("other", "x86_64-pc-windows-msvc", assets), None);
+    }
+}
@@ -0,0 +1,283 @@
+//! Checking where a pre-built binary comes from against
+//! [`crate::config::PrebuiltBinariesConfig::trust_prebuilt_only_from`].
+//!
+//! The GitHub and GitLab release providers only ever look in the crate's own repository, at the
+//! release tagged with its version, so whatever they find passes.  Quickinstall builds binaries
+//! itself, so nothing it has does.  That leaves the URLs in a crate's binstall metadata, which can
+//! point anywhere and so are checked one by one, and GitLab's generic packages, which are the
+//! repository's own but not attached to a release.
+
+use crate::{
+    Result,
+    config::{BinaryProvider, PrebuiltOrigin},
+    crate_resolver::ResolvedSource,
+    downloader::DownloadedCrate,
+    error,
+};
+use url::Url;
+
+/// Where pre-built binaries of one crate are accepted from.
+#[derive(Clone, Debug)]
+pub(in crate::bin_resolver) struct OriginPolicy {
+    origin: PrebuiltOrigin,
+
+    /// The crate's repository, if it declares one.  Only looked up if `origin` needs it.
+    repository: Option<Url>,
+
+    /// The version of the crate, which the tag of a release has to be for.
+    version: String,
+}
+
+impl OriginPolicy {
+    pub(in crate::bin_resolver) fn new(origin: PrebuiltOrigin, krate: &DownloadedCrate) -> Result<Self> {
+        let repository = if origin == PrebuiltOrigin::Anywhere {
+            None
+        } else {
+            crate_repository(krate)?.and_then(|url| Url::parse(&url).ok())
+        };
+
+        Ok(Self {
+            origin,
+            repository,
+            version: krate.resolved.version.to_string(),
+        })
+    }
+
+    /// Fail if binaries from `provider` can't be trusted no matter where they're from.
+    pub(in crate::bin_resolver) fn check_provider(&self, provider: BinaryProvider) -> Result<()> {
+        if self.origin == PrebuiltOrigin::Anywhere || provider != BinaryProvider::Quickinstall {
+            return Ok(());
+        }
+
+        error::UntrustedPrebuiltBinarySnafu {
+            origin: provider.to_string(),
+            reason: "its binaries are built by the quickinstall project, not the crate's author",
+        }
+        .fail()
+    }
+
+    /// Fail if the binary at `url` can't be trusted.
+    pub(in crate::bin_resolver) fn check_url(&self, url: &str) -> Result<()> {
+        if self.origin == PrebuiltOrigin::Anywhere {
+            return Ok(());
+        }
+        let untrusted = |reason: String| error::UntrustedPrebuiltBinarySnafu { origin: url, reason }.fail();
+
+        let Some(repository) = &self.repository else {
+            return untrusted("the crate doesn't declare a repository".to_string());
+        };
+        let Ok(parsed) = Url::parse(url) else {
+            return untrusted("it isn't a valid URL".to_string());
+        };
+
+        let repository_path = path_segments(repository);
+        let path = path_segments(&parsed);
+        let owner = repository_path.first().copied().unwrap_or_default();
+        if parsed.host_str() != repository.host_str()
+            || !path
+                .first()
+                .is_some_and(|segment| segment.eq_ignore_ascii_case(owner))
+        {
+            return untrusted(format!(
+                "it isn't hosted by {} on {}, who owns the crate's repository",
+                owner,
+                repository.host_str().unwrap_or_default()
+            ));
+        }
+
+        if self.origin == PrebuiltOrigin::RepositoryRelease {
+            let in_repository = path.len() > repository_path.len()
+                && path
+                    .iter()
+                    .zip(&repository_path)
+                    .all(|(segment, repository_segment)| segment.eq_ignore_ascii_case(repository_segment));
+            let tag = if in_repository {
+                release_tag(&path[repository_path.len()..])
+            } else {
+                None
+            };
+
+            match tag {
+                Some(tag) if is_tag_for_version(tag, &self.version) => {}
+                Some(tag) => {
+                    return untrusted(format!(
+                        "it's attached to the release tagged {}, not one for version {}",
+                        tag, self.version
+                    ));
+                }
+                None => return untrusted(format!("it isn't attached to a release of {}", repository)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fail if the binary at `url` in the repository's generic package registry can't be trusted.
+    pub(in crate::bin_resolver) fn check_generic_package(&self, url: &str) -> Result<()> {
+        if self.origin != PrebuiltOrigin::RepositoryRelease {
+            return Ok(());
+        }
+
+        error::UntrustedPrebuiltBinarySnafu {
+            origin: url,
+            reason: "it's in the repository's package registry rather than attached to a release",
+        }
+        .fail()
+    }
+}
+
+/// The repository of `krate`: the forge repository it came from, or else the one its `Cargo.toml`
+/// declares.
+///
+/// A crate from a fork on a forge may still declare the upstream repository, which isn't the
+/// one it was built from.
+pub(in crate::bin_resolver) fn crate_repository(krate: &DownloadedCrate) -> Result<Option<String>> {
+    match &krate.resolved.source {
+        ResolvedSource::Forge { forge, .. } => Ok(Some(forge.repo_url())),
+        ResolvedSource::CratesIo
+        | ResolvedSource::Registry { .. }
+        | ResolvedSource::Git { .. }
+        | ResolvedSource::LocalDir { .. }
+        | ResolvedSource::Custom { .. } => krate.repository_url(),
+    }
+}
+
+fn path_segments(url: &Url) -> Vec<&str> {
+    url.path_segments()
+        .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// The tag of the release that a URL with `path` after the repository's own path downloads an
+/// asset of, in GitHub's (`releases/download/<tag>/...`) or GitLab's
+/// (`-/releases/<tag>/downloads/...`) form.
+fn release_tag<'a>(path: &[&'a str]) -> Option<&'a str> {
+    match path {
+        ["releases", "download", tag, _, ..] | ["-", "releases", tag, "downloads", _, ..] => Some(*tag),
+        _ => None,
+    }
+}
+
+/// Whether the release tagged `tag` is for `version`: the version itself, or the version after
+/// a `v` or the crate's name, like `v1.2.3` or `tool-1.2.3`.
+fn is_tag_for_version(tag: &str, version: &str) -> bool {
+    let Some(prefix) = tag.strip_suffix(version) else {
+        return false;
+    };
+
+    prefix.is_empty()
+        || ["v", "-", "_", "/", "@", "%40"]
+            .iter()
+            .any(|end| prefix.ends_with(end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn policy_for(origin: PrebuiltOrigin, repository: Option<&str>) -> OriginPolicy {
+        OriginPolicy {
+            origin,
+            repository: repository.map(|url| Url::parse(url).unwrap()),
+            version: "1.2.3".to_string(),
+        }
+    }
+
+    #[test]
+    fn anywhere_trusts_everything() {
+        let policy = policy_for(PrebuiltOrigin::Anywhere, None);
+
+        assert!(policy.check_provider(BinaryProvider::Quickinstall).is_ok());
+        assert!(policy.check_url("https://mirror.example.com/tool.tar.gz").is_ok());
+        assert!(
+            policy
+                .check_generic_package("https://gitlab.com/api/v4/x")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn repository_owner_trusts_only_the_owner() {
+        let policy = policy_for(
+            PrebuiltOrigin::RepositoryOwner,
+            Some("https://github.com/Owner/tool"),
+        );
+
+        assert_matches!(
+            policy.check_provider(BinaryProvider::Quickinstall),
+            Err(error::Error::UntrustedPrebuiltBinary { .. })
+        );
+        assert!(policy.check_provider(BinaryProvider::Binstall).is_ok());
+        assert!(
+            policy
+                .check_url("https://github.com/owner/tool-bin/releases/download/nightly/tool.tar.gz")
+                .is_ok()
+        );
+        assert_matches!(
+            policy.check_url("https://github.com/someone-else/tool/releases/download/v1.2.3/tool.tar.gz"),
+            Err(error::Error::UntrustedPrebuiltBinary { .. })
+        );
+        assert_matches!(
+            policy.check_url("https://mirror.example.com/owner/tool.tar.gz"),
+            Err(error::Error::UntrustedPrebuiltBinary { .. })
+        );
+        assert!(
+            policy
+                .check_generic_package("https://gitlab.com/api/v4/x")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn repository_release_needs_the_release_of_the_version() {
+        let policy = policy_for(
+            PrebuiltOrigin::RepositoryRelease,
+            Some("https://github.com/owner/tool"),
+        );
+
+        for url in [
+            "https://github.com/owner/tool/releases/download/v1.2.3/tool.tar.gz",
+            "https://github.com/owner/tool/releases/download/1.2.3/tool.tar.gz",
+            "https://github.com/owner/tool/releases/download/tool-v1.2.3/tool.tar.gz",
+        ] {
+            assert!(policy.check_url(url).is_ok(), "{url}");
+        }
+        for url in [
+            "https://github.com/owner/tool/releases/download/v11.2.3/tool.tar.gz",
+            "https://github.com/owner/tool/releases/download/nightly/tool.tar.gz",
+            "https://github.com/owner/tool-bin/releases/download/v1.2.3/tool.tar.gz",
+            "https://github.com/owner/tool/raw/main/dist/tool.tar.gz",
+        ] {
+            assert_matches!(
+                policy.check_url(url),
+                Err(error::Error::UntrustedPrebuiltBinary { .. }),
+                "{url}"
+            );
+        }
+        assert_matches!(
+            policy.check_generic_package("https://gitlab.com/api/v4/x"),
+            Err(error::Error::UntrustedPrebuiltBinary { .. })
+        );
+
+        let gitlab = policy_for(
+            PrebuiltOrigin::RepositoryRelease,
+            Some("https://gitlab.com/group/tool"),
+        );
+        assert!(
+            gitlab
+                .check_url("https://gitlab.com/group/tool/-/releases/v1.2.3/downloads/binaries/tool.tar.gz")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn nothing_is_trusted_without_a_repository() {
+        let policy = policy_for(PrebuiltOrigin::RepositoryOwner, None);
+
+        assert_matches!(
+            policy.check_url("https://github.com/owner/tool/releases/download/v1.2.3/tool.tar.gz"),
+            Err(error::Error::UntrustedPrebuiltBinary { .. })
+        );
+    }
+}
@@ -1,80 +1,95 @@
-use super::{ArchiveFormat, Provider};
+use super::{ArchiveCache, ArchiveFormat, BinaryPicker, Provider};
 use crate::{
     Result,
     bin_resolver::ResolvedBinary,
-    config::BinaryProvider,
+    config::{BinaryProvider, QuickinstallIndex},
     crate_resolver::ResolvedCrate,
     downloader::DownloadedCrate,
-    error,
-    http::{Bytes, HttpClient},
+    error::{self, Error},
+    http::{Download, HeaderMap, HttpClient},
     messages::PrebuiltBinaryMessage,
 };
-use snafu::ResultExt;
 use std::path::PathBuf;
+use url::Url;
 
 pub(in crate::bin_resolver) struct QuickinstallProvider {
     reporter: crate::messages::MessageReporter,
     cache_dir: PathBuf,
+    archives: ArchiveCache,
     http_client: HttpClient,
+    indexes: Vec<QuickinstallIndex>,
 }
 
 impl QuickinstallProvider {
     pub(in crate::bin_resolver) fn new(
         reporter: crate::messages::MessageReporter,
         cache_dir: PathBuf,
+        archives: ArchiveCache,
         http_client: HttpClient,
+        indexes: Vec<QuickinstallIndex>,
     ) -> Self {
         Self {
             reporter,
             cache_dir,
+            archives,
             http_client,
+            indexes,
         }
     }
 
-    fn construct_url(krate: &ResolvedCrate, platform: &str) -> String {
-        let base = "https://github.com/cargo-bins/cargo-quickinstall/releases/download";
+    fn construct_url(base_url: &Url, krate: &ResolvedCrate, platform: &str) -> String {
+        let base = base_url.as_str().trim_end_matches('/');
         let tag = format!("{}-{}", krate.name, krate.version);
         format!("{base}/{tag}/{tag}-{platform}.tar.gz")
     }
 
-    fn download_file(&self, url: &str) -> Result<Option<Bytes>> {
-        self.http_client.try_download(url)
+    /// The URL that tells `stats_url` of a lookup of `krate` for `platform`, with the query
+    /// cargo-binstall sends quickinstall's stats server.
+    fn construct_stats_url(stats_url: &Url, krate: &ResolvedCrate, platform: &str, found: bool) -> Url {
+        let mut url = stats_url.clone();
+        url.query_pairs_mut()
+            .append_pair("crate", &krate.name)
+            .append_pair("version", &krate.version.to_string())
+            .append_pair("target", platform)
+            .append_pair("agent", &format!("cgx/{}", env!("CARGO_PKG_VERSION")))
+            .append_pair("status", if found { "found" } else { "not-found" });
+        url
     }
-}
-
-impl Provider for QuickinstallProvider {
-    fn try_resolve(&self, krate: &DownloadedCrate, platform: &str) -> Result<Option<ResolvedBinary>> {
-        let url = Self::construct_url(&krate.resolved, platform);
-
-        self.reporter
-            .report(|| PrebuiltBinaryMessage::downloading_binary(&url, BinaryProvider::Quickinstall));
-
-        let Ok(Some(data)) = self.download_file(&url) else {
-            self.reporter.report(|| {
-                PrebuiltBinaryMessage::provider_has_no_binary(
-                    BinaryProvider::Quickinstall,
-                    "download failed or binary not found",
-                )
-            });
-            return Ok(None);
-        };
-
-        // TODO(#80): verify .sig (minisign) signatures when support is added
 
-        let temp_dir = tempfile::tempdir().with_context(|_| error::TempDirCreationSnafu {
-            parent: self.cache_dir.clone(),
-        })?;
+    fn download_file(&self, url: &str) -> Result<Option<Download>> {
+        super::download_binary(
+            &self.http_client,
+            &self.reporter,
+            url,
+            BinaryProvider::Quickinstall,
+        )
+    }
 
-        let archive_path = temp_dir.path().join(ArchiveFormat::TarGz.canonical_filename());
-        std::fs::write(&archive_path, &data).with_context(|_| error::IoSnafu {
-            path: archive_path.clone(),
-        })?;
+    /// Tell `index`'s stats endpoint, if it has one, whether it had a binary of `krate`.
+    ///
+    /// The stats are only for whoever runs the index, so failing to send them is only logged.
+    fn record_stats(&self, index: &QuickinstallIndex, krate: &ResolvedCrate, platform: &str, found: bool) {
+        let Some(stats_url) = &index.stats else {
+            return;
+        };
 
-        let binary_name = krate.default_binary_name()?;
-        let extract_dir = temp_dir.path().join("extracted");
-        let binary_path =
-            super::extract_binary(&archive_path, ArchiveFormat::TarGz, &binary_name, &extract_dir)?;
+        let url = Self::construct_stats_url(stats_url, krate, platform, found);
+        if let Err(e) = self
+            .http_client
+            .post_with_headers(url.as_str(), &HeaderMap::new(), "")
+        {
+            tracing::debug!("Failed to send quickinstall stats to {}: {}", stats_url, e);
+        }
+    }
+}
 
+impl Provider for QuickinstallProvider {
+    fn try_resolve(
+        &self,
+        krate: &DownloadedCrate,
+        platform: &str,
+        picker: &BinaryPicker,
+    ) -> Result<Option<ResolvedBinary>> {
         let final_dir = self
             .cache_dir
             .join("binaries")
@@ -83,33 +98,95 @@ impl Provider for QuickinstallProvider {
             .join(krate.resolved.version.to_string())
             .join(platform);
 
-        std::fs::create_dir_all(&final_dir).with_context(|_| error::IoSnafu {
-            path: final_dir.clone(),
-        })?;
+        // The indexes are tried in order, so that an organization's own can come before the
+        // public one, and one that can't be reached only fails the provider if none of the others
+        // has the binary either
+        let mut failure = None;
+        for index in &self.indexes {
+            let url = Self::construct_url(&index.url, &krate.resolved, platform);
+
+            if let Some(extract_dir) = self.archives.lookup(&url, None) {
+                self.reporter.report(|| {
+                    PrebuiltBinaryMessage::reusing_archive(&url, &extract_dir, BinaryProvider::Quickinstall)
+                });
+                return Ok(Some(ResolvedBinary {
+                    krate: krate.resolved.clone(),
+                    provider: BinaryProvider::Quickinstall,
+                    path: picker.install_from_dir(&extract_dir, &final_dir)?,
+                    artifact_sha256: ArchiveCache::digest(&extract_dir),
+                }));
+            }
+
+            self.reporter
+                .report(|| PrebuiltBinaryMessage::downloading_binary(&url, BinaryProvider::Quickinstall));
+
+            let data = match self.download_file(&url) {
+                Ok(data) => data,
+                Err(Error::Cancelled) => return error::CancelledSnafu.fail(),
+                Err(e) => {
+                    tracing::debug!("Failed to look for a binary at {}: {}", url, e);
+                    failure.get_or_insert(e);
+                    continue;
+                }
+            };
+            self.record_stats(index, &krate.resolved, platform, data.is_some());
+            let Some(data) = data else {
+                tracing::debug!("No binary at {}", url);
+                continue;
+            };
+
+            // TODO(#80): verify .sig (minisign) signatures when support is added
+
+            let final_path =
+                picker.install_from_asset(&self.archives, &url, &data, ArchiveFormat::TarGz, &final_dir)?;
+
+            return Ok(Some(ResolvedBinary {
+                krate: krate.resolved.clone(),
+                provider: BinaryProvider::Quickinstall,
+                path: final_path,
+                artifact_sha256: Some(data.sha256),
+            }));
+        }
 
-        let final_path = final_dir.join(format!("{}{}", binary_name, std::env::consts::EXE_SUFFIX));
-        std::fs::copy(&binary_path, &final_path).with_context(|_| error::IoSnafu {
-            path: final_path.clone(),
-        })?;
+        if let Some(e) = failure {
+            return Err(e);
+        }
 
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = std::fs::metadata(&final_path)
-                .with_context(|_| error::IoSnafu {
-                    path: final_path.clone(),
-                })?
-                .permissions();
-            perms.set_mode(0o755);
-            std::fs::set_permissions(&final_path, perms).with_context(|_| error::IoSnafu {
-                path: final_path.clone(),
-            })?;
+        self.reporter.report(|| {
+            PrebuiltBinaryMessage::provider_has_no_binary(BinaryProvider::Quickinstall, "binary not found")
+        });
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_resolver::ResolvedSource;
+
+    fn krate() -> ResolvedCrate {
+        ResolvedCrate {
+            name: "ripgrep".to_string(),
+            version: semver::Version::new(14, 1, 1),
+            source: ResolvedSource::CratesIo,
         }
+    }
 
-        Ok(Some(ResolvedBinary {
-            krate: krate.resolved.clone(),
-            provider: BinaryProvider::Quickinstall,
-            path: final_path,
-        }))
+    #[test]
+    fn stats_url_reports_the_lookup() {
+        let stats_url = Url::parse("https://artifacts.example.com/record-install").unwrap();
+        let url = QuickinstallProvider::construct_stats_url(
+            &stats_url,
+            &krate(),
+            "x86_64-unknown-linux-gnu",
+            false,
+        );
+
+        let query = url.query_pairs().into_owned().collect::<Vec<_>>();
+        assert_eq!(url.path(), "/record-install");
+        assert!(query.contains(&("crate".to_string(), "ripgrep".to_string())));
+        assert!(query.contains(&("version".to_string(), "14.1.1".to_string())));
+        assert!(query.contains(&("target".to_string(), "x86_64-unknown-linux-gnu".to_string())));
+        assert!(query.contains(&("status".to_string(), "not-found".to_string())));
     }
 }
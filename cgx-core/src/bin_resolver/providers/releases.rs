@@ -0,0 +1,189 @@
+use crate::{Result, config::Config, helpers::format_hex_lower};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{path::PathBuf, time::Duration};
+
+/// The assets of a release, as `(asset_name, download_url)` pairs.
+pub(in crate::bin_resolver) type ReleaseAssets = Vec<(String, String)>;
+
+/// A listing kept on disk, and when it was fetched.
+#[derive(Debug, Serialize, Deserialize)]
+struct KeptListing {
+    assets: ReleaseAssets,
+    listed_at: DateTime<Utc>,
+}
+
+/// The asset listings of releases, as fetched from a provider's API, kept for a while by
+/// repository and version.
+///
+/// Looking for a binary that isn't cached yet lists the assets of the release first, and closely
+/// spaced runs, such as the jobs of a CI matrix sharing a runner or a tool looked for on several
+/// targets, would otherwise each list the same release again, counting against the API's rate
+/// limit every time.  Each listing is kept in `binaries/releases/<SHA256>.json` in the cache
+/// directory, and reused for the configured `negative_cache_ttl`, the same as a provider's finding
+/// that it has no binary, since assets are sometimes added to a release after it's published.  A
+/// release that wasn't found at all is kept the same way.  With `--refresh=binary` the release is
+/// always listed again, and the new listing replaces the kept one.
+#[derive(Debug, Clone)]
+pub(in crate::bin_resolver) struct ReleaseListingCache {
+    dir: PathBuf,
+    ttl: Duration,
+
+    /// Whether a kept listing is used in place of listing the release again.
+    reuse_listings: bool,
+}
+
+impl ReleaseListingCache {
+    pub(in crate::bin_resolver) fn new(config: &Config) -> Self {
+        Self {
+            dir: config.binary_cache_root().join("binaries").join("releases"),
+            ttl: config.prebuilt_binaries.negative_cache_ttl,
+            reuse_listings: !config.refresh.binary,
+        }
+    }
+
+    /// The listing kept for the release of `version` in the repository at `repo_url`, if there is
+    /// one recent enough to use.
+    pub(in crate::bin_resolver) fn lookup(&self, repo_url: &str, version: &str) -> Option<ReleaseAssets> {
+        if !self.reuse_listings {
+            return None;
+        }
+
+        let contents = std::fs::read_to_string(self.listing_path(repo_url, version)).ok()?;
+        let kept: KeptListing = serde_json::from_str(&contents).ok()?;
+        let age = Utc::now()
+            .signed_duration_since(kept.listed_at)
+            .to_std()
+            .unwrap_or(Duration::ZERO);
+
+        (age < self.ttl).then_some(kept.assets)
+    }
+
+    /// Keep `assets` as the listing of the release of `version` in the repository at `repo_url`.
+    ///
+    /// A listing that can't be kept only means the release is listed again next time, so that's
+    /// logged rather than failing the lookup.
+    pub(in crate::bin_resolver) fn keep(&self, repo_url: &str, version: &str, assets: &ReleaseAssets) {
+        let kept = KeptListing {
+            assets: assets.clone(),
+            listed_at: Utc::now(),
+        };
+        let path = self.listing_path(repo_url, version);
+
+        let written = serde_json::to_string_pretty(&kept)
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                std::fs::create_dir_all(&self.dir)?;
+                std::fs::write(&path, json)
+            });
+        if let Err(e) = written {
+            tracing::debug!("Failed to keep the release listing at {}: {}", path.display(), e);
+        }
+    }
+
+    /// The listing of the release of `version` in the repository at `repo_url`, kept or else
+    /// fetched with `list` and kept.
+    ///
+    /// Returns whether the listing was a kept one along with it.
+    pub(in crate::bin_resolver) fn get_or_list(
+        &self,
+        repo_url: &str,
+        version: &str,
+        list: impl FnOnce() -> Result<ReleaseAssets>,
+    ) -> Result<(ReleaseAssets, bool)> {
+        if let Some(assets) = self.lookup(repo_url, version) {
+            return Ok((assets, true));
+        }
+
+        let assets = list()?;
+        self.keep(repo_url, version, &assets);
+        Ok((assets, false))
+    }
+
+    /// The file the listing of the release of `version` in the repository at `repo_url` is kept
+    /// in.
+    fn listing_path(&self, repo_url: &str, version: &str) -> PathBuf {
+        let key = format!("{}\n{}", repo_url.trim_end_matches('/'), version);
+        self.dir.join(format!(
+            "{}.json",
+            format_hex_lower(Sha256::digest(key.as_bytes()))
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error;
+
+    fn assets() -> ReleaseAssets {
+        vec![(
+            "tool-x86_64-unknown-linux-gnu.tar.gz".to_string(),
+            "https://github.com/o/r/releases/download/v1.0.0/tool-x86_64-unknown-linux-gnu.tar.gz"
+                .to_string(),
+        )]
+    }
+
+    #[test]
+    fn listings_are_kept_by_repo_and_version() {
+        let (_temp_dir, config) = crate::config::create_test_env();
+        let releases = ReleaseListingCache::new(&config);
+
+        let (listed, kept) = releases
+            .get_or_list("https://github.com/o/r", "1.0.0", || Ok(assets()))
+            .unwrap();
+        assert_eq!((listed, kept), (assets(), false));
+
+        let (listed, kept) = releases
+            .get_or_list("https://github.com/o/r/", "1.0.0", || {
+                panic!("the kept listing should have been used")
+            })
+            .unwrap();
+        assert_eq!((listed, kept), (assets(), true));
+
+        assert_eq!(releases.lookup("https://github.com/o/r", "1.0.1"), None);
+        assert_eq!(releases.lookup("https://github.com/o/other", "1.0.0"), None);
+    }
+
+    #[test]
+    fn expired_and_refreshed_listings_are_listed_again() {
+        let (_temp_dir, mut config) = crate::config::create_test_env();
+        ReleaseListingCache::new(&config).keep("https://github.com/o/r", "1.0.0", &Vec::new());
+
+        config.refresh.binary = true;
+        let refreshed = ReleaseListingCache::new(&config);
+        assert_eq!(refreshed.lookup("https://github.com/o/r", "1.0.0"), None);
+        refreshed
+            .get_or_list("https://github.com/o/r", "1.0.0", || Ok(assets()))
+            .unwrap();
+
+        config.refresh.binary = false;
+        assert_eq!(
+            ReleaseListingCache::new(&config).lookup("https://github.com/o/r", "1.0.0"),
+            Some(assets())
+        );
+
+        config.prebuilt_binaries.negative_cache_ttl = Duration::ZERO;
+        assert_eq!(
+            ReleaseListingCache::new(&config).lookup("https://github.com/o/r", "1.0.0"),
+            None
+        );
+    }
+
+    #[test]
+    fn failed_listings_are_not_kept() {
+        let (_temp_dir, config) = crate::config::create_test_env();
+        let releases = ReleaseListingCache::new(&config);
+
+        let result = releases.get_or_list("https://github.com/o/r", "1.0.0", || {
+            error::HttpStatusSnafu {
+                url: "https://api.github.com/repos/o/r/releases/tags/v1.0.0",
+                status: 403u16,
+            }
+            .fail()
+        });
+        assert!(result.is_err());
+        assert_eq!(releases.lookup("https://github.com/o/r", "1.0.0"), None);
+    }
+}
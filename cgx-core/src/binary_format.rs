@@ -0,0 +1,587 @@
+//! Checks that a downloaded binary is an executable for the target it was downloaded for.
+//!
+//! A mislabeled release asset, or a provider serving the wrong file, would otherwise only come to
+//! light when the binary is run, as an `exec format error` or `bad CPU type in executable` that
+//! says nothing about where the binary came from.  Reading the header of the executable instead
+//! lets cgx say what the binary really is, and move on to the next provider.
+//!
+//! Mostly the format (ELF, PE, or Mach-O) and the architecture are checked, which is enough to
+//! catch a binary for the wrong OS or CPU, except among the OSes that all use ELF.  For those,
+//! the OS is checked too when the binary's ELF header names one, as FreeBSD binaries always do
+//! and Linux and Solaris ones sometimes do; NetBSD, OpenBSD, and most Linux and illumos binaries
+//! leave it unset, so they're taken on trust.
+//!
+//! A glibc binary that is right for the OS and CPU can still fail to start, with `GLIBC_2.xx not
+//! found`, on a system with an older glibc than it was linked against.  So [`check_glibc`] reads
+//! the glibc versions it needs from its ELF version requirements, and compares the newest to the
+//! host's.
+//!
+//! Only [`is_wasm`] is needed without the `prebuilt-binaries` feature, by the runner.
+#![cfg_attr(not(feature = "prebuilt-binaries"), allow(dead_code))]
+
+use crate::{
+    Result, error,
+    platform::{self, GlibcVersion},
+};
+use snafu::ResultExt;
+use std::{fmt, fs::File, io::Read, path::Path};
+
+/// How much of the start of a binary is read to identify it.
+///
+/// Enough to cover the DOS stub that precedes the PE header of Windows executables.
+const HEADER_LEN: u64 = 4096;
+
+/// Executable file formats, as told apart by their magic numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Elf,
+    Pe,
+    MachO,
+    /// A universal Mach-O binary, with code for several architectures.
+    MachOUniversal,
+    /// A script with a `#!` line, which runs wherever its interpreter does.
+    Script,
+    /// A WebAssembly module, run by a WebAssembly runtime rather than by the OS.
+    Wasm,
+}
+
+/// What the header of a binary says it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BinaryKind {
+    format: Format,
+    /// The architecture as it appears in target triples, if the header has one cgx knows.
+    arch: Option<&'static str>,
+    /// The OS as it appears in target triples, if the header says which it's for.
+    os: Option<&'static str>,
+}
+
+impl fmt::Display for BinaryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self.format {
+            Format::Elf => "an ELF executable",
+            Format::Pe => "a Windows (PE) executable",
+            Format::MachO => "a macOS (Mach-O) executable",
+            Format::MachOUniversal => "a universal macOS (Mach-O) executable",
+            Format::Script => "a script",
+            Format::Wasm => "a WebAssembly module",
+        };
+
+        match (self.os, self.arch) {
+            (Some(os), Some(arch)) => write!(f, "{} for {} on {}", format, os, arch),
+            (Some(os), None) => write!(f, "{} for {}", format, os),
+            (None, Some(arch)) => write!(f, "{} for {}", format, arch),
+            (None, None) => f.write_str(format),
+        }
+    }
+}
+
+/// Check that the binary at `path` can run on `target`.
+///
+/// Fails with [`error::Error::BinaryFormatMismatch`] if it's an executable for another OS or
+/// architecture, or not an executable at all.
+pub(crate) fn check(path: &Path, target: &str) -> Result<()> {
+    let mut header = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(HEADER_LEN).read_to_end(&mut header))
+        .context(error::IoSnafu { path })?;
+
+    let actual = match identify(&header) {
+        Some(kind) if is_compatible(kind, target) => return Ok(()),
+        Some(kind) => kind.to_string(),
+        None => "not an executable".to_string(),
+    };
+
+    error::BinaryFormatMismatchSnafu { path, target, actual }.fail()
+}
+
+/// Whether the binary at `path` is an executable that can run on `target`, as [`check`] would have
+/// it, for telling apart the builds for several targets.
+pub(crate) fn runs_on(path: &Path, target: &str) -> bool {
+    check(path, target).is_ok()
+}
+
+/// Whether the binary at `path` is a WebAssembly module, which has to be run by a runtime (see
+/// [`crate::runner::wasm_runtime`]).
+///
+/// A binary that can't be read isn't one, and fails to run the usual way instead.
+pub(crate) fn is_wasm(path: &Path) -> bool {
+    let mut header = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(4).read_to_end(&mut header))
+        .is_ok_and(|_| identify(&header).is_some_and(|kind| kind.format == Format::Wasm))
+}
+
+/// Check that the binary at `path`, if it's for a glibc `target`, doesn't need a newer glibc than
+/// the host has.
+///
+/// Fails with [`error::Error::IncompatibleGlibc`] if it does.  If the host's glibc version can't be
+/// detected, or the binary's requirements can't be read, it's given the benefit of the doubt.
+pub(crate) fn check_glibc(path: &Path, target: &str) -> Result<()> {
+    if !target.contains("-linux-gnu") {
+        return Ok(());
+    }
+    let Some(available) = platform::host_glibc_version() else {
+        return Ok(());
+    };
+
+    let data = std::fs::read(path).context(error::IoSnafu { path })?;
+    match required_glibc(&data) {
+        Some(required) if required > available => error::IncompatibleGlibcSnafu {
+            path,
+            required: required.to_string(),
+            available: available.to_string(),
+        }
+        .fail(),
+        _ => Ok(()),
+    }
+}
+
+/// ELF section type of the GNU version requirements, `.gnu.version_r`.
+const SHT_GNU_VERNEED: u32 = 0x6fff_fffe;
+
+/// The newest glibc version the ELF executable `data` needs, or `None` if it needs none or isn't
+/// an ELF executable with section headers.
+///
+/// The versions are the `GLIBC_2.xx` names in its version requirements section, which lists the
+/// symbol versions it needs from each shared library.
+fn required_glibc(data: &[u8]) -> Option<GlibcVersion> {
+    if !data.starts_with(b"\x7fELF") {
+        return None;
+    }
+    let is_64 = data.get(4) == Some(&2);
+    let big_endian = data.get(5) == Some(&2);
+    let u16_at = |offset| read_u16(data, offset, big_endian).map(usize::from);
+    let u32_at = |offset| read_u32(data, offset, big_endian).map(|value| value as usize);
+    let word_at = |offset| {
+        if is_64 {
+            read_u64(data, offset, big_endian).and_then(|value| usize::try_from(value).ok())
+        } else {
+            u32_at(offset)
+        }
+    };
+
+    // Where the section headers are, and the offsets of the fields of one that are used
+    let (shoff, shentsize, shnum) = if is_64 {
+        (word_at(0x28)?, u16_at(0x3a)?, u16_at(0x3c)?)
+    } else {
+        (word_at(0x20)?, u16_at(0x2e)?, u16_at(0x30)?)
+    };
+    let (offset_field, size_field, link_field) = if is_64 {
+        (0x18, 0x20, 0x28)
+    } else {
+        (0x10, 0x14, 0x18)
+    };
+    let section = |index: usize| shoff.checked_add(index.checked_mul(shentsize)?);
+
+    let verneed = (0..shnum)
+        .filter_map(section)
+        .find(|&header| read_u32(data, header + 4, big_endian) == Some(SHT_GNU_VERNEED))?;
+    let strtab = word_at(section(u32_at(verneed + link_field)?)? + offset_field)?;
+    let start = word_at(verneed + offset_field)?;
+    let end = start.checked_add(word_at(verneed + size_field)?)?;
+
+    let name_at = |offset: usize| {
+        let name = data.get(strtab.checked_add(offset)?..)?;
+        let len = name.iter().position(|&byte| byte == 0)?;
+        std::str::from_utf8(&name[..len]).ok()
+    };
+
+    // A list of `Elf_Verneed` entries, one per library, each with a list of `Elf_Vernaux` entries,
+    // one per version; both are linked by offsets relative to the entry.  Every entry takes at
+    // least 16 bytes, which bounds how many there can be in a malformed section.
+    let mut newest = None;
+    let mut need = start;
+    for _ in 0..(end.saturating_sub(start) / 16) {
+        let mut aux = need.checked_add(u32_at(need + 8)?)?;
+        for _ in 0..u16_at(need + 2)? {
+            let version = name_at(u32_at(aux + 8)?)
+                .and_then(|name| name.strip_prefix("GLIBC_"))
+                .and_then(GlibcVersion::parse);
+            newest = newest.max(version);
+
+            match u32_at(aux + 12)? {
+                0 => break,
+                next => aux = aux.checked_add(next)?,
+            }
+        }
+
+        match u32_at(need + 12)? {
+            0 => break,
+            next => need = need.checked_add(next)?,
+        }
+        if need >= end {
+            break;
+        }
+    }
+
+    newest
+}
+
+/// Whether a binary of the given kind can run on `target`.
+fn is_compatible(kind: BinaryKind, target: &str) -> bool {
+    let expected_format = if target.starts_with("wasm") {
+        Format::Wasm
+    } else if target.contains("-windows") {
+        Format::Pe
+    } else if target.contains("-apple-") {
+        Format::MachO
+    } else {
+        Format::Elf
+    };
+
+    match kind.format {
+        Format::Script => expected_format != Format::Pe,
+        Format::MachOUniversal => expected_format == Format::MachO,
+        format => {
+            format == expected_format
+                && match (kind.arch, target_arch(target)) {
+                    (Some(actual), Some(expected)) => actual == expected,
+                    // An architecture cgx doesn't know can't be told apart
+                    _ => true,
+                }
+                && match (kind.os, target_os(target)) {
+                    // illumos runs Solaris binaries
+                    (Some(actual), Some(expected)) => {
+                        actual == expected || (actual, expected) == ("solaris", "illumos")
+                    }
+                    _ => true,
+                }
+        }
+    }
+}
+
+/// The OS of `target`, in the form [`identify`] reports it, if it's one that uses ELF.
+fn target_os(target: &str) -> Option<&'static str> {
+    [
+        "linux",
+        "freebsd",
+        "netbsd",
+        "openbsd",
+        "dragonfly",
+        "illumos",
+        "solaris",
+    ]
+    .into_iter()
+    .find(|os| target.split('-').skip(1).any(|part| part == *os))
+}
+
+/// The architecture of `target`, in the form [`identify`] reports it.
+fn target_arch(target: &str) -> Option<&'static str> {
+    let arch = target.split_once('-').map_or(target, |(arch, _)| arch);
+    match arch {
+        "x86_64" => Some("x86_64"),
+        "i386" | "i586" | "i686" => Some("x86"),
+        "aarch64" | "arm64" | "arm64e" => Some("aarch64"),
+        "riscv64gc" | "riscv64" => Some("riscv64"),
+        "powerpc64" | "powerpc64le" => Some("powerpc64"),
+        "s390x" => Some("s390x"),
+        "loongarch64" => Some("loongarch64"),
+        "sparcv9" | "sparc64" => Some("sparc64"),
+        arch if arch.starts_with("arm") || arch.starts_with("thumb") => Some("arm"),
+        _ => None,
+    }
+}
+
+/// Identify a binary from the start of its contents, or `None` if it isn't an executable.
+fn identify(header: &[u8]) -> Option<BinaryKind> {
+    let kind = |format, arch| {
+        Some(BinaryKind {
+            format,
+            arch,
+            os: None,
+        })
+    };
+
+    match header {
+        [0x7f, b'E', b'L', b'F', ..] => {
+            let big_endian = header.get(5) == Some(&2);
+            let machine = read_u16(header, 18, big_endian)?;
+            let arch = match machine {
+                3 => Some("x86"),
+                62 => Some("x86_64"),
+                40 => Some("arm"),
+                183 => Some("aarch64"),
+                243 => Some("riscv64"),
+                21 => Some("powerpc64"),
+                22 => Some("s390x"),
+                258 => Some("loongarch64"),
+                43 => Some("sparc64"),
+                _ => None,
+            };
+            // `EI_OSABI`, which is 0 for binaries that don't say
+            let os = match header.get(7) {
+                Some(3) => Some("linux"),
+                Some(6) => Some("solaris"),
+                Some(9) => Some("freebsd"),
+                Some(2) => Some("netbsd"),
+                Some(12) => Some("openbsd"),
+                _ => None,
+            };
+            Some(BinaryKind {
+                format: Format::Elf,
+                arch,
+                os,
+            })
+        }
+        [0xfe, 0xed, 0xfa, 0xce | 0xcf, ..] | [0xce | 0xcf, 0xfa, 0xed, 0xfe, ..] => {
+            let big_endian = header[0] == 0xfe;
+            let cpu_type = read_u32(header, 4, big_endian)?;
+            let arch = match cpu_type {
+                7 => Some("x86"),
+                0x0100_0007 => Some("x86_64"),
+                12 => Some("arm"),
+                0x0100_000c => Some("aarch64"),
+                _ => None,
+            };
+            kind(Format::MachO, arch)
+        }
+        [0xca, 0xfe, 0xba, 0xbe | 0xbf, ..] => kind(Format::MachOUniversal, None),
+        [b'M', b'Z', ..] => {
+            // The PE header is wherever the DOS header says; past what was read, only the format
+            // is known
+            let pe_offset = read_u32(header, 0x3c, false)? as usize;
+            let arch = match header.get(pe_offset..pe_offset + 4) {
+                Some(b"PE\0\0") => match read_u16(header, pe_offset + 4, false) {
+                    Some(0x014c) => Some("x86"),
+                    Some(0x8664) => Some("x86_64"),
+                    Some(0xaa64) => Some("aarch64"),
+                    Some(0x01c4) => Some("arm"),
+                    _ => None,
+                },
+                Some(_) => return None,
+                None => None,
+            };
+            kind(Format::Pe, arch)
+        }
+        [b'#', b'!', ..] => kind(Format::Script, None),
+        [0x00, b'a', b's', b'm', ..] => kind(Format::Wasm, None),
+        _ => None,
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes: [u8; 2] = bytes.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+fn read_u64(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u64> {
+    let bytes: [u8; 8] = bytes.get(offset..offset + 8)?.try_into().ok()?;
+    Some(if big_endian {
+        u64::from_be_bytes(bytes)
+    } else {
+        u64::from_le_bytes(bytes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    /// The start of a little-endian 64-bit ELF executable for the given `e_machine`.
+    fn elf_header(machine: u16) -> Vec<u8> {
+        let mut header = vec![0u8; 64];
+        header[..4].copy_from_slice(b"\x7fELF");
+        header[4] = 2;
+        header[5] = 1;
+        header[18..20].copy_from_slice(&machine.to_le_bytes());
+        header
+    }
+
+    /// The start of a Windows executable for the given machine type.
+    fn pe_header(machine: u16) -> Vec<u8> {
+        let mut header = vec![0u8; 0x100];
+        header[..2].copy_from_slice(b"MZ");
+        header[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        header[0x80..0x84].copy_from_slice(b"PE\0\0");
+        header[0x84..0x86].copy_from_slice(&machine.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn identifies_executable_formats() {
+        assert_eq!(
+            identify(&elf_header(62)),
+            Some(BinaryKind {
+                format: Format::Elf,
+                arch: Some("x86_64"),
+                os: None,
+            })
+        );
+        assert_eq!(
+            identify(&pe_header(0xaa64)),
+            Some(BinaryKind {
+                format: Format::Pe,
+                arch: Some("aarch64"),
+                os: None,
+            })
+        );
+        assert_eq!(
+            identify(&[0xcf, 0xfa, 0xed, 0xfe, 0x0c, 0x00, 0x00, 0x01]),
+            Some(BinaryKind {
+                format: Format::MachO,
+                arch: Some("aarch64"),
+                os: None,
+            })
+        );
+        assert_eq!(
+            identify(b"#!/bin/sh\n").map(|kind| kind.format),
+            Some(Format::Script)
+        );
+        assert_eq!(
+            identify(b"\0asm\x01\0\0\0").map(|kind| kind.format),
+            Some(Format::Wasm)
+        );
+        assert_eq!(identify(b"<!DOCTYPE html>"), None);
+        assert_eq!(identify(b""), None);
+    }
+
+    #[test]
+    fn compatibility_depends_on_format_and_arch() {
+        let elf_x86_64 = identify(&elf_header(62)).unwrap();
+        let elf_aarch64 = identify(&elf_header(183)).unwrap();
+        let pe_x86_64 = identify(&pe_header(0x8664)).unwrap();
+        let universal = identify(&[0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 2]).unwrap();
+
+        assert!(is_compatible(elf_x86_64, "x86_64-unknown-linux-musl"));
+        assert!(!is_compatible(elf_aarch64, "x86_64-unknown-linux-gnu"));
+        assert!(!is_compatible(elf_x86_64, "x86_64-pc-windows-msvc"));
+        assert!(is_compatible(pe_x86_64, "x86_64-pc-windows-msvc"));
+        assert!(is_compatible(universal, "aarch64-apple-darwin"));
+        assert!(!is_compatible(universal, "aarch64-unknown-linux-gnu"));
+        assert!(!is_compatible(elf_aarch64, "armv7-unknown-linux-gnueabihf"));
+
+        let wasm = identify(b"\0asm\x01\0\0\0").unwrap();
+        assert!(is_compatible(wasm, "wasm32-wasip1"));
+        assert!(!is_compatible(wasm, "x86_64-unknown-linux-gnu"));
+        assert!(!is_compatible(elf_x86_64, "wasm32-wasip1"));
+    }
+
+    #[test]
+    fn compatibility_among_elf_oses_depends_on_osabi() {
+        let with_osabi = |osabi| {
+            let mut header = elf_header(62);
+            header[7] = osabi;
+            identify(&header).unwrap()
+        };
+        let unmarked = with_osabi(0);
+        let freebsd = with_osabi(9);
+        let solaris = with_osabi(6);
+
+        assert!(is_compatible(freebsd, "x86_64-unknown-freebsd"));
+        assert!(!is_compatible(freebsd, "x86_64-unknown-linux-gnu"));
+        assert!(!is_compatible(freebsd, "x86_64-unknown-netbsd"));
+        assert!(is_compatible(solaris, "x86_64-unknown-illumos"));
+        assert!(is_compatible(solaris, "x86_64-pc-solaris"));
+        assert!(!is_compatible(solaris, "x86_64-unknown-freebsd"));
+        assert!(is_compatible(unmarked, "x86_64-unknown-netbsd"));
+        assert!(is_compatible(unmarked, "x86_64-unknown-openbsd"));
+        assert!(is_compatible(with_osabi(3), "x86_64-unknown-linux-musl"));
+        assert_eq!(freebsd.to_string(), "an ELF executable for freebsd on x86_64");
+    }
+
+    #[test]
+    fn check_names_what_the_binary_is() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("tool");
+        std::fs::write(&path, elf_header(183)).unwrap();
+
+        assert_matches!(check(&path, "aarch64-unknown-linux-gnu"), Ok(()));
+        assert_matches!(
+            check(&path, "x86_64-unknown-linux-gnu"),
+            Err(error::Error::BinaryFormatMismatch { actual, .. }) if actual == "an ELF executable for aarch64"
+        );
+
+        std::fs::write(&path, b"Not Found").unwrap();
+        assert_matches!(
+            check(&path, "x86_64-unknown-linux-gnu"),
+            Err(error::Error::BinaryFormatMismatch { actual, .. }) if actual == "not an executable"
+        );
+    }
+
+    /// A 64-bit ELF executable whose version requirements on libc.so.6 are `versions`.
+    fn elf_with_version_needs(versions: &[&str]) -> Vec<u8> {
+        let mut data = elf_header(62);
+
+        // The string table, after the header
+        let strtab_offset = data.len();
+        data.extend_from_slice(b"\0libc.so.6\0");
+        let mut name_offsets = Vec::new();
+        for version in versions {
+            name_offsets.push((data.len() - strtab_offset) as u32);
+            data.extend_from_slice(version.as_bytes());
+            data.push(0);
+        }
+        let strtab_size = data.len() - strtab_offset;
+
+        // One `Elf_Verneed`, followed by an `Elf_Vernaux` for each version
+        let verneed_offset = data.len();
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&(versions.len() as u16).to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        for (index, name_offset) in name_offsets.iter().enumerate() {
+            let next: u32 = if index + 1 < versions.len() { 16 } else { 0 };
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&name_offset.to_le_bytes());
+            data.extend_from_slice(&next.to_le_bytes());
+        }
+        let verneed_size = data.len() - verneed_offset;
+
+        // The section headers: the null section, the string table, and the version requirements
+        let shoff = data.len();
+        data.extend_from_slice(&[0; 64]);
+        for (kind, offset, size, link) in [
+            (3u32, strtab_offset, strtab_size, 0u32),
+            (SHT_GNU_VERNEED, verneed_offset, verneed_size, 1),
+        ] {
+            let mut header = [0u8; 64];
+            header[4..8].copy_from_slice(&kind.to_le_bytes());
+            header[0x18..0x20].copy_from_slice(&(offset as u64).to_le_bytes());
+            header[0x20..0x28].copy_from_slice(&(size as u64).to_le_bytes());
+            header[0x28..0x2c].copy_from_slice(&link.to_le_bytes());
+            data.extend_from_slice(&header);
+        }
+        data[0x28..0x30].copy_from_slice(&(shoff as u64).to_le_bytes());
+        data[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        data[0x3c..0x3e].copy_from_slice(&3u16.to_le_bytes());
+
+        data
+    }
+
+    #[test]
+    fn reads_newest_required_glibc() {
+        assert_eq!(
+            required_glibc(&elf_with_version_needs(&[
+                "GLIBC_2.2.5",
+                "GLIBC_2.34",
+                "GLIBC_PRIVATE",
+                "GLIBC_2.17"
+            ])),
+            GlibcVersion::parse("2.34")
+        );
+        assert_eq!(required_glibc(&elf_with_version_needs(&[])), None);
+        assert_eq!(required_glibc(&elf_header(62)), None);
+        assert_eq!(required_glibc(&pe_header(0x8664)), None);
+    }
+
+    #[test]
+    fn current_exe_matches_build_target() {
+        check(&std::env::current_exe().unwrap(), build_context::TARGET).unwrap();
+        check_glibc(&std::env::current_exe().unwrap(), build_context::TARGET).unwrap();
+    }
+}
@@ -0,0 +1,532 @@
+//! Recognizing builds that fail, or are bound to, for want of a system library or tool, and
+//! saying how to install it.
+//!
+//! A crate whose build script needs OpenSSL, `pkg-config`, `cmake` and the like fails to build
+//! without them, with an error from deep in the build script that doesn't say what to do about it
+//! on the system at hand.  When a build fails, [`diagnose`] looks through cargo's output for the
+//! errors these build scripts are known to give, and each [`Diagnosis`] it finds says what's
+//! missing and the command that installs it, for this system if it's one that's known, and for each
+//! of the known ones otherwise.
+//!
+//! Some tools can be checked for before building at all: [`missing_build_tools`] finds the ones
+//! that crates in the build are known to run but that aren't installed, so that the user is warned
+//! before a long build fails for want of them.
+
+use crate::cargo::Metadata;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt};
+
+/// A system that cgx knows the packages providing build dependencies for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum System {
+    Debian,
+    Fedora,
+    Arch,
+    Alpine,
+    MacOs,
+}
+
+impl System {
+    /// The system cgx is running on, if it's one that's known.
+    pub fn host() -> Option<Self> {
+        if cfg!(target_os = "macos") {
+            return Some(Self::MacOs);
+        }
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+
+        let os_release = std::fs::read_to_string("/etc/os-release").ok()?;
+        Self::from_os_release(&os_release)
+    }
+
+    /// The system described by the contents of `/etc/os-release`, going by its `ID`, then the
+    /// distributions in `ID_LIKE` that it's derived from.
+    fn from_os_release(os_release: &str) -> Option<Self> {
+        let field = |name: &str| {
+            os_release.lines().find_map(|line| {
+                line.strip_prefix(name)
+                    .and_then(|rest| rest.strip_prefix('='))
+                    .map(|value| value.trim().trim_matches('"').to_string())
+            })
+        };
+
+        let ids = field("ID").into_iter().chain(field("ID_LIKE"));
+        ids.flat_map(|ids| ids.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+            .find_map(|id| match id.as_str() {
+                "debian" | "ubuntu" => Some(Self::Debian),
+                "fedora" | "rhel" | "centos" => Some(Self::Fedora),
+                "arch" => Some(Self::Arch),
+                "alpine" => Some(Self::Alpine),
+                _ => None,
+            })
+    }
+}
+
+impl fmt::Display for System {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Debian => "Debian and Ubuntu",
+            Self::Fedora => "Fedora and RHEL",
+            Self::Arch => "Arch",
+            Self::Alpine => "Alpine",
+            Self::MacOs => "macOS",
+        })
+    }
+}
+
+/// Something a build needs that isn't installed, and how to install it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnosis {
+    /// What's missing, like "the OpenSSL development files".
+    pub missing: String,
+
+    /// The commands that install it, on each system they're known for.  Only the one for this
+    /// system, if there is one for it.
+    pub install: Vec<(System, String)>,
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the build needs {}, which isn't installed", self.missing)?;
+
+        match self.install.as_slice() {
+            [] => write!(
+                f,
+                "; install its development package, usually named like `lib<name>-dev` or `<name>-devel`"
+            ),
+            [(_, command)] => write!(f, "; install it with `{}`", command),
+            commands => {
+                let commands: Vec<String> = commands
+                    .iter()
+                    .map(|(system, command)| format!("`{}` ({})", command, system))
+                    .collect();
+                write!(f, "; install it with {}", commands.join(", "))
+            }
+        }
+    }
+}
+
+/// Something build scripts are known to need from the system.
+struct SystemDependency {
+    /// What's missing when it is, as told to the user.
+    missing: &'static str,
+
+    /// Its names to `pkg-config`, whose build-script errors name them.
+    libraries: &'static [&'static str],
+
+    /// Other text in cargo's output that shows it's missing.
+    signs: &'static [&'static str],
+
+    /// The command that installs it on each system.
+    install: &'static [(System, &'static str)],
+
+    /// For a tool that can be checked for before building, the executable, and the environment
+    /// variable that the crates running it take its path from instead.
+    executable: Option<(&'static str, &'static str)>,
+
+    /// The crates whose build scripts run the tool.
+    needed_by: &'static [&'static str],
+
+    /// Crates that provide the tool themselves, so it needn't be installed if one of them is in the
+    /// build.
+    bundled_by: &'static [&'static str],
+}
+
+const SYSTEM_DEPENDENCIES: &[SystemDependency] = &[
+    SystemDependency {
+        missing: "the OpenSSL development files",
+        libraries: &["openssl", "libssl"],
+        signs: &[
+            "Could not find directory of OpenSSL installation",
+            "openssl/opensslv.h: No such file",
+            "cannot find -lssl",
+        ],
+        install: &[
+            (System::Debian, "apt install libssl-dev"),
+            (System::Fedora, "dnf install openssl-devel"),
+            (System::Arch, "pacman -S openssl"),
+            (System::Alpine, "apk add openssl-dev"),
+            (System::MacOs, "brew install openssl@3"),
+        ],
+        executable: None,
+        needed_by: &[],
+        bundled_by: &[],
+    },
+    SystemDependency {
+        missing: "`pkg-config`",
+        libraries: &[],
+        signs: &[
+            "The pkg-config command could not be found",
+            "pkg-config: not found",
+        ],
+        install: &[
+            (System::Debian, "apt install pkg-config"),
+            (System::Fedora, "dnf install pkgconf-pkg-config"),
+            (System::Arch, "pacman -S pkgconf"),
+            (System::Alpine, "apk add pkgconf"),
+            (System::MacOs, "brew install pkg-config"),
+        ],
+        executable: None,
+        needed_by: &[],
+        bundled_by: &[],
+    },
+    SystemDependency {
+        missing: "a C compiler",
+        libraries: &[],
+        signs: &[
+            "linker `cc` not found",
+            "failed to find tool \"cc\"",
+            "Is `cc` installed?",
+        ],
+        install: &[
+            (System::Debian, "apt install build-essential"),
+            (System::Fedora, "dnf install gcc"),
+            (System::Arch, "pacman -S base-devel"),
+            (System::Alpine, "apk add build-base"),
+            (System::MacOs, "xcode-select --install"),
+        ],
+        executable: None,
+        needed_by: &[],
+        bundled_by: &[],
+    },
+    SystemDependency {
+        missing: "`cmake`",
+        libraries: &[],
+        signs: &["is `cmake` not installed?"],
+        install: &[
+            (System::Debian, "apt install cmake"),
+            (System::Fedora, "dnf install cmake"),
+            (System::Arch, "pacman -S cmake"),
+            (System::Alpine, "apk add cmake"),
+            (System::MacOs, "brew install cmake"),
+        ],
+        executable: Some(("cmake", "CMAKE")),
+        needed_by: &["cmake"],
+        bundled_by: &[],
+    },
+    SystemDependency {
+        missing: "the protocol buffers compiler, `protoc`",
+        libraries: &[],
+        signs: &["Could not find `protoc`"],
+        install: &[
+            (System::Debian, "apt install protobuf-compiler"),
+            (System::Fedora, "dnf install protobuf-compiler"),
+            (System::Arch, "pacman -S protobuf"),
+            (System::Alpine, "apk add protoc"),
+            (System::MacOs, "brew install protobuf"),
+        ],
+        executable: Some(("protoc", "PROTOC")),
+        needed_by: &["prost-build"],
+        bundled_by: &["protoc-bin-vendored"],
+    },
+    SystemDependency {
+        missing: "libclang",
+        libraries: &[],
+        signs: &["Unable to find libclang"],
+        install: &[
+            (System::Debian, "apt install libclang-dev"),
+            (System::Fedora, "dnf install clang-devel"),
+            (System::Arch, "pacman -S clang"),
+            (System::Alpine, "apk add clang-dev"),
+            (System::MacOs, "brew install llvm"),
+        ],
+        executable: None,
+        needed_by: &[],
+        bundled_by: &[],
+    },
+    SystemDependency {
+        missing: "the SQLite development files",
+        libraries: &["sqlite3"],
+        signs: &["cannot find -lsqlite3"],
+        install: &[
+            (System::Debian, "apt install libsqlite3-dev"),
+            (System::Fedora, "dnf install sqlite-devel"),
+            (System::Arch, "pacman -S sqlite"),
+            (System::Alpine, "apk add sqlite-dev"),
+            (System::MacOs, "brew install sqlite"),
+        ],
+        executable: None,
+        needed_by: &[],
+        bundled_by: &[],
+    },
+    SystemDependency {
+        missing: "the D-Bus development files",
+        libraries: &["dbus-1"],
+        signs: &[],
+        install: &[
+            (System::Debian, "apt install libdbus-1-dev"),
+            (System::Fedora, "dnf install dbus-devel"),
+            (System::Arch, "pacman -S dbus"),
+            (System::Alpine, "apk add dbus-dev"),
+            (System::MacOs, "brew install dbus"),
+        ],
+        executable: None,
+        needed_by: &[],
+        bundled_by: &[],
+    },
+    SystemDependency {
+        missing: "the udev development files",
+        libraries: &["libudev"],
+        signs: &[],
+        install: &[
+            (System::Debian, "apt install libudev-dev"),
+            (System::Fedora, "dnf install systemd-devel"),
+            (System::Arch, "pacman -S systemd-libs"),
+            (System::Alpine, "apk add eudev-dev"),
+        ],
+        executable: None,
+        needed_by: &[],
+        bundled_by: &[],
+    },
+    SystemDependency {
+        missing: "the ALSA development files",
+        libraries: &["alsa"],
+        signs: &[],
+        install: &[
+            (System::Debian, "apt install libasound2-dev"),
+            (System::Fedora, "dnf install alsa-lib-devel"),
+            (System::Arch, "pacman -S alsa-lib"),
+            (System::Alpine, "apk add alsa-lib-dev"),
+        ],
+        executable: None,
+        needed_by: &[],
+        bundled_by: &[],
+    },
+];
+
+/// What cargo's `output` from a failed build shows to be missing from this system.
+pub fn diagnose(output: &str) -> Vec<Diagnosis> {
+    diagnose_for(output, System::host())
+}
+
+/// What cargo's `output` from a failed build shows to be missing, with the commands to install it
+/// on `system`, or on every known system if it's `None`.
+fn diagnose_for(output: &str, system: Option<System>) -> Vec<Diagnosis> {
+    let missing_libraries = missing_libraries(output);
+    let mut diagnoses = Vec::new();
+
+    for dependency in SYSTEM_DEPENDENCIES {
+        let missing = dependency.signs.iter().any(|sign| output.contains(sign))
+            || dependency
+                .libraries
+                .iter()
+                .any(|library| missing_libraries.contains(library));
+        if missing {
+            diagnoses.push(dependency.diagnosis(system));
+        }
+    }
+
+    // Libraries with no known packages can at least be named
+    for library in missing_libraries {
+        let known = SYSTEM_DEPENDENCIES
+            .iter()
+            .any(|dependency| dependency.libraries.contains(&library));
+        if !known {
+            diagnoses.push(Diagnosis {
+                missing: format!("the system library `{}`", library),
+                install: Vec::new(),
+            });
+        }
+    }
+
+    diagnoses
+}
+
+/// The libraries that the `pkg-config` crate says in `output` it couldn't find, in the order it
+/// said so.
+fn missing_libraries(output: &str) -> Vec<&str> {
+    const PREFIX: &str = "The system library `";
+
+    let mut libraries = Vec::new();
+    for (start, _) in output.match_indices(PREFIX) {
+        let rest = &output[start + PREFIX.len()..];
+        if let Some((library, _)) = rest.split_once('`') {
+            if !libraries.contains(&library) {
+                libraries.push(library);
+            }
+        }
+    }
+    libraries
+}
+
+impl SystemDependency {
+    fn diagnosis(&self, system: Option<System>) -> Diagnosis {
+        let for_system: Vec<(System, String)> = self
+            .install
+            .iter()
+            .filter(|(known, _)| Some(*known) == system)
+            .map(|(known, command)| (*known, (*command).to_string()))
+            .collect();
+
+        Diagnosis {
+            missing: self.missing.to_string(),
+            install: if for_system.is_empty() {
+                self.install
+                    .iter()
+                    .map(|(known, command)| (*known, (*command).to_string()))
+                    .collect()
+            } else {
+                for_system
+            },
+        }
+    }
+}
+
+/// A tool that a crate in a build runs from its build script, but that isn't installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingBuildTool {
+    /// The crate whose build script runs it.
+    pub needed_by: String,
+
+    pub diagnosis: Diagnosis,
+}
+
+/// The tools that crates in the build described by `metadata` are known to run from their build
+/// scripts, but that aren't on the `PATH` or named by the environment variable the crates look for
+/// them in.
+pub fn missing_build_tools(metadata: &Metadata) -> Vec<MissingBuildTool> {
+    // Only the packages actually in the build, not every one that could be with other features
+    let names: HashSet<&str> = match &metadata.resolve {
+        Some(resolve) => {
+            let ids: HashSet<_> = resolve.nodes.iter().map(|node| &node.id).collect();
+            metadata
+                .packages
+                .iter()
+                .filter(|package| ids.contains(&package.id))
+                .map(|package| package.name.as_str())
+                .collect()
+        }
+        None => return Vec::new(),
+    };
+
+    missing_build_tools_in(&names, |executable, env_var| {
+        std::env::var_os(env_var).is_some() || which::which(executable).is_ok()
+    })
+}
+
+fn missing_build_tools_in(
+    names: &HashSet<&str>,
+    is_installed: impl Fn(&str, &str) -> bool,
+) -> Vec<MissingBuildTool> {
+    let system = System::host();
+
+    SYSTEM_DEPENDENCIES
+        .iter()
+        .filter_map(|dependency| {
+            let (executable, env_var) = dependency.executable?;
+            let needed_by = dependency.needed_by.iter().find(|name| names.contains(**name))?;
+            if dependency.bundled_by.iter().any(|name| names.contains(name))
+                || is_installed(executable, env_var)
+            {
+                return None;
+            }
+
+            Some(MissingBuildTool {
+                needed_by: (*needed_by).to_string(),
+                diagnosis: dependency.diagnosis(system),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPENSSL_FAILURE: &str = r#"error: failed to run custom build command for `openssl-sys v0.9.102`
+
+Caused by:
+  process didn't exit successfully: `/tmp/build/debug/build/openssl-sys-1/build-script-main` (exit status: 101)
+  --- stderr
+  thread 'main' panicked at build/find_normal.rs:190:5:
+
+  Could not find directory of OpenSSL installation, and this `-sys` crate cannot
+  proceed without this knowledge. If OpenSSL is installed and this crate had
+  trouble finding it,  you can set the `OPENSSL_DIR` environment variable for the
+  compilation process.
+
+  Make sure you also have the development packages of openssl installed.
+  For example, `libssl-dev` on Ubuntu or `openssl-devel` on Fedora.
+"#;
+
+    #[test]
+    fn recognizes_missing_libraries_and_tools() {
+        let diagnoses = diagnose_for(OPENSSL_FAILURE, Some(System::Debian));
+        assert_eq!(
+            diagnoses,
+            vec![Diagnosis {
+                missing: "the OpenSSL development files".to_string(),
+                install: vec![(System::Debian, "apt install libssl-dev".to_string())],
+            }]
+        );
+        assert_eq!(
+            diagnoses[0].to_string(),
+            "the build needs the OpenSSL development files, which isn't installed; install it with `apt \
+             install libssl-dev`"
+        );
+
+        let output = "The system library `alsa` required by crate `alsa-sys` was not found.\n\
+                      The system library `libfoo` required by crate `foo-sys` was not found.\n\
+                      is `cmake` not installed?";
+        let missing: Vec<String> = diagnose_for(output, None)
+            .into_iter()
+            .map(|diagnosis| diagnosis.missing)
+            .collect();
+        assert_eq!(
+            missing,
+            [
+                "`cmake`",
+                "the ALSA development files",
+                "the system library `libfoo`"
+            ]
+        );
+
+        assert_eq!(diagnose_for("error[E0308]: mismatched types", None), Vec::new());
+    }
+
+    #[test]
+    fn lists_every_system_when_this_one_is_unknown() {
+        let diagnosis = &diagnose_for(OPENSSL_FAILURE, None)[0];
+        assert_eq!(diagnosis.install.len(), 5);
+        assert!(
+            diagnosis
+                .to_string()
+                .contains("`dnf install openssl-devel` (Fedora and RHEL), `pacman -S openssl` (Arch)")
+        );
+
+        // Without an entry for macOS, every system is listed there too
+        let udev = diagnose_for(
+            "The system library `libudev` required by crate `libudev-sys` was not found.",
+            Some(System::MacOs),
+        );
+        assert_eq!(udev[0].install.len(), 4);
+    }
+
+    #[test]
+    fn recognizes_systems_from_os_release() {
+        assert_eq!(
+            System::from_os_release("NAME=\"Ubuntu\"\nID=ubuntu\nID_LIKE=debian\n"),
+            Some(System::Debian)
+        );
+        assert_eq!(
+            System::from_os_release("ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\n"),
+            Some(System::Fedora)
+        );
+        assert_eq!(System::from_os_release("ID=nixos\n"), None);
+    }
+
+    #[test]
+    fn finds_tools_missing_for_crates_in_the_build() {
+        let names: HashSet<&str> = ["tool", "cmake", "prost-build"].into();
+        let missing = missing_build_tools_in(&names, |_, _| false);
+        let needed_by: Vec<&str> = missing.iter().map(|tool| tool.needed_by.as_str()).collect();
+        assert_eq!(needed_by, ["cmake", "prost-build"]);
+
+        assert_eq!(missing_build_tools_in(&names, |_, _| true), Vec::new());
+
+        let bundled: HashSet<&str> = ["tool", "prost-build", "protoc-bin-vendored"].into();
+        assert_eq!(missing_build_tools_in(&bundled, |_, _| false), Vec::new());
+    }
+}
@@ -3,14 +3,23 @@ use crate::{
     cache::Cache,
     cargo::{CargoMetadataOptions, CargoRunner, CargoVerbosity, Metadata},
     cli::BuildOptionsArgs,
-    config::Config,
+    config::{BuildPriority, Config, DefaultBinPolicy, LocalTargetDir},
     crate_resolver::ResolvedSource,
     downloader::DownloadedCrate,
     error,
+    messages::{BuildMessage, MessageReporter},
 };
 use cargo_metadata::Target;
+use chrono::{DateTime, Utc};
 use snafu::ResultExt;
-use std::{borrow::Cow, path::PathBuf, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
 /// Which executable within a crate to build.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
@@ -29,6 +38,57 @@ pub enum BuildTarget {
     Example(String),
 }
 
+/// A binary that was built from source and is stored in the build cache.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CachedBuild {
+    /// Name of the crate the binary was built from.
+    pub name: String,
+
+    /// Version of the crate the binary was built from.
+    pub version: String,
+
+    /// Identifies this exact build among all builds of the crate.
+    ///
+    /// This is derived from the crate name and version, the hash of its source, and the hash of
+    /// the [`BuildOptions`] it was built with, and is what `--pin-build` takes to run this binary
+    /// again.
+    pub build_key: String,
+
+    /// Path to the cached binary.
+    pub binary_path: PathBuf,
+
+    /// What was recorded about the build when it was stored, or [`None`] for builds cached by a
+    /// release of cgx that didn't record this.
+    pub stats: Option<BuildStats>,
+}
+
+/// What was measured when a binary was built from source, recorded in the build cache next to it.
+///
+/// These are used to tell the user what to expect when the same crate has to be built again,
+/// and are shown by `cgx --list-installed -v`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BuildStats {
+    /// When the build finished.
+    pub built_at: DateTime<Utc>,
+
+    /// How long the build took on this machine, including fetching dependencies.
+    #[serde(with = "humantime_serde")]
+    pub duration: Duration,
+
+    /// Size of the built binary in bytes.
+    pub binary_size: u64,
+
+    /// Number of crates in the binary's dependency graph, from its SBOM.
+    pub dependency_count: usize,
+}
+
+impl BuildStats {
+    /// The build duration rounded to whole seconds, formatted for humans (e.g. `2m 5s`).
+    pub fn build_time(&self) -> String {
+        humantime::format_duration(Duration::from_secs(self.duration.as_secs().max(1))).to_string()
+    }
+}
+
 /// Options that control how a crate is built.
 ///
 /// These options map to flags passed to `cargo build` (or `cargo install`).
@@ -86,6 +146,11 @@ pub struct BuildOptions {
     /// Which executable within the crate to build.
     pub build_target: BuildTarget,
 
+    /// How [`BuildTarget::DefaultBin`] is resolved for a crate with several binaries and no
+    /// `default-run`.
+    #[serde(default)]
+    pub default_bin_policy: DefaultBinPolicy,
+
     /// Rust toolchain override to use for this build (e.g., "nightly", "1.70.0", "stable").
     ///
     /// When set, cargo will be invoked with `+{toolchain}` prefix, allowing rustup to
@@ -96,6 +161,26 @@ pub struct BuildOptions {
     ///
     /// Controls the `-v` flags passed to cargo build commands.
     pub cargo_verbosity: CargoVerbosity,
+
+    /// Cargo config that has the crate's dependencies built from vendored copies (corresponds to
+    /// `--config`).
+    ///
+    /// This is set by the builder when `vendor_dependencies` is enabled (see [`crate::vendor`]),
+    /// never from args.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor_config: Option<PathBuf>,
+
+    /// Directory for cargo to put what it builds in (corresponds to `--target-dir`).
+    ///
+    /// This is set by the builder for crates in a local directory, from
+    /// [`Config::local_target_dir`], never from args.  When `None`, cargo picks as it usually
+    /// would.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_dir: Option<PathBuf>,
+
+    /// The CPU and IO priority to run cargo at.
+    #[serde(default, skip_serializing_if = "BuildPriority::is_normal")]
+    pub priority: BuildPriority,
 }
 
 impl Default for BuildOptions {
@@ -111,8 +196,12 @@ impl Default for BuildOptions {
             jobs: None,
             ignore_rust_version: false,
             build_target: BuildTarget::default(),
+            default_bin_policy: DefaultBinPolicy::default(),
             toolchain: None,
             cargo_verbosity: CargoVerbosity::default(),
+            vendor_config: None,
+            target_dir: None,
+            priority: BuildPriority::default(),
         }
     }
 }
@@ -120,21 +209,40 @@ impl Default for BuildOptions {
 impl BuildOptions {
     /// Load build options from config and CLI args, with proper precedence.
     ///
-    /// Config-handled settings (`locked`, `offline`, `toolchain`) come from [`Config`], which
-    /// has already processed CLI overrides like `--locked`, `--unlocked`, `--frozen`, `--offline`,
-    /// and `+toolchain`.
+    /// Config-handled settings (`locked`, `offline`, `toolchain`, `priority`) come from
+    /// [`Config`], which has already processed CLI overrides like `--locked`, `--unlocked`,
+    /// `--frozen`, `--offline`, `--background`, and `+toolchain`.
     ///
-    /// Crate-specific settings (features, profile, target, etc.) come from [`BuildOptionsArgs`].
+    /// Crate-specific settings (features, profile, target, etc.) come from [`BuildOptionsArgs`],
+    /// with `--preset` expanding into the feature flags of that preset in [`Config::presets`].
     ///
     /// The `verbose` parameter is passed separately because it controls both overall cgx
     /// logging (via tracing) and cargo build verbosity (passed to `cargo build`).
+    ///
+    /// Only one `--target` can be given here; see [`Self::load_per_target`] for several.
     pub fn load(config: &Config, args: &BuildOptionsArgs, verbose: u8) -> Result<Self> {
-        // Parse features from CLI string (space or comma separated)
-        let features = if let Some(features_str) = &args.features {
-            Self::parse_features(features_str)
-        } else {
-            Vec::new()
-        };
+        if args.target.len() > 1 {
+            return error::MultipleTargetsNotSupportedSnafu {
+                targets: args.target.join(", "),
+            }
+            .fail();
+        }
+
+        // A preset's features come first, with any given on the CLI (space or comma separated)
+        // added to them
+        let preset = args
+            .preset
+            .as_deref()
+            .map(|name| config.preset(name))
+            .transpose()?;
+        let mut features = preset.map(|preset| preset.features.clone()).unwrap_or_default();
+        if let Some(features_str) = &args.features {
+            for feature in Self::parse_features(features_str) {
+                if !features.contains(&feature) {
+                    features.push(feature);
+                }
+            }
+        }
 
         // Profile: CLI --debug maps to "dev", otherwise use explicit --profile value
         let profile = if args.debug {
@@ -158,20 +266,50 @@ impl BuildOptions {
             locked: config.locked,
             offline: config.offline,
             toolchain: config.toolchain.clone(),
+            default_bin_policy: config.default_bin_policy,
+            priority: config.build_priority,
 
-            // The rest of these come exclusively from CLI args
+            // The rest of these come exclusively from CLI args, and a preset named by them
             features,
-            all_features: args.all_features,
-            no_default_features: args.no_default_features,
+            all_features: args.all_features || preset.is_some_and(|preset| preset.all_features),
+            no_default_features: args.no_default_features
+                || preset.is_some_and(|preset| preset.no_default_features),
             profile,
-            target: args.target.clone(),
+            target: args
+                .target
+                .first()
+                .cloned()
+                .or_else(|| config.container_target.clone()),
             jobs: args.jobs,
             ignore_rust_version: args.ignore_rust_version,
             build_target,
             cargo_verbosity: CargoVerbosity::from_count(verbose),
+            vendor_config: None,
+            target_dir: None,
         })
     }
 
+    /// Load the build options for each `--target` given, in the order they were given, the same way
+    /// as [`Self::load`].
+    ///
+    /// If no target was given, this is just the one set of build options for the default target.
+    pub fn load_per_target(config: &Config, args: &BuildOptionsArgs, verbose: u8) -> Result<Vec<Self>> {
+        if args.target.len() <= 1 {
+            return Ok(vec![Self::load(config, args, verbose)?]);
+        }
+
+        args.target
+            .iter()
+            .map(|target| {
+                let args = BuildOptionsArgs {
+                    target: vec![target.clone()],
+                    ..args.clone()
+                };
+                Self::load(config, &args, verbose)
+            })
+            .collect()
+    }
+
     /// Parse a feature string into a vector of feature names.
     ///
     /// Handles both comma-separated and space-separated features.
@@ -184,7 +322,7 @@ impl BuildOptions {
     }
 }
 
-pub trait CrateBuilder {
+pub trait CrateBuilder: Send + Sync {
     /// List the targets in the given crate that can be build using [`Self::build`].
     ///
     /// [`Self::build`] can run any bin or example target in the crate.
@@ -213,11 +351,13 @@ pub(crate) fn create_builder(
     config: Config,
     cache: Cache,
     cargo_runner: Arc<dyn CargoRunner>,
+    reporter: MessageReporter,
 ) -> impl CrateBuilder {
     RealCrateBuilder {
         config,
         cache,
         cargo_runner,
+        reporter,
     }
 }
 
@@ -226,6 +366,7 @@ struct RealCrateBuilder {
     config: Config,
     cache: Cache,
     cargo_runner: Arc<dyn CargoRunner>,
+    reporter: MessageReporter,
 }
 
 impl CrateBuilder for RealCrateBuilder {
@@ -242,6 +383,20 @@ impl CrateBuilder for RealCrateBuilder {
     }
 
     fn build(&self, krate: &DownloadedCrate, options: &BuildOptions) -> Result<PathBuf> {
+        // Point cargo at the vendored dependencies, if there are to be any, before it's asked about
+        // them at all, so that an offline build needs nothing else
+        let vendored;
+        let options = match self.vendor_config(krate, options)? {
+            Some(vendor_config) => {
+                vendored = BuildOptions {
+                    vendor_config: Some(vendor_config),
+                    ..options.clone()
+                };
+                &vendored
+            }
+            None => options,
+        };
+
         // Gather metadata about the crate in its current source form.
         // The act of building will re-gather the metadata after the build, but this is needed to
         // resolve target and package information before building.
@@ -268,7 +423,11 @@ impl CrateBuilder for RealCrateBuilder {
         // build` does), and that doesn't seem worth it.  So local crates are always built directly
         // from their sources, and never cached
         if matches!(krate.resolved.source, ResolvedSource::LocalDir { .. }) {
-            let (binary_path, _sbom) = self.build_uncached(krate, options.as_ref(), &metadata)?;
+            let options = BuildOptions {
+                target_dir: self.local_target_dir(krate),
+                ..options.into_owned()
+            };
+            let (binary_path, _sbom) = self.build_uncached(krate, &options, &metadata)?;
             return Ok(binary_path);
         }
 
@@ -280,6 +439,48 @@ impl CrateBuilder for RealCrateBuilder {
 }
 
 impl RealCrateBuilder {
+    /// The directory cargo is to build `krate`, a crate in a local directory, in, or `None` to leave
+    /// that to cargo (see [`LocalTargetDir`]).
+    ///
+    /// Since cargo reports where the binary ended up, finding it works the same wherever this is.
+    fn local_target_dir(&self, krate: &DownloadedCrate) -> Option<PathBuf> {
+        match &self.config.local_target_dir {
+            LocalTargetDir::Workspace => None,
+            LocalTargetDir::Isolated => {
+                let mut hasher = DefaultHasher::new();
+                krate.crate_path.hash(&mut hasher);
+                Some(self.config.build_dir.join("local").join(format!(
+                    "{}-{:016x}",
+                    krate.resolved.name,
+                    hasher.finish()
+                )))
+            }
+            LocalTargetDir::Path(path) => Some(krate.crate_path.join(path)),
+        }
+    }
+
+    /// The cargo config to build `krate` with its vendored dependencies, if `vendor_dependencies`
+    /// is enabled (see [`crate::vendor`]).
+    ///
+    /// A crate in a local directory is built in place with whatever its dependencies are at the
+    /// time, so there's no point vendoring them.
+    fn vendor_config(&self, krate: &DownloadedCrate, options: &BuildOptions) -> Result<Option<PathBuf>> {
+        if !self.config.vendor_dependencies
+            || matches!(krate.resolved.source, ResolvedSource::LocalDir { .. })
+        {
+            return Ok(None);
+        }
+
+        let vendor_dir = self.cache.vendor_path(&krate.resolved);
+        crate::vendor::vendor_config(
+            self.cargo_runner.as_ref(),
+            &self.config,
+            &vendor_dir,
+            krate,
+            options,
+        )
+    }
+
     /// List the targets in the given crate that can be build using [`Self::build`].
     ///
     /// Unlike the public [`CrateBuilder::list_targets`], this internal version takes the cargo
@@ -364,6 +565,43 @@ impl RealCrateBuilder {
             options.build_target.clone()
         };
 
+        let build_target = Self::select_build_target(krate, options, build_target, &bins, &examples)?;
+
+        // Cargo leaves a target out of the build entirely if the features it requires aren't
+        // enabled, which would otherwise only show up as a confusing build failure
+        let (kind, target) = match &build_target {
+            BuildTarget::Bin(name) => ("binary", bins.iter().find(|t| t.name == *name)),
+            BuildTarget::Example(name) => ("example", examples.iter().find(|t| t.name == *name)),
+            BuildTarget::DefaultBin => ("binary", None),
+        };
+        let package = metadata
+            .packages
+            .iter()
+            .find(|p| p.name.as_str() == krate.resolved.name);
+        if let (Some(target), Some(package)) = (target, package) {
+            let missing = missing_required_features(&package.features, &target.required_features, options);
+            if !missing.is_empty() {
+                return error::TargetRequiresFeaturesSnafu {
+                    kind,
+                    package: krate.resolved.name.clone(),
+                    target: target.name.clone(),
+                    features: missing,
+                }
+                .fail();
+            }
+        }
+
+        Ok(build_target)
+    }
+
+    /// Pick the target to build out of those the package has, given the one `options` asks for.
+    fn select_build_target(
+        krate: &DownloadedCrate,
+        options: &BuildOptions,
+        build_target: BuildTarget,
+        bins: &[Target],
+        examples: &[Target],
+    ) -> Result<BuildTarget> {
         // Select a specific build target.  There are a few possible permutations here:
         // - The user didn't explicitly ask for a particular target, but the package has a
         // `default_run`, so act like the user specified that explicitly and proceed further.
@@ -371,7 +609,7 @@ impl RealCrateBuilder {
         // runnable targets, fail if it's not, then we're good
         // - The user didn't explicitly ask for a particular target, and the package does not have
         // a `default_run`.  If the package has exactly one binary, use that.  If it has no
-        // binaries, fail.  If it has multiple binaries, fail.
+        // binaries, fail.  If it has multiple binaries, it's up to the `default_bin_policy`.
 
         match build_target {
             BuildTarget::DefaultBin => {
@@ -389,12 +627,23 @@ impl RealCrateBuilder {
                         Ok(BuildTarget::Bin(bins[0].name.clone()))
                     }
                     _ => {
-                        // Multiple binaries - ambiguous
-                        error::AmbiguousBinaryTargetSnafu {
-                            package: krate.resolved.name.clone(),
-                            available: bins.iter().map(|t| t.name.clone()).collect::<Vec<_>>(),
+                        // Multiple binaries - ambiguous, unless the policy picks one
+                        let chosen = match options.default_bin_policy {
+                            DefaultBinPolicy::Error => None,
+                            DefaultBinPolicy::MatchCrateName => {
+                                bins.iter().find(|t| t.name == krate.resolved.name)
+                            }
+                            DefaultBinPolicy::First => bins.first(),
+                        };
+
+                        match chosen {
+                            Some(bin) => Ok(BuildTarget::Bin(bin.name.clone())),
+                            None => error::AmbiguousBinaryTargetSnafu {
+                                package: krate.resolved.name.clone(),
+                                available: bins.iter().map(|t| t.name.clone()).collect::<Vec<_>>(),
+                            }
+                            .fail(),
                         }
-                        .fail()
                     }
                 }
             }
@@ -464,14 +713,50 @@ impl RealCrateBuilder {
         krate: &DownloadedCrate,
         options: &BuildOptions,
         metadata: &Metadata,
-    ) -> Result<(PathBuf, crate::sbom::CycloneDx)> {
+    ) -> Result<(PathBuf, Option<crate::sbom::BuildSbom>)> {
         let build_dir = self.prepare_build_dir(krate, options)?;
 
         let package_name = Self::resolve_package_name(metadata, &krate.resolved.name)?;
+        let log_path = self.build_log_path(krate, options);
+
+        // Warn up front about tools the build is known to need but won't find, rather than only
+        // after however long it takes to get to the crate that runs them
+        for tool in crate::build_diagnostics::missing_build_tools(metadata) {
+            tracing::warn!(
+                "{} is built with the `{}` crate, so {}",
+                krate.resolved.name,
+                tool.needed_by,
+                tool.diagnosis
+            );
+        }
+
+        self.reporter
+            .report(|| BuildMessage::planned(metadata.packages.len()));
 
-        let binary_path = self
+        // Download dependencies as a separate step, so that a network failure is retried and
+        // reported as such rather than as a compile error, and so that an offline build fails up
+        // front if anything it needs isn't available locally.
+        let built = self
             .cargo_runner
-            .build(&build_dir, package_name.as_deref(), options)?;
+            .fetch(&build_dir, options, &self.config.http)
+            .and_then(|()| {
+                self.cargo_runner
+                    .build(&build_dir, package_name.as_deref(), options, &log_path)
+            });
+
+        let binary_path = match built {
+            Ok(binary_path) => binary_path,
+            Err(e @ error::Error::Cancelled) => {
+                // Build dirs are normally left for inspection, but one from a build that was
+                // cancelled partway through is of no use to anyone.  A local crate is built in
+                // place, so there's nothing of ours to remove.
+                if !matches!(krate.resolved.source, ResolvedSource::LocalDir { .. }) {
+                    let _ = std::fs::remove_dir_all(crate::helpers::long_path(&build_dir));
+                }
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
 
         // Re-read metadata from the build directory AFTER building. This is critical for accurate
         // SBOM generation: if --unlocked was used, Cargo.lock was deleted from the build dir and
@@ -485,7 +770,7 @@ impl RealCrateBuilder {
             .metadata(&build_dir, &CargoMetadataOptions::from(options))?;
 
         // Generate SBOM from the post-build metadata
-        let sbom = crate::sbom::generate_sbom(&metadata, &krate.resolved, options)?;
+        let sbom = crate::sbom::for_build(&metadata, &krate.resolved, options)?;
 
         Ok((binary_path, sbom))
     }
@@ -507,8 +792,10 @@ impl RealCrateBuilder {
             return Ok(krate.crate_path.clone());
         }
 
-        std::fs::create_dir_all(&self.config.build_dir).with_context(|_| error::IoSnafu {
-            path: self.config.build_dir.clone(),
+        std::fs::create_dir_all(crate::helpers::long_path(&self.config.build_dir)).with_context(|_| {
+            error::IoSnafu {
+                path: self.config.build_dir.clone(),
+            }
         })?;
 
         let temp_dir = tempfile::Builder::new()
@@ -519,14 +806,32 @@ impl RealCrateBuilder {
             })?;
 
         let temp_path = temp_dir.path().to_path_buf();
+
+        // Files can only be cloned within a filesystem, so otherwise every byte of the source is
+        // copied, which is worth knowing when a large crate is slow to start building
+        if crate::helpers::same_filesystem(&krate.crate_path, &temp_path) == Some(false) {
+            tracing::debug!(
+                source = %krate.crate_path.display(),
+                build_dir = %self.config.build_dir.display(),
+                "Source cache and build dir are on different filesystems, so sources are copied rather than cloned"
+            );
+        }
+
+        let started = std::time::Instant::now();
         crate::helpers::copy_source_tree(&krate.crate_path, &temp_path)?;
+        tracing::debug!(
+            "Populated build dir {} in {:?}",
+            temp_path.display(),
+            started.elapsed()
+        );
 
         // If locked is false (--unlocked was passed), delete Cargo.lock
         // to force cargo to resolve dependencies fresh
         if !options.locked {
             let lock_path = temp_path.join("Cargo.lock");
             if lock_path.exists() {
-                std::fs::remove_file(&lock_path).with_context(|_| error::IoSnafu { path: lock_path })?;
+                std::fs::remove_file(crate::helpers::long_path(&lock_path))
+                    .with_context(|_| error::IoSnafu { path: lock_path })?;
             }
         }
 
@@ -534,6 +839,22 @@ impl RealCrateBuilder {
         Ok(temp_path)
     }
 
+    /// Where cargo's output is saved while building `krate`, so that a failed build can be looked
+    /// into after the fact.
+    ///
+    /// Logs are kept in the build dir alongside the build dirs themselves, one per crate version
+    /// and target, so a later build of the same thing replaces the log of the one before.
+    fn build_log_path(&self, krate: &DownloadedCrate, options: &BuildOptions) -> PathBuf {
+        let mut file_name = format!("{}-{}", krate.resolved.name, krate.resolved.version);
+        if let Some(target) = &options.target {
+            file_name.push('-');
+            file_name.push_str(target);
+        }
+        file_name.push_str(".log");
+
+        self.config.build_dir.join("logs").join(file_name)
+    }
+
     /// Given metadata for a workspace and the name of a crate, determine the appropriate
     /// `--package` argument to pass to cargo, if any.
     ////
@@ -568,6 +889,45 @@ impl RealCrateBuilder {
     }
 }
 
+/// The features in `required` (a target's `required-features`) that a build with `options` of a
+/// package with `features` (its `[features]` table) doesn't enable, sorted.
+///
+/// A feature is enabled if it's asked for, is `default` and default features aren't turned off,
+/// or is enabled by another enabled feature.  Features of dependencies can't be told from the
+/// package alone, so one in `required` is taken to be enabled.
+///
+/// This is also how [`crate::runner::missing_features`] checks a tool's `feature_hints`.
+pub(crate) fn missing_required_features(
+    features: &BTreeMap<String, Vec<String>>,
+    required: &[String],
+    options: &BuildOptions,
+) -> Vec<String> {
+    if options.all_features {
+        return Vec::new();
+    }
+
+    let mut enabled: BTreeSet<&str> = options.features.iter().map(String::as_str).collect();
+    if !options.no_default_features {
+        enabled.insert("default");
+    }
+    let mut pending: Vec<&str> = enabled.iter().copied().collect();
+    while let Some(feature) = pending.pop() {
+        for implied in features.get(feature).into_iter().flatten() {
+            if !implied.contains('/') && !implied.starts_with("dep:") && enabled.insert(implied) {
+                pending.push(implied);
+            }
+        }
+    }
+
+    required
+        .iter()
+        .filter(|feature| !feature.contains('/') && !enabled.contains(feature.as_str()))
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -590,13 +950,14 @@ mod tests {
         fs::create_dir_all(&config.bin_dir).unwrap();
         fs::create_dir_all(&config.build_dir).unwrap();
 
-        let cache = Cache::new(config.clone(), crate::messages::MessageReporter::null());
-        let cargo_runner = Arc::new(find_cargo(crate::messages::MessageReporter::null()).unwrap());
+        let cache = Cache::new(config.clone(), MessageReporter::null());
+        let cargo_runner = Arc::new(find_cargo(MessageReporter::null()).unwrap());
 
         let builder = RealCrateBuilder {
             config,
             cache,
             cargo_runner,
+            reporter: MessageReporter::null(),
         };
 
         (builder, temp_dir)
@@ -782,7 +1143,7 @@ mod tests {
         #[test]
         fn builds_all_testcases_with_bins() {
             let (builder, _temp) = test_builder();
-            let cargo = find_cargo(crate::messages::MessageReporter::null()).unwrap();
+            let cargo = find_cargo(MessageReporter::null()).unwrap();
 
             for tc in CrateTestCase::all() {
                 let metadata_opts = CargoMetadataOptions::default();
@@ -977,6 +1338,40 @@ mod tests {
                         && available.contains(&"bin2".to_string())
             );
         }
+
+        #[test]
+        fn default_bin_policy_picks_among_multiple_bins() {
+            let (builder, _temp) = test_builder();
+            let tc = CrateTestCase::single_crate_multiple_bins();
+            let krate = fake_downloaded_crate(
+                &tc,
+                FakeSourceType::Registry {
+                    version: "1.0.0".to_string(),
+                },
+                None,
+            );
+
+            // Neither binary is named after the crate
+            let options = BuildOptions {
+                profile: Some("dev".to_string()),
+                default_bin_policy: DefaultBinPolicy::MatchCrateName,
+                ..Default::default()
+            };
+            assert_matches!(
+                builder.build(&krate, &options),
+                Err(Error::AmbiguousBinaryTarget { .. })
+            );
+
+            let options = BuildOptions {
+                default_bin_policy: DefaultBinPolicy::First,
+                ..options
+            };
+            let binary = builder.build(&krate, &options).unwrap();
+            assert_eq!(
+                binary.file_name().unwrap().to_str().unwrap(),
+                expected_bin_name("bin1")
+            );
+        }
     }
 
     mod workspace_handling {
@@ -1061,7 +1456,7 @@ mod tests {
             assert_eq!(binary1_name, expected_bin_name("timestamp"));
             let output1 = run_timestamp_binary(&binary1);
 
-            std::thread::sleep(std::time::Duration::from_millis(100));
+            std::thread::sleep(Duration::from_millis(100));
 
             let krate2 = fake_downloaded_crate(
                 &tc,
@@ -1162,9 +1557,10 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "sbom")]
     mod dependency_resolution {
         use super::*;
-        use crate::sbom::tests::get_sbom_component_version;
+        use crate::sbom::cyclonedx::tests::get_sbom_component_version;
 
         #[test]
         fn locked_vs_unlocked_produces_different_cache_entries() {
@@ -1218,7 +1614,7 @@ mod tests {
             );
             assert!(version.starts_with("1.0."), "Should still be serde 1.0.x");
 
-            crate::sbom::tests::assert_sboms_ne(&sbom1, &sbom2);
+            crate::sbom::cyclonedx::tests::assert_sboms_ne(&sbom1, &sbom2);
             assert_cache_miss(&binary1, &binary2);
         }
 
@@ -1303,7 +1699,7 @@ mod tests {
             assert!(output1.features.contains(&"gonkolator".to_string()));
             assert!(output2.features.contains(&"frobnulator".to_string()));
 
-            crate::sbom::tests::assert_sboms_ne(&sbom1, &sbom2);
+            crate::sbom::cyclonedx::tests::assert_sboms_ne(&sbom1, &sbom2);
             assert_cache_miss_by_timestamp(&output1, &output2);
         }
 
@@ -1424,6 +1820,30 @@ mod tests {
         }
 
         #[test]
+        fn local_dir_builds_in_isolated_target_dir() {
+            let (mut builder, _temp) = test_builder();
+            builder.config.local_target_dir = LocalTargetDir::Isolated;
+            let tc = CrateTestCase::simple_bin_no_deps();
+
+            let krate = fake_downloaded_crate(&tc, FakeSourceType::LocalDir, None);
+
+            let options = BuildOptions {
+                profile: Some("dev".to_string()),
+                ..Default::default()
+            };
+
+            let binary = builder.build(&krate, &options).unwrap();
+
+            assert!(binary.starts_with(builder.config.build_dir.join("local")));
+            assert!(binary.is_file());
+            assert_eq!(
+                binary.file_name().unwrap().to_str().unwrap(),
+                expected_bin_name("simple-bin-no-deps")
+            );
+        }
+
+        #[test]
+        #[cfg(feature = "sbom")]
         fn registry_source_cached_with_sbom() {
             let (builder, _temp) = test_builder();
             let tc = CrateTestCase::simple_bin_no_deps();
@@ -1465,6 +1885,7 @@ mod tests {
         }
 
         #[test]
+        #[cfg(feature = "sbom")]
         fn git_source_cached_with_sbom() {
             let (builder, _temp) = test_builder();
             let tc = CrateTestCase::simple_bin_no_deps();
@@ -1508,6 +1929,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "sbom")]
     mod proc_macro_detection {
         use super::*;
 
@@ -1555,6 +1977,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn required_features_follow_defaults_and_implied_features() {
+        let features = BTreeMap::from([
+            ("default".to_string(), vec!["cli".to_string()]),
+            ("cli".to_string(), vec!["dep:clap".to_string()]),
+            (
+                "full".to_string(),
+                vec!["json".to_string(), "serde/derive".to_string()],
+            ),
+            ("json".to_string(), vec![]),
+        ]);
+        let required = vec!["cli".to_string(), "json".to_string(), "serde/std".to_string()];
+        let options = |features: &[&str], no_default_features: bool| BuildOptions {
+            features: features.iter().map(|f| (*f).to_string()).collect(),
+            no_default_features,
+            ..BuildOptions::default()
+        };
+
+        assert_eq!(
+            missing_required_features(&features, &required, &options(&[], false)),
+            vec!["json"]
+        );
+        assert!(missing_required_features(&features, &required, &options(&["full"], false)).is_empty());
+        assert_eq!(
+            missing_required_features(&features, &required, &options(&["full"], true)),
+            vec!["cli"]
+        );
+        assert!(
+            missing_required_features(
+                &features,
+                &required,
+                &BuildOptions {
+                    all_features: true,
+                    ..options(&[], true)
+                }
+            )
+            .is_empty()
+        );
+    }
+
     mod build_options {
         use super::*;
         use crate::cli::CliArgs;
@@ -1633,6 +2095,70 @@ mod tests {
             }
         }
 
+        mod presets {
+            use super::*;
+            use crate::config::{FeaturePreset, ToolConfig};
+
+            fn config_with_preset() -> Config {
+                let mut config = Config::default();
+                config.presets.insert(
+                    "full".to_string(),
+                    FeaturePreset {
+                        features: vec!["a".to_string(), "b".to_string()],
+                        all_features: false,
+                        no_default_features: true,
+                    },
+                );
+                config
+            }
+
+            /// Test that `--preset` expands into the preset's feature flags, with features from
+            /// `--features` added to them.
+            #[test]
+            fn preset_expands_into_feature_flags() {
+                let config = config_with_preset();
+                let args = CliArgs::parse_from_test_args(["--preset", "full", "--features", "b,c", "tool"]);
+                let options = BuildOptions::load(&config, &args.build_options, args.verbose).unwrap();
+
+                assert_eq!(options.features, vec!["a", "b", "c"]);
+                assert!(!options.all_features);
+                assert!(options.no_default_features);
+            }
+
+            /// Test that an undefined preset is an error naming the presets there are.
+            #[test]
+            fn unknown_preset() {
+                let config = config_with_preset();
+                let args = CliArgs::parse_from_test_args(["--preset", "ful", "tool"]);
+
+                assert_matches!(
+                    BuildOptions::load(&config, &args.build_options, args.verbose),
+                    Err(Error::UnknownPreset { name, defined }) if name == "ful" && defined == ["full"]
+                );
+            }
+
+            /// Test that a tool's preset is used unless `--preset` names another.
+            #[test]
+            fn tool_preset() {
+                let mut config = config_with_preset();
+                config
+                    .presets
+                    .insert("none".to_string(), FeaturePreset::default());
+                let tool_config: ToolConfig = toml::from_str(r#"preset = "full""#).unwrap();
+                config.tools.insert("taplo".to_string(), tool_config);
+
+                let args = CliArgs::parse_from_test_args(["taplo"]);
+                let tool_args = args.build_options.for_tool(&config, Some("taplo"));
+                let options = BuildOptions::load(&config, &tool_args, args.verbose).unwrap();
+                assert_eq!(options.features, vec!["a", "b"]);
+
+                let args = CliArgs::parse_from_test_args(["--preset", "none", "taplo"]);
+                let tool_args = args.build_options.for_tool(&config, Some("taplo"));
+                let options = BuildOptions::load(&config, &tool_args, args.verbose).unwrap();
+                assert!(options.features.is_empty());
+            }
+        }
+
         mod profile_selection {
             use super::*;
 
@@ -1843,6 +2369,38 @@ mod tests {
                 assert_eq!(options.target, Some("x86_64-unknown-linux-gnu".to_string()));
             }
 
+            /// Test that repeated `--target` flags give one set of build options per target.
+            #[test]
+            fn multiple_targets() {
+                let config = Config::default();
+                let args = CliArgs::parse_from_test_args([
+                    "--target",
+                    "x86_64-unknown-linux-musl",
+                    "--target",
+                    "aarch64-unknown-linux-musl",
+                    "tool",
+                ]);
+
+                let options =
+                    BuildOptions::load_per_target(&config, &args.build_options, args.verbose).unwrap();
+                let targets = options
+                    .iter()
+                    .map(|options| options.target.as_deref())
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    targets,
+                    vec![
+                        Some("x86_64-unknown-linux-musl"),
+                        Some("aarch64-unknown-linux-musl")
+                    ]
+                );
+
+                assert_matches!(
+                    BuildOptions::load(&config, &args.build_options, args.verbose),
+                    Err(Error::MultipleTargetsNotSupported { .. })
+                );
+            }
+
             /// Test that `--jobs` flag is passed through.
             #[test]
             fn jobs() {
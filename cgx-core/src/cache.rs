@@ -1,35 +1,65 @@
 use crate::{
     Result,
     bin_resolver::ResolvedBinary,
-    builder::BuildOptions,
-    config::{Config, UsePrebuiltBinaries},
+    builder::{BuildOptions, BuildStats, CachedBuild},
+    config::{BinaryProvider, Config, PrebuiltOrigin, UsePrebuiltBinaries},
     crate_resolver::{ResolvedCrate, ResolvedSource},
     cratespec::{CrateSpec, Forge, RegistrySource},
     downloader::DownloadedCrate,
     error,
+    helpers::{SHORT_HASH_LEN, install_executable, long_path},
+    memory_cache::MemoryCache,
     messages::{BuildCacheMessage, CrateResolutionMessage, PrebuiltBinaryMessage, SourceMessage},
+    singleflight::SingleFlight,
 };
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use sha2::{Digest, Sha256};
 use snafu::ResultExt;
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{BTreeMap, BTreeSet, hash_map::DefaultHasher},
     fs,
     hash::{Hash, Hasher},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
 };
 use tracing::*;
 
+/// Length of the build keys that identify binaries in the build cache.
+const BUILD_KEY_LEN: usize = 16;
+
+/// The most entries of each kind kept in memory (see [`CacheInner`]).
+const MEMORY_CACHE_ENTRIES: usize = 256;
+
+/// Name of the file next to each cached binary that holds its [`BuildStats`].
+const BUILD_STATS_FILE_NAME: &str = "build-stats.json";
+
+/// Name of the file next to each cached binary that holds its SBOM.
+pub(crate) const SBOM_FILE_NAME: &str = "sbom.cyclonedx.json";
+
+/// Record in the current span whether the cache had what was looked up (see [`crate::telemetry`]).
+///
+/// Only spans with a `cache` field take this; in any other it's a no-op.
+fn record_cache_outcome(hit: bool) {
+    Span::current().record("cache", if hit { "hit" } else { "miss" });
+}
+
 /// A cache entry wrapping a value with timestamp metadata.
 ///
 /// This generic wrapper is used for any cached data that has an expiration policy.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 struct CacheEntry<T> {
     value: T,
     cached_at: DateTime<Utc>,
+
+    /// For a resolved crate, the revision of the index it was resolved from, if known (see
+    /// [`CrateResolver::index_revision`]).
+    ///
+    /// [`CrateResolver::index_revision`]: crate::crate_resolver::CrateResolver::index_revision
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index_revision: Option<String>,
 }
 
 impl<T> CacheEntry<T> {
@@ -38,6 +68,7 @@ impl<T> CacheEntry<T> {
         Self {
             value,
             cached_at: Utc::now(),
+            index_revision: None,
         }
     }
 
@@ -58,6 +89,19 @@ impl<T> CacheEntry<T> {
 /// A cache entry for a resolved crate specification.
 type CrateResolveCacheEntry = CacheEntry<ResolvedCrate>;
 
+/// An entry kept in memory, along with the [`FileStamp`] of the cache file it was read from or
+/// written to.
+type MemoryEntry<T> = (FileStamp, CacheEntry<T>);
+
+/// The modification time and length of a cache file, which change whenever it's written, so that
+/// an entry in memory is only used while its file is as it was, and one written since by another
+/// process is read again.
+type FileStamp = (Option<SystemTime>, u64);
+
+fn file_stamp(metadata: &fs::Metadata) -> FileStamp {
+    (metadata.modified().ok(), metadata.len())
+}
+
 /// Manages the various caches that cgx uses to operate.
 ///
 /// The root of the caches is controlled by [`Config::cache_dir`].  Below that are multiple
@@ -77,7 +121,16 @@ impl Cache {
     /// Create a new [`Cache`] with the given configuration and message reporter.
     pub(crate) fn new(config: Config, reporter: crate::messages::MessageReporter) -> Self {
         Self {
-            inner: Arc::new(CacheInner { config, reporter }),
+            inner: Arc::new(CacheInner {
+                config,
+                reporter,
+                resolved_crates: MemoryCache::new(MEMORY_CACHE_ENTRIES),
+                binaries: MemoryCache::new(MEMORY_CACHE_ENTRIES),
+                missing_binaries: MemoryCache::new(MEMORY_CACHE_ENTRIES),
+                binary_lookups: SingleFlight::new(),
+                downloads: SingleFlight::new(),
+                builds: SingleFlight::new(),
+            }),
         }
     }
 
@@ -89,35 +142,92 @@ impl Cache {
     /// - On success, cache the result and return it
     /// - On transient errors (network/IO), fall back to stale cache if available
     /// - On permanent errors, propagate without using stale cache
+    #[cfg(test)]
     pub(crate) fn get_or_resolve_crate<F>(&self, spec: &CrateSpec, resolver: F) -> Result<ResolvedCrate>
     where
         F: FnOnce() -> Result<ResolvedCrate>,
+    {
+        self.get_or_resolve_crate_revalidated(spec, || None, |_| None, resolver)
+    }
+
+    /// Like [`Self::get_or_resolve_crate`], but a non-expired cache entry is only used if it's
+    /// known to be current with the local copy of the registry index.
+    ///
+    /// `index_revision` gets the revision of the local copy of the index, which is recorded with
+    /// each resolution.  While it's unchanged, the cached resolution is what resolving again
+    /// against that copy would give.  Once it has changed, `newer_release` is asked whether the
+    /// index now has a newer version than the cached one that satisfies `spec`, and if it does the
+    /// spec is resolved again, so a release that has cheaply been detected takes effect without
+    /// waiting for the cache entry to expire.  When there's no revision to compare, the cached
+    /// resolution is used as it is, and reported as such since it may be out of date.
+    pub(crate) fn get_or_resolve_crate_revalidated<I, N, F>(
+        &self,
+        spec: &CrateSpec,
+        index_revision: I,
+        newer_release: N,
+        resolver: F,
+    ) -> Result<ResolvedCrate>
+    where
+        I: Fn() -> Option<String>,
+        N: FnOnce(&ResolvedCrate) -> Option<Version>,
+        F: FnOnce() -> Result<ResolvedCrate>,
     {
         self.inner
             .reporter
             .report(|| CrateResolutionMessage::cache_lookup(spec));
+        record_cache_outcome(false);
 
-        let stale_entry = if !self.inner.config.refresh {
+        let stale_entry = if !self.inner.config.refresh.resolve {
             if let Ok(Some(entry)) = self.get_resolved_crate(spec) {
                 let age = entry.age();
-                let ttl = self.inner.config.resolve_cache_timeout;
+                let ttl = self.inner.config.resolve_cache_timeout_for(spec);
 
                 if age < ttl {
-                    let cache_path = self.crate_resolve_cache_path(spec).ok();
-                    if let Some(path) = &cache_path {
+                    let current_revision = index_revision();
+                    let newer_version = match &current_revision {
+                        Some(current) if entry.index_revision.as_ref() != Some(current) => {
+                            newer_release(&entry.value)
+                        }
+                        _ => None,
+                    };
+
+                    if let Some(newer_version) = newer_version {
+                        self.inner.reporter.report(|| {
+                            CrateResolutionMessage::cache_outdated(spec, &entry.value.version, &newer_version)
+                        });
+                    } else {
+                        let cache_path = self.crate_resolve_cache_path(spec).ok();
+                        if let Some(path) = &cache_path {
+                            self.inner.reporter.report(|| {
+                                CrateResolutionMessage::cache_hit(path, age, ttl.saturating_sub(age))
+                            });
+                        }
+                        if current_revision.is_none() {
+                            self.inner.reporter.report(|| {
+                                CrateResolutionMessage::using_cached_resolution(spec, &entry.value, age)
+                            });
+                        } else if entry.index_revision != current_revision {
+                            // Still current as of the index's new revision, so record that to
+                            // not have to check again until it changes once more
+                            let _ = self.put_resolved_crate_entry(
+                                spec,
+                                CacheEntry {
+                                    index_revision: current_revision,
+                                    ..entry.clone()
+                                },
+                            );
+                        }
                         self.inner
                             .reporter
-                            .report(|| CrateResolutionMessage::cache_hit(path, age, ttl.saturating_sub(age)));
+                            .report(|| CrateResolutionMessage::resolved(&entry.value));
+                        record_cache_outcome(true);
+                        return Ok(entry.value);
                     }
+                } else {
                     self.inner
                         .reporter
-                        .report(|| CrateResolutionMessage::resolved(&entry.value));
-                    return Ok(entry.value);
+                        .report(|| CrateResolutionMessage::cache_stale(spec, age));
                 }
-
-                self.inner
-                    .reporter
-                    .report(|| CrateResolutionMessage::cache_stale(spec, age));
                 Some(entry)
             } else {
                 self.inner
@@ -141,17 +251,21 @@ impl Cache {
                 self.inner
                     .reporter
                     .report(|| CrateResolutionMessage::resolved(&resolved));
+                let entry = CacheEntry {
+                    index_revision: index_revision(),
+                    ..CacheEntry::new(resolved.clone())
+                };
                 if let Ok(path) = self.crate_resolve_cache_path(spec) {
-                    let _ = self.put_resolved_crate(spec, &resolved);
+                    let _ = self.put_resolved_crate_entry(spec, entry);
                     self.inner
                         .reporter
                         .report(|| CrateResolutionMessage::cache_stored(&path));
                 } else {
-                    let _ = self.put_resolved_crate(spec, &resolved);
+                    let _ = self.put_resolved_crate_entry(spec, entry);
                 }
                 Ok(resolved)
             }
-            Err(e) if !self.inner.config.refresh && Self::should_use_stale_cache(&e) => {
+            Err(e) if !self.inner.config.refresh.resolve && Self::should_use_stale_cache(&e) => {
                 // If there was already an entry in the cache, but we didn't use it because it was
                 // stale, return it now as a fallback since a stale cache entry is better than
                 // failing with this error
@@ -166,21 +280,121 @@ impl Cache {
                         .report(|| CrateResolutionMessage::resolved(&resolved));
                     Ok(resolved)
                 } else {
-                    Err(e)
+                    self.resolve_from_bin_dir(spec).ok_or(e)
                 }
             }
+            // Offline, there's no stale entry to fall back on, since the spec would have been
+            // resolved from the local copy of the index if it were in it
+            Err(e @ error::Error::OfflineMode { .. }) if !self.inner.config.refresh.resolve => {
+                self.resolve_from_bin_dir(spec).ok_or(e)
+            }
             Err(e) => Err(e),
         }
     }
 
-    /// Get a cached binary resolution result, or resolve it using the provided resolver function.
+    /// Resolve `spec` to the newest version that satisfies it of which there's a binary in
+    /// [`Config::bin_dir`], for when it can't be resolved any other way.
     ///
-    /// Binary resolution results never expire because crates are immutable. Once we determine
-    /// whether a binary exists for a specific version on a specific platform, that answer remains
-    /// valid forever. We cache both positive (binary found) and negative (no binary) results to
-    /// avoid repeatedly checking providers.
+    /// Only versions whose source is still cached are considered, since getting the binary goes
+    /// through the source, and only specs for a registry, since a git spec's commit can't be had
+    /// from the version.
+    fn resolve_from_bin_dir(&self, spec: &CrateSpec) -> Option<ResolvedCrate> {
+        let (name, version, source) = match spec {
+            CrateSpec::CratesIo { name, version } => (name, version, ResolvedSource::CratesIo),
+            CrateSpec::Registry {
+                source,
+                name,
+                version,
+            } => (
+                name,
+                version,
+                ResolvedSource::Registry {
+                    source: source.clone(),
+                },
+            ),
+            _ => return None,
+        };
+        let requirement = version.clone().unwrap_or(VersionReq::STAR);
+
+        let versions = match self.bin_dir_versions(&source) {
+            Ok(versions) => versions,
+            Err(e) => {
+                debug!("Failed to index the binary cache: {}", e);
+                return None;
+            }
+        };
+
+        let resolved = versions
+            .get(name)?
+            .iter()
+            .rev()
+            .filter(|version| requirement.matches(version))
+            .map(|version| ResolvedCrate {
+                name: name.clone(),
+                version: version.clone(),
+                source: source.clone(),
+            })
+            .find(|resolved| matches!(self.get_cached_crate_source(resolved), Ok(Some(_))))?;
+
+        self.inner
+            .reporter
+            .report(|| CrateResolutionMessage::using_cached_binary_version(spec, &resolved));
+        self.inner
+            .reporter
+            .report(|| CrateResolutionMessage::resolved(&resolved));
+        Some(resolved)
+    }
+
+    /// Index the binaries in [`Config::bin_dir`] from `source`, built or pre-built: the versions of
+    /// each crate that there's at least one of, by crate name.
+    fn bin_dir_versions(&self, source: &ResolvedSource) -> Result<BTreeMap<String, BTreeSet<Version>>> {
+        let bin_dir = &self.inner.config.bin_dir;
+        let mut index: BTreeMap<String, BTreeSet<Version>> = BTreeMap::new();
+        if !bin_dir.exists() {
+            return Ok(index);
+        }
+
+        // Pre-built binaries are kept under a hash of the source of their own
+        let source_hashes = [
+            Self::compute_source_hash(source),
+            crate::bin_resolver::prebuilt_source_hash(source),
+        ];
+
+        for crate_dir in Self::read_subdirs(bin_dir)? {
+            let Some((name, version)) = crate_dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(Self::parse_crate_dir_name)
+            else {
+                continue;
+            };
+
+            let mut has_binary = false;
+            for source_dir in source_hashes.iter().map(|hash| crate_dir.join(hash)) {
+                if !source_dir.is_dir() {
+                    continue;
+                }
+                for build_dir in Self::read_subdirs(&source_dir)? {
+                    has_binary |= Self::find_cached_binary(&build_dir)?.is_some();
+                }
+            }
+
+            if has_binary {
+                if let Ok(version) = Version::parse(&version) {
+                    index.entry(name).or_default().insert(version);
+                }
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Get a cached binary resolution result, or resolve it using the provided resolver function.
     ///
-    /// Unlike crate resolution, there is no TTL check - the cache entry is permanent.
+    /// We cache both positive (binary found) and negative (no binary) results to avoid repeatedly
+    /// checking providers.  A binary that was found never expires, because crates are immutable.
+    /// That no binary was found expires after the configured `negative_cache_ttl`, since binaries
+    /// are sometimes added to a release after it's published.
     ///
     /// # Arguments
     ///
@@ -209,15 +423,37 @@ impl Cache {
             return Ok(None);
         }
 
+        let key = self.binary_cache_path(krate)?;
+        self.inner
+            .binary_lookups
+            .run(key, || self.resolve_binary_uncoalesced(krate, resolver))
+    }
+
+    /// [`Self::get_or_resolve_binary`], without waiting for the same lookup by another thread.
+    fn resolve_binary_uncoalesced<F>(
+        &self,
+        krate: &ResolvedCrate,
+        resolver: F,
+    ) -> Result<Option<ResolvedBinary>>
+    where
+        F: FnOnce() -> Result<Option<ResolvedBinary>>,
+    {
         // Check cache unless refresh mode is enabled
-        let use_cache = !self.inner.config.refresh;
+        let use_cache = !self.inner.config.refresh.binary;
+        record_cache_outcome(false);
 
         if use_cache {
             self.inner
                 .reporter
                 .report(|| PrebuiltBinaryMessage::cache_lookup(krate));
 
-            if let Ok(Some(entry)) = self.get_cached_binary(krate) {
+            let negative_cache_ttl = self.inner.config.prebuilt_binaries.negative_cache_ttl;
+            if let Some(entry) = self
+                .get_cached_binary(krate)
+                .ok()
+                .flatten()
+                .filter(|entry| entry.value.is_some() || entry.age() < negative_cache_ttl)
+            {
                 match &entry.value {
                     Some(binary) => {
                         self.inner
@@ -235,6 +471,7 @@ impl Cache {
                     }
                 }
                 // Return the cached result whether it's Some or None
+                record_cache_outcome(true);
                 return Ok(entry.value);
             }
 
@@ -285,21 +522,38 @@ impl Cache {
         downloader: F,
     ) -> Result<DownloadedCrate>
     where
-        F: FnOnce(&std::path::Path) -> Result<()>,
+        F: FnOnce(&Path) -> Result<()>,
+    {
+        let key = self.crate_source_cache_path(resolved)?;
+        self.inner
+            .downloads
+            .run(key, || self.download_crate_uncoalesced(resolved, downloader))
+    }
+
+    /// [`Self::get_or_download_crate`], without waiting for the same download by another thread.
+    fn download_crate_uncoalesced<F>(
+        &self,
+        resolved: &ResolvedCrate,
+        downloader: F,
+    ) -> Result<DownloadedCrate>
+    where
+        F: FnOnce(&Path) -> Result<()>,
     {
         self.inner
             .reporter
             .report(|| SourceMessage::cache_lookup(resolved));
+        record_cache_outcome(false);
 
         // Compute the target cache path
         let cache_path = self.crate_source_cache_path(resolved)?;
 
         // Check if already cached
-        if !self.inner.config.refresh {
+        if !self.inner.config.refresh.source {
             if let Ok(Some(cached)) = self.get_cached_crate_source(resolved) {
                 self.inner
                     .reporter
                     .report(|| SourceMessage::cache_hit(&cached.crate_path));
+                record_cache_outcome(true);
                 return Ok(cached);
             }
         } else {
@@ -309,7 +563,7 @@ impl Cache {
                     "Refresh mode: removing existing source cache at {}",
                     cache_path.display()
                 );
-                let _ = fs::remove_dir_all(&cache_path);
+                let _ = fs::remove_dir_all(long_path(&cache_path));
             }
         }
 
@@ -321,7 +575,7 @@ impl Cache {
 
         // Ensure parent directory exists
         let parent = cache_path.parent().expect("BUG: Cache path has no parent");
-        fs::create_dir_all(parent).with_context(|_| error::IoSnafu {
+        fs::create_dir_all(long_path(parent)).with_context(|_| error::IoSnafu {
             path: parent.to_path_buf(),
         })?;
 
@@ -330,8 +584,11 @@ impl Cache {
             parent: parent.to_path_buf(),
         })?;
 
-        // Call the downloader with the temp path
+        // Call the downloader with the temp path.  A download that was cancelled partway through
+        // may have left some of the files behind, so that's checked for before anything is kept;
+        // returning drops `temp_dir`, which removes them.
         downloader(temp_dir.path())?;
+        crate::cancel::check()?;
         self.inner
             .reporter
             .report(|| SourceMessage::downloaded(temp_dir.path()));
@@ -340,7 +597,7 @@ impl Cache {
         // Use keep() to prevent temp_dir cleanup
         let temp_path = temp_dir.keep();
 
-        match fs::rename(&temp_path, &cache_path) {
+        match fs::rename(long_path(&temp_path), long_path(&cache_path)) {
             Ok(()) => {
                 self.inner
                     .reporter
@@ -354,7 +611,7 @@ impl Cache {
             Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
                 // Someone else won the race - that's fine, use their result
                 // Clean up our temp dir
-                let _ = fs::remove_dir_all(&temp_path);
+                let _ = fs::remove_dir_all(long_path(&temp_path));
                 Ok(DownloadedCrate {
                     resolved: resolved.clone(),
                     crate_path: cache_path,
@@ -362,7 +619,7 @@ impl Cache {
             }
             Err(e) => {
                 // Some other error during rename - clean up and propagate
-                let _ = fs::remove_dir_all(&temp_path);
+                let _ = fs::remove_dir_all(long_path(&temp_path));
                 Err(e).with_context(|_| error::RenameFileSnafu {
                     src: temp_path.clone(),
                     dst: cache_path.clone(),
@@ -376,20 +633,17 @@ impl Cache {
     /// Returns `None` if there is no cached entry or if reading the cache fails.
     fn get_resolved_crate(&self, spec: &CrateSpec) -> Result<Option<CacheEntry<ResolvedCrate>>> {
         let cache_file = self.crate_resolve_cache_path(spec)?;
-        if !cache_file.exists() {
-            return Ok(None);
-        }
-
-        let contents = fs::read_to_string(&cache_file).with_context(|_| error::IoSnafu {
-            path: cache_file.clone(),
-        })?;
-        let entry: CrateResolveCacheEntry = serde_json::from_str(&contents).context(error::JsonSnafu)?;
-
-        Ok(Some(entry))
+        Self::read_through(&self.inner.resolved_crates, cache_file)
     }
 
     /// Store a resolved crate in the cache for the given [`CrateSpec`].
+    #[cfg(test)]
     fn put_resolved_crate(&self, spec: &CrateSpec, resolved: &ResolvedCrate) -> Result<()> {
+        self.put_resolved_crate_entry(spec, CacheEntry::new(resolved.clone()))
+    }
+
+    /// Store the cache entry of a resolved crate for the given [`CrateSpec`].
+    fn put_resolved_crate_entry(&self, spec: &CrateSpec, entry: CrateResolveCacheEntry) -> Result<()> {
         let cache_file = self.crate_resolve_cache_path(spec)?;
 
         if let Some(parent) = cache_file.parent() {
@@ -398,14 +652,8 @@ impl Cache {
             })?;
         }
 
-        let entry = CacheEntry::new(resolved.clone());
-
         let json = serde_json::to_string_pretty(&entry).context(error::JsonSnafu)?;
-        fs::write(&cache_file, json).with_context(|_| error::IoSnafu {
-            path: cache_file.clone(),
-        })?;
-
-        Ok(())
+        Self::write_through(&self.inner.resolved_crates, cache_file, json, entry)
     }
 
     /// Get a cached binary resolution result for the given [`ResolvedCrate`], if one exists.
@@ -415,17 +663,7 @@ impl Cache {
     /// both positive and negative results.
     fn get_cached_binary(&self, krate: &ResolvedCrate) -> Result<Option<CacheEntry<Option<ResolvedBinary>>>> {
         let cache_file = self.binary_cache_path(krate)?;
-        if !cache_file.exists() {
-            return Ok(None);
-        }
-
-        let contents = fs::read_to_string(&cache_file).with_context(|_| error::IoSnafu {
-            path: cache_file.clone(),
-        })?;
-        let entry: CacheEntry<Option<ResolvedBinary>> =
-            serde_json::from_str(&contents).context(error::JsonSnafu)?;
-
-        Ok(Some(entry))
+        Self::read_through(&self.inner.binaries, cache_file)
     }
 
     /// Store a binary resolution result in the cache for the given [`ResolvedCrate`].
@@ -443,24 +681,163 @@ impl Cache {
         let entry = CacheEntry::new(result.clone());
 
         let json = serde_json::to_string_pretty(&entry).context(error::JsonSnafu)?;
+        Self::write_through(&self.inner.binaries, cache_file, json, entry)
+    }
+
+    /// Whether `provider` was found to have no binary of `krate` for `platform` within the
+    /// configured `negative_cache_ttl`.
+    ///
+    /// Always `false` with `--refresh=binary`, so that every provider is asked again.
+    #[cfg_attr(not(feature = "prebuilt-binaries"), allow(dead_code))]
+    pub(crate) fn is_binary_missing(
+        &self,
+        krate: &ResolvedCrate,
+        platform: &str,
+        provider: BinaryProvider,
+    ) -> bool {
+        if self.inner.config.refresh.binary {
+            return false;
+        }
+
+        let Ok(cache_file) = self.missing_binary_cache_path(krate, platform, provider) else {
+            return false;
+        };
+        let Ok(Some(entry)) = Self::read_through(&self.inner.missing_binaries, cache_file) else {
+            return false;
+        };
+
+        entry.age() < self.inner.config.prebuilt_binaries.negative_cache_ttl
+    }
+
+    /// Record that `provider` has no binary of `krate` for `platform`, so that it isn't asked again
+    /// until the configured `negative_cache_ttl` has passed.
+    #[cfg_attr(not(feature = "prebuilt-binaries"), allow(dead_code))]
+    pub(crate) fn put_missing_binary(
+        &self,
+        krate: &ResolvedCrate,
+        platform: &str,
+        provider: BinaryProvider,
+    ) -> Result<()> {
+        let cache_file = self.missing_binary_cache_path(krate, platform, provider)?;
+
+        if let Some(parent) = cache_file.parent() {
+            fs::create_dir_all(parent).with_context(|_| error::IoSnafu {
+                path: parent.to_path_buf(),
+            })?;
+        }
+
+        let entry = CacheEntry::new(());
+        let json = serde_json::to_string_pretty(&entry).context(error::JsonSnafu)?;
+        Self::write_through(&self.inner.missing_binaries, cache_file, json, entry)
+    }
+
+    /// Read the entry in `cache_file`, if there is one, from `memory` if the file hasn't changed
+    /// since it was kept there, or else from the file, keeping it in `memory` for next time.
+    ///
+    /// Only the file's metadata is read for an entry in memory, which is much cheaper than reading
+    /// and parsing it, and catches a write by another process sharing the cache directory.
+    fn read_through<T: Clone + DeserializeOwned>(
+        memory: &MemoryCache<PathBuf, MemoryEntry<T>>,
+        cache_file: PathBuf,
+    ) -> Result<Option<CacheEntry<T>>> {
+        let stamp = match fs::metadata(&cache_file) {
+            Ok(metadata) => file_stamp(&metadata),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                memory.remove(&cache_file);
+                return Ok(None);
+            }
+            Err(source) => {
+                return Err(error::Error::Io {
+                    path: cache_file,
+                    source,
+                });
+            }
+        };
+        if let Some((kept_stamp, entry)) = memory.get(&cache_file) {
+            if kept_stamp == stamp {
+                return Ok(Some(entry));
+            }
+        }
+
+        let contents = fs::read_to_string(&cache_file).with_context(|_| error::IoSnafu {
+            path: cache_file.clone(),
+        })?;
+        let entry: CacheEntry<T> = serde_json::from_str(&contents).context(error::JsonSnafu)?;
+        memory.insert(cache_file, (stamp, entry.clone()));
+
+        Ok(Some(entry))
+    }
+
+    /// Write `json` to `cache_file`, and keep `entry`, which it's the serialized form of, in
+    /// `memory` so that it needn't be read back.
+    ///
+    /// The entry in memory is dropped before writing, so that if the write fails, the next read
+    /// goes to disk rather than finding an entry that disagrees with it.
+    fn write_through<T: Clone>(
+        memory: &MemoryCache<PathBuf, MemoryEntry<T>>,
+        cache_file: PathBuf,
+        json: String,
+        entry: CacheEntry<T>,
+    ) -> Result<()> {
+        memory.remove(&cache_file);
         fs::write(&cache_file, json).with_context(|_| error::IoSnafu {
             path: cache_file.clone(),
         })?;
+        if let Ok(metadata) = fs::metadata(&cache_file) {
+            memory.insert(cache_file, (file_stamp(&metadata), entry));
+        }
 
         Ok(())
     }
 
+    /// Get the filesystem path of the record that `provider` has no binary of `krate` for
+    /// `platform`.
+    #[cfg_attr(not(feature = "prebuilt-binaries"), allow(dead_code))]
+    fn missing_binary_cache_path(
+        &self,
+        krate: &ResolvedCrate,
+        platform: &str,
+        provider: BinaryProvider,
+    ) -> Result<PathBuf> {
+        #[derive(Serialize)]
+        struct MissingBinaryKey<'a> {
+            name: &'a str,
+            version: &'a Version,
+            source: &'a ResolvedSource,
+            platform: &'a str,
+            provider: BinaryProvider,
+        }
+
+        let key = MissingBinaryKey {
+            name: &krate.name,
+            version: &krate.version,
+            source: &krate.source,
+            platform,
+            provider,
+        };
+        let json = serde_json::to_string(&key).context(error::JsonSnafu)?;
+
+        Ok(self
+            .inner
+            .config
+            .binary_cache_root()
+            .join("binaries")
+            .join("missing")
+            .join(format!("{}.json", Self::compute_hash(json.as_bytes()))))
+    }
+
     /// Get the filesystem path for the binary resolution cache file for a given [`ResolvedCrate`].
     ///
-    /// The cache key includes the crate identity (name, version, source) and the current platform.
+    /// The cache key includes the crate identity (name, version, source) and the acceptable
+    /// platforms.
     /// This ensures that binaries are cached per-platform, which is essential since pre-built
     /// binaries are platform-specific.
     fn binary_cache_path(&self, krate: &ResolvedCrate) -> Result<PathBuf> {
-        let hash = Self::compute_binary_cache_hash(krate)?;
+        let hash = self.compute_binary_cache_hash(krate)?;
         Ok(self
             .inner
             .config
-            .cache_dir
+            .binary_cache_root()
             .join("binaries")
             .join(format!("{}.json", hash)))
     }
@@ -471,23 +848,43 @@ impl Cache {
     /// - Crate name
     /// - Crate version
     /// - Resolved source (crates.io vs git vs forge, etc.)
-    /// - Current platform triple
+    /// - Acceptable target triples, in preference order
+    /// - The binary digests pinned in the tool's config, if any
+    /// - Where pre-built binaries are trusted from, unless that's anywhere
     ///
-    /// This ensures that the same crate on different platforms gets different cache entries.
-    fn compute_binary_cache_hash(krate: &ResolvedCrate) -> Result<String> {
+    /// This ensures that the same crate on different platforms gets different cache entries, and
+    /// that a binary resolved before its digest was pinned (or changed), or from a provider that's
+    /// no longer trusted, is verified again.
+    fn compute_binary_cache_hash(&self, krate: &ResolvedCrate) -> Result<String> {
         #[derive(Serialize)]
         struct BinaryCacheKey<'a> {
             name: &'a str,
-            version: &'a semver::Version,
+            version: &'a Version,
             source: &'a ResolvedSource,
-            platform: &'a str,
+            platforms: &'a [String],
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pinned_sha256: Option<BTreeMap<&'a str, &'a str>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            trusted_origin: Option<PrebuiltOrigin>,
         }
 
         let key = BinaryCacheKey {
             name: &krate.name,
             version: &krate.version,
             source: &krate.source,
-            platform: build_context::TARGET,
+            platforms: &self.inner.config.prebuilt_binaries.target_preference(),
+            pinned_sha256: self
+                .inner
+                .config
+                .tool_prebuilt_sha256(&krate.name)
+                .map(|digests| {
+                    digests
+                        .iter()
+                        .map(|(target, digest)| (target.as_str(), digest.as_str()))
+                        .collect()
+                }),
+            trusted_origin: Some(self.inner.config.prebuilt_binaries.trust_prebuilt_only_from)
+                .filter(|origin| *origin != PrebuiltOrigin::Anywhere),
         };
 
         let json = serde_json::to_string(&key).context(error::JsonSnafu)?;
@@ -495,8 +892,15 @@ impl Cache {
     }
 
     /// Get the filesystem path for the resolve cache file for a given [`CrateSpec`].
+    ///
+    /// A resolution as of a date (see [`crate::as_of`]) is cached apart from the current one.
     fn crate_resolve_cache_path(&self, spec: &CrateSpec) -> Result<PathBuf> {
-        let hash = Self::compute_spec_hash(spec)?;
+        let hash = match self.inner.config.as_of {
+            Some(date) => {
+                Self::compute_hash(format!("{}@{}", Self::compute_spec_hash(spec)?, date).as_bytes())
+            }
+            None => Self::compute_spec_hash(spec)?,
+        };
         Ok(self
             .inner
             .config
@@ -561,7 +965,7 @@ impl Cache {
                     .join(resolved.version.to_string()),
 
                 RegistrySource::IndexUrl(url) => {
-                    let url_hash = Self::compute_hash(url.as_str().as_bytes());
+                    let url_hash = &Self::compute_hash(url.as_str().as_bytes())[..SHORT_HASH_LEN];
                     base.join("registry-index")
                         .join(url_hash)
                         .join(&resolved.name)
@@ -570,7 +974,7 @@ impl Cache {
             },
 
             ResolvedSource::Git { repo, commit } => {
-                let repo_hash = Self::compute_hash(repo.as_bytes());
+                let repo_hash = &Self::compute_hash(repo.as_bytes())[..SHORT_HASH_LEN];
                 base.join("git").join(repo_hash).join(commit)
             }
 
@@ -579,6 +983,15 @@ impl Cache {
                 Forge::GitLab { owner, repo, .. } => base.join("gitlab").join(owner).join(repo).join(commit),
             },
 
+            ResolvedSource::Custom { url, revision } => {
+                let url_hash = &Self::compute_hash(url.as_str().as_bytes())[..SHORT_HASH_LEN];
+                let revision_hash = &Self::compute_hash(revision.as_bytes())[..SHORT_HASH_LEN];
+                base.join("custom")
+                    .join(url.scheme())
+                    .join(url_hash)
+                    .join(revision_hash)
+            }
+
             ResolvedSource::LocalDir { .. } => {
                 unreachable!("LocalDir sources should not be passed to source_cache_path")
             }
@@ -588,12 +1001,14 @@ impl Cache {
     }
 
     /// Get the cache path for a git database (bare repo) for a URL.
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
     pub(crate) fn git_db_path(&self, url: &str) -> PathBuf {
         let ident = Self::compute_git_ident(url);
         self.inner.config.cache_dir.join("git-db").join(ident)
     }
 
     /// Get the cache path for a git checkout at a specific commit.
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
     pub(crate) fn git_checkout_path(&self, url: &str, commit: &str) -> PathBuf {
         let ident = Self::compute_git_ident(url);
         self.inner
@@ -604,10 +1019,21 @@ impl Cache {
             .join(commit)
     }
 
+    /// Get the cache path for the vendored dependencies of a crate (see [`crate::vendor`]).
+    pub(crate) fn vendor_path(&self, krate: &ResolvedCrate) -> PathBuf {
+        self.inner
+            .config
+            .cache_dir
+            .join(crate::vendor::VENDOR_DIR_NAME)
+            .join(format!("{}-{}", krate.name, krate.version))
+            .join(Self::compute_source_hash(&krate.source))
+    }
+
     /// Compute stable identifier for git URL (like cargo's ident).
     ///
     /// Format: `{repo-name}-{short-hash}`
     /// Example: `tokio-a1b2c3d4` for `https://github.com/tokio-rs/tokio`
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
     fn compute_git_ident(url: &str) -> String {
         // Extract repo name from URL (last path component)
         let name = url
@@ -634,26 +1060,11 @@ impl Cache {
         resolved: &ResolvedCrate,
         age: Duration,
     ) -> Result<()> {
-        let cache_file = self.crate_resolve_cache_path(spec)?;
-
-        if let Some(parent) = cache_file.parent() {
-            fs::create_dir_all(parent).with_context(|_| error::IoSnafu {
-                path: parent.to_path_buf(),
-            })?;
-        }
-
-        let cached_at = Utc::now() - chrono::Duration::from_std(age).unwrap();
         let entry = CacheEntry {
-            value: resolved.clone(),
-            cached_at,
+            cached_at: Utc::now() - chrono::Duration::from_std(age).unwrap(),
+            ..CacheEntry::new(resolved.clone())
         };
-
-        let json = serde_json::to_string_pretty(&entry).context(error::JsonSnafu)?;
-        fs::write(&cache_file, json).with_context(|_| error::IoSnafu {
-            path: cache_file.clone(),
-        })?;
-
-        Ok(())
+        self.put_resolved_crate_entry(spec, entry)
     }
 
     /// Get a cached binary or build it if not present.
@@ -682,7 +1093,7 @@ impl Cache {
         build_fn: F,
     ) -> Result<PathBuf>
     where
-        F: FnOnce() -> Result<(PathBuf, crate::sbom::CycloneDx)>,
+        F: FnOnce() -> Result<(PathBuf, Option<crate::sbom::BuildSbom>)>,
     {
         // Don't cache local directories - their source can change
         if matches!(krate.source, ResolvedSource::LocalDir { .. }) {
@@ -693,72 +1104,289 @@ impl Cache {
             return Ok(binary_path);
         }
 
+        let key = Self::compute_build_key(
+            &format!("{}-{}", krate.name, krate.version),
+            &Self::compute_source_hash(&krate.source),
+            &Self::compute_build_hash(options),
+        );
+        self.inner
+            .builds
+            .run(key, || self.build_binary_uncoalesced(krate, options, build_fn))
+    }
+
+    /// [`Self::get_or_build_binary`] for a crate that isn't in a local directory, without waiting
+    /// for the same build by another thread.
+    fn build_binary_uncoalesced<F>(
+        &self,
+        krate: &ResolvedCrate,
+        options: &BuildOptions,
+        build_fn: F,
+    ) -> Result<PathBuf>
+    where
+        F: FnOnce() -> Result<(PathBuf, Option<crate::sbom::BuildSbom>)>,
+    {
         self.inner
             .reporter
             .report(|| BuildCacheMessage::cache_lookup(krate, options));
 
         let source_hash = Self::compute_source_hash(&krate.source);
         let build_hash = Self::compute_build_hash(options);
+        let crate_dir_name = format!("{}-{}", krate.name, krate.version);
+        let build_key = Self::compute_build_key(&crate_dir_name, &source_hash, &build_hash);
         let binary_name = Self::expected_binary_name(&krate.name, &options.build_target);
 
         let cache_dir = self
             .inner
             .config
             .bin_dir
-            .join(format!("{}-{}", krate.name, krate.version))
+            .join(crate_dir_name)
             .join(source_hash)
             .join(build_hash);
 
         let cache_path = cache_dir.join(&binary_name);
-        let sbom_path = cache_dir.join("sbom.cyclonedx.json");
+        let sbom_path = cache_dir.join(SBOM_FILE_NAME);
 
         // Return cached binary if it exists (SBOM is presumed to also exist in this case)
         if cache_path.exists() {
-            if !self.inner.config.refresh {
+            if !self.inner.config.refresh.binary {
                 self.inner
                     .reporter
-                    .report(|| BuildCacheMessage::cache_hit(&cache_path, &sbom_path));
+                    .report(|| BuildCacheMessage::cache_hit(&build_key, &cache_path, &sbom_path));
+                record_cache_outcome(true);
                 return Ok(cache_path);
             } else {
                 debug!(
                     cache_dir = %cache_dir.display(),
                     "Refresh mode: removing existing binary cache",
                 );
-                let _ = fs::remove_dir_all(&cache_dir);
+                let _ = fs::remove_dir_all(long_path(&cache_dir));
             }
         }
 
         self.inner
             .reporter
             .report(|| BuildCacheMessage::cache_miss(krate));
+        record_cache_outcome(false);
+        self.report_previous_build(krate);
 
         // Build the binary and get the SBOM
+        let started = Instant::now();
         let (built_binary, sbom) = build_fn()?;
+        let duration = started.elapsed();
 
-        // Create cache directory
-        fs::create_dir_all(&cache_dir).with_context(|_| error::IoSnafu {
-            path: cache_dir.clone(),
-        })?;
+        // Copy binary to cache, sharing the copy of an identical binary built another way
+        install_executable(&built_binary, &cache_path)?;
+        crate::bin_dedup::deduplicate(&self.inner.config.bin_dir, &cache_path);
 
-        // Copy binary to cache
-        fs::copy(&built_binary, &cache_path).with_context(|_| error::CopyBinarySnafu {
-            src: built_binary.clone(),
-            dst: cache_path.clone(),
-        })?;
+        // Write the SBOM to cache, if there is one
+        if let Some(sbom) = &sbom {
+            fs::write(long_path(&sbom_path), &sbom.json).with_context(|_| error::IoSnafu {
+                path: sbom_path.clone(),
+            })?;
+        }
 
-        // Serialize and write SBOM to cache
-        let sbom_json = serde_json::to_string_pretty(&sbom).context(error::JsonSnafu)?;
-        fs::write(&sbom_path, sbom_json).with_context(|_| error::IoSnafu {
-            path: sbom_path.clone(),
+        // Record what the build was like, to set expectations the next time this crate is built
+        let binary_size = fs::metadata(long_path(&cache_path))
+            .with_context(|_| error::IoSnafu {
+                path: cache_path.clone(),
+            })?
+            .len();
+        let stats = BuildStats {
+            built_at: Utc::now(),
+            duration,
+            binary_size,
+            dependency_count: sbom.map_or(0, |sbom| sbom.dependency_count),
+        };
+        let stats_path = cache_dir.join(BUILD_STATS_FILE_NAME);
+        let stats_json = serde_json::to_string_pretty(&stats).context(error::JsonSnafu)?;
+        fs::write(long_path(&stats_path), stats_json).with_context(|_| error::IoSnafu {
+            path: stats_path.clone(),
         })?;
 
         self.inner
             .reporter
-            .report(|| BuildCacheMessage::cache_stored(&cache_path, &sbom_path));
+            .report(|| BuildCacheMessage::cache_stored(&build_key, &cache_path, &sbom_path));
 
         Ok(cache_path)
     }
 
+    /// List the binaries that have been built from source and stored in the build cache.
+    ///
+    /// Each one is identified by its build key (see [`Self::compute_build_key`]), which can be
+    /// passed to [`Self::find_build`] to get at that exact binary again.
+    pub(crate) fn list_builds(&self) -> Result<Vec<CachedBuild>> {
+        let bin_dir = &self.inner.config.bin_dir;
+        if !bin_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut builds = Vec::new();
+        for crate_dir in Self::read_subdirs(bin_dir)? {
+            let Some(crate_dir_name) = crate_dir.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some((name, version)) = Self::parse_crate_dir_name(crate_dir_name) else {
+                debug!(path = %crate_dir.display(), "Ignoring unrecognized directory in binary cache");
+                continue;
+            };
+
+            for source_dir in Self::read_subdirs(&crate_dir)? {
+                for build_dir in Self::read_subdirs(&source_dir)? {
+                    let (Some(source_hash), Some(build_hash)) = (
+                        source_dir.file_name().and_then(|name| name.to_str()),
+                        build_dir.file_name().and_then(|name| name.to_str()),
+                    ) else {
+                        continue;
+                    };
+
+                    let Some(binary_path) = Self::find_cached_binary(&build_dir)? else {
+                        continue;
+                    };
+
+                    builds.push(CachedBuild {
+                        name: name.clone(),
+                        version: version.clone(),
+                        build_key: Self::compute_build_key(crate_dir_name, source_hash, build_hash),
+                        binary_path,
+                        stats: Self::read_build_stats(&build_dir),
+                    });
+                }
+            }
+        }
+
+        builds.sort_by(|a, b| (&a.name, &a.version, &a.build_key).cmp(&(&b.name, &b.version, &b.build_key)));
+        Ok(builds)
+    }
+
+    /// Find the binary in the build cache with the given build key, as reported by
+    /// [`Self::list_builds`] and in [`BuildCacheMessage`]s.
+    pub(crate) fn find_build(&self, build_key: &str) -> Result<Option<CachedBuild>> {
+        Ok(self
+            .list_builds()?
+            .into_iter()
+            .find(|build| build.build_key == build_key))
+    }
+
+    /// Report the most recent earlier build of `krate`'s crate (of any version), if its stats were
+    /// recorded, so the user knows roughly what to expect of the build that's about to start.
+    fn report_previous_build(&self, krate: &ResolvedCrate) {
+        let Some((version, stats)) = self.last_build_stats(&krate.name) else {
+            return;
+        };
+
+        info!(
+            "The last build of {}@{} took {} and had {} dependencies",
+            krate.name,
+            version,
+            stats.build_time(),
+            stats.dependency_count
+        );
+
+        let prebuilt_binaries_disabled =
+            self.inner.config.prebuilt_binaries.use_prebuilt_binaries == UsePrebuiltBinaries::Never;
+        self.inner.reporter.report(|| {
+            BuildCacheMessage::previous_build(&krate.name, &version, &stats, prebuilt_binaries_disabled)
+        });
+    }
+
+    /// The version and [`BuildStats`] of the most recent cached build of the crate `name`, of any
+    /// version, if its stats were recorded.
+    pub(crate) fn last_build_stats(&self, name: &str) -> Option<(String, BuildStats)> {
+        let builds = match self.list_builds() {
+            Ok(builds) => builds,
+            Err(e) => {
+                debug!("Failed to list cached builds for build history: {}", e);
+                return None;
+            }
+        };
+
+        builds
+            .into_iter()
+            .filter(|build| build.name == name)
+            .filter_map(|build| Some((build.version, build.stats?)))
+            .max_by_key(|(_, stats)| stats.built_at)
+    }
+
+    /// Read the [`BuildStats`] recorded in a build cache directory.
+    ///
+    /// Builds cached before these were recorded have none, and a file that can't be read is
+    /// treated the same way since the stats are only informational.
+    fn read_build_stats(build_dir: &Path) -> Option<BuildStats> {
+        let stats_path = build_dir.join(BUILD_STATS_FILE_NAME);
+        let json = fs::read_to_string(long_path(&stats_path)).ok()?;
+        match serde_json::from_str(&json) {
+            Ok(stats) => Some(stats),
+            Err(e) => {
+                debug!(path = %stats_path.display(), "Ignoring invalid build stats: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Compute the key that identifies a binary in the build cache.
+    ///
+    /// The source and build hashes are only unique per crate name and version (all crates.io
+    /// sources hash the same), so the key is a short hash of the binary's whole path below
+    /// [`Config::bin_dir`].
+    fn compute_build_key(crate_dir_name: &str, source_hash: &str, build_hash: &str) -> String {
+        let path = format!("{}/{}/{}", crate_dir_name, source_hash, build_hash);
+        Self::compute_hash(path.as_bytes())[..BUILD_KEY_LEN].to_string()
+    }
+
+    /// Split a binary cache directory name of the form `{name}-{version}` into its parts.
+    ///
+    /// Both crate names and (pre-release) versions can contain dashes, so this is split at the
+    /// first dash after which the rest of the name is a valid version.
+    fn parse_crate_dir_name(dir_name: &str) -> Option<(String, String)> {
+        dir_name
+            .match_indices('-')
+            .map(|(index, _)| (&dir_name[..index], &dir_name[index + 1..]))
+            .find(|(name, version)| !name.is_empty() && Version::parse(version).is_ok())
+            .map(|(name, version)| (name.to_string(), version.to_string()))
+    }
+
+    /// The binary in a build cache directory, which is the file alongside the SBOM and build stats.
+    fn find_cached_binary(build_dir: &Path) -> Result<Option<PathBuf>> {
+        let entries = fs::read_dir(long_path(build_dir)).with_context(|_| error::IoSnafu {
+            path: build_dir.to_path_buf(),
+        })?;
+
+        for entry in entries {
+            let entry = entry.with_context(|_| error::IoSnafu {
+                path: build_dir.to_path_buf(),
+            })?;
+            let path = build_dir.join(entry.file_name());
+            if path.is_file()
+                && entry.file_name() != SBOM_FILE_NAME
+                && entry.file_name() != BUILD_STATS_FILE_NAME
+            {
+                return Ok(Some(path));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The subdirectories of `dir`, in no particular order.
+    fn read_subdirs(dir: &Path) -> Result<Vec<PathBuf>> {
+        let entries = fs::read_dir(long_path(dir)).with_context(|_| error::IoSnafu {
+            path: dir.to_path_buf(),
+        })?;
+
+        let mut subdirs = Vec::new();
+        for entry in entries {
+            let entry = entry.with_context(|_| error::IoSnafu {
+                path: dir.to_path_buf(),
+            })?;
+            let path = dir.join(entry.file_name());
+            if path.is_dir() {
+                subdirs.push(path);
+            }
+        }
+
+        Ok(subdirs)
+    }
+
     /// Compute a hash of the resolved source to distinguish different crate origins.
     ///
     /// Different sources (crates.io vs git vs forge) will produce different hashes
@@ -787,6 +1415,11 @@ impl Cache {
                 format!("{:?}", forge).hash(&mut hasher);
                 commit.hash(&mut hasher);
             }
+            ResolvedSource::Custom { url, revision } => {
+                "custom".hash(&mut hasher);
+                url.as_str().hash(&mut hasher);
+                revision.hash(&mut hasher);
+            }
             ResolvedSource::LocalDir { .. } => {
                 panic!("Should not compute hash for LocalDir sources");
             }
@@ -805,7 +1438,7 @@ impl Cache {
     ///
     /// Features are sorted before hashing to ensure consistent cache keys
     /// regardless of the order they're specified.
-    fn compute_build_hash(options: &BuildOptions) -> String {
+    pub(crate) fn compute_build_hash(options: &BuildOptions) -> String {
         let mut hasher = DefaultHasher::new();
 
         // Sort features for consistency - order shouldn't matter for cache key
@@ -827,6 +1460,11 @@ impl Cache {
         // - offline: affects network access, not binary
         // - jobs: affects build parallelism, not binary
         // - ignore_rust_version: affects cargo checks, not binary
+        // - default_bin_policy: only picks the binary, and the builder resolves build_target to the
+        //   one it picked before the binary is cached
+        // - vendor_config: affects where dependencies are read from, not binary
+        // - priority: affects how fast the build goes, not binary
+        // - target_dir: affects where the build happens, not binary
 
         format!("{:016x}", hasher.finish())
     }
@@ -855,13 +1493,34 @@ impl Cache {
 struct CacheInner {
     config: Config,
     reporter: crate::messages::MessageReporter,
+
+    /// The most recently used entries of each kind that's read on every run of a tool, keyed by
+    /// the path of the cache file they were read from or written to, so that an embedding that
+    /// keeps a [`Cache`] around can look them up again without going to disk.
+    ///
+    /// Every write to one of those files goes through [`Cache::write_through`], which keeps these
+    /// in step with what's on disk, and every read through [`Cache::read_through`], which only
+    /// uses an entry here while its file is unchanged, so that a write by another process is seen.
+    /// Nothing in them outlives the expiry of the entry itself, since that's worked out from
+    /// `cached_at` just as for an entry read from disk.
+    resolved_crates: MemoryCache<PathBuf, MemoryEntry<ResolvedCrate>>,
+    binaries: MemoryCache<PathBuf, MemoryEntry<Option<ResolvedBinary>>>,
+    #[cfg_attr(not(feature = "prebuilt-binaries"), allow(dead_code))]
+    missing_binaries: MemoryCache<PathBuf, MemoryEntry<()>>,
+
+    /// The pre-built binary lookups, source downloads and builds under way, keyed by the cache
+    /// file or build key they're for, so that the same one asked for by several threads at once
+    /// is only done once (see [`crate::singleflight`]).
+    binary_lookups: SingleFlight<PathBuf, Option<ResolvedBinary>>,
+    downloads: SingleFlight<PathBuf, DownloadedCrate>,
+    builds: SingleFlight<String, PathBuf>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Refresh;
     use assert_matches::assert_matches;
-    use semver::Version;
     use snafu::IntoError;
     use std::{cell::RefCell, rc::Rc, time::Duration};
     use tempfile::TempDir;
@@ -882,10 +1541,14 @@ mod tests {
     }
 
     fn test_cache_with_refresh() -> (Cache, TempDir) {
+        test_cache_refreshing(Refresh::ALL)
+    }
+
+    fn test_cache_refreshing(refresh: Refresh) -> (Cache, TempDir) {
         crate::logging::init_test_logging();
 
         let (temp_dir, mut config) = crate::config::create_test_env();
-        config.refresh = true;
+        config.refresh = refresh;
         (
             Cache::new(config, crate::messages::MessageReporter::null()),
             temp_dir,
@@ -1032,6 +1695,64 @@ mod tests {
             assert_matches!(result.unwrap_err(), error::Error::Registry { .. });
         }
 
+        #[test]
+        fn network_error_without_stale_uses_newest_cached_binary_version() {
+            let (cache, _temp) = test_cache();
+            let spec = CrateSpec::CratesIo {
+                name: "serde".to_string(),
+                version: Some(VersionReq::parse("^1.0").unwrap()),
+            };
+
+            // Only versions with both a binary and their source cached can be used
+            for (version, source_cached) in [("1.0.0", true), ("1.0.1", false), ("2.0.0", true)] {
+                let resolved = ResolvedCrate {
+                    version: Version::parse(version).unwrap(),
+                    ..test_resolved()
+                };
+                let build_dir = cache
+                    .inner
+                    .config
+                    .bin_dir
+                    .join(format!("serde-{}", version))
+                    .join(Cache::compute_source_hash(&resolved.source))
+                    .join(Cache::compute_build_hash(&BuildOptions::default()));
+                fs::create_dir_all(&build_dir).unwrap();
+                fs::write(build_dir.join("serde"), b"binary").unwrap();
+                if source_cached {
+                    fs::create_dir_all(cache.crate_source_cache_path(&resolved).unwrap()).unwrap();
+                }
+            }
+
+            let result = cache.get_or_resolve_crate(&spec, || {
+                Err(
+                    error::RegistrySnafu.into_error(tame_index::Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        "network error",
+                    ))),
+                )
+            });
+            assert_eq!(result.unwrap(), test_resolved());
+
+            let result = cache.get_or_resolve_crate(&test_spec(), || {
+                error::OfflineModeSnafu {
+                    name: "serde",
+                    version: "*",
+                }
+                .fail()
+            });
+            assert_eq!(result.unwrap().version, Version::parse("2.0.0").unwrap());
+
+            // Errors that say the spec can't be satisfied at all are never papered over
+            let result = cache.get_or_resolve_crate(&spec, || {
+                error::NoMatchingVersionSnafu {
+                    name: "serde",
+                    requirement: "^1.0",
+                }
+                .fail()
+            });
+            assert_matches!(result.unwrap_err(), error::Error::NoMatchingVersion { .. });
+        }
+
         #[test]
         fn io_error_with_stale_returns_stale() {
             let (cache, _temp) = test_cache_with_timeout(Duration::from_secs(0));
@@ -1096,6 +1817,59 @@ mod tests {
             assert_eq!(cached.map(|e| e.value), Some(new_resolved));
         }
 
+        #[test]
+        fn newer_release_overrides_valid_cache() {
+            let (cache, _temp) = test_cache();
+            let spec = test_spec();
+            let old_resolved = test_resolved();
+            let new_resolved = test_resolved_alt();
+            let revision = |etag: &'static str| move || Some(format!("etag: {}", etag));
+            let recorded_revision = || cache.get_resolved_crate(&spec).unwrap().unwrap().index_revision;
+
+            let result = cache.get_or_resolve_crate_revalidated(
+                &spec,
+                revision("a"),
+                |_| None,
+                || Ok(old_resolved.clone()),
+            );
+            assert_eq!(result.unwrap(), old_resolved);
+            assert_eq!(recorded_revision().as_deref(), Some("etag: a"));
+
+            // While the index hasn't changed, it isn't looked at
+            let result = cache.get_or_resolve_crate_revalidated(
+                &spec,
+                revision("a"),
+                |_| panic!("The index should not be read while its revision is unchanged"),
+                || panic!("Closure should not be called while the cache is current"),
+            );
+            assert_eq!(result.unwrap(), old_resolved);
+
+            // Once it has, the cached resolution is kept if there's nothing newer in it
+            let result = cache.get_or_resolve_crate_revalidated(
+                &spec,
+                revision("b"),
+                |_| None,
+                || panic!("Closure should not be called while the cache is current"),
+            );
+            assert_eq!(result.unwrap(), old_resolved);
+            assert_eq!(recorded_revision().as_deref(), Some("etag: b"));
+
+            let result = cache.get_or_resolve_crate_revalidated(
+                &spec,
+                revision("c"),
+                |cached| {
+                    assert_eq!(cached, &old_resolved);
+                    Some(new_resolved.version.clone())
+                },
+                || Ok(new_resolved.clone()),
+            );
+            assert_eq!(result.unwrap(), new_resolved);
+
+            let cached = cache.get_resolved_crate(&spec).unwrap().unwrap();
+            assert_eq!(cached.value, new_resolved);
+            assert_eq!(cached.index_revision.as_deref(), Some("etag: c"));
+        }
+
         #[test]
         fn refresh_bypasses_valid_cache() {
             let (cache, _temp) = test_cache_with_refresh();
@@ -1147,6 +1921,163 @@ mod tests {
         }
     }
 
+    mod missing_binaries {
+        use super::*;
+
+        fn test_cache_with_negative_ttl(ttl: Duration, refresh: Refresh) -> (Cache, TempDir) {
+            crate::logging::init_test_logging();
+
+            let (temp_dir, mut config) = crate::config::create_test_env();
+            config.prebuilt_binaries.negative_cache_ttl = ttl;
+            config.refresh = refresh;
+            (
+                Cache::new(config, crate::messages::MessageReporter::null()),
+                temp_dir,
+            )
+        }
+
+        #[test]
+        fn recorded_per_platform_and_provider() {
+            let (cache, _temp) = test_cache_with_negative_ttl(Duration::from_secs(3600), Refresh::default());
+            let krate = test_resolved();
+            let platform = "x86_64-unknown-linux-gnu";
+
+            assert!(!cache.is_binary_missing(&krate, platform, BinaryProvider::GithubReleases));
+            cache
+                .put_missing_binary(&krate, platform, BinaryProvider::GithubReleases)
+                .unwrap();
+
+            assert!(cache.is_binary_missing(&krate, platform, BinaryProvider::GithubReleases));
+            assert!(!cache.is_binary_missing(&krate, platform, BinaryProvider::Quickinstall));
+            assert!(!cache.is_binary_missing(
+                &krate,
+                "aarch64-unknown-linux-gnu",
+                BinaryProvider::GithubReleases
+            ));
+            assert!(!cache.is_binary_missing(&test_resolved_alt(), platform, BinaryProvider::GithubReleases));
+        }
+
+        #[test]
+        fn expire_and_are_bypassed_by_refresh() {
+            let krate = test_resolved();
+            let platform = "x86_64-unknown-linux-gnu";
+
+            let (cache, _temp) = test_cache_with_negative_ttl(Duration::ZERO, Refresh::default());
+            cache
+                .put_missing_binary(&krate, platform, BinaryProvider::Binstall)
+                .unwrap();
+            assert!(!cache.is_binary_missing(&krate, platform, BinaryProvider::Binstall));
+
+            let (cache, _temp) = test_cache_with_negative_ttl(Duration::from_secs(3600), Refresh::ALL);
+            cache
+                .put_missing_binary(&krate, platform, BinaryProvider::Binstall)
+                .unwrap();
+            assert!(!cache.is_binary_missing(&krate, platform, BinaryProvider::Binstall));
+        }
+
+        #[test]
+        fn expired_negative_result_is_resolved_again() {
+            let (cache, _temp) = test_cache_with_negative_ttl(Duration::ZERO, Refresh::default());
+            let krate = test_resolved();
+            cache.put_cached_binary(&krate, &None).unwrap();
+
+            let call_count = Rc::new(RefCell::new(0));
+            let call_count_clone = call_count.clone();
+            let result = cache.get_or_resolve_binary(&krate, || {
+                *call_count_clone.borrow_mut() += 1;
+                Ok(None)
+            });
+
+            assert_matches!(result, Ok(None));
+            assert_eq!(*call_count.borrow(), 1);
+        }
+    }
+
+    mod in_memory {
+        use super::*;
+
+        #[test]
+        fn warm_lookups_skip_disk() {
+            let (cache, _temp) = test_cache();
+            let spec = test_spec();
+            cache.put_resolved_crate(&spec, &test_resolved()).unwrap();
+
+            // With the file garbled but its modification time and length as they were, only the
+            // entry kept in memory can answer
+            let cache_file = cache.crate_resolve_cache_path(&spec).unwrap();
+            let metadata = fs::metadata(&cache_file).unwrap();
+            fs::write(&cache_file, " ".repeat(metadata.len() as usize)).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&cache_file)
+                .unwrap()
+                .set_modified(metadata.modified().unwrap())
+                .unwrap();
+            let result = cache.get_or_resolve_crate(&spec, || panic!("BUG: cache was bypassed"));
+            assert_eq!(result.unwrap(), test_resolved());
+
+            // A fresh cache over the same directory has nothing in memory yet, so reads the file
+            let cold = Cache::new(
+                cache.inner.config.clone(),
+                crate::messages::MessageReporter::null(),
+            );
+            assert_matches!(cold.get_resolved_crate(&spec), Err(error::Error::Json { .. }));
+        }
+
+        #[test]
+        fn writes_replace_the_entry_in_memory() {
+            let (cache, _temp) = test_cache();
+            let spec = test_spec();
+            cache.put_resolved_crate(&spec, &test_resolved()).unwrap();
+            assert_eq!(
+                cache.get_resolved_crate(&spec).unwrap().map(|e| e.value),
+                Some(test_resolved())
+            );
+
+            cache
+                .insert_stale_resolve_entry(&spec, &test_resolved_alt(), Duration::from_secs(7200))
+                .unwrap();
+            let entry = cache.get_resolved_crate(&spec).unwrap().unwrap();
+            assert_eq!(entry.value, test_resolved_alt());
+            assert!(entry.age() >= Duration::from_secs(7200));
+
+            let krate = test_resolved();
+            cache.put_cached_binary(&krate, &None).unwrap();
+            assert_matches!(
+                cache.get_cached_binary(&krate),
+                Ok(Some(CacheEntry { value: None, .. }))
+            );
+
+            // A file that's gone, such as after `cgx --clean` in another process, isn't answered
+            // from memory
+            fs::remove_file(cache.binary_cache_path(&krate).unwrap()).unwrap();
+            assert_matches!(cache.get_cached_binary(&krate), Ok(None));
+        }
+
+        #[test]
+        fn writes_by_another_cache_are_seen() {
+            let (cache, _temp) = test_cache();
+            let spec = test_spec();
+            cache.put_resolved_crate(&spec, &test_resolved()).unwrap();
+            assert_eq!(
+                cache.get_resolved_crate(&spec).unwrap().map(|e| e.value),
+                Some(test_resolved())
+            );
+
+            // Another process sharing the cache directory writes the same file
+            let other = Cache::new(
+                cache.inner.config.clone(),
+                crate::messages::MessageReporter::null(),
+            );
+            other.put_resolved_crate(&spec, &test_resolved_alt()).unwrap();
+
+            assert_eq!(
+                cache.get_resolved_crate(&spec).unwrap().map(|e| e.value),
+                Some(test_resolved_alt())
+            );
+        }
+    }
+
     mod get_or_download {
         use super::*;
 
@@ -1346,11 +2277,165 @@ mod tests {
                 "Downloader should be called even with cached source"
             );
         }
+
+        #[test]
+        fn refreshing_other_layers_keeps_source_cache() {
+            let (cache, _temp) = test_cache_refreshing(Refresh {
+                resolve: true,
+                binary: true,
+                ..Refresh::default()
+            });
+            let resolved = test_resolved();
+            let cache_path = cache.crate_source_cache_path(&resolved).unwrap();
+
+            fs::create_dir_all(&cache_path).unwrap();
+            fs::write(cache_path.join("cached.txt"), b"cached content").unwrap();
+
+            let result = cache.get_or_download_crate(&resolved, |_download_path| {
+                panic!("Downloader should not be called when only other layers are refreshed");
+            });
+
+            assert_eq!(result.unwrap().crate_path, cache_path);
+        }
+    }
+
+    mod build_listing {
+        use super::*;
+        use crate::builder::BuildOptions;
+
+        /// Store a fake build of `krate` in the build cache, laid out the way
+        /// [`Cache::get_or_build_binary`] does, returning the cached binary path.
+        fn store_build(cache: &Cache, krate: &ResolvedCrate, options: &BuildOptions) -> PathBuf {
+            let cache_dir = cache
+                .inner
+                .config
+                .bin_dir
+                .join(format!("{}-{}", krate.name, krate.version))
+                .join(Cache::compute_source_hash(&krate.source))
+                .join(Cache::compute_build_hash(options));
+            fs::create_dir_all(&cache_dir).unwrap();
+
+            let binary_path = cache_dir.join(Cache::expected_binary_name(&krate.name, &options.build_target));
+            fs::write(&binary_path, b"binary").unwrap();
+            fs::write(cache_dir.join("sbom.cyclonedx.json"), b"{}").unwrap();
+            binary_path
+        }
+
+        #[test]
+        fn empty_cache_lists_nothing() {
+            let (cache, _temp) = test_cache();
+
+            assert_eq!(cache.list_builds().unwrap(), Vec::new());
+        }
+
+        #[test]
+        fn lists_each_cached_build_with_a_distinct_key() {
+            let (cache, _temp) = test_cache();
+            let debug = BuildOptions {
+                profile: Some("dev".to_string()),
+                ..Default::default()
+            };
+
+            let path1 = store_build(&cache, &test_resolved(), &BuildOptions::default());
+            let path2 = store_build(&cache, &test_resolved_alt(), &BuildOptions::default());
+            let path3 = store_build(&cache, &test_resolved(), &debug);
+
+            let builds = cache.list_builds().unwrap();
+            assert_eq!(builds.len(), 3);
+            assert!(builds.iter().all(|build| build.name == "serde"));
+
+            let keys = builds
+                .iter()
+                .map(|build| &build.build_key)
+                .collect::<std::collections::HashSet<_>>();
+            assert_eq!(keys.len(), 3, "Build keys must be unique: {builds:?}");
+
+            let mut paths = builds
+                .iter()
+                .map(|build| build.binary_path.clone())
+                .collect::<Vec<_>>();
+            paths.sort();
+            let mut expected = vec![path1, path2, path3];
+            expected.sort();
+            assert_eq!(paths, expected);
+        }
+
+        #[test]
+        fn find_build_by_key() {
+            let (cache, _temp) = test_cache();
+            store_build(&cache, &test_resolved(), &BuildOptions::default());
+            let path = store_build(&cache, &test_resolved_alt(), &BuildOptions::default());
+
+            let build_key = cache
+                .list_builds()
+                .unwrap()
+                .into_iter()
+                .find(|build| build.version == "1.0.1")
+                .unwrap()
+                .build_key;
+
+            let build = cache.find_build(&build_key).unwrap().unwrap();
+            assert_eq!(build.version, "1.0.1");
+            assert_eq!(build.binary_path, path);
+
+            assert_eq!(cache.find_build("0123456789abcdef").unwrap(), None);
+        }
+
+        #[test]
+        fn recorded_build_stats_are_listed() {
+            let (cache, _temp) = test_cache();
+            let binary_path = store_build(&cache, &test_resolved(), &BuildOptions::default());
+            let stats = BuildStats {
+                built_at: Utc::now(),
+                duration: Duration::from_secs(125),
+                binary_size: 6,
+                dependency_count: 42,
+            };
+            fs::write(
+                binary_path.with_file_name(BUILD_STATS_FILE_NAME),
+                serde_json::to_string(&stats).unwrap(),
+            )
+            .unwrap();
+
+            let builds = cache.list_builds().unwrap();
+            assert_eq!(builds.len(), 1);
+            assert_eq!(builds[0].binary_path, binary_path);
+            assert_eq!(builds[0].stats, Some(stats));
+            assert_eq!(builds[0].stats.as_ref().unwrap().build_time(), "2m 5s");
+        }
+
+        #[test]
+        fn missing_or_invalid_build_stats_are_ignored() {
+            let (cache, _temp) = test_cache();
+            store_build(&cache, &test_resolved(), &BuildOptions::default());
+            let binary_path = store_build(&cache, &test_resolved_alt(), &BuildOptions::default());
+            fs::write(binary_path.with_file_name(BUILD_STATS_FILE_NAME), b"not json").unwrap();
+
+            let builds = cache.list_builds().unwrap();
+            assert_eq!(builds.len(), 2);
+            assert!(builds.iter().all(|build| build.stats.is_none()));
+        }
+
+        #[test]
+        fn parse_crate_dir_name_handles_dashes() {
+            assert_eq!(
+                Cache::parse_crate_dir_name("cargo-deny-0.16.1"),
+                Some(("cargo-deny".to_string(), "0.16.1".to_string()))
+            );
+            assert_eq!(
+                Cache::parse_crate_dir_name("foo-2d-1.0.0-alpha.1"),
+                Some(("foo-2d".to_string(), "1.0.0-alpha.1".to_string()))
+            );
+            assert_eq!(Cache::parse_crate_dir_name("not-a-crate"), None);
+        }
     }
 
     mod binary_cache_hash {
         use super::*;
-        use crate::builder::{BuildOptions, BuildTarget};
+        use crate::{
+            builder::{BuildOptions, BuildTarget},
+            config::DefaultBinPolicy,
+        };
 
         #[test]
         fn same_inputs_produce_same_hash() {
@@ -1539,6 +2624,26 @@ mod tests {
             );
         }
 
+        #[test]
+        fn default_bin_policy_does_not_affect_hash() {
+            let options1 = BuildOptions {
+                build_target: BuildTarget::Bin("rg".to_string()),
+                default_bin_policy: DefaultBinPolicy::First,
+                ..Default::default()
+            };
+            let options2 = BuildOptions {
+                build_target: BuildTarget::Bin("rg".to_string()),
+                default_bin_policy: DefaultBinPolicy::MatchCrateName,
+                ..Default::default()
+            };
+
+            assert_eq!(
+                Cache::compute_build_hash(&options1),
+                Cache::compute_build_hash(&options2),
+                "the same binary picked by different policies should be cached once"
+            );
+        }
+
         #[test]
         fn jobs_does_not_affect_hash() {
             let options1 = BuildOptions {
@@ -1658,6 +2763,58 @@ mod tests {
             #[cfg(not(windows))]
             assert_eq!(name, "bar");
         }
+
+        #[test]
+        fn pinned_digests_change_prebuilt_binary_key() {
+            let (cache, _temp) = test_cache();
+            let unpinned = cache.compute_binary_cache_hash(&test_resolved()).unwrap();
+
+            let mut config = cache.inner.config.clone();
+            config.tools.insert(
+                "serde".to_string(),
+                crate::config::ToolConfig::Detailed {
+                    version: None,
+                    features: None,
+                    registry: None,
+                    git: None,
+                    branch: None,
+                    tag: None,
+                    rev: None,
+                    path: None,
+                    argv0: None,
+                    prebuilt_sha256: Some(
+                        [("x86_64-unknown-linux-gnu".to_string(), "ab".repeat(32))]
+                            .into_iter()
+                            .collect(),
+                    ),
+                    assets: None,
+                    wrapper: None,
+                    channels: None,
+                    helpers: None,
+                    feature_hints: None,
+                    preset: None,
+                },
+            );
+            let pinned_cache = Cache::new(config, crate::messages::MessageReporter::null());
+            let pinned = pinned_cache.compute_binary_cache_hash(&test_resolved()).unwrap();
+
+            assert_ne!(unpinned, pinned);
+        }
+
+        #[test]
+        fn trusted_origin_changes_prebuilt_binary_key() {
+            let (cache, _temp) = test_cache();
+            let anywhere = cache.compute_binary_cache_hash(&test_resolved()).unwrap();
+
+            let mut config = cache.inner.config.clone();
+            config.prebuilt_binaries.trust_prebuilt_only_from = PrebuiltOrigin::RepositoryOwner;
+            let restricted_cache = Cache::new(config, crate::messages::MessageReporter::null());
+            let restricted = restricted_cache
+                .compute_binary_cache_hash(&test_resolved())
+                .unwrap();
+
+            assert_ne!(anywhere, restricted);
+        }
     }
 
     mod utility {
@@ -0,0 +1,340 @@
+//! Versioning of the on-disk layout of the directories cgx caches things in.
+//!
+//! [`Config::cache_dir`] and [`Config::bin_dir`] are owned by cgx, and how they are laid out
+//! internally is an implementation detail that changes between releases.  So that a new release
+//! doesn't silently ignore (and orphan) whatever an older one left behind, each of these
+//! directories records the version of the layout it uses in a `layout-version` file, and is
+//! brought up to date when cgx starts:
+//!
+//! - A directory with no version file that is empty is new, and is simply stamped with the
+//!   current version.
+//! - A directory with no version file that has contents predates layout versioning, which is
+//!   treated as version 0, unless it has files directly in it: cgx only ever keeps subdirectories
+//!   there, so such a directory (say, a `bin_dir` pointed at `~/.local/bin`) isn't cgx's, and is
+//!   left untagged and unversioned with a warning.
+//! - A directory at an older version is upgraded in place, one version at a time, by the
+//!   [`MIGRATIONS`] that exist for it.  If there's no migration from some version, whatever is in
+//!   the directory is moved aside into a `legacy-v{N}` subdirectory instead, which can be deleted
+//!   once nothing needs it anymore.
+//! - A directory at a newer version than this build of cgx understands is an error, rather than
+//!   risking misreading (or damaging) it.
+//!
+//! Each directory also gets a [`CACHEDIR.TAG`](https://bford.info/cachedir/) so that backup and
+//! mirroring tools know its contents can be regenerated.
+
+use crate::{Result, config::Config, error, helpers::long_path};
+use snafu::{OptionExt, ResultExt};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::*;
+
+/// Version of the cache directory layout that this build of cgx reads and writes.
+///
+/// Bump this whenever the layout below [`Config::cache_dir`] or [`Config::bin_dir`] changes in a
+/// way older releases can't read, and add the migration from the previous version to
+/// [`MIGRATIONS`].
+pub(crate) const CACHE_LAYOUT_VERSION: u32 = 1;
+
+/// Name of the file in each cache directory that records its layout version.
+const LAYOUT_VERSION_FILE_NAME: &str = "layout-version";
+
+/// Name of the cache directory tag file, as defined by the Cache Directory Tagging Specification.
+const CACHEDIR_TAG_FILE_NAME: &str = "CACHEDIR.TAG";
+
+/// Contents of [`CACHEDIR_TAG_FILE_NAME`].  The first line is mandated by the spec.
+const CACHEDIR_TAG: &str = "Signature: 8a477f597d28d172789f06886806bc55\n\
+# This file is a cache directory tag created by cgx.\n\
+# For information about cache directory tags see https://bford.info/cachedir/\n";
+
+/// Upgrades a cache directory in place from one layout version to the next.
+type Migration = fn(&Path) -> Result<()>;
+
+/// The migrations from each layout version to the one after it, in order.
+const MIGRATIONS: &[(u32, Migration)] = &[(0, migrate_unversioned)];
+
+/// Version 0 is the layout from before it was versioned, which is identical to version 1.
+fn migrate_unversioned(_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Prepare the cache directories in `config` for use, creating them and upgrading their layouts
+/// as needed.
+pub(crate) fn prepare(config: &Config) -> Result<()> {
+    prepare_dir(&config.cache_dir, CACHE_LAYOUT_VERSION, MIGRATIONS)?;
+    if config.bin_dir != config.cache_dir {
+        prepare_dir(&config.bin_dir, CACHE_LAYOUT_VERSION, MIGRATIONS)?;
+    }
+
+    Ok(())
+}
+
+/// Bring the layout of the cache directory `dir` up to version `current`.
+fn prepare_dir(dir: &Path, current: u32, migrations: &[(u32, Migration)]) -> Result<()> {
+    fs::create_dir_all(long_path(dir)).context(error::IoSnafu { path: dir })?;
+
+    let recorded = read_layout_version(dir)?;
+    if recorded.is_none() {
+        if let Some(foreign) = foreign_file(dir)? {
+            warn!(
+                "Cache directory {} has files in it that cgx didn't put there, such as {}, so it \
+                 isn't tagged as a cache or given a layout version; point cgx at a directory of its \
+                 own instead",
+                dir.display(),
+                foreign.display()
+            );
+            return Ok(());
+        }
+    }
+
+    let tag_path = dir.join(CACHEDIR_TAG_FILE_NAME);
+    if !tag_path.exists() {
+        fs::write(long_path(&tag_path), CACHEDIR_TAG).context(error::IoSnafu { path: &tag_path })?;
+    }
+
+    let mut version = match recorded {
+        Some(version) => version,
+        None if is_empty(dir)? => current,
+        None => 0,
+    };
+
+    if version > current {
+        return error::CacheLayoutTooNewSnafu {
+            path: dir,
+            version,
+            supported: current,
+        }
+        .fail();
+    }
+
+    while version < current {
+        if let Some((_, migrate)) = migrations.iter().find(|(from, _)| *from == version) {
+            info!(
+                dir = %dir.display(),
+                "Upgrading cache directory layout from version {} to {}",
+                version,
+                version + 1
+            );
+            migrate(dir)?;
+            version += 1;
+        } else {
+            let legacy_dir = isolate(dir, version)?;
+            warn!(
+                "Cache directory {} used layout version {}, which this version of cgx can't upgrade; \
+                 its contents were moved to {} and can be deleted",
+                dir.display(),
+                version,
+                legacy_dir.display()
+            );
+            version = current;
+        }
+
+        // Record progress after every step so that an interrupted upgrade resumes where it
+        // stopped rather than starting over
+        write_layout_version(dir, version)?;
+    }
+
+    if read_layout_version(dir)?.is_none() {
+        write_layout_version(dir, version)?;
+    }
+
+    Ok(())
+}
+
+/// Read the layout version recorded in `dir`, if there is one.
+fn read_layout_version(dir: &Path) -> Result<Option<u32>> {
+    let path = dir.join(LAYOUT_VERSION_FILE_NAME);
+    let contents = match fs::read_to_string(long_path(&path)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context(error::IoSnafu { path }),
+    };
+
+    let version = contents
+        .trim()
+        .parse()
+        .ok()
+        .context(error::InvalidCacheLayoutVersionSnafu {
+            path,
+            contents: contents.trim(),
+        })?;
+
+    Ok(Some(version))
+}
+
+/// Record `version` as the layout version of `dir`.
+///
+/// The file is replaced atomically, so that a concurrently running cgx never sees it half
+/// written.
+fn write_layout_version(dir: &Path, version: u32) -> Result<()> {
+    let path = dir.join(LAYOUT_VERSION_FILE_NAME);
+    let temp_path = dir.join(format!("{}.{}.tmp", LAYOUT_VERSION_FILE_NAME, std::process::id()));
+
+    fs::write(long_path(&temp_path), format!("{}\n", version))
+        .context(error::IoSnafu { path: &temp_path })?;
+    fs::rename(long_path(&temp_path), long_path(&path)).context(error::RenameFileSnafu {
+        src: &temp_path,
+        dst: &path,
+    })
+}
+
+/// Whether `dir` contains nothing but a cache directory tag.
+fn is_empty(dir: &Path) -> Result<bool> {
+    for entry in fs::read_dir(long_path(dir)).context(error::IoSnafu { path: dir })? {
+        let entry = entry.context(error::IoSnafu { path: dir })?;
+        if entry.file_name() != CACHEDIR_TAG_FILE_NAME {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// The first file directly in the unversioned directory `dir` that cgx wouldn't have put there,
+/// if any.
+///
+/// Every layout cgx has used keeps only subdirectories at the top of its cache directories, so a
+/// file there (like the executables in `~/.local/bin`) means the directory belongs to something
+/// else, and must not be tagged as a cache that backup tools can skip.
+fn foreign_file(dir: &Path) -> Result<Option<PathBuf>> {
+    for entry in fs::read_dir(long_path(dir)).context(error::IoSnafu { path: dir })? {
+        let entry = entry.context(error::IoSnafu { path: dir })?;
+        let file_type = entry.file_type().context(error::IoSnafu { path: entry.path() })?;
+        if !file_type.is_dir() && entry.file_name() != CACHEDIR_TAG_FILE_NAME {
+            return Ok(Some(entry.path()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Move the contents of `dir`, which are in layout `version`, into a subdirectory of their own
+/// so that they don't get in the way of the current layout.
+///
+/// Returns the path of that subdirectory.
+fn isolate(dir: &Path, version: u32) -> Result<PathBuf> {
+    let legacy_name = format!("legacy-v{}", version);
+    let legacy_dir = dir.join(&legacy_name);
+    fs::create_dir_all(long_path(&legacy_dir)).context(error::IoSnafu { path: &legacy_dir })?;
+
+    for entry in fs::read_dir(long_path(dir)).context(error::IoSnafu { path: dir })? {
+        let entry = entry.context(error::IoSnafu { path: dir })?;
+        let name = entry.file_name();
+
+        // The tag and version file stay, as do the contents isolated by earlier upgrades
+        if name == CACHEDIR_TAG_FILE_NAME
+            || name == LAYOUT_VERSION_FILE_NAME
+            || name.to_string_lossy().starts_with("legacy-v")
+        {
+            continue;
+        }
+
+        let src = dir.join(&name);
+        let dst = legacy_dir.join(&name);
+        fs::rename(long_path(&src), long_path(&dst)).context(error::RenameFileSnafu { src, dst })?;
+    }
+
+    Ok(legacy_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn layout_version(dir: &Path) -> Option<u32> {
+        read_layout_version(dir).unwrap()
+    }
+
+    #[test]
+    fn new_dir_is_tagged_and_stamped() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("cache");
+
+        prepare_dir(&dir, CACHE_LAYOUT_VERSION, MIGRATIONS).unwrap();
+
+        assert_eq!(layout_version(&dir), Some(CACHE_LAYOUT_VERSION));
+        let tag = fs::read_to_string(dir.join(CACHEDIR_TAG_FILE_NAME)).unwrap();
+        assert!(tag.starts_with("Signature: 8a477f597d28d172789f06886806bc55"));
+    }
+
+    #[test]
+    fn unversioned_dir_is_upgraded_in_place() {
+        let temp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(temp.path().join("resolve")).unwrap();
+        fs::write(temp.path().join("resolve").join("entry.json"), b"{}").unwrap();
+
+        prepare_dir(temp.path(), CACHE_LAYOUT_VERSION, MIGRATIONS).unwrap();
+
+        assert_eq!(layout_version(temp.path()), Some(CACHE_LAYOUT_VERSION));
+        assert!(temp.path().join("resolve").join("entry.json").exists());
+    }
+
+    #[test]
+    fn dir_with_foreign_files_is_left_alone() {
+        let temp = tempfile::tempdir().unwrap();
+        fs::write(temp.path().join("rg"), b"not cgx's").unwrap();
+
+        prepare_dir(temp.path(), CACHE_LAYOUT_VERSION, MIGRATIONS).unwrap();
+
+        assert_eq!(layout_version(temp.path()), None);
+        assert!(!temp.path().join(CACHEDIR_TAG_FILE_NAME).exists());
+        assert!(temp.path().join("rg").exists());
+    }
+
+    #[test]
+    fn migrations_run_in_order() {
+        let temp = tempfile::tempdir().unwrap();
+        write_layout_version(temp.path(), 1).unwrap();
+
+        let migrations: &[(u32, Migration)] = &[
+            (1, |dir| {
+                fs::write(dir.join("v2"), b"").context(error::IoSnafu { path: dir })
+            }),
+            (2, |dir| {
+                fs::rename(dir.join("v2"), dir.join("v3")).context(error::IoSnafu { path: dir })
+            }),
+        ];
+        prepare_dir(temp.path(), 3, migrations).unwrap();
+
+        assert_eq!(layout_version(temp.path()), Some(3));
+        assert!(temp.path().join("v3").exists());
+        assert!(!temp.path().join("v2").exists());
+    }
+
+    #[test]
+    fn dir_without_migration_is_isolated() {
+        let temp = tempfile::tempdir().unwrap();
+        write_layout_version(temp.path(), 1).unwrap();
+        fs::create_dir_all(temp.path().join("sources")).unwrap();
+
+        prepare_dir(temp.path(), 2, &[]).unwrap();
+
+        assert_eq!(layout_version(temp.path()), Some(2));
+        assert!(!temp.path().join("sources").exists());
+        assert!(temp.path().join("legacy-v1").join("sources").exists());
+        assert!(temp.path().join(CACHEDIR_TAG_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn newer_layout_is_refused() {
+        let temp = tempfile::tempdir().unwrap();
+        write_layout_version(temp.path(), CACHE_LAYOUT_VERSION + 1).unwrap();
+
+        let result = prepare_dir(temp.path(), CACHE_LAYOUT_VERSION, MIGRATIONS);
+
+        assert_matches!(result, Err(error::Error::CacheLayoutTooNew { .. }));
+        assert_eq!(layout_version(temp.path()), Some(CACHE_LAYOUT_VERSION + 1));
+    }
+
+    #[test]
+    fn garbled_version_file_is_an_error() {
+        let temp = tempfile::tempdir().unwrap();
+        fs::write(temp.path().join(LAYOUT_VERSION_FILE_NAME), b"banana").unwrap();
+
+        let result = prepare_dir(temp.path(), CACHE_LAYOUT_VERSION, MIGRATIONS);
+
+        assert_matches!(result, Err(error::Error::InvalidCacheLayoutVersion { .. }));
+    }
+}
@@ -0,0 +1,128 @@
+//! Cancellation of a running [`crate::Cgx`] operation, for embedders that need to abort a
+//! resolution, download or build that is taking too long or is no longer wanted.
+//!
+//! [`crate::Cgx::crate_to_bin_with_cancel`] makes the [`CancellationToken`] it's given the current
+//! one of the calling thread for the duration of the call.  The resolver, downloader, binary
+//! providers and cargo runner check it before each step that can block for a while, and a cargo
+//! process that is running when the token is cancelled is killed.  The operation then fails with
+//! [`crate::error::Error::Cancelled`], after removing any temporary state it had created.
+//!
+//! The token is kept in a thread local rather than passed down explicitly so that it doesn't have
+//! to appear in the signature of every component in between, while concurrent operations on
+//! different threads can still be cancelled independently.
+
+use crate::{Result, error};
+use std::{
+    cell::RefCell,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+thread_local! {
+    /// The token of the operation running on this thread, if any.
+    static CURRENT: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+/// A handle with which a running operation can be cancelled from another thread.
+///
+/// Clones share the same state, so one clone can be handed to the operation and another kept to
+/// cancel it.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the operation using this token.  This only sets a flag and returns immediately; the
+    /// operation notices at its next check and fails with [`crate::error::Error::Cancelled`].
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// The underlying flag, for libraries like `gix` that take an interrupt flag directly.
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub(crate) fn as_atomic(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+}
+
+/// Run `f` with `token` as the current token of this thread, restoring the previous one after.
+pub(crate) fn with_token<T>(token: &CancellationToken, f: impl FnOnce() -> T) -> T {
+    /// Restores the previous token even if `f` panics.
+    struct Restore(Option<CancellationToken>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let previous = CURRENT.with(|current| current.borrow_mut().replace(token.clone()));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// The current token of this thread, or one that is never cancelled if no operation that can be
+/// cancelled is running.
+pub(crate) fn current() -> CancellationToken {
+    CURRENT
+        .with(|current| current.borrow().clone())
+        .unwrap_or_default()
+}
+
+/// Fail with [`crate::error::Error::Cancelled`] if the current operation has been cancelled.
+pub(crate) fn check() -> Result<()> {
+    if current().is_cancelled() {
+        return error::CancelledSnafu.fail();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn check_passes_without_a_token() {
+        assert!(check().is_ok());
+    }
+
+    #[test]
+    fn check_fails_once_cancelled() {
+        let token = CancellationToken::new();
+
+        with_token(&token, || {
+            assert!(check().is_ok());
+            token.clone().cancel();
+            assert_matches!(check(), Err(error::Error::Cancelled));
+        });
+
+        // The token only applies for the duration of the call
+        assert!(check().is_ok());
+    }
+
+    #[test]
+    fn tokens_are_per_thread() {
+        let token = CancellationToken::new();
+        token.cancel();
+
+        with_token(&token, || {
+            let other_thread = std::thread::spawn(|| check().is_ok()).join().unwrap();
+            assert!(other_thread);
+            assert!(check().is_err());
+        });
+    }
+}
@@ -1,17 +1,30 @@
 use crate::{
     Result,
     builder::{BuildOptions, BuildTarget},
+    cancel,
+    config::{BuildPriority, HttpConfig},
     error,
     messages::{BuildMessage, MessageReporter},
 };
+use backon::BlockingRetryable;
 use snafu::{OptionExt, ResultExt};
 use std::{
-    io::{BufRead, BufReader, Read},
+    collections::VecDeque,
+    ffi::OsString,
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
     path::{Path, PathBuf},
-    process::{Command, Stdio},
-    thread,
+    process::{Child, ChildStderr, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
 };
-use tracing::debug;
+use tracing::{debug, warn};
+
+#[cfg(unix)]
+use crate::config::IoPriority;
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
 
 pub(crate) use cargo_metadata::Metadata;
 
@@ -79,6 +92,10 @@ pub(crate) struct CargoMetadataOptions {
     /// Require Cargo.lock is up to date.
     /// Corresponds to `--locked` flag.
     pub locked: bool,
+
+    /// Cargo config that replaces dependency sources with vendored copies.
+    /// Corresponds to `--config` flag.
+    pub vendor_config: Option<PathBuf>,
 }
 
 impl From<&BuildOptions> for CargoMetadataOptions {
@@ -91,6 +108,7 @@ impl From<&BuildOptions> for CargoMetadataOptions {
             no_default_features: opts.no_default_features,
             offline: opts.offline,
             locked: opts.locked,
+            vendor_config: opts.vendor_config.clone(),
         }
     }
 }
@@ -116,6 +134,29 @@ pub(crate) trait CargoRunner: std::fmt::Debug + Send + Sync + 'static {
     /// * `options` - Options controlling metadata invocation (deps, features, platform, etc.)
     fn metadata(&self, source_dir: &Path, options: &CargoMetadataOptions) -> Result<Metadata>;
 
+    /// Download the dependencies of a crate ahead of building it.
+    ///
+    /// Executes `cargo fetch` in the specified directory, so that failures to download
+    /// dependencies are reported as such rather than surfacing as a failed build.  Failures are
+    /// retried using the retry and backoff settings in `http`.
+    ///
+    /// When `options.offline` is set nothing is downloaded; this instead verifies that every
+    /// dependency is already available locally, failing with
+    /// [`error::Error::DependenciesUnavailableOffline`] otherwise.
+    ///
+    /// The toolchain is selected the same way as for [`CargoRunner::build`].
+    fn fetch(&self, source_dir: &Path, options: &BuildOptions, http: &HttpConfig) -> Result<()>;
+
+    /// Copy the dependencies of a crate into `vendor_dir` with `cargo vendor`.
+    ///
+    /// Returns the cargo config that `cargo vendor` prints, which replaces the crate's registry
+    /// and git dependency sources with the copies in `vendor_dir`; passing it to cargo (see
+    /// [`BuildOptions::vendor_config`]) lets later builds run offline without cargo's own caches.
+    ///
+    /// The crate's `Cargo.lock` is honored if `options.locked` is set, and the toolchain is
+    /// selected the same way as for [`CargoRunner::build`].
+    fn vendor(&self, source_dir: &Path, vendor_dir: &Path, options: &BuildOptions) -> Result<String>;
+
     /// Build a binary from source.
     ///
     /// Executes cargo build with specified options and returns the absolute path
@@ -129,6 +170,7 @@ pub(crate) trait CargoRunner: std::fmt::Debug + Send + Sync + 'static {
     /// * `source_dir` - Directory containing Cargo.toml
     /// * `package` - Package name for `-p` flag (required for multi-package workspaces)
     /// * `options` - Build configuration
+    /// * `log_path` - File to save cargo's output in, so a failed build can be looked into
     ///
     /// # Toolchain Handling
     ///
@@ -149,9 +191,15 @@ pub(crate) trait CargoRunner: std::fmt::Debug + Send + Sync + 'static {
     ///
     /// - Cargo.toml not found in `source_dir`
     /// - Toolchain specified but rustup not found
-    /// - Cargo build command fails
+    /// - Cargo build command fails, in which case the error has the end of cargo's output
     /// - Expected binary not found in cargo's JSON output
-    fn build(&self, source_dir: &Path, package: Option<&str>, options: &BuildOptions) -> Result<PathBuf>;
+    fn build(
+        &self,
+        source_dir: &Path,
+        package: Option<&str>,
+        options: &BuildOptions,
+        log_path: &Path,
+    ) -> Result<PathBuf>;
 }
 
 /// Locate cargo and construct a runner instance that will use it.
@@ -183,10 +231,92 @@ struct RealCargoRunner {
     reporter: MessageReporter,
 }
 
+impl RealCargoRunner {
+    /// Construct the command to invoke cargo, going through rustup if a toolchain was specified,
+    /// at the priority in `options`.
+    fn cargo_command(&self, options: &BuildOptions) -> Result<Command> {
+        if let Some(toolchain) = &options.toolchain {
+            // If toolchain is specified, we need rustup
+            let rustup_path = self
+                .rustup_path
+                .as_ref()
+                .with_context(|| error::RustupNotFoundSnafu {
+                    toolchain: toolchain.clone(),
+                })?;
+
+            let mut cmd = prioritized_command(rustup_path, options.priority);
+            cmd.args(["run", toolchain, "cargo"]);
+            Ok(cmd)
+        } else {
+            Ok(prioritized_command(&self.cargo_path, options.priority))
+        }
+    }
+
+    /// Run `cargo fetch` once, without retrying.
+    ///
+    /// Cargo is given the CA bundle in `http`, if any, so that it trusts the same registries and
+    /// proxies cgx does.
+    fn fetch_once(&self, source_dir: &Path, options: &BuildOptions, http: &HttpConfig) -> Result<()> {
+        let mut cmd = self.cargo_command(options)?;
+        cmd.arg("fetch");
+        cmd.current_dir(source_dir);
+        if let Some(ca_bundle) = &http.ca_bundle {
+            cmd.env("CARGO_HTTP_CAINFO", ca_bundle);
+        }
+
+        if let Some(target) = &options.target {
+            cmd.args(["--target", target]);
+        }
+        if options.offline {
+            cmd.arg("--offline");
+        }
+        if options.locked {
+            cmd.arg("--locked");
+        }
+        if let Some(vendor_config) = &options.vendor_config {
+            cmd.arg("--config").arg(vendor_config);
+        }
+        if let Some(flag) = verbosity_flag(options.cargo_verbosity) {
+            cmd.arg(flag);
+        }
+
+        cmd.stdout(Stdio::null());
+        cmd.stderr(Stdio::piped());
+
+        let mut child = cmd.spawn().context(error::CommandExecutionSnafu)?;
+        let stderr_handle = child
+            .stderr
+            .take()
+            .map(|stderr| forward_stderr(stderr, self.reporter.clone(), None));
+
+        let status = wait_cancellable(&mut child);
+        if let Some(handle) = stderr_handle {
+            handle.join().expect("stderr thread panicked");
+        }
+        let status = status?;
+
+        if status.success() {
+            Ok(())
+        } else if options.offline {
+            error::DependenciesUnavailableOfflineSnafu {
+                source_dir: source_dir.to_path_buf(),
+            }
+            .fail()
+        } else {
+            error::CargoFetchFailedSnafu {
+                exit_code: status.code(),
+            }
+            .fail()
+        }
+    }
+}
+
 impl CargoRunner for RealCargoRunner {
     fn metadata(&self, source_dir: &Path, options: &CargoMetadataOptions) -> Result<Metadata> {
         use snafu::ResultExt;
 
+        cancel::check()?;
+
         let mut cmd = cargo_metadata::MetadataCommand::new();
         cmd.cargo_path(&self.cargo_path).current_dir(source_dir);
 
@@ -239,6 +369,11 @@ impl CargoRunner for RealCargoRunner {
             other_args.push("--locked".to_string());
         }
 
+        if let Some(vendor_config) = &options.vendor_config {
+            other_args.push("--config".to_string());
+            other_args.push(vendor_config.to_string_lossy().into_owned());
+        }
+
         if !other_args.is_empty() {
             cmd.other_options(other_args);
         }
@@ -249,7 +384,106 @@ impl CargoRunner for RealCargoRunner {
         })
     }
 
-    fn build(&self, source_dir: &Path, package: Option<&str>, options: &BuildOptions) -> Result<PathBuf> {
+    fn fetch(&self, source_dir: &Path, options: &BuildOptions, http: &HttpConfig) -> Result<()> {
+        if !source_dir.join("Cargo.toml").exists() {
+            return error::CargoTomlNotFoundSnafu {
+                source_dir: source_dir.to_path_buf(),
+            }
+            .fail();
+        }
+
+        self.reporter
+            .report(|| BuildMessage::fetch_started(options.offline));
+
+        // Only download failures are worth retrying; a dependency missing from an offline cache
+        // won't appear by trying again.
+        let mut attempt = 0;
+        (|| self.fetch_once(source_dir, options, http))
+            .retry(crate::http::backoff(http))
+            .when(|e| matches!(e, error::Error::CargoFetchFailed { .. }))
+            .notify(|err, delay| {
+                attempt += 1;
+                warn!("{err}; retrying in {delay:?}");
+                self.reporter
+                    .report(|| BuildMessage::fetch_retrying(attempt, delay));
+            })
+            .call()?;
+
+        self.reporter.report(BuildMessage::fetch_completed);
+        Ok(())
+    }
+
+    fn vendor(&self, source_dir: &Path, vendor_dir: &Path, options: &BuildOptions) -> Result<String> {
+        if !source_dir.join("Cargo.toml").exists() {
+            return error::CargoTomlNotFoundSnafu {
+                source_dir: source_dir.to_path_buf(),
+            }
+            .fail();
+        }
+
+        cancel::check()?;
+
+        let mut cmd = self.cargo_command(options)?;
+        cmd.arg("vendor");
+        cmd.current_dir(source_dir);
+
+        // A mirror configured in place of crates.io is where the dependencies would come from
+        // when building, so it's where they're vendored from too
+        cmd.arg("--respect-source-config");
+        if options.locked {
+            cmd.arg("--locked");
+        }
+        if let Some(flag) = verbosity_flag(options.cargo_verbosity) {
+            cmd.arg(flag);
+        }
+        cmd.arg(vendor_dir);
+
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+
+        let mut child = cmd.spawn().context(error::CommandExecutionSnafu)?;
+        let stderr_handle = child
+            .stderr
+            .take()
+            .map(|stderr| forward_stderr(stderr, self.reporter.clone(), None));
+
+        // The config is printed to stdout, which is read on a thread of its own so that waiting
+        // for cargo can still be cancelled
+        let stdout_handle = child.stdout.take().map(|mut stdout| {
+            thread::spawn(move || {
+                let mut config = String::new();
+                let _ = stdout.read_to_string(&mut config);
+                config
+            })
+        });
+
+        let status = wait_cancellable(&mut child);
+        if let Some(handle) = stderr_handle {
+            handle.join().expect("stderr thread panicked");
+        }
+        let config = stdout_handle
+            .map(|handle| handle.join().expect("stdout thread panicked"))
+            .unwrap_or_default();
+        let status = status?;
+
+        if status.success() {
+            Ok(config)
+        } else {
+            error::CargoVendorFailedSnafu {
+                source_dir: source_dir.to_path_buf(),
+                exit_code: status.code(),
+            }
+            .fail()
+        }
+    }
+
+    fn build(
+        &self,
+        source_dir: &Path,
+        package: Option<&str>,
+        options: &BuildOptions,
+        log_path: &Path,
+    ) -> Result<PathBuf> {
         // Verify Cargo.toml exists
         if !source_dir.join("Cargo.toml").exists() {
             return error::CargoTomlNotFoundSnafu {
@@ -261,21 +495,7 @@ impl CargoRunner for RealCargoRunner {
         self.reporter.report(|| BuildMessage::started(options));
 
         // Build the command
-        let mut cmd = if let Some(toolchain) = &options.toolchain {
-            // If toolchain is specified, we need rustup
-            let rustup_path = self
-                .rustup_path
-                .as_ref()
-                .with_context(|| error::RustupNotFoundSnafu {
-                    toolchain: toolchain.clone(),
-                })?;
-
-            let mut cmd = Command::new(rustup_path);
-            cmd.args(["run", toolchain, "cargo"]);
-            cmd
-        } else {
-            Command::new(&self.cargo_path)
-        };
+        let mut cmd = self.cargo_command(options)?;
 
         // Add cargo build command and flags
         cmd.arg("build");
@@ -338,19 +558,16 @@ impl CargoRunner for RealCargoRunner {
         if options.locked {
             cmd.arg("--locked");
         }
+        if let Some(vendor_config) = &options.vendor_config {
+            cmd.arg("--config").arg(vendor_config);
+        }
+        if let Some(target_dir) = &options.target_dir {
+            cmd.arg("--target-dir").arg(target_dir);
+        }
 
         // Verbosity flags
-        match options.cargo_verbosity {
-            CargoVerbosity::Normal => {}
-            CargoVerbosity::Verbose => {
-                cmd.arg("-v");
-            }
-            CargoVerbosity::VeryVerbose => {
-                cmd.arg("-vv");
-            }
-            CargoVerbosity::ExtremelyVerbose => {
-                cmd.arg("-vvv");
-            }
+        if let Some(flag) = verbosity_flag(options.cargo_verbosity) {
+            cmd.arg(flag);
         }
 
         // Configure pipes for streaming
@@ -377,6 +594,9 @@ impl CargoRunner for RealCargoRunner {
         // Clone build target for stdout thread
         let build_target = options.build_target.clone();
 
+        let log = BuildLog::create(log_path);
+        let stdout_log = log.clone();
+
         // Spawn stdout parsing thread
         let stdout_handle = thread::spawn(move || {
             debug!("stdout parser thread starting");
@@ -392,6 +612,14 @@ impl CargoRunner for RealCargoRunner {
                 if let Ok(cargo_msg) = serde_json::from_str::<cargo_metadata::Message>(&line) {
                     stdout_reporter.report(|| BuildMessage::cargo_message(cargo_msg.clone()));
 
+                    // With JSON output, compiler errors and warnings come on stdout rather than
+                    // stderr, so their human-readable form is what goes in the log
+                    if let cargo_metadata::Message::CompilerMessage(msg) = &cargo_msg {
+                        if let Some(rendered) = &msg.message.rendered {
+                            stdout_log.append(rendered.as_bytes());
+                        }
+                    }
+
                     if let cargo_metadata::Message::CompilerArtifact(artifact) = &cargo_msg {
                         let kinds = &artifact.target.kind;
                         let name = &artifact.target.name;
@@ -423,35 +651,23 @@ impl CargoRunner for RealCargoRunner {
             binary_path
         });
 
-        // Spawn stderr chunk reading thread
-        let stderr_handle = thread::spawn(move || {
-            debug!("stderr reader thread starting");
-            let mut reader = BufReader::new(stderr);
-            let mut buffer = [0u8; 4096];
-
-            loop {
-                match reader.read(&mut buffer) {
-                    Ok(0) | Err(_) => break,
-                    Ok(n) => {
-                        let chunk = buffer[..n].to_vec();
-                        stderr_reporter.report(|| BuildMessage::cargo_stderr(chunk));
-                    }
-                }
-            }
-
-            debug!("stderr reader thread exiting");
-        });
+        let stderr_handle = forward_stderr(stderr, stderr_reporter, Some(log.clone()));
 
         // Wait for process completion
-        let status = child.wait().context(error::CommandExecutionSnafu)?;
+        let status = wait_cancellable(&mut child);
 
-        // Join both threads after wait() returns
+        // Join both threads after the process exits, even if it was killed, so that nothing is
+        // left reading from its pipes
         let binary_path = stdout_handle.join().expect("stdout thread panicked");
         stderr_handle.join().expect("stderr thread panicked");
+        let status = status?;
 
         if !status.success() {
             return error::CargoBuildFailedSnafu {
                 exit_code: status.code(),
+                log_tail: log.tail(),
+                log_path: log.path(),
+                diagnoses: crate::build_diagnostics::diagnose(&log.contents()),
             }
             .fail();
         }
@@ -466,7 +682,247 @@ impl CargoRunner for RealCargoRunner {
     }
 }
 
+/// The cargo command line flag corresponding to a verbosity level, if any.
+fn verbosity_flag(verbosity: CargoVerbosity) -> Option<&'static str> {
+    match verbosity {
+        CargoVerbosity::Normal => None,
+        CargoVerbosity::Verbose => Some("-v"),
+        CargoVerbosity::VeryVerbose => Some("-vv"),
+        CargoVerbosity::ExtremelyVerbose => Some("-vvv"),
+    }
+}
+
+/// How often a running cargo process is checked on to see whether it should be killed because
+/// the operation was cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Wait for `child` to exit, killing it if the current operation is cancelled in the meantime.
+fn wait_cancellable(child: &mut Child) -> Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait().context(error::CommandExecutionSnafu)? {
+            return Ok(status);
+        }
+
+        if cancel::current().is_cancelled() {
+            debug!("Operation cancelled; killing cargo process {}", child.id());
+            // The process may have exited on its own since it was checked; either way it's gone
+            let _ = child.kill();
+            child.wait().context(error::CommandExecutionSnafu)?;
+            return error::CancelledSnafu.fail();
+        }
+
+        thread::sleep(CANCEL_POLL_INTERVAL);
+    }
+}
+
+/// Spawn a thread that reports everything cargo writes to stderr as
+/// [`BuildMessage::CargoStderr`] chunks, until the pipe is closed, also appending it to `log` if
+/// there is one.
+fn forward_stderr(stderr: ChildStderr, reporter: MessageReporter, log: Option<BuildLog>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        debug!("stderr reader thread starting");
+        let mut reader = BufReader::new(stderr);
+        let mut buffer = [0u8; 4096];
+
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if let Some(log) = &log {
+                        log.append(&buffer[..n]);
+                    }
+                    let chunk = buffer[..n].to_vec();
+                    reporter.report(|| BuildMessage::cargo_stderr(chunk));
+                }
+            }
+        }
+
+        debug!("stderr reader thread exiting");
+    })
+}
+
+/// How much of the end of cargo's output is kept to be included in the error when a build fails.
+const BUILD_LOG_TAIL_BYTES: usize = 16 * 1024;
+
+/// The output of a cargo build, saved in full to a log file and with its last
+/// [`BUILD_LOG_TAIL_BYTES`] kept in memory.
+///
+/// Clones share the same log, so the threads reading cargo's stdout and stderr can both append to
+/// it.
+#[derive(Clone)]
+struct BuildLog(Arc<Mutex<BuildLogInner>>);
+
+struct BuildLogInner {
+    /// The log file and its path, unless it couldn't be created.
+    file: Option<(File, PathBuf)>,
+    tail: VecDeque<u8>,
+    /// Whether anything has been dropped from the start of `tail`.
+    truncated: bool,
+}
+
+impl BuildLog {
+    /// Start a log saved to `path`.
+    ///
+    /// A log file that can't be created only loses the full output, not the build, so that's
+    /// logged rather than treated as an error.
+    fn create(path: &Path) -> Self {
+        let file = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| File::create(path))
+            .inspect_err(|e| debug!("Failed to create build log {}: {}", path.display(), e))
+            .ok()
+            .map(|file| (file, path.to_path_buf()));
+
+        Self(Arc::new(Mutex::new(BuildLogInner {
+            file,
+            tail: VecDeque::with_capacity(BUILD_LOG_TAIL_BYTES),
+            truncated: false,
+        })))
+    }
+
+    fn append(&self, bytes: &[u8]) {
+        let mut inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some((file, path)) = &mut inner.file {
+            if let Err(e) = file.write_all(bytes) {
+                debug!("Failed to write to build log {}: {}", path.display(), e);
+            }
+        }
+
+        inner.tail.extend(bytes);
+        let excess = inner.tail.len().saturating_sub(BUILD_LOG_TAIL_BYTES);
+        if excess > 0 {
+            inner.tail.drain(..excess);
+            inner.truncated = true;
+        }
+    }
+
+    /// The end of the output, starting from the first whole line.
+    fn tail(&self) -> String {
+        let inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let bytes = inner.tail.iter().copied().collect::<Vec<u8>>();
+        let text = String::from_utf8_lossy(&bytes);
+
+        let text = if inner.truncated {
+            text.split_once('\n').map_or(&*text, |(_, rest)| rest)
+        } else {
+            &text
+        };
+        text.trim_end().to_string()
+    }
+
+    /// All of the output, from the log file, or just the end of it if the file can't be read.
+    ///
+    /// A build script's error can be well before the end of the output when other crates were
+    /// being built at the same time, so it's looked for in all of it.
+    fn contents(&self) -> String {
+        self.path().and_then(|path| std::fs::read(path).ok()).map_or_else(
+            || self.tail(),
+            |bytes| String::from_utf8_lossy(&bytes).into_owned(),
+        )
+    }
+
+    /// The path of the log file, if it could be created.
+    fn path(&self) -> Option<PathBuf> {
+        let inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        inner.file.as_ref().map(|(_, path)| path.clone())
+    }
+}
+
+/// Environment variables that a cargo subcommand is run with, to match what it gets when cargo
+/// runs it.
+///
+/// Cargo sets `CARGO` to its own path, which subcommands use to run cargo themselves, and puts
+/// the directory it's in at the front of `PATH`.  With a `toolchain`, `RUSTUP_TOOLCHAIN` makes
+/// that cargo, and the `rustc` it runs, the ones from that toolchain.  If cargo can't be found,
+/// only the toolchain is set.
+pub fn subcommand_env(toolchain: Option<&str>) -> Vec<(OsString, OsString)> {
+    let mut env = Vec::new();
+
+    if let Ok(cargo_path) = find_executable("cargo", "CARGO") {
+        if let Some(cargo_dir) = cargo_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            let path = std::env::var_os("PATH").unwrap_or_default();
+            let dirs = std::iter::once(cargo_dir.to_path_buf())
+                .chain(std::env::split_paths(&path).filter(|dir| dir != cargo_dir));
+            match std::env::join_paths(dirs) {
+                Ok(path) => env.push((OsString::from("PATH"), path)),
+                Err(e) => debug!("Not adding {} to PATH: {}", cargo_dir.display(), e),
+            }
+        }
+        env.push((OsString::from("CARGO"), cargo_path.into_os_string()));
+    }
+
+    if let Some(toolchain) = toolchain {
+        env.push((OsString::from("RUSTUP_TOOLCHAIN"), OsString::from(toolchain)));
+    }
+
+    env
+}
+
 /// Find an executable by name, checking environment variable, PATH, and default locations.
+/// A command to run `program` at `priority`.
+///
+/// Unix has no way to lower the priority of a child without `unsafe`, so `program` is run through
+/// `nice` and `ionice` instead, whichever of them is needed and can be found.  Those that can't
+/// are skipped, leaving cargo at the usual priority of that kind.
+#[cfg(unix)]
+fn prioritized_command(program: &Path, priority: BuildPriority) -> Command {
+    let mut wrapper: Vec<OsString> = Vec::new();
+
+    if let Some(nice) = priority.nice.filter(|nice| *nice > 0) {
+        if let Ok(path) = which::which("nice") {
+            wrapper.extend([path.into(), "-n".into(), nice.to_string().into()]);
+        } else {
+            debug!("`nice` wasn't found, so cargo runs at the usual CPU priority");
+        }
+    }
+
+    let ionice_args: &[&str] = match priority.io.unwrap_or_default() {
+        IoPriority::Normal => &[],
+        IoPriority::Low => &["-c", "2", "-n", "7"],
+        IoPriority::Idle => &["-c", "3"],
+    };
+    if !ionice_args.is_empty() && cfg!(target_os = "linux") {
+        if let Ok(path) = which::which("ionice") {
+            wrapper.push(path.into());
+            wrapper.extend(ionice_args.iter().map(OsString::from));
+        } else {
+            debug!("`ionice` wasn't found, so cargo runs at the usual IO priority");
+        }
+    }
+
+    if wrapper.is_empty() {
+        return Command::new(program);
+    }
+
+    let mut cmd = Command::new(&wrapper[0]);
+    cmd.args(&wrapper[1..]).arg(program);
+    cmd
+}
+
+/// A command to run `program` at `priority`.
+///
+/// The niceness picks the priority class the process is created in; Windows has nothing to match
+/// the IO priority with.
+#[cfg(windows)]
+fn prioritized_command(program: &Path, priority: BuildPriority) -> Command {
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+    const IDLE_PRIORITY_CLASS: u32 = 0x0000_0040;
+
+    let mut cmd = Command::new(program);
+    match priority.nice.unwrap_or(0) {
+        0 => {}
+        1..=14 => {
+            cmd.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+        }
+        _ => {
+            cmd.creation_flags(IDLE_PRIORITY_CLASS);
+        }
+    }
+    cmd
+}
+
 fn find_executable(name: &str, env_var: &str) -> Result<PathBuf> {
     // Check environment variable
     if let Ok(path) = std::env::var(env_var) {
@@ -510,6 +966,7 @@ fn find_executable(name: &str, env_var: &str) -> Result<PathBuf> {
 mod tests {
     use super::*;
     use crate::{builder::BuildTarget, testdata::CrateTestCase};
+    use assert_matches::assert_matches;
 
     /// Get the path to the cgx workspace root directory.
     fn cgx_project_root() -> PathBuf {
@@ -529,6 +986,45 @@ mod tests {
         let _cargo = find_cargo(MessageReporter::null()).unwrap();
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn prioritized_command_runs_through_nice() {
+        let cargo = Path::new("/usr/bin/cargo");
+
+        let cmd = prioritized_command(cargo, BuildPriority::default());
+        assert_eq!(cmd.get_program(), cargo);
+        assert_eq!(cmd.get_args().count(), 0);
+
+        let Ok(nice) = which::which("nice") else {
+            return;
+        };
+        let cmd = prioritized_command(
+            cargo,
+            BuildPriority {
+                nice: Some(10),
+                io: None,
+            },
+        );
+        assert_eq!(cmd.get_program(), nice);
+        assert_eq!(cmd.get_args().collect::<Vec<_>>(), ["-n", "10", "/usr/bin/cargo"]);
+    }
+
+    #[test]
+    fn subcommand_env_points_at_cargo() {
+        let env = subcommand_env(Some("nightly"));
+        let var = |name: &str| {
+            env.iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+
+        let cargo = PathBuf::from(var("CARGO").unwrap());
+        assert!(cargo.exists());
+        let path = var("PATH").unwrap();
+        assert_eq!(std::env::split_paths(&path).next().as_deref(), cargo.parent());
+        assert_eq!(var("RUSTUP_TOOLCHAIN"), Some(OsString::from("nightly")));
+    }
+
     #[test]
     fn metadata_reads_cgx_crate() {
         crate::logging::init_test_logging();
@@ -590,7 +1086,11 @@ mod tests {
             ..Default::default()
         };
 
-        let binary_path = cargo.build(temp_dir.path(), Some("cgx"), &options).unwrap();
+        let log_path = temp_dir.path().join("logs/build.log");
+        let binary_path = cargo
+            .build(temp_dir.path(), Some("cgx"), &options, &log_path)
+            .unwrap();
+        assert!(log_path.is_file(), "Build log should be saved");
 
         // Verify binary exists and is a file
         assert!(binary_path.exists(), "Binary should exist at {:?}", binary_path);
@@ -605,6 +1105,131 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fetch_offline_succeeds_without_deps() {
+        crate::logging::init_test_logging();
+
+        let cargo = find_cargo(MessageReporter::null()).unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        crate::helpers::copy_source_tree(CrateTestCase::simple_bin_no_deps().path(), temp_dir.path())
+            .unwrap();
+
+        // Without any dependencies there's nothing to download, even if the lock file has to be
+        // generated.
+        let options = BuildOptions {
+            offline: true,
+            locked: false,
+            ..Default::default()
+        };
+
+        cargo
+            .fetch(temp_dir.path(), &options, &HttpConfig::default())
+            .unwrap();
+    }
+
+    #[test]
+    fn fetch_offline_fails_when_deps_missing() {
+        crate::logging::init_test_logging();
+
+        let cargo = find_cargo(MessageReporter::null()).unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            r#"[package]
+name = "needs-missing-dep"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+cgx-test-crate-that-does-not-exist = "1.0.0"
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir(temp_dir.path().join("src")).unwrap();
+        std::fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+
+        let options = BuildOptions {
+            offline: true,
+            ..Default::default()
+        };
+
+        let result = cargo.fetch(temp_dir.path(), &options, &HttpConfig::default());
+        assert_matches!(result, Err(error::Error::DependenciesUnavailableOffline { .. }));
+    }
+
+    #[test]
+    fn build_failure_has_compiler_errors_and_log() {
+        crate::logging::init_test_logging();
+
+        let cargo = find_cargo(MessageReporter::null()).unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            r#"[package]
+name = "does-not-compile"
+version = "0.1.0"
+edition = "2021"
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir(temp_dir.path().join("src")).unwrap();
+        std::fs::write(
+            temp_dir.path().join("src/main.rs"),
+            "fn main() { let x: u32 = \"not a number\"; }\n",
+        )
+        .unwrap();
+
+        let options = BuildOptions {
+            profile: Some("dev".to_string()),
+            offline: true,
+            ..Default::default()
+        };
+        let log_path = temp_dir.path().join("logs/build.log");
+
+        let result = cargo.build(temp_dir.path(), None, &options, &log_path);
+        assert_matches!(result, Err(error::Error::CargoBuildFailed { log_tail, log_path: Some(path), .. }) => {
+            assert!(log_tail.contains("mismatched types"), "{log_tail}");
+            assert_eq!(path, log_path);
+            assert!(std::fs::read_to_string(&path).unwrap().contains("mismatched types"));
+        });
+    }
+
+    #[test]
+    fn build_log_tail_starts_at_whole_line() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = BuildLog::create(&temp_dir.path().join("build.log"));
+
+        log.append(b"first line\n");
+        assert_eq!(log.tail(), "first line");
+
+        let line = format!("{}\n", "x".repeat(99));
+        for _ in 0..(BUILD_LOG_TAIL_BYTES / line.len() + 10) {
+            log.append(line.as_bytes());
+        }
+        log.append(b"last line\n");
+
+        let tail = log.tail();
+        assert!(tail.len() <= BUILD_LOG_TAIL_BYTES);
+        assert!(tail.starts_with(&line[..99]), "tail should start at a whole line");
+        assert!(tail.ends_with("\nlast line"));
+        assert!(!tail.contains("first line"));
+
+        // The file has all of it
+        let contents = std::fs::read_to_string(temp_dir.path().join("build.log")).unwrap();
+        assert!(contents.starts_with("first line\n"));
+    }
+
+    #[test]
+    fn fetch_requires_cargo_toml() {
+        crate::logging::init_test_logging();
+
+        let cargo = find_cargo(MessageReporter::null()).unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let result = cargo.fetch(temp_dir.path(), &BuildOptions::default(), &HttpConfig::default());
+        assert_matches!(result, Err(error::Error::CargoTomlNotFound { .. }));
+    }
+
     #[test]
     fn metadata_loads_all_testcases() {
         crate::logging::init_test_logging();
@@ -1,5 +1,12 @@
-use crate::config::{BinaryProvider, UsePrebuiltBinaries};
-use clap::{ArgAction, CommandFactory, Parser, ValueEnum, builder::TypedValueParser};
+use crate::{
+    Result,
+    config::{
+        BinaryProvider, Config, DefaultBinPolicy, LocalTargetDir, PrebuiltOrigin, RefreshLayer,
+        UsePrebuiltBinaries,
+    },
+    error,
+};
+use clap::{ArgAction, ArgGroup, CommandFactory, Parser, ValueEnum, builder::TypedValueParser};
 use std::{collections::HashSet, path::PathBuf};
 use strum::VariantNames;
 
@@ -20,6 +27,24 @@ pub enum MessageFormat {
     Json,
 }
 
+/// Format of the manifest written by `--freeze`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FreezeFormat {
+    /// TOML, like a config file
+    Toml,
+    /// JSON
+    Json,
+}
+
+/// Format of the variables printed by `--emit`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum EmitFormat {
+    /// `export` statements for a POSIX shell to evaluate
+    ShellEnv,
+    /// A JSON object
+    Json,
+}
+
 /// CLI arguments that are crate-specific and passed through to cargo build.
 ///
 /// These args are segreated from the other CLI args to make the semantic distinction more
@@ -39,6 +64,10 @@ pub struct BuildOptionsArgs {
     #[arg(long)]
     pub no_default_features: bool,
 
+    /// Build with the features of the named preset from `[presets]` in config
+    #[arg(long, value_name = "NAME")]
+    pub preset: Option<String>,
+
     /// Build with the specified profile
     #[arg(long, value_name = "PROFILE-NAME", conflicts_with = "debug")]
     pub profile: Option<String>,
@@ -47,9 +76,9 @@ pub struct BuildOptionsArgs {
     #[arg(long)]
     pub debug: bool,
 
-    /// Build for the target triple
+    /// Build for the target triple; repeat to build for several targets at once (with --no-exec)
     #[arg(long, value_name = "TRIPLE")]
-    pub target: Option<String>,
+    pub target: Vec<String>,
 
     /// Number of parallel jobs, defaults to # of CPUs
     #[arg(short = 'j', long, value_name = "N")]
@@ -68,10 +97,29 @@ pub struct BuildOptionsArgs {
     pub example: Option<String>,
 }
 
+impl BuildOptionsArgs {
+    /// These args for building the tool `tool`, which is built with the preset its [`ToolConfig`]
+    /// names if `--preset` didn't name one.
+    ///
+    /// [`ToolConfig`]: crate::config::ToolConfig
+    pub fn for_tool(&self, config: &Config, tool: Option<&str>) -> Self {
+        let preset = self
+            .preset
+            .clone()
+            .or_else(|| tool.and_then(|tool| config.tool_preset(tool)).map(str::to_string));
+
+        Self {
+            preset,
+            ..self.clone()
+        }
+    }
+}
+
 #[derive(Clone, Debug, Parser)]
 #[command(name = "cgx")]
 #[command(about = "Rust equivalent of uvx or npx, for use with Rust crates")]
 #[command(disable_version_flag = true)]
+#[command(group(ArgGroup::new("config_edit").args(["pin", "unpin", "alias", "unalias"])))]
 #[non_exhaustive]
 pub struct CliArgs {
     /// Rust toolchain to use for building (e.g., +nightly, +stable, +1.70.0)
@@ -102,6 +150,9 @@ pub struct CliArgs {
     pub gitlab: Option<String>,
 
     /// Registry index URL to use
+    ///
+    /// A `file://` URL of a cargo local registry (a directory of `.crate` files with an `index`
+    /// directory, as made by `cargo local-registry`) is read from disk without network access.
     #[arg(long, conflicts_with_all = ["git", "registry", "path", "github", "gitlab"], value_name = "INDEX")]
     pub index: Option<String>,
 
@@ -130,9 +181,37 @@ pub struct CliArgs {
     /// When used without a value (e.g., `cgx --version`), prints the version of cgx itself.
     /// When used with a value (e.g., `cgx foo --version 1.0`), specifies the version of the
     /// crate to install (alternative to @VERSION suffix in crate name).
+    ///
+    /// Combined with `--message-format json`, the version of cgx is printed to stdout as a JSON
+    /// object that also includes the git sha, build date, enabled features, and supported message
+    /// schema versions.
     #[arg(short = 'V', long, num_args = 0..=1, default_missing_value = "", value_name = "VERSION")]
     pub version: Option<String>,
 
+    /// Only use a version of the crate at least this new, on top of any other version
+    /// requirement.
+    ///
+    /// A resolution cached before that version was released doesn't satisfy this, so it also gets
+    /// a crate that's stuck on an older release unstuck without a full `--refresh`.
+    #[arg(long, value_name = "VERSION")]
+    pub min_version: Option<semver::Version>,
+
+    /// Ignore the versions tools are pinned to under `[tools]`, for this run only.
+    ///
+    /// A tool's other settings, like where it comes from and its features, are still used, but
+    /// any version it's pinned to is not, nor are the digests of prebuilt binaries pinned with it.
+    #[arg(long, conflicts_with = "config_edit")]
+    pub ignore_pins: bool,
+
+    /// Pin a tool to a version for this run only, like `ripgrep@13`, in place of whatever
+    /// `[tools]` pins it to.
+    ///
+    /// Takes the same form as `--pin`, and may be given more than once.  It's applied after
+    /// `--ignore-pins`, and like a pin in the config, a `--version` or `@VERSION` given with the
+    /// crate itself takes precedence over it.
+    #[arg(long, value_name = "TOOL@VERSION", conflicts_with = "config_edit")]
+    pub override_pin: Vec<String>,
+
     /// Build-specific options that are passed through to cargo.
     #[command(flatten)]
     pub build_options: BuildOptionsArgs,
@@ -166,11 +245,58 @@ pub struct CliArgs {
     #[arg(long)]
     pub offline: bool,
 
+    /// Vendor the dependencies of crates built from source into the cache
+    ///
+    /// The first build of a crate version while online runs `cargo vendor`, and every build of it
+    /// after that uses the vendored copies, so it can be built again with --offline even once
+    /// cargo's own registry cache no longer has them.
+    #[arg(long)]
+    pub vendor: bool,
+
+    /// Where cargo puts what it builds for a crate in a local directory: `workspace`, `isolated`,
+    /// or a path
+    ///
+    /// `workspace`, the default, builds in the target directory the project's own builds use, so
+    /// whatever they've already compiled with the same profile is reused.  `isolated` uses a
+    /// directory of cgx's own in the build dir instead, so that the project's builds are left
+    /// alone.  A relative path is taken to be relative to the crate's directory.
+    #[arg(long, value_name = "DIR")]
+    pub local_target_dir: Option<LocalTargetDir>,
+
+    /// Build at the lowest CPU and IO priority, so as not to slow down other work
+    ///
+    /// Meant for getting tools ahead of time, as with --batch.  Cargo, and everything it runs,
+    /// is run at the priority given by `[build_priority]` in the config, or the lowest there is
+    /// wherever that doesn't say.
+    #[arg(long)]
+    pub background: bool,
+
+    /// Give up on getting the binary after this long (e.g., "10m", "1h")
+    ///
+    /// Counts the time spent resolving, downloading and building, from when cgx starts and, with
+    /// --batch, across every crate.  Whatever phase is in progress when it passes is cancelled,
+    /// killing cargo if it's building, and cgx fails with an error naming that phase.  Overrides
+    /// `deadline` in the `[timeouts]` config, where each phase can be given a timeout of its own
+    /// too.  The tool's own runtime is limited with --max-runtime instead.
+    #[arg(long, value_name = "DURATION", env = "CGX_DEADLINE")]
+    pub deadline: Option<String>,
+
+    /// Show what changed in a crate since the version last run here
+    ///
+    /// When a crate resolves to a newer version than the one last run on this machine, the
+    /// entries for the versions in between are printed from the changelog in its source before
+    /// it's run, so that changes to its command line don't go unnoticed.
+    #[arg(long)]
+    pub release_notes: bool,
+
     /// Use verbose output (-vv very verbose/build.rs output)
     #[arg(short = 'v', long, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
-    /// Do not print cargo log messages
+    /// Do not print cargo log messages.
+    ///
+    /// On an interactive terminal, progress is instead summarized on a single status line, and
+    /// cargo's output is only shown if the build fails.
     #[arg(short = 'q', long)]
     pub quiet: bool,
 
@@ -279,6 +405,17 @@ pub struct CliArgs {
     #[arg(long, value_name = "URL", env = "CGX_HTTP_PROXY")]
     pub http_proxy: Option<String>,
 
+    /// Refuse any single download larger than this (e.g., "200M", "2G").
+    ///
+    /// Applies to pre-built binaries and crate source tarballs, and guards against a wrongly
+    /// matched release asset or a misbehaving server.  Accepts a byte count with an optional K,
+    /// M, G or T suffix (powers of 1024).  Downloads that announce a larger size are refused up
+    /// front, and others are abandoned as soon as they go past the limit.
+    ///
+    /// If not set, the `max_download_size` setting in the `[http]` config is used, or else 1G.
+    #[arg(long, value_name = "SIZE", env = "CGX_MAX_DOWNLOAD_SIZE")]
+    pub max_download_size: Option<String>,
+
     /// Build the binary but do not execute it; print its path to stdout instead.
     ///
     /// Performs all normal operations (resolve, download, build) but instead of executing
@@ -290,14 +427,115 @@ pub struct CliArgs {
     #[arg(long)]
     pub no_exec: bool,
 
-    /// Force refresh of all cached data for this crate.
+    /// Stage the binary in a directory of its own for this run, and delete it once the tool exits.
+    ///
+    /// The binary is built or downloaded into a temporary directory instead of the binary cache,
+    /// and cgx runs the tool as a child process and waits for it, rather than replacing itself
+    /// with it, so that it can remove the directory afterwards.  Crate sources, git checkouts and
+    /// resolved versions are still cached as usual, so running the same tool again needn't
+    /// download its source again.
+    #[arg(long, conflicts_with_all = ["no_exec", "pin_build", "batch", "freeze", "list_installed"])]
+    pub ephemeral: bool,
+
+    /// Start the tool in the background and exit, leaving it running, as for a dev server or file
+    /// watcher.
+    ///
+    /// The tool runs as a child process in a process group of its own, with nothing on its
+    /// standard input, and its standard output and error appended to log files in the `run`
+    /// directory under the app directory, next to a pidfile naming it after its crate.  Manage it
+    /// afterwards with `--ps` and `--stop`.
+    #[arg(long, conflicts_with_all = ["no_exec", "ephemeral", "max_runtime", "pin_build", "batch", "freeze", "list_installed"])]
+    pub detach: bool,
+
+    /// Copy the binary into this directory, as well as keeping it in the cache.
+    ///
+    /// When building for several targets, each binary goes into a subdirectory named after its
+    /// target.  Combined with `--no-exec`, the paths printed are those of the copies.  With
+    /// `--fetch-source`, the crate's source is copied here instead.
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+
+    /// Kill the executed tool if it is still running after this long (e.g., "30s", "10m").
+    ///
+    /// When set, cgx spawns the tool as a child process instead of replacing itself with it, and
+    /// terminates the child once the limit elapses. In that case cgx exits with code 124, the
+    /// same convention used by `timeout(1)`.
+    #[arg(long, value_name = "DURATION", env = "CGX_MAX_RUNTIME")]
+    pub max_runtime: Option<String>,
+
+    /// Limit the virtual memory available to the executed tool (e.g., "512M", "2G").
+    ///
+    /// Accepts a byte count with an optional K, M, G or T suffix (powers of 1024). Allocations
+    /// beyond the limit fail inside the tool. When set, cgx spawns the tool as a child process
+    /// instead of replacing itself with it, and if the tool is killed by a signal (as it is when
+    /// it aborts on a failed allocation) cgx reports that it most likely ran out of memory under
+    /// the limit.
+    ///
+    /// Only supported on Unix platforms. Windows job objects are not implemented, as cgx can't
+    /// create one without unsafe code, so on Windows the limit is refused (CGX0045).
+    #[arg(long, value_name = "SIZE", env = "CGX_MAX_MEMORY")]
+    pub max_memory: Option<String>,
+
+    /// Run the tool's binary under this name instead of its own (its `argv[0]`).
+    ///
+    /// This is for multi-call binaries like `busybox` or `uutils` that act as a different tool
+    /// depending on the name they are invoked as.  On Windows, which has no separate `argv[0]`,
+    /// the name is passed as the first argument instead.  Overrides the `argv0` setting of the
+    /// tool in the config file.
+    #[arg(long, value_name = "NAME")]
+    pub argv0: Option<String>,
+
+    /// Run the tool under this command, such as "valgrind --leak-check=full" or "strace -f".
     ///
-    /// When set, cgx will bypass all cache lookups and perform fresh resolution, download, and
-    /// build operations. This also disables the fallback to stale cache entries on network errors,
-    /// so cgx will fail if a network error occurs rather than using potentially outdated cached
-    /// data.
-    #[arg(long)]
-    pub refresh: bool,
+    /// The command is split into words like a shell would, and run with the tool's path and
+    /// arguments appended, in place of the tool.  Overrides the `wrapper` setting of the tool in
+    /// the config file; pass an empty string to run a tool without the wrapper it is configured
+    /// with.
+    #[arg(long, value_name = "COMMAND")]
+    pub wrapper: Option<String>,
+
+    /// Read the arguments to pass to the tool from a file, instead of the command line.
+    ///
+    /// The file holds each argument exactly as the tool should receive it: either a JSON array of
+    /// strings if its name ends in `.json`, or otherwise one argument per line.  Nothing is split,
+    /// unquoted or interpreted as a cgx option, so this is the most reliable way for scripts to
+    /// pass arbitrary arguments.  No arguments may follow the crate on the command line.
+    #[arg(long, value_name = "PATH")]
+    pub args_file: Option<PathBuf>,
+
+    /// Force refresh of cached data for this crate: resolve, source, binary, or all (the default).
+    ///
+    /// When set, cgx bypasses the cache lookups of the given layers and performs fresh resolution,
+    /// download, or binary lookup and build for them; several can be given separated by commas.
+    /// This also disables the fallback to stale cache entries on network errors, so cgx will fail
+    /// if a network error occurs rather than using potentially outdated cached data.  A value must
+    /// be given with `=`, as in `--refresh=source`.
+    #[arg(
+        long,
+        value_name = "LAYERS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all",
+        value_delimiter = ',',
+        value_parser = strum_value_parser!(RefreshLayer)
+    )]
+    pub refresh: Vec<RefreshLayer>,
+
+    /// Resolve crates as they were on this date (e.g., "2024-06-01"), to reproduce an old
+    /// environment
+    ///
+    /// A crate from crates.io resolves to the newest matching version published before the date.
+    /// One from git with no --branch, --tag or --rev resolves to the newest release tag committed
+    /// before the date, or else the last commit on the default branch before it.  The date is
+    /// recorded in the manifest written by --freeze and the plan written by --plan.
+    #[arg(long, value_name = "DATE")]
+    pub as_of: Option<String>,
+
+    /// What to run for a crate with several binaries and no `default-run`, when `--bin` isn't
+    /// given: error (fail, listing the binaries), match-crate-name (the binary named after the
+    /// crate), or first (the first binary in the crate's manifest).
+    #[arg(long, value_name = "POLICY", value_parser = strum_value_parser!(DefaultBinPolicy))]
+    pub default_bin_policy: Option<DefaultBinPolicy>,
 
     /// Control use of pre-built binaries: never (always build from source), always (fail if no
     /// prebuilt binary found), or auto (use if available, fallback to build).
@@ -336,6 +574,34 @@ pub struct CliArgs {
     #[arg(long)]
     pub prebuilt_binary_no_verify_signatures: bool,
 
+    /// Target triples to accept pre-built binaries for, in order of preference.
+    ///
+    /// Accepts a comma-separated list, e.g.
+    /// `x86_64-unknown-linux-musl,x86_64-unknown-linux-gnu` to prefer static musl binaries and
+    /// fall back to glibc ones.  The first target for which any provider has a binary wins.
+    /// Defaults to the host's native target, followed by any it can run under emulation (e.g.
+    /// x86_64 on Windows on ARM64).
+    #[arg(long, value_name = "TRIPLES", value_delimiter = ',')]
+    pub prebuilt_targets: Option<Vec<String>>,
+
+    /// Only accept pre-built binaries from the crate's own repository: anywhere (the default),
+    /// repository-owner, or repository-release.
+    ///
+    /// With repository-owner, a binary has to be hosted by the owner of the repository the crate
+    /// declares, so community builds like quickinstall's are passed over.  With
+    /// repository-release, it also has to be attached to the release tagged with the crate's
+    /// version.
+    #[arg(long, value_name = "ORIGIN", value_parser = strum_value_parser!(PrebuiltOrigin))]
+    pub trust_prebuilt_only_from: Option<PrebuiltOrigin>,
+
+    /// Fail rather than run a binary that has no SBOM.
+    ///
+    /// Only binaries built from source come with an SBOM, so pre-built and already installed
+    /// binaries are passed over and the crate is built instead.  Can't be combined with
+    /// `--prebuilt-binary always`.
+    #[arg(long)]
+    pub require_sbom: bool,
+
     /// Output structured messages in the specified format.
     ///
     /// When set to "json", cgx will output machine-readable JSON messages to stdout describing
@@ -346,10 +612,112 @@ pub struct CliArgs {
     /// Each message is a single line of JSON.
     ///
     /// NOTE: The format of the JSON messages is considered unstable and may change in future
-    /// releases. This option is primarily intended for debugging and testing purposes.
+    /// releases. This option is primarily intended for debugging and testing purposes.  Breaking
+    /// changes bump the message schema version reported by `cgx --version --message-format json`.
     #[arg(long, value_name = "FMT")]
     pub message_format: Option<MessageFormat>,
 
+    /// Print an extended explanation of an error code (e.g. `CGX0012`) and exit.
+    ///
+    /// Every cgx failure is reported with a stable error code; this describes what the error
+    /// means and the most common ways to fix it.
+    #[arg(long, value_name = "CODE")]
+    pub explain: Option<String>,
+
+    /// Trust the `cgx.toml` config file in a directory, and exit.
+    ///
+    /// Config files found while walking up from the current directory are only honored in full
+    /// when their directory has been trusted; until then only settings that can't change which
+    /// code cgx runs are used.  This keeps a cloned repository from silently changing your tool
+    /// pins and aliases.  Trust applies to the directory itself, not its subdirectories.
+    #[arg(long, value_name = "DIR")]
+    pub trust: Option<PathBuf>,
+
+    /// Pin a tool to a version in a config file, and exit.
+    ///
+    /// Takes the tool's name and a version requirement, like `ripgrep@14.1`, and writes it to the
+    /// tool's entry under `[tools]` in the closest `cgx.toml` (creating one in the current
+    /// directory if there is none), the user config file with `--global`, or the file given with
+    /// `--config-file`.  The rest of the file, comments included, is left as it was.
+    #[arg(long, value_name = "TOOL@VERSION")]
+    pub pin: Option<String>,
+
+    /// Remove the version a tool is pinned to from a config file, and exit.
+    ///
+    /// Edits the same file as `--pin`.  A tool whose entry under `[tools]` has other settings
+    /// keeps them; one that was only pinned is removed.
+    #[arg(long, value_name = "TOOL")]
+    pub unpin: Option<String>,
+
+    /// Add an alias for a crate to a config file, and exit.
+    ///
+    /// Takes the alias and the name of the crate it stands for, like `rg=ripgrep`, and writes it
+    /// under `[aliases]` in the same file as `--pin` would, replacing any alias of that name
+    /// there.  The crate is looked for the same way running the alias would look for it, and the
+    /// alias isn't added if it can't be found.  Since an alias takes the place of any crate of the
+    /// same name, there's a warning if there's a crate named like the alias.
+    #[arg(long, value_name = "ALIAS=CRATE")]
+    pub alias: Option<String>,
+
+    /// Remove an alias from a config file, and exit.
+    ///
+    /// Edits the same file as `--pin`.
+    #[arg(long, value_name = "ALIAS")]
+    pub unalias: Option<String>,
+
+    /// With `--pin`, `--unpin`, `--alias` or `--unalias`, edit the user config file rather than
+    /// the closest `cgx.toml`.
+    #[arg(long, requires = "config_edit", conflicts_with = "config_file")]
+    pub global: bool,
+
+    /// List the aliases in effect in the current directory, and the crates they stand for, and
+    /// exit.
+    #[arg(long, conflicts_with = "config_edit")]
+    pub list_aliases: bool,
+
+    /// List the tools pinned to a version in the current directory, and their versions, and exit.
+    #[arg(long, conflicts_with_all = ["config_edit", "list_aliases"])]
+    pub list_pins: bool,
+
+    /// List the tools started with `--detach` that haven't been stopped, whether each is still
+    /// running, and where its logs are, and exit.
+    #[arg(long, conflicts_with_all = ["config_edit", "list_aliases", "list_pins"])]
+    pub ps: bool,
+
+    /// Stop the tool started with `--detach` under this name, and exit.
+    ///
+    /// The tool is sent a termination signal if it's still running (on Windows it's ended, along
+    /// with any processes it started), and its pidfile is removed.  Its logs are kept.
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["config_edit", "list_aliases", "list_pins", "ps"])]
+    pub stop: Option<String>,
+
+    /// Create a project environment in the current directory, and exit.
+    ///
+    /// A project environment is a `.cgx` directory that cgx uses in place of `--app-dir` whenever
+    /// it runs in that directory or below it, so that the project's tools, caches, and cgx config
+    /// are kept apart from everything else, much like a Python virtualenv.  The directory is
+    /// trusted as if with `--trust`, since an untrusted project environment is ignored.
+    #[arg(long, conflicts_with = "app_dir")]
+    pub init_env: bool,
+
+    /// Install a git hook script for each hook under `[hooks.git]` in the config, and exit.
+    ///
+    /// The scripts go in the hooks directory of the git repository the current directory is in,
+    /// and do nothing but run `cgx --git-hook <NAME>`, so the hooks always run the tools and
+    /// versions the config calls for without being installed again.  A hook that cgx didn't
+    /// install is never replaced.
+    #[arg(long)]
+    pub install_git_hooks: bool,
+
+    /// Run the commands under `[hooks.git]` for the git hook NAME, and exit.
+    ///
+    /// This is what the scripts installed by `--install-git-hooks` run, and can be run by hand to
+    /// check a hook without committing or pushing.  Each command is run through cgx in turn, as if
+    /// it followed `cgx` on the command line, and the first one that fails fails the hook.  Any
+    /// arguments after NAME, which git gives hooks like `commit-msg`, are passed on to each tool.
+    #[arg(long, value_name = "NAME", conflicts_with = "install_git_hooks")]
+    pub git_hook: Option<String>,
+
     /// List the crate's executable targets (bins and examples) without building or executing.
     ///
     /// Performs resolve and download operations, then inspects the crate's Cargo.toml
@@ -364,6 +732,176 @@ pub struct CliArgs {
     #[arg(long)]
     pub list_targets: bool,
 
+    /// List the binaries that cgx has built from source and cached, of the crate if one is given
+    /// or else of every crate, and exit.
+    ///
+    /// Each line gives the crate name, version, build key, and path of one cached binary.  The
+    /// build key identifies the exact source and build options the binary was built from, and
+    /// can be passed to `--pin-build` to run that binary again.  With `--verbose`, what was
+    /// recorded about each build is shown too: when it was built, how long the build took on this
+    /// machine, the size of the binary and the number of crates in its dependency graph.  Nothing
+    /// is resolved, downloaded or built.
+    #[arg(long, conflicts_with_all = ["list_targets", "pin_build"])]
+    pub list_installed: bool,
+
+    /// Install the tool into the install directory for good, rather than running it, and exit.
+    ///
+    /// The binary is got the same way as for running it, and then copied into `install_dir`,
+    /// which defaults to `~/.cargo/bin`.  What was installed, and how, is recorded in cgx's data
+    /// directory so that `--list-install-dir`, `--uninstall` and `--upgrade` can manage it.  A file in the install
+    /// directory that cgx didn't put there is never replaced.
+    #[arg(long, conflicts_with_all = ["no_exec", "ephemeral", "detach", "out_dir", "pin_build", "batch", "freeze", "list_installed", "list_targets"])]
+    pub install: bool,
+
+    /// List the tools installed into the install directory with `--install`, their versions and
+    /// where their binaries are, and exit.
+    ///
+    /// Unlike `--list-installed`, which lists the binaries in cgx's cache, this lists only what
+    /// was installed for good.
+    #[arg(long, conflicts_with_all = ["install", "list_installed", "list_targets"])]
+    pub list_install_dir: bool,
+
+    /// Remove a tool installed with `--install`, by the name of its crate, and exit.
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["install", "list_install_dir"])]
+    pub uninstall: Option<String>,
+
+    /// Install the newest version of the tools installed with `--install`, or only of the crate
+    /// given, and exit.
+    ///
+    /// Each tool is resolved again from what it was installed from and with the same build
+    /// options, bypassing any cached resolution, so a tool installed at a version requirement is
+    /// only upgraded within it.
+    #[arg(long, conflicts_with_all = ["install", "list_install_dir", "uninstall"])]
+    pub upgrade: bool,
+
+    /// Print every setting the config files in effect here make, with the file each came from,
+    /// and exit.
+    ///
+    /// Settings left at their defaults are listed as coming from `defaults`.  Tables are
+    /// flattened into dotted keys like `http.timeout`.
+    #[arg(long, conflicts_with_all = ["list_targets", "list_installed", "pin_build", "batch"])]
+    pub show_config: bool,
+
+    /// With `--show-config`, print only the settings that differ between here and another
+    /// directory, as a diff.
+    ///
+    /// This is handy for finding out why cgx behaves differently in CI, or in another checkout
+    /// of a project, than it does here.
+    #[arg(long, value_name = "DIR", requires = "show_config")]
+    pub compare: Option<PathBuf>,
+
+    /// Print the usage metrics collected on this machine, and exit.
+    ///
+    /// Metrics are only collected with `collect_metrics = true` in the config.  They're counts of
+    /// runs, cache hits and misses, and how each pre-built binary provider fared, with no crate
+    /// names or anything else identifying, and they never leave this machine unless exported.
+    #[arg(long, conflicts_with_all = ["list_targets", "list_installed", "show_config", "pin_build", "batch"])]
+    pub show_metrics: bool,
+
+    /// Write the usage metrics collected on this machine to a file as JSON, and exit.
+    ///
+    /// The file is meant to be collected by whatever an organization already uses to gather data
+    /// from its machines; cgx never sends it anywhere itself.
+    #[arg(long, value_name = "FILE",
+        conflicts_with_all = ["list_targets", "list_installed", "show_config", "show_metrics", "pin_build", "batch"])]
+    pub export_metrics: Option<PathBuf>,
+
+    /// Print the names of crates starting with PARTIAL, one per line, and exit.
+    ///
+    /// This is for shell completion scripts.  Names come from the config, the registry index
+    /// entries cargo has cached locally, and a crates.io search with a short timeout, so it never
+    /// keeps the prompt waiting for long.
+    #[arg(long, hide = true, value_name = "PARTIAL")]
+    pub complete_crate: Option<String>,
+
+    /// Get the binaries of many tools at once, reading one per line from a file (or stdin if `-`).
+    ///
+    /// Each line holds a crate spec with any flags that select the crate and how it is built, as
+    /// they would follow `cgx` on the command line (e.g. `--features foo ripgrep@14`).  Blank lines
+    /// and lines starting with `#` are ignored.  Other cgx settings, like `--prebuilt-binary`, apply
+    /// to every line and are taken from this command line.
+    ///
+    /// The tools are resolved and built but not executed, as with `--no-exec`.  A summary of
+    /// which tools succeeded is printed at the end, and cgx fails if any of them did not.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["list_targets", "list_installed", "pin_build"])]
+    pub batch: Option<PathBuf>,
+
+    /// Get the binary of every tool in the `[tools]` table, and print a manifest of exactly what
+    /// each one resolved to, then exit.
+    ///
+    /// The manifest gives each tool's exact version and source, down to the commit for a tool
+    /// from git, and the SHA256 of its binary.  It can be committed, and given to `--batch` on
+    /// another machine to get the same versions of the same tools from the same sources.
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "toml",
+        conflicts_with_all = ["list_targets", "list_installed", "show_config", "pin_build", "batch", "crate_spec"])]
+    pub freeze: Option<FreezeFormat>,
+
+    /// Get the binary of every tool in the `[tools]` table, and start a shell with them all on
+    /// `PATH`.
+    ///
+    /// The binaries are linked into a directory that's put at the front of `PATH` in the shell,
+    /// and `CGX_SHELL` is set to it, so each tool can be run by name in the version the config
+    /// calls for, without being installed.  The shell is `$SHELL` (`%COMSPEC%` on Windows), and
+    /// cgx exits with its exit code once it exits, removing the directory.
+    #[arg(long,
+        conflicts_with_all = ["list_targets", "list_installed", "show_config", "pin_build", "batch", "freeze", "crate_spec", "no_exec", "ephemeral", "plan"])]
+    pub shell: bool,
+
+    /// Get the binary of every tool in the `[tools]` table, or in the `--batch` file if one is
+    /// given, and print a variable for each holding the path to its binary, then exit.
+    ///
+    /// Each variable is named after its tool, in upper case with `-` as `_`, as in
+    /// `TAPLO_CLI=/path/to/taplo`.  With `shell-env`, they're printed as `export` statements, for
+    /// a build script to evaluate once with `eval "$(cgx --emit shell-env)"` rather than start cgx
+    /// for every tool it runs; with `json`, as a JSON object.
+    #[arg(long, value_name = "FORMAT",
+        conflicts_with_all = ["list_targets", "list_installed", "show_config", "pin_build", "freeze", "shell", "crate_spec", "no_exec", "ephemeral", "detach", "plan"])]
+    pub emit: Option<EmitFormat>,
+
+    /// Download the crate's source without building it, print the path to it, and exit.
+    ///
+    /// The crate is resolved and downloaded as usual, but cargo is never run, which is handy for
+    /// reading or auditing a crate's source.  The path printed is in cgx's source cache, so
+    /// anything done there should be read-only; with `--out-dir`, the source is copied into a
+    /// `<name>-<version>` directory there and that path is printed instead.
+    #[arg(long, conflicts_with_all = ["list_targets", "list_installed", "show_config", "pin_build", "batch"])]
+    pub fetch_source: bool,
+
+    /// Run a file from the crate's source, such as a helper script it ships, rather than its
+    /// binary.
+    ///
+    /// The path is relative to the root of the crate's source.  The crate is resolved and
+    /// downloaded as usual but not built, and the file is run from cgx's source cache with the
+    /// arguments that would have gone to the binary, in the current directory, with
+    /// `CRATE_SOURCE_DIR` set to the root of the source.  A file with an extension listed under
+    /// `[script_interpreters]` in the config, or a common one like `.sh` or `.py`, is run by that
+    /// interpreter; anything else is run directly.
+    #[arg(long, value_name = "PATH",
+        conflicts_with_all = ["list_targets", "list_installed", "show_config", "pin_build", "batch", "freeze", "fetch_source", "no_exec", "ephemeral", "out_dir"])]
+    pub run_source_file: Option<PathBuf>,
+
+    /// Run exactly the cached binary with this build key, bypassing crate resolution entirely.
+    ///
+    /// Build keys are listed by `--list-installed` and reported in the build cache messages of
+    /// `--message-format json`.  Nothing is resolved, downloaded or built, so this runs the same
+    /// binary even after newer versions are published or the config changes, which makes it
+    /// possible to compare builds when tracking down a regression.  If a crate is named as well,
+    /// the pinned build must be of that crate.
+    #[arg(long, value_name = "KEY", conflicts_with = "list_targets")]
+    pub pin_build: Option<String>,
+
+    /// Follow the acquisition plan in this file, or write one there
+    ///
+    /// A plan records what this invocation resolved the crate to and the path and SHA256 of the
+    /// binary it got.  When the file has a plan for the same crate and build options, and the
+    /// binary is still there with that digest, it's run without resolving anything; if the binary
+    /// is gone, the planned crate is got again without resolving it.  Otherwise the crate is
+    /// resolved as usual and a new plan written.  Meant for CI, where the same invocation runs
+    /// many times.
+    #[arg(long, value_name = "FILE",
+        conflicts_with_all = ["list_targets", "list_installed", "show_config", "pin_build", "batch", "freeze", "fetch_source", "run_source_file"])]
+    pub plan: Option<PathBuf>,
+
     /// The crate to run (optionally with @VERSION suffix).
     ///
     /// This is optional when using `--path`, `--git`, `--github`, or `--gitlab`, as the crate
@@ -373,7 +911,7 @@ pub struct CliArgs {
     /// element of `args` is treated as a cargo subcommand name, and "cargo-" is prepended
     /// to form the actual crate name (e.g., `cgx cargo deny` runs the crate `cargo-deny`).
     #[arg(value_name = "CRATE[@VERSION]",
-        required_unless_present_any = ["version", "path", "git", "github", "gitlab"])]
+        required_unless_present_any = ["version", "explain", "trust", "pin", "unpin", "alias", "unalias", "list_aliases", "list_pins", "ps", "stop", "init_env", "install_git_hooks", "git_hook", "list_installed", "show_config", "show_metrics", "export_metrics", "complete_crate", "pin_build", "batch", "freeze", "shell", "path", "git", "github", "gitlab"])]
     pub crate_spec: Option<String>,
 
     /// Arguments to pass to the executed tool.
@@ -403,6 +941,30 @@ impl CliArgs {
         cli
     }
 
+    /// Parse one line of a `--batch` file into a [`CliArgs`] struct.
+    ///
+    /// The line holds the same arguments as a cgx command line without the leading `cgx`, split
+    /// into words the way a POSIX shell would, so that an argument with spaces in it can be
+    /// quoted.  Unlike [`Self::parse_from_cli_args`], invalid arguments are returned as an error
+    /// rather than exiting the process.
+    pub fn parse_batch_line(line: &str) -> Result<Self> {
+        let words = crate::runner::split_words(line)
+            .map_err(|message| error::InvalidBatchArgsSnafu { message }.build())?;
+        let args = std::iter::once("cgx".to_string()).chain(words);
+        let (toolchain, filtered_args) = Self::extract_toolchain(args);
+        let (cgx_args, binary_args) = Self::split_at_crate_spec(filtered_args);
+
+        let mut cli = Self::try_parse_from(cgx_args).map_err(|e| {
+            error::InvalidBatchArgsSnafu {
+                message: e.to_string().trim_end().to_string(),
+            }
+            .build()
+        })?;
+        cli.args = binary_args;
+        cli.toolchain = toolchain;
+        Ok(cli)
+    }
+
     /// Parse the CLI args from an arbitary iterator of strings, useful for constructing
     /// [`CLiArgs`] values for testing.
     #[cfg(test)]
@@ -555,8 +1117,10 @@ impl CliArgs {
     /// and which are flag values. The first argument that is neither a flag nor a flag
     /// value is identified as the crate spec, and serves as the split point.
     ///
-    /// For compatibility, if an explicit `--` delimiter is present, it is used as the
-    /// split point instead.
+    /// A `--` delimiter ends cgx's arguments: if it comes before the crate spec it is the split
+    /// point instead, and if it directly follows the crate spec it is dropped.  Everything after
+    /// it, including any further `--`, is passed to the binary exactly as given.  Automation that
+    /// can't be sure how this heuristic treats its arguments can use `--args-file` instead.
     ///
     /// # Examples
     ///
@@ -586,21 +1150,14 @@ impl CliArgs {
     {
         let args: Vec<String> = args.into_iter().map(|s| s.into()).collect();
 
-        // Check for explicit -- separator (standard POSIX convention)
-        // If present, everything before it goes to cgx, everything after goes to the binary
-        if let Some(dash_dash_pos) = args.iter().position(|arg| arg == "--") {
-            let cgx_args = args[..dash_dash_pos].to_vec();
-            let binary_args = args[dash_dash_pos + 1..].to_vec();
-            return (cgx_args, binary_args);
-        }
-
         // Build flag lists dynamically from clap metadata, so that this function works reliably
         // as we add and modify CLI options.
 
         let cmd = CliArgs::command();
-        let mut no_value_flags = HashSet::new();
         let mut value_taking_flags = HashSet::new();
+        let mut optional_value_flags = HashSet::new();
         let mut short_value_taking_flags = HashSet::new();
+        let mut short_optional_value_flags = HashSet::new();
 
         for arg in cmd.get_arguments() {
             // Skip positional arguments
@@ -608,85 +1165,92 @@ impl CliArgs {
                 continue;
             }
 
-            // Determine if this flag takes a value based on its action
-            let takes_value = matches!(arg.get_action(), ArgAction::Set | ArgAction::Append);
-
-            // Handle long flags
+            // Determine if this flag takes a value based on its action, and whether that value can
+            // be omitted (like `--version`, which both prints cgx's version and selects a crate
+            // version)
+            // A flag whose value can only be given with `=` is always a single argument
+            let takes_value = matches!(arg.get_action(), ArgAction::Set | ArgAction::Append)
+                && !arg.is_require_equals_set();
+            let value_optional =
+                takes_value && arg.get_num_args().is_some_and(|range| range.min_values() == 0);
+
+            // Handle long flags.  Flags that take no value, and unknown flags, need no tracking as
+            // they are a single argument
             if let Some(long) = arg.get_long() {
                 let flag = format!("--{}", long);
-                if takes_value {
+                if value_optional {
+                    optional_value_flags.insert(flag);
+                } else if takes_value {
                     value_taking_flags.insert(flag);
-                } else {
-                    no_value_flags.insert(flag);
                 }
             }
 
             // Handle short flags
             if let Some(short) = arg.get_short() {
-                if takes_value {
+                if value_optional {
+                    short_optional_value_flags.insert(short);
+                } else if takes_value {
                     short_value_taking_flags.insert(short);
                 }
-                // Note: short no-value flags don't need tracking; we handle them via else clause
             }
         }
 
+        // Whether the argument after `position` is the value of an optional-value flag
+        let has_optional_value =
+            |position: usize| args.get(position + 1).is_some_and(|next| !next.starts_with('-'));
+
         let mut position = 1; // Start after binary name (args[0])
 
         while position < args.len() {
             let arg = &args[position];
 
-            if let Some(flag) = arg.strip_prefix("--") {
+            if arg == "--" {
+                // Explicit `--` before the crate spec (standard POSIX convention): everything before
+                // it goes to cgx, everything after goes to the binary
+                let cgx_args = args[..position].to_vec();
+                let binary_args = args[position + 1..].to_vec();
+                return (cgx_args, binary_args);
+            } else if let Some(flag) = arg.strip_prefix("--") {
                 // Long flag
                 if flag.contains('=') {
                     // --flag=value syntax, counts as one argument
                     position += 1;
-                } else if no_value_flags.contains(arg.as_str()) {
-                    position += 1;
+                } else if optional_value_flags.contains(arg.as_str()) {
+                    // If next arg exists and doesn't look like a flag, it's the flag's value
+                    position += if has_optional_value(position) { 2 } else { 1 };
                 } else if value_taking_flags.contains(arg.as_str()) {
                     // Skip flag and its value (next argument)
                     position += 2;
-                } else if arg == "--version" {
-                    // Special case: --version can have 0 or 1 values
-                    // If next arg exists and doesn't look like a flag, it's the version value
-                    if position + 1 < args.len() && !args[position + 1].starts_with('-') {
-                        position += 2;
-                    } else {
-                        position += 1;
-                    }
                 } else {
-                    // Unknown long flag, conservatively assume it takes no value
+                    // Flag without a value, or unknown long flag which we conservatively assume
+                    // takes no value
                     position += 1;
                 }
-            } else if let Some(flag) = arg.strip_prefix('-') {
+            } else if let Some(flags) = arg.strip_prefix('-') {
                 // Short flag(s)
-                if flag.is_empty() {
+                if flags.is_empty() {
                     // Just "-", often used to indicate stdin - treat as positional argument
                     break;
                 }
 
-                let first_char = flag.chars().next().unwrap();
-
-                if short_value_taking_flags.contains(&first_char) {
-                    if flag.len() == 1 {
-                        // -F foo (flag and value are separate arguments)
-                        position += 2;
-                    } else {
-                        // -Ffoo (flag and value combined in one argument)
-                        position += 1;
-                    }
-                } else if first_char == 'V' {
-                    // -V is short for --version, same special handling
-                    if flag.len() == 1 && position + 1 < args.len() && !args[position + 1].starts_with('-') {
-                        // -V 14 (separate arguments)
-                        position += 2;
-                    } else {
-                        // -V or -V14 (combined)
-                        position += 1;
+                // Short flags can be bundled (`-vq`), and the last one can take a value, either
+                // in the same argument (`-vFfoo`) or the next one (`-vF foo`)
+                let mut consumed = 1;
+                for (index, flag) in flags.char_indices() {
+                    let rest = &flags[index + flag.len_utf8()..];
+                    if short_value_taking_flags.contains(&flag) {
+                        if rest.is_empty() {
+                            consumed = 2;
+                        }
+                        break;
+                    } else if short_optional_value_flags.contains(&flag) {
+                        if rest.is_empty() && has_optional_value(position) {
+                            consumed = 2;
+                        }
+                        break;
                     }
-                } else {
-                    // Assume no value (like -v, -q, or bundled flags like -vvv or -qv)
-                    position += 1;
                 }
+                position += consumed;
             } else {
                 // Not a flag - this is the crate spec (first positional argument)!
                 break;
@@ -697,7 +1261,21 @@ impl CliArgs {
         if position < args.len() {
             // Found a crate spec: split after it
             let cgx_args = args[..=position].to_vec();
-            let binary_args = args[position + 1..].to_vec();
+            let mut binary_args = args[position + 1..].to_vec();
+
+            // A `--` right after the crate spec (or after the subcommand in `cgx cargo deny --
+            // ...`) only separates it from the binary's arguments, so it is dropped.  Any other
+            // `--` is one of the binary's arguments and passed on as is.
+            let separator = if args[position] == "cargo" && binary_args.first().is_some_and(|arg| arg != "--")
+            {
+                1
+            } else {
+                0
+            };
+            if binary_args.get(separator).is_some_and(|arg| arg == "--") {
+                binary_args.remove(separator);
+            }
+
             (cgx_args, binary_args)
         } else {
             // No crate spec found (e.g., `cgx --path ./foo` with no crate name specified)
@@ -775,7 +1353,7 @@ mod tests {
         #[test]
         fn test_crate_with_conflicting_versions() {
             let result = parse_cratespec_from_args(&["--version", "15", "ripgrep@14"]);
-            assert_matches!(result, Err(crate::error::Error::ConflictingVersions { .. }));
+            assert_matches!(result, Err(error::Error::ConflictingVersions { .. }));
         }
 
         #[test]
@@ -1153,25 +1731,25 @@ mod tests {
         #[test]
         fn test_git_selector_without_git_source() {
             let result = parse_cratespec_from_args(&["--branch", "main", "mycrate"]);
-            assert_matches!(result, Err(crate::error::Error::GitSelectorWithoutGitSource));
+            assert_matches!(result, Err(error::Error::GitSelectorWithoutGitSource));
         }
 
         #[test]
         fn test_invalid_repo_format() {
             let result = parse_cratespec_from_args(&["--github", "invalid-repo", "mycrate"]);
-            assert_matches!(result, Err(crate::error::Error::InvalidRepoFormat { .. }));
+            assert_matches!(result, Err(error::Error::InvalidRepoFormat { .. }));
         }
 
         #[test]
         fn test_invalid_version() {
             let result = parse_cratespec_from_args(&["ripgrep@not-a-version"]);
-            assert_matches!(result, Err(crate::error::Error::InvalidVersionReq { .. }));
+            assert_matches!(result, Err(error::Error::InvalidVersionReq { .. }));
         }
 
         #[test]
         fn test_invalid_index_url() {
             let result = parse_cratespec_from_args(&["--index", "not-a-valid-url", "mycrate"]);
-            assert_matches!(result, Err(crate::error::Error::InvalidUrl { .. }));
+            assert_matches!(result, Err(error::Error::InvalidUrl { .. }));
         }
 
         #[test]
@@ -1514,6 +2092,25 @@ mod tests {
             assert_eq!(binary_args, vec!["--version"]);
         }
 
+        #[test]
+        fn test_explain_does_not_require_crate() {
+            let cli = CliArgs::parse_from_test_args(["--explain", "CGX0012"]);
+
+            assert_eq!(cli.explain, Some("CGX0012".to_string()));
+            assert_eq!(cli.crate_spec, None);
+        }
+
+        #[test]
+        fn test_ps_and_stop_do_not_require_crate() {
+            let cli = CliArgs::parse_from_test_args(["--ps"]);
+            assert!(cli.ps);
+            assert_eq!(cli.crate_spec, None);
+
+            let cli = CliArgs::parse_from_test_args(["--stop", "bacon"]);
+            assert_eq!(cli.stop, Some("bacon".to_string()));
+            assert_eq!(cli.crate_spec, None);
+        }
+
         #[test]
         fn test_split_with_cgx_flags_before_crate() {
             let args = vec!["cgx", "--features", "foo", "ripgrep", "--color=always", "-i"];
@@ -1523,6 +2120,30 @@ mod tests {
             assert_eq!(binary_args, vec!["--color=always", "-i"]);
         }
 
+        #[test]
+        fn test_refresh_layers() {
+            let cli = CliArgs::parse_from_test_args(["--refresh", "ripgrep"]);
+            assert_eq!(cli.refresh, vec![RefreshLayer::All]);
+            assert_eq!(cli.crate_spec, Some("ripgrep".to_string()));
+
+            let cli = CliArgs::parse_from_test_args(["--refresh=source,binary", "ripgrep"]);
+            assert_eq!(cli.refresh, vec![RefreshLayer::Source, RefreshLayer::Binary]);
+
+            let cli = CliArgs::parse_from_test_args(["ripgrep"]);
+            assert_eq!(cli.refresh, Vec::<RefreshLayer>::new());
+
+            assert!(CliArgs::try_parse_from(["cgx", "--refresh=everything", "ripgrep"]).is_err());
+        }
+
+        #[test]
+        fn test_split_with_refresh_flag() {
+            let args = vec!["cgx", "--refresh", "ripgrep", "--version"];
+            let (cgx_args, binary_args) = CliArgs::split_at_crate_spec(args);
+
+            assert_eq!(cgx_args, vec!["cgx", "--refresh", "ripgrep"]);
+            assert_eq!(binary_args, vec!["--version"]);
+        }
+
         #[test]
         fn test_split_with_path_flag() {
             let args = vec!["cgx", "--path", "./foo", "mycrate", "--help"];
@@ -1640,6 +2261,60 @@ mod tests {
             assert_eq!(binary_args, vec!["deny", "--help"]);
         }
 
+        #[test]
+        fn test_dash_dash_after_binary_args_is_passed_on() {
+            let args = vec!["cgx", "ripgrep", "foo", "--", "--bar"];
+            let (cgx_args, binary_args) = CliArgs::split_at_crate_spec(args);
+
+            assert_eq!(cgx_args, vec!["cgx", "ripgrep"]);
+            assert_eq!(binary_args, vec!["foo", "--", "--bar"]);
+        }
+
+        #[test]
+        fn test_only_first_dash_dash_after_crate_spec_is_dropped() {
+            let args = vec!["cgx", "ripgrep", "--", "-e", "--", "pattern"];
+            let (cgx_args, binary_args) = CliArgs::split_at_crate_spec(args);
+
+            assert_eq!(cgx_args, vec!["cgx", "ripgrep"]);
+            assert_eq!(binary_args, vec!["-e", "--", "pattern"]);
+        }
+
+        #[test]
+        fn test_cargo_subcommand_with_dash_dash() {
+            let args = vec!["cgx", "cargo", "deny", "--", "--help"];
+            let (cgx_args, binary_args) = CliArgs::split_at_crate_spec(args);
+
+            assert_eq!(cgx_args, vec!["cgx", "cargo"]);
+            assert_eq!(binary_args, vec!["deny", "--help"]);
+        }
+
+        #[test]
+        fn test_bundled_short_flags_with_value() {
+            let args = vec!["cgx", "-qF", "foo", "ripgrep", "-i"];
+            let (cgx_args, binary_args) = CliArgs::split_at_crate_spec(args);
+
+            assert_eq!(cgx_args, vec!["cgx", "-qF", "foo", "ripgrep"]);
+            assert_eq!(binary_args, vec!["-i"]);
+        }
+
+        #[test]
+        fn test_bundled_short_flags_with_inline_value() {
+            let args = vec!["cgx", "-qFfoo", "ripgrep", "-i"];
+            let (cgx_args, binary_args) = CliArgs::split_at_crate_spec(args);
+
+            assert_eq!(cgx_args, vec!["cgx", "-qFfoo", "ripgrep"]);
+            assert_eq!(binary_args, vec!["-i"]);
+        }
+
+        #[test]
+        fn test_short_version_flag_with_value() {
+            let args = vec!["cgx", "-V", "14", "ripgrep", "-i"];
+            let (cgx_args, binary_args) = CliArgs::split_at_crate_spec(args);
+
+            assert_eq!(cgx_args, vec!["cgx", "-V", "14", "ripgrep"]);
+            assert_eq!(binary_args, vec!["-i"]);
+        }
+
         #[test]
         fn test_multiple_binary_flags() {
             let args = vec![
@@ -1689,6 +2364,13 @@ mod tests {
             assert_eq!(cli.http_timeout, None);
             assert_eq!(cli.http_retries, None);
             assert_eq!(cli.http_proxy, None);
+            assert_eq!(cli.max_download_size, None);
+        }
+
+        #[test]
+        fn test_max_download_size_cli_arg() {
+            let cli = CliArgs::parse_from_test_args(["--max-download-size", "200M", "test-crate"]);
+            assert_eq!(cli.max_download_size, Some("200M".to_string()));
         }
 
         #[test]
@@ -0,0 +1,130 @@
+//! Completion of crate names, for shell completion scripts.
+//!
+//! `cgx --complete-crate <PARTIAL>` prints the names of crates that start with `PARTIAL`, one per
+//! line, for a completion script to offer.  It runs every time the user presses tab, so it has to
+//! be quick and can't fail: names come from the tools and aliases in the config, the index
+//! entries cargo has cached on disk, and a crates.io search that's given up on after
+//! [`SEARCH_TIMEOUT`].  A source that fails is left out rather than reported.
+
+use crate::{
+    Result,
+    config::{Config, HttpConfig},
+    error,
+    http::HttpClient,
+    registry,
+};
+use serde::Deserialize;
+use snafu::ResultExt;
+use std::{collections::BTreeSet, time::Duration};
+use tracing::*;
+
+/// How long the crates.io search is waited for, which bounds how long completion takes.
+pub const SEARCH_TIMEOUT: Duration = Duration::from_millis(800);
+
+/// Partial names shorter than this aren't searched for, since nearly every crate would match.
+const MIN_SEARCH_LEN: usize = 2;
+
+/// The most names that are returned.
+const MAX_NAMES: usize = 100;
+
+/// The parts of a crates.io search response that are used.
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    crates: Vec<SearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResult {
+    name: String,
+}
+
+/// The names of crates that start with `partial`, sorted and without duplicates.
+///
+/// Names are matched case-insensitively.  Nothing is searched for when offline.
+pub fn crate_names(config: &Config, partial: &str) -> Vec<String> {
+    let prefix = partial.to_lowercase();
+    let matches = |name: &String| name.to_lowercase().starts_with(&prefix);
+
+    let mut names: BTreeSet<String> = config
+        .tools
+        .keys()
+        .chain(config.aliases.keys())
+        .filter(|name| matches(name))
+        .cloned()
+        .collect();
+    names.extend(registry::cached_crate_names(&config.endpoints, &prefix));
+
+    if !config.offline && prefix.len() >= MIN_SEARCH_LEN {
+        match search(config, &prefix) {
+            Ok(found) => names.extend(found.into_iter().filter(matches)),
+            Err(e) => debug!("Searching crates.io for completions failed: {}", e),
+        }
+    }
+
+    names.into_iter().take(MAX_NAMES).collect()
+}
+
+/// Search crates.io for crates matching `query`.
+fn search(config: &Config, query: &str) -> Result<Vec<String>> {
+    let http = HttpConfig {
+        timeout: SEARCH_TIMEOUT,
+        retries: 0,
+        ..config.http.clone()
+    };
+    let client = HttpClient::new(&http)?;
+
+    let mut url = config
+        .endpoints
+        .crates_io_api
+        .join("api/v1/crates")
+        .expect("BUG: relative URL is valid");
+    url.query_pairs_mut()
+        .append_pair("q", query)
+        .append_pair("per_page", "50");
+
+    let Some(body) = client.try_download(url.as_str())? else {
+        return Ok(Vec::new());
+    };
+    let response: SearchResponse = serde_json::from_slice(&body).context(error::JsonSnafu)?;
+
+    Ok(response.crates.into_iter().map(|krate| krate.name).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ToolConfig;
+    use httpmock::prelude::*;
+    use url::Url;
+
+    #[test]
+    fn completes_from_config_and_search() {
+        let server = MockServer::start();
+        let search = server.mock(|when, then| {
+            when.method(GET).path("/api/v1/crates").query_param("q", "rip");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"crates": [{"name": "ripgrep"}, {"name": "ripunzip"}, {"name": "grip"}]}"#);
+        });
+
+        // An empty local registry, so that nothing cargo has cached on this machine turns up
+        let registry = tempfile::tempdir().unwrap();
+        std::fs::create_dir(registry.path().join("index")).unwrap();
+
+        let mut config = Config::default();
+        config.endpoints.crates_io_index = Some(Url::from_directory_path(registry.path()).unwrap());
+        config.endpoints.crates_io_api = Url::parse(&server.base_url()).unwrap();
+        config
+            .tools
+            .insert("ripgrep".to_string(), ToolConfig::Version("14".to_string()));
+        config.aliases.insert("rip".to_string(), "ripgrep".to_string());
+        config.aliases.insert("rg".to_string(), "ripgrep".to_string());
+
+        assert_eq!(crate_names(&config, "Rip"), vec!["rip", "ripgrep", "ripunzip"]);
+        search.assert();
+
+        config.offline = true;
+        assert_eq!(crate_names(&config, "rip"), vec!["rip", "ripgrep"]);
+        search.assert_calls(1);
+    }
+}
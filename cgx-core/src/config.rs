@@ -1,19 +1,33 @@
-use crate::{Result, cli::CliArgs};
+use crate::{
+    Result, cli::CliArgs, cratespec::CrateSpec, hooks::HooksConfig, project_env, timeouts::Phase,
+    trust::TrustStore,
+};
+use chrono::NaiveDate;
 use etcetera::{AppStrategy, AppStrategyArgs, choose_app_strategy};
+use figment::{
+    Figment, Metadata, Profile, Provider, Source,
+    providers::{Format, Serialized, Toml},
+    value::{Dict, Map},
+};
 use serde::{Deserialize, Serialize};
-use snafu::ResultExt;
+use snafu::{IntoError, OptionExt, ResultExt};
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
     time::Duration,
 };
 use strum::{Display, EnumIter, EnumString, IntoStaticStr, VariantNames};
+use url::Url;
 
 const DEFAULT_RESOLVE_CACHE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_VENDOR_CACHE_TIMEOUT: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+const DEFAULT_NEGATIVE_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
 const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(30);
 const DEFAULT_HTTP_RETRIES: usize = 2;
 const DEFAULT_HTTP_BACKOFF_BASE: Duration = Duration::from_millis(500);
 const DEFAULT_HTTP_BACKOFF_MAX: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_DOWNLOAD_SIZE: u64 = 1 << 30;
 
 /// The user's preference for using pre-built binaries.
 #[derive(
@@ -33,6 +47,136 @@ pub enum UsePrebuiltBinaries {
     Never,
 }
 
+/// Which C library to prefer pre-built binaries for on Linux.
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Deserialize,
+    Serialize,
+    EnumString,
+    Display,
+    VariantNames,
+)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum LibcPreference {
+    /// Prefer binaries for the C library cgx itself was built for, falling back to the other.
+    #[default]
+    Auto,
+    /// Prefer binaries linked against glibc, falling back to static musl binaries.
+    Gnu,
+    /// Prefer static musl binaries, falling back to glibc binaries if the host has glibc.
+    Musl,
+}
+
+/// What to run for a crate that has several binaries and doesn't say which is the default with
+/// `default-run`, when `--bin` isn't given.
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Deserialize,
+    Serialize,
+    EnumString,
+    Display,
+    VariantNames,
+)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum DefaultBinPolicy {
+    /// Fail, listing the binaries to choose from with `--bin`.
+    #[default]
+    Error,
+    /// Run the binary named after the crate, failing as with `error` if there isn't one.
+    MatchCrateName,
+    /// Run the first binary listed in the crate's manifest.
+    First,
+}
+
+/// A layer of cgx's caches that `--refresh` can bypass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display, VariantNames)]
+#[strum(serialize_all = "kebab-case")]
+pub enum RefreshLayer {
+    /// Resolution of crate specs to exact versions.
+    Resolve,
+    /// Downloaded and checked out crate sources.
+    Source,
+    /// Pre-built binaries (including the record that a crate has none) and binaries built from
+    /// source.
+    Binary,
+    /// All of the above.
+    All,
+}
+
+/// Which cache layers are bypassed, so that what they hold is fetched or built afresh.
+///
+/// A bypassed layer is neither read nor used as a fallback on network errors, and its entries for
+/// the crate are replaced with fresh ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Refresh {
+    pub resolve: bool,
+    pub source: bool,
+    pub binary: bool,
+}
+
+impl Refresh {
+    /// Bypass every layer.
+    pub const ALL: Self = Self {
+        resolve: true,
+        source: true,
+        binary: true,
+    };
+
+    /// Bypass the given layers.
+    pub fn from_layers(layers: &[RefreshLayer]) -> Self {
+        layers.iter().fold(Self::default(), |refresh, layer| match layer {
+            RefreshLayer::Resolve => Self {
+                resolve: true,
+                ..refresh
+            },
+            RefreshLayer::Source => Self {
+                source: true,
+                ..refresh
+            },
+            RefreshLayer::Binary => Self {
+                binary: true,
+                ..refresh
+            },
+            RefreshLayer::All => Self::ALL,
+        })
+    }
+}
+
+/// How to treat `cgx.toml` files found in the directory hierarchy that have not been approved with
+/// `cgx --trust <DIR>`.
+///
+/// This can only be set in the system or user config file (or an explicit `--config-file`); a
+/// directory config file can't loosen the policy that applies to itself.
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, EnumString, Display, VariantNames,
+)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum UntrustedConfigPolicy {
+    /// Honor only settings that cannot change which code cgx runs or where it comes from, such
+    /// as `log_level`, and ignore the rest.
+    #[default]
+    Restricted,
+    /// Ignore untrusted config files entirely.
+    Ignore,
+    /// Honor untrusted config files in full, as if every directory were trusted.
+    Allow,
+}
+
 /// Represents the sources to check for pre-built binaries before building from source.
 #[derive(
     Debug,
@@ -63,6 +207,170 @@ pub enum BinaryProvider {
     Quickinstall,
 }
 
+/// Whose pre-built binaries to accept, as [`PrebuiltBinariesConfig::trust_prebuilt_only_from`].
+///
+/// Providers like quickinstall, and `[package.metadata.binstall]` URLs pointing at mirrors, serve
+/// binaries that weren't built by the crate's author.  The stricter settings only accept binaries
+/// from the repository the crate declares (or the forge repository it came from), which passes
+/// over those.
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Deserialize,
+    Serialize,
+    EnumString,
+    Display,
+    VariantNames,
+)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum PrebuiltOrigin {
+    /// Accept binaries from any configured provider.
+    #[default]
+    Anywhere,
+    /// Only accept binaries hosted by the owner of the crate's repository, on the same host.
+    RepositoryOwner,
+    /// Only accept binaries attached to the release of the crate's repository tagged with the
+    /// crate's version.
+    RepositoryRelease,
+}
+
+/// A source to look a bare crate name up in, as listed in [`Config::source_fallback`].
+///
+/// In config files these are written as strings: `default-registry`, `crates-io`,
+/// `github:<owner>`, or `gitlab:<owner>`.  The forge entries look for a repository named after
+/// the crate, owned by the given user or organization.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SourceFallback {
+    /// The registry named by [`Config::default_registry`]; skipped if none is configured.
+    DefaultRegistry,
+    /// crates.io
+    CratesIo,
+    /// The repository `<owner>/<crate name>` on github.com
+    Github { owner: String },
+    /// The repository `<owner>/<crate name>` on gitlab.com
+    Gitlab { owner: String },
+}
+
+impl std::str::FromStr for SourceFallback {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let forge_owner = |owner: &str| {
+            if owner.is_empty() || owner.contains('/') {
+                Err(format!("invalid owner in source fallback '{s}'"))
+            } else {
+                Ok(owner.to_string())
+            }
+        };
+
+        match s.split_once(':') {
+            None if s == "default-registry" => Ok(Self::DefaultRegistry),
+            None if s == "crates-io" => Ok(Self::CratesIo),
+            Some(("github", owner)) => Ok(Self::Github {
+                owner: forge_owner(owner)?,
+            }),
+            Some(("gitlab", owner)) => Ok(Self::Gitlab {
+                owner: forge_owner(owner)?,
+            }),
+            _ => Err(format!(
+                "unknown source fallback '{s}'; expected 'default-registry', 'crates-io', \
+                 'github:<owner>', or 'gitlab:<owner>'"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for SourceFallback {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl std::fmt::Display for SourceFallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DefaultRegistry => write!(f, "default-registry"),
+            Self::CratesIo => write!(f, "crates-io"),
+            Self::Github { owner } => write!(f, "github:{owner}"),
+            Self::Gitlab { owner } => write!(f, "gitlab:{owner}"),
+        }
+    }
+}
+
+impl From<SourceFallback> for String {
+    fn from(source: SourceFallback) -> Self {
+        source.to_string()
+    }
+}
+
+/// Where cargo puts what it builds for a crate in a local directory, as set by
+/// [`Config::local_target_dir`].
+///
+/// In config files and on the command line this is written as `workspace`, `isolated`, or a path.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum LocalTargetDir {
+    /// Wherever cargo itself would put it when run in the crate's directory: the `target`
+    /// directory of its workspace, unless `CARGO_TARGET_DIR` or the project's cargo config say
+    /// otherwise.  Artifacts are shared with the project's own builds of the same profile.
+    #[default]
+    Workspace,
+    /// A directory of cgx's own in [`Config::build_dir`], one for each crate directory, so that
+    /// building with cgx never touches, or waits on, the project's own builds.
+    Isolated,
+    /// The given directory, which if relative is taken to be relative to the crate's directory.
+    Path(PathBuf),
+}
+
+impl std::str::FromStr for LocalTargetDir {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "" => Err(
+                "the local target dir can't be empty; expected 'workspace', 'isolated', or a path"
+                    .to_string(),
+            ),
+            "workspace" => Ok(Self::Workspace),
+            "isolated" => Ok(Self::Isolated),
+            path => Ok(Self::Path(PathBuf::from(shellexpand::tilde(path).as_ref()))),
+        }
+    }
+}
+
+impl TryFrom<String> for LocalTargetDir {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl std::fmt::Display for LocalTargetDir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Workspace => write!(f, "workspace"),
+            Self::Isolated => write!(f, "isolated"),
+            Self::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl From<LocalTargetDir> for String {
+    fn from(target_dir: LocalTargetDir) -> Self {
+        target_dir.to_string()
+    }
+}
+
 /// Configuration for how (and whether) to look for pre-built binaries when running a crate.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(default, deny_unknown_fields)]
@@ -90,6 +398,65 @@ pub struct PrebuiltBinariesConfig {
     /// minisign tooling  available to perform verification.  However it adds stronger security
     /// against malicious binaries.
     pub verify_signatures: bool,
+
+    /// Target triples whose pre-built binaries are acceptable, in order of preference.
+    ///
+    /// Each target is tried against every provider before moving on to the next one, so e.g.
+    /// `["x86_64-unknown-linux-musl", "x86_64-unknown-linux-gnu"]` prefers a static musl binary
+    /// from any provider and only falls back to glibc binaries if none is found.  This mirrors
+    /// the semantics of `cargo binstall --targets`.
+    ///
+    /// If empty, the targets the host can run are used: its native target first, then any it can
+    /// run under emulation, such as x86_64 binaries on Windows on ARM64.
+    pub targets: Vec<String>,
+
+    /// Cargo profiles whose builds a pre-built binary can stand in for.
+    ///
+    /// Pre-built binaries are release builds, so asking for `--profile release` explicitly
+    /// shouldn't force a build from source.  Any other `--profile` still does.
+    pub compatible_profiles: Vec<String>,
+
+    /// If enabled, remove the quarantine attribute macOS may put on downloaded binaries, which
+    /// makes Gatekeeper refuse to run them.  Has no effect on other platforms.
+    pub remove_quarantine: bool,
+
+    /// How long to remember that a provider has no binary for a crate version and target, before
+    /// asking it again, and the assets a release was listed with.
+    ///
+    /// Binaries are sometimes uploaded to a release some time after it's published, so this
+    /// can't be forever.  `--refresh=binary` asks every provider again regardless.
+    #[serde(with = "humantime_serde")]
+    pub negative_cache_ttl: Duration,
+
+    /// Which C library to prefer binaries for on Linux, when [`Self::targets`] isn't set.
+    ///
+    /// Targets for the other one are looked for as a fallback.  Whichever is preferred, a glibc
+    /// binary that needs a newer glibc than the host has is passed over.
+    pub libc: LibcPreference,
+
+    /// Whose binaries to accept.  Binaries from anywhere else are passed over, as if the provider
+    /// didn't have one.
+    pub trust_prebuilt_only_from: PrebuiltOrigin,
+}
+
+impl PrebuiltBinariesConfig {
+    /// The target triples to look for pre-built binaries for, in order of preference.
+    ///
+    /// This is [`Self::targets`] if specified, otherwise the targets the host is detected to be
+    /// able to run, native ones first.  That isn't necessarily the target cgx was built for, since
+    /// cgx may itself be running under emulation.  On Linux, those are for both C libraries, in
+    /// the order of [`Self::libc`].
+    pub fn target_preference(&self) -> Vec<String> {
+        if self.targets.is_empty() {
+            crate::platform::with_libc_preference(
+                &crate::platform::host_targets(),
+                self.libc,
+                crate::platform::has_glibc(),
+            )
+        } else {
+            self.targets.clone()
+        }
+    }
 }
 
 impl Default for PrebuiltBinariesConfig {
@@ -104,6 +471,12 @@ impl Default for PrebuiltBinariesConfig {
             ],
             verify_checksums: true,
             verify_signatures: true,
+            targets: Vec::new(),
+            compatible_profiles: vec!["release".to_string()],
+            remove_quarantine: true,
+            negative_cache_ttl: DEFAULT_NEGATIVE_CACHE_TTL,
+            libc: LibcPreference::default(),
+            trust_prebuilt_only_from: PrebuiltOrigin::default(),
         }
     }
 }
@@ -139,6 +512,38 @@ pub struct HttpConfig {
     /// HTTP or SOCKS5 proxy URL for all HTTP requests.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub proxy: Option<String>,
+
+    /// Hosts reached directly rather than through the proxy, as a `NO_PROXY` list of host names,
+    /// domains, IP addresses and CIDR blocks (see [`crate::proxy_rules`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+
+    /// The proxy URL requests to hosts matching each host pattern go through in place of
+    /// [`Self::proxy`], or `"direct"` for none.  The most specific matching pattern wins, and takes
+    /// precedence over [`Self::no_proxy`].
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub proxies: BTreeMap<String, String>,
+
+    /// netrc file consulted for proxy credentials when the proxy URL doesn't include any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub netrc: Option<PathBuf>,
+
+    /// Whether to look up proxy credentials in the OS keychain when neither the proxy URL nor the
+    /// netrc file provide any.
+    pub proxy_keychain: bool,
+
+    /// PEM file of the root certificates to trust in place of the OS's own, for registries,
+    /// mirrors and proxies that present certificates from a private CA, as with cargo's
+    /// `http.cainfo`.  It's used for git-over-HTTP and `cargo fetch` as well.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// The most bytes a single download, of a pre-built binary or a crate's source, may be.
+    ///
+    /// Downloads whose `Content-Length` says they're larger are refused before anything is read,
+    /// and those that turn out to be larger as they're read are abandoned once they go past it,
+    /// so that a wrongly matched asset or a hostile server can't fill the disk or memory.
+    pub max_download_size: u64,
 }
 
 impl Default for HttpConfig {
@@ -149,6 +554,12 @@ impl Default for HttpConfig {
             backoff_base: DEFAULT_HTTP_BACKOFF_BASE,
             backoff_max: DEFAULT_HTTP_BACKOFF_MAX,
             proxy: None,
+            no_proxy: None,
+            proxies: BTreeMap::new(),
+            netrc: None,
+            proxy_keychain: false,
+            ca_bundle: None,
+            max_download_size: DEFAULT_MAX_DOWNLOAD_SIZE,
         }
     }
 }
@@ -174,6 +585,238 @@ pub struct HttpConfigFile {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub proxy: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxies: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub netrc: Option<PathBuf>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_keychain: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// A byte count with an optional K, M, G or T suffix, as for `--max-download-size`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_download_size: Option<String>,
+}
+
+/// How to authenticate to git hosts when fetching crates from private repositories, from the
+/// `[git]` table of a config file.
+///
+/// Repositories reached over SSH use whatever `ssh` itself would, including the SSH agent, and
+/// those reached over HTTPS use git's credential helpers, so this is only needed for a host that
+/// should be given a token instead (see [`crate::git`]).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitConfig {
+    /// The token to authenticate with over HTTPS, keyed by host name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub hosts: BTreeMap<String, GitHostAuth>,
+}
+
+/// The token to authenticate to a git host with.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GitHostAuth {
+    /// The environment variable the token is read from, so that the token itself never has to be
+    /// written into a config file.
+    pub token_env: String,
+
+    /// The username sent along with the token.  Most hosts ignore it, but some, such as Bitbucket,
+    /// expect a particular one.
+    #[serde(default = "GitHostAuth::default_username")]
+    pub username: String,
+}
+
+impl GitHostAuth {
+    /// The username GitHub expects with an access token, which GitLab and Gitea accept too.
+    fn default_username() -> String {
+        "x-access-token".to_string()
+    }
+}
+
+/// The priority cargo is run at when building a crate from source, from the `[build_priority]`
+/// table of a config file.
+///
+/// Only ever lowers the priority, so that building tools, say while prewarming them with
+/// `--batch`, doesn't slow down other work on the machine.  Whatever cargo runs, like rustc and
+/// build scripts, runs at the same priority.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildPriority {
+    /// The niceness to run cargo with, from 0 (the usual priority) to 19 (the lowest).
+    ///
+    /// On Windows, cargo is run in the below normal priority class, or from 15 up the idle one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice: Option<u8>,
+
+    /// The IO priority to run cargo with.  Only Linux has IO priorities, so this is ignored
+    /// elsewhere.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io: Option<IoPriority>,
+}
+
+impl BuildPriority {
+    /// The lowest niceness there is.
+    pub const MAX_NICE: u8 = 19;
+
+    /// Whether this leaves cargo at the usual priority.
+    pub fn is_normal(&self) -> bool {
+        self.nice.unwrap_or(0) == 0 && self.io.unwrap_or_default() == IoPriority::Normal
+    }
+
+    /// This priority with the lowest of each kind wherever it doesn't set one, for a build in the
+    /// background.
+    fn background(self) -> Self {
+        Self {
+            nice: self.nice.or(Some(Self::MAX_NICE)),
+            io: self.io.or(Some(IoPriority::Idle)),
+        }
+    }
+}
+
+/// How long each phase of getting a binary may take, and a deadline for a run as a whole, from the
+/// `[timeouts]` table of a config file (see [`crate::timeouts`]).
+///
+/// These are on top of the timeout of each HTTP request, and don't limit the tool once it's
+/// running; `--max-runtime` does that.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhaseTimeouts {
+    /// How long resolving a crate spec to an exact version may take.
+    #[serde(default, with = "humantime_serde::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolve: Option<Duration>,
+
+    /// How long downloading a crate's source and any pre-built binary may take, all told.
+    #[serde(default, with = "humantime_serde::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download: Option<Duration>,
+
+    /// How long building a crate from source may take.
+    #[serde(default, with = "humantime_serde::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<Duration>,
+
+    /// How long a run may spend getting binaries, counting every phase and, with `--batch`, every
+    /// crate.  `--deadline` overrides this.
+    #[serde(default, with = "humantime_serde::option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<Duration>,
+}
+
+impl PhaseTimeouts {
+    /// The timeout of `phase`, if it has one.
+    pub fn for_phase(&self, phase: Phase) -> Option<Duration> {
+        match phase {
+            Phase::Resolve => self.resolve,
+            Phase::Download => self.download,
+            Phase::Build => self.build,
+        }
+    }
+}
+
+/// The IO priority to run cargo with (see [`BuildPriority`]).
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Deserialize,
+    Serialize,
+    EnumString,
+    Display,
+    VariantNames,
+)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum IoPriority {
+    /// The usual IO priority.
+    #[default]
+    Normal,
+    /// The lowest priority among processes that share the disk (best-effort class, level 7).
+    Low,
+    /// Only use the disk when nothing else is (idle class).
+    Idle,
+}
+
+/// Base URLs of the remote services cgx talks to.
+///
+/// The defaults are the public services.  Overriding them is useful for mirrors, and is how the
+/// `test-support` mock servers are wired in so integration tests never touch the real network.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EndpointsConfig {
+    /// Sparse index URL (including the `sparse+` prefix) used in place of crates.io.
+    ///
+    /// If not set, the crates.io index is located the same way cargo does, honoring any source
+    /// replacement in `.cargo/config.toml`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crates_io_index: Option<Url>,
+
+    /// Base URL of the crates.io web API, which crate names are searched for in when completing
+    /// them, and which tells when versions were published for `--as-of`.
+    pub crates_io_api: Url,
+
+    /// Base URL of the GitHub REST API used for repositories hosted on `github.com`.
+    pub github_api: Url,
+
+    /// Base URL that repositories hosted on `gitlab.com` are served from.
+    pub gitlab: Url,
+
+    /// Quickinstall-style indexes that binaries are looked for in, in order, by the quickinstall
+    /// provider.
+    ///
+    /// Given as a single base URL, or a list of base URLs or tables with a `url` and a `stats`
+    /// endpoint, so that an index run within an organization can be tried ahead of the public one.
+    #[serde(deserialize_with = "deserialize_quickinstall_indexes")]
+    pub quickinstall: Vec<QuickinstallIndex>,
+}
+
+/// A quickinstall-style index of pre-built binaries: a base URL under which the archive of a
+/// crate's binaries for a target is at `<name>-<version>/<name>-<version>-<target>.tar.gz`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuickinstallIndex {
+    /// Base URL the archives are downloaded from.
+    pub url: Url,
+
+    /// Endpoint told of each lookup in the index and whether it found a binary, the way
+    /// cargo-binstall tells quickinstall's, so that whoever runs the index can see what's asked
+    /// for.  Nothing is sent when this isn't set, which it isn't for the public index.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Url>,
+}
+
+impl QuickinstallIndex {
+    /// The index at `url`, without a stats endpoint.
+    pub fn new(url: Url) -> Self {
+        Self { url, stats: None }
+    }
+}
+
+impl Default for EndpointsConfig {
+    fn default() -> Self {
+        Self {
+            crates_io_index: None,
+            crates_io_api: Url::parse("https://crates.io").expect("BUG: hard-coded URL is valid"),
+            github_api: Url::parse("https://api.github.com").expect("BUG: hard-coded URL is valid"),
+            gitlab: Url::parse("https://gitlab.com").expect("BUG: hard-coded URL is valid"),
+            quickinstall: vec![QuickinstallIndex::new(
+                Url::parse("https://github.com/cargo-bins/cargo-quickinstall/releases/download")
+                    .expect("BUG: hard-coded URL is valid"),
+            )],
+        }
+    }
 }
 
 /// Configuration for a specific tool, matching Cargo.toml dependency format.
@@ -182,6 +825,7 @@ pub struct HttpConfigFile {
 /// with version, features, registry, git repo, etc.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, untagged)]
+#[allow(clippy::large_enum_variant)] // a handful per config, so boxing the details saves nothing
 pub enum ToolConfig {
     /// Simple version specification (e.g., "1.0", "*")
     Version(String),
@@ -203,16 +847,126 @@ pub enum ToolConfig {
         rev: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         path: Option<PathBuf>,
-    },
-}
-
-/// Intermediate structure for deserializing config files from TOML.
-///
-/// This matches the structure of cgx.toml files and is used during the deserialization
+        /// Name to run the tool's binary as, for multi-call binaries (corresponds to `--argv0`).
+        #[serde(skip_serializing_if = "Option::is_none")]
+        argv0: Option<String>,
+        /// Expected SHA256 digests of the release asset the tool's prebuilt binary is downloaded in,
+        /// keyed by target triple.
+        ///
+        /// When set, a prebuilt binary is only used if the digest of its asset matches the one
+        /// pinned for its target, and never for a target that has no digest pinned.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        prebuilt_sha256: Option<HashMap<String, String>>,
+        /// Files from the tool's source to stage next to its binary, for tools that read data
+        /// files like templates from their workspace at runtime.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        assets: Option<ToolAssets>,
+        /// Command to run the tool under, such as a profiler or debugger (corresponds to
+        /// `--wrapper`).
+        #[serde(skip_serializing_if = "Option::is_none")]
+        wrapper: Option<String>,
+        /// Release channels of the tool besides its stable releases, keyed by the name they are
+        /// selected with, as in `tool@nightly`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channels: Option<HashMap<String, ToolChannel>>,
+        /// Other executables in the tool's prebuilt release archives to install alongside its
+        /// binary, for tools that run helpers shipped with them.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        helpers: Option<Vec<String>>,
+        /// Features of the tool that its subcommands or flags need, keyed by the subcommand or
+        /// flag, so that running it with one of them from a build without those features gets a
+        /// hint.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        feature_hints: Option<HashMap<String, Vec<String>>>,
+        /// Name of the feature preset in `[presets]` to build the tool with, unless `--preset`
+        /// names another.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        preset: Option<String>,
+    },
+}
+
+/// A release channel of a tool, tracking a branch of its git repository rather than its releases.
+///
+/// `tool@<channel>` builds whatever commit the branch is at, which is looked up again once the
+/// previous lookup is older than [`Self::refresh`], so development builds stay reasonably current
+/// without a round trip to the repository on every run.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolChannel {
+    /// The repository to build the channel from.  Defaults to the tool's own `git`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+
+    /// The branch to track.  Defaults to the repository's default branch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+
+    /// How long the commit the branch was found at is reused before looking it up again.
+    /// Defaults to `resolve_cache_timeout`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "humantime_serde::option"
+    )]
+    pub refresh: Option<Duration>,
+}
+
+/// Auxiliary files a tool needs at runtime, which are copied from its source into a directory
+/// next to its cached binary.
+///
+/// When the tool is run, an environment variable is set to the path of that directory, so that
+/// the tool (or a wrapper script) can find them.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolAssets {
+    /// Gitignore-style glob patterns of the files to stage, relative to the root of the crate's
+    /// source, e.g. `templates/**`.  Files keep their paths relative to that root.
+    pub include: Vec<String>,
+
+    /// Name of the environment variable set to the staged asset directory.  Defaults to
+    /// [`ToolAssets::DEFAULT_ENV_VAR`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+}
+
+impl ToolAssets {
+    /// Environment variable that is set to the staged asset directory if [`Self::env`] isn't.
+    pub const DEFAULT_ENV_VAR: &str = "CGX_ASSETS_DIR";
+
+    /// The name of the environment variable to set to the staged asset directory.
+    pub fn env_var(&self) -> &str {
+        self.env.as_deref().unwrap_or(Self::DEFAULT_ENV_VAR)
+    }
+}
+
+/// A named set of cargo feature flags, defined under `[presets]` and selected with `--preset` or
+/// a tool's `preset`, to save repeating long feature lists.
+///
+/// A preset only expands into the feature flags of the [`BuildOptions`](crate::builder::BuildOptions),
+/// so builds with the same features are cached as one whether or not they came from a preset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeaturePreset {
+    /// Features to activate (as with `--features`).
+    pub features: Vec<String>,
+
+    /// Activate all available features (as with `--all-features`).
+    pub all_features: bool,
+
+    /// Do not activate the `default` feature (as with `--no-default-features`).
+    pub no_default_features: bool,
+}
+
+/// Intermediate structure for deserializing config files from TOML.
+///
+/// This matches the structure of cgx.toml files and is used during the deserialization
 /// process. Fields are then mapped to the final [`Config`] struct.
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct ConfigFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(deserialize_with = "deserialize_optional_expanded_path")]
     pub bin_dir: Option<PathBuf>,
@@ -221,6 +975,10 @@ pub struct ConfigFile {
     #[serde(deserialize_with = "deserialize_optional_expanded_path")]
     pub build_dir: Option<PathBuf>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "deserialize_optional_expanded_path")]
+    pub install_dir: Option<PathBuf>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(deserialize_with = "deserialize_optional_expanded_path")]
     pub cache_dir: Option<PathBuf>,
@@ -244,17 +1002,87 @@ pub struct ConfigFile {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_registry: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_bin_policy: Option<DefaultBinPolicy>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_fallback: Option<Vec<SourceFallback>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prebuilt_binaries: Option<PrebuiltBinariesConfig>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefer_system_binaries: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_sbom: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggest_pins: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_platform: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor_dependencies: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_target_dir: Option<LocalTargetDir>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "humantime_serde")]
+    pub vendor_cache_timeout: Option<Duration>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_priority: Option<BuildPriority>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeouts: Option<PhaseTimeouts>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_notes: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collect_metrics: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<HooksConfig>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub http: Option<HttpConfigFile>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoints: Option<EndpointsConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitConfig>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<HashMap<String, ToolConfig>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub aliases: Option<HashMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presets: Option<HashMap<String, FeaturePreset>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub successors: Option<HashMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_successors: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script_interpreters: Option<HashMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wasm_runtime: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub untrusted_configs: Option<UntrustedConfigPolicy>,
 }
 
 impl ConfigFile {
@@ -267,8 +1095,10 @@ impl ConfigFile {
     /// in the config hierarchy, before any config files are applied.
     pub fn base_config() -> Self {
         Self {
+            include: None,
             bin_dir: None,
             build_dir: None,
+            install_dir: None,
             cache_dir: None,
             locked: Some(true),
             log_level: None,
@@ -276,12 +1106,249 @@ impl ConfigFile {
             resolve_cache_timeout: Some(DEFAULT_RESOLVE_CACHE_TIMEOUT),
             toolchain: None,
             default_registry: None,
+            default_bin_policy: None,
+            source_fallback: None,
             prebuilt_binaries: Some(PrebuiltBinariesConfig::default()),
+            prefer_system_binaries: None,
+            require_sbom: None,
+            suggest_pins: None,
+            container_platform: None,
+            vendor_dependencies: None,
+            local_target_dir: None,
+            vendor_cache_timeout: Some(DEFAULT_VENDOR_CACHE_TIMEOUT),
+            build_priority: None,
+            timeouts: None,
+            background: None,
+            release_notes: None,
+            collect_metrics: None,
+            hooks: None,
             http: None,
+            endpoints: None,
+            git: None,
             tools: None,
             aliases: None,
+            presets: None,
+            successors: None,
+            follow_successors: None,
+            script_interpreters: None,
+            wasm_runtime: None,
+            untrusted_configs: None,
+        }
+    }
+
+    /// Split off the settings an untrusted config file is allowed to make.
+    ///
+    /// Returns the restricted config, along with the names of the settings that were dropped.
+    fn restrict_to_untrusted(self) -> (Self, Vec<&'static str>) {
+        let dropped = [
+            ("include", self.include.is_some()),
+            ("bin_dir", self.bin_dir.is_some()),
+            ("build_dir", self.build_dir.is_some()),
+            ("install_dir", self.install_dir.is_some()),
+            ("cache_dir", self.cache_dir.is_some()),
+            ("locked", self.locked.is_some()),
+            ("toolchain", self.toolchain.is_some()),
+            ("default_registry", self.default_registry.is_some()),
+            ("default_bin_policy", self.default_bin_policy.is_some()),
+            ("source_fallback", self.source_fallback.is_some()),
+            ("prebuilt_binaries", self.prebuilt_binaries.is_some()),
+            ("prefer_system_binaries", self.prefer_system_binaries.is_some()),
+            ("suggest_pins", self.suggest_pins.is_some()),
+            ("container_platform", self.container_platform.is_some()),
+            ("vendor_dependencies", self.vendor_dependencies.is_some()),
+            ("local_target_dir", self.local_target_dir.is_some()),
+            ("vendor_cache_timeout", self.vendor_cache_timeout.is_some()),
+            ("collect_metrics", self.collect_metrics.is_some()),
+            ("hooks", self.hooks.is_some()),
+            ("http", self.http.is_some()),
+            ("endpoints", self.endpoints.is_some()),
+            ("git", self.git.is_some()),
+            ("tools", self.tools.is_some()),
+            ("aliases", self.aliases.is_some()),
+            ("presets", self.presets.is_some()),
+            ("successors", self.successors.is_some()),
+            ("follow_successors", self.follow_successors.is_some()),
+            ("script_interpreters", self.script_interpreters.is_some()),
+            ("wasm_runtime", self.wasm_runtime.is_some()),
+            ("untrusted_configs", self.untrusted_configs.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, is_set)| is_set.then_some(name))
+        .collect();
+
+        // Requiring an SBOM and time limits only ever make a run stricter, the build priority can
+        // only be lowered, and release notes are only shown, so a project can ask for any of them
+        // without being trusted
+        let restricted = Self {
+            log_level: self.log_level,
+            offline: self.offline,
+            resolve_cache_timeout: self.resolve_cache_timeout,
+            require_sbom: self.require_sbom,
+            build_priority: self.build_priority,
+            timeouts: self.timeouts,
+            background: self.background,
+            release_notes: self.release_notes,
+            ..Self::default()
+        };
+
+        (restricted, dropped)
+    }
+}
+
+/// A [`Provider`] of settings that didn't come straight from a file of their own, labeled with
+/// where they did come from so that `--show-config` can say.
+struct Labeled<P> {
+    provider: P,
+    metadata: Metadata,
+}
+
+impl<P: Provider> Provider for Labeled<P> {
+    fn metadata(&self) -> Metadata {
+        self.metadata.clone()
+    }
+
+    fn data(&self) -> std::result::Result<Map<Profile, Dict>, figment::Error> {
+        self.provider.data()
+    }
+
+    fn profile(&self) -> Option<Profile> {
+        self.provider.profile()
+    }
+}
+
+/// One setting in effect in a directory, as listed by [`Config::settings_in_dir`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigSetting {
+    /// Dotted path of the setting, like `http.timeout` or `tools.ripgrep.version`.
+    pub key: String,
+
+    /// The value, formatted as TOML.
+    pub value: String,
+
+    /// Where the value came from: the path of a config file, or `defaults`.
+    pub source: String,
+}
+
+/// Flatten the settings in `table` into `settings`, with keys under `prefix`.
+///
+/// A non-empty table is flattened into a setting for each of its values, since each value of a
+/// table can come from a different config file; anything else, arrays included, is one setting.
+fn collect_settings(figment: &Figment, prefix: &str, table: &toml::Table, settings: &mut Vec<ConfigSetting>) {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+
+        if let toml::Value::Table(table) = value {
+            if !table.is_empty() {
+                collect_settings(figment, &key, table, settings);
+                continue;
+            }
+        }
+
+        settings.push(ConfigSetting {
+            source: setting_source(figment, &key),
+            key,
+            value: value.to_string(),
+        });
+    }
+}
+
+/// Where the setting `key` in `figment` came from: the path of a config file, or the name of
+/// whatever else provided it.
+fn setting_source(figment: &Figment, key: &str) -> String {
+    figment
+        .find_metadata(key)
+        .map(|metadata| match &metadata.source {
+            Some(Source::File(path)) => path.display().to_string(),
+            _ => metadata.name.to_string(),
+        })
+        .unwrap_or_default()
+}
+
+/// Extract a [`ConfigFile`] from `figment`, which is the single config file at `path` if given.
+///
+/// A setting that isn't one cgx knows, which is most often a typo, is reported by its dotted key
+/// and the file it's in, along with the setting it's closest to if there is one.
+fn extract_config_file(figment: &Figment, path: Option<&Path>) -> Result<ConfigFile> {
+    figment.extract().map_err(|source| {
+        let figment::error::Kind::UnknownField(field, expected) = &source.kind else {
+            return match path {
+                Some(path) => crate::error::ConfigLoadSnafu {
+                    path: path.to_path_buf(),
+                }
+                .into_error(source),
+                None => crate::error::ConfigExtractSnafu.into_error(source),
+            };
+        };
+
+        let mut key = source.path.clone();
+        if key.last() != Some(field) {
+            key.push(field.clone());
+        }
+        let key = key.join(".");
+        let file = match setting_source(figment, &key) {
+            file if file.is_empty() => path.map(|path| path.display().to_string()).unwrap_or_default(),
+            file => file,
+        };
+
+        crate::error::UnknownConfigKeySnafu {
+            key,
+            file,
+            hint: nearest_setting(field, expected)
+                .map(|setting| format!("; did you mean '{}'?", setting))
+                .unwrap_or_default(),
+        }
+        .build()
+    })
+}
+
+/// The one of `settings` that `unknown` is most likely a typo of, if any is close enough.
+fn nearest_setting<'a>(unknown: &str, settings: &[&'a str]) -> Option<&'a str> {
+    let max_distance = unknown.chars().count().max(3) / 3;
+    settings
+        .iter()
+        .map(|setting| (edit_distance(unknown, setting), *setting))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, setting)| setting)
+}
+
+/// The number of single-character insertions, deletions and substitutions it takes to turn `a`
+/// into `b`, ignoring case and treating `-` as `_`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let normalize = |s: &str| -> Vec<char> {
+        s.chars()
+            .map(|c| if c == '-' { '_' } else { c.to_ascii_lowercase() })
+            .collect()
+    };
+    let (a, b) = (normalize(a), normalize(b));
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
         }
+        previous = current;
     }
+
+    previous[b.len()]
+}
+
+/// Config files found by [`Config::discover_config_files`], each list ordered from lowest to
+/// highest precedence.
+#[derive(Debug, Default)]
+struct DiscoveredConfigFiles {
+    /// System and user config files, or the explicit `--config-file`.  These are always trusted.
+    global: Vec<PathBuf>,
+
+    /// `cgx.toml` files found in the directory hierarchy, which are subject to the
+    /// [`UntrustedConfigPolicy`].
+    hierarchy: Vec<PathBuf>,
 }
 
 /// Custom deserializer for optional [`PathBuf`] that expands ~ to home directory.
@@ -301,6 +1368,41 @@ where
     }
 }
 
+/// Custom deserializer for the quickinstall indexes in [`EndpointsConfig`], which can be given as
+/// one or a list, each a base URL or a [`QuickinstallIndex`] table.
+fn deserialize_quickinstall_indexes<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<QuickinstallIndex>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Index {
+        Url(Url),
+        Table(QuickinstallIndex),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Indexes {
+        One(Index),
+        Many(Vec<Index>),
+    }
+
+    let indexes = match Indexes::deserialize(deserializer)? {
+        Indexes::One(index) => vec![index],
+        Indexes::Many(indexes) => indexes,
+    };
+    Ok(indexes
+        .into_iter()
+        .map(|index| match index {
+            Index::Url(url) => QuickinstallIndex::new(url),
+            Index::Table(index) => index,
+        })
+        .collect())
+}
+
 /// Configuration settings for cgx.
 ///
 /// Configuration is loaded from multiple sources in order of precedence (later sources override
@@ -328,6 +1430,26 @@ pub struct Config {
     /// Only the final compiled binary is retained; all other build artifacts are cleaned up.
     pub build_dir: PathBuf,
 
+    /// Directory where the records and logs of tools started with `--detach` are kept (see
+    /// [`crate::detach`]).
+    pub detach_dir: PathBuf,
+
+    /// Directory tools installed with `--install` are put in, so that they're on the `PATH` (see
+    /// [`crate::installer`]).
+    ///
+    /// Defaults to the `bin` directory of `$CARGO_HOME`, where `cargo install` puts them.
+    pub install_dir: PathBuf,
+
+    /// Directory the record of the tools installed with `--install` is kept in (see
+    /// [`crate::installer`]).
+    pub installs_dir: PathBuf,
+
+    /// The directory of an `--ephemeral` run, if this is one.
+    ///
+    /// Pre-built binaries and what's recorded about them are kept here rather than in
+    /// [`Config::cache_dir`], so that they go when the run does.  See [`crate::ephemeral`].
+    pub run_dir: Option<PathBuf>,
+
     /// How long to keep resolved crate information in the cache before re-resolving
     pub resolve_cache_timeout: Duration,
 
@@ -335,7 +1457,11 @@ pub struct Config {
 
     pub locked: bool,
 
-    pub refresh: bool,
+    /// The cache layers to bypass (see `--refresh`).
+    pub refresh: Refresh,
+
+    /// The date to resolve crates as of, from `--as-of` (see [`crate::as_of`]).
+    pub as_of: Option<NaiveDate>,
 
     /// Rust toolchain to use for building (e.g., "nightly", "1.70.0", "stable")
     pub toolchain: Option<String>,
@@ -346,12 +1472,83 @@ pub struct Config {
     /// Default registry to use instead of crates.io when no registry is explicitly specified
     pub default_registry: Option<String>,
 
+    /// What to run for a crate with several binaries and no `default-run`, unless `--bin` is
+    /// given.
+    pub default_bin_policy: DefaultBinPolicy,
+
+    /// Sources to try, in order, for a crate given by name alone.
+    ///
+    /// The first source that has the crate is used.  If empty, only [`Self::default_registry`]
+    /// (or crates.io if that's not set) is consulted.  Crates whose source is given on the
+    /// command line or in [`Self::tools`] are never subject to fallback.
+    pub source_fallback: Vec<SourceFallback>,
+
     /// How or whether to look for pre-built binaries published for the crates being run.
     pub prebuilt_binaries: PrebuiltBinariesConfig,
 
+    /// Whether to run a binary of the tool that's already installed, with `cargo install` or on
+    /// `PATH`, when its version satisfies the requirement (see [`crate::system_binary`]).
+    pub prefer_system_binaries: bool,
+
+    /// Whether to fail rather than run a binary that has no SBOM.
+    ///
+    /// Only binaries built from source have one, so this rules out pre-built and already
+    /// installed binaries.
+    pub require_sbom: bool,
+
+    /// Whether to record the crates run without a pin, and suggest pinning those run often (see
+    /// [`crate::usage`]).  Off unless asked for, since it writes to the cache directory on every
+    /// run.
+    pub suggest_pins: bool,
+
+    /// The target triple of the container image docker buildx is building, when it's for another
+    /// platform than the host (see [`crate::platform::container_target`]).
+    ///
+    /// This is only set with `container_platform = true` in a config file, and with `--no-exec` or
+    /// `--out-dir`, since a binary for the image that is run on the host is of no use.
+    ///
+    /// Unless `--target` is given, tools are built for this target, and pre-built binaries are
+    /// looked for for it alone unless other targets are configured.
+    pub container_target: Option<String>,
+
+    /// Whether to vendor the dependencies of crates built from source into the cache, so that they
+    /// can be built again offline (see [`crate::vendor`]).
+    pub vendor_dependencies: bool,
+
+    /// Where cargo puts what it builds for a crate in a local directory, such as one given with
+    /// `--path`.
+    pub local_target_dir: LocalTargetDir,
+
+    /// How long vendored dependencies are kept without being used.
+    pub vendor_cache_timeout: Duration,
+
+    /// The priority to run cargo at, already lowered to the lowest for `--background` or
+    /// `background = true` wherever the config doesn't set it.
+    pub build_priority: BuildPriority,
+
+    /// How long each phase of getting a binary may take, and the deadline for the whole run, with
+    /// `--deadline` already applied.
+    pub timeouts: PhaseTimeouts,
+
+    /// Whether to show the changelog entries of a crate that resolves to a newer version than was
+    /// last run here (see [`crate::release_notes`]).
+    pub release_notes: bool,
+
+    /// Whether to count what each run does in the local metrics (see [`crate::metrics`]).
+    pub collect_metrics: bool,
+
+    /// Commands to run at points in getting and running a tool (see [`crate::hooks`]).
+    pub hooks: HooksConfig,
+
     /// HTTP client configuration for registry queries, binary downloads, and API calls.
     pub http: HttpConfig,
 
+    /// Base URLs of the registry, forges, and binary hosts cgx talks to.
+    pub endpoints: EndpointsConfig,
+
+    /// How to authenticate to git hosts.
+    pub git: GitConfig,
+
     /// Pinned tool versions and configurations.
     ///
     /// Tools listed here will use the specified version/source instead of being resolved
@@ -363,6 +1560,38 @@ pub struct Config {
     /// Maps convenient names to actual crate names. For example, `rg` -> `ripgrep`.
     /// Note that aliases shadow actual crate names, so aliased crates become inaccessible.
     pub aliases: HashMap<String, String>,
+
+    /// Where each of [`Self::aliases`] and the settings in [`Self::tools`] came from, by dotted
+    /// key like `aliases.rg` or `tools.ripgrep.version`: the path of a config file, or `defaults`.
+    ///
+    /// This is so that what a crate spec resolved to can be traced back to the setting that
+    /// changed it (see [`crate::cratespec::CrateSpec::config_decisions`]).
+    pub setting_sources: HashMap<String, String>,
+
+    /// Named sets of feature flags, selected with `--preset` or a tool's `preset`.
+    pub presets: HashMap<String, FeaturePreset>,
+
+    /// Crates that have been renamed or replaced, and the crate that took their place, on top of
+    /// those [`crate::successors`] knows about.  An empty successor means the crate has none.
+    pub successors: HashMap<String, String>,
+
+    /// Whether to run the successor of a crate that has been renamed or replaced in its place,
+    /// rather than only pointing it out.
+    pub follow_successors: bool,
+
+    /// Commands to run files from a crate's source with, keyed by file extension, for
+    /// `--run-source-file`.
+    ///
+    /// These are on top of the common ones in [`crate::source_file`]; an empty command has a file
+    /// with that extension run directly.
+    pub script_interpreters: HashMap<String, String>,
+
+    /// The command to run WebAssembly (WASI) binaries with, in place of a runtime found on the
+    /// `PATH` (see [`crate::runner::wasm_runtime`]).
+    pub wasm_runtime: Option<String>,
+
+    /// How config files from directories that haven't been trusted were treated.
+    pub untrusted_configs: UntrustedConfigPolicy,
 }
 
 impl Default for Config {
@@ -372,17 +1601,45 @@ impl Default for Config {
             cache_dir: PathBuf::default(),
             bin_dir: PathBuf::default(),
             build_dir: PathBuf::default(),
+            detach_dir: PathBuf::default(),
+            install_dir: PathBuf::default(),
+            installs_dir: PathBuf::default(),
+            run_dir: None,
             resolve_cache_timeout: Duration::from_secs(3600),
             offline: false,
             locked: true,
-            refresh: false,
+            refresh: Refresh::default(),
+            as_of: None,
             toolchain: None,
             log_level: None,
             default_registry: None,
+            default_bin_policy: DefaultBinPolicy::default(),
+            source_fallback: Vec::new(),
             prebuilt_binaries: PrebuiltBinariesConfig::default(),
+            prefer_system_binaries: false,
+            require_sbom: false,
+            suggest_pins: false,
+            container_target: None,
+            vendor_dependencies: false,
+            local_target_dir: LocalTargetDir::default(),
+            vendor_cache_timeout: DEFAULT_VENDOR_CACHE_TIMEOUT,
+            build_priority: BuildPriority::default(),
+            timeouts: PhaseTimeouts::default(),
+            release_notes: false,
+            collect_metrics: false,
+            hooks: HooksConfig::default(),
             http: HttpConfig::default(),
+            endpoints: EndpointsConfig::default(),
+            git: GitConfig::default(),
             tools: HashMap::default(),
             aliases: HashMap::default(),
+            setting_sources: HashMap::default(),
+            presets: HashMap::default(),
+            successors: HashMap::default(),
+            follow_successors: false,
+            script_interpreters: HashMap::default(),
+            wasm_runtime: None,
+            untrusted_configs: UntrustedConfigPolicy::default(),
         }
     }
 }
@@ -406,22 +1663,19 @@ impl Config {
     /// Load config from the CLI args and a specified directory which may or may not contain config
     /// files.
     pub fn load_from_dir(cwd: &Path, args: &CliArgs) -> Result<Self> {
-        use figment::{
-            Figment,
-            providers::{Format, Serialized, Toml},
-        };
-
         let strategy = Self::get_user_dirs()?;
 
-        // Start with base config defaults, then merge config files
-        let mut figment = Figment::new().merge(Serialized::defaults(ConfigFile::base_config()));
+        // The trust store always comes from the user's own config dir, even when a project
+        // environment takes the place of the app dir
+        let trust_store = TrustStore::load(&Self::user_config_dir(args)?)?;
+        let args = Self::args_for_dir(cwd, args, &trust_store);
+        let args = args.as_ref();
+        let config_dir = Self::user_config_dir(args)?;
 
-        for config_file in Self::discover_config_files(cwd, args)? {
-            figment = figment.merge(Toml::file(config_file));
-        }
+        let (figment, untrusted_configs) = Self::merge_config_files(cwd, args, &trust_store)?;
 
         // Extract merged config file values (no CLI overrides applied yet via Figment)
-        let config_file: ConfigFile = figment.extract().context(crate::error::ConfigExtractSnafu)?;
+        let config_file = extract_config_file(&figment, None)?;
 
         // Override the config file values using any CLI args that were specified
 
@@ -444,15 +1698,6 @@ impl Config {
         // toolchain: CLI > config
         let toolchain = args.toolchain.clone().or(config_file.toolchain);
 
-        // Determine config_dir based on override precedence
-        let config_dir = if let Some(user_config_dir) = &args.user_config_dir {
-            user_config_dir.clone()
-        } else if let Some(app_dir) = &args.app_dir {
-            app_dir.join("config")
-        } else {
-            strategy.config_dir()
-        };
-
         // Determine cache_dir: CLI (app-dir) > config file > strategy
         let cache_dir = if let Some(app_dir) = &args.app_dir {
             app_dir.join("cache")
@@ -478,6 +1723,33 @@ impl Config {
                 .unwrap_or_else(|| strategy.in_data_dir("build"))
         };
 
+        // Determine detach_dir: CLI (app-dir) > strategy
+        let detach_dir = match &args.app_dir {
+            Some(app_dir) => app_dir.join("run"),
+            None => strategy.in_data_dir("run"),
+        };
+
+        // Determine install_dir: config file > `$CARGO_HOME/bin`.  It's meant to be on the `PATH`,
+        // so unlike the directories above an app dir has no bearing on it.
+        let install_dir = config_file.install_dir.unwrap_or_else(|| {
+            home::cargo_home().map_or_else(
+                |_| strategy.in_data_dir("installed"),
+                |cargo_home| cargo_home.join("bin"),
+            )
+        });
+
+        // Determine installs_dir: CLI (app-dir) > strategy
+        let installs_dir = match &args.app_dir {
+            Some(app_dir) => app_dir.join("installs"),
+            None => strategy.in_data_dir("installs"),
+        };
+
+        // Resolve symlinks up front, so that a symlinked directory can't make the same file show
+        // up under two different paths when binaries are copied between these directories.
+        let cache_dir = crate::helpers::canonicalize_lenient(&cache_dir);
+        let bin_dir = crate::helpers::canonicalize_lenient(&bin_dir);
+        let build_dir = crate::helpers::canonicalize_lenient(&build_dir);
+
         let mut prebuilt_binaries = config_file.prebuilt_binaries.unwrap_or_default();
 
         // Apply CLI overrides for prebuilt binaries
@@ -493,6 +1765,26 @@ impl Config {
         if args.prebuilt_binary_no_verify_signatures {
             prebuilt_binaries.verify_signatures = false;
         }
+        if let Some(ref targets) = args.prebuilt_targets {
+            prebuilt_binaries.targets = targets.clone();
+        }
+        if let Some(origin) = args.trust_prebuilt_only_from {
+            prebuilt_binaries.trust_prebuilt_only_from = origin;
+        }
+
+        // In a docker buildx build for another platform, binaries are for the image being built,
+        // as long as they're only being put somewhere rather than run here
+        let container_target =
+            if config_file.container_platform.unwrap_or(false) && (args.no_exec || args.out_dir.is_some()) {
+                crate::platform::container_target(std::env::var("TARGETPLATFORM").ok().as_deref())
+            } else {
+                None
+            };
+        if let Some(target) = &container_target {
+            if prebuilt_binaries.targets.is_empty() {
+                prebuilt_binaries.targets = vec![target.clone()];
+            }
+        }
 
         // Validate prebuilt binaries configuration
         if prebuilt_binaries.binary_providers.is_empty()
@@ -501,6 +1793,64 @@ impl Config {
             return crate::error::NoProvidersConfiguredSnafu.fail();
         }
 
+        let require_sbom = args.require_sbom || config_file.require_sbom.unwrap_or(false);
+        if require_sbom && prebuilt_binaries.use_prebuilt_binaries == UsePrebuiltBinaries::Always {
+            return crate::error::InvalidConfigValueSnafu {
+                field: "require_sbom",
+                message: "pre-built binaries have no SBOM, so it can't be required when only pre-built binaries are \
+                          allowed",
+            }
+            .fail();
+        }
+
+        let mut tools = config_file.tools.unwrap_or_default();
+        let aliases = config_file.aliases.unwrap_or_default();
+        let mut setting_sources = Self::alias_and_tool_sources(&figment, &aliases, &tools);
+        Self::validate_versions(&figment, &tools)?;
+        Self::override_pins(args, &aliases, &mut tools, &mut setting_sources)?;
+        Self::validate_prebuilt_sha256(&tools)?;
+        let presets = config_file.presets.unwrap_or_default();
+        Self::validate_tool_presets(&tools, &presets)?;
+        Self::validate_assets(&tools)?;
+        Self::validate_wrappers(&tools)?;
+        Self::validate_channels(&tools)?;
+
+        let mut build_priority = config_file.build_priority.unwrap_or_default();
+        if build_priority
+            .nice
+            .is_some_and(|nice| nice > BuildPriority::MAX_NICE)
+        {
+            return crate::error::InvalidConfigValueSnafu {
+                field: "build_priority.nice",
+                message: format!("it can be at most {}", BuildPriority::MAX_NICE),
+            }
+            .fail();
+        }
+        if args.background || config_file.background.unwrap_or(false) {
+            build_priority = build_priority.background();
+        }
+
+        let mut timeouts = config_file.timeouts.unwrap_or_default();
+        if let Some(value) = &args.deadline {
+            timeouts.deadline = Some(
+                humantime::parse_duration(value)
+                    .context(crate::error::InvalidDeadlineSnafu { value: value.clone() })?,
+            );
+        }
+
+        let hooks = config_file.hooks.unwrap_or_default();
+        hooks.validate()?;
+
+        let script_interpreters = config_file.script_interpreters.unwrap_or_default();
+        for interpreter in script_interpreters.values() {
+            crate::runner::parse_wrapper(interpreter)?;
+        }
+        if let Some(wasm_runtime) = &config_file.wasm_runtime {
+            crate::runner::parse_wrapper(wasm_runtime)?;
+        }
+
+        let as_of = args.as_of.as_deref().map(crate::as_of::parse_date).transpose()?;
+
         // Build HTTP config with precedence: CLI > config file > Cargo env vars > defaults
         let http_config_file = config_file.http.unwrap_or_default();
         let http = Self::build_http_config(&http_config_file, args)?;
@@ -510,80 +1860,632 @@ impl Config {
             cache_dir,
             bin_dir,
             build_dir,
+            detach_dir,
+            install_dir,
+            installs_dir,
+            run_dir: None,
             resolve_cache_timeout: config_file
                 .resolve_cache_timeout
                 .unwrap_or(DEFAULT_RESOLVE_CACHE_TIMEOUT),
             offline,
             locked,
-            refresh: args.refresh,
+            refresh: Refresh::from_layers(&args.refresh),
+            as_of,
             toolchain,
             log_level: config_file.log_level,
             default_registry: config_file.default_registry,
+            default_bin_policy: args
+                .default_bin_policy
+                .or(config_file.default_bin_policy)
+                .unwrap_or_default(),
+            source_fallback: config_file.source_fallback.unwrap_or_default(),
             prebuilt_binaries,
+            prefer_system_binaries: config_file.prefer_system_binaries.unwrap_or(false),
+            require_sbom,
+            suggest_pins: config_file.suggest_pins.unwrap_or(false),
+            container_target,
+            vendor_dependencies: args.vendor || config_file.vendor_dependencies.unwrap_or(false),
+            local_target_dir: args
+                .local_target_dir
+                .clone()
+                .or(config_file.local_target_dir)
+                .unwrap_or_default(),
+            vendor_cache_timeout: config_file
+                .vendor_cache_timeout
+                .unwrap_or(DEFAULT_VENDOR_CACHE_TIMEOUT),
+            build_priority,
+            timeouts,
+            release_notes: args.release_notes || config_file.release_notes.unwrap_or(false),
+            collect_metrics: config_file.collect_metrics.unwrap_or(false),
+            hooks,
             http,
-            tools: config_file.tools.unwrap_or_default(),
-            aliases: config_file.aliases.unwrap_or_default(),
+            endpoints: config_file.endpoints.unwrap_or_default(),
+            git: config_file.git.unwrap_or_default(),
+            tools,
+            aliases,
+            setting_sources,
+            presets,
+            successors: config_file.successors.unwrap_or_default(),
+            follow_successors: config_file.follow_successors.unwrap_or(false),
+            script_interpreters,
+            wasm_runtime: config_file.wasm_runtime,
+            untrusted_configs,
         })
     }
 
-    /// Discover all config file locations in order of precedence.
-    ///
-    /// Returns paths from lowest to highest precedence. Later config files override earlier ones.
-    ///
-    /// The search order is:
-    /// 1. System config: `/etc/cgx.toml` on Unix, Windows equivalent (or override location)
-    /// 2. User config: `$XDG_CONFIG_HOME/cgx/cgx.toml` or platform equivalent (or override
-    ///    location)
-    /// 3. Directory hierarchy: All `cgx.toml` files from filesystem root to current directory
-    fn discover_config_files(cwd: &Path, args: &CliArgs) -> Result<Vec<PathBuf>> {
-        let mut config_files = Vec::new();
+    /// The args to load config for `cwd` with: `args` itself, unless `cwd` is in a trusted project
+    /// environment, which takes the place of the app dir.
+    fn args_for_dir<'a>(cwd: &Path, args: &'a CliArgs, trust_store: &TrustStore) -> Cow<'a, CliArgs> {
+        if args.app_dir.is_some() {
+            return Cow::Borrowed(args);
+        }
 
-        // If the user explicitly specified a config file, read ONLY that file
-        if let Some(config_path) = &args.config_file {
-            return Ok(vec![config_path.clone()]);
+        match project_env::find(cwd, trust_store) {
+            Some(env_dir) => {
+                tracing::debug!("Using project environment in {}", env_dir.display());
+                Cow::Owned(CliArgs {
+                    app_dir: Some(env_dir),
+                    ..args.clone()
+                })
+            }
+            None => Cow::Borrowed(args),
         }
+    }
 
-        // System config (can be overridden)
-        if let Some(system_config_dir) = &args.system_config_dir {
-            let system_config = system_config_dir.join("cgx.toml");
-            if system_config.exists() {
-                config_files.push(system_config);
+    /// Merge the defaults and every config file that applies in `cwd`, in order of precedence.
+    ///
+    /// Returns the merged config, which records where each setting came from, along with the
+    /// policy that was applied to the untrusted config files in the hierarchy.
+    fn merge_config_files(
+        cwd: &Path,
+        args: &CliArgs,
+        trust_store: &TrustStore,
+    ) -> Result<(Figment, UntrustedConfigPolicy)> {
+        // Start with base config defaults, then merge config files
+        let mut figment = Figment::new().merge(Labeled {
+            provider: Serialized::defaults(ConfigFile::base_config()),
+            metadata: Metadata::named("defaults"),
+        });
+
+        let discovered = Self::discover_config_files(cwd, args)?;
+        for config_file in &discovered.global {
+            for layer in Self::with_includes(config_file)? {
+                figment = figment.merge(Toml::file(layer));
             }
-        } else {
-            #[cfg(unix)]
-            {
-                let system_config = PathBuf::from("/etc/cgx.toml");
-                if system_config.exists() {
-                    config_files.push(system_config);
+        }
+
+        // Only the global config files get a say in how much the hierarchy config files are
+        // trusted
+        let global_config_file = extract_config_file(&figment, None)?;
+        let untrusted_configs = global_config_file.untrusted_configs.unwrap_or_default();
+
+        for config_file in discovered.hierarchy {
+            let dir = config_file.parent().unwrap_or(cwd);
+
+            if untrusted_configs == UntrustedConfigPolicy::Allow || trust_store.is_trusted(dir) {
+                for layer in Self::with_includes(&config_file)? {
+                    figment = figment.merge(Toml::file(layer));
                 }
+                continue;
             }
 
-            #[cfg(windows)]
+            if untrusted_configs == UntrustedConfigPolicy::Ignore {
+                tracing::warn!(
+                    "Ignoring untrusted config file {}; run `cgx --trust {}` to use it.",
+                    config_file.display(),
+                    dir.display()
+                );
+                continue;
+            }
+
+            let untrusted =
+                extract_config_file(&Figment::from(Toml::file(&config_file)), Some(&config_file))?;
+            let (restricted, dropped) = untrusted.restrict_to_untrusted();
+            if !dropped.is_empty() {
+                tracing::warn!(
+                    "Ignoring {} in untrusted config file {}; run `cgx --trust {}` to use them.",
+                    dropped.join(", "),
+                    config_file.display(),
+                    dir.display()
+                );
+            }
+            figment = figment.merge(Labeled {
+                provider: Serialized::defaults(restricted),
+                metadata: Metadata::from("untrusted TOML file", Source::File(config_file.clone())),
+            });
+        }
+
+        Ok((figment, untrusted_configs))
+    }
+
+    /// The files to merge for `config_file`, from lowest to highest precedence: the files named by
+    /// its `include`, each preceded by the files it includes in turn, and then `config_file` itself.
+    ///
+    /// Included paths are relative to the directory of the file that includes them.  A file that's
+    /// included more than once is merged the first time only, and files that include each other are
+    /// an error.
+    fn with_includes(config_file: &Path) -> Result<Vec<PathBuf>> {
+        let mut layers = Vec::new();
+        Self::collect_includes(config_file, &mut Vec::new(), &mut layers)?;
+        Ok(layers)
+    }
+
+    /// Add `config_file` to `layers` after the files it includes, where `chain` is the files that
+    /// led to including it.
+    fn collect_includes(
+        config_file: &Path,
+        chain: &mut Vec<PathBuf>,
+        layers: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        let canonical = crate::helpers::canonicalize_lenient(config_file);
+        if let Some(start) = chain.iter().position(|path| *path == canonical) {
+            let mut cycle = chain[start..].to_vec();
+            cycle.push(canonical);
+            return crate::error::ConfigIncludeCycleSnafu { cycle }.fail();
+        }
+        if layers
+            .iter()
+            .any(|layer| crate::helpers::canonicalize_lenient(layer) == canonical)
+        {
+            return Ok(());
+        }
+
+        #[derive(Default, Deserialize)]
+        struct Includes {
+            #[serde(default)]
+            include: Vec<String>,
+        }
+        // A file that can't be parsed is left for the extraction of the merged configuration to
+        // report, the same as one without includes would be
+        let includes: Includes = Figment::from(Toml::file(config_file))
+            .extract()
+            .unwrap_or_default();
+
+        let dir = config_file.parent().unwrap_or(Path::new(""));
+        chain.push(canonical);
+        for include in includes.include {
+            let path = dir.join(shellexpand::tilde(&include).as_ref());
+            if !path.is_file() {
+                return crate::error::ConfigIncludeNotFoundSnafu {
+                    path,
+                    included_from: config_file.to_path_buf(),
+                }
+                .fail();
+            }
+            Self::collect_includes(&crate::helpers::canonicalize_lenient(&path), chain, layers)?;
+        }
+        chain.pop();
+
+        layers.push(config_file.to_path_buf());
+        Ok(())
+    }
+
+    /// Every setting that the defaults and config files in effect in `cwd` make, along with where
+    /// each came from (see `--show-config`).
+    ///
+    /// Tables are flattened into dotted keys, like `http.timeout`.  CLI args only play a part in
+    /// choosing the config files, so this is what `cwd` itself brings to the config.
+    pub fn settings_in_dir(cwd: &Path, args: &CliArgs) -> Result<Vec<ConfigSetting>> {
+        let trust_store = TrustStore::load(&Self::user_config_dir(args)?)?;
+        let args = Self::args_for_dir(cwd, args, &trust_store);
+        let (figment, _) = Self::merge_config_files(cwd, &args, &trust_store)?;
+
+        let table: toml::Table = figment.extract().context(crate::error::ConfigExtractSnafu)?;
+        let mut settings = Vec::new();
+        collect_settings(&figment, "", &table, &mut settings);
+
+        Ok(settings)
+    }
+
+    /// Where each alias, and each setting of a tool that changes what it resolves to, came from
+    /// (see [`Self::setting_sources`]).
+    fn alias_and_tool_sources(
+        figment: &Figment,
+        aliases: &HashMap<String, String>,
+        tools: &HashMap<String, ToolConfig>,
+    ) -> HashMap<String, String> {
+        let alias_keys = aliases.keys().map(|alias| format!("aliases.{}", alias));
+        let tool_keys = tools.iter().flat_map(|(tool, tool_config)| {
+            let fields: &[&str] = match tool_config {
+                ToolConfig::Version(_) => &[],
+                ToolConfig::Detailed { .. } => {
+                    &["version", "registry", "git", "branch", "tag", "rev", "path"]
+                }
+            };
+            std::iter::once(format!("tools.{}", tool)).chain(
+                fields
+                    .iter()
+                    .map(move |field| format!("tools.{}.{}", tool, field)),
+            )
+        });
+
+        alias_keys
+            .chain(tool_keys)
+            .filter_map(|key| {
+                figment
+                    .find_metadata(&key)
+                    .is_some()
+                    .then(|| (key.clone(), setting_source(figment, &key)))
+            })
+            .collect()
+    }
+
+    /// Apply `--ignore-pins` and then `--override-pin` to the tools in the config.
+    ///
+    /// A tool pinned with `--override-pin` has its setting recorded as coming from there, so that
+    /// it's named as such when it changes what a crate resolves to.  The digests of prebuilt
+    /// binaries pinned along with a version are dropped when the version is, since they're of the
+    /// binaries of that version.
+    fn override_pins(
+        args: &CliArgs,
+        aliases: &HashMap<String, String>,
+        tools: &mut HashMap<String, ToolConfig>,
+        setting_sources: &mut HashMap<String, String>,
+    ) -> Result<()> {
+        if args.ignore_pins {
+            for (tool, tool_config) in tools.iter_mut() {
+                match tool_config {
+                    ToolConfig::Version(_) => {
+                        setting_sources.remove(&format!("tools.{}", tool));
+                    }
+                    ToolConfig::Detailed {
+                        version,
+                        prebuilt_sha256,
+                        ..
+                    } => {
+                        *version = None;
+                        *prebuilt_sha256 = None;
+                        setting_sources.remove(&format!("tools.{}.version", tool));
+                    }
+                }
+            }
+
+            // A tool given as a version alone has nothing else to keep
+            tools.retain(|_, tool_config| matches!(tool_config, ToolConfig::Detailed { .. }));
+        }
+
+        for spec in &args.override_pin {
+            let (name, version) = crate::tool_pin::parse(spec)?;
+            let name = aliases.get(&name).cloned().unwrap_or(name);
+
+            let key = match tools.get_mut(&name) {
+                Some(ToolConfig::Detailed {
+                    version: pinned,
+                    prebuilt_sha256,
+                    ..
+                }) => {
+                    *pinned = Some(version);
+                    *prebuilt_sha256 = None;
+                    format!("tools.{}.version", name)
+                }
+                Some(tool_config) => {
+                    *tool_config = ToolConfig::Version(version);
+                    format!("tools.{}", name)
+                }
+                None => {
+                    tools.insert(name.clone(), ToolConfig::Version(version));
+                    format!("tools.{}", name)
+                }
+            };
+            setting_sources.insert(key, "--override-pin".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Check that the version every tool is pinned to is a valid version requirement, so that a
+    /// typo is reported along with the file it's in, rather than the pin being ignored.
+    fn validate_versions(figment: &Figment, tools: &HashMap<String, ToolConfig>) -> Result<()> {
+        for (tool, tool_config) in tools {
+            let (key, version) = match tool_config {
+                ToolConfig::Version(version) => (format!("tools.{}", tool), version),
+                ToolConfig::Detailed {
+                    version: Some(version),
+                    ..
+                } => (format!("tools.{}.version", tool), version),
+                ToolConfig::Detailed { version: None, .. } => continue,
+            };
+
+            semver::VersionReq::parse(version.trim()).with_context(|_| {
+                crate::error::InvalidToolVersionSnafu {
+                    config_file: setting_source(figment, &key),
+                    key: &key,
+                    version,
+                    hint: crate::cratespec::version_req_hint(version),
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Check that every digest pinned in `prebuilt_sha256` of a tool is a SHA256 digest, so that a
+    /// typo fails loudly rather than silently rejecting every prebuilt binary.
+    fn validate_prebuilt_sha256(tools: &HashMap<String, ToolConfig>) -> Result<()> {
+        for (tool, tool_config) in tools {
+            let ToolConfig::Detailed {
+                prebuilt_sha256: Some(digests),
+                ..
+            } = tool_config
+            else {
+                continue;
+            };
+
+            for (target, digest) in digests {
+                if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return crate::error::InvalidPinnedDigestSnafu { tool, target, digest }.fail();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that every preset a tool names is defined, so that a typo is reported when the
+    /// config is loaded rather than when the tool is next run.
+    fn validate_tool_presets(
+        tools: &HashMap<String, ToolConfig>,
+        presets: &HashMap<String, FeaturePreset>,
+    ) -> Result<()> {
+        for tool_config in tools.values() {
+            if let ToolConfig::Detailed {
+                preset: Some(preset), ..
+            } = tool_config
+            {
+                Self::find_preset(presets, preset)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The preset named `name` in `presets`, failing with the names of those there are if there's
+    /// no such preset.
+    fn find_preset<'a>(presets: &'a HashMap<String, FeaturePreset>, name: &str) -> Result<&'a FeaturePreset> {
+        presets.get(name).ok_or_else(|| {
+            let mut defined = presets.keys().cloned().collect::<Vec<_>>();
+            defined.sort();
+            crate::error::UnknownPresetSnafu { name, defined }.build()
+        })
+    }
+
+    /// Check that the asset patterns of every tool are valid globs, so that a typo is reported
+    /// when the config is loaded rather than when the tool is next run.
+    fn validate_assets(tools: &HashMap<String, ToolConfig>) -> Result<()> {
+        for (tool, tool_config) in tools {
+            if let ToolConfig::Detailed {
+                assets: Some(assets), ..
+            } = tool_config
+            {
+                crate::assets::matcher(tool, Path::new(""), assets)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the wrapper command of every tool can be split into words.
+    fn validate_wrappers(tools: &HashMap<String, ToolConfig>) -> Result<()> {
+        for tool_config in tools.values() {
+            if let ToolConfig::Detailed {
+                wrapper: Some(wrapper),
+                ..
+            } = tool_config
+            {
+                crate::runner::parse_wrapper(wrapper)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that every channel of a tool has a repository to build from, and can't be mistaken
+    /// for a version requirement.
+    fn validate_channels(tools: &HashMap<String, ToolConfig>) -> Result<()> {
+        for (tool, tool_config) in tools {
+            let ToolConfig::Detailed {
+                channels: Some(channels),
+                git,
+                ..
+            } = tool_config
+            else {
+                continue;
+            };
+
+            for (channel, channel_config) in channels {
+                if semver::VersionReq::parse(channel).is_ok() {
+                    return crate::error::InvalidChannelSnafu {
+                        tool,
+                        channel,
+                        message: "the name is also a version requirement",
+                    }
+                    .fail();
+                }
+                if channel_config.git.is_none() && git.is_none() {
+                    return crate::error::InvalidChannelSnafu {
+                        tool,
+                        channel,
+                        message: "neither the channel nor the tool has a `git` repository to build from",
+                    }
+                    .fail();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The channel `channel` of the tool `name`, if its [`ToolConfig`] has one by that name.
+    pub fn tool_channel(&self, name: &str, channel: &str) -> Option<&ToolChannel> {
+        match self.tools.get(name)? {
+            ToolConfig::Detailed {
+                channels: Some(channels),
+                ..
+            } => channels.get(channel),
+            _ => None,
+        }
+    }
+
+    /// How long the resolution of `spec` is reused before resolving it again.
+    ///
+    /// This is [`Self::resolve_cache_timeout`], unless `spec` is that of a [`ToolChannel`] with a
+    /// refresh interval of its own.
+    pub fn resolve_cache_timeout_for(&self, spec: &CrateSpec) -> Duration {
+        let channel_refresh = spec
+            .name()
+            .and_then(|name| match self.tools.get(name)? {
+                ToolConfig::Detailed {
+                    channels: Some(channels),
+                    ..
+                } => Some((name, channels)),
+                _ => None,
+            })
+            .and_then(|(name, channels)| {
+                channels.iter().find_map(|(channel, channel_config)| {
+                    let channel_spec = CrateSpec::for_channel(self, name, channel)?;
+                    (channel_spec == *spec)
+                        .then_some(channel_config.refresh)
+                        .flatten()
+                })
+            });
+
+        channel_refresh.unwrap_or(self.resolve_cache_timeout)
+    }
+
+    /// The name the binary of the tool `name` should be run as, if its [`ToolConfig`] sets one.
+    pub fn tool_argv0(&self, name: &str) -> Option<&str> {
+        match self.tools.get(name)? {
+            ToolConfig::Detailed { argv0, .. } => argv0.as_deref(),
+            ToolConfig::Version(_) => None,
+        }
+    }
+
+    /// The SHA256 digests pinned for the prebuilt binaries of the tool `name`, keyed by target
+    /// triple, if its [`ToolConfig`] pins any.
+    pub fn tool_prebuilt_sha256(&self, name: &str) -> Option<&HashMap<String, String>> {
+        match self.tools.get(name)? {
+            ToolConfig::Detailed { prebuilt_sha256, .. } => prebuilt_sha256.as_ref(),
+            ToolConfig::Version(_) => None,
+        }
+    }
+
+    /// The directory that pre-built binaries, downloaded archives, and the records of which
+    /// binaries were found or missing are kept under: [`Config::run_dir`] for an `--ephemeral`
+    /// run, otherwise [`Config::cache_dir`].
+    pub fn binary_cache_root(&self) -> &Path {
+        self.run_dir.as_deref().unwrap_or(&self.cache_dir)
+    }
+
+    /// The files to stage next to the binary of the tool `name`, if its [`ToolConfig`] has any.
+    pub fn tool_assets(&self, name: &str) -> Option<&ToolAssets> {
+        match self.tools.get(name)? {
+            ToolConfig::Detailed { assets, .. } => assets.as_ref(),
+            ToolConfig::Version(_) => None,
+        }
+    }
+
+    /// The helper executables to install alongside the prebuilt binary of the tool `name`, if its
+    /// [`ToolConfig`] lists any.
+    pub fn tool_helpers(&self, name: &str) -> Option<&[String]> {
+        match self.tools.get(name)? {
+            ToolConfig::Detailed { helpers, .. } => helpers.as_deref(),
+            ToolConfig::Version(_) => None,
+        }
+    }
+
+    /// The features that subcommands or flags of the tool `name` need, if its [`ToolConfig`] gives
+    /// any.
+    pub fn tool_feature_hints(&self, name: &str) -> Option<&HashMap<String, Vec<String>>> {
+        match self.tools.get(name)? {
+            ToolConfig::Detailed { feature_hints, .. } => feature_hints.as_ref(),
+            ToolConfig::Version(_) => None,
+        }
+    }
+
+    /// The name of the feature preset to build the tool `name` with, if its [`ToolConfig`] gives
+    /// one.
+    pub fn tool_preset(&self, name: &str) -> Option<&str> {
+        match self.tools.get(name)? {
+            ToolConfig::Detailed { preset, .. } => preset.as_deref(),
+            ToolConfig::Version(_) => None,
+        }
+    }
+
+    /// The feature preset named `name` in `[presets]`.
+    pub fn preset(&self, name: &str) -> Result<&FeaturePreset> {
+        Self::find_preset(&self.presets, name)
+    }
+
+    /// The command to run the tool `name` under, if its [`ToolConfig`] sets one.
+    pub fn tool_wrapper(&self, name: &str) -> Option<&str> {
+        match self.tools.get(name)? {
+            ToolConfig::Detailed { wrapper, .. } => wrapper.as_deref(),
+            ToolConfig::Version(_) => None,
+        }
+    }
+
+    /// The user config directory, which holds the user config file and the [`TrustStore`].
+    ///
+    /// `--user-config-dir` takes precedence over `--app-dir`, which takes precedence over the
+    /// platform-specific default.
+    pub fn user_config_dir(args: &CliArgs) -> Result<PathBuf> {
+        if let Some(user_config_dir) = &args.user_config_dir {
+            Ok(user_config_dir.clone())
+        } else if let Some(app_dir) = &args.app_dir {
+            Ok(app_dir.join("config"))
+        } else {
+            Ok(Self::get_user_dirs()?.config_dir())
+        }
+    }
+
+    /// Discover all config file locations in order of precedence.
+    ///
+    /// Returns paths from lowest to highest precedence. Later config files override earlier ones.
+    ///
+    /// The search order is:
+    /// 1. System config: `/etc/cgx.toml` on Unix, Windows equivalent (or override location)
+    /// 2. User config: `$XDG_CONFIG_HOME/cgx/cgx.toml` or platform equivalent (or override
+    ///    location)
+    /// 3. Directory hierarchy: All `cgx.toml` files from filesystem root to current directory
+    fn discover_config_files(cwd: &Path, args: &CliArgs) -> Result<DiscoveredConfigFiles> {
+        let mut config_files = DiscoveredConfigFiles::default();
+
+        // If the user explicitly specified a config file, read ONLY that file
+        if let Some(config_path) = &args.config_file {
+            config_files.global.push(config_path.clone());
+            return Ok(config_files);
+        }
+
+        // System config (can be overridden)
+        if let Some(system_config_dir) = &args.system_config_dir {
+            let system_config = system_config_dir.join("cgx.toml");
+            if system_config.exists() {
+                config_files.global.push(system_config);
+            }
+        } else {
+            #[cfg(unix)]
+            {
+                let system_config = PathBuf::from("/etc/cgx.toml");
+                if system_config.exists() {
+                    config_files.global.push(system_config);
+                }
+            }
+
+            #[cfg(windows)]
             {
                 if let Some(program_data) = std::env::var_os("ProgramData") {
                     let system_config = PathBuf::from(program_data).join("cgx").join("cgx.toml");
                     if system_config.exists() {
-                        config_files.push(system_config);
+                        config_files.global.push(system_config);
                     }
                 }
             }
         }
 
         // User config (can be overridden via user-config-dir or app-dir)
-        let user_config = if let Some(user_config_dir) = &args.user_config_dir {
-            // Most specific: explicit user config directory
-            user_config_dir.join("cgx.toml")
-        } else if let Some(app_dir) = &args.app_dir {
-            // App dir provides a base for config
-            app_dir.join("config").join("cgx.toml")
-        } else {
-            // Default: use platform-specific config directory
-            let strategy = Self::get_user_dirs()?;
-            strategy.config_dir().join("cgx.toml")
-        };
-
+        let user_config = Self::user_config_dir(args)?.join("cgx.toml");
         if user_config.exists() {
-            config_files.push(user_config);
+            config_files.global.push(user_config);
         }
 
         let mut ancestors: Vec<PathBuf> = cwd.ancestors().map(|p| p.to_path_buf()).collect();
@@ -592,7 +2494,7 @@ impl Config {
         for ancestor in ancestors {
             let config_file = ancestor.join("cgx.toml");
             if config_file.exists() {
-                config_files.push(config_file);
+                config_files.hierarchy.push(config_file);
             }
         }
 
@@ -618,6 +2520,7 @@ impl Config {
         let cli_timeout = args.http_timeout.as_ref();
         let cli_retries = args.http_retries;
         let cli_proxy = args.http_proxy.as_ref();
+        let cli_max_download_size = args.max_download_size.as_ref();
 
         // timeout: CLI > config > CARGO_HTTP_TIMEOUT > default
         let timeout = if let Some(timeout_str) = cli_timeout {
@@ -672,16 +2575,72 @@ impl Config {
             None
         };
 
+        // proxy bypass: config > NO_PROXY > None
+        let no_proxy = config_file.no_proxy.clone().or_else(|| {
+            ["NO_PROXY", "no_proxy"]
+                .iter()
+                .filter_map(|var| std::env::var(var).ok())
+                .find(|value| !value.is_empty())
+        });
+
+        // per-host proxies: config only
+        let proxies = config_file.proxies.clone().unwrap_or_default();
+        for (host, proxy) in &proxies {
+            let message = if crate::proxy_rules::HostPattern::parse(host).is_none() {
+                Some("it isn't a host name, domain, IP address or CIDR block".to_string())
+            } else if proxy.eq_ignore_ascii_case(crate::proxy_rules::DIRECT) {
+                None
+            } else {
+                Url::parse(proxy)
+                    .err()
+                    .map(|e| format!("'{proxy}' is neither a proxy URL nor \"direct\": {e}"))
+            };
+            if let Some(message) = message {
+                return crate::error::InvalidProxyRuleSnafu {
+                    host: host.clone(),
+                    message,
+                }
+                .fail();
+            }
+        }
+
         // backoff settings: config > defaults (no CLI or Cargo env fallback)
         let backoff_base = config_file.backoff_base.unwrap_or(DEFAULT_HTTP_BACKOFF_BASE);
         let backoff_max = config_file.backoff_max.unwrap_or(DEFAULT_HTTP_BACKOFF_MAX);
 
+        // proxy credentials: config > NETRC > ~/.netrc; the keychain only if enabled in config
+        let netrc = config_file
+            .netrc
+            .clone()
+            .or_else(crate::proxy_auth::default_netrc_path);
+        let proxy_keychain = config_file.proxy_keychain.unwrap_or(false);
+
+        // root certificates: config > CARGO_HTTP_CAINFO > None (the OS's own)
+        let ca_bundle = config_file.ca_bundle.clone().or_else(|| {
+            std::env::var_os("CARGO_HTTP_CAINFO")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+        });
+
+        // download size limit: CLI > config > default
+        let max_download_size = match cli_max_download_size.or(config_file.max_download_size.as_ref()) {
+            Some(value) => crate::runner::parse_byte_size(value)
+                .context(crate::error::InvalidMaxDownloadSizeSnafu { value: value.clone() })?,
+            None => DEFAULT_MAX_DOWNLOAD_SIZE,
+        };
+
         Ok(HttpConfig {
             timeout,
             retries,
             backoff_base,
             backoff_max,
             proxy,
+            no_proxy,
+            proxies,
+            netrc,
+            proxy_keychain,
+            ca_bundle,
+            max_download_size,
         })
     }
 }
@@ -696,6 +2655,9 @@ pub(crate) fn create_test_env() -> (tempfile::TempDir, Config) {
         cache_dir: temp_dir.path().join("cache"),
         bin_dir: temp_dir.path().join("bins"),
         build_dir: temp_dir.path().join("build"),
+        detach_dir: temp_dir.path().join("run"),
+        install_dir: temp_dir.path().join("installed"),
+        installs_dir: temp_dir.path().join("installs"),
         resolve_cache_timeout: Duration::from_secs(3600),
         locked: true,
         ..Default::default()
@@ -707,6 +2669,7 @@ pub(crate) fn create_test_env() -> (tempfile::TempDir, Config) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use assert_matches::assert_matches;
     use std::path::Path;
 
     /// Apply test-local config directory overrides so config loading cannot read
@@ -719,6 +2682,21 @@ mod tests {
         args
     }
 
+    /// Isolate config loading as [`with_isolated_global_config`] does, and also trust every
+    /// directory config file between the filesystem root and `cwd`.
+    ///
+    /// For tests of how hierarchy config files are merged, rather than of whether they're trusted.
+    fn with_trusted_hierarchy(args: CliArgs, root: &Path, cwd: &Path) -> CliArgs {
+        let args = with_isolated_global_config(args, root);
+
+        let mut trust_store = TrustStore::load(&root.join("user")).unwrap();
+        for dir in cwd.ancestors().filter(|dir| dir.join("cgx.toml").exists()) {
+            trust_store.trust(dir).unwrap();
+        }
+
+        args
+    }
+
     #[test]
     fn test_deserialize_basic_config() {
         let toml_content = r#"
@@ -726,6 +2704,7 @@ mod tests {
             cache_dir = "/tmp/cache"
             offline = true
             locked = false
+            prefer_system_binaries = true
         "#;
 
         let config: ConfigFile = toml::from_str(toml_content).unwrap();
@@ -733,6 +2712,7 @@ mod tests {
         assert_eq!(config.cache_dir, Some(PathBuf::from("/tmp/cache")));
         assert_eq!(config.offline, Some(true));
         assert_eq!(config.locked, Some(false));
+        assert_eq!(config.prefer_system_binaries, Some(true));
     }
 
     #[test]
@@ -813,81 +2793,409 @@ mod tests {
     }
 
     #[test]
-    fn test_deserialize_aliases() {
+    fn test_tool_argv0() {
         let toml_content = r#"
-            [aliases]
-            rg = "ripgrep"
-            taplo = "taplo-cli"
+            [tools]
+            coreutils = { version = "0.0.28", argv0 = "ls" }
+            ripgrep = "14.0"
         "#;
 
-        let config: ConfigFile = toml::from_str(toml_content).unwrap();
-        let aliases = config.aliases.unwrap();
-        assert_eq!(aliases.get("rg"), Some(&"ripgrep".to_string()));
-        assert_eq!(aliases.get("taplo"), Some(&"taplo-cli".to_string()));
+        let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+        let config = Config {
+            tools: config_file.tools.unwrap(),
+            ..Default::default()
+        };
+
+        assert_eq!(config.tool_argv0("coreutils"), Some("ls"));
+        assert_eq!(config.tool_argv0("ripgrep"), None);
+        assert_eq!(config.tool_argv0("taplo-cli"), None);
     }
 
     #[test]
-    fn test_config_defaults() {
-        let args = CliArgs::parse_from_test_args(["test-crate"]);
-        let config = Config::load(&args).unwrap();
+    fn test_tool_prebuilt_sha256() {
+        let toml_content = r#"
+            [tools]
+            taplo-cli = "0.9"
 
-        assert!(!config.offline);
-        assert!(config.locked); // Default is true per issue #55
-        assert_eq!(config.toolchain, None);
-        assert_eq!(config.resolve_cache_timeout, Duration::from_secs(60 * 60));
-    }
+            [tools.ripgrep]
+            version = "=14.1.1"
+            prebuilt_sha256 = { x86_64-unknown-linux-gnu = "4cf9f2741e6c465ffdb7c26f38056a59e2a2544b51f7cc128ef28337eeae4d8e" }
+        "#;
 
-    #[test]
-    fn test_cli_overrides() {
-        let args = CliArgs::parse_from_test_args(["+nightly", "--offline", "--locked", "test-crate"]);
-        let config = Config::load(&args).unwrap();
+        let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+        let tools = config_file.tools.unwrap();
+        Config::validate_prebuilt_sha256(&tools).unwrap();
+        let config = Config {
+            tools,
+            ..Default::default()
+        };
 
-        assert!(config.offline);
-        assert!(config.locked);
-        assert_eq!(config.toolchain, Some("nightly".to_string()));
+        let digests = config.tool_prebuilt_sha256("ripgrep").unwrap();
+        assert_eq!(
+            digests.get("x86_64-unknown-linux-gnu").map(String::as_str),
+            Some("4cf9f2741e6c465ffdb7c26f38056a59e2a2544b51f7cc128ef28337eeae4d8e")
+        );
+        assert_eq!(config.tool_prebuilt_sha256("taplo-cli"), None);
     }
 
     #[test]
-    fn test_frozen_implies_locked_and_offline() {
-        let args = CliArgs::parse_from_test_args(["--frozen", "test-crate"]);
-        let config = Config::load(&args).unwrap();
+    fn test_invalid_prebuilt_sha256_is_rejected() {
+        let toml_content = r#"
+            [tools]
+            ripgrep = { version = "=14.1.1", prebuilt_sha256 = { x86_64-unknown-linux-gnu = "abc123" } }
+        "#;
 
-        assert!(config.offline);
-        assert!(config.locked);
+        let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+
+        assert_matches::assert_matches!(
+            Config::validate_prebuilt_sha256(&config_file.tools.unwrap()),
+            Err(crate::error::Error::InvalidPinnedDigest { ref tool, ref digest, .. })
+                if tool == "ripgrep" && digest == "abc123"
+        );
     }
 
     #[test]
-    fn test_full_config_example() {
+    fn test_presets() {
         let toml_content = r#"
-            bin_dir = "~/.local/bin"
-            build_dir = "~/.local/build"
-            cache_dir = "~/.cache/cgx"
-            locked = true
-            log_level = "info"
-            offline = false
-            resolve_cache_timeout = "1h"
-            toolchain = "stable"
-            default_registry = "my-registry"
-
-            [prebuilt_binaries]
-            binary_providers = ["github-releases", "gitlab-releases", "quickinstall"]
+            [presets.full]
+            features = ["a", "b"]
+            no_default_features = true
 
             [tools]
-            ripgrep = "*"
-            taplo-cli = { version = "1.11.0", features = ["schema"] }
-
-            [aliases]
-            rg = "ripgrep"
-            taplo = "taplo-cli"
+            taplo-cli = { version = "0.9", preset = "full" }
+            ripgrep = "14.0"
         "#;
 
-        let config: ConfigFile = toml::from_str(toml_content).unwrap();
+        let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+        let tools = config_file.tools.unwrap();
+        let presets = config_file.presets.unwrap();
+        Config::validate_tool_presets(&tools, &presets).unwrap();
+        let config = Config {
+            tools,
+            presets,
+            ..Default::default()
+        };
 
-        assert_eq!(config.log_level, Some("info".to_string()));
-        assert_eq!(config.toolchain, Some("stable".to_string()));
-        assert_eq!(config.default_registry, Some("my-registry".to_string()));
-        assert_eq!(config.locked, Some(true));
-        assert_eq!(config.offline, Some(false));
+        assert_eq!(config.tool_preset("taplo-cli"), Some("full"));
+        assert_eq!(config.tool_preset("ripgrep"), None);
+        assert_eq!(
+            config.preset("full").unwrap(),
+            &FeaturePreset {
+                features: vec!["a".to_string(), "b".to_string()],
+                all_features: false,
+                no_default_features: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_undefined_tool_preset_is_rejected() {
+        let toml_content = r#"
+            [tools]
+            taplo-cli = { version = "0.9", preset = "full" }
+        "#;
+
+        let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+
+        assert_matches::assert_matches!(
+            Config::validate_tool_presets(&config_file.tools.unwrap(), &HashMap::new()),
+            Err(crate::error::Error::UnknownPreset { ref name, ref defined })
+                if name == "full" && defined.is_empty()
+        );
+    }
+
+    #[test]
+    fn test_tool_assets() {
+        let toml_content = r#"
+            [tools]
+            ripgrep = "14.0"
+
+            [tools.site-gen]
+            version = "0.3"
+            assets = { include = ["templates/**"], env = "SITE_GEN_DATA" }
+
+            [tools.doc-gen]
+            version = "1.0"
+            assets = { include = ["completions/*"] }
+        "#;
+
+        let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+        let tools = config_file.tools.unwrap();
+        Config::validate_assets(&tools).unwrap();
+        let config = Config {
+            tools,
+            ..Default::default()
+        };
+
+        let assets = config.tool_assets("site-gen").unwrap();
+        assert_eq!(assets.include, vec!["templates/**".to_string()]);
+        assert_eq!(assets.env_var(), "SITE_GEN_DATA");
+        assert_eq!(
+            config.tool_assets("doc-gen").unwrap().env_var(),
+            ToolAssets::DEFAULT_ENV_VAR
+        );
+        assert_eq!(config.tool_assets("ripgrep"), None);
+    }
+
+    #[test]
+    fn test_invalid_asset_pattern_is_rejected() {
+        let toml_content = r#"
+            [tools]
+            site-gen = { version = "0.3", assets = { include = ["templates/[*"] } }
+        "#;
+
+        let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+        assert_matches::assert_matches!(
+            Config::validate_assets(&config_file.tools.unwrap()),
+            Err(crate::error::Error::InvalidAssetPattern { tool, .. }) if tool == "site-gen"
+        );
+    }
+
+    #[test]
+    fn test_tool_wrapper() {
+        let toml_content = r#"
+            [tools]
+            ripgrep = "14.0"
+            my-tool = { version = "1.0", wrapper = "valgrind --leak-check=full" }
+        "#;
+
+        let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+        let tools = config_file.tools.unwrap();
+        Config::validate_wrappers(&tools).unwrap();
+        let config = Config {
+            tools,
+            ..Default::default()
+        };
+
+        assert_eq!(config.tool_wrapper("my-tool"), Some("valgrind --leak-check=full"));
+        assert_eq!(config.tool_wrapper("ripgrep"), None);
+    }
+
+    #[test]
+    fn test_invalid_wrapper_is_rejected() {
+        let toml_content = r#"
+            [tools]
+            my-tool = { version = "1.0", wrapper = "strace -o 'trace.txt" }
+        "#;
+
+        let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+        assert_matches::assert_matches!(
+            Config::validate_wrappers(&config_file.tools.unwrap()),
+            Err(crate::error::Error::InvalidWrapper { .. })
+        );
+    }
+
+    #[test]
+    fn test_tool_channel_refresh() {
+        let toml_content = r#"
+            [tools.my-tool]
+            version = "1.0"
+            channels.nightly = { git = "https://example.com/my-tool.git", refresh = "6h" }
+            channels.next = { git = "https://example.com/my-tool.git", branch = "next" }
+        "#;
+
+        let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+        let tools = config_file.tools.unwrap();
+        Config::validate_channels(&tools).unwrap();
+        let config = Config {
+            tools,
+            ..Config::default()
+        };
+
+        let nightly = CrateSpec::for_channel(&config, "my-tool", "nightly").unwrap();
+        assert_eq!(
+            config.resolve_cache_timeout_for(&nightly),
+            Duration::from_secs(6 * 60 * 60)
+        );
+
+        let next = CrateSpec::for_channel(&config, "my-tool", "next").unwrap();
+        assert_eq!(
+            config.resolve_cache_timeout_for(&next),
+            config.resolve_cache_timeout
+        );
+
+        let stable = CrateSpec::CratesIo {
+            name: "my-tool".to_string(),
+            version: None,
+        };
+        assert_eq!(
+            config.resolve_cache_timeout_for(&stable),
+            config.resolve_cache_timeout
+        );
+    }
+
+    #[test]
+    fn test_invalid_channels_are_rejected() {
+        for toml_content in [
+            r#"tools.my-tool = { version = "1.0", channels.nightly = {} }"#,
+            r#"tools.my-tool = { git = "https://example.com/my-tool.git", channels."2" = {} }"#,
+        ] {
+            let config_file: ConfigFile = toml::from_str(toml_content).unwrap();
+            assert_matches::assert_matches!(
+                Config::validate_channels(&config_file.tools.unwrap()),
+                Err(crate::error::Error::InvalidChannel { .. })
+            );
+        }
+    }
+
+    #[test]
+    fn test_deserialize_aliases() {
+        let toml_content = r#"
+            [aliases]
+            rg = "ripgrep"
+            taplo = "taplo-cli"
+        "#;
+
+        let config: ConfigFile = toml::from_str(toml_content).unwrap();
+        let aliases = config.aliases.unwrap();
+        assert_eq!(aliases.get("rg"), Some(&"ripgrep".to_string()));
+        assert_eq!(aliases.get("taplo"), Some(&"taplo-cli".to_string()));
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let args = CliArgs::parse_from_test_args(["test-crate"]);
+        let config = Config::load(&args).unwrap();
+
+        assert!(!config.offline);
+        assert!(config.locked); // Default is true per issue #55
+        assert_eq!(config.toolchain, None);
+        assert_eq!(config.resolve_cache_timeout, Duration::from_secs(60 * 60));
+    }
+
+    #[test]
+    fn test_cli_overrides() {
+        let args = CliArgs::parse_from_test_args(["+nightly", "--offline", "--locked", "test-crate"]);
+        let config = Config::load(&args).unwrap();
+
+        assert!(config.offline);
+        assert!(config.locked);
+        assert_eq!(config.toolchain, Some("nightly".to_string()));
+    }
+
+    #[test]
+    fn test_frozen_implies_locked_and_offline() {
+        let args = CliArgs::parse_from_test_args(["--frozen", "test-crate"]);
+        let config = Config::load(&args).unwrap();
+
+        assert!(config.offline);
+        assert!(config.locked);
+    }
+
+    #[test]
+    fn test_as_of() {
+        let args = CliArgs::parse_from_test_args(["--as-of", "2024-06-01", "test-crate"]);
+        let config = Config::load(&args).unwrap();
+        assert_eq!(config.as_of, NaiveDate::from_ymd_opt(2024, 6, 1));
+
+        let args = CliArgs::parse_from_test_args(["--as-of", "last summer", "test-crate"]);
+        assert_matches!(
+            Config::load(&args),
+            Err(crate::error::Error::InvalidAsOfDate { .. })
+        );
+    }
+
+    /// Test that `--ignore-pins` drops the versions tools are pinned to, and that
+    /// `--override-pin` pins a tool, by its alias too, in place of the config.
+    #[test]
+    fn test_pins_overridden_on_command_line() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("cgx.toml");
+        std::fs::write(
+            &config_path,
+            r#"
+            [aliases]
+            rg = "ripgrep"
+
+            [tools]
+            just = "1.30"
+            ripgrep = { version = "14", registry = "internal", prebuilt_sha256 = { x86_64-unknown-linux-gnu = "0000000000000000000000000000000000000000000000000000000000000000" } }
+            "#,
+        )
+        .unwrap();
+        let load = |flags: &[&str]| {
+            let mut args = CliArgs::parse_from_test_args(flags.iter().copied().chain(["test-crate"]));
+            args.config_file = Some(config_path.clone());
+            Config::load(&args)
+        };
+
+        let config = load(&["--ignore-pins"]).unwrap();
+        assert!(!config.tools.contains_key("just"));
+        assert_matches!(
+            config.tools.get("ripgrep"),
+            Some(ToolConfig::Detailed {
+                version: None,
+                registry: Some(_),
+                prebuilt_sha256: None,
+                ..
+            })
+        );
+        assert!(!config.setting_sources.contains_key("tools.ripgrep.version"));
+        assert!(config.setting_sources.contains_key("tools.ripgrep.registry"));
+
+        let config = load(&["--override-pin", "rg@13", "--override-pin", "fd-find@10"]).unwrap();
+        assert_matches!(
+            config.tools.get("ripgrep"),
+            Some(ToolConfig::Detailed {
+                version: Some(version),
+                prebuilt_sha256: None,
+                ..
+            }) if version == "13"
+        );
+        assert_eq!(
+            config.tools.get("fd-find"),
+            Some(&ToolConfig::Version("10".to_string()))
+        );
+        assert_eq!(
+            config.tools.get("just"),
+            Some(&ToolConfig::Version("1.30".to_string()))
+        );
+        assert_eq!(
+            config
+                .setting_sources
+                .get("tools.ripgrep.version")
+                .map(String::as_str),
+            Some("--override-pin")
+        );
+
+        assert_matches!(
+            load(&["--override-pin", "ripgrep"]),
+            Err(crate::error::Error::InvalidToolPin { .. })
+        );
+    }
+
+    #[test]
+    fn test_full_config_example() {
+        let toml_content = r#"
+            bin_dir = "~/.local/bin"
+            build_dir = "~/.local/build"
+            cache_dir = "~/.cache/cgx"
+            locked = true
+            log_level = "info"
+            offline = false
+            resolve_cache_timeout = "1h"
+            toolchain = "stable"
+            default_registry = "my-registry"
+
+            [prebuilt_binaries]
+            binary_providers = ["github-releases", "gitlab-releases", "quickinstall"]
+
+            [tools]
+            ripgrep = "*"
+            taplo-cli = { version = "1.11.0", features = ["schema"] }
+
+            [aliases]
+            rg = "ripgrep"
+            taplo = "taplo-cli"
+        "#;
+
+        let config: ConfigFile = toml::from_str(toml_content).unwrap();
+
+        assert_eq!(config.log_level, Some("info".to_string()));
+        assert_eq!(config.toolchain, Some("stable".to_string()));
+        assert_eq!(config.default_registry, Some("my-registry".to_string()));
+        assert_eq!(config.locked, Some(true));
+        assert_eq!(config.offline, Some(false));
         assert_eq!(config.resolve_cache_timeout, Some(Duration::from_secs(60 * 60)));
 
         let prebuilt_binaries = config.prebuilt_binaries.unwrap();
@@ -906,6 +3214,53 @@ mod tests {
         assert_eq!(aliases.len(), 2);
     }
 
+    #[test]
+    fn test_source_fallback_parsing() {
+        let toml_content = r#"
+            source_fallback = ["default-registry", "crates-io", "github:my-org", "gitlab:my-group"]
+        "#;
+
+        let config: ConfigFile = toml::from_str(toml_content).unwrap();
+        assert_eq!(
+            config.source_fallback.unwrap(),
+            vec![
+                SourceFallback::DefaultRegistry,
+                SourceFallback::CratesIo,
+                SourceFallback::Github {
+                    owner: "my-org".to_string()
+                },
+                SourceFallback::Gitlab {
+                    owner: "my-group".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_fallback_round_trip() {
+        for source in [
+            "default-registry",
+            "crates-io",
+            "github:my-org",
+            "gitlab:my-group",
+        ] {
+            assert_eq!(source.parse::<SourceFallback>().unwrap().to_string(), source);
+        }
+    }
+
+    #[test]
+    fn test_source_fallback_invalid() {
+        for source in ["crates", "github", "github:", "github:a/b", "bitbucket:my-org"] {
+            assert!(
+                source.parse::<SourceFallback>().is_err(),
+                "Expected '{source}' to be rejected"
+            );
+        }
+
+        let result: std::result::Result<ConfigFile, _> = toml::from_str(r#"source_fallback = ["nope"]"#);
+        assert!(result.is_err());
+    }
+
     mod prebuilt_validation_tests {
         use super::*;
         use assert_matches::assert_matches;
@@ -919,46 +3274,330 @@ mod tests {
             temp_dir
         }
 
-        #[test]
-        fn test_empty_providers_with_auto_fails() {
-            let toml_content = r#"
-                [prebuilt_binaries]
-                use_prebuilt_binaries = "auto"
-                binary_providers = []
-            "#;
+        #[test]
+        fn test_empty_providers_with_auto_fails() {
+            let toml_content = r#"
+                [prebuilt_binaries]
+                use_prebuilt_binaries = "auto"
+                binary_providers = []
+            "#;
+
+            let temp_dir = create_temp_config(toml_content);
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let result = Config::load_from_dir(temp_dir.path(), &args);
+            assert_matches!(result, Err(crate::error::Error::NoProvidersConfigured));
+        }
+
+        #[test]
+        fn test_empty_providers_with_always_fails() {
+            let toml_content = r#"
+                [prebuilt_binaries]
+                use_prebuilt_binaries = "always"
+                binary_providers = []
+            "#;
+
+            let temp_dir = create_temp_config(toml_content);
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let result = Config::load_from_dir(temp_dir.path(), &args);
+            assert_matches!(result, Err(crate::error::Error::NoProvidersConfigured));
+        }
+
+        #[test]
+        fn test_empty_providers_with_never_ok() {
+            let toml_content = r#"
+                [prebuilt_binaries]
+                use_prebuilt_binaries = "never"
+                binary_providers = []
+            "#;
+
+            let temp_dir = create_temp_config(toml_content);
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let result = Config::load_from_dir(temp_dir.path(), &args);
+            assert!(result.is_ok(), "Empty providers with 'never' mode should succeed");
+        }
+
+        #[test]
+        fn test_require_sbom_with_always_fails() {
+            let toml_content = r#"
+                [prebuilt_binaries]
+                use_prebuilt_binaries = "always"
+            "#;
+
+            let temp_dir = create_temp_config(toml_content);
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["--require-sbom", "test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let result = Config::load_from_dir(temp_dir.path(), &args);
+            assert_matches!(
+                result,
+                Err(crate::error::Error::InvalidConfigValue { field, .. }) if field == "require_sbom"
+            );
+        }
+
+        #[test]
+        fn test_invalid_tool_version_names_file_and_key() {
+            let toml_content = r#"
+                [tools]
+                ripgrep = ">=13, <15"
+                just = "~1.36"
+
+                [tools.taplo-cli]
+                version = ">=0.9 <0.10"
+            "#;
+
+            let temp_dir = create_temp_config(toml_content);
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let result = Config::load_from_dir(temp_dir.path(), &args);
+            assert_matches!(
+                result,
+                Err(crate::error::Error::InvalidToolVersion { ref key, ref config_file, ref hint, .. })
+                    if key == "tools.taplo-cli.version"
+                        && config_file.ends_with("cgx.toml")
+                        && hint == "; did you mean '>=0.9, <0.10'?"
+            );
+        }
+
+        #[test]
+        fn test_vendor_dependencies_from_config_and_cli() {
+            let temp_dir = create_temp_config(
+                r#"
+                vendor_cache_timeout = "7days"
+            "#,
+            );
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert!(!config.vendor_dependencies);
+            assert_eq!(config.vendor_cache_timeout, Duration::from_secs(7 * 24 * 60 * 60));
+
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["--vendor", "test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert!(config.vendor_dependencies);
+        }
+
+        #[test]
+        fn test_background_lowers_unset_build_priorities() {
+            let temp_dir = create_temp_config(
+                r#"
+                [build_priority]
+                nice = 5
+            "#,
+            );
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(
+                config.build_priority,
+                BuildPriority {
+                    nice: Some(5),
+                    io: None
+                }
+            );
+
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["--background", "test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(
+                config.build_priority,
+                BuildPriority {
+                    nice: Some(5),
+                    io: Some(IoPriority::Idle)
+                }
+            );
+        }
+
+        #[test]
+        fn test_build_priority_nice_out_of_range_is_rejected() {
+            let temp_dir = create_temp_config(
+                r#"
+                [build_priority]
+                nice = 20
+            "#,
+            );
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            assert_matches::assert_matches!(
+                Config::load_from_dir(temp_dir.path(), &args),
+                Err(crate::error::Error::InvalidConfigValue { .. })
+            );
+        }
+
+        #[test]
+        fn test_deadline_flag_overrides_configured_deadline() {
+            let temp_dir = create_temp_config(
+                r#"
+                [timeouts]
+                build = "20m"
+                deadline = "1h"
+            "#,
+            );
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(
+                config.timeouts,
+                PhaseTimeouts {
+                    build: Some(Duration::from_secs(20 * 60)),
+                    deadline: Some(Duration::from_secs(60 * 60)),
+                    ..Default::default()
+                }
+            );
+
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["--deadline", "15m", "test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(config.timeouts.build, Some(Duration::from_secs(20 * 60)));
+            assert_eq!(config.timeouts.deadline, Some(Duration::from_secs(15 * 60)));
+
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["--deadline", "soon", "test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            assert_matches::assert_matches!(
+                Config::load_from_dir(temp_dir.path(), &args),
+                Err(crate::error::Error::InvalidDeadline { .. })
+            );
+        }
+
+        #[test]
+        fn test_local_target_dir() {
+            let temp_dir = create_temp_config(
+                r#"
+                local_target_dir = "isolated"
+            "#,
+            );
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(config.local_target_dir, LocalTargetDir::Isolated);
+
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["--local-target-dir", "../shared-target", "test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(
+                config.local_target_dir,
+                LocalTargetDir::Path(PathBuf::from("../shared-target"))
+            );
+            assert_eq!(
+                "workspace".parse::<LocalTargetDir>(),
+                Ok(LocalTargetDir::Workspace)
+            );
+            assert!("".parse::<LocalTargetDir>().is_err());
+        }
+    }
+
+    mod prebuilt_targets_tests {
+        use super::*;
 
-            let temp_dir = create_temp_config(toml_content);
-            let args = CliArgs::parse_from_test_args(["test-crate"]);
-            let result = Config::load_from_dir(temp_dir.path(), &args);
-            assert_matches!(result, Err(crate::error::Error::NoProvidersConfigured));
+        #[test]
+        fn test_target_preference_defaults_to_host_targets() {
+            let config = PrebuiltBinariesConfig::default();
+            let targets = config.target_preference();
+            assert_eq!(targets.first(), crate::platform::host_targets().first());
+            assert!(
+                crate::platform::host_targets()
+                    .iter()
+                    .all(|target| targets.contains(target))
+            );
         }
 
         #[test]
-        fn test_empty_providers_with_always_fails() {
-            let toml_content = r#"
-                [prebuilt_binaries]
-                use_prebuilt_binaries = "always"
-                binary_providers = []
-            "#;
+        fn test_targets_from_config_file() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            std::fs::write(
+                temp_dir.path().join("cgx.toml"),
+                r#"
+                    [prebuilt_binaries]
+                    targets = ["x86_64-unknown-linux-musl", "x86_64-unknown-linux-gnu"]
+                "#,
+            )
+            .unwrap();
 
-            let temp_dir = create_temp_config(toml_content);
-            let args = CliArgs::parse_from_test_args(["test-crate"]);
-            let result = Config::load_from_dir(temp_dir.path(), &args);
-            assert_matches!(result, Err(crate::error::Error::NoProvidersConfigured));
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(
+                config.prebuilt_binaries.target_preference(),
+                vec!["x86_64-unknown-linux-musl", "x86_64-unknown-linux-gnu"]
+            );
         }
 
         #[test]
-        fn test_empty_providers_with_never_ok() {
-            let toml_content = r#"
-                [prebuilt_binaries]
-                use_prebuilt_binaries = "never"
-                binary_providers = []
-            "#;
+        fn test_targets_cli_overrides_config_file() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            std::fs::write(
+                temp_dir.path().join("cgx.toml"),
+                r#"
+                    [prebuilt_binaries]
+                    targets = ["x86_64-unknown-linux-gnu"]
+                "#,
+            )
+            .unwrap();
 
-            let temp_dir = create_temp_config(toml_content);
-            let args = CliArgs::parse_from_test_args(["test-crate"]);
-            let result = Config::load_from_dir(temp_dir.path(), &args);
-            assert!(result.is_ok(), "Empty providers with 'never' mode should succeed");
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args([
+                    "--prebuilt-targets",
+                    "aarch64-unknown-linux-musl,aarch64-unknown-linux-gnu",
+                    "test-crate",
+                ]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(
+                config.prebuilt_binaries.targets,
+                vec!["aarch64-unknown-linux-musl", "aarch64-unknown-linux-gnu"]
+            );
         }
     }
 
@@ -981,7 +3620,12 @@ mod tests {
         fn test_config_hierarchy_project1() {
             let test_case = crate::testdata::ConfigTestCase::hierarchy_project1();
 
-            let args = CliArgs::parse_from_test_args(["test-crate"]);
+            let root = tempfile::tempdir().unwrap();
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                root.path(),
+                test_case.path(),
+            );
             let config = Config::load_from_dir(test_case.path(), &args).unwrap();
 
             assert_eq!(config.resolve_cache_timeout, Duration::from_secs(3 * 60));
@@ -1009,7 +3653,12 @@ mod tests {
         fn test_config_hierarchy_project2() {
             let test_case = crate::testdata::ConfigTestCase::hierarchy_project2();
 
-            let args = CliArgs::parse_from_test_args(["test-crate"]);
+            let root = tempfile::tempdir().unwrap();
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                root.path(),
+                test_case.path(),
+            );
             let config = Config::load_from_dir(test_case.path(), &args).unwrap();
 
             assert_eq!(config.resolve_cache_timeout, Duration::from_secs(5 * 60));
@@ -1036,7 +3685,12 @@ mod tests {
         fn test_config_hierarchy_work() {
             let test_case = crate::testdata::ConfigTestCase::hierarchy_work();
 
-            let args = CliArgs::parse_from_test_args(["test-crate"]);
+            let root = tempfile::tempdir().unwrap();
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                root.path(),
+                test_case.path(),
+            );
             let config = Config::load_from_dir(test_case.path(), &args).unwrap();
 
             assert_eq!(config.resolve_cache_timeout, Duration::from_secs(2 * 60));
@@ -1062,7 +3716,12 @@ mod tests {
         fn test_config_hierarchy_root() {
             let test_case = crate::testdata::ConfigTestCase::hierarchy_root();
 
-            let args = CliArgs::parse_from_test_args(["test-crate"]);
+            let root = tempfile::tempdir().unwrap();
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                root.path(),
+                test_case.path(),
+            );
             let config = Config::load_from_dir(test_case.path(), &args).unwrap();
 
             assert_eq!(config.resolve_cache_timeout, Duration::from_secs(60));
@@ -1091,92 +3750,414 @@ mod tests {
             let mut args = CliArgs::parse_from_test_args(["test-crate"]);
             args.config_file = Some(test_case.path().to_path_buf());
 
-            let config = Config::load(&args).unwrap();
+            let config = Config::load(&args).unwrap();
+
+            assert_eq!(config.resolve_cache_timeout, Duration::from_secs(6 * 60));
+
+            assert!(config.tools.contains_key("project1_tool"));
+            assert_eq!(config.tools.len(), 1);
+
+            assert_eq!(
+                config.aliases.get("dummytool"),
+                Some(&"not_called_cgx_project1".to_string())
+            );
+            assert_eq!(config.aliases.len(), 1);
+        }
+
+        /// Test that detailed tool configurations are preserved during hierarchy merging.
+        ///
+        /// Verifies that tools specified with detailed configs (version, features, etc.) maintain
+        /// their structure when merged across the hierarchy. The taplo-cli tool from root should
+        /// retain its version="1.11.0" and features=["schema"] specification.
+        #[test]
+        fn test_tools_detailed_config_preserved() {
+            let test_case = crate::testdata::ConfigTestCase::hierarchy_root();
+
+            let root = tempfile::tempdir().unwrap();
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                root.path(),
+                test_case.path(),
+            );
+            let config = Config::load_from_dir(test_case.path(), &args).unwrap();
+
+            let taplo_tool = config.tools.get("taplo-cli").unwrap();
+            assert_matches!(
+                taplo_tool,
+                ToolConfig::Detailed {
+                    version: Some(v),
+                    features: Some(f),
+                    ..
+                } if v == "1.11.0" && f == &vec!["schema".to_string()]
+            );
+        }
+
+        /// Test that CLI arguments have the highest precedence over config files.
+        ///
+        /// Command-line flags should override any values set in config files, regardless of
+        /// where those config files appear in the hierarchy. This verifies that --offline,
+        /// --locked, and +toolchain flags take precedence over the merged config.
+        #[test]
+        fn test_cli_args_override_config_files() {
+            let test_case = crate::testdata::ConfigTestCase::hierarchy_project1();
+
+            let root = tempfile::tempdir().unwrap();
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["+stable", "--offline", "--locked", "test-crate"]),
+                root.path(),
+                test_case.path(),
+            );
+            let config = Config::load_from_dir(test_case.path(), &args).unwrap();
+
+            assert!(config.offline);
+            assert!(config.locked);
+            assert_eq!(config.toolchain, Some("stable".to_string()));
+        }
+
+        #[test]
+        fn test_refresh_layers_from_cli() {
+            let root = tempfile::tempdir().unwrap();
+            let load = |args: &[&str]| {
+                let args = with_isolated_global_config(CliArgs::parse_from_test_args(args), root.path());
+                Config::load_from_dir(root.path(), &args).unwrap().refresh
+            };
+
+            assert_eq!(load(&["test-crate"]), Refresh::default());
+            assert_eq!(load(&["--refresh", "test-crate"]), Refresh::ALL);
+            assert_eq!(
+                load(&["--refresh=resolve,source", "test-crate"]),
+                Refresh {
+                    resolve: true,
+                    source: true,
+                    binary: false,
+                }
+            );
+            assert_eq!(load(&["--refresh=binary,all", "test-crate"]), Refresh::ALL);
+        }
+
+        /// Test that --config-file reads only the specified file.
+        ///
+        /// When --config-file is specified, only that single config file should be loaded,
+        /// bypassing all config discovery (system, user, and hierarchy configs).
+        #[test]
+        fn test_config_file_reads_only_specified_file() {
+            // The hierarchy has configs with resolve_cache_timeout set to various values:
+            // root=1m, work=2m, project1=3m
+            let hierarchy_dir = crate::testdata::ConfigTestCase::hierarchy_project1();
+
+            // The explicit config has a different timeout (6m)
+            let explicit_config = crate::testdata::ConfigTestCase::explicit_non_standard_name();
+
+            // Load config from project1 directory but with --config-file pointing to explicit config
+            let mut args = CliArgs::parse_from_test_args(["test-crate"]);
+            args.config_file = Some(explicit_config.path().to_path_buf());
+
+            let config = Config::load_from_dir(hierarchy_dir.path(), &args).unwrap();
+
+            // Should have the explicit config's timeout (6m), not any from the hierarchy (1m/2m/3m)
+            assert_eq!(config.resolve_cache_timeout, Duration::from_secs(6 * 60));
+
+            // Should have only the tool from explicit config, not from hierarchy
+            assert!(config.tools.contains_key("project1_tool"));
+            assert_eq!(config.tools.len(), 1);
+
+            // Should have only the alias from explicit config
+            assert_eq!(
+                config.aliases.get("dummytool"),
+                Some(&"not_called_cgx_project1".to_string())
+            );
+            assert_eq!(config.aliases.len(), 1);
+        }
+    }
+
+    mod trust_tests {
+        use super::*;
+        use assert_matches::assert_matches;
+        use std::fs;
+
+        const PROJECT_CONFIG: &str = r#"
+            resolve_cache_timeout = "7m"
+            toolchain = "nightly"
+            require_sbom = true
+            release_notes = true
+            collect_metrics = true
+
+            [tools]
+            ripgrep = "=13.0.0"
+
+            [aliases]
+            rg = "ripgrep"
+        "#;
+
+        /// Create a project dir with [`PROJECT_CONFIG`] in it, plus isolated global config dirs
+        /// whose user config file has the given contents.
+        fn setup(user_config: &str) -> (tempfile::TempDir, PathBuf, CliArgs) {
+            let temp_dir = tempfile::tempdir().unwrap();
+
+            let project = temp_dir.path().join("project");
+            fs::create_dir_all(&project).unwrap();
+            fs::write(project.join("cgx.toml"), PROJECT_CONFIG).unwrap();
+
+            let user_dir = temp_dir.path().join("user");
+            fs::create_dir_all(&user_dir).unwrap();
+            fs::write(user_dir.join("cgx.toml"), user_config).unwrap();
+
+            let args =
+                with_isolated_global_config(CliArgs::parse_from_test_args(["test-crate"]), temp_dir.path());
+            (temp_dir, project, args)
+        }
+
+        #[test]
+        fn test_untrusted_config_is_restricted_by_default() {
+            let (_temp_dir, project, args) = setup("");
+            let config = Config::load_from_dir(&project, &args).unwrap();
+
+            assert_eq!(config.untrusted_configs, UntrustedConfigPolicy::Restricted);
+            assert_eq!(config.resolve_cache_timeout, Duration::from_secs(7 * 60));
+            assert!(config.require_sbom);
+            assert!(config.release_notes);
+            assert!(!config.collect_metrics);
+            assert_eq!(config.toolchain, None);
+            assert!(config.tools.is_empty());
+            assert!(config.aliases.is_empty());
+        }
+
+        #[test]
+        fn test_trusted_config_is_honored() {
+            let (temp_dir, project, args) = setup("");
+            TrustStore::load(&temp_dir.path().join("user"))
+                .unwrap()
+                .trust(&project)
+                .unwrap();
+
+            let config = Config::load_from_dir(&project, &args).unwrap();
+
+            assert_eq!(config.toolchain, Some("nightly".to_string()));
+            assert!(config.collect_metrics);
+            assert!(config.tools.contains_key("ripgrep"));
+            assert_eq!(config.aliases.get("rg"), Some(&"ripgrep".to_string()));
+        }
+
+        #[test]
+        fn test_trust_does_not_extend_to_subdirectories() {
+            let (temp_dir, project, args) = setup("");
+            TrustStore::load(&temp_dir.path().join("user"))
+                .unwrap()
+                .trust(temp_dir.path())
+                .unwrap();
+
+            let config = Config::load_from_dir(&project, &args).unwrap();
+            assert!(config.tools.is_empty());
+        }
+
+        #[test]
+        fn test_ignore_policy_skips_untrusted_config() {
+            let (_temp_dir, project, args) = setup(r#"untrusted_configs = "ignore""#);
+            let config = Config::load_from_dir(&project, &args).unwrap();
+
+            assert_eq!(config.resolve_cache_timeout, DEFAULT_RESOLVE_CACHE_TIMEOUT);
+            assert!(config.tools.is_empty());
+        }
+
+        #[test]
+        fn test_allow_policy_honors_untrusted_config() {
+            let (_temp_dir, project, args) = setup(r#"untrusted_configs = "allow""#);
+            let config = Config::load_from_dir(&project, &args).unwrap();
+
+            assert_eq!(config.toolchain, Some("nightly".to_string()));
+            assert!(config.tools.contains_key("ripgrep"));
+        }
+
+        #[test]
+        fn test_untrusted_config_cannot_loosen_policy() {
+            let (_temp_dir, project, args) = setup("");
+            fs::write(
+                project.join("cgx.toml"),
+                format!("untrusted_configs = \"allow\"\n{PROJECT_CONFIG}"),
+            )
+            .unwrap();
+
+            let config = Config::load_from_dir(&project, &args).unwrap();
+
+            assert_eq!(config.untrusted_configs, UntrustedConfigPolicy::Restricted);
+            assert!(config.tools.is_empty());
+        }
+
+        #[test]
+        fn test_settings_in_dir_name_their_source() {
+            let (temp_dir, project, args) = setup("");
+            let project_config = project.join("cgx.toml").display().to_string();
+            let setting = |settings: &[ConfigSetting], key: &str| {
+                settings
+                    .iter()
+                    .find(|setting| setting.key == key)
+                    .map(|setting| (setting.value.clone(), setting.source.clone()))
+            };
+
+            let settings = Config::settings_in_dir(&project, &args).unwrap();
+            assert_eq!(
+                setting(&settings, "locked"),
+                Some(("true".to_string(), "defaults".to_string()))
+            );
+            assert_eq!(
+                setting(&settings, "resolve_cache_timeout"),
+                Some(("\"7m\"".to_string(), project_config.clone()))
+            );
+            assert_eq!(setting(&settings, "tools.ripgrep"), None);
+
+            TrustStore::load(&temp_dir.path().join("user"))
+                .unwrap()
+                .trust(&project)
+                .unwrap();
+
+            let settings = Config::settings_in_dir(&project, &args).unwrap();
+            assert_eq!(
+                setting(&settings, "tools.ripgrep"),
+                Some(("\"=13.0.0\"".to_string(), project_config))
+            );
+        }
+
+        #[test]
+        fn test_invalid_untrusted_config_still_fails() {
+            let (_temp_dir, project, args) = setup("");
+            fs::write(project.join("cgx.toml"), "no_such_setting = 1").unwrap();
+
+            let result = Config::load_from_dir(&project, &args);
+            assert_matches!(
+                result,
+                Err(crate::error::Error::UnknownConfigKey { key, file, .. })
+                    if key == "no_such_setting" && file == project.join("cgx.toml").display().to_string()
+            );
+        }
+    }
+
+    mod include_tests {
+        use super::*;
+        use assert_matches::assert_matches;
+        use std::fs;
+
+        /// Create a project dir whose `cgx.toml` has the given contents, next to a `shared` dir for
+        /// the files it includes, with isolated global config dirs and the project trusted.
+        fn setup(project_config: &str) -> (tempfile::TempDir, PathBuf, CliArgs) {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let root = fs::canonicalize(temp_dir.path()).unwrap();
+
+            let project = root.join("project");
+            fs::create_dir_all(&project).unwrap();
+            fs::create_dir_all(root.join("shared")).unwrap();
+            fs::write(project.join("cgx.toml"), project_config).unwrap();
+
+            let args = with_trusted_hierarchy(CliArgs::parse_from_test_args(["test-crate"]), &root, &project);
+            (temp_dir, project, args)
+        }
+
+        /// Test that included files are merged beneath the file that includes them, with paths
+        /// relative to the including file, including those in nested includes.
+        #[test]
+        fn test_included_files_are_merged_beneath_including_file() {
+            let (_temp_dir, project, args) = setup(
+                r#"
+                include = ["../shared/tools.toml"]
+                resolve_cache_timeout = "4m"
+
+                [aliases]
+                fd = "fd-find"
+                "#,
+            );
+            let shared = project.parent().unwrap().join("shared");
+            fs::write(
+                shared.join("tools.toml"),
+                r#"
+                include = ["aliases.toml"]
+                resolve_cache_timeout = "2m"
 
-            assert_eq!(config.resolve_cache_timeout, Duration::from_secs(6 * 60));
+                [tools]
+                ripgrep = "14"
+                "#,
+            )
+            .unwrap();
+            fs::write(
+                shared.join("aliases.toml"),
+                "[aliases]\nrg = \"ripgrep\"\nfd = \"fd\"",
+            )
+            .unwrap();
 
-            assert!(config.tools.contains_key("project1_tool"));
-            assert_eq!(config.tools.len(), 1);
+            let config = Config::load_from_dir(&project, &args).unwrap();
 
+            assert_eq!(config.resolve_cache_timeout, Duration::from_secs(4 * 60));
             assert_eq!(
-                config.aliases.get("dummytool"),
-                Some(&"not_called_cgx_project1".to_string())
+                config.tools.get("ripgrep"),
+                Some(&ToolConfig::Version("14".to_string()))
+            );
+            assert_eq!(config.aliases.get("rg"), Some(&"ripgrep".to_string()));
+            assert_eq!(config.aliases.get("fd"), Some(&"fd-find".to_string()));
+            assert_eq!(
+                config.setting_sources.get("tools.ripgrep"),
+                Some(&shared.join("tools.toml").display().to_string())
             );
-            assert_eq!(config.aliases.len(), 1);
         }
 
-        /// Test that detailed tool configurations are preserved during hierarchy merging.
-        ///
-        /// Verifies that tools specified with detailed configs (version, features, etc.) maintain
-        /// their structure when merged across the hierarchy. The taplo-cli tool from root should
-        /// retain its version="1.11.0" and features=["schema"] specification.
+        /// Test that a file included twice, through different files, is only merged once, so that
+        /// it can't override the file that included it the first time.
         #[test]
-        fn test_tools_detailed_config_preserved() {
-            let test_case = crate::testdata::ConfigTestCase::hierarchy_root();
+        fn test_file_included_twice_is_merged_once() {
+            let (_temp_dir, project, args) =
+                setup(r#"include = ["../shared/base.toml", "../shared/team.toml"]"#);
+            let shared = project.parent().unwrap().join("shared");
+            fs::write(shared.join("base.toml"), "resolve_cache_timeout = \"2m\"").unwrap();
+            fs::write(
+                shared.join("team.toml"),
+                "include = [\"base.toml\"]\nresolve_cache_timeout = \"3m\"",
+            )
+            .unwrap();
 
-            let args = CliArgs::parse_from_test_args(["test-crate"]);
-            let config = Config::load_from_dir(test_case.path(), &args).unwrap();
+            let config = Config::load_from_dir(&project, &args).unwrap();
+            assert_eq!(config.resolve_cache_timeout, Duration::from_secs(3 * 60));
+        }
 
-            let taplo_tool = config.tools.get("taplo-cli").unwrap();
+        #[test]
+        fn test_include_cycle_is_an_error() {
+            let (_temp_dir, project, args) = setup(r#"include = ["../shared/a.toml"]"#);
+            let shared = project.parent().unwrap().join("shared");
+            fs::write(shared.join("a.toml"), r#"include = ["b.toml"]"#).unwrap();
+            fs::write(shared.join("b.toml"), r#"include = ["a.toml"]"#).unwrap();
+
+            let result = Config::load_from_dir(&project, &args);
             assert_matches!(
-                taplo_tool,
-                ToolConfig::Detailed {
-                    version: Some(v),
-                    features: Some(f),
-                    ..
-                } if v == "1.11.0" && f == &vec!["schema".to_string()]
+                result,
+                Err(crate::error::Error::ConfigIncludeCycle { cycle })
+                    if cycle == [shared.join("a.toml"), shared.join("b.toml"), shared.join("a.toml")]
             );
         }
 
-        /// Test that CLI arguments have the highest precedence over config files.
-        ///
-        /// Command-line flags should override any values set in config files, regardless of
-        /// where those config files appear in the hierarchy. This verifies that --offline,
-        /// --locked, and +toolchain flags take precedence over the merged config.
         #[test]
-        fn test_cli_args_override_config_files() {
-            let test_case = crate::testdata::ConfigTestCase::hierarchy_project1();
-
-            let args = CliArgs::parse_from_test_args(["+stable", "--offline", "--locked", "test-crate"]);
-            let config = Config::load_from_dir(test_case.path(), &args).unwrap();
+        fn test_missing_include_is_an_error() {
+            let (_temp_dir, project, args) = setup(r#"include = ["../shared/missing.toml"]"#);
 
-            assert!(config.offline);
-            assert!(config.locked);
-            assert_eq!(config.toolchain, Some("stable".to_string()));
+            let result = Config::load_from_dir(&project, &args);
+            assert_matches!(
+                result,
+                Err(crate::error::Error::ConfigIncludeNotFound { included_from, .. })
+                    if included_from == project.join("cgx.toml")
+            );
         }
 
-        /// Test that --config-file reads only the specified file.
-        ///
-        /// When --config-file is specified, only that single config file should be loaded,
-        /// bypassing all config discovery (system, user, and hierarchy configs).
+        /// Test that an untrusted config file's includes aren't followed.
         #[test]
-        fn test_config_file_reads_only_specified_file() {
-            // The hierarchy has configs with resolve_cache_timeout set to various values:
-            // root=1m, work=2m, project1=3m
-            let hierarchy_dir = crate::testdata::ConfigTestCase::hierarchy_project1();
-
-            // The explicit config has a different timeout (6m)
-            let explicit_config = crate::testdata::ConfigTestCase::explicit_non_standard_name();
-
-            // Load config from project1 directory but with --config-file pointing to explicit config
-            let mut args = CliArgs::parse_from_test_args(["test-crate"]);
-            args.config_file = Some(explicit_config.path().to_path_buf());
-
-            let config = Config::load_from_dir(hierarchy_dir.path(), &args).unwrap();
-
-            // Should have the explicit config's timeout (6m), not any from the hierarchy (1m/2m/3m)
-            assert_eq!(config.resolve_cache_timeout, Duration::from_secs(6 * 60));
-
-            // Should have only the tool from explicit config, not from hierarchy
-            assert!(config.tools.contains_key("project1_tool"));
-            assert_eq!(config.tools.len(), 1);
-
-            // Should have only the alias from explicit config
-            assert_eq!(
-                config.aliases.get("dummytool"),
-                Some(&"not_called_cgx_project1".to_string())
+        fn test_untrusted_config_includes_are_ignored() {
+            let (temp_dir, project, _) = setup(r#"include = ["../shared/tools.toml"]"#);
+            fs::write(
+                project.parent().unwrap().join("shared").join("tools.toml"),
+                "[tools]\nripgrep = \"14\"",
+            )
+            .unwrap();
+            let args = with_isolated_global_config(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                &temp_dir.path().join("untrusted"),
             );
-            assert_eq!(config.aliases.len(), 1);
+
+            let config = Config::load_from_dir(&project, &args).unwrap();
+            assert!(config.tools.is_empty());
         }
     }
 
@@ -1251,13 +4232,12 @@ mod tests {
             // Should contain ONLY the explicit config file (no system, user, or hierarchy configs)
             // This will FAIL if the bug exists, showing [user_config, explicit_config]
             assert_eq!(
-                discovered.len(),
-                1,
-                "Expected only 1 config file, got {}: {:?}",
-                discovered.len(),
+                discovered.global,
+                vec![explicit_config],
+                "Expected only the explicit config file, got {:?}",
                 discovered
             );
-            assert_eq!(discovered[0], explicit_config);
+            assert!(discovered.hierarchy.is_empty());
         }
 
         /// Test that hierarchy configs are discovered when --config-file is not set.
@@ -1283,11 +4263,11 @@ mod tests {
             // Should contain both hierarchy configs (and possibly system/user if they exist)
             // We check that at least our two configs are present
             assert!(
-                discovered.contains(&root_config),
+                discovered.hierarchy.contains(&root_config),
                 "Root config should be discovered"
             );
             assert!(
-                discovered.contains(&sub_config),
+                discovered.hierarchy.contains(&sub_config),
                 "Sub config should be discovered"
             );
         }
@@ -1361,6 +4341,37 @@ mod tests {
         mod app_dir_tests {
             use super::*;
 
+            #[test]
+            fn test_trusted_project_env_is_app_dir() {
+                let temp_dir = tempfile::tempdir().unwrap();
+                let project = temp_dir.path().join("project");
+                let cwd = project.join("src");
+                fs::create_dir_all(&cwd).unwrap();
+                fs::create_dir_all(project.join(project_env::ENV_DIR_NAME)).unwrap();
+
+                let args = with_isolated_global_config(
+                    CliArgs::parse_from_test_args(["test-crate"]),
+                    temp_dir.path(),
+                );
+                let config = Config::load_from_dir(&cwd, &args).unwrap();
+                assert!(!config.bin_dir.starts_with(&project));
+
+                let mut trust_store = TrustStore::load(&temp_dir.path().join("user")).unwrap();
+                let env_dir = project_env::init(&project, &mut trust_store).unwrap();
+
+                let config = Config::load_from_dir(&cwd, &args).unwrap();
+                assert_eq!(config.cache_dir, env_dir.join("cache"));
+                assert_eq!(config.bin_dir, env_dir.join("bins"));
+                assert_eq!(config.build_dir, env_dir.join("build"));
+
+                // An explicit app dir still wins
+                let mut args = args;
+                let app_dir = temp_dir.path().join("app");
+                args.app_dir = Some(app_dir.clone());
+                let config = Config::load_from_dir(&cwd, &args).unwrap();
+                assert_eq!(config.bin_dir, app_dir.join("bins"));
+            }
+
             #[test]
             fn test_app_dir_config_location() {
                 let temp_dir = tempfile::tempdir().unwrap();
@@ -1577,6 +4588,10 @@ mod tests {
                 fs::create_dir_all(&sub).unwrap();
                 fs::write(sub.join("cgx.toml"), "[tools]\nsub_tool = \"1\"").unwrap();
 
+                let mut trust_store = TrustStore::load(&app_dir.join("config")).unwrap();
+                trust_store.trust(&root).unwrap();
+                trust_store.trust(&sub).unwrap();
+
                 let mut args = CliArgs::parse_from_test_args(["test-crate"]);
                 args.app_dir = Some(app_dir);
 
@@ -1653,6 +4668,96 @@ mod tests {
         }
     }
 
+    mod endpoints_config_tests {
+        use super::*;
+
+        #[test]
+        fn test_endpoints_default_to_public_services() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let args =
+                with_isolated_global_config(CliArgs::parse_from_test_args(["test-crate"]), temp_dir.path());
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(config.endpoints, EndpointsConfig::default());
+            assert_eq!(config.endpoints.crates_io_index, None);
+            assert_eq!(config.endpoints.github_api.as_str(), "https://api.github.com/");
+        }
+
+        #[test]
+        fn test_partial_endpoints_from_config_file() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            std::fs::write(
+                temp_dir.path().join("cgx.toml"),
+                r#"
+                    [endpoints]
+                    crates_io_index = "sparse+https://mirror.example.com/index/"
+                    github_api = "https://github-proxy.example.com/api"
+                "#,
+            )
+            .unwrap();
+
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(
+                config.endpoints.crates_io_index.unwrap().as_str(),
+                "sparse+https://mirror.example.com/index/"
+            );
+            assert_eq!(
+                config.endpoints.github_api.as_str(),
+                "https://github-proxy.example.com/api"
+            );
+            assert_eq!(config.endpoints.gitlab, EndpointsConfig::default().gitlab);
+        }
+
+        #[test]
+        fn test_endpoints_unknown_field_rejected() {
+            let toml_content = r#"
+                [endpoints]
+                bitbucket = "https://bitbucket.org"
+            "#;
+
+            assert!(toml::from_str::<ConfigFile>(toml_content).is_err());
+        }
+
+        #[test]
+        fn test_quickinstall_indexes_from_one_url_or_a_list() {
+            let config: ConfigFile = toml::from_str(
+                r#"
+                [endpoints]
+                quickinstall = "https://artifacts.example.com/quickinstall"
+            "#,
+            )
+            .unwrap();
+            assert_eq!(
+                config.endpoints.unwrap().quickinstall,
+                [QuickinstallIndex::new(
+                    Url::parse("https://artifacts.example.com/quickinstall").unwrap()
+                )]
+            );
+
+            let config: ConfigFile = toml::from_str(
+                r#"
+                [endpoints]
+                quickinstall = [
+                    { url = "https://artifacts.example.com/quickinstall", stats = "https://artifacts.example.com/stats" },
+                    "https://github.com/cargo-bins/cargo-quickinstall/releases/download",
+                ]
+            "#,
+            )
+            .unwrap();
+            let indexes = config.endpoints.unwrap().quickinstall;
+            assert_eq!(indexes.len(), 2);
+            assert_eq!(
+                indexes[0].stats.as_ref().map(Url::as_str),
+                Some("https://artifacts.example.com/stats")
+            );
+            assert_eq!(indexes[1], EndpointsConfig::default().quickinstall[0]);
+        }
+    }
+
     mod http_config_deserialization_tests {
         use super::*;
 
@@ -1767,9 +4872,8 @@ mod tests {
                 proxy = "http://proxy:3128"
             "#;
             let temp_dir = create_temp_config(toml_content);
-            let mut args = CliArgs::parse_from_test_args(["test-crate"]);
-            args.system_config_dir = Some(temp_dir.path().join("system"));
-            args.user_config_dir = Some(temp_dir.path().join("user"));
+            let args = CliArgs::parse_from_test_args(["test-crate"]);
+            let args = with_trusted_hierarchy(args, temp_dir.path(), temp_dir.path());
 
             let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
             assert_eq!(config.http.timeout, Duration::from_secs(120));
@@ -1786,7 +4890,7 @@ mod tests {
                 proxy = "http://proxy:3128"
             "#;
             let temp_dir = create_temp_config(toml_content);
-            let mut args = CliArgs::parse_from_test_args([
+            let args = CliArgs::parse_from_test_args([
                 "--http-timeout",
                 "10s",
                 "--http-retries",
@@ -1795,8 +4899,7 @@ mod tests {
                 "socks5://other:1080",
                 "test-crate",
             ]);
-            args.system_config_dir = Some(temp_dir.path().join("system"));
-            args.user_config_dir = Some(temp_dir.path().join("user"));
+            let args = with_trusted_hierarchy(args, temp_dir.path(), temp_dir.path());
 
             let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
             assert_eq!(config.http.timeout, Duration::from_secs(10));
@@ -1813,9 +4916,8 @@ mod tests {
                 proxy = "http://proxy:3128"
             "#;
             let temp_dir = create_temp_config(toml_content);
-            let mut args = CliArgs::parse_from_test_args(["--http-timeout", "10s", "test-crate"]);
-            args.system_config_dir = Some(temp_dir.path().join("system"));
-            args.user_config_dir = Some(temp_dir.path().join("user"));
+            let args = CliArgs::parse_from_test_args(["--http-timeout", "10s", "test-crate"]);
+            let args = with_trusted_hierarchy(args, temp_dir.path(), temp_dir.path());
 
             let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
             assert_eq!(config.http.timeout, Duration::from_secs(10));
@@ -1853,9 +4955,8 @@ mod tests {
                 backoff_max = "60s"
             "#;
             let temp_dir = create_temp_config(toml_content);
-            let mut args = CliArgs::parse_from_test_args(["test-crate"]);
-            args.system_config_dir = Some(temp_dir.path().join("system"));
-            args.user_config_dir = Some(temp_dir.path().join("user"));
+            let args = CliArgs::parse_from_test_args(["test-crate"]);
+            let args = with_trusted_hierarchy(args, temp_dir.path(), temp_dir.path());
 
             let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
             assert_eq!(config.http.backoff_base, Duration::from_secs(2));
@@ -1869,9 +4970,8 @@ mod tests {
                 timeout = "45s"
             "#;
             let temp_dir = create_temp_config(toml_content);
-            let mut args = CliArgs::parse_from_test_args(["test-crate"]);
-            args.system_config_dir = Some(temp_dir.path().join("system"));
-            args.user_config_dir = Some(temp_dir.path().join("user"));
+            let args = CliArgs::parse_from_test_args(["test-crate"]);
+            let args = with_trusted_hierarchy(args, temp_dir.path(), temp_dir.path());
 
             let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
             assert_eq!(config.http.backoff_base, Duration::from_millis(500));
@@ -1907,8 +5007,11 @@ mod tests {
             )
             .unwrap();
 
-            let args =
-                with_isolated_global_config(CliArgs::parse_from_test_args(["test-crate"]), temp_dir.path());
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                &child,
+            );
 
             let config = Config::load_from_dir(&child, &args).unwrap();
             // Timeout comes from the child, overriding the parent, but since retries wasn't
@@ -1947,8 +5050,11 @@ mod tests {
             )
             .unwrap();
 
-            let args =
-                with_isolated_global_config(CliArgs::parse_from_test_args(["test-crate"]), temp_dir.path());
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                &child,
+            );
 
             let config = Config::load_from_dir(&child, &args).unwrap();
             assert_eq!(config.http.timeout, Duration::from_secs(45));
@@ -2043,8 +5149,11 @@ mod tests {
                 proxy = "http://config-proxy:8080"
             "#;
             let temp_dir = create_temp_config(toml_content);
-            let args =
-                with_isolated_global_config(CliArgs::parse_from_test_args(["test-crate"]), temp_dir.path());
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
 
             let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
             assert_eq!(config.http.timeout, Duration::from_secs(120));
@@ -2052,6 +5161,57 @@ mod tests {
             assert_eq!(config.http.proxy, Some("http://config-proxy:8080".to_string()));
         }
 
+        #[sealed_test(env = [("NETRC", "/env/netrc")])]
+        /// Verifies the `NETRC` env variable picks the netrc file when the config file doesn't.
+        fn test_env_netrc_used_when_not_configured() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let args =
+                with_isolated_global_config(CliArgs::parse_from_test_args(["test-crate"]), temp_dir.path());
+
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(config.http.netrc, Some(PathBuf::from("/env/netrc")));
+            assert!(!config.http.proxy_keychain);
+        }
+
+        #[sealed_test(env = [("NETRC", "/env/netrc")])]
+        /// Verifies the config file's `netrc` and `proxy_keychain` take precedence over `NETRC`.
+        fn test_config_file_netrc_overrides_env() {
+            let toml_content = r#"
+                [http]
+                netrc = "/config/netrc"
+                proxy_keychain = true
+            "#;
+            let temp_dir = create_temp_config(toml_content);
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(config.http.netrc, Some(PathBuf::from("/config/netrc")));
+            assert!(config.http.proxy_keychain);
+        }
+
+        #[sealed_test(env = [("CARGO_HTTP_CAINFO", "/env/ca.pem")])]
+        /// Verifies `CARGO_HTTP_CAINFO` is the CA bundle unless the config file names one.
+        fn test_ca_bundle_from_config_or_env() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let args =
+                with_isolated_global_config(CliArgs::parse_from_test_args(["test-crate"]), temp_dir.path());
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(config.http.ca_bundle, Some(PathBuf::from("/env/ca.pem")));
+
+            let temp_dir = create_temp_config("[http]\nca_bundle = \"/config/ca.pem\"\n");
+            let args = with_trusted_hierarchy(
+                CliArgs::parse_from_test_args(["test-crate"]),
+                temp_dir.path(),
+                temp_dir.path(),
+            );
+            let config = Config::load_from_dir(temp_dir.path(), &args).unwrap();
+            assert_eq!(config.http.ca_bundle, Some(PathBuf::from("/config/ca.pem")));
+        }
+
         #[sealed_test(env = [("CARGO_HTTP_TIMEOUT", "not-a-number")])]
         /// Verifies invalid `CARGO_HTTP_TIMEOUT` falls back to the built-in default timeout.
         fn test_invalid_env_timeout_falls_back_to_default() {
@@ -2078,6 +5238,33 @@ mod tests {
     mod build_http_config_direct_tests {
         use super::*;
 
+        #[test]
+        fn test_proxy_rules() {
+            let config_file = HttpConfigFile {
+                no_proxy: Some(".corp.example.com,10.0.0.0/8".to_string()),
+                proxies: Some(BTreeMap::from([
+                    ("github.com".to_string(), "http://egress:3128".to_string()),
+                    ("registry.corp.example.com".to_string(), "direct".to_string()),
+                ])),
+                ..Default::default()
+            };
+            let args = CliArgs::parse_from_test_args(["test-crate"]);
+            let http = Config::build_http_config(&config_file, &args).unwrap();
+            assert_eq!(http.no_proxy.as_deref(), Some(".corp.example.com,10.0.0.0/8"));
+            assert_eq!(http.proxies.len(), 2);
+
+            for (host, proxy) in [("10.0.0.0/99", "direct"), ("github.com", "egress")] {
+                let config_file = HttpConfigFile {
+                    proxies: Some(BTreeMap::from([(host.to_string(), proxy.to_string())])),
+                    ..Default::default()
+                };
+                assert!(matches!(
+                    Config::build_http_config(&config_file, &args),
+                    Err(crate::error::Error::InvalidProxyRule { .. })
+                ));
+            }
+        }
+
         #[test]
         fn test_config_file_timeout_overrides_defaults() {
             let config_file = HttpConfigFile {
@@ -2169,6 +5356,29 @@ mod tests {
             assert_eq!(http.backoff_base, Duration::from_secs(2));
             assert_eq!(http.backoff_max, Duration::from_secs(60));
         }
+
+        #[test]
+        fn test_max_download_size() {
+            let config_file = HttpConfigFile {
+                max_download_size: Some("200M".to_string()),
+                ..Default::default()
+            };
+            let args = CliArgs::parse_from_test_args(["test-crate"]);
+            let http = Config::build_http_config(&HttpConfigFile::default(), &args).unwrap();
+            assert_eq!(http.max_download_size, DEFAULT_MAX_DOWNLOAD_SIZE);
+            let http = Config::build_http_config(&config_file, &args).unwrap();
+            assert_eq!(http.max_download_size, 200 << 20);
+
+            let args = CliArgs::parse_from_test_args(["--max-download-size", "3G", "test-crate"]);
+            let http = Config::build_http_config(&config_file, &args).unwrap();
+            assert_eq!(http.max_download_size, 3 << 30);
+
+            let args = CliArgs::parse_from_test_args(["--max-download-size", "lots", "test-crate"]);
+            assert!(matches!(
+                Config::build_http_config(&config_file, &args),
+                Err(crate::error::Error::InvalidMaxDownloadSize { .. })
+            ));
+        }
     }
 
     mod error_tests {
@@ -2194,7 +5404,51 @@ mod tests {
             args.config_file = Some(test_case.path().to_path_buf());
 
             let result = Config::load(&args);
-            assert_matches!(result, Err(crate::error::Error::ConfigExtract { .. }));
+            assert_matches!(
+                result,
+                Err(crate::error::Error::UnknownConfigKey { key, file, hint })
+                    if key == "answer" && file == test_case.path().display().to_string() && hint.is_empty()
+            );
+        }
+
+        /// Test that an unknown setting that's a typo of a known one, including in a table, is
+        /// reported with the setting it's closest to.
+        #[test]
+        fn test_unknown_config_key_suggests_nearest_setting() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config_path = temp_dir.path().join("cgx.toml");
+            let load = |contents: &str| {
+                std::fs::write(&config_path, contents).unwrap();
+                let mut args = CliArgs::parse_from_test_args(["test-crate"]);
+                args.config_file = Some(config_path.clone());
+                Config::load(&args)
+            };
+
+            assert_matches!(
+                load("[tool]\nripgrep = \"14\""),
+                Err(crate::error::Error::UnknownConfigKey { key, hint, .. })
+                    if key == "tool" && hint == "; did you mean 'tools'?"
+            );
+            assert_matches!(
+                load("[http]\ntimout = \"5s\""),
+                Err(crate::error::Error::UnknownConfigKey { key, hint, .. })
+                    if key == "http.timout" && hint == "; did you mean 'timeout'?"
+            );
+        }
+
+        #[test]
+        fn test_nearest_setting() {
+            let settings = ["tools", "toolchain", "offline", "resolve_cache_timeout"];
+
+            assert_eq!(nearest_setting("tool", &settings), Some("tools"));
+            assert_eq!(nearest_setting("toolchian", &settings), Some("toolchain"));
+            assert_eq!(nearest_setting("Offline", &settings), Some("offline"));
+            assert_eq!(
+                nearest_setting("resolve-cache-timeout", &settings),
+                Some("resolve_cache_timeout")
+            );
+            assert_eq!(nearest_setting("answer", &settings), None);
+            assert_eq!(nearest_setting("to", &settings), None);
         }
 
         #[test]
@@ -0,0 +1,120 @@
+//! Editing config files in place, for `--pin`, `--alias` and the like.
+//!
+//! Files are edited with `toml_edit`, so everything in them that isn't changed, comments and
+//! formatting included, is left as it was.  The edited file is written to a temporary file next to
+//! it, which then replaces it, so that a file is never left half written, and one that can't be
+//! edited isn't touched at all.
+
+use crate::{Result, error};
+use snafu::ResultExt;
+use std::{io::Write, path::Path};
+use toml_edit::{DocumentMut, TableLike};
+
+/// Edit the config file at `path` with `edit`, creating the file if need be, and return what
+/// `edit` does.
+///
+/// A missing file is edited as if it were empty.  If `edit` fails, the file is left alone, and
+/// it's only written if `edit` changes it.
+pub fn edit<T>(path: &Path, edit: impl FnOnce(&mut DocumentMut) -> Result<T>) -> Result<T> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context(error::IoSnafu { path }),
+    };
+
+    let mut document: DocumentMut = contents.parse().map_err(|e: toml_edit::TomlError| {
+        error::ConfigFileEditSnafu {
+            path,
+            message: e.to_string(),
+        }
+        .build()
+    })?;
+
+    let result = edit(&mut document)?;
+
+    let edited = document.to_string();
+    if edited != contents {
+        write_atomically(path, &edited)?;
+    }
+
+    Ok(result)
+}
+
+/// The table `key` of `document`, which is added if it isn't there yet.
+///
+/// `path` is that of the file being edited, for the error if `key` isn't a table.
+pub fn table_mut<'a>(document: &'a mut DocumentMut, path: &Path, key: &str) -> Result<&'a mut dyn TableLike> {
+    document
+        .entry(key)
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .ok_or_else(|| {
+            error::ConfigFileEditSnafu {
+                path,
+                message: format!("`{key}` is not a table"),
+            }
+            .build()
+        })
+}
+
+/// Replace the file at `path` with `contents`, creating its directory if need be.
+fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(parent).context(error::IoSnafu { path: parent })?;
+
+    let mut temp_file = tempfile::NamedTempFile::new_in(parent).context(error::IoSnafu { path: parent })?;
+    temp_file
+        .write_all(contents.as_bytes())
+        .and_then(|()| temp_file.as_file().sync_all())
+        .context(error::IoSnafu {
+            path: temp_file.path().to_path_buf(),
+        })?;
+
+    // Keep the permissions of the file being replaced, rather than the owner-only ones of a
+    // temporary file
+    if let Ok(metadata) = std::fs::metadata(path) {
+        std::fs::set_permissions(temp_file.path(), metadata.permissions()).context(error::IoSnafu {
+            path: temp_file.path().to_path_buf(),
+        })?;
+    }
+
+    temp_file
+        .persist(path)
+        .map_err(|e| e.error)
+        .context(error::IoSnafu { path })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn failed_edit_leaves_file_alone() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("cgx.toml");
+        std::fs::write(&path, "tools = \"ripgrep\"\n").unwrap();
+
+        assert_matches!(
+            edit(&path, |document| table_mut(document, &path, "tools").map(|_| ())),
+            Err(error::Error::ConfigFileEdit { .. })
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "tools = \"ripgrep\"\n");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn unchanged_file_isnt_created() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config/cgx.toml");
+
+        let found = edit(&path, |document| Ok(document.contains_key("aliases"))).unwrap();
+        assert!(!found);
+        assert!(!path.exists());
+    }
+}
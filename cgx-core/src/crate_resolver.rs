@@ -1,5 +1,5 @@
 use crate::{
-    Result,
+    Result, as_of,
     cache::Cache,
     cargo::{CargoMetadataOptions, CargoRunner},
     config::Config,
@@ -7,8 +7,11 @@ use crate::{
     error,
     git::{GitClient, GitSelector},
     http::HttpClient,
-    registry::RegistryClient,
+    registry::{self, RegistryClient, RegistryVersionInfo},
+    source_provider::SourceProviders,
+    successors,
 };
+use cargo_metadata::{Metadata, Package};
 use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 use snafu::OptionExt;
@@ -16,6 +19,7 @@ use std::{
     path::{Path, PathBuf},
     sync::Arc,
 };
+use url::Url;
 
 /// A resolved crate represents a concrete, validated reference to a specific crate version.
 ///
@@ -57,6 +61,26 @@ pub trait CrateResolver: std::fmt::Debug + Send + Sync + 'static {
     /// Returns an error if the crate specification is invalid, if the crate cannot be found,
     /// or if the specified version is not compatible with the found version.
     fn resolve(&self, spec: &CrateSpec) -> Result<ResolvedCrate>;
+
+    /// Resolve a crate specification from what is already known locally, without any network
+    /// access, if this resolver is able to.
+    ///
+    /// This is used to notice that a cached resolution has been overtaken by a newer release
+    /// that's since been seen locally, such as in the registry index cache cargo shares with cgx.
+    /// Returns `None` if the spec can't be resolved that way.
+    fn resolve_locally(&self, _spec: &CrateSpec) -> Option<ResolvedCrate> {
+        None
+    }
+
+    /// The revision of the local copy of the index a crate specification is resolved from, such as
+    /// the `ETag` of its entry in a sparse index, if this resolver knows it.
+    ///
+    /// This is recorded with a cached resolution, so that as long as it hasn't changed the
+    /// resolution is known to be current without [`Self::resolve_locally`] having to read the
+    /// index.  Returns `None` if there's no such revision for the spec.
+    fn index_revision(&self, _spec: &CrateSpec) -> Option<String> {
+        None
+    }
 }
 
 /// The source location of a resolved crate.
@@ -98,6 +122,15 @@ pub enum ResolvedSource {
         /// The path to the directory containing the crate
         path: PathBuf,
     },
+
+    /// A crate from a custom source provider (see [`crate::source_provider`])
+    Custom {
+        /// The URL the crate was resolved from, whose scheme picks the provider
+        url: Url,
+
+        /// What the provider identifies exactly this source of the crate by
+        revision: String,
+    },
 }
 
 /// Create the default [`CrateResolver`] implementation, repecting the given config and using the
@@ -108,8 +141,10 @@ pub(crate) fn create_resolver(
     git_client: GitClient,
     cargo: Arc<dyn CargoRunner>,
     http_client: HttpClient,
+    source_providers: SourceProviders,
 ) -> impl CrateResolver {
-    let inner = DefaultCrateResolver::new(config, git_client, cargo, http_client);
+    let mut inner = DefaultCrateResolver::new(config, git_client, cargo, http_client);
+    inner.source_providers = source_providers;
     CachingResolver::new(inner, cache)
 }
 
@@ -121,6 +156,7 @@ struct DefaultCrateResolver {
     git_client: GitClient,
     cargo: Arc<dyn CargoRunner>,
     http_client: HttpClient,
+    source_providers: SourceProviders,
 }
 
 impl DefaultCrateResolver {
@@ -136,6 +172,7 @@ impl DefaultCrateResolver {
             git_client,
             cargo,
             http_client,
+            source_providers: SourceProviders::default(),
         }
     }
 
@@ -154,28 +191,7 @@ impl DefaultCrateResolver {
             },
         )?;
 
-        let package = if let Some(n) = name {
-            metadata
-                .packages
-                .iter()
-                .find(|p| p.name.as_str() == n)
-                .with_context(|| error::PackageNotFoundInWorkspaceSnafu {
-                    name: n.clone(),
-                    available: metadata
-                        .packages
-                        .iter()
-                        .map(|p| p.name.to_string())
-                        .collect::<Vec<_>>(),
-                })?
-        } else {
-            if metadata.packages.len() != 1 {
-                return error::AmbiguousPackageNameSnafu {
-                    count: metadata.packages.len(),
-                }
-                .fail();
-            }
-            &metadata.packages[0]
-        };
+        let package = Self::select_package(&metadata, name, None)?;
 
         if let Some(req) = version {
             if !req.matches(&package.version) {
@@ -199,18 +215,27 @@ impl DefaultCrateResolver {
     /// Resolve a registry crate specification.
     ///
     /// `source` is `None` to indicate the default (crates.io) registry.
+    ///
+    /// With `offline`, only the locally cached copy of the index is consulted.
     fn resolve_registry(
         &self,
         name: &str,
         version: Option<&VersionReq>,
         source: Option<&RegistrySource>,
+        offline: bool,
     ) -> Result<ResolvedCrate> {
         // There is always some VersionReq; if not specified explicitly then "*" is implied
         let version = version.cloned().unwrap_or(VersionReq::STAR);
-        let registry = RegistryClient::new(source, &self.http_client, &self.config.http)?;
-        let versions = match registry.crate_versions(name, self.config.offline)? {
+        let registry = RegistryClient::new(
+            source,
+            &self.config.endpoints,
+            &self.http_client,
+            &self.config.http,
+            &self.git_client,
+        )?;
+        let versions = match registry.crate_versions(name, offline)? {
             Some(versions) => versions,
-            None if self.config.offline => {
+            None if offline && !registry.is_local() => {
                 return error::OfflineModeSnafu {
                     name: name.to_string(),
                     version: version.to_string(),
@@ -225,11 +250,46 @@ impl DefaultCrateResolver {
             }
         };
 
+        // As of a date, only the versions published before it count, which only crates.io makes
+        // known
+        let published = match (self.config.as_of, source) {
+            (Some(date), None) => {
+                if offline {
+                    return error::OfflineModeSnafu {
+                        name: name.to_string(),
+                        version: version.to_string(),
+                    }
+                    .fail();
+                }
+                let published = as_of::crates_io_publish_times(&self.config, &self.http_client, name)?
+                    .with_context(|| error::CrateNotFoundInRegistrySnafu { name })?;
+                Some((date, published))
+            }
+            (Some(date), Some(_)) => {
+                tracing::warn!(
+                    "Only crates.io makes known when versions were published, so {} is resolved as \
+                     it is now rather than as of {}",
+                    name,
+                    date
+                );
+                None
+            }
+            (None, _) => None,
+        };
+        let was_published = |v: &RegistryVersionInfo| {
+            published.as_ref().is_none_or(|(date, published)| {
+                published
+                    .get(&v.version)
+                    .is_some_and(|time| *time < as_of::cutoff(*date))
+            })
+        };
+
         // Filter non-yanked versions matching the requirement and select the best, by which
         // we mean the highest version number.
         let best_version = versions
             .iter()
             .filter(|v| !v.yanked)
+            .filter(|v| was_published(v))
             .filter_map(|v| {
                 Version::parse(&v.version)
                     .ok()
@@ -237,11 +297,35 @@ impl DefaultCrateResolver {
                     .map(|ver| (v.version.clone(), ver))
             })
             .max_by(|(_, a), (_, b)| a.cmp(b))
-            .map(|(_, best)| best)
-            .with_context(|| error::NoMatchingVersionSnafu {
-                name: name.to_string(),
-                requirement: version.to_string(),
-            })?;
+            .map(|(_, best)| best);
+        let Some(best_version) = best_version else {
+            if let Some((date, _)) = published {
+                return error::NoVersionAsOfSnafu {
+                    name,
+                    requirement: version.to_string(),
+                    date,
+                }
+                .fail();
+            }
+
+            // Yanking every version is often what a crate that has been renamed or replaced leaves
+            // behind, so one that's known to have been is reported along with its successor
+            let matches_yanked = versions
+                .iter()
+                .any(|v| v.yanked && Version::parse(&v.version).is_ok_and(|ver| version.matches(&ver)));
+            let successor = source
+                .is_none()
+                .then(|| successors::successor(&self.config, name))
+                .flatten();
+            return match successor {
+                Some(successor) if matches_yanked => error::CrateSupersededSnafu { name, successor }.fail(),
+                _ => error::NoMatchingVersionSnafu {
+                    name: name.to_string(),
+                    requirement: version.to_string(),
+                }
+                .fail(),
+            };
+        };
 
         // Record the resolved source which we store alongside the crate, as we will still need
         // to retrieve the crate contents at some point later.
@@ -260,15 +344,28 @@ impl DefaultCrateResolver {
     }
 
     /// Resolve a git repository crate specification.
+    ///
+    /// `repo_name` is a hint used to pick a package out of a multi-crate repo when no `name` was
+    /// given; see [`Self::select_package`].
     fn resolve_git(
         &self,
         repo: &str,
         selector: &GitSelector,
         name: &Option<String>,
         version: &Option<VersionReq>,
+        repo_name: Option<&str>,
     ) -> Result<ResolvedCrate> {
+        // As of a date, the default branch stands for what it would have been built from then
+        let selector = match (self.config.as_of, selector) {
+            (Some(date), GitSelector::DefaultBranch) => self
+                .git_client
+                .selector_as_of(repo, as_of::cutoff(date), self.config.offline)?
+                .with_context(|| error::NoCommitAsOfSnafu { repo, date })?,
+            _ => selector.clone(),
+        };
+
         // Checkout using git client (returns cached checkout path and commit hash)
-        let (checkout_path, commit_hash) = self.git_client.checkout_ref(repo, selector.clone())?;
+        let (checkout_path, commit_hash) = self.git_client.checkout_ref(repo, selector)?;
 
         // Use cargo_metadata to read the crate info
         let metadata = self.cargo.metadata(
@@ -279,28 +376,7 @@ impl DefaultCrateResolver {
             },
         )?;
 
-        let package = if let Some(n) = name {
-            metadata
-                .packages
-                .iter()
-                .find(|p| p.name.as_str() == n)
-                .with_context(|| error::PackageNotFoundInWorkspaceSnafu {
-                    name: n.clone(),
-                    available: metadata
-                        .packages
-                        .iter()
-                        .map(|p| p.name.to_string())
-                        .collect::<Vec<_>>(),
-                })?
-        } else {
-            if metadata.packages.len() != 1 {
-                return error::AmbiguousPackageNameSnafu {
-                    count: metadata.packages.len(),
-                }
-                .fail();
-            }
-            &metadata.packages[0]
-        };
+        let package = Self::select_package(&metadata, name, repo_name)?;
 
         if let Some(req) = version {
             if !req.matches(&package.version) {
@@ -333,8 +409,12 @@ impl DefaultCrateResolver {
         // Convert Forge to git URL
         let git_url = forge.git_url();
 
+        let repo_name = match forge {
+            Forge::GitHub { repo, .. } | Forge::GitLab { repo, .. } => repo.as_str(),
+        };
+
         // Resolve using git resolution logic
-        let mut resolved = self.resolve_git(&git_url, selector, name, version)?;
+        let mut resolved = self.resolve_git(&git_url, selector, name, version, Some(repo_name))?;
 
         // Replace the source with Forge instead of Git
         if let ResolvedSource::Git { commit, .. } = resolved.source {
@@ -348,23 +428,89 @@ impl DefaultCrateResolver {
 
         Ok(resolved)
     }
+
+    /// Pick the package to resolve from the metadata of a crate or workspace.
+    ///
+    /// If `name` is given, the package with that name is used.  Otherwise a workspace with a single
+    /// package is unambiguous.  Forge repos commonly host multi-crate workspaces (a CLI plus its
+    /// supporting libraries), so when `repo_name` is known the following are tried in order:
+    ///
+    /// 1. The package with the same name as the repo
+    /// 2. The only package with a binary target
+    ///
+    /// If none of those single out a package, the error lists the candidates.
+    fn select_package<'m>(
+        metadata: &'m Metadata,
+        name: &Option<String>,
+        repo_name: Option<&str>,
+    ) -> Result<&'m Package> {
+        let available = || {
+            metadata
+                .packages
+                .iter()
+                .map(|p| p.name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        if let Some(n) = name {
+            return metadata
+                .packages
+                .iter()
+                .find(|p| p.name.as_str() == n)
+                .with_context(|| error::PackageNotFoundInWorkspaceSnafu {
+                    name: n.clone(),
+                    available: available(),
+                });
+        }
+
+        if let [package] = metadata.packages.as_slice() {
+            return Ok(package);
+        }
+
+        if let Some(repo_name) = repo_name {
+            if let Some(package) = metadata.packages.iter().find(|p| p.name.as_str() == repo_name) {
+                tracing::debug!("Selected package '{}' matching the repository name", package.name);
+                return Ok(package);
+            }
+
+            let mut bin_packages = metadata
+                .packages
+                .iter()
+                .filter(|p| p.targets.iter().any(|t| t.is_bin()));
+            if let (Some(package), None) = (bin_packages.next(), bin_packages.next()) {
+                tracing::debug!(
+                    "Selected package '{}' as the only package with a binary target",
+                    package.name
+                );
+                return Ok(package);
+            }
+        }
+
+        error::AmbiguousPackageNameSnafu {
+            count: metadata.packages.len(),
+            candidates: available(),
+        }
+        .fail()
+    }
 }
 
 impl CrateResolver for DefaultCrateResolver {
     fn resolve(&self, spec: &CrateSpec) -> Result<ResolvedCrate> {
         match spec {
-            CrateSpec::CratesIo { name, version } => self.resolve_registry(name, version.as_ref(), None),
+            CrateSpec::CratesIo { name, version } => {
+                self.resolve_registry(name, version.as_ref(), None, self.config.offline)
+            }
             CrateSpec::Registry {
                 source,
                 name,
                 version,
-            } => self.resolve_registry(name, version.as_ref(), Some(source)),
+            } => self.resolve_registry(name, version.as_ref(), Some(source), self.config.offline),
             CrateSpec::Git {
                 repo,
                 selector,
                 name,
                 version,
-            } => self.resolve_git(repo, selector, name, version),
+            } => self.resolve_git(repo, selector, name, version, None),
             CrateSpec::Forge {
                 forge,
                 selector,
@@ -372,6 +518,42 @@ impl CrateResolver for DefaultCrateResolver {
                 version,
             } => self.resolve_forge(forge, selector, name, version),
             CrateSpec::LocalDir { path, name, version } => self.resolve_local_dir(path, name, version),
+            CrateSpec::Custom { url, version } => self.source_providers.resolve(url, version.as_ref()),
+        }
+    }
+
+    fn resolve_locally(&self, spec: &CrateSpec) -> Option<ResolvedCrate> {
+        // A resolution as of a date isn't overtaken by anything released since
+        if self.config.as_of.is_some() {
+            return None;
+        }
+
+        let result = match spec {
+            CrateSpec::CratesIo { name, version } => {
+                self.resolve_registry(name, version.as_ref(), None, true)
+            }
+            CrateSpec::Registry {
+                source,
+                name,
+                version,
+            } => self.resolve_registry(name, version.as_ref(), Some(source), true),
+            _ => return None,
+        };
+
+        result
+            .inspect_err(|e| {
+                tracing::debug!("Unable to resolve {:?} from the local index cache: {}", spec, e);
+            })
+            .ok()
+    }
+
+    fn index_revision(&self, spec: &CrateSpec) -> Option<String> {
+        match spec {
+            CrateSpec::CratesIo { name, .. } => registry::index_revision(None, &self.config.endpoints, name),
+            CrateSpec::Registry { source, name, .. } => {
+                registry::index_revision(Some(source), &self.config.endpoints, name)
+            }
+            _ => None,
         }
     }
 }
@@ -400,7 +582,20 @@ impl<R: CrateResolver> CrateResolver for CachingResolver<R> {
             return self.inner.resolve(spec);
         }
 
-        self.cache.get_or_resolve_crate(spec, || self.inner.resolve(spec))
+        // A cached resolution is only as current as the newest release seen locally
+        let newer_release = |cached: &ResolvedCrate| {
+            self.inner
+                .resolve_locally(spec)
+                .filter(|local| local.version > cached.version)
+                .map(|local| local.version)
+        };
+
+        self.cache.get_or_resolve_crate_revalidated(
+            spec,
+            || self.inner.index_revision(spec),
+            newer_release,
+            || self.inner.resolve(spec),
+        )
     }
 }
 
@@ -420,7 +615,7 @@ mod tests {
         let (temp_dir, config) = crate::config::create_test_env();
         let reporter = crate::messages::MessageReporter::null();
         let cache = Cache::new(config.clone(), reporter.clone());
-        let git_client = GitClient::new(cache.clone(), reporter.clone(), config.http.clone());
+        let git_client = GitClient::new(cache.clone(), reporter.clone(), &config);
         let http_client = HttpClient::new(&config.http).unwrap();
         let resolver = DefaultCrateResolver::new(
             config.clone(),
@@ -438,7 +633,7 @@ mod tests {
         config.offline = true;
         let reporter = crate::messages::MessageReporter::null();
         let cache = Cache::new(config.clone(), reporter.clone());
-        let git_client = GitClient::new(cache.clone(), reporter, config.http.clone());
+        let git_client = GitClient::new(cache.clone(), reporter, &config);
         let http_client = HttpClient::new(&config.http).unwrap();
         let resolver = DefaultCrateResolver::new(config, git_client, resolver.inner.cargo, http_client);
         (CachingResolver::new(resolver, cache), temp_dir)
@@ -619,6 +814,68 @@ mod tests {
         }
     }
 
+    /// Tests for picking a package out of a multi-crate workspace when no name was given.
+    mod package_selection {
+        use super::*;
+        use crate::error::Error;
+
+        fn metadata_for(testcase: &CrateTestCase) -> Metadata {
+            crate::cargo::find_cargo(crate::messages::MessageReporter::null())
+                .unwrap()
+                .metadata(
+                    testcase.path(),
+                    &CargoMetadataOptions {
+                        no_deps: true,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+        }
+
+        #[test]
+        fn prefers_package_matching_repo_name() {
+            let metadata = metadata_for(&CrateTestCase::workspace_multiple_bin_crates());
+
+            let package = DefaultCrateResolver::select_package(&metadata, &None, Some("bin2")).unwrap();
+            assert_eq!(package.name.as_str(), "bin2");
+        }
+
+        #[test]
+        fn falls_back_to_only_bin_package() {
+            let metadata = metadata_for(&CrateTestCase::workspace_single_bin_crate());
+
+            let package = DefaultCrateResolver::select_package(&metadata, &None, Some("some-repo")).unwrap();
+            assert_eq!(package.name.as_str(), "tool");
+        }
+
+        #[test]
+        fn multiple_bin_packages_lists_candidates() {
+            let metadata = metadata_for(&CrateTestCase::workspace_multiple_bin_crates());
+
+            let result = DefaultCrateResolver::select_package(&metadata, &None, Some("some-repo"));
+            assert_matches!(result, Err(Error::AmbiguousPackageName { count: 6, ref candidates })
+                if candidates.contains(&"bin1".to_string()) && candidates.contains(&"lib3".to_string()));
+        }
+
+        #[test]
+        fn explicit_name_wins_over_repo_name() {
+            let metadata = metadata_for(&CrateTestCase::workspace_multiple_bin_crates());
+
+            let package =
+                DefaultCrateResolver::select_package(&metadata, &Some("lib1".to_string()), Some("bin2"))
+                    .unwrap();
+            assert_eq!(package.name.as_str(), "lib1");
+        }
+
+        #[test]
+        fn no_heuristics_without_repo_name() {
+            let metadata = metadata_for(&CrateTestCase::workspace_single_bin_crate());
+
+            let result = DefaultCrateResolver::select_package(&metadata, &None, None);
+            assert_matches!(result, Err(Error::AmbiguousPackageName { count: 2, .. }));
+        }
+    }
+
     /// Tests exercising crate specs using a registry (mostly crates.io).
     ///
     /// These tests will actually hit the registry over the network.  Hopefully they don't get
@@ -762,7 +1019,7 @@ mod tests {
             let git_client = GitClient::new(
                 online_resolver.cache.clone(),
                 crate::messages::MessageReporter::null(),
-                offline_config.http.clone(),
+                &offline_config,
             );
             let http_client = HttpClient::new(&offline_config.http).unwrap();
             let offline_resolver = CachingResolver::new(
@@ -825,7 +1082,7 @@ mod tests {
             let git_client = GitClient::new(
                 resolver.cache.clone(),
                 crate::messages::MessageReporter::null(),
-                offline_config.http.clone(),
+                &offline_config,
             );
             let http_client = HttpClient::new(&offline_config.http).unwrap();
             let offline_resolver = CachingResolver::new(
@@ -1000,6 +1257,7 @@ mod tests {
     }
 
     /// Tests exercising crate specs pointing to git repositories.
+    #[cfg(feature = "git")]
     mod git {
         use super::*;
         use crate::error::Error;
@@ -1164,6 +1422,7 @@ mod tests {
     /// Mostly this is just a thin wrapper around git resolution, so these tests are lighter.
     /// We don't care about the forge vs other git distinction until we start looking for
     /// pre-built binaries to download, which is outside of the scope of this module.
+    #[cfg(feature = "git")]
     mod forge {
         use super::*;
 
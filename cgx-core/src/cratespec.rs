@@ -1,16 +1,93 @@
 use crate::{
     Result,
     cli::CliArgs,
-    config::{Config, ToolConfig},
+    config::{Config, SourceFallback, ToolConfig},
     error,
     git::GitSelector,
+    source_provider,
 };
-use semver::VersionReq;
+use semver::{Comparator, Op, Version, VersionReq};
 use serde::{Deserialize, Serialize};
 use snafu::{OptionExt, ResultExt};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use url::Url;
 
+/// Parse a version requirement given on the command line or in a config file.
+///
+/// This is the same syntax as in `Cargo.toml`, so besides plain versions there are operators like
+/// `=1.2.3`, `~1.4` and `>=1.2`, and several requirements can be combined with commas, like
+/// `>=1.2, <2`.  If the requirement doesn't parse, the error suggests what was probably meant, if
+/// that's clear.
+pub fn parse_version_req(version: &str) -> Result<VersionReq> {
+    VersionReq::parse(version.trim()).with_context(|_| error::InvalidVersionReqSnafu {
+        version,
+        hint: version_req_hint(version),
+    })
+}
+
+/// A suggestion for a version requirement that doesn't parse, to be appended to the error, or an
+/// empty string if there's nothing to suggest.
+///
+/// This catches the usual slips: requirements separated by spaces rather than commas, a leading
+/// `v` as in a git tag, `==` as in Python, and `latest`.
+pub(crate) fn version_req_hint(version: &str) -> String {
+    let version = version.trim();
+    if version.eq_ignore_ascii_case("latest") {
+        return "; leave the version out, or use `*`, for the latest version".to_string();
+    }
+
+    let suggestion = version
+        .split(',')
+        .flat_map(split_clause)
+        .map(|clause| {
+            let clause = clause
+                .strip_prefix('=')
+                .filter(|rest| rest.starts_with('='))
+                .unwrap_or(clause.as_str());
+            let rest = clause.trim_start_matches(is_operator);
+            let op = &clause[..clause.len() - rest.len()];
+            let rest = rest
+                .strip_prefix(['v', 'V'])
+                .filter(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+                .unwrap_or(rest);
+            format!("{op}{rest}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if suggestion != version && VersionReq::parse(&suggestion).is_ok() {
+        format!("; did you mean '{}'?", suggestion)
+    } else {
+        String::new()
+    }
+}
+
+/// Whether `c` is part of a version requirement's operator.
+fn is_operator(c: char) -> bool {
+    matches!(c, '=' | '<' | '>' | '~' | '^')
+}
+
+/// Split a clause of a version requirement that's really several separated by spaces, like
+/// `>=1.2 <2`, into those requirements.
+///
+/// An operator followed by a space, like `>= 1.2`, is kept together with its version.
+fn split_clause(clause: &str) -> Vec<String> {
+    let mut clauses = Vec::new();
+    let mut op = String::new();
+    for word in clause.split_whitespace() {
+        if word.chars().all(is_operator) {
+            op.push_str(word);
+        } else {
+            clauses.push(format!("{op}{word}"));
+            op.clear();
+        }
+    }
+    if !op.is_empty() {
+        clauses.push(op);
+    }
+    clauses
+}
+
 /// A specification of a crate that the user wants to execute.
 ///
 /// Note that "crate" here doesn't necessarily mean "crate on Crates.io".  We support various ways
@@ -90,6 +167,26 @@ pub enum CrateSpec {
         name: Option<String>,
         version: Option<VersionReq>,
     },
+
+    /// A crate from the source provider registered for the scheme of its URL, like
+    /// `mysrc://team/tool` (see [`crate::source_provider`]).
+    ///
+    /// The provider names the crate when it resolves the URL.
+    Custom { url: Url, version: Option<VersionReq> },
+}
+
+/// A setting in the config that changed what a crate named on the command line resolves to, such
+/// as an alias or a `[tools]` pin (see [`CrateSpec::config_decisions`]).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigDecision {
+    /// Dotted key of the setting, like `aliases.rg` or `tools.ripgrep.version`.
+    pub key: String,
+
+    /// The value of the setting.
+    pub value: String,
+
+    /// Where the setting came from: the path of a config file, or `defaults`.
+    pub source: String,
 }
 
 impl CrateSpec {
@@ -105,22 +202,21 @@ impl CrateSpec {
     /// 2. `@version` suffix in crate name
     /// 3. Config tool pinning
     /// 4. Latest version (lowest)
+    ///
+    /// This is the first of [`Self::load_candidates`], i.e. the spec as it would be resolved
+    /// without any [`Config::source_fallback`].
     pub fn load(config: &Config, args: &CliArgs) -> Result<Self> {
-        // Parse the base crate spec from CLI args, including special cargo handling
-        let (name, at_version) = if let Some(crate_spec) = &args.crate_spec {
-            if crate_spec == "cargo" && !args.args.is_empty() {
-                // Special case: `cgx cargo deny` -> crate name is `cargo-deny`
-                let subcommand = &args.args[0];
-                let (subcommand_name, subcommand_version) = Self::parse_crate_name_and_version(subcommand)?;
-                let cargo_crate_name = format!("cargo-{}", subcommand_name);
-                (Some(cargo_crate_name), subcommand_version)
-            } else {
-                let (n, v) = Self::parse_crate_name_and_version(crate_spec)?;
-                (Some(n), v)
-            }
-        } else {
-            (None, None)
-        };
+        Self::load_candidates(config, args).map(|mut candidates| candidates.remove(0))
+    }
+
+    /// Load the crate specs to try, in order, for the crate named on the command line.
+    ///
+    /// This applies the same overrides as [`Self::load`].  When the crate is given by name alone,
+    /// with no source on the command line or in the tool config, there is one candidate per
+    /// entry in [`Config::source_fallback`]; otherwise there is exactly one.  The result is never
+    /// empty.
+    pub fn load_candidates(config: &Config, args: &CliArgs) -> Result<Vec<Self>> {
+        let (name, at_version) = Self::named_crate(args)?;
 
         // Apply alias resolution from config
         let name = name.map(|n| config.aliases.get(&n).cloned().unwrap_or(n));
@@ -132,6 +228,12 @@ impl CrateSpec {
             .filter(|v| !v.is_empty())
             .map(|s| s.as_str());
 
+        if let Some(spec) =
+            Self::load_channel(config, args, name.as_deref(), at_version.as_deref(), flag_version)
+        {
+            return Ok(vec![spec]);
+        }
+
         let cli_version = match (at_version.as_deref(), flag_version) {
             (Some(at_ver), Some(flag_ver)) => {
                 if at_ver != flag_ver {
@@ -141,19 +243,10 @@ impl CrateSpec {
                     }
                     .fail();
                 }
-                Some(
-                    VersionReq::parse(at_ver)
-                        .with_context(|_| error::InvalidVersionReqSnafu { version: at_ver })?,
-                )
+                Some(parse_version_req(at_ver)?)
             }
-            (Some(at_ver), None) => Some(
-                VersionReq::parse(at_ver)
-                    .with_context(|_| error::InvalidVersionReqSnafu { version: at_ver })?,
-            ),
-            (None, Some(flag_ver)) => Some(
-                VersionReq::parse(flag_ver)
-                    .with_context(|_| error::InvalidVersionReqSnafu { version: flag_ver })?,
-            ),
+            (Some(at_ver), None) => Some(parse_version_req(at_ver)?),
+            (None, Some(flag_ver)) => Some(parse_version_req(flag_ver)?),
             (None, None) => None,
         };
 
@@ -164,8 +257,9 @@ impl CrateSpec {
                     .tools
                     .get(tool_name)
                     .and_then(|tool_config| match tool_config {
+                        // Already validated when the config was loaded
                         ToolConfig::Version(v) | ToolConfig::Detailed { version: Some(v), .. } => {
-                            VersionReq::parse(v).ok()
+                            VersionReq::parse(v.trim()).ok()
                         }
                         ToolConfig::Detailed { version: None, .. } => None,
                     })
@@ -176,6 +270,12 @@ impl CrateSpec {
             cli_version
         };
 
+        // `--min-version` narrows whatever the requirement turned out to be
+        let version = match &args.min_version {
+            Some(min_version) => Some(Self::at_least(version, min_version)),
+            None => version,
+        };
+
         // Construct GitSelector from CLI flags
         let git_selector = match (&args.branch, &args.tag, &args.rev) {
             (Some(branch), None, None) => GitSelector::Branch(branch.clone()),
@@ -194,42 +294,42 @@ impl CrateSpec {
         // Construct the appropriate CrateSpec variant based on source flags
         if let Some(git_url) = &args.git {
             if let Some(forge) = Forge::try_parse_from_url(git_url) {
-                Ok(CrateSpec::Forge {
+                Ok(vec![CrateSpec::Forge {
                     forge,
                     selector: git_selector.clone(),
                     name,
                     version,
-                })
+                }])
             } else {
-                Ok(CrateSpec::Git {
+                Ok(vec![CrateSpec::Git {
                     repo: git_url.clone(),
                     selector: git_selector.clone(),
                     name,
                     version,
-                })
+                }])
             }
         } else if let Some(registry) = &args.registry {
             let name = name.context(error::MissingCrateParameterSnafu)?;
-            Ok(CrateSpec::Registry {
+            Ok(vec![CrateSpec::Registry {
                 source: RegistrySource::Named(registry.clone()),
                 name,
                 version,
-            })
+            }])
         } else if let Some(index_str) = &args.index {
             let name = name.context(error::MissingCrateParameterSnafu)?;
             let index_url =
                 Url::parse(index_str).with_context(|_| error::InvalidUrlSnafu { url: index_str })?;
-            Ok(CrateSpec::Registry {
+            Ok(vec![CrateSpec::Registry {
                 source: RegistrySource::IndexUrl(index_url),
                 name,
                 version,
-            })
+            }])
         } else if let Some(path) = &args.path {
-            Ok(CrateSpec::LocalDir {
+            Ok(vec![CrateSpec::LocalDir {
                 path: path.clone(),
                 name,
                 version,
-            })
+            }])
         } else if let Some(github_repo) = &args.github {
             let (owner, repo) = Self::parse_owner_repo(github_repo)?;
             let custom_url = if let Some(url_str) = &args.github_url {
@@ -237,7 +337,7 @@ impl CrateSpec {
             } else {
                 None
             };
-            Ok(CrateSpec::Forge {
+            Ok(vec![CrateSpec::Forge {
                 forge: Forge::GitHub {
                     custom_url,
                     owner,
@@ -246,7 +346,7 @@ impl CrateSpec {
                 selector: git_selector.clone(),
                 name,
                 version,
-            })
+            }])
         } else if let Some(gitlab_repo) = &args.gitlab {
             let (owner, repo) = Self::parse_owner_repo(gitlab_repo)?;
             let custom_url = if let Some(url_str) = &args.gitlab_url {
@@ -254,7 +354,7 @@ impl CrateSpec {
             } else {
                 None
             };
-            Ok(CrateSpec::Forge {
+            Ok(vec![CrateSpec::Forge {
                 forge: Forge::GitLab {
                     custom_url,
                     owner,
@@ -263,9 +363,13 @@ impl CrateSpec {
                 selector: git_selector.clone(),
                 name,
                 version,
-            })
+            }])
         } else {
-            // No CLI source flags - check tool config, then default_registry, then crates.io
+            // No CLI source flags - check for a source provider URL, then tool config, then
+            // default_registry, then crates.io
+            if let Some(url) = name.as_deref().and_then(source_provider::provider_url) {
+                return Ok(vec![CrateSpec::Custom { url, version }]);
+            }
 
             // First check if tool config specifies a source
             if let Some(ref tool_name) = name {
@@ -287,19 +391,19 @@ impl CrateSpec {
                             };
 
                             if let Some(forge) = Forge::try_parse_from_url(git_url) {
-                                return Ok(CrateSpec::Forge {
+                                return Ok(vec![CrateSpec::Forge {
                                     forge,
                                     selector,
                                     name,
                                     version,
-                                });
+                                }]);
                             } else {
-                                return Ok(CrateSpec::Git {
+                                return Ok(vec![CrateSpec::Git {
                                     repo: git_url.clone(),
                                     selector,
                                     name,
                                     version,
-                                });
+                                }]);
                             }
                         }
                         ToolConfig::Detailed {
@@ -307,19 +411,19 @@ impl CrateSpec {
                         } => {
                             // Tool config specifies registry
                             let name = name.context(error::MissingCrateParameterSnafu)?;
-                            return Ok(CrateSpec::Registry {
+                            return Ok(vec![CrateSpec::Registry {
                                 source: RegistrySource::Named(reg.clone()),
                                 name,
                                 version,
-                            });
+                            }]);
                         }
                         ToolConfig::Detailed { path: Some(p), .. } => {
                             // Tool config specifies local path
-                            return Ok(CrateSpec::LocalDir {
+                            return Ok(vec![CrateSpec::LocalDir {
                                 path: p.clone(),
                                 name,
                                 version,
-                            });
+                            }]);
                         }
                         _ => {
                             // Tool config doesn't specify source - fall through to defaults
@@ -334,18 +438,226 @@ impl CrateSpec {
             // optional have been eliminated, so we require a crate name.
             let name = name.context(error::MissingCrateParameterSnafu)?;
 
-            if let Some(ref default_registry) = config.default_registry {
-                // Use config's default registry
-                Ok(CrateSpec::Registry {
-                    source: RegistrySource::Named(default_registry.clone()),
-                    name,
-                    version,
-                })
-            } else {
-                // Use crates.io
-                Ok(CrateSpec::CratesIo { name, version })
+            Ok(Self::default_source_candidates(config, name, version))
+        }
+    }
+
+    /// The settings in the config that [`Self::load_candidates`] applies to the crate named on the
+    /// command line, each with the config file it's from: the alias the crate was given by, the
+    /// version `[tools]` pins it to, and the source `[tools]` gives for it.
+    ///
+    /// Nothing is returned for a command line that doesn't load, since no spec is resolved then.
+    pub fn config_decisions(config: &Config, args: &CliArgs) -> Vec<ConfigDecision> {
+        let Ok((Some(given), at_version)) = Self::named_crate(args) else {
+            return Vec::new();
+        };
+        let decision = |key: String, value: String| ConfigDecision {
+            source: config.setting_sources.get(&key).cloned().unwrap_or_default(),
+            key,
+            value,
+        };
+
+        let mut decisions = Vec::new();
+        let name = match config.aliases.get(&given) {
+            Some(aliased) => {
+                decisions.push(decision(format!("aliases.{}", given), aliased.clone()));
+                aliased.clone()
+            }
+            None => given,
+        };
+
+        let flag_version = args.version.as_deref().filter(|v| !v.is_empty());
+        if Self::load_channel(config, args, Some(&name), at_version.as_deref(), flag_version).is_some() {
+            return decisions;
+        }
+        let Some(tool_config) = config.tools.get(&name) else {
+            return decisions;
+        };
+
+        if at_version.is_none() && flag_version.is_none() {
+            match tool_config {
+                ToolConfig::Version(version) => {
+                    decisions.push(decision(format!("tools.{}", name), version.clone()));
+                }
+                ToolConfig::Detailed {
+                    version: Some(version),
+                    ..
+                } => decisions.push(decision(format!("tools.{}.version", name), version.clone())),
+                ToolConfig::Detailed { version: None, .. } => {}
+            }
+        }
+
+        if !Self::has_cli_source(args) && source_provider::provider_url(&name).is_none() {
+            if let ToolConfig::Detailed {
+                git, registry, path, ..
+            } = tool_config
+            {
+                let source = match (git, registry, path) {
+                    (Some(git), ..) => Some(("git", git.clone())),
+                    (None, Some(registry), _) => Some(("registry", registry.clone())),
+                    (None, None, Some(path)) => Some(("path", path.display().to_string())),
+                    (None, None, None) => None,
+                };
+                decisions.extend(
+                    source.map(|(field, value)| decision(format!("tools.{}.{}", name, field), value)),
+                );
+            }
+        }
+
+        decisions
+    }
+
+    /// The crate named on the command line, if any, and the version given with it after an `@`.
+    ///
+    /// `cgx cargo <subcommand>` names the crate `cargo-<subcommand>`.
+    fn named_crate(args: &CliArgs) -> Result<(Option<String>, Option<String>)> {
+        let Some(crate_spec) = &args.crate_spec else {
+            return Ok((None, None));
+        };
+
+        if crate_spec == "cargo" && !args.args.is_empty() {
+            // Special case: `cgx cargo deny` -> crate name is `cargo-deny`
+            let (subcommand_name, subcommand_version) = Self::parse_crate_name_and_version(&args.args[0])?;
+            Ok((Some(format!("cargo-{}", subcommand_name)), subcommand_version))
+        } else {
+            let (name, version) = Self::parse_crate_name_and_version(crate_spec)?;
+            Ok((Some(name), version))
+        }
+    }
+
+    /// Whether the command line gives a source or git selector of its own, which the config
+    /// doesn't override.
+    fn has_cli_source(args: &CliArgs) -> bool {
+        args.git.is_some()
+            || args.registry.is_some()
+            || args.index.is_some()
+            || args.path.is_some()
+            || args.github.is_some()
+            || args.gitlab.is_some()
+            || args.branch.is_some()
+            || args.tag.is_some()
+            || args.rev.is_some()
+    }
+
+    /// The spec of the channel of the tool `name` that was given in place of a version, as in
+    /// `tool@nightly`, if that's what it was.
+    ///
+    /// Channels only apply when the command line doesn't name a source or git selector of its own.
+    fn load_channel(
+        config: &Config,
+        args: &CliArgs,
+        name: Option<&str>,
+        at_version: Option<&str>,
+        flag_version: Option<&str>,
+    ) -> Option<Self> {
+        let channel = match (at_version, flag_version) {
+            (Some(at_version), Some(flag_version)) if at_version != flag_version => return None,
+            (at_version, flag_version) => at_version.or(flag_version)?,
+        };
+
+        if Self::has_cli_source(args) {
+            return None;
+        }
+
+        Self::for_channel(config, name?, channel)
+    }
+
+    /// The spec for the channel `channel` of the tool `name`, if the tool has a channel by that
+    /// name.
+    ///
+    /// This is the branch the channel tracks, without any version requirement, since the version
+    /// the tool is pinned to is that of its stable releases.
+    pub fn for_channel(config: &Config, name: &str, channel: &str) -> Option<Self> {
+        let channel_config = config.tool_channel(name, channel)?;
+        let tool_git = match config.tools.get(name)? {
+            ToolConfig::Detailed { git, .. } => git.as_deref(),
+            ToolConfig::Version(_) => None,
+        };
+        let repo = channel_config.git.as_deref().or(tool_git)?;
+
+        let selector = match &channel_config.branch {
+            Some(branch) => GitSelector::Branch(branch.clone()),
+            None => GitSelector::DefaultBranch,
+        };
+        let name = Some(name.to_string());
+
+        Some(match Forge::try_parse_from_url(repo) {
+            Some(forge) => CrateSpec::Forge {
+                forge,
+                selector,
+                name,
+                version: None,
+            },
+            None => CrateSpec::Git {
+                repo: repo.to_string(),
+                selector,
+                name,
+                version: None,
+            },
+        })
+    }
+
+    /// The specs for a crate given by name alone, in the order of [`Config::source_fallback`].
+    ///
+    /// Without any fallback configured, this is just [`Config::default_registry`], or crates.io
+    /// if that isn't set either.
+    fn default_source_candidates(config: &Config, name: String, version: Option<VersionReq>) -> Vec<Self> {
+        let default_source = || match &config.default_registry {
+            Some(default_registry) => CrateSpec::Registry {
+                source: RegistrySource::Named(default_registry.clone()),
+                name: name.clone(),
+                version: version.clone(),
+            },
+            None => CrateSpec::CratesIo {
+                name: name.clone(),
+                version: version.clone(),
+            },
+        };
+
+        let mut candidates = Vec::new();
+        for fallback in &config.source_fallback {
+            let candidate = match fallback {
+                SourceFallback::DefaultRegistry if config.default_registry.is_none() => {
+                    tracing::debug!("Skipping source fallback '{fallback}' as no default_registry is set");
+                    continue;
+                }
+                SourceFallback::DefaultRegistry => default_source(),
+                SourceFallback::CratesIo => CrateSpec::CratesIo {
+                    name: name.clone(),
+                    version: version.clone(),
+                },
+                SourceFallback::Github { owner } => CrateSpec::Forge {
+                    forge: Forge::GitHub {
+                        custom_url: None,
+                        owner: owner.clone(),
+                        repo: name.clone(),
+                    },
+                    selector: GitSelector::DefaultBranch,
+                    name: Some(name.clone()),
+                    version: version.clone(),
+                },
+                SourceFallback::Gitlab { owner } => CrateSpec::Forge {
+                    forge: Forge::GitLab {
+                        custom_url: None,
+                        owner: owner.clone(),
+                        repo: name.clone(),
+                    },
+                    selector: GitSelector::DefaultBranch,
+                    name: Some(name.clone()),
+                    version: version.clone(),
+                },
+            };
+
+            if !candidates.contains(&candidate) {
+                candidates.push(candidate);
             }
         }
+
+        if candidates.is_empty() {
+            candidates.push(default_source());
+        }
+
+        candidates
     }
 
     /// Parse a crate name that may include an @version suffix.
@@ -373,12 +685,63 @@ impl CrateSpec {
         }
     }
 
+    /// The version requirement `version` (or any version, if `None`), further required to be at
+    /// least `min_version`.
+    fn at_least(version: Option<VersionReq>, min_version: &Version) -> VersionReq {
+        let mut version = version.unwrap_or(VersionReq::STAR);
+        version.comparators.push(Comparator {
+            op: Op::GreaterEq,
+            major: min_version.major,
+            minor: Some(min_version.minor),
+            patch: Some(min_version.patch),
+            pre: min_version.pre.clone(),
+        });
+        version
+    }
+
+    /// The name of the crate, if known.
+    ///
+    /// Crates from git repositories and local directories may be given without a name, in which
+    /// case it's discovered when the spec is resolved, and a source provider always names the
+    /// crate itself.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            CrateSpec::CratesIo { name, .. } | CrateSpec::Registry { name, .. } => Some(name),
+            CrateSpec::Git { name, .. }
+            | CrateSpec::Forge { name, .. }
+            | CrateSpec::LocalDir { name, .. } => name.as_deref(),
+            CrateSpec::Custom { .. } => None,
+        }
+    }
+
+    /// Whether the spec's version requirement only allows a single version, as `=1.2.3` does.
+    pub fn is_exact_version(&self) -> bool {
+        let version = match self {
+            CrateSpec::CratesIo { version, .. }
+            | CrateSpec::Registry { version, .. }
+            | CrateSpec::Git { version, .. }
+            | CrateSpec::Forge { version, .. }
+            | CrateSpec::LocalDir { version, .. }
+            | CrateSpec::Custom { version, .. } => version,
+        };
+
+        version.as_ref().is_some_and(|version| {
+            matches!(
+                version.comparators.as_slice(),
+                [comparator] if comparator.op == Op::Exact && comparator.patch.is_some()
+            )
+        })
+    }
+
     /// Get the arguments that should be passed to the executed binary.
     ///
     /// For the special case of `cgx cargo <subcommand>`, the first argument is consumed
     /// as part of the crate spec (to form `cargo-<subcommand>`), so we skip it.
     /// Otherwise, all trailing args are passed to the binary.
-    pub fn get_binary_args(args: &CliArgs) -> Vec<std::ffi::OsString> {
+    ///
+    /// With `--args-file`, the arguments are read from that file instead, and it's an error for
+    /// there to be trailing args as well.
+    pub fn get_binary_args(args: &CliArgs) -> Result<Vec<std::ffi::OsString>> {
         let skip = if args.crate_spec.as_deref() == Some("cargo") && !args.args.is_empty() {
             // Skip the first arg (the cargo subcommand name)
             1
@@ -386,11 +749,42 @@ impl CrateSpec {
             0
         };
 
-        args.args
-            .iter()
-            .skip(skip)
-            .map(std::ffi::OsString::from)
-            .collect()
+        match &args.args_file {
+            Some(path) => {
+                if args.args.len() > skip {
+                    return error::ConflictingBinaryArgsSnafu.fail();
+                }
+
+                Ok(Self::read_args_file(path)?
+                    .into_iter()
+                    .map(std::ffi::OsString::from)
+                    .collect())
+            }
+            None => Ok(args
+                .args
+                .iter()
+                .skip(skip)
+                .map(std::ffi::OsString::from)
+                .collect()),
+        }
+    }
+
+    /// Read the arguments in an `--args-file`: a JSON array of strings if the file's name ends in
+    /// `.json`, or else one argument per line.
+    ///
+    /// The format goes by the name rather than the contents, so that a line-per-argument file
+    /// whose first argument starts with `[` is read as written.
+    fn read_args_file(path: &Path) -> Result<Vec<String>> {
+        let contents = std::fs::read_to_string(path).context(error::IoSnafu { path })?;
+
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+        {
+            serde_json::from_str(&contents).context(error::InvalidArgsFileSnafu { path })
+        } else {
+            Ok(contents.lines().map(String::from).collect())
+        }
     }
 }
 
@@ -517,7 +911,7 @@ mod tests {
     use super::*;
     use crate::{
         cli::CliArgs,
-        config::{Config, ToolConfig},
+        config::{Config, ToolChannel, ToolConfig},
     };
     use assert_matches::assert_matches;
 
@@ -546,6 +940,145 @@ mod tests {
         );
     }
 
+    /// Test that the alias and `[tools]` settings that shaped a spec are named along with the
+    /// config file they're from.
+    ///
+    /// Simulated config:
+    /// ```toml
+    /// [aliases]
+    /// rg = "ripgrep"
+    ///
+    /// [tools]
+    /// ripgrep = { version = "14", registry = "internal" }
+    /// ```
+    ///
+    /// Expected: `cgx rg` names the alias, the pin and the registry; `cgx rg@13` only the alias,
+    /// and `cgx --path . rg` the alias and the pin.
+    #[test]
+    fn test_config_decisions() {
+        let mut config = Config::default();
+        config.aliases.insert("rg".to_string(), "ripgrep".to_string());
+        config.tools.insert(
+            "ripgrep".to_string(),
+            toml::from_str(
+                r#"version = "14"
+registry = "internal""#,
+            )
+            .unwrap(),
+        );
+        for key in ["aliases.rg", "tools.ripgrep.version", "tools.ripgrep.registry"] {
+            config
+                .setting_sources
+                .insert(key.to_string(), "/work/cgx.toml".to_string());
+        }
+        let decision = |key: &str, value: &str| ConfigDecision {
+            key: key.to_string(),
+            value: value.to_string(),
+            source: "/work/cgx.toml".to_string(),
+        };
+
+        let decisions =
+            |args: &[&str]| CrateSpec::config_decisions(&config, &CliArgs::parse_from_test_args(args));
+        assert_eq!(
+            decisions(&["rg"]),
+            vec![
+                decision("aliases.rg", "ripgrep"),
+                decision("tools.ripgrep.version", "14"),
+                decision("tools.ripgrep.registry", "internal"),
+            ]
+        );
+        // A version given on the command line replaces the configured one, but not its registry
+        assert_eq!(
+            decisions(&["rg@13"]),
+            vec![
+                decision("aliases.rg", "ripgrep"),
+                decision("tools.ripgrep.registry", "internal"),
+            ]
+        );
+        assert_eq!(
+            decisions(&["--path", ".", "rg"]),
+            vec![
+                decision("aliases.rg", "ripgrep"),
+                decision("tools.ripgrep.version", "14"),
+            ]
+        );
+        assert_eq!(decisions(&["fd"]), vec![]);
+    }
+
+    /// Test that an `@` suffix takes the full requirement syntax, several clauses included.
+    ///
+    /// Command: `cgx 'ripgrep@>=13.0.1, <15'`
+    ///
+    /// Expected: Both clauses end up in the spec's version requirement.
+    #[test]
+    fn test_at_version_with_several_clauses() {
+        let config = Config::default();
+
+        let args = CliArgs::parse_from_test_args(["ripgrep@>=13.0.1, <15"]);
+        let spec = CrateSpec::load(&config, &args).unwrap();
+
+        assert_matches!(
+            spec,
+            CrateSpec::CratesIo { version: Some(ref v), .. }
+            if v.comparators.len() == 2 && v == &VersionReq::parse(">=13.0.1, <15").unwrap()
+        );
+    }
+
+    /// Test that a URL of a scheme cgx doesn't handle itself is for a source provider, whether
+    /// it's given directly or through an alias.
+    ///
+    /// Simulated config:
+    /// ```toml
+    /// [aliases]
+    /// tool = "mysrc://team/tool"
+    /// ```
+    ///
+    /// Commands: `cgx mysrc://team/tool@=1.2.3`, `cgx tool`
+    ///
+    /// Expected: Both produce a custom spec for the URL, with the version of the first.
+    #[test]
+    fn test_source_provider_url() {
+        let mut config = Config::default();
+        config
+            .aliases
+            .insert("tool".to_string(), "mysrc://team/tool".to_string());
+
+        let args = CliArgs::parse_from_test_args(["mysrc://team/tool@=1.2.3"]);
+        assert_matches!(
+            CrateSpec::load(&config, &args).unwrap(),
+            CrateSpec::Custom { ref url, version: Some(ref v) }
+            if url.as_str() == "mysrc://team/tool" && v == &VersionReq::parse("=1.2.3").unwrap()
+        );
+
+        let args = CliArgs::parse_from_test_args(["tool"]);
+        assert_matches!(
+            CrateSpec::load(&config, &args).unwrap(),
+            CrateSpec::Custom { ref url, version: None } if url.scheme() == "mysrc"
+        );
+    }
+
+    #[test]
+    fn test_version_req_hints() {
+        let hint = |version: &str| {
+            let e = parse_version_req(version).unwrap_err();
+            assert_matches!(e, error::Error::InvalidVersionReq { .. });
+            version_req_hint(version)
+        };
+
+        assert_eq!(hint(">=1.2 <2"), "; did you mean '>=1.2, <2'?");
+        assert_eq!(hint(">= 1.2 < 2"), "; did you mean '>=1.2, <2'?");
+        assert_eq!(hint("v1.4"), "; did you mean '1.4'?");
+        assert_eq!(hint("==1.2.3"), "; did you mean '=1.2.3'?");
+        assert_eq!(
+            hint("latest"),
+            "; leave the version out, or use `*`, for the latest version"
+        );
+        assert_eq!(hint("beta"), "");
+
+        assert!(parse_version_req(" ~1.4 ").is_ok());
+        assert!(parse_version_req("=1.2.3").is_ok());
+    }
+
     /// Test that tools can be pinned to specific versions using simple string syntax.
     ///
     /// Simulated config:
@@ -599,6 +1132,14 @@ mod tests {
                 tag: None,
                 rev: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -639,6 +1180,14 @@ mod tests {
                 tag: None,
                 rev: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -681,6 +1230,14 @@ mod tests {
                 tag: None,
                 rev: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -724,6 +1281,14 @@ mod tests {
                 branch: None,
                 rev: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -767,6 +1332,14 @@ mod tests {
                 branch: None,
                 rev: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -810,6 +1383,14 @@ mod tests {
                 branch: None,
                 tag: None,
                 rev: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -852,6 +1433,14 @@ mod tests {
                 tag: None,
                 rev: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -869,6 +1458,83 @@ mod tests {
         );
     }
 
+    /// Test that a tool's channel selects the branch it tracks, while the tool's stable releases
+    /// stay the default.
+    ///
+    /// Simulated config:
+    /// ```toml
+    /// [tools.my-tool]
+    /// version = "1.2"
+    /// channels.nightly = { git = "https://example.com/repo.git" }
+    /// ```
+    ///
+    /// Commands: `cgx my-tool@nightly`, `cgx my-tool --version nightly`, and `cgx my-tool`
+    #[test]
+    fn test_tool_channel() {
+        let mut config = Config::default();
+        config.tools.insert(
+            "my-tool".to_string(),
+            ToolConfig::Detailed {
+                version: Some("1.2".to_string()),
+                git: None,
+                branch: None,
+                registry: None,
+                features: None,
+                tag: None,
+                rev: None,
+                path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: Some(
+                    [(
+                        "nightly".to_string(),
+                        ToolChannel {
+                            git: Some("https://example.com/repo.git".to_string()),
+                            branch: None,
+                            refresh: None,
+                        },
+                    )]
+                    .into(),
+                ),
+                helpers: None,
+                feature_hints: None,
+                preset: None,
+            },
+        );
+
+        for args in [
+            ["my-tool@nightly"].as_slice(),
+            ["--version", "nightly", "my-tool"].as_slice(),
+        ] {
+            let args = CliArgs::parse_from_test_args(args);
+            let spec = CrateSpec::load(&config, &args).unwrap();
+
+            assert_matches!(
+                spec,
+                CrateSpec::Git {
+                    ref repo,
+                    selector: GitSelector::DefaultBranch,
+                    name: Some(ref n),
+                    version: None
+                } if repo == "https://example.com/repo.git" && n == "my-tool"
+            );
+        }
+
+        let args = CliArgs::parse_from_test_args(["my-tool"]);
+        assert_matches!(
+            CrateSpec::load(&config, &args).unwrap(),
+            CrateSpec::CratesIo { version: Some(_), .. }
+        );
+
+        let args = CliArgs::parse_from_test_args(["my-tool@beta"]);
+        assert_matches!(
+            CrateSpec::load(&config, &args),
+            Err(error::Error::InvalidVersionReq { .. })
+        );
+    }
+
     /// Test that tools can specify git + tag selector in config.
     ///
     /// Simulated config:
@@ -895,6 +1561,14 @@ mod tests {
                 branch: None,
                 rev: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -938,6 +1612,14 @@ mod tests {
                 branch: None,
                 tag: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -1011,6 +1693,46 @@ mod tests {
         );
     }
 
+    /// Test that `--min-version` narrows the version requirement from the config.
+    ///
+    /// Simulated config:
+    /// ```toml
+    /// [tools]
+    /// ripgrep = "14"
+    /// ```
+    ///
+    /// Command: `cgx --min-version 14.1.1 ripgrep`
+    ///
+    /// Expected: Requires a version of ripgrep 14 that's at least 14.1.1.
+    #[test]
+    fn test_min_version_narrows_requirement() {
+        let mut config = Config::default();
+        config
+            .tools
+            .insert("ripgrep".to_string(), ToolConfig::Version("14".to_string()));
+
+        let args = CliArgs::parse_from_test_args(["--min-version", "14.1.1", "ripgrep"]);
+        let spec = CrateSpec::load(&config, &args).unwrap();
+
+        assert_matches!(
+            spec,
+            CrateSpec::CratesIo { ref name, version: Some(ref v) }
+            if name == "ripgrep" && v == &VersionReq::parse("^14, >=14.1.1").unwrap()
+        );
+
+        let args = CliArgs::parse_from_test_args(["--min-version", "1.2.0", "just"]);
+        let spec = CrateSpec::load(&config, &args).unwrap();
+
+        assert_matches!(
+            spec,
+            CrateSpec::CratesIo { version: Some(ref v), .. } if v == &VersionReq::parse(">=1.2.0").unwrap()
+        );
+        assert!(!spec.is_exact_version());
+
+        let args = CliArgs::parse_from_test_args(["just@=1.36.0"]);
+        assert!(CrateSpec::load(&config, &args).unwrap().is_exact_version());
+    }
+
     /// Test that CLI `--registry` flag takes precedence over config git source.
     ///
     /// Simulated config:
@@ -1037,6 +1759,14 @@ mod tests {
                 tag: None,
                 rev: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -1081,6 +1811,14 @@ mod tests {
                 tag: None,
                 rev: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -1201,6 +1939,14 @@ mod tests {
                     tag: None,
                     rev: None,
                     path: None,
+                    argv0: None,
+                    prebuilt_sha256: None,
+                    assets: None,
+                    wrapper: None,
+                    channels: None,
+                    helpers: None,
+                    feature_hints: None,
+                    preset: None,
                 },
             )]
             .into_iter()
@@ -1223,6 +1969,101 @@ mod tests {
         );
     }
 
+    /// Test that a bare crate name yields one candidate per [`Config::source_fallback`] entry.
+    ///
+    /// Simulated config:
+    /// ```toml
+    /// default_registry = "internal"
+    /// source_fallback = ["default-registry", "crates-io", "github:my-org"]
+    /// ```
+    ///
+    /// Command: `cgx my-tool@1.0`
+    ///
+    /// Expected: `internal`, then crates.io, then `github.com/my-org/my-tool`, all with the same
+    /// name and version; [`CrateSpec::load`] returns the first.
+    #[test]
+    fn test_source_fallback_candidates() {
+        let config = Config {
+            default_registry: Some("internal".to_string()),
+            source_fallback: vec![
+                SourceFallback::DefaultRegistry,
+                SourceFallback::CratesIo,
+                SourceFallback::Github {
+                    owner: "my-org".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let args = CliArgs::parse_from_test_args(["my-tool@1.0"]);
+        let candidates = CrateSpec::load_candidates(&config, &args).unwrap();
+        let version = Some(VersionReq::parse("1.0").unwrap());
+
+        assert_eq!(
+            candidates,
+            vec![
+                CrateSpec::Registry {
+                    source: RegistrySource::Named("internal".to_string()),
+                    name: "my-tool".to_string(),
+                    version: version.clone(),
+                },
+                CrateSpec::CratesIo {
+                    name: "my-tool".to_string(),
+                    version: version.clone(),
+                },
+                CrateSpec::Forge {
+                    forge: Forge::GitHub {
+                        custom_url: None,
+                        owner: "my-org".to_string(),
+                        repo: "my-tool".to_string(),
+                    },
+                    selector: GitSelector::DefaultBranch,
+                    name: Some("my-tool".to_string()),
+                    version,
+                },
+            ]
+        );
+        assert_eq!(CrateSpec::load(&config, &args).unwrap(), candidates[0]);
+    }
+
+    /// Test that `default-registry` in [`Config::source_fallback`] is skipped when there is no
+    /// [`Config::default_registry`], rather than meaning crates.io twice.
+    #[test]
+    fn test_source_fallback_skips_unset_default_registry() {
+        let config = Config {
+            source_fallback: vec![SourceFallback::DefaultRegistry, SourceFallback::CratesIo],
+            ..Default::default()
+        };
+
+        let args = CliArgs::parse_from_test_args(["my-tool"]);
+        let candidates = CrateSpec::load_candidates(&config, &args).unwrap();
+
+        assert_matches!(
+            candidates.as_slice(),
+            [CrateSpec::CratesIo { name, version: None }] if name == "my-tool"
+        );
+    }
+
+    /// Test that an explicit source is never subject to [`Config::source_fallback`].
+    #[test]
+    fn test_source_fallback_ignored_with_explicit_source() {
+        let config = Config {
+            source_fallback: vec![SourceFallback::CratesIo],
+            ..Default::default()
+        };
+
+        let args = CliArgs::parse_from_test_args(["--registry", "other", "my-tool"]);
+        let candidates = CrateSpec::load_candidates(&config, &args).unwrap();
+
+        assert_matches!(
+            candidates.as_slice(),
+            [CrateSpec::Registry {
+                source: RegistrySource::Named(reg),
+                ..
+            }] if reg == "other"
+        );
+    }
+
     #[test]
     fn test_repo_url_github_default() {
         let forge = Forge::GitHub {
@@ -1299,6 +2140,14 @@ mod tests {
                 tag: None,
                 rev: None,
                 path: None,
+                argv0: None,
+                prebuilt_sha256: None,
+                assets: None,
+                wrapper: None,
+                channels: None,
+                helpers: None,
+                feature_hints: None,
+                preset: None,
             },
         );
 
@@ -1311,4 +2160,122 @@ mod tests {
             if name == "my-tool"
         );
     }
+
+    mod binary_args {
+        use super::*;
+        use std::ffi::OsString;
+
+        fn write_args_file(name: &str, contents: &str) -> (tempfile::TempDir, PathBuf) {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            (dir, path)
+        }
+
+        #[test]
+        fn test_trailing_args() {
+            let args = CliArgs::parse_from_test_args(["ripgrep", "-i", "pattern"]);
+
+            assert_eq!(
+                CrateSpec::get_binary_args(&args).unwrap(),
+                vec![OsString::from("-i"), OsString::from("pattern")]
+            );
+        }
+
+        #[test]
+        fn test_cargo_subcommand_name_skipped() {
+            let args = CliArgs::parse_from_test_args(["cargo", "deny", "check"]);
+
+            assert_eq!(
+                CrateSpec::get_binary_args(&args).unwrap(),
+                vec![OsString::from("check")]
+            );
+        }
+
+        #[test]
+        fn test_args_file_lines() {
+            let (_dir, path) = write_args_file("args", "--flag\n  two words  \n\n--\n");
+            let args = CliArgs::parse_from_test_args([
+                OsString::from("--args-file"),
+                path.into_os_string(),
+                OsString::from("ripgrep"),
+            ]);
+
+            assert_eq!(
+                CrateSpec::get_binary_args(&args).unwrap(),
+                vec![
+                    OsString::from("--flag"),
+                    OsString::from("  two words  "),
+                    OsString::from(""),
+                    OsString::from("--"),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_args_file_json() {
+            let (_dir, path) = write_args_file("args.json", r#"["-e", "line one\nline two", "--"]"#);
+            let args = CliArgs::parse_from_test_args([
+                OsString::from("--args-file"),
+                path.into_os_string(),
+                OsString::from("cargo"),
+                OsString::from("deny"),
+            ]);
+
+            assert_eq!(
+                CrateSpec::get_binary_args(&args).unwrap(),
+                vec![
+                    OsString::from("-e"),
+                    OsString::from("line one\nline two"),
+                    OsString::from("--"),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_args_file_lines_starting_with_bracket() {
+            let (_dir, path) = write_args_file("args", "[a-z]+\nfile.txt\n");
+            let args = CliArgs::parse_from_test_args([
+                OsString::from("--args-file"),
+                path.into_os_string(),
+                OsString::from("ripgrep"),
+            ]);
+
+            assert_eq!(
+                CrateSpec::get_binary_args(&args).unwrap(),
+                vec![OsString::from("[a-z]+"), OsString::from("file.txt")]
+            );
+        }
+
+        #[test]
+        fn test_args_file_invalid_json() {
+            let (_dir, path) = write_args_file("args.json", "[\"unterminated");
+            let args = CliArgs::parse_from_test_args([
+                OsString::from("--args-file"),
+                path.into_os_string(),
+                OsString::from("ripgrep"),
+            ]);
+
+            assert_matches!(
+                CrateSpec::get_binary_args(&args),
+                Err(error::Error::InvalidArgsFile { .. })
+            );
+        }
+
+        #[test]
+        fn test_args_file_with_trailing_args() {
+            let (_dir, path) = write_args_file("args", "--flag\n");
+            let args = CliArgs::parse_from_test_args([
+                OsString::from("--args-file"),
+                path.into_os_string(),
+                OsString::from("ripgrep"),
+                OsString::from("-i"),
+            ]);
+
+            assert_matches!(
+                CrateSpec::get_binary_args(&args),
+                Err(error::Error::ConflictingBinaryArgs)
+            );
+        }
+    }
 }
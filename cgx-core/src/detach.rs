@@ -0,0 +1,511 @@
+//! Tools started in the background with `--detach`, such as dev servers and file watchers, and the
+//! records `--ps` and `--stop` use to manage them.
+//!
+//! A detached tool runs in a process group of its own with nothing on its standard input, and its
+//! standard output and error are appended to `<name>.stdout.log` and `<name>.stderr.log` in the
+//! `run` directory under the app directory.  Next to them, `<name>.json` (a [`DetachedTool`]) is
+//! its pidfile, recording the process ID and when that process started, which together tell it
+//! apart from a later process given the same ID, along with what was run and where its logs are.
+//! Tools are named after their crate, and only one can run under a name at a time; starting
+//! another while it's still running fails with [`Error::AlreadyDetached`].  Starting one locks
+//! `<name>.lock` from checking for a running tool until its record is written, so that two runs
+//! at once can't both start it.
+//!
+//! Nothing watches a detached tool once cgx has exited, so a record is only found to be stale,
+//! because its tool has exited, when it's listed or stopped.  That's only possible on Unix and
+//! Windows; elsewhere every tool looks like it has exited, and stopping one fails.
+//!
+//! [`Error::AlreadyDetached`]: crate::error::Error::AlreadyDetached
+
+use crate::{
+    Result,
+    config::Config,
+    error,
+    runner::{self, RunLimits},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+#[cfg(any(unix, windows))]
+use std::process::{Command, Stdio};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+};
+use tame_index::utils::flock::{FileLock, LockOptions};
+
+/// The record of a tool started with `--detach`, kept as its pidfile until it's stopped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetachedTool {
+    /// The name the tool was started under, which `--stop` takes.
+    pub name: String,
+
+    /// The process ID of the tool, or of its wrapper if it was run under one.
+    pub pid: u32,
+
+    /// When the process started, as the OS reports it, so that it isn't mistaken for a later
+    /// process given the same ID.  Only `None` if the OS wouldn't say.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process_start: Option<String>,
+
+    /// The binary that was run.
+    pub binary: PathBuf,
+
+    /// The arguments it was run with.
+    pub args: Vec<String>,
+
+    /// When it was started.
+    pub started_at: DateTime<Utc>,
+
+    /// The file its standard output is appended to.
+    pub stdout_log: PathBuf,
+
+    /// The file its standard error is appended to.
+    pub stderr_log: PathBuf,
+}
+
+/// Start the binary in the background under `name`, recording it so that `--ps` and `--stop` can
+/// find it.
+///
+/// Fails with [`Error::AlreadyDetached`] if a tool started under the same name is still running.
+/// The record of one that has since exited is replaced, and its logs appended to.  A name that
+/// couldn't be one, such as one with a path separator, fails with [`Error::InvalidDetachName`].
+///
+/// [`Error::AlreadyDetached`]: crate::error::Error::AlreadyDetached
+/// [`Error::InvalidDetachName`]: crate::error::Error::InvalidDetachName
+#[allow(clippy::too_many_arguments)]
+pub fn start(
+    config: &Config,
+    name: &str,
+    bin_path: &Path,
+    argv0: Option<&OsStr>,
+    env: &[(OsString, OsString)],
+    wrapper: &[OsString],
+    args: &[OsString],
+    limits: &RunLimits,
+) -> Result<DetachedTool> {
+    let record_path = record_path(config, name).ok_or_else(|| {
+        error::InvalidDetachNameSnafu {
+            name: name.to_string(),
+        }
+        .build()
+    })?;
+
+    std::fs::create_dir_all(&config.detach_dir).context(error::IoSnafu {
+        path: config.detach_dir.clone(),
+    })?;
+
+    // Held until the record is written, so that a run starting the same tool at the same time
+    // waits, and then finds it running, rather than starting it again and orphaning this one
+    let _lock = lock_name(config, name)?;
+    if let Some(running) = read_record(&record_path)?.filter(is_running) {
+        return error::AlreadyDetachedSnafu {
+            name: name.to_string(),
+            pid: running.pid,
+        }
+        .fail();
+    }
+
+    let stdout_log = config.detach_dir.join(format!("{name}.stdout.log"));
+    let stderr_log = config.detach_dir.join(format!("{name}.stderr.log"));
+    let open_log = |path: &Path| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(error::IoSnafu { path })
+    };
+
+    let pid = runner::spawn_detached(
+        bin_path,
+        argv0,
+        env,
+        wrapper,
+        args,
+        limits,
+        open_log(&stdout_log)?,
+        open_log(&stderr_log)?,
+    )?;
+
+    let tool = DetachedTool {
+        name: name.to_string(),
+        pid,
+        process_start: process_start(pid),
+        binary: bin_path.to_path_buf(),
+        args: args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect(),
+        started_at: Utc::now(),
+        stdout_log,
+        stderr_log,
+    };
+    write_record(&record_path, &tool)?;
+
+    Ok(tool)
+}
+
+/// The tools started with `--detach` that haven't been stopped, sorted by name, along with whether
+/// each is still running.
+pub fn list(config: &Config) -> Result<Vec<(DetachedTool, bool)>> {
+    let entries = match std::fs::read_dir(&config.detach_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => {
+            return Err(error::Error::Io {
+                path: config.detach_dir.clone(),
+                source,
+            });
+        }
+    };
+
+    let mut tools = Vec::new();
+    for entry in entries {
+        let path = entry
+            .context(error::IoSnafu {
+                path: config.detach_dir.clone(),
+            })?
+            .path();
+        if path.extension() != Some(OsStr::new("json")) {
+            continue;
+        }
+
+        if let Some(tool) = read_record(&path)? {
+            let running = is_running(&tool);
+            tools.push((tool, running));
+        }
+    }
+    tools.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+
+    Ok(tools)
+}
+
+/// Stop the tool started under `name`, if it's still running, and remove its record, returning it
+/// along with whether it was running.
+///
+/// Its logs are left in place.
+pub fn stop(config: &Config, name: &str) -> Result<(DetachedTool, bool)> {
+    let not_detached = || {
+        error::NotDetachedSnafu {
+            name: name.to_string(),
+        }
+        .build()
+    };
+    let record_path = record_path(config, name).ok_or_else(not_detached)?;
+    let tool = read_record(&record_path)?.ok_or_else(not_detached)?;
+
+    let running = is_running(&tool);
+    if running {
+        terminate(tool.pid).map_err(|message| {
+            error::StopFailedSnafu {
+                name: name.to_string(),
+                pid: tool.pid,
+                message,
+            }
+            .build()
+        })?;
+    }
+
+    std::fs::remove_file(&record_path).context(error::IoSnafu {
+        path: record_path.clone(),
+    })?;
+
+    Ok((tool, running))
+}
+
+/// Lock the name `name`, which must already have been checked, against tools being started under
+/// it by other processes, waiting for whichever has it locked, until the lock returned is dropped.
+fn lock_name(config: &Config, name: &str) -> Result<FileLock> {
+    let path = config.detach_dir.join(format!("{name}.lock"));
+    let locked = match tame_index::Path::from_path(&path) {
+        Some(utf8_path) => LockOptions::new(utf8_path).exclusive(false).lock(|_| None),
+        None => Err(tame_index::Error::NonUtf8Path(path.clone())),
+    };
+    locked.context(error::DetachLockSnafu { name, path })
+}
+
+/// The file the record of the tool started under `name` is kept in, or `None` if `name` couldn't
+/// be the name of one, so that a name given to `--stop` can't reach outside the directory.
+fn record_path(config: &Config, name: &str) -> Option<PathBuf> {
+    let valid = !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\']);
+
+    valid.then(|| config.detach_dir.join(format!("{name}.json")))
+}
+
+fn read_record(path: &Path) -> Result<Option<DetachedTool>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(serde_json::from_str(&contents).context(error::JsonSnafu)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(error::Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// Write the record, by way of a temporary file so that `--ps` never sees half of one.
+fn write_record(path: &Path, tool: &DetachedTool) -> Result<()> {
+    let json = serde_json::to_string_pretty(tool).context(error::JsonSnafu)?;
+    let temp_path = path.with_extension("json.tmp");
+    std::fs::write(&temp_path, json).context(error::IoSnafu {
+        path: temp_path.clone(),
+    })?;
+    std::fs::rename(&temp_path, path).context(error::RenameFileSnafu {
+        src: temp_path.clone(),
+        dst: path.to_path_buf(),
+    })
+}
+
+/// Whether the process of `tool` is still running, rather than having exited, perhaps leaving its
+/// ID to another process since.
+fn is_running(tool: &DetachedTool) -> bool {
+    match &tool.process_start {
+        Some(started) => process_start(tool.pid).as_ref() == Some(started),
+        None => pid_exists(tool.pid),
+    }
+}
+
+/// When the process with the ID `pid` started, as the OS reports it, or `None` if there's no such
+/// process or the OS wouldn't say.
+///
+/// An ID is only ever reused by a process that starts later, so this and the ID together identify
+/// a process.
+fn process_start(pid: u32) -> Option<String> {
+    #[cfg(unix)]
+    let output = Command::new("ps")
+        .args(["-o", "lstart=", "-p", &pid.to_string()])
+        .stderr(Stdio::null())
+        .output();
+
+    #[cfg(windows)]
+    let output = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            &format!("(Get-Process -Id {pid}).StartTime.ToUniversalTime().Ticks"),
+        ])
+        .stderr(Stdio::null())
+        .output();
+
+    #[cfg(not(any(unix, windows)))]
+    let output: std::io::Result<std::process::Output> = {
+        let _ = pid;
+        Err(std::io::ErrorKind::Unsupported.into())
+    };
+
+    let output = output.ok().filter(|output| output.status.success())?;
+    let started = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!started.is_empty()).then_some(started)
+}
+
+/// Whether a process with the ID `pid` exists, for a record without the time its process started.
+///
+/// The ID may since have been reused by another process, which makes a tool that has exited look
+/// like it's still running.
+fn pid_exists(pid: u32) -> bool {
+    #[cfg(unix)]
+    let running = Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+
+    #[cfg(windows)]
+    let running = Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {pid}"), "/NH", "/FO", "CSV"])
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains(&format!("\"{pid}\"")));
+
+    #[cfg(not(any(unix, windows)))]
+    let running = {
+        let _ = pid;
+        false
+    };
+
+    running
+}
+
+/// Ask the process with the ID `pid` and the processes it started to exit, returning what went
+/// wrong if that failed.
+///
+/// On Unix the signal goes to the process group a detached tool leads, whose ID is the tool's, so
+/// that a tool run under a wrapper or a shell is stopped along with it.  On Windows the process
+/// and its descendants are ended.
+fn terminate(pid: u32) -> std::result::Result<(), String> {
+    #[cfg(unix)]
+    let output = Command::new("kill")
+        .args(["-TERM", "--", &format!("-{pid}")])
+        .stdin(Stdio::null())
+        .output();
+
+    #[cfg(windows)]
+    let output = Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .stdin(Stdio::null())
+        .output();
+
+    #[cfg(not(any(unix, windows)))]
+    let output: std::io::Result<std::process::Output> = {
+        let _ = pid;
+        Err(std::io::ErrorKind::Unsupported.into())
+    };
+
+    let output = output.map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn names_cannot_reach_outside_the_run_directory() {
+        let (_temp_dir, config) = crate::config::create_test_env();
+
+        assert_eq!(
+            record_path(&config, "bacon"),
+            Some(config.detach_dir.join("bacon.json"))
+        );
+        for name in ["", ".hidden", "../bacon", "a/b", "a\\b"] {
+            assert_eq!(record_path(&config, name), None, "{name}");
+        }
+        assert_matches!(
+            start(
+                &config,
+                "../bacon",
+                Path::new("/bin/sh"),
+                None,
+                &[],
+                &[],
+                &[],
+                &RunLimits::default()
+            ),
+            Err(error::Error::InvalidDetachName { .. })
+        );
+        assert_matches!(stop(&config, "../bacon"), Err(error::Error::NotDetached { .. }));
+        assert_matches!(stop(&config, "bacon"), Err(error::Error::NotDetached { .. }));
+        assert!(list(&config).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tools_whose_pid_was_reused_are_not_running() {
+        let (_temp_dir, config) = crate::config::create_test_env();
+        std::fs::create_dir_all(&config.detach_dir).unwrap();
+
+        // This test's own process stands in for one that was given the ID of a tool that exited
+        let pid = std::process::id();
+        assert!(process_start(pid).is_some());
+        let tool = DetachedTool {
+            name: "reused".to_string(),
+            pid,
+            process_start: Some("Thu Jan  1 00:00:00 1970".to_string()),
+            binary: PathBuf::from("/bin/sh"),
+            args: Vec::new(),
+            started_at: Utc::now(),
+            stdout_log: config.detach_dir.join("reused.stdout.log"),
+            stderr_log: config.detach_dir.join("reused.stderr.log"),
+        };
+        write_record(&record_path(&config, "reused").unwrap(), &tool).unwrap();
+
+        assert_eq!(list(&config).unwrap(), vec![(tool.clone(), false)]);
+        assert_eq!(stop(&config, "reused").unwrap(), (tool, false));
+        assert!(list(&config).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn detached_tools_are_listed_until_stopped() {
+        let (_temp_dir, config) = crate::config::create_test_env();
+        let args = [
+            OsString::from("-c"),
+            OsString::from("echo started; exec sleep 30"),
+        ];
+        let start_sleeper = || {
+            start(
+                &config,
+                "sleeper",
+                Path::new("/bin/sh"),
+                None,
+                &[],
+                &[],
+                &args,
+                &RunLimits::default(),
+            )
+        };
+
+        let tool = start_sleeper().unwrap();
+        assert_eq!(tool.args, ["-c", "echo started; exec sleep 30"]);
+        assert_matches!(
+            start_sleeper(),
+            Err(error::Error::AlreadyDetached { pid, .. }) if pid == tool.pid
+        );
+        assert_eq!(list(&config).unwrap(), vec![(tool.clone(), true)]);
+
+        // The shell writes to its log soon after it starts, but not necessarily before now
+        let mut logged = String::new();
+        for _ in 0..50 {
+            logged = std::fs::read_to_string(&tool.stdout_log).unwrap();
+            if !logged.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        assert_eq!(logged, "started\n");
+
+        let (stopped, was_running) = stop(&config, "sleeper").unwrap();
+        assert_eq!((stopped, was_running), (tool, true));
+        assert!(list(&config).unwrap().is_empty());
+        assert_matches!(stop(&config, "sleeper"), Err(error::Error::NotDetached { .. }));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn concurrent_starts_under_one_name_start_one_tool() {
+        let (_temp_dir, config) = crate::config::create_test_env();
+        let args = [OsString::from("-c"), OsString::from("exec sleep 30")];
+
+        let started = std::thread::scope(|scope| {
+            let starts = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        start(
+                            &config,
+                            "racer",
+                            Path::new("/bin/sh"),
+                            None,
+                            &[],
+                            &[],
+                            &args,
+                            &RunLimits::default(),
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            starts
+                .into_iter()
+                .map(|start| start.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let tools = started
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+            .collect::<Vec<_>>();
+        assert_eq!(tools.len(), 1, "{started:?}");
+        for result in &started {
+            assert_matches!(result, Ok(_) | Err(error::Error::AlreadyDetached { .. }));
+        }
+        assert_eq!(list(&config).unwrap(), vec![(tools[0].clone(), true)]);
+        stop(&config, "racer").unwrap();
+    }
+}
@@ -8,6 +8,7 @@ use crate::{
     git::{GitClient, GitSelector},
     http::HttpClient,
     registry::{DownloadUrlLookup, RegistryClient},
+    source_provider::SourceProviders,
 };
 use semver::Version;
 use snafu::ResultExt;
@@ -154,8 +155,11 @@ pub(crate) fn create_downloader(
     cache: Cache,
     git_client: GitClient,
     http_client: HttpClient,
+    source_providers: SourceProviders,
 ) -> impl CrateDownloader {
-    DefaultCrateDownloader::new(cache, config, git_client, http_client)
+    let mut downloader = DefaultCrateDownloader::new(cache, config, git_client, http_client);
+    downloader.source_providers = source_providers;
+    downloader
 }
 
 /// Default implementation of [`CrateDownloader`] that performs actual network requests
@@ -166,6 +170,7 @@ struct DefaultCrateDownloader {
     config: Config,
     git_client: GitClient,
     http_client: HttpClient,
+    source_providers: SourceProviders,
 }
 
 impl DefaultCrateDownloader {
@@ -176,6 +181,7 @@ impl DefaultCrateDownloader {
             config,
             git_client,
             http_client,
+            source_providers: SourceProviders::default(),
         }
     }
 
@@ -187,9 +193,29 @@ impl DefaultCrateDownloader {
         version: &Version,
         source: Option<&RegistrySource>,
     ) -> Result<()> {
-        let registry = RegistryClient::new(source, &self.http_client, &self.config.http)?;
+        let registry = RegistryClient::new(
+            source,
+            &self.config.endpoints,
+            &self.http_client,
+            &self.config.http,
+            &self.git_client,
+        )?;
+
+        // A local registry is on disk, so offline mode doesn't stop crates being extracted from it
+        if self.config.offline && !registry.is_local() {
+            return error::OfflineModeSnafu {
+                name: name.to_string(),
+                version: version.to_string(),
+            }
+            .fail();
+        }
+
         let download_url = match registry.crate_download_url(name, version, self.config.offline)? {
             DownloadUrlLookup::Url(download_url) => download_url,
+            DownloadUrlLookup::LocalFile(path) => {
+                let file = std::fs::File::open(&path).context(error::IoSnafu { path })?;
+                return Self::extract_crate(file, download_path);
+            }
             DownloadUrlLookup::CrateNotFound => {
                 return error::CrateNotFoundInRegistrySnafu {
                     name: name.to_string(),
@@ -212,16 +238,21 @@ impl DefaultCrateDownloader {
             }
         };
 
-        // Download the .crate file
+        // Download the .crate file, extracting it as it's read
         let response = self.http_client.get(&download_url)?;
+        let mut body = self.http_client.download_body(response, &download_url)?;
+        let extracted = Self::extract_crate(&mut body, download_path);
+        body.check()?;
+        extracted
+    }
 
-        // The .crate file is a gzipped tarball, extract it to download_path
-        //
+    /// Extract a `.crate` file, which is a gzipped tarball, to `download_path`.
+    fn extract_crate(crate_file: impl std::io::Read, download_path: &Path) -> Result<()> {
         // Crates.io tarballs have all files nested under a top-level directory named
         // "{name}-{version}/" (e.g., "serde-1.0.200/Cargo.toml"). We need to strip this
         // prefix during extraction so files end up directly in download_path rather than
         // in a subdirectory. This is equivalent to `tar --strip-components=1`.
-        let tar_gz = flate2::read::GzDecoder::new(response);
+        let tar_gz = flate2::read::GzDecoder::new(crate_file);
         let mut archive = tar::Archive::new(tar_gz);
 
         for entry in archive.entries().context(error::TarExtractionSnafu)? {
@@ -259,14 +290,14 @@ impl DefaultCrateDownloader {
             .map(|(path, _commit_hash)| path) // Discard commit hash, downloader only needs path
             .map_err(|e| {
                 // If we're offline and the checkout isn't cached, return OfflineMode error
-                if self.config.offline {
+                if self.config.offline && !matches!(e, error::Error::FeatureDisabled { .. }) {
                     error::OfflineModeSnafu {
                         name: krate.name.clone(),
                         version: krate.version.to_string(),
                     }
                     .build()
                 } else {
-                    e.into()
+                    e
                 }
             })
     }
@@ -304,16 +335,11 @@ impl CrateDownloader for DefaultCrateDownloader {
                 })
             }
 
-            ResolvedSource::CratesIo { .. } | ResolvedSource::Registry { .. } => {
-                // For registry sources, use the cache which handles checking for existing
-                // cached copies and atomically downloading if not present
+            ResolvedSource::Custom { url, revision } => {
                 let cached_krate_path = self
                     .cache
                     .get_or_download_crate(&krate, |download_path| {
-                        // The cache check happens before this closure is called, so if we're here
-                        // it means we need to actually download the crate.
-                        //
-                        // Check offline mode AFTER the cache check, so cached entries work offline
+                        // As with registries, only a source that isn't cached yet needs the network
                         if self.config.offline {
                             return error::OfflineModeSnafu {
                                 name: krate.name.clone(),
@@ -322,6 +348,27 @@ impl CrateDownloader for DefaultCrateDownloader {
                             .fail();
                         }
 
+                        self.source_providers
+                            .download(&url, &revision, &krate, download_path)
+                    })
+                    .map(|cached| cached.crate_path)?;
+
+                Ok(DownloadedCrate {
+                    resolved: krate,
+                    crate_path: cached_krate_path,
+                })
+            }
+
+            ResolvedSource::CratesIo { .. } | ResolvedSource::Registry { .. } => {
+                // For registry sources, use the cache which handles checking for existing
+                // cached copies and atomically downloading if not present
+                let cached_krate_path = self
+                    .cache
+                    .get_or_download_crate(&krate, |download_path| {
+                        // The cache check happens before this closure is called, so if we're here
+                        // it means we need to actually download the crate.  Offline mode is
+                        // checked there, after the cache check, so cached entries work offline.
+                        //
                         // Perform the actual download based on source type
                         match source {
                             ResolvedSource::CratesIo => {
@@ -335,7 +382,7 @@ impl CrateDownloader for DefaultCrateDownloader {
                                 &krate.version,
                                 Some(&registry_source),
                             ),
-                            _ => unreachable!("Git, Forge, and LocalDir handled above"),
+                            _ => unreachable!("Git, Forge, LocalDir, and Custom handled above"),
                         }
                     })
                     .map(|cached| cached.crate_path)?;
@@ -364,7 +411,7 @@ mod tests {
         let (temp_dir, config) = crate::config::create_test_env();
         let reporter = crate::messages::MessageReporter::null();
         let cache = Cache::new(config.clone(), reporter.clone());
-        let git_client = GitClient::new(cache.clone(), reporter, config.http.clone());
+        let git_client = GitClient::new(cache.clone(), reporter, &config);
         let http_client = HttpClient::new(&config.http).unwrap();
         (
             DefaultCrateDownloader::new(cache, config, git_client, http_client),
@@ -379,7 +426,7 @@ mod tests {
         config.offline = true;
         let reporter = crate::messages::MessageReporter::null();
         let cache = Cache::new(config.clone(), reporter.clone());
-        let git_client = GitClient::new(cache.clone(), reporter, config.http.clone());
+        let git_client = GitClient::new(cache.clone(), reporter, &config);
         let http_client = HttpClient::new(&config.http).unwrap();
         (
             DefaultCrateDownloader::new(cache, config, git_client, http_client),
@@ -508,7 +555,7 @@ mod tests {
             };
             let reporter = crate::messages::MessageReporter::null();
             let cache = Cache::new(offline_config.clone(), reporter.clone());
-            let git_client = GitClient::new(cache.clone(), reporter, offline_config.http.clone());
+            let git_client = GitClient::new(cache.clone(), reporter, &offline_config);
             let http_client = HttpClient::new(&offline_config.http).unwrap();
             let offline_downloader =
                 DefaultCrateDownloader::new(cache, offline_config, git_client, http_client);
@@ -533,6 +580,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "git")]
     mod git {
         use super::*;
 
@@ -627,7 +675,7 @@ mod tests {
             };
             let reporter = crate::messages::MessageReporter::null();
             let cache = Cache::new(offline_config.clone(), reporter.clone());
-            let git_client = GitClient::new(cache.clone(), reporter, offline_config.http.clone());
+            let git_client = GitClient::new(cache.clone(), reporter, &offline_config);
             let http_client = HttpClient::new(&offline_config.http).unwrap();
             let offline_downloader =
                 DefaultCrateDownloader::new(cache, offline_config, git_client, http_client);
@@ -657,6 +705,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "git")]
     mod forge {
         use super::*;
         use crate::cratespec::Forge;
@@ -740,7 +789,7 @@ mod tests {
             };
             let reporter = crate::messages::MessageReporter::null();
             let cache = Cache::new(offline_config.clone(), reporter.clone());
-            let git_client = GitClient::new(cache.clone(), reporter, offline_config.http.clone());
+            let git_client = GitClient::new(cache.clone(), reporter, &offline_config);
             let http_client = HttpClient::new(&offline_config.http).unwrap();
             let offline_downloader =
                 DefaultCrateDownloader::new(cache, offline_config, git_client, http_client);
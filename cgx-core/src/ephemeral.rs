@@ -0,0 +1,136 @@
+//! Per-run directories for `cgx --ephemeral`, which leave no binary behind once the tool exits.
+//!
+//! An ephemeral run gets a directory of its own under [`Config::build_dir`], and
+//! [`Config::bin_dir`] and [`Config::run_dir`] are pointed into it, so that the binary that's
+//! built or downloaded, and what's recorded about it, is staged there rather than in the cache.
+//! Crate sources, git checkouts and resolved versions are still cached as usual, subject to the
+//! same settings and `--refresh` flags as any other run, so that running the same tool again
+//! needn't download its source again even though it has to be built again.
+//!
+//! The directory is removed when the [`RunDir`] is dropped, which cgx does once the tool has
+//! exited.  If cgx itself is killed before then, the directory is left behind; those are removed
+//! by a later ephemeral run, once they're older than [`LEFTOVER_AGE`].
+
+use crate::{Result, config::Config, error, helpers::long_path};
+use snafu::ResultExt;
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// The prefix of the name of every per-run directory, by which leftovers are recognized.
+const RUN_DIR_PREFIX: &str = "cgx-run-";
+
+/// How old a per-run directory has to be before another run takes it to have been left behind.
+///
+/// No run is expected to last this long, and a directory that's still in use is at worst removed
+/// from under a tool that's already running, which is unaffected on Unix.
+pub const LEFTOVER_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The directory of a single ephemeral run, which is removed along with everything in it when
+/// this is dropped.
+#[derive(Debug)]
+pub struct RunDir {
+    dir: tempfile::TempDir,
+}
+
+impl RunDir {
+    /// Create a directory for this run, and point `config` at it.
+    ///
+    /// Any per-run directories left behind by earlier runs are removed first.
+    pub fn create(config: &mut Config) -> Result<Self> {
+        let parent = config.build_dir.clone();
+        fs::create_dir_all(long_path(&parent)).with_context(|_| error::IoSnafu { path: &parent })?;
+        remove_leftovers(&parent, RUN_DIR_PREFIX, LEFTOVER_AGE);
+
+        let dir = tempfile::Builder::new()
+            .prefix(RUN_DIR_PREFIX)
+            .tempdir_in(&parent)
+            .with_context(|_| error::TempDirCreationSnafu { parent: &parent })?;
+        tracing::debug!(dir = %dir.path().display(), "Created ephemeral run directory");
+
+        config.bin_dir = dir.path().join("bins");
+        config.run_dir = Some(dir.path().to_path_buf());
+
+        Ok(Self { dir })
+    }
+
+    /// The path of the directory.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+/// Remove the directories in `parent` whose names start with `prefix` and that were last modified
+/// more than `age` ago.
+///
+/// This is only housekeeping, so a directory that can't be removed is logged and left alone.
+pub(crate) fn remove_leftovers(parent: &Path, prefix: &str, age: Duration) {
+    let Ok(entries) = fs::read_dir(long_path(parent)) else {
+        return;
+    };
+
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with(prefix) {
+            continue;
+        }
+
+        let stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| now.duration_since(modified).unwrap_or_default() >= age)
+            .unwrap_or(false);
+        if !stale {
+            continue;
+        }
+
+        let path = entry.path();
+        match fs::remove_dir_all(long_path(&path)) {
+            Ok(()) => tracing::debug!(path = %path.display(), "Removed leftover directory"),
+            Err(e) => tracing::debug!(
+                path = %path.display(),
+                "Unable to remove leftover directory: {}",
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_dir_is_removed_on_drop() {
+        let (_temp_dir, mut config) = crate::config::create_test_env();
+        let cache_dir = config.cache_dir.clone();
+
+        let run_dir = RunDir::create(&mut config).unwrap();
+        let path = run_dir.path().to_path_buf();
+        assert!(path.starts_with(&config.build_dir));
+        assert_eq!(config.bin_dir, path.join("bins"));
+        assert_eq!(config.binary_cache_root(), path);
+        assert_eq!(config.cache_dir, cache_dir);
+
+        drop(run_dir);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn only_old_run_dirs_are_leftovers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let leftover = temp_dir.path().join(format!("{}abc", RUN_DIR_PREFIX));
+        let build = temp_dir.path().join("ripgrep-14.1.1");
+        fs::create_dir_all(leftover.join("bins")).unwrap();
+        fs::create_dir_all(&build).unwrap();
+
+        remove_leftovers(temp_dir.path(), RUN_DIR_PREFIX, LEFTOVER_AGE);
+        assert!(leftover.exists());
+
+        remove_leftovers(temp_dir.path(), RUN_DIR_PREFIX, Duration::ZERO);
+        assert!(!leftover.exists());
+        assert!(build.exists());
+    }
+}
@@ -1,6 +1,12 @@
-pub use reqwest::StatusCode;
+use crate::{
+    bin_resolver::ProviderFailure, build_diagnostics::Diagnosis, http::RequestError, timeouts::Phase,
+};
 use snafu::prelude::*;
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+pub use tame_index::external::http::StatusCode;
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub))]
@@ -18,8 +24,21 @@ pub enum Error {
     ))]
     GitSelectorWithoutGitSource,
 
-    #[snafu(display("Invalid version requirement '{version}': {source}"))]
-    InvalidVersionReq { version: String, source: semver::Error },
+    #[snafu(display("Invalid version requirement '{version}': {source}{hint}"))]
+    InvalidVersionReq {
+        version: String,
+        hint: String,
+        source: semver::Error,
+    },
+
+    #[snafu(display("Invalid version requirement '{version}' for `{key}` in {config_file}: {source}{hint}"))]
+    InvalidToolVersion {
+        key: String,
+        config_file: String,
+        version: String,
+        hint: String,
+        source: semver::Error,
+    },
 
     #[snafu(display("Invalid URL '{url}': {source}"))]
     InvalidUrl { url: String, source: url::ParseError },
@@ -48,10 +67,11 @@ pub enum Error {
     PackageNotFoundInWorkspace { name: String, available: Vec<String> },
 
     #[snafu(display(
-        "Ambiguous package name: found {count} packages in workspace, but no name was specified. Specify \
-         which package to use with the 'name' field."
+        "Ambiguous package name: found {count} packages in workspace ({}), but no name was specified. \
+         Specify which package to use with the 'name' field.",
+        candidates.join(", ")
     ))]
-    AmbiguousPackageName { count: usize },
+    AmbiguousPackageName { count: usize, candidates: Vec<String> },
 
     #[snafu(display("The crate '{krate}' does not have any binary targets so it cannot be executed"))]
     NoPackageBinaries { krate: String },
@@ -75,6 +95,19 @@ pub enum Error {
         available: Vec<String>,
     },
 
+    #[snafu(display(
+        "The {kind} target '{target}' of package '{package}' requires features that aren't enabled: {}. Run \
+         with --features {}, or add them to `features` in its [tools] entry",
+        features.join(", "),
+        features.join(",")
+    ))]
+    TargetRequiresFeatures {
+        kind: &'static str,
+        package: String,
+        target: String,
+        features: Vec<String>,
+    },
+
     #[snafu(display("Version mismatch: required version '{requirement}' but found '{found}'"))]
     VersionMismatch {
         requirement: String,
@@ -86,6 +119,9 @@ pub enum Error {
         source: Box<dyn std::error::Error + Send + Sync>,
     },
 
+    #[snafu(display("Unable to authenticate to the git repository '{url}'; tried {attempted}"))]
+    GitAuthFailed { url: String, attempted: String },
+
     #[snafu(display("Failed to query registry: {source}"))]
     Registry { source: tame_index::Error },
 
@@ -139,7 +175,7 @@ pub enum Error {
     OfflineMode { name: String, version: String },
 
     #[snafu(display("Failed to download registry crate: {source}"))]
-    RegistryDownload { source: reqwest::Error },
+    RegistryDownload { source: RequestError },
 
     #[snafu(display("Failed to extract crate tarball: {source}"))]
     TarExtraction { source: std::io::Error },
@@ -157,10 +193,19 @@ pub enum Error {
     BinaryNotFoundInOutput,
 
     #[snafu(display(
-        "cargo build failed with exit code {}",
-        exit_code.map(|c| c.to_string()).unwrap_or_else(|| "unknown".to_string())
+        "cargo build failed with exit code {}{}",
+        exit_code.map(|c| c.to_string()).unwrap_or_else(|| "unknown".to_string()),
+        build_log(log_tail, log_path.as_deref(), diagnoses)
     ))]
-    CargoBuildFailed { exit_code: Option<i32> },
+    CargoBuildFailed {
+        exit_code: Option<i32>,
+        /// The end of cargo's output, including any compiler errors.
+        log_tail: String,
+        /// The file all of cargo's output was saved to, if it could be.
+        log_path: Option<PathBuf>,
+        /// What cargo's output shows to be missing from the system, if anything.
+        diagnoses: Vec<Diagnosis>,
+    },
 
     #[snafu(display("Failed to copy source tree from {} to {}: {}", src.display(), dst.display(), source))]
     CopySourceTree {
@@ -189,6 +234,23 @@ pub enum Error {
     #[snafu(display("Failed to wait for child process: {source}"))]
     WaitFailed { source: std::io::Error },
 
+    #[snafu(display("Failed to kill child process at {}: {source}", path.display()))]
+    KillFailed { path: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Invalid maximum runtime '{value}': {source}"))]
+    InvalidMaxRuntime {
+        value: String,
+        source: humantime::DurationError,
+    },
+
+    #[snafu(display(
+        "Invalid maximum memory '{value}': expected a non-zero byte count with an optional K, M, G, or T suffix"
+    ))]
+    InvalidMaxMemory { value: String },
+
+    #[snafu(display("The {limit} run limit is not supported on this platform"))]
+    RunLimitUnsupported { limit: String },
+
     #[cfg(windows)]
     #[snafu(display("Failed to set up Windows console control handler"))]
     ConsoleHandlerFailed { source: ctrlc::Error },
@@ -205,9 +267,24 @@ pub enum Error {
 
     #[snafu(display(
         "Prebuilt binary required (--prebuilt-binary always) but no prebuilt binary found for crate \
-         '{name}' version '{version}'"
+         '{name}' version '{version}'{}",
+        provider_failures(failures)
     ))]
-    PrebuiltBinaryRequired { name: String, version: String },
+    PrebuiltBinaryRequired {
+        name: String,
+        version: String,
+        failures: Vec<ProviderFailure>,
+    },
+
+    #[snafu(display(
+        "Unable to tell whether crate '{name}' version '{version}' has a prebuilt binary{}",
+        provider_failures(failures)
+    ))]
+    PrebuiltBinaryUnavailable {
+        name: String,
+        version: String,
+        failures: Vec<ProviderFailure>,
+    },
 
     #[snafu(display(
         "Checksum verification failed for downloaded binary: expected {expected}, got {actual}"
@@ -228,13 +305,13 @@ pub enum Error {
     },
 
     #[snafu(display("Failed to download prebuilt binary from {url}: {source}"))]
-    BinaryDownloadFailed { url: String, source: reqwest::Error },
+    BinaryDownloadFailed { url: String, source: RequestError },
 
     #[snafu(display("HTTP {} downloading prebuilt binary from {url}: {source}", status.as_u16()))]
     BinaryDownloadHttpError {
         url: String,
         status: StatusCode,
-        source: reqwest::Error,
+        source: RequestError,
     },
 
     #[snafu(display("Failed to extract binary archive: {source}"))]
@@ -252,7 +329,7 @@ pub enum Error {
     HttpClientBuild { message: String },
 
     #[snafu(display("HTTP request to {url} failed: {source}"))]
-    HttpRequest { url: String, source: reqwest::Error },
+    HttpRequest { url: String, source: RequestError },
 
     #[snafu(display("HTTP {status} from {url}"))]
     HttpStatus { url: String, status: u16 },
@@ -262,14 +339,1355 @@ pub enum Error {
         value: String,
         source: humantime::DurationError,
     },
+
+    #[snafu(display("Unknown error code '{code}'. Error codes look like CGX0012."))]
+    UnknownErrorCode { code: String },
+
+    #[snafu(display("Failed to parse trusted directories in {}: {}", path.display(), source))]
+    TrustStoreParse { path: PathBuf, source: toml::de::Error },
+
+    #[snafu(display("Failed to serialize trusted directories: {source}"))]
+    TrustStoreSerialize { source: toml::ser::Error },
+
+    #[snafu(display(
+        "cargo fetch failed to download dependencies with exit code {}",
+        exit_code.map(|c| c.to_string()).unwrap_or_else(|| "unknown".to_string())
+    ))]
+    CargoFetchFailed { exit_code: Option<i32> },
+
+    #[snafu(display(
+        "Offline mode is enabled, but not all dependencies of {} are available locally",
+        source_dir.display()
+    ))]
+    DependenciesUnavailableOffline { source_dir: PathBuf },
+
+    #[snafu(display(
+        "cargo vendor failed to vendor the dependencies of {} with exit code {}",
+        source_dir.display(),
+        exit_code.map(|c| c.to_string()).unwrap_or_else(|| "unknown".to_string())
+    ))]
+    CargoVendorFailed {
+        source_dir: PathBuf,
+        exit_code: Option<i32>,
+    },
+
+    #[snafu(display("Not using the pre-built binary from {origin}, since {reason}"))]
+    UntrustedPrebuiltBinary { origin: String, reason: String },
+
+    #[snafu(display("{} is not a valid acquisition plan: {message}", path.display()))]
+    InvalidAcquisitionPlan { path: PathBuf, message: String },
+
+    #[snafu(display("Can't put {} on PATH, since its path contains the PATH separator", path.display()))]
+    InvalidShellDir { path: PathBuf },
+
+    #[snafu(display("The CA bundle {} can't be used: {message}", path.display()))]
+    InvalidCaBundle { path: PathBuf, message: String },
+
+    #[snafu(display("Invalid deadline '{value}': {source}"))]
+    InvalidDeadline {
+        value: String,
+        source: humantime::DurationError,
+    },
+
+    #[snafu(display(
+        "The {phase} phase ran out of time, having taken its whole timeout of {}",
+        humantime::format_duration(*timeout)
+    ))]
+    PhaseTimedOut { phase: Phase, timeout: Duration },
+
+    #[snafu(display(
+        "The deadline of {} passed during the {phase} phase",
+        humantime::format_duration(*deadline)
+    ))]
+    DeadlineExceeded { phase: Phase, deadline: Duration },
+
+    #[snafu(display(
+        "No feature preset named '{name}' is defined under [presets]{}",
+        defined_presets(defined)
+    ))]
+    UnknownPreset { name: String, defined: Vec<String> },
+
+    #[snafu(display(
+        "Every matching version of crate '{name}' is yanked, and it has been renamed or replaced by \
+         '{successor}'; run that instead, or set follow_successors = true to have cgx do so"
+    ))]
+    CrateSuperseded { name: String, successor: String },
+
+    #[snafu(display(
+        "Invalid maximum download size '{value}': expected a non-zero byte count with an optional K, M, G, or T suffix"
+    ))]
+    InvalidMaxDownloadSize { value: String },
+
+    #[snafu(display("Failed to read the response from {url}: {source}"))]
+    HttpBodyRead { url: String, source: std::io::Error },
+
+    #[snafu(display(
+        "Download of {url} is {}larger than the maximum download size of {limit} bytes; if that's \
+         expected, raise the limit with --max-download-size or `max_download_size` in the [http] config",
+        size.map(|size| format!("{size} bytes, ")).unwrap_or_default()
+    ))]
+    DownloadTooLarge {
+        url: String,
+        size: Option<u64>,
+        limit: u64,
+    },
+
+    #[snafu(display(
+        "{name} is already running in the background (pid {pid}); stop it with `cgx --stop {name}` first"
+    ))]
+    AlreadyDetached { name: String, pid: u32 },
+
+    #[snafu(display("No tool named '{name}' was started with --detach (see `cgx --ps`)"))]
+    NotDetached { name: String },
+
+    #[snafu(display("Failed to stop {name} (pid {pid}): {message}"))]
+    StopFailed { name: String, pid: u32, message: String },
+
+    #[snafu(display(
+        "{} is a WebAssembly module, and there's no runtime configured or on the PATH to run it with; getting {runtime_crate} to run it with failed: {source}",
+        path.display()
+    ))]
+    NoWasmRuntime {
+        path: PathBuf,
+        runtime_crate: String,
+        source: Box<Error>,
+    },
+
+    #[snafu(display("No source provider is registered for {scheme}:// URLs, such as {url}"))]
+    UnknownSourceScheme { scheme: String, url: String },
+
+    #[snafu(display("The source provider for {url} failed: {source}"))]
+    SourceProviderFailed {
+        url: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Invalid [http.proxies] rule for '{host}': {message}"))]
+    InvalidProxyRule { host: String, message: String },
+
+    #[snafu(display("Invalid --as-of date '{value}', which should be like 2024-06-01: {source}"))]
+    InvalidAsOfDate {
+        value: String,
+        source: chrono::ParseError,
+    },
+
+    #[snafu(display("No version of '{name}' matching '{requirement}' was published before {date}"))]
+    NoVersionAsOf {
+        name: String,
+        requirement: String,
+        date: chrono::NaiveDate,
+    },
+
+    #[snafu(display("Nothing in the git repository {repo} was committed before {date}"))]
+    NoCommitAsOf { repo: String, date: chrono::NaiveDate },
+
+    #[snafu(display("Config file {} included from {} does not exist", path.display(), included_from.display()))]
+    ConfigIncludeNotFound { path: PathBuf, included_from: PathBuf },
+
+    #[snafu(display("Config files include each other: {}", cycle.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(" -> ")))]
+    ConfigIncludeCycle { cycle: Vec<PathBuf> },
+
+    #[snafu(display("Unknown setting '{key}' in config file {file}{hint}"))]
+    UnknownConfigKey { key: String, file: String, hint: String },
+
+    #[snafu(display(
+        "Not installing over {}, which {}",
+        path.display(),
+        owner.as_deref().map_or_else(|| "cgx didn't install".to_string(), |owner| format!("is installed for {owner}"))
+    ))]
+    InstallConflict { path: PathBuf, owner: Option<String> },
+
+    #[snafu(display("No tool named '{name}' was installed with --install (see `cgx --list-install-dir`)"))]
+    NotInstalled { name: String },
+
+    #[snafu(display("Failed to lock the record of installed tools at {}: {source}", path.display()))]
+    InstallManifestLock {
+        path: PathBuf,
+        source: tame_index::Error,
+    },
+
+    #[snafu(display(
+        "'{name}' can't be the name of a tool started with --detach, since its pidfile and logs are named after it"
+    ))]
+    InvalidDetachName { name: String },
+
+    #[snafu(display("Failed to lock the name {name} of a tool started with --detach at {}: {source}", path.display()))]
+    DetachLock {
+        name: String,
+        path: PathBuf,
+        source: tame_index::Error,
+    },
+
+    #[snafu(display("Crate '{name}' not found in any of the fallback sources: {}", sources.join(", ")))]
+    CrateNotFoundInAnySource { name: String, sources: Vec<String> },
+
+    #[snafu(display("No cached build with key '{build_key}' found (see `cgx --list-installed`)"))]
+    PinnedBuildNotFound { build_key: String },
+
+    #[snafu(display("Cached build '{build_key}' is of crate '{actual}', not '{expected}'"))]
+    PinnedBuildCrateMismatch {
+        build_key: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[snafu(display(
+        "Cache directory {} uses layout version {version}, but this version of cgx only supports up \
+         to version {supported}",
+        path.display()
+    ))]
+    CacheLayoutTooNew {
+        path: PathBuf,
+        version: u32,
+        supported: u32,
+    },
+
+    #[snafu(display("Invalid cache layout version '{contents}' in {}", path.display()))]
+    InvalidCacheLayoutVersion { path: PathBuf, contents: String },
+
+    #[snafu(display("Running a tool with a custom argv[0] can't be combined with the max-memory run limit"))]
+    Argv0WithMemoryLimit,
+
+    #[snafu(display("{message}"))]
+    InvalidBatchArgs { message: String },
+
+    #[snafu(display("Invalid entry on line {line_number} of batch file {}", path.display()))]
+    InvalidBatchEntry {
+        path: PathBuf,
+        line_number: usize,
+        source: Box<Error>,
+    },
+
+    #[snafu(display("{failed} of {total} tools in the batch failed"))]
+    BatchFailed { failed: usize, total: usize },
+
+    #[snafu(display("Args file {} is not a valid JSON array of strings", path.display()))]
+    InvalidArgsFile {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[snafu(display("Arguments for the tool were given both on the command line and in --args-file"))]
+    ConflictingBinaryArgs,
+
+    #[snafu(display(
+        "Invalid prebuilt_sha256 digest '{digest}' for target {target} of tool {tool}: expected 64 hex \
+         digits"
+    ))]
+    InvalidPinnedDigest {
+        tool: String,
+        target: String,
+        digest: String,
+    },
+
+    #[snafu(display(
+        "Prebuilt binary of {name} for {platform} doesn't match its pinned digest: expected {expected}, got \
+         {actual}"
+    ))]
+    PinnedDigestMismatch {
+        name: String,
+        platform: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[snafu(display("The operation was cancelled"))]
+    Cancelled,
+
+    #[snafu(display("Invalid asset pattern '{pattern}' for tool {tool}: {source}"))]
+    InvalidAssetPattern {
+        tool: String,
+        pattern: String,
+        source: ignore::Error,
+    },
+
+    #[snafu(display("Failed to stage the assets of {tool} in {}: {source}", dst.display()))]
+    StageAssets {
+        tool: String,
+        dst: PathBuf,
+        source: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
+
+    #[snafu(display("Invalid wrapper command '{wrapper}': {message}"))]
+    InvalidWrapper { wrapper: String, message: String },
+
+    #[snafu(display("'{}' is not a path within the crate's source: {message}", path.display()))]
+    InvalidSourceFilePath { path: PathBuf, message: String },
+
+    #[snafu(display("The crate's source in {} has no file '{}'", source_dir.display(), path.display()))]
+    SourceFileNotFound { path: PathBuf, source_dir: PathBuf },
+
+    #[snafu(display("Running a tool with a custom argv[0] can't be combined with a wrapper command"))]
+    Argv0WithWrapper,
+
+    #[snafu(display("Built for several targets ({targets}), but only one binary can be run; use --no-exec"))]
+    MultipleTargetsNeedNoExec { targets: String },
+
+    #[snafu(display("Only one --target can be given here, not several ({targets})"))]
+    MultipleTargetsNotSupported { targets: String },
+
+    #[snafu(display("Invalid channel '{channel}' of tool '{tool}': {message}"))]
+    InvalidChannel {
+        tool: String,
+        channel: String,
+        message: String,
+    },
+
+    #[snafu(display(
+        "Release archive has several executables named '{binary}' ({}); can't tell which to use",
+        candidates.join(", ")
+    ))]
+    AmbiguousArchiveBinary { binary: String, candidates: Vec<String> },
+
+    #[snafu(display(
+        "An SBOM is required, but the binary of '{name}' version '{version}' has none at {}",
+        sbom_path.display()
+    ))]
+    SbomUnavailable {
+        name: String,
+        version: String,
+        sbom_path: PathBuf,
+    },
+
+    #[snafu(display("Downloaded binary {} is {actual}, which can't run on {target}", path.display()))]
+    BinaryFormatMismatch {
+        path: PathBuf,
+        target: String,
+        actual: String,
+    },
+
+    #[snafu(display(
+        "Downloaded binary {} needs glibc {required}, but this system has glibc {available}",
+        path.display()
+    ))]
+    IncompatibleGlibc {
+        path: PathBuf,
+        required: String,
+        available: String,
+    },
+
+    #[snafu(display("{} is not a valid frozen toolset manifest: {message}", path.display()))]
+    InvalidFrozenManifest { path: PathBuf, message: String },
+
+    #[snafu(display("Unable to install git hook {}: {message}", path.display()))]
+    GitHookInstall { path: PathBuf, message: String },
+
+    #[snafu(display("The git hook {name} command `{command}` failed: {message}"))]
+    GitHookFailed {
+        name: String,
+        command: String,
+        message: String,
+    },
+
+    #[snafu(display("No commands are configured for the git hook {name} under [hooks.git]"))]
+    GitHookNotConfigured { name: String },
+
+    #[snafu(display("'{spec}' is not a tool and version to pin, like 'ripgrep@14'"))]
+    InvalidToolPin { spec: String },
+
+    #[snafu(display("Unable to update config file {}: {message}", path.display()))]
+    ConfigFileEdit { path: PathBuf, message: String },
+
+    #[snafu(display("'{spec}' is not an alias and the crate it stands for, like 'rg=ripgrep'"))]
+    InvalidAlias { spec: String },
+
+    #[snafu(display("Not aliasing '{alias}' to '{target}', because no crate '{target}' could be found"))]
+    AliasTargetNotFound { alias: String, target: String },
+
+    #[snafu(display("The {hook} hook `{command}` failed: {message}"))]
+    HookFailed {
+        hook: String,
+        command: String,
+        message: String,
+    },
+
+    #[snafu(display("{what} needs the `{feature}` feature of cgx-core, which this build doesn't have"))]
+    FeatureDisabled { feature: &'static str, what: String },
+}
+
+/// Extended guidance for an error code, as printed by `cgx --explain`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ErrorExplanation {
+    /// The stable error code, e.g. `CGX0012`.
+    pub code: &'static str,
+
+    /// Name of the [`Error`] variant this code identifies.
+    pub name: &'static str,
+
+    /// What the error means and how it is commonly fixed.
+    pub text: &'static str,
 }
 
-impl From<crate::git::Error> for Error {
-    fn from(e: crate::git::Error) -> Self {
-        Self::Git {
-            source: Box::new(e) as Box<dyn std::error::Error + Send + Sync>,
+impl Error {
+    /// The stable short code identifying this kind of error, e.g. `CGX0012`.
+    ///
+    /// Codes are never renumbered or reused, so they are safe to match on in scripts and to link
+    /// to from documentation.  [`Error::explain`] returns the extended guidance for a code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingCrateParameter => "CGX0001",
+            Self::InvalidRepoFormat { .. } => "CGX0002",
+            Self::GitSelectorWithoutGitSource => "CGX0003",
+            Self::InvalidVersionReq { .. } => "CGX0004",
+            Self::InvalidUrl { .. } => "CGX0005",
+            Self::ConflictingVersions { .. } => "CGX0006",
+            Self::CrateNotFoundInRegistry { .. } => "CGX0007",
+            Self::NoMatchingVersion { .. } => "CGX0008",
+            Self::PackageNotFoundInWorkspace { .. } => "CGX0009",
+            Self::AmbiguousPackageName { .. } => "CGX0010",
+            Self::NoPackageBinaries { .. } => "CGX0011",
+            Self::AmbiguousBinaryTarget { .. } => "CGX0012",
+            Self::RunnableTargetNotFound { .. } => "CGX0013",
+            Self::VersionMismatch { .. } => "CGX0014",
+            Self::Git { .. } => "CGX0015",
+            Self::Registry { .. } => "CGX0016",
+            Self::CargoMetadata { .. } => "CGX0017",
+            Self::CargoTomlNotFound { .. } => "CGX0018",
+            Self::InvalidVersion { .. } => "CGX0019",
+            Self::Io { .. } => "CGX0020",
+            Self::RenameFile { .. } => "CGX0021",
+            Self::CopyBinary { .. } => "CGX0022",
+            Self::TempDirCreation { .. } => "CGX0023",
+            Self::CommandExecution { .. } => "CGX0024",
+            Self::SbomBuilder { .. } => "CGX0025",
+            Self::Json { .. } => "CGX0026",
+            Self::OfflineMode { .. } => "CGX0027",
+            Self::RegistryDownload { .. } => "CGX0028",
+            Self::TarExtraction { .. } => "CGX0029",
+            Self::DownloadUrlUnavailable { .. } => "CGX0030",
+            Self::ExecutableNotFound { .. } => "CGX0031",
+            Self::RustupNotFound { .. } => "CGX0032",
+            Self::BinaryNotFoundInOutput => "CGX0033",
+            Self::CargoBuildFailed { .. } => "CGX0034",
+            Self::CopySourceTree { .. } => "CGX0035",
+            Self::ConfigLoad { .. } => "CGX0036",
+            Self::InvalidConfigValue { .. } => "CGX0037",
+            Self::ConfigExtract { .. } => "CGX0038",
+            Self::ExecFailed { .. } => "CGX0039",
+            Self::SpawnFailed { .. } => "CGX0040",
+            Self::WaitFailed { .. } => "CGX0041",
+            Self::KillFailed { .. } => "CGX0042",
+            Self::InvalidMaxRuntime { .. } => "CGX0043",
+            Self::InvalidMaxMemory { .. } => "CGX0044",
+            Self::RunLimitUnsupported { .. } => "CGX0045",
+            #[cfg(windows)]
+            Self::ConsoleHandlerFailed { .. } => "CGX0046",
+            Self::Etcetera { .. } => "CGX0047",
+            Self::NoProvidersConfigured => "CGX0048",
+            Self::PrebuiltBinaryRequired { .. } => "CGX0049",
+            Self::ChecksumMismatch { .. } => "CGX0050",
+            Self::UnsupportedArchiveFormat { .. } => "CGX0051",
+            Self::GithubApiError { .. } => "CGX0052",
+            Self::QuickinstallApiError { .. } => "CGX0053",
+            Self::BinaryDownloadFailed { .. } => "CGX0054",
+            Self::BinaryDownloadHttpError { .. } => "CGX0055",
+            Self::ArchiveExtractionFailed { .. } => "CGX0056",
+            Self::CargoTomlParse { .. } => "CGX0057",
+            Self::BinstallMetadataInvalid { .. } => "CGX0058",
+            Self::HttpClientBuild { .. } => "CGX0059",
+            Self::HttpRequest { .. } => "CGX0060",
+            Self::HttpStatus { .. } => "CGX0061",
+            Self::InvalidHttpTimeout { .. } => "CGX0062",
+            Self::UnknownErrorCode { .. } => "CGX0063",
+            Self::TrustStoreParse { .. } => "CGX0064",
+            Self::TrustStoreSerialize { .. } => "CGX0065",
+            Self::CargoFetchFailed { .. } => "CGX0066",
+            Self::DependenciesUnavailableOffline { .. } => "CGX0067",
+            Self::CrateNotFoundInAnySource { .. } => "CGX0068",
+            Self::PrebuiltBinaryUnavailable { .. } => "CGX0069",
+            Self::PinnedBuildNotFound { .. } => "CGX0070",
+            Self::PinnedBuildCrateMismatch { .. } => "CGX0071",
+            Self::CacheLayoutTooNew { .. } => "CGX0072",
+            Self::InvalidCacheLayoutVersion { .. } => "CGX0073",
+            Self::Argv0WithMemoryLimit => "CGX0074",
+            Self::InvalidBatchArgs { .. } => "CGX0075",
+            Self::InvalidBatchEntry { .. } => "CGX0076",
+            Self::BatchFailed { .. } => "CGX0077",
+            Self::InvalidArgsFile { .. } => "CGX0078",
+            Self::ConflictingBinaryArgs => "CGX0079",
+            Self::InvalidPinnedDigest { .. } => "CGX0080",
+            Self::PinnedDigestMismatch { .. } => "CGX0081",
+            Self::Cancelled => "CGX0082",
+            Self::InvalidAssetPattern { .. } => "CGX0083",
+            Self::StageAssets { .. } => "CGX0084",
+            Self::InvalidWrapper { .. } => "CGX0085",
+            Self::Argv0WithWrapper => "CGX0086",
+            Self::MultipleTargetsNeedNoExec { .. } => "CGX0087",
+            Self::MultipleTargetsNotSupported { .. } => "CGX0088",
+            Self::InvalidChannel { .. } => "CGX0089",
+            Self::AmbiguousArchiveBinary { .. } => "CGX0090",
+            Self::SbomUnavailable { .. } => "CGX0091",
+            Self::BinaryFormatMismatch { .. } => "CGX0092",
+            Self::InvalidToolPin { .. } => "CGX0093",
+            Self::ConfigFileEdit { .. } => "CGX0094",
+            Self::HookFailed { .. } => "CGX0095",
+            Self::FeatureDisabled { .. } => "CGX0096",
+            Self::IncompatibleGlibc { .. } => "CGX0097",
+            Self::InvalidFrozenManifest { .. } => "CGX0098",
+            Self::GitHookInstall { .. } => "CGX0099",
+            Self::GitHookFailed { .. } => "CGX0100",
+            Self::GitHookNotConfigured { .. } => "CGX0101",
+            Self::TargetRequiresFeatures { .. } => "CGX0102",
+            Self::GitAuthFailed { .. } => "CGX0103",
+            Self::InvalidAlias { .. } => "CGX0104",
+            Self::AliasTargetNotFound { .. } => "CGX0105",
+            Self::InvalidToolVersion { .. } => "CGX0106",
+            Self::InvalidSourceFilePath { .. } => "CGX0107",
+            Self::SourceFileNotFound { .. } => "CGX0108",
+            Self::CargoVendorFailed { .. } => "CGX0109",
+            Self::UntrustedPrebuiltBinary { .. } => "CGX0110",
+            Self::InvalidAcquisitionPlan { .. } => "CGX0111",
+            Self::InvalidShellDir { .. } => "CGX0112",
+            Self::InvalidCaBundle { .. } => "CGX0113",
+            Self::InvalidDeadline { .. } => "CGX0114",
+            Self::PhaseTimedOut { .. } => "CGX0115",
+            Self::DeadlineExceeded { .. } => "CGX0116",
+            Self::UnknownPreset { .. } => "CGX0117",
+            Self::CrateSuperseded { .. } => "CGX0118",
+            Self::InvalidMaxDownloadSize { .. } => "CGX0119",
+            Self::HttpBodyRead { .. } => "CGX0120",
+            Self::DownloadTooLarge { .. } => "CGX0121",
+            Self::AlreadyDetached { .. } => "CGX0122",
+            Self::NotDetached { .. } => "CGX0123",
+            Self::StopFailed { .. } => "CGX0124",
+            Self::NoWasmRuntime { .. } => "CGX0125",
+            Self::UnknownSourceScheme { .. } => "CGX0126",
+            Self::SourceProviderFailed { .. } => "CGX0127",
+            Self::InvalidProxyRule { .. } => "CGX0128",
+            Self::InvalidAsOfDate { .. } => "CGX0129",
+            Self::NoVersionAsOf { .. } => "CGX0130",
+            Self::NoCommitAsOf { .. } => "CGX0131",
+            Self::ConfigIncludeNotFound { .. } => "CGX0132",
+            Self::ConfigIncludeCycle { .. } => "CGX0133",
+            Self::UnknownConfigKey { .. } => "CGX0134",
+            Self::InstallConflict { .. } => "CGX0135",
+            Self::NotInstalled { .. } => "CGX0136",
+            Self::InstallManifestLock { .. } => "CGX0137",
+            Self::InvalidDetachName { .. } => "CGX0138",
+            Self::DetachLock { .. } => "CGX0139",
         }
     }
+
+    /// Look up the extended explanation for an error code.  The code is matched
+    /// case-insensitively.
+    pub fn explain(code: &str) -> Option<&'static ErrorExplanation> {
+        EXPLANATIONS
+            .iter()
+            .find(|explanation| explanation.code.eq_ignore_ascii_case(code.trim()))
+    }
+}
+
+static EXPLANATIONS: &[ErrorExplanation] = &[
+    ErrorExplanation {
+        code: "CGX0001",
+        name: "MissingCrateParameter",
+        text: "No crate to run was given on the command line. Pass the crate name as the first positional argument (e.g. `cgx ripgrep`), or use `--path`, `--git`, `--github` or `--gitlab` to point cgx at a source that contains exactly one binary crate.",
+    },
+    ErrorExplanation {
+        code: "CGX0002",
+        name: "InvalidRepoFormat",
+        text: "The value passed to `--github` or `--gitlab` was not of the form `owner/repo`. Pass only the owner and repository name (e.g. `--github BurntSushi/ripgrep`); use `--git <URL>` for a full clone URL.",
+    },
+    ErrorExplanation {
+        code: "CGX0003",
+        name: "GitSelectorWithoutGitSource",
+        text: "`--branch`, `--tag` and `--rev` select a commit in a git repository, so they require a git source. Add `--git`, `--github` or `--gitlab`, or drop the selector and use `@VERSION` to choose a registry version.",
+    },
+    ErrorExplanation {
+        code: "CGX0004",
+        name: "InvalidVersionReq",
+        text: "The version requirement could not be parsed as a semver requirement. Use the same syntax as Cargo.toml dependencies, e.g. `1.2.3`, `=1.2.3`, `^1.2` or `>=1, <2`.",
+    },
+    ErrorExplanation {
+        code: "CGX0005",
+        name: "InvalidUrl",
+        text: "A URL given on the command line or in a config file is malformed. Check for a missing scheme (`https://`), stray whitespace, or unescaped characters.",
+    },
+    ErrorExplanation {
+        code: "CGX0006",
+        name: "ConflictingVersions",
+        text: "The crate version was given twice, once as an `@VERSION` suffix and once with `--version`, and the two disagree. Specify the version only once, preferably with the `@VERSION` suffix.",
+    },
+    ErrorExplanation {
+        code: "CGX0007",
+        name: "CrateNotFoundInRegistry",
+        text: "The registry has no crate with this name. Check the spelling (crate names treat `-` and `_` the same, but are otherwise exact), and if the crate lives in an alternate registry select it with `--registry` or `--index`.",
+    },
+    ErrorExplanation {
+        code: "CGX0008",
+        name: "NoMatchingVersion",
+        text: "The crate exists but no published, non-yanked version satisfies the requested version requirement. Relax the requirement, or check the crate's published versions on its registry page. Versions pinned in a cgx config file also count as requirements.",
+    },
+    ErrorExplanation {
+        code: "CGX0009",
+        name: "PackageNotFoundInWorkspace",
+        text: "The source is a Cargo workspace and none of its members has the requested package name. Pick one of the listed packages as the crate name.",
+    },
+    ErrorExplanation {
+        code: "CGX0010",
+        name: "AmbiguousPackageName",
+        text: "The source is a Cargo workspace with several packages and cgx could not tell which one to run. Name the package explicitly, either as the positional crate name or with the `name` field of a config file tool entry.",
+    },
+    ErrorExplanation {
+        code: "CGX0011",
+        name: "NoPackageBinaries",
+        text: "The selected package is a library only and has no `[[bin]]` or example targets, so there is nothing to execute. Check whether the tool is published under a different crate name (often with a `-cli` suffix).",
+    },
+    ErrorExplanation {
+        code: "CGX0012",
+        name: "AmbiguousBinaryTarget",
+        text: "The package has more than one binary target and does not declare `default-run` in its Cargo.toml. Choose one with `--bin <NAME>`; `cgx --list-targets <CRATE>` shows the available targets. To have cgx pick one instead, set `default_bin_policy` in the config, or pass `--default-bin-policy`, to `match-crate-name` or `first`.",
+    },
+    ErrorExplanation {
+        code: "CGX0013",
+        name: "RunnableTargetNotFound",
+        text: "The binary or example named with `--bin` or `--example` does not exist in the package. Use one of the listed targets; `cgx --list-targets <CRATE>` shows them all.",
+    },
+    ErrorExplanation {
+        code: "CGX0014",
+        name: "VersionMismatch",
+        text: "The crate found at the source has a version that does not satisfy the requested requirement. This usually happens with `--path` or git sources, where the version is whatever the source contains; drop the version requirement or select a different commit.",
+    },
+    ErrorExplanation {
+        code: "CGX0015",
+        name: "Git",
+        text: "An operation on a git repository failed, for example cloning, fetching, or resolving a branch, tag or commit. Check that the URL is reachable and that the selector exists; for private repositories make sure your git credentials are available to cgx.",
+    },
+    ErrorExplanation {
+        code: "CGX0016",
+        name: "Registry",
+        text: "Querying the crate registry index failed. This is usually a network problem or an invalid index URL; check connectivity and proxy settings, or retry with `--offline` if the crate was resolved before.",
+    },
+    ErrorExplanation {
+        code: "CGX0017",
+        name: "CargoMetadata",
+        text: "Running `cargo metadata` on the crate's source failed. Make sure a working `cargo` is on PATH, and that the source's Cargo.toml is valid for the toolchain in use.",
+    },
+    ErrorExplanation {
+        code: "CGX0018",
+        name: "CargoTomlNotFound",
+        text: "The source directory does not contain a Cargo.toml. Point `--path` at the crate or workspace root, or check that the git repository is a Rust project.",
+    },
+    ErrorExplanation {
+        code: "CGX0019",
+        name: "InvalidVersion",
+        text: "A version string could not be parsed as a semver version. Versions must have three numeric components, e.g. `1.2.3`.",
+    },
+    ErrorExplanation {
+        code: "CGX0020",
+        name: "Io",
+        text: "A file system operation failed on the path shown. Check that the path exists, that you have permission to access it, and that the disk is not full.",
+    },
+    ErrorExplanation {
+        code: "CGX0021",
+        name: "RenameFile",
+        text: "Moving a file or directory into place in the cgx cache failed. This can happen when the cache spans file systems or another process holds the file open; check the permissions on the cache directory and retry.",
+    },
+    ErrorExplanation {
+        code: "CGX0022",
+        name: "CopyBinary",
+        text: "Copying the built or downloaded binary into place failed. Check the permissions and free space of the destination directory.",
+    },
+    ErrorExplanation {
+        code: "CGX0023",
+        name: "TempDirCreation",
+        text: "cgx could not create a temporary working directory. Check that the parent directory exists and is writable.",
+    },
+    ErrorExplanation {
+        code: "CGX0024",
+        name: "CommandExecution",
+        text: "An external command that cgx runs (such as `cargo` or `rustup`) could not be started. Make sure the Rust toolchain is installed and on PATH.",
+    },
+    ErrorExplanation {
+        code: "CGX0025",
+        name: "SbomBuilder",
+        text: "Generating the software bill of materials for a build failed. This is likely a bug in cgx; please report it along with the crate being built.",
+    },
+    ErrorExplanation {
+        code: "CGX0026",
+        name: "Json",
+        text: "Serializing or deserializing JSON data failed. If this mentions a cache file, clearing the cgx cache directory usually resolves it.",
+    },
+    ErrorExplanation {
+        code: "CGX0027",
+        name: "OfflineMode",
+        text: "The crate is not in the local cache and offline mode (`--offline` or `offline = true` in config) forbids downloading it. Run once without offline mode to populate the cache.",
+    },
+    ErrorExplanation {
+        code: "CGX0028",
+        name: "RegistryDownload",
+        text: "Downloading the crate tarball from the registry failed. Check network connectivity and proxy configuration, or raise `--http-retries`.",
+    },
+    ErrorExplanation {
+        code: "CGX0029",
+        name: "TarExtraction",
+        text: "The downloaded crate tarball could not be unpacked. It may be truncated or corrupt; clear the cache entry and retry.",
+    },
+    ErrorExplanation {
+        code: "CGX0030",
+        name: "DownloadUrlUnavailable",
+        text: "The registry index does not provide a download location for this crate version. Check the registry's `config.json`, or that the crate version has not been removed.",
+    },
+    ErrorExplanation {
+        code: "CGX0031",
+        name: "ExecutableNotFound",
+        text: "A program cgx needs (for example `cargo`) could not be found. Install the Rust toolchain via rustup, or make sure its `bin` directory is on PATH.",
+    },
+    ErrorExplanation {
+        code: "CGX0032",
+        name: "RustupNotFound",
+        text: "A specific toolchain was requested with `+TOOLCHAIN` or in a config file, but selecting toolchains requires rustup. Install rustup, or remove the toolchain override.",
+    },
+    ErrorExplanation {
+        code: "CGX0033",
+        name: "BinaryNotFoundInOutput",
+        text: "`cargo build` succeeded but did not report building the expected executable. This can happen with unusual build configurations; try selecting the target explicitly with `--bin`.",
+    },
+    ErrorExplanation {
+        code: "CGX0034",
+        name: "CargoBuildFailed",
+        text: "Compiling the crate failed. The end of cargo's output is included in the error, and all of it is saved to the log file it names, under the build directory; common causes are a too-old toolchain, missing system libraries, or a dependency that no longer builds without `--locked`. When the output shows a known system library or tool to be missing, such as OpenSSL, `pkg-config` or `cmake`, the error ends with the command that installs it; alternatively, `--prebuilt-binary always` avoids building at all if the crate publishes pre-built binaries.",
+    },
+    ErrorExplanation {
+        code: "CGX0035",
+        name: "CopySourceTree",
+        text: "Copying the crate's source into the build directory failed. Check the permissions and free space of the build directory.",
+    },
+    ErrorExplanation {
+        code: "CGX0036",
+        name: "ConfigLoad",
+        text: "A cgx config file could not be read or parsed. The message includes the offending file and location; fix the TOML syntax or remove the file.",
+    },
+    ErrorExplanation {
+        code: "CGX0037",
+        name: "InvalidConfigValue",
+        text: "A configuration setting has a value cgx does not accept. The message names the field; see the README for its valid values.",
+    },
+    ErrorExplanation {
+        code: "CGX0038",
+        name: "ConfigExtract",
+        text: "The merged configuration could not be interpreted. This usually means a setting has the wrong type (e.g. a string where a table is expected) or is misspelled.",
+    },
+    ErrorExplanation {
+        code: "CGX0039",
+        name: "ExecFailed",
+        text: "Replacing the cgx process with the tool binary failed. Check that the binary exists and is executable, and that its directory is not mounted `noexec`.",
+    },
+    ErrorExplanation {
+        code: "CGX0040",
+        name: "SpawnFailed",
+        text: "Starting the tool binary as a child process failed. Check that the binary exists and is executable, and that its directory is not mounted `noexec`.",
+    },
+    ErrorExplanation {
+        code: "CGX0041",
+        name: "WaitFailed",
+        text: "cgx lost track of the tool it was running. This is rare and usually indicates the process was interfered with externally.",
+    },
+    ErrorExplanation {
+        code: "CGX0042",
+        name: "KillFailed",
+        text: "cgx tried to terminate the tool after it exceeded a run limit, but could not. The process may already have exited, or may need to be stopped by hand.",
+    },
+    ErrorExplanation {
+        code: "CGX0043",
+        name: "InvalidMaxRuntime",
+        text: "The `--max-runtime` value is not a valid duration. Use a humantime duration such as `30s`, `5m` or `1h 30m`.",
+    },
+    ErrorExplanation {
+        code: "CGX0044",
+        name: "InvalidMaxMemory",
+        text: "The `--max-memory` value is not a valid size. Use a non-zero byte count with an optional `K`, `M`, `G` or `T` suffix, e.g. `512M`.",
+    },
+    ErrorExplanation {
+        code: "CGX0045",
+        name: "RunLimitUnsupported",
+        text: "The requested run limit cannot be enforced on this platform. On Windows that's `--max-memory`, since limiting a process's memory there takes a job object, which cgx cannot create without unsafe code. Remove the limit, or enforce it with an operating system facility instead.",
+    },
+    ErrorExplanation {
+        code: "CGX0046",
+        name: "ConsoleHandlerFailed",
+        text: "cgx could not install its Windows console control handler, which it uses to pass Ctrl-C through to the tool. Retry from a regular console window.",
+    },
+    ErrorExplanation {
+        code: "CGX0047",
+        name: "Etcetera",
+        text: "cgx could not determine your home directory, which it needs to locate its config and cache directories. Set the `HOME` (or on Windows, `USERPROFILE`) environment variable, or set the directories explicitly in config.",
+    },
+    ErrorExplanation {
+        code: "CGX0048",
+        name: "NoProvidersConfigured",
+        text: "Prebuilt binaries are enabled but every binary provider has been disabled, so none can be tried. Enable at least one provider, or set `use_prebuilt_binaries` to `never`.",
+    },
+    ErrorExplanation {
+        code: "CGX0049",
+        name: "PrebuiltBinaryRequired",
+        text: "`--prebuilt-binary always` forbids building from source, and no configured provider had a prebuilt binary for this crate version and platform. Allow building with `--prebuilt-binary auto`, or pick a version that publishes binaries. If providers failed rather than just not having a binary, the message lists them with the kind of failure; \"authentication required\" from GitHub is usually fixed by setting `GITHUB_TOKEN`.",
+    },
+    ErrorExplanation {
+        code: "CGX0050",
+        name: "ChecksumMismatch",
+        text: "A downloaded prebuilt binary did not match its published checksum, so it was rejected. Retry the download; if it persists, the release assets may have been altered and should be reported to the crate's maintainers.",
+    },
+    ErrorExplanation {
+        code: "CGX0051",
+        name: "UnsupportedArchiveFormat",
+        text: "A prebuilt binary was published in an archive format cgx cannot unpack. Build from source with `--prebuilt-binary never`.",
+    },
+    ErrorExplanation {
+        code: "CGX0052",
+        name: "GithubApiError",
+        text: "Querying the GitHub API for release information failed. Unauthenticated requests are rate limited; set `GITHUB_TOKEN`, or retry later.",
+    },
+    ErrorExplanation {
+        code: "CGX0053",
+        name: "QuickinstallApiError",
+        text: "Querying the quickinstall service failed. Retry later, or disable the quickinstall provider in config.",
+    },
+    ErrorExplanation {
+        code: "CGX0054",
+        name: "BinaryDownloadFailed",
+        text: "Downloading a prebuilt binary failed before a response was received. Check network connectivity and proxy configuration.",
+    },
+    ErrorExplanation {
+        code: "CGX0055",
+        name: "BinaryDownloadHttpError",
+        text: "The server returned an HTTP error for a prebuilt binary download. The release asset may have been removed; retry, or build from source with `--prebuilt-binary never`.",
+    },
+    ErrorExplanation {
+        code: "CGX0056",
+        name: "ArchiveExtractionFailed",
+        text: "A downloaded prebuilt binary archive could not be unpacked, or did not contain the expected executable. Retry, or build from source with `--prebuilt-binary never`.",
+    },
+    ErrorExplanation {
+        code: "CGX0057",
+        name: "CargoTomlParse",
+        text: "The crate's Cargo.toml is not valid TOML. Fix the manifest, or select a different version of the crate.",
+    },
+    ErrorExplanation {
+        code: "CGX0058",
+        name: "BinstallMetadataInvalid",
+        text: "The crate's `[package.metadata.binstall]` section is malformed, so its prebuilt binary locations cannot be determined. Build from source with `--prebuilt-binary never`, or report it to the crate's maintainers.",
+    },
+    ErrorExplanation {
+        code: "CGX0059",
+        name: "HttpClientBuild",
+        text: "The HTTP client could not be created from the HTTP configuration. Check the proxy URL and any custom CA certificate settings.",
+    },
+    ErrorExplanation {
+        code: "CGX0060",
+        name: "HttpRequest",
+        text: "An HTTP request failed before a response was received. Check network connectivity, proxy settings, and `--http-timeout`.",
+    },
+    ErrorExplanation {
+        code: "CGX0061",
+        name: "HttpStatus",
+        text: "A server returned an unexpected HTTP status. A 404 usually means the resource does not exist; 401 or 403 point to missing or invalid credentials; 5xx errors are usually transient.",
+    },
+    ErrorExplanation {
+        code: "CGX0062",
+        name: "InvalidHttpTimeout",
+        text: "The HTTP timeout value is not a valid duration. Use a humantime duration such as `30s` or `2m`.",
+    },
+    ErrorExplanation {
+        code: "CGX0063",
+        name: "UnknownErrorCode",
+        text: "The code passed to `cgx --explain` is not a cgx error code. Codes are `CGX` followed by four digits, and are printed alongside every cgx failure.",
+    },
+    ErrorExplanation {
+        code: "CGX0064",
+        name: "TrustStoreParse",
+        text: "The file recording which directories' config files are trusted (`trusted.toml` in the user config directory) is malformed. Fix or delete it; deleting it revokes all trust, and directories can be trusted again with `cgx --trust <DIR>`.",
+    },
+    ErrorExplanation {
+        code: "CGX0065",
+        name: "TrustStoreSerialize",
+        text: "The list of trusted directories could not be written out, usually because a directory path is not valid UTF-8. Trust a directory whose path is valid UTF-8 instead.",
+    },
+    ErrorExplanation {
+        code: "CGX0066",
+        name: "CargoFetchFailed",
+        text: "Downloading the crate's dependencies failed, even after retrying. This is a network or registry problem rather than a compile error; check connectivity and proxy settings, or raise `--http-retries`. Cargo's output above has the details.",
+    },
+    ErrorExplanation {
+        code: "CGX0067",
+        name: "DependenciesUnavailableOffline",
+        text: "Offline mode (`--offline`, `--frozen`, or `offline = true` in config) was requested, but some of the crate's dependencies have never been downloaded. Run once without offline mode to populate cargo's cache, or run `cargo fetch` in the crate's directory.",
+    },
+    ErrorExplanation {
+        code: "CGX0068",
+        name: "CrateNotFoundInAnySource",
+        text: "The crate was looked up in every source listed in `source_fallback` in the config, and none of them has it. Check the spelling of the crate name, and that the registries and forge owners in `source_fallback` are the ones the crate is published to.",
+    },
+    ErrorExplanation {
+        code: "CGX0069",
+        name: "PrebuiltBinaryUnavailable",
+        text: "At least one binary provider failed for a reason other than not having the binary, so whether a prebuilt binary exists is unknown. cgx builds from source instead and asks the providers again next time. The listed failures say what went wrong; \"authentication required\" from GitHub is usually fixed by setting `GITHUB_TOKEN`.",
+    },
+    ErrorExplanation {
+        code: "CGX0070",
+        name: "PinnedBuildNotFound",
+        text: "`--pin-build` names a build that isn't in the build cache. Build keys are reported by `cgx --list-installed` and in the build cache messages of `--message-format json`. Only binaries built from source are cached under a build key, and a build is gone once the cache has been cleared.",
+    },
+    ErrorExplanation {
+        code: "CGX0071",
+        name: "PinnedBuildCrateMismatch",
+        text: "`--pin-build` was given together with a crate name, but the pinned build is of a different crate. Either drop the crate name, or check `cgx --list-installed` for the right build key.",
+    },
+    ErrorExplanation {
+        code: "CGX0072",
+        name: "CacheLayoutTooNew",
+        text: "The cache directory was last used by a newer release of cgx, which laid it out in a way this release doesn't understand. Upgrade cgx, or point this release at a different directory with `--app-dir` or the `cache_dir` and `bin_dir` config settings.",
+    },
+    ErrorExplanation {
+        code: "CGX0073",
+        name: "InvalidCacheLayoutVersion",
+        text: "The `layout-version` file in a cache directory doesn't contain a version number, so cgx can't tell how the directory is laid out. If nothing else writes to that directory, delete the file and cgx will treat the directory as predating layout versioning.",
+    },
+    ErrorExplanation {
+        code: "CGX0074",
+        name: "Argv0WithMemoryLimit",
+        text: "The memory limit is applied by running the tool through `/bin/sh`, which has no portable way to give the tool a different `argv[0]`. Drop either `--max-memory` or `--argv0` (or the tool's `argv0` config setting).",
+    },
+    ErrorExplanation {
+        code: "CGX0075",
+        name: "InvalidBatchArgs",
+        text: "A line of a `--batch` file isn't a valid set of cgx arguments. Each line must hold what would follow `cgx` on the command line, such as `ripgrep@14` or `--features foo my-tool`.",
+    },
+    ErrorExplanation {
+        code: "CGX0076",
+        name: "InvalidBatchEntry",
+        text: "A line of a `--batch` file couldn't be turned into a crate to get, for the reason given after this error. The whole file is checked before anything is resolved or built, so nothing has been done yet; fix that line and run the batch again.",
+    },
+    ErrorExplanation {
+        code: "CGX0077",
+        name: "BatchFailed",
+        text: "At least one of the tools in a `--batch` run could not be resolved or built. The summary printed before this error shows which ones failed and why; the tools that succeeded are ready to use.",
+    },
+    ErrorExplanation {
+        code: "CGX0078",
+        name: "InvalidArgsFile",
+        text: "An `--args-file` starting with `[` is read as a JSON array, and this one isn't an array of strings. Either fix the JSON, or write the file with one argument per line and no leading `[`.",
+    },
+    ErrorExplanation {
+        code: "CGX0079",
+        name: "ConflictingBinaryArgs",
+        text: "With `--args-file`, the file holds every argument passed to the tool, so none may follow the crate on the command line. Move the trailing arguments into the file.",
+    },
+    ErrorExplanation {
+        code: "CGX0080",
+        name: "InvalidPinnedDigest",
+        text: "A digest in the `prebuilt_sha256` table of a tool in the config isn't a SHA256 digest. Each entry maps a target triple to the 64 hex digit SHA256 of the release asset the tool's binary for that target is downloaded in, as printed by `sha256sum`.",
+    },
+    ErrorExplanation {
+        code: "CGX0081",
+        name: "PinnedDigestMismatch",
+        text: "A prebuilt binary was found, but the SHA256 of the release asset it was downloaded in isn't the one pinned for its target in the tool's `prebuilt_sha256` config, so it was not used. Other providers are tried next, and unless `prebuilt_binary` is `always` the tool is built from source if none of them match. If the tool's version was deliberately changed, update the pinned digests to match; otherwise the published binary may have been replaced and should be investigated.",
+    },
+    ErrorExplanation {
+        code: "CGX0082",
+        name: "Cancelled",
+        text: "The program embedding cgx cancelled the operation before it finished. Any cargo process it had started was killed and its temporary files removed; nothing was added to the caches that wasn't complete.",
+    },
+    ErrorExplanation {
+        code: "CGX0083",
+        name: "InvalidAssetPattern",
+        text: "A pattern in the `include` list of a tool's `assets` config isn't a valid glob. Patterns use the same syntax as `.gitignore` files and are relative to the root of the crate's source, like `templates/**` or `completions/*.bash`.",
+    },
+    ErrorExplanation {
+        code: "CGX0084",
+        name: "StageAssets",
+        text: "The tool's binary was ready, but copying the asset files its config asks for from its source into the binary cache failed. Check the permissions and free space of the bin directory; nothing half-copied is left behind, so running cgx again retries.",
+    },
+    ErrorExplanation {
+        code: "CGX0085",
+        name: "InvalidWrapper",
+        text: "The command given with `--wrapper`, in a tool's `wrapper` config, or under `[script_interpreters]` can't be split into words. It is split like a shell would, without running one: quotes must be closed, and a backslash must be followed by the character it escapes.",
+    },
+    ErrorExplanation {
+        code: "CGX0086",
+        name: "Argv0WithWrapper",
+        text: "A wrapper command runs the tool itself, so cgx has no way to give the tool a different `argv[0]`. Drop either the wrapper or `--argv0` (or the tool's `argv0` config setting).",
+    },
+    ErrorExplanation {
+        code: "CGX0087",
+        name: "MultipleTargetsNeedNoExec",
+        text: "Repeating `--target` builds the tool for each of the targets, but there's no single binary to run afterwards, and most of them won't run on this machine anyway. Add `--no-exec` to just build them (and `--out-dir` to collect them in one place), or give a single `--target`.",
+    },
+    ErrorExplanation {
+        code: "CGX0088",
+        name: "MultipleTargetsNotSupported",
+        text: "Several `--target` flags were given somewhere that builds a single binary, such as a line of a `--batch` file. Give each target its own line instead.",
+    },
+    ErrorExplanation {
+        code: "CGX0089",
+        name: "InvalidChannel",
+        text: "A channel in a tool's `channels` config can't be used. A channel is built from a git repository, so either the channel or the tool itself needs a `git` URL, and its name must not be something that could be read as a version requirement (like `1` or `*`), since `tool@<name>` would be ambiguous.",
+    },
+    ErrorExplanation {
+        code: "CGX0090",
+        name: "AmbiguousArchiveBinary",
+        text: "A prebuilt release archive holds more than one executable with the name of the crate's binary, in different directories, so cgx won't guess which is the tool. The message lists them. Build from source with `--prebuilt-binary never`, or report the archive layout to the tool's maintainers.",
+    },
+    ErrorExplanation {
+        code: "CGX0091",
+        name: "SbomUnavailable",
+        text: "`--require-sbom` (or `require_sbom` in the config) is set, but the cached build cgx would run has no SBOM next to it, which happens if the file was deleted or the build was cached by an older release. Run again with `--refresh=binary` to rebuild it along with its SBOM.",
+    },
+    ErrorExplanation {
+        code: "CGX0092",
+        name: "BinaryFormatMismatch",
+        text: "A prebuilt binary a provider offered for this platform turned out to be for another OS or CPU architecture, or not an executable at all (such as an error page saved in its place). cgx moves on to the next provider, or builds from source. The message says what the binary really is; if a tool's release assets are mislabeled, report it to its maintainers.",
+    },
+    ErrorExplanation {
+        code: "CGX0093",
+        name: "InvalidToolPin",
+        text: "`--pin` takes a tool's name and the version requirement to pin it to, separated by `@`, such as `ripgrep@14` or `just@=1.36.0`.",
+    },
+    ErrorExplanation {
+        code: "CGX0094",
+        name: "ConfigFileEdit",
+        text: "`--pin`, `--unpin`, `--alias` or `--unalias` couldn't update the config file it names, because the file isn't valid TOML, its `tools` or `aliases` isn't a table, or, for `--pin`, the tool's entry gets it from git or a local directory and so has no version to pin. Fix the file, or edit the entry by hand.",
+    },
+    ErrorExplanation {
+        code: "CGX0095",
+        name: "HookFailed",
+        text: "A command configured under `[hooks]` couldn't be started or exited with an error, and with `on_failure = \"block\"` (the default) that stops the run, so that a binary a scanner rejected is never run. Its output above should say why. Fix the command, or set `on_failure = \"warn\"` under `[hooks]` to carry on regardless.",
+    },
+    ErrorExplanation {
+        code: "CGX0096",
+        name: "FeatureDisabled",
+        text: "This build of cgx was made from cgx-core without one of its optional features: `git` for crates from git repositories, `http` for reaching registries and downloading anything, `prebuilt-binaries` for downloading prebuilt binaries, or `sbom` for writing SBOMs of builds. The cgx binary has them all by default; a program embedding cgx-core needs to enable the one named in the message.",
+    },
+    ErrorExplanation {
+        code: "CGX0097",
+        name: "IncompatibleGlibc",
+        text: "A prebuilt binary for a glibc (`-linux-gnu`) target was linked against a newer glibc than this system has, so it would fail to start with a `GLIBC_2.xx not found` error. cgx passes it over and looks for a static musl binary instead, or builds from source. To look for musl binaries first, set `libc = \"musl\"` under `[prebuilt_binaries]`.",
+    },
+    ErrorExplanation {
+        code: "CGX0098",
+        name: "InvalidFrozenManifest",
+        text: "A `--batch` file ending in `.toml` or `.json`, or starting with `{` or `[`, is read as a manifest written by `--freeze`, and this one couldn't be parsed. Every tool in it needs an exact `version` and a `sha256`, and can have one source (`registry`, `index`, `git` with its `rev`, or `path`); nothing else is allowed. Write it again with `--freeze`, or give `--batch` a list of tools, one per line.",
+    },
+    ErrorExplanation {
+        code: "CGX0099",
+        name: "GitHookInstall",
+        text: "`--install-git-hooks` has to be run in a git repository, with git on the `PATH`, to find where its hooks go. It won't replace a hook that it didn't install itself, so that a hook set up some other way is never lost; move that hook's commands under `[hooks.git]` in cgx.toml, delete it, and run `--install-git-hooks` again.",
+    },
+    ErrorExplanation {
+        code: "CGX0100",
+        name: "GitHookFailed",
+        text: "One of the commands under `[hooks.git]` for this git hook failed, and the rest weren't run; for `pre-commit` or `pre-push` that stops the commit or push. Its output above should say why. Fix what it found, or run `git` with `--no-verify` to skip the hook this once.",
+    },
+    ErrorExplanation {
+        code: "CGX0101",
+        name: "GitHookNotConfigured",
+        text: "`--git-hook` ran a git hook that has no commands under `[hooks.git]` in the config in effect here. This happens when a hook is removed from cgx.toml but its script is left installed, or the cgx.toml it's in isn't trusted, since `[hooks]` is ignored in an untrusted config. Add the hook back, trust the directory with `--trust`, or delete the script from the repository's hooks directory.",
+    },
+    ErrorExplanation {
+        code: "CGX0102",
+        name: "TargetRequiresFeatures",
+        text: "The binary or example to build has `required-features` in the crate's Cargo.toml that the build doesn't enable, so cargo would leave it out. Enable the listed features with `--features`, or with `features` in the tool's `[tools]` entry so that every run gets them; `--all-features` enables them too.",
+    },
+    ErrorExplanation {
+        code: "CGX0103",
+        name: "GitAuthFailed",
+        text: "The git host refused to let cgx fetch the repository, which usually means it's private. Over SSH (`ssh://` and `git@host:path` URLs), cgx uses whatever `ssh` would, so add the key to the SSH agent with `ssh-add` or configure it in `~/.ssh/config`, and check that `ssh -T git@host` works. Over HTTPS, cgx asks git's credential helpers, so configure one with `git config --global credential.helper`, or give the host a token from an environment variable under `[git.hosts]` in the config. A host that doesn't know the repository may refuse it the same way, so check the URL too.",
+    },
+    ErrorExplanation {
+        code: "CGX0104",
+        name: "InvalidAlias",
+        text: "`--alias` takes the alias and the crate it stands for, separated by `=`, such as `rg=ripgrep` or `deny=cargo-deny`. The crate is given by name alone; a version or source for it goes in its entry under `[tools]`.",
+    },
+    ErrorExplanation {
+        code: "CGX0105",
+        name: "AliasTargetNotFound",
+        text: "`--alias` checks that the crate an alias stands for can be found, the same way running it would look for it, so that a typo isn't written to the config. Check the crate's name, or if it isn't on the default registry, give it a source with an entry under `[tools]` first.",
+    },
+    ErrorExplanation {
+        code: "CGX0106",
+        name: "InvalidToolVersion",
+        text: "A tool's version under `[tools]` in the config file named in the error couldn't be parsed as a semver requirement, so cgx stops rather than ignoring the pin. Use the same syntax as Cargo.toml dependencies: `1.2.3` or `^1.2` for compatible versions, `~1.4` for patch releases of 1.4, `=1.2.3` for exactly that version, `*` for any, or several of these separated by commas, like `>=1.2, <2`.",
+    },
+    ErrorExplanation {
+        code: "CGX0107",
+        name: "InvalidSourceFilePath",
+        text: "`--run-source-file` takes the path of a file relative to the root of the crate's source, such as `scripts/setup.sh`. It can't be an absolute path, or use `..` or a symlink to reach outside the source.",
+    },
+    ErrorExplanation {
+        code: "CGX0108",
+        name: "SourceFileNotFound",
+        text: "The crate's source has no file at the path given to `--run-source-file`. The path is relative to the root of the crate's source, which is where its `Cargo.toml` is; `cgx --fetch-source` prints where that is, to look around in. Files that aren't published with the crate aren't in a crate from a registry, so a script only in the crate's repository needs `--git` or the like.",
+    },
+    ErrorExplanation {
+        code: "CGX0109",
+        name: "CargoVendorFailed",
+        text: "`--vendor` (or `vendor_dependencies = true` in config) has cgx run `cargo vendor` to keep a copy of a crate's dependencies for offline builds, and that failed, usually because the dependencies couldn't be downloaded or the crate's `Cargo.lock` is out of date. The crate is built without vendoring; cargo's output above has the details. It's tried again the next time the crate is built while online.",
+    },
+    ErrorExplanation {
+        code: "CGX0110",
+        name: "UntrustedPrebuiltBinary",
+        text: "`--trust-prebuilt-only-from` (or `trust_prebuilt_only_from` in config) only accepts pre-built binaries from the crate's own repository, and a provider offered one from somewhere else, such as quickinstall or a mirror named in the crate's binstall metadata, or with `repository-release`, one that isn't attached to the release of the crate's version. It was passed over without being used, and the other providers are tried next. A crate that doesn't declare a `repository` can't have a binary trusted this way, so it's built from source.",
+    },
+    ErrorExplanation {
+        code: "CGX0111",
+        name: "InvalidAcquisitionPlan",
+        text: "The file given to `--plan` exists but isn't a plan that cgx wrote, or was written by a release of cgx that plans differently. A plan is only ever written by cgx itself after a successful run, so delete the file, or give `--plan` a path that doesn't exist yet, and the next run writes a new one there.",
+    },
+    ErrorExplanation {
+        code: "CGX0112",
+        name: "InvalidShellDir",
+        text: "`--shell` links the tools' binaries into a directory under the build directory and puts that directory at the front of `PATH`, which can't be done when its path contains the character that separates the directories in `PATH` (`:` on Unix, `;` on Windows). Point `build_dir` in config (or `--app-dir`) somewhere whose path doesn't contain it.",
+    },
+    ErrorExplanation {
+        code: "CGX0113",
+        name: "InvalidCaBundle",
+        text: "`ca_bundle` in the `[http]` config (or `CARGO_HTTP_CAINFO`, when that isn't set) names a file of the root certificates to trust, and that file isn't one cgx can use. It has to hold one or more certificates in PEM form, each between `-----BEGIN CERTIFICATE-----` and `-----END CERTIFICATE-----` lines, as a private CA usually hands them out; convert a DER certificate with `openssl x509 -inform der -in ca.cer -out ca.pem`.",
+    },
+    ErrorExplanation {
+        code: "CGX0114",
+        name: "InvalidDeadline",
+        text: "The `--deadline` value (or `CGX_DEADLINE`) is not a valid duration. Use a humantime duration such as `30s`, `10m` or `1h 30m`.",
+    },
+    ErrorExplanation {
+        code: "CGX0115",
+        name: "PhaseTimedOut",
+        text: "A phase of getting the binary took longer than the timeout the `[timeouts]` config gives it, so cgx cancelled it, killing cargo if it was building, and gave up. A timeout covers every step of its phase for a crate, so `download` counts both the source and any pre-built binary. Raise the timeout if the phase is just slow here, or look at why it's slow: a cold build of a big crate, a slow registry or mirror, or a network that drops connections. Once a binary is cached, later runs skip the download and build phases.",
+    },
+    ErrorExplanation {
+        code: "CGX0116",
+        name: "DeadlineExceeded",
+        text: "The run went past its deadline, from `--deadline`, `CGX_DEADLINE` or `deadline` in the `[timeouts]` config, so cgx cancelled the phase in progress, killing cargo if it was building, and gave up. The deadline counts from when cgx starts and, with `--batch`, covers every crate, but not the tool's own runtime, which `--max-runtime` limits. Raise the deadline, or get the tools ahead of time (say with `cgx --batch` in an earlier, cached CI step) so the run that's short on time finds them cached.",
+    },
+    ErrorExplanation {
+        code: "CGX0117",
+        name: "UnknownPreset",
+        text: "`--preset`, or the `preset` of a tool in `[tools]`, names a feature preset that no config file in effect defines. Presets are tables under `[presets]`, such as `[presets.full]` with `features = [\"a\", \"b\"]`, and like `[tools]` they are only read from trusted config files. Check the name for typos, or define the preset.",
+    },
+    ErrorExplanation {
+        code: "CGX0118",
+        name: "CrateSuperseded",
+        text: "The crate has no versions left that aren't yanked and match the requirement, and it's one that has been renamed or replaced by another crate, which is what its maintainers usually expect to be used now. Run the successor instead, check whether it has the binary you're after under the same name, or set `follow_successors = true` in config to have cgx run it in place of the old crate. If cgx is wrong about the successor, set the crate's entry in the `[successors]` config table to an empty string.",
+    },
+    ErrorExplanation {
+        code: "CGX0119",
+        name: "InvalidMaxDownloadSize",
+        text: "The `--max-download-size` value, or `max_download_size` in the `[http]` config, is not a valid size. Use a non-zero byte count with an optional `K`, `M`, `G` or `T` suffix, e.g. `200M`.",
+    },
+    ErrorExplanation {
+        code: "CGX0120",
+        name: "HttpBodyRead",
+        text: "The server responded, but the connection failed while the body of the response was being read. This is usually a transient network problem; retry, or check proxy settings and `--http-timeout`.",
+    },
+    ErrorExplanation {
+        code: "CGX0121",
+        name: "DownloadTooLarge",
+        text: "A download, of a pre-built binary or of a crate's source, was larger than the maximum download size (1G unless configured otherwise), so cgx refused it, or stopped reading it once it went past the limit. This usually means the wrong release asset was matched, or a server is misbehaving. If the download really is that large, raise the limit with `--max-download-size`, `CGX_MAX_DOWNLOAD_SIZE` or `max_download_size` in the `[http]` config.",
+    },
+    ErrorExplanation {
+        code: "CGX0122",
+        name: "AlreadyDetached",
+        text: "A tool started with `--detach` is still running under the same name, and only one can run under a name at a time, since its pidfile and logs are named after it. Stop it with `cgx --stop <name>` and start it again, or leave it running; `cgx --ps` lists what is running.",
+    },
+    ErrorExplanation {
+        code: "CGX0123",
+        name: "NotDetached",
+        text: "`cgx --stop` was given a name that no tool was started under with `--detach`, or whose record has already been removed by an earlier `--stop`. `cgx --ps` lists the names of the tools started that way. Tools are named after their crate, or after their binary if the crate spec has no name.",
+    },
+    ErrorExplanation {
+        code: "CGX0124",
+        name: "StopFailed",
+        text: "cgx couldn't stop a tool started with `--detach`: sending it a termination signal (`kill` on Unix, `taskkill` on Windows) failed, usually because it's now running as another user. Its record is kept so that `cgx --ps` still shows it; stop the process by hand, then run `cgx --stop` again to remove the record.",
+    },
+    ErrorExplanation {
+        code: "CGX0125",
+        name: "NoWasmRuntime",
+        text: "The tool is a WebAssembly (WASI) binary, such as one built with `--target wasm32-wasip1`, which runs under a WebAssembly runtime rather than directly. With no `wasm_runtime` in the config and neither `wasmtime` nor `wasmer` on the `PATH`, cgx tried to get wasmtime the same way it gets any other tool, from the `wasmtime-cli` crate, and that failed for the reason given. Install a runtime, or set `wasm_runtime` to the command that runs a module, like `wasmtime run --dir=.`.",
+    },
+    ErrorExplanation {
+        code: "CGX0126",
+        name: "UnknownSourceScheme",
+        text: "The crate was given as a URL of a scheme cgx doesn't handle itself, which is how crates from a custom source provider are named, but no provider for that scheme is registered. Source providers are registered by programs that embed cgx-core, with `CgxBuilder::source_provider`; the `cgx` binary has none of its own. Check the scheme for typos, or use the program that provides it.",
+    },
+    ErrorExplanation {
+        code: "CGX0127",
+        name: "SourceProviderFailed",
+        text: "The custom source provider registered for the crate's URL scheme couldn't resolve the crate, or couldn't provide its source. The message after the URL is the provider's own; what it means depends on the provider, such as the crate not existing at that URL or the service behind the provider being unreachable.",
+    },
+    ErrorExplanation {
+        code: "CGX0128",
+        name: "InvalidProxyRule",
+        text: "A rule in the `[http.proxies]` config table is invalid. Each key must be a host pattern as in `NO_PROXY`: a host name, a domain (optionally with a leading `.`), an IP address, a CIDR block like `10.0.0.0/8`, or `*`. Each value must be the URL of a proxy, with its scheme, like `http://proxy.example.com:3128` or `socks5://localhost:1080`, or `direct` to reach matching hosts without a proxy.",
+    },
+    ErrorExplanation {
+        code: "CGX0129",
+        name: "InvalidAsOfDate",
+        text: "The `--as-of` value is not a valid date. Give a calendar date as year, month and day, like `2024-06-01`; crates are resolved as they were at the start of that day, UTC.",
+    },
+    ErrorExplanation {
+        code: "CGX0130",
+        name: "NoVersionAsOf",
+        text: "With `--as-of`, a crate from crates.io resolves to the newest version satisfying its requirement that was published before the date, and none was: either the crate didn't exist yet, or every version published by then is outside the requirement or has since been yanked. Give a later date, or relax the version requirement.",
+    },
+    ErrorExplanation {
+        code: "CGX0131",
+        name: "NoCommitAsOf",
+        text: "With `--as-of`, a crate from a git repository or forge with no branch, tag or commit given resolves to the newest release tag, or else the last commit on the default branch, from before the date, and the repository has nothing that old. Give a later date, or pick a branch, tag or commit explicitly, which `--as-of` leaves as it is.",
+    },
+    ErrorExplanation {
+        code: "CGX0132",
+        name: "ConfigIncludeNotFound",
+        text: "A config file's `include` names a file that does not exist. Paths in `include` are relative to the directory of the file that includes them; fix the path or remove it from the list.",
+    },
+    ErrorExplanation {
+        code: "CGX0133",
+        name: "ConfigIncludeCycle",
+        text: "Config files include each other, directly or through other included files, so there is no order to merge them in. The message lists the files in the cycle; remove one of the `include` entries that closes it.",
+    },
+    ErrorExplanation {
+        code: "CGX0134",
+        name: "UnknownConfigKey",
+        text: "A config file has a setting cgx does not know, most often a typo such as `tool` for `tools`. Rather than ignore it, cgx stops so the mistake doesn't go unnoticed. The message names the setting and the file it's in, and the closest setting cgx knows if there is one; see cgx-example.toml for every setting.",
+    },
+    ErrorExplanation {
+        code: "CGX0135",
+        name: "InstallConflict",
+        text: "`--install` would replace a file in the install directory that cgx didn't put there, or that belongs to another installed tool, such as a binary installed by `cargo install`. Remove the file, uninstall the tool it belongs to with `--uninstall`, or set `install_dir` to another directory.",
+    },
+    ErrorExplanation {
+        code: "CGX0136",
+        name: "NotInstalled",
+        text: "`--uninstall` or `--upgrade` was given a tool that isn't recorded as installed. `cgx --list-install-dir` lists the tools that are, by the name of their crate.",
+    },
+    ErrorExplanation {
+        code: "CGX0137",
+        name: "InstallManifestLock",
+        text: "`--install` and `--uninstall` lock cgx's record of installed tools while they change it, so that two at once don't lose each other's changes, and the lock file couldn't be opened. Check that cgx's data directory is writable, and that its path is valid UTF-8.",
+    },
+    ErrorExplanation {
+        code: "CGX0138",
+        name: "InvalidDetachName",
+        text: "A tool started with `--detach` is named after its crate, or after its binary if the crate spec has no name, and its pidfile and logs in the `run` directory are named after that. A name that's empty, starts with `.`, or contains a path separator would put them somewhere else, so it's refused.",
+    },
+    ErrorExplanation {
+        code: "CGX0139",
+        name: "DetachLock",
+        text: "`--detach` locks the name of the tool it starts, from checking that nothing else is running under it until its pidfile is written, so that two runs at once can't both start it, and the lock file in the `run` directory under the app directory couldn't be opened. Check that the directory is writable, and that its path is valid UTF-8.",
+    },
+];
+
+/// The presets that are defined, formatted to be appended to an error message about one that
+/// isn't.
+fn defined_presets(defined: &[String]) -> String {
+    if defined.is_empty() {
+        return String::new();
+    }
+
+    format!("; defined presets: {}", defined.join(", "))
+}
+
+/// The provider failures behind a missing prebuilt binary, formatted to be appended to an error
+/// message.
+fn provider_failures(failures: &[ProviderFailure]) -> String {
+    if failures.is_empty() {
+        return String::new();
+    }
+
+    let failures = failures.iter().map(ToString::to_string).collect::<Vec<_>>();
+    format!("; provider errors: {}", failures.join("; "))
+}
+
+/// The end of a failed build's output and where the rest of it is, formatted to be appended to an
+/// error message.
+fn build_log(log_tail: &str, log_path: Option<&Path>, diagnoses: &[Diagnosis]) -> String {
+    let mut formatted = String::new();
+    if let Some(log_path) = log_path {
+        formatted.push_str(&format!("; full log in {}", log_path.display()));
+    }
+    if !log_tail.is_empty() {
+        formatted.push_str(&format!("\n\n{log_tail}"));
+    }
+    if !diagnoses.is_empty() {
+        formatted.push('\n');
+        for diagnosis in diagnoses {
+            formatted.push_str(&format!("\nhelp: {diagnosis}"));
+        }
+        formatted.push_str(
+            "\nhelp: or, if the crate publishes pre-built binaries, `--prebuilt-binary always` uses one \
+             instead of building it",
+        );
+    }
+    formatted
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_codes_are_unique_and_well_formed() {
+        let mut codes = HashSet::new();
+        let mut names = HashSet::new();
+        for explanation in EXPLANATIONS {
+            assert!(
+                codes.insert(explanation.code),
+                "duplicate code {}",
+                explanation.code
+            );
+            assert!(
+                names.insert(explanation.name),
+                "duplicate name {}",
+                explanation.name
+            );
+            assert_eq!(explanation.code.len(), 7);
+            assert!(explanation.code.starts_with("CGX"));
+            assert!(explanation.code[3..].chars().all(|c| c.is_ascii_digit()));
+            assert!(!explanation.text.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_code_maps_to_matching_explanation() {
+        let errors = [
+            Error::MissingCrateParameter,
+            Error::InvalidRepoFormat {
+                repo: "foo".to_string(),
+            },
+            Error::AmbiguousBinaryTarget {
+                package: "foo".to_string(),
+                available: vec!["a".to_string(), "b".to_string()],
+            },
+            Error::CargoBuildFailed {
+                exit_code: Some(101),
+                log_tail: "error[E0308]: mismatched types".to_string(),
+                log_path: Some(PathBuf::from("/tmp/build.log")),
+                diagnoses: Vec::new(),
+            },
+            Error::NoProvidersConfigured,
+            Error::HttpStatus {
+                url: "https://example.com".to_string(),
+                status: 404,
+            },
+        ];
+
+        for error in errors {
+            let explanation = Error::explain(error.code()).unwrap();
+            assert!(
+                format!("{error:?}").starts_with(explanation.name),
+                "{} maps to {}",
+                explanation.code,
+                explanation.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_explain_is_case_insensitive() {
+        assert_eq!(Error::explain("cgx0001").unwrap().name, "MissingCrateParameter");
+        assert_eq!(Error::explain(" CGX0001 ").unwrap().name, "MissingCrateParameter");
+        assert!(Error::explain("CGX9999").is_none());
+        assert!(Error::explain("E0425").is_none());
+    }
+}
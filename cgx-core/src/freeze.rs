@@ -0,0 +1,282 @@
+//! Freezing the tools in the `[tools]` table to exactly what they resolve to, for `cgx --freeze`.
+//!
+//! A [`FrozenManifest`] gives each tool's exact version and source, down to the commit for one
+//! from git, along with the SHA256 of the binary it got.  It's meant to be committed, and given to
+//! `cgx --batch` on another machine to get the same versions of the same tools from the same
+//! sources (see [`FrozenManifest::batch_lines`]).  The digests record what each binary was here; a
+//! binary built from source elsewhere isn't generally byte for byte the same, so they aren't
+//! checked.  A toolset frozen with `--as-of` records the date it was resolved as of (see
+//! [`crate::as_of`]).
+
+use crate::{
+    Result,
+    cli::FreezeFormat,
+    crate_resolver::{ResolvedCrate, ResolvedSource},
+    cratespec::RegistrySource,
+    error,
+    runner::quote_word,
+};
+use chrono::NaiveDate;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use snafu::ResultExt;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Every tool in a toolset, frozen to exactly what it resolved to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FrozenManifest {
+    /// The date the tools were resolved as of, if they were resolved as of one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<NaiveDate>,
+
+    /// The tools, keyed by name.
+    pub tools: BTreeMap<String, FrozenTool>,
+}
+
+/// One tool in a [`FrozenManifest`].
+///
+/// At most one of the source fields is set; a tool from crates.io has none of them.  They're named
+/// after the fields of a tool in the `[tools]` table, except for [`Self::url`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FrozenTool {
+    /// The exact version the tool resolved to.
+    pub version: Version,
+
+    /// The named registry the tool is from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+
+    /// The URL of the registry index the tool is from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+
+    /// The git repository the tool is from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+
+    /// The commit of [`Self::git`] the tool was built from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+
+    /// The local directory the tool is from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+
+    /// The URL the tool is from, for a source provider (see [`crate::source_provider`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// The SHA256 of the tool's binary.
+    pub sha256: String,
+}
+
+impl FrozenTool {
+    /// Freeze the crate `resolved` to, whose binary is at `binary`.
+    pub fn new(resolved: &ResolvedCrate, binary: &Path) -> Result<Self> {
+        let data = std::fs::read(binary).context(error::IoSnafu { path: binary })?;
+
+        let mut tool = Self {
+            version: resolved.version.clone(),
+            registry: None,
+            index: None,
+            git: None,
+            rev: None,
+            path: None,
+            url: None,
+            sha256: crate::helpers::format_hex_lower(Sha256::digest(&data)),
+        };
+        match &resolved.source {
+            ResolvedSource::CratesIo => {}
+            ResolvedSource::Registry {
+                source: RegistrySource::Named(name),
+            } => tool.registry = Some(name.clone()),
+            ResolvedSource::Registry {
+                source: RegistrySource::IndexUrl(url),
+            } => tool.index = Some(url.to_string()),
+            ResolvedSource::Git { repo, commit } => {
+                tool.git = Some(repo.clone());
+                tool.rev = Some(commit.clone());
+            }
+            ResolvedSource::Forge { forge, commit } => {
+                tool.git = Some(forge.git_url());
+                tool.rev = Some(commit.clone());
+            }
+            ResolvedSource::LocalDir { path } => tool.path = Some(path.clone()),
+            ResolvedSource::Custom { url, .. } => tool.url = Some(url.to_string()),
+        }
+
+        Ok(tool)
+    }
+
+    /// The arguments to `cgx` that get exactly this version of the tool `name`, as a line of a
+    /// `--batch` file.
+    ///
+    /// Words that need it, like a path with spaces in it, are quoted the way a batch file is read.
+    pub fn batch_line(&self, name: &str) -> String {
+        let exact = format!("{}@={}", name, self.version);
+        let words = if let Some(url) = &self.url {
+            vec![format!("{}@={}", url, self.version)]
+        } else {
+            match (&self.registry, &self.index, &self.git, &self.rev, &self.path) {
+                (Some(registry), ..) => vec!["--registry".to_string(), registry.clone(), exact],
+                (_, Some(index), ..) => vec!["--index".to_string(), index.clone(), exact],
+                (_, _, Some(git), Some(rev), _) => vec![
+                    "--git".to_string(),
+                    git.clone(),
+                    "--rev".to_string(),
+                    rev.clone(),
+                    name.to_string(),
+                ],
+                (_, _, Some(git), None, _) => vec!["--git".to_string(), git.clone(), name.to_string()],
+                (.., Some(path)) => vec!["--path".to_string(), path.display().to_string(), name.to_string()],
+                _ => vec![exact],
+            }
+        };
+
+        words
+            .iter()
+            .map(|word| quote_word(word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl FrozenManifest {
+    /// Write the manifest in `format`.
+    pub fn to_string(&self, format: FreezeFormat) -> String {
+        match format {
+            FreezeFormat::Toml => toml::to_string(self).expect("BUG: manifest is always valid TOML"),
+            FreezeFormat::Json => {
+                let mut json =
+                    serde_json::to_string_pretty(self).expect("BUG: manifest is always valid JSON");
+                json.push('\n');
+                json
+            }
+        }
+    }
+
+    /// Read a manifest written by [`Self::to_string`] in either format, from the file at `path`.
+    pub fn parse(path: &Path, contents: &str) -> Result<Self> {
+        let parsed = if contents.trim_start().starts_with('{') {
+            serde_json::from_str(contents).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(contents).map_err(|e| e.to_string())
+        };
+
+        parsed.map_err(|message| error::InvalidFrozenManifestSnafu { path, message }.build())
+    }
+
+    /// Whether the file at `path`, with `contents`, is a manifest rather than a list of tools,
+    /// going by its extension or else by how it starts.
+    pub fn is_manifest(path: &Path, contents: &str) -> bool {
+        let contents = contents.trim_start();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml" | "json") => true,
+            _ => contents.starts_with(['{', '[']) || contents.starts_with("as_of ="),
+        }
+    }
+
+    /// The lines of a `--batch` file that get every tool in the manifest.
+    pub fn batch_lines(&self) -> Vec<String> {
+        self.tools
+            .iter()
+            .map(|(name, tool)| tool.batch_line(name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn tool(version: &str) -> FrozenTool {
+        FrozenTool {
+            version: Version::parse(version).unwrap(),
+            registry: None,
+            index: None,
+            git: None,
+            rev: None,
+            path: None,
+            url: None,
+            sha256: "00".repeat(32),
+        }
+    }
+
+    #[test]
+    fn freezes_source_and_digest() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let binary = temp_dir.path().join("tool");
+        std::fs::write(&binary, b"binary").unwrap();
+
+        let resolved = ResolvedCrate {
+            name: "my-tool".to_string(),
+            version: Version::parse("0.3.1").unwrap(),
+            source: ResolvedSource::Git {
+                repo: "https://example.com/me/my-tool.git".to_string(),
+                commit: "4b825dc642cb6eb9a060e54bf8d69288fbee4904".to_string(),
+            },
+        };
+        let frozen = FrozenTool::new(&resolved, &binary).unwrap();
+
+        assert_eq!(
+            frozen.sha256,
+            "9a3a45d01531a20e89ac6ae10b0b0beb0492acd7216a368aa062d1a5fecaf9cd"
+        );
+        assert_eq!(
+            frozen.batch_line("my-tool"),
+            "--git https://example.com/me/my-tool.git --rev 4b825dc642cb6eb9a060e54bf8d69288fbee4904 my-tool"
+        );
+    }
+
+    #[test]
+    fn round_trips_in_both_formats() {
+        let manifest = FrozenManifest {
+            as_of: NaiveDate::from_ymd_opt(2024, 6, 1),
+            tools: BTreeMap::from([
+                ("ripgrep".to_string(), tool("14.1.1")),
+                (
+                    "taplo-cli".to_string(),
+                    FrozenTool {
+                        registry: Some("internal".to_string()),
+                        ..tool("0.9.3")
+                    },
+                ),
+            ]),
+        };
+
+        for format in [FreezeFormat::Toml, FreezeFormat::Json] {
+            let written = manifest.to_string(format);
+            assert_eq!(FrozenManifest::parse(Path::new("-"), &written).unwrap(), manifest);
+            assert!(FrozenManifest::is_manifest(Path::new("-"), &written));
+        }
+
+        assert_eq!(
+            manifest.batch_lines(),
+            vec!["ripgrep@=14.1.1", "--registry internal taplo-cli@=0.9.3"]
+        );
+        let local = FrozenTool {
+            path: Some(PathBuf::from("/home/me/My Tools/tool")),
+            ..tool("1.0.0")
+        };
+        assert_eq!(local.batch_line("tool"), "--path '/home/me/My Tools/tool' tool");
+        assert_eq!(
+            crate::runner::split_words(&local.batch_line("tool")).unwrap(),
+            ["--path", "/home/me/My Tools/tool", "tool"]
+        );
+        assert!(!FrozenManifest::is_manifest(
+            Path::new("tools.txt"),
+            "ripgrep@14\n"
+        ));
+        assert_matches!(
+            FrozenManifest::parse(Path::new("tools.toml"), "[tools.ripgrep]\nversion = \"14\"\n"),
+            Err(error::Error::InvalidFrozenManifest { .. })
+        );
+    }
+}
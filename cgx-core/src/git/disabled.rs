@@ -0,0 +1,54 @@
+//! The stand-in for the git operations of [`GitClient`] without the `git` feature, which all
+//! fail with [`crate::error::Error::FeatureDisabled`].
+
+use super::{GitClient, GitSelector};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+
+impl GitClient {
+    /// Without the `git` feature there's no git support, so this always fails.
+    pub(crate) fn checkout_ref(&self, url: &str, _selector: GitSelector) -> crate::Result<(PathBuf, String)> {
+        crate::error::FeatureDisabledSnafu {
+            feature: "git",
+            what: format!("Getting a crate from the git repository {}", url),
+        }
+        .fail()
+    }
+
+    /// Without the `git` feature there's no git support, so this always fails.
+    pub(crate) fn fetch_default_branch(&self, url: &str, _offline: bool) -> crate::Result<Option<String>> {
+        crate::error::FeatureDisabledSnafu {
+            feature: "git",
+            what: format!("Reading the git repository {}", url),
+        }
+        .fail()
+    }
+
+    /// Without the `git` feature there's no git support, so this always fails.
+    pub(crate) fn selector_as_of(
+        &self,
+        url: &str,
+        _before: DateTime<Utc>,
+        _offline: bool,
+    ) -> crate::Result<Option<GitSelector>> {
+        crate::error::FeatureDisabledSnafu {
+            feature: "git",
+            what: format!("Getting a crate from the git repository {}", url),
+        }
+        .fail()
+    }
+
+    /// Without the `git` feature there's no git support, so this always fails.
+    pub(crate) fn read_file(&self, url: &str, _commit: &str, _path: &Path) -> crate::Result<Option<Vec<u8>>> {
+        crate::error::FeatureDisabledSnafu {
+            feature: "git",
+            what: format!("Reading the git repository {}", url),
+        }
+        .fail()
+    }
+}
+
+/// Without the `git` feature there are no git failures.
+pub(crate) fn is_missing_repo_or_ref(_e: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    false
+}
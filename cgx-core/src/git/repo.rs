@@ -0,0 +1,1464 @@
+//! The git operations of [`GitClient`], done with gix, which is only compiled with the `git`
+//! feature.
+
+use super::{GitClient, GitSelector};
+use crate::{
+    cancel,
+    config::{GitConfig, GitHostAuth, HttpConfig},
+    messages::GitMessage,
+    proxy_rules::{ProxyRules, Route},
+};
+use backon::{BlockingRetryable, ExponentialBuilder};
+use chrono::{DateTime, Utc};
+use gix::{ObjectId, bstr::BString, protocol::transport::IsSpuriousError, remote::Direction};
+use snafu::{IntoError, ResultExt, prelude::*};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// Errors specific to git operations
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub(crate) enum Error {
+    #[snafu(display("Git commit hash is invalid: {hash}"))]
+    InvalidCommitHash {
+        hash: String,
+        #[snafu(source(from(gix::hash::decode::Error, Box::new)))]
+        source: Box<gix::hash::decode::Error>,
+    },
+
+    #[snafu(display("Failed to initialize bare repository at {}", path.display()))]
+    InitBareRepo {
+        path: PathBuf,
+        #[snafu(source(from(gix::init::Error, Box::new)))]
+        source: Box<gix::init::Error>,
+    },
+
+    #[snafu(display("Failed to open git repository at {}", path.display()))]
+    OpenRepo {
+        path: PathBuf,
+        #[snafu(source(from(gix::open::Error, Box::new)))]
+        source: Box<gix::open::Error>,
+    },
+
+    #[snafu(display("Failed to resolve git selector: {message}"))]
+    ResolveSelector {
+        message: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Failed to fetch ref from '{url}'"))]
+    FetchRef {
+        url: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Failed to read {} from git repository", path.display()))]
+    ReadFile {
+        path: PathBuf,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Failed to checkout from database to {}", path.display()))]
+    CheckoutFromDb {
+        path: PathBuf,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Failed to create git directory at {}", path.display()))]
+    CreateDirectory { path: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Failed to write marker file at {}", path.display()))]
+    WriteMarkerFile { path: PathBuf, source: std::io::Error },
+}
+
+pub(crate) type Result<T> = std::result::Result<T, Error>;
+
+/// The refspec that fetches every tag, into where [`resolve_selector`] looks for them.
+const TAGS_REFSPEC: &str = "+refs/tags/*:refs/remotes/origin/tags/*";
+
+/// Where fetched tags are.
+const TAGS_PREFIX: &str = "refs/remotes/origin/tags/";
+
+impl From<Error> for crate::error::Error {
+    fn from(e: Error) -> Self {
+        Self::Git {
+            source: Box::new(e) as Box<dyn std::error::Error + Send + Sync>,
+        }
+    }
+}
+
+impl GitClient {
+    /// Checkout a git ref and return the path to the working tree.
+    ///
+    /// This uses a two-tier cache:
+    /// 1. Bare repository cache (one per URL) - for efficient fetching
+    /// 2. Checkout cache (one per commit) - the actual source code
+    ///
+    /// Returns a tuple of (`checkout_path`, `commit_hash`) where:
+    /// - `checkout_path`: Path to the checked-out working tree (the final source code)
+    /// - `commit_hash`: Full 40-character SHA-1 hash of the checked-out commit
+    pub(crate) fn checkout_ref(&self, url: &str, selector: GitSelector) -> crate::Result<(PathBuf, String)> {
+        let db_path = self.ensure_db(url)?;
+
+        // About to check if ref exists locally
+        self.reporter.report(|| GitMessage::resolving_ref(url, &selector));
+
+        let commit_str = if let Ok(oid) = resolve_selector(&db_path, &selector) {
+            // Ref found locally - no network needed
+            let commit_str = oid.to_string();
+            self.reporter
+                .report(|| GitMessage::ref_found_locally(url, &selector, &commit_str));
+            commit_str
+        } else {
+            // Ref not present - need to fetch from network
+            self.reporter.report(|| GitMessage::fetching_repo(url, &selector));
+            self.fetch(&db_path, url, &[selector_refspec(&selector)])?;
+            let oid = resolve_selector(&db_path, &selector)?;
+            let commit_str = oid.to_string();
+            self.reporter.report(|| GitMessage::resolved_ref(&commit_str));
+            commit_str
+        };
+
+        let checkout_path = self.ensure_checkout(&db_path, url, &commit_str)?;
+        Ok((checkout_path, commit_str))
+    }
+
+    /// Fetch the remote's default branch into the bare repository cached for `url`, unless
+    /// `offline`, and return the commit it's at, without checking it out.
+    ///
+    /// This is for repositories that are read rather than built, like a registry's git index, so
+    /// unlike [`Self::checkout_ref`] the branch is fetched even if it's cached already, to pick up
+    /// what's been pushed since.  Returns `Ok(None)` when offline and the repository hasn't been
+    /// fetched before.
+    pub(crate) fn fetch_default_branch(&self, url: &str, offline: bool) -> crate::Result<Option<String>> {
+        let db_path = self.ensure_db(url)?;
+        let selector = GitSelector::DefaultBranch;
+
+        if !offline {
+            self.reporter.report(|| GitMessage::fetching_repo(url, &selector));
+            self.fetch(&db_path, url, &[selector_refspec(&selector)])?;
+        }
+
+        match resolve_selector(&db_path, &selector) {
+            Ok(oid) => Ok(Some(oid.to_string())),
+            Err(_) if offline => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// What to check out of `url` to get its default branch as it was before `before`: the newest
+    /// release tag whose commit was made before then, or else the last commit on the default branch
+    /// made before then (see [`crate::as_of`]).
+    ///
+    /// Unless `offline`, the default branch and every tag are fetched first.  Returns `Ok(None)` if
+    /// nothing in the repository is that old.
+    pub(crate) fn selector_as_of(
+        &self,
+        url: &str,
+        before: DateTime<Utc>,
+        offline: bool,
+    ) -> crate::Result<Option<GitSelector>> {
+        let db_path = self.ensure_db(url)?;
+
+        if !offline {
+            let selector = GitSelector::DefaultBranch;
+            self.reporter.report(|| GitMessage::fetching_repo(url, &selector));
+            self.fetch(
+                &db_path,
+                url,
+                &[selector_refspec(&selector), TAGS_REFSPEC.to_string()],
+            )?;
+        }
+
+        let selector = selector_before(&db_path, before.timestamp())?;
+        if let Some(selector) = &selector {
+            tracing::debug!("Resolving {} as of {} from {:?}", url, before, selector);
+        }
+
+        Ok(selector)
+    }
+
+    /// The contents of the file at `path`, relative to the root of the repository, as of `commit`
+    /// in the bare repository cached for `url`, or `None` if there's no such file.
+    ///
+    /// The commit has to have been fetched already, by [`Self::fetch_default_branch`].
+    pub(crate) fn read_file(&self, url: &str, commit: &str, path: &Path) -> crate::Result<Option<Vec<u8>>> {
+        let db_path = self.cache.git_db_path(url);
+        let commit_oid = ObjectId::from_hex(commit.as_bytes())
+            .map_err(|e| InvalidCommitHashSnafu { hash: commit }.into_error(e))?;
+
+        Ok(read_blob(&db_path, commit_oid, path)?)
+    }
+
+    /// Fetch `refspecs` from `url` into the bare repository at `db_path`, telling an
+    /// authentication failure apart from any other.
+    fn fetch(&self, db_path: &Path, url: &str, refspecs: &[String]) -> crate::Result<()> {
+        fetch_ref(db_path, url, refspecs, &self.http_config, &self.git_config).map_err(|e| {
+            if is_auth_failure(&e) {
+                crate::error::GitAuthFailedSnafu {
+                    url,
+                    attempted: auth_methods(url, db_path, &self.git_config).join("; "),
+                }
+                .build()
+            } else {
+                e.into()
+            }
+        })
+    }
+
+    fn ensure_db(&self, url: &str) -> Result<PathBuf> {
+        let db_path = self.cache.git_db_path(url);
+
+        if !db_path.exists() {
+            fs::create_dir_all(&db_path).with_context(|_| CreateDirectorySnafu {
+                path: db_path.clone(),
+            })?;
+            init_bare_repo(&db_path)?;
+        }
+
+        Ok(db_path)
+    }
+
+    fn ensure_checkout(&self, db_path: &Path, url: &str, commit: &str) -> Result<PathBuf> {
+        let checkout_path = self.cache.git_checkout_path(url, commit);
+
+        // Check if valid checkout exists (use .cgx-ok marker like cargo's .cargo-ok)
+        if checkout_path.exists() && checkout_path.join(".cgx-ok").exists() {
+            self.reporter
+                .report(|| GitMessage::checkout_exists(commit, &checkout_path));
+            return Ok(checkout_path);
+        }
+
+        // Need to perform checkout - emit CheckingOut before extraction
+        self.reporter
+            .report(|| GitMessage::checking_out(commit, &checkout_path));
+
+        fs::create_dir_all(&checkout_path).with_context(|_| CreateDirectorySnafu {
+            path: checkout_path.clone(),
+        })?;
+        let _ = fs::remove_file(checkout_path.join(".cgx-ok"));
+
+        let commit_oid = ObjectId::from_hex(commit.as_bytes())
+            .map_err(|e| InvalidCommitHashSnafu { hash: commit }.into_error(e))?;
+
+        checkout_from_db(db_path, commit_oid, &checkout_path)?;
+
+        // Mark as ready
+        let marker_path = checkout_path.join(".cgx-ok");
+        fs::write(&marker_path, "").with_context(|_| WriteMarkerFileSnafu {
+            path: marker_path.clone(),
+        })?;
+
+        // Extraction complete
+        self.reporter
+            .report(|| GitMessage::checkout_complete(&checkout_path));
+
+        Ok(checkout_path)
+    }
+}
+
+// Low-level git operations (private functions), which need gix
+
+fn init_bare_repo(path: &Path) -> Result<()> {
+    gix::init_bare(path)
+        .map_err(|e| {
+            InitBareRepoSnafu {
+                path: path.to_path_buf(),
+            }
+            .into_error(e)
+        })
+        .map(|_| ())
+}
+
+fn fetch_ref(
+    db_path: &Path,
+    url: &str,
+    refspecs: &[String],
+    http_config: &HttpConfig,
+    git_config: &GitConfig,
+) -> Result<()> {
+    let backoff = ExponentialBuilder::default()
+        .with_min_delay(http_config.backoff_base)
+        .with_max_delay(http_config.backoff_max)
+        .with_max_times(http_config.retries)
+        .with_jitter();
+
+    (|| fetch_ref_impl(db_path, url, refspecs, http_config, git_config))
+        .retry(backoff)
+        .when(is_retryable_error)
+        .sleep(std::thread::sleep)
+        .call()
+}
+
+/// Determine whether a failed fetch should be retried.
+///
+/// Only [`Error::FetchRef`] errors are candidates. We downcast the boxed source to the three
+/// concrete gix error types produced by [`fetch_ref_impl`] and delegate to gix's
+/// [`is_spurious()`](gix::protocol::transport::IsSpuriousError::is_spurious), which recursively
+/// inspects the error chain for transient conditions: 5xx HTTP status codes (mapped to
+/// `ConnectionAborted`), connection timeouts/resets/refused, curl transport failures (DNS, proxy,
+/// SSL, HTTP/2, partial file), broken pipe, interrupted, and unexpected EOF. It correctly returns
+/// `false` for 4xx errors like 401, 403, and 404.
+///
+/// One gap: gix maps HTTP 429 (Too Many Requests) to `io::ErrorKind::Other` which
+/// `is_spurious()` considers non-retryable. We want to retry on 429, so we also walk the
+/// error source chain looking for the `io::Error` with gix's exact format string.
+fn is_retryable_error(e: &Error) -> bool {
+    let Error::FetchRef { source, .. } = e else {
+        return false;
+    };
+    let err = source.as_ref();
+
+    let spurious = if let Some(e) = err.downcast_ref::<gix::remote::connect::Error>() {
+        e.is_spurious()
+    } else if let Some(e) = err.downcast_ref::<gix::remote::fetch::prepare::Error>() {
+        e.is_spurious()
+    } else if let Some(e) = err.downcast_ref::<gix::remote::fetch::Error>() {
+        e.is_spurious()
+    } else {
+        false
+    };
+
+    if spurious {
+        return true;
+    }
+
+    // Check for HTTP 429 by walking the source chain for an io::Error with gix's exact message.
+    let mut source: Option<&(dyn std::error::Error)> = Some(err);
+    while let Some(current) = source {
+        if let Some(io_err) = current.downcast_ref::<std::io::Error>() {
+            if io_err.to_string().contains("Received HTTP status 429") {
+                return true;
+            }
+        }
+        source = current.source();
+    }
+
+    false
+}
+
+/// Whether a fetch failed because the host wouldn't let cgx in, rather than because the
+/// repository or ref doesn't exist or the network failed.
+///
+/// How gix reports this differs between transports, so it's recognized by the messages in the
+/// error chain: an HTTP 401, credentials that were rejected or couldn't be had, or `ssh`
+/// reporting that none of its keys were accepted.
+fn is_auth_failure(e: &Error) -> bool {
+    const MARKERS: &[&str] = &[
+        "status 401",
+        "authentication",
+        "credentials",
+        "could not read username",
+        "permission denied (publickey",
+    ];
+
+    let Error::FetchRef { source, .. } = e else {
+        return false;
+    };
+
+    let mut source: Option<&(dyn std::error::Error)> = Some(source.as_ref());
+    while let Some(current) = source {
+        let message = current.to_string().to_lowercase();
+        if MARKERS.iter().any(|marker| message.contains(marker)) {
+            return true;
+        }
+        source = current.source();
+    }
+
+    false
+}
+
+/// Whether a git failure means the repository or the ref that was asked for doesn't exist, as
+/// opposed to the host being unreachable or refusing cgx.
+///
+/// A ref that still can't be found after fetching it fails to resolve, and a fetch fails this way
+/// when the host answers 404, or says the repository or the ref isn't there, or has no ref matching
+/// the refspec.  Any other failure, such as a network error, isn't a missing repository or ref.
+pub(crate) fn is_missing_repo_or_ref(e: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    const MARKERS: &[&str] = &[
+        "status 404",
+        "repository not found",
+        "does not appear to be a git repository",
+        "not our ref",
+        "none of the refspec",
+    ];
+
+    match e.downcast_ref::<Error>() {
+        Some(Error::ResolveSelector { .. }) => true,
+        Some(fetch_error @ Error::FetchRef { source, .. }) if !is_auth_failure(fetch_error) => {
+            let mut source: Option<&(dyn std::error::Error)> = Some(source.as_ref());
+            while let Some(current) = source {
+                let message = current.to_string().to_lowercase();
+                if MARKERS.iter().any(|marker| message.contains(marker)) {
+                    return true;
+                }
+                source = current.source();
+            }
+            false
+        }
+        _ => false,
+    }
+}
+
+/// Whether `url` is reached over SSH: an `ssh://` URL, or the `user@host:path` form that git
+/// takes to mean SSH.
+fn is_ssh_url(url: &str) -> bool {
+    if url.starts_with("ssh://") || url.starts_with("git+ssh://") {
+        return true;
+    }
+
+    match url.split_once(':') {
+        Some((host, path)) => !host.contains('/') && host.contains('@') && !path.starts_with("//"),
+        None => false,
+    }
+}
+
+/// The host of `url` and the token configured for it, if `url` is reached over HTTP(S) and the
+/// `[git]` config has a token for its host.
+fn configured_auth<'a>(url: &str, git_config: &'a GitConfig) -> Option<(String, &'a GitHostAuth)> {
+    let url = Url::parse(url).ok()?;
+    if !matches!(url.scheme(), "https" | "http") {
+        return None;
+    }
+
+    let host = url.host_str()?.to_string();
+    let auth = git_config.hosts.get(&host)?;
+    Some((host, auth))
+}
+
+/// The ways cgx tries to authenticate to `url`, for telling the user which were tried when the
+/// host refused them all.
+fn auth_methods(url: &str, db_path: &Path, git_config: &GitConfig) -> Vec<String> {
+    if is_ssh_url(url) {
+        let agent = if cfg!(windows) || std::env::var_os("SSH_AUTH_SOCK").is_some() {
+            "keys in the SSH agent"
+        } else {
+            "keys in the SSH agent, which isn't running (SSH_AUTH_SOCK isn't set)"
+        };
+        return vec![
+            agent.to_string(),
+            "the keys `ssh` is configured to use (~/.ssh/config and ~/.ssh/id_*)".to_string(),
+        ];
+    }
+
+    if let Some((host, auth)) = configured_auth(url, git_config) {
+        let token = if std::env::var_os(&auth.token_env).is_some() {
+            format!(
+                "the token in ${} for {}, as user {}",
+                auth.token_env, host, auth.username
+            )
+        } else {
+            format!(
+                "the token in ${} for {}, but that environment variable isn't set",
+                auth.token_env, host
+            )
+        };
+        return vec![token];
+    }
+
+    let helpers: Vec<String> = gix::open(db_path)
+        .ok()
+        .and_then(|repo| {
+            repo.config_snapshot()
+                .strings("credential.helper")
+                .map(|helpers| helpers.iter().map(|helper| helper.to_string()).collect())
+        })
+        .unwrap_or_default();
+    if helpers.is_empty() {
+        vec!["git credential helpers, but none are configured (credential.helper)".to_string()]
+    } else {
+        vec![format!("git credential helpers ({})", helpers.join(", "))]
+    }
+}
+
+fn http_config_overrides(http_config: &HttpConfig, url: &str) -> Vec<BString> {
+    let ua = crate::http::user_agent();
+
+    // `connectTimeout` only covers the TCP handshake. To also abort on stalled transfers
+    // (server accepted the connection but stops sending data), we set curl's low-speed
+    // threshold: if fewer than 1 byte/sec is sustained for `timeout` seconds, curl aborts
+    // with CURLE_OPERATION_TIMEDOUT, which gix surfaces as a spurious/retryable error.
+    let low_speed_time_secs = http_config.timeout.as_secs().max(1);
+
+    let mut overrides = vec![
+        // Controls the git protocol `agent` value (and acts as gix's fallback UA source).
+        // We set it so servers/proxies see cgx identity at the git protocol layer, not the
+        // default `git/oxide-*`. If omitted, protocol-layer identity reverts to gix default.
+        format!("gitoxide.userAgent={ua}").into(),
+        // Controls the HTTP backend's configured user-agent option (`http.userAgent`).
+        // This keeps transport-level UA settings aligned with cgx identity. If omitted,
+        // gix falls back to its default `oxide-*` transport agent for this setting.
+        format!("http.userAgent={ua}").into(),
+        // Forces an explicit `User-Agent` HTTP header on each request.
+        // This is currently required for our observed behavior with gix+curl: without this,
+        // requests in integration tests carry `User-Agent: git/oxide-*` instead of cgx UA.
+        format!("http.extraHeader=User-Agent: {ua}").into(),
+        format!("gitoxide.http.connectTimeout={}", http_config.timeout.as_millis()).into(),
+        "http.lowSpeedLimit=1".into(),
+        format!("http.lowSpeedTime={low_speed_time_secs}").into(),
+    ];
+
+    // The proxy rules pick a proxy for the repository's host, or none; curl itself honors the
+    // proxy environment variables and `NO_PROXY`, so there's nothing to override otherwise.
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string));
+    let route = host.and_then(|host| ProxyRules::new(http_config).route(&host));
+    let proxy = match route {
+        Some(Route::Proxy(proxy)) => Some(proxy),
+        Some(Route::Direct) => {
+            overrides.push("gitoxide.http.noProxy=*".into());
+            None
+        }
+        None => http_config.proxy.clone(),
+    };
+    if let Some(proxy) = proxy {
+        let proxy = crate::proxy_auth::with_credentials(&proxy, http_config);
+        overrides.push(format!("http.proxy={proxy}").into());
+    }
+
+    if let Some(ref ca_bundle) = http_config.ca_bundle {
+        overrides.push(format!("http.sslCAInfo={}", ca_bundle.display()).into());
+    }
+
+    overrides
+}
+
+fn http_open_options(http_config: &HttpConfig, url: &str) -> gix::open::Options {
+    let overrides = http_config_overrides(http_config, url);
+    gix::open::Options::default().config_overrides(overrides)
+}
+
+fn fetch_ref_impl(
+    db_path: &Path,
+    url: &str,
+    refspecs: &[String],
+    http_config: &HttpConfig,
+    git_config: &GitConfig,
+) -> Result<()> {
+    let repo = gix::open_opts(db_path, http_open_options(http_config, url)).map_err(|e| {
+        OpenRepoSnafu {
+            path: db_path.to_path_buf(),
+        }
+        .into_error(e)
+    })?;
+
+    // Fetch with explicit refspecs
+    let remote = repo
+        .remote_at(url)
+        .map_err(|e| FetchRefSnafu { url: url.to_string() }.into_error(Box::new(e)))?
+        .with_refspecs(refspecs.iter().map(String::as_str), Direction::Fetch)
+        .map_err(|e| FetchRefSnafu { url: url.to_string() }.into_error(Box::new(e)))?;
+
+    let connection = remote
+        .connect(Direction::Fetch)
+        .map_err(|e| FetchRefSnafu { url: url.to_string() }.into_error(Box::new(e)))?;
+
+    // A host with a token configured is given that rather than asking the credential helpers
+    let token = configured_auth(url, git_config).and_then(|(_, auth)| {
+        std::env::var(&auth.token_env)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .map(|token| (auth.username.clone(), token))
+    });
+    let connection = match token {
+        Some((username, token)) => connection.with_credentials(move |action| match action {
+            gix::credentials::helper::Action::Get(context) => Ok(Some(gix::credentials::protocol::Outcome {
+                identity: gix::sec::identity::Account {
+                    username: username.clone(),
+                    password: token.clone(),
+                    oauth_refresh_token: None,
+                },
+                next: context.into(),
+            })),
+            _ => Ok(None),
+        }),
+        None => connection,
+    };
+
+    connection
+        .prepare_fetch(&mut gix::progress::Discard, Default::default())
+        .map_err(|e| FetchRefSnafu { url: url.to_string() }.into_error(Box::new(e)))?
+        .receive(&mut gix::progress::Discard, cancel::current().as_atomic())
+        .map_err(|e| FetchRefSnafu { url: url.to_string() }.into_error(Box::new(e)))?;
+
+    Ok(())
+}
+
+/// The refspec that fetches what `selector` selects, targeted so that as little as possible is
+/// fetched.
+fn selector_refspec(selector: &GitSelector) -> String {
+    match selector {
+        GitSelector::DefaultBranch => "+HEAD:refs/remotes/origin/HEAD".to_string(),
+        GitSelector::Branch(b) => format!("+refs/heads/{b}:refs/remotes/origin/{b}"),
+        GitSelector::Tag(t) => format!("+refs/tags/{t}:refs/remotes/origin/tags/{t}"),
+        GitSelector::Commit(c) if c.len() == 40 => {
+            // Full hash: try targeted fetch (may fail if commit not advertised)
+            // NOTE: This implementation assumes git servers support fetching arbitrary commits
+            // via protocol v2's allow-any-sha1-in-want capability (true for GitHub, GitLab.com).
+            // Servers that don't support this will fail for non-advertised commits.
+            // A fallback to broader fetch could be added if needed for restrictive servers.
+            // As of this writing I haven't even been able to *find* a public git server that
+            // doesn't support fetching arbitrary commits, so this is probably fine.
+            format!("+{c}:refs/commit/{c}")
+        }
+        GitSelector::Commit(_) => {
+            // Short hash or potentially unadvertised commit: fetch default branch with history
+            // so that we can search the commits and find the one that has this commit hash prefix.
+            "+HEAD:refs/remotes/origin/HEAD".to_string()
+        }
+    }
+}
+
+fn resolve_selector(db_path: &Path, selector: &GitSelector) -> Result<ObjectId> {
+    let repo = gix::open(db_path).map_err(|e| {
+        OpenRepoSnafu {
+            path: db_path.to_path_buf(),
+        }
+        .into_error(e)
+    })?;
+
+    let oid = match selector {
+        GitSelector::DefaultBranch => {
+            let ref_name = "refs/remotes/origin/HEAD";
+            let reference = repo.find_reference(ref_name).map_err(|e| {
+                ResolveSelectorSnafu {
+                    message: format!("Failed to find {}", ref_name),
+                }
+                .into_error(Box::new(e))
+            })?;
+            reference
+                .into_fully_peeled_id()
+                .map_err(|e| {
+                    ResolveSelectorSnafu {
+                        message: "Failed to peel reference".to_string(),
+                    }
+                    .into_error(Box::new(e))
+                })?
+                .detach()
+        }
+        GitSelector::Branch(b) => {
+            let ref_name = format!("refs/remotes/origin/{}", b);
+            let reference = repo.find_reference(&ref_name).map_err(|e| {
+                ResolveSelectorSnafu {
+                    message: format!("Branch '{}' not found", b),
+                }
+                .into_error(Box::new(e))
+            })?;
+            reference
+                .into_fully_peeled_id()
+                .map_err(|e| {
+                    ResolveSelectorSnafu {
+                        message: format!("Failed to peel branch '{}'", b),
+                    }
+                    .into_error(Box::new(e))
+                })?
+                .detach()
+        }
+        GitSelector::Tag(t) => {
+            let ref_name = format!("refs/remotes/origin/tags/{}", t);
+            let reference = repo.find_reference(&ref_name).map_err(|e| {
+                ResolveSelectorSnafu {
+                    message: format!("Tag '{}' not found", t),
+                }
+                .into_error(Box::new(e))
+            })?;
+            // Peel annotated tags to get commit
+            reference
+                .into_fully_peeled_id()
+                .map_err(|e| {
+                    ResolveSelectorSnafu {
+                        message: format!("Failed to peel tag '{}'", t),
+                    }
+                    .into_error(Box::new(e))
+                })?
+                .detach()
+        }
+        GitSelector::Commit(c) => {
+            // Use rev_parse_single to resolve both short and full commit hashes
+            let spec = repo.rev_parse_single(c.as_bytes()).map_err(|e| {
+                ResolveSelectorSnafu {
+                    message: format!("Failed to resolve commit '{}'", c),
+                }
+                .into_error(Box::new(e))
+            })?;
+            spec.object()
+                .map_err(|e| {
+                    ResolveSelectorSnafu {
+                        message: format!("Failed to get object for commit '{}'", c),
+                    }
+                    .into_error(Box::new(e))
+                })?
+                .id
+        }
+    };
+
+    Ok(oid)
+}
+
+/// The selector for the newest release tag fetched into the bare repository at `db_path` whose
+/// commit was made before `before`, in seconds since the epoch, or else for the last commit on the
+/// default branch made before then, or `None` if there's neither.
+fn selector_before(db_path: &Path, before: i64) -> Result<Option<GitSelector>> {
+    let repo = gix::open(db_path).map_err(|e| {
+        OpenRepoSnafu {
+            path: db_path.to_path_buf(),
+        }
+        .into_error(e)
+    })?;
+    let walk_error = |message: &str, e: Box<dyn std::error::Error + Send + Sync>| {
+        ResolveSelectorSnafu {
+            message: message.to_string(),
+        }
+        .into_error(e)
+    };
+
+    let references = repo
+        .references()
+        .map_err(|e| walk_error("Failed to list tags", Box::new(e)))?;
+    let mut tags = Vec::new();
+    for reference in references
+        .prefixed(TAGS_PREFIX)
+        .map_err(|e| walk_error("Failed to list tags", Box::new(e)))?
+    {
+        let reference = reference.map_err(|e| walk_error("Failed to list tags", e))?;
+        let name = reference.name().as_bstr().to_string();
+        let Some(tag) = name.strip_prefix(TAGS_PREFIX).map(str::to_string) else {
+            continue;
+        };
+
+        // A tag of something other than a commit is no release
+        let committed = reference
+            .into_fully_peeled_id()
+            .ok()
+            .and_then(|id| repo.find_commit(id.detach()).ok())
+            .and_then(|commit| commit.time().ok());
+        if let Some(committed) = committed {
+            tags.push((tag, committed.seconds));
+        }
+    }
+    if let Some(tag) = crate::as_of::release_tag(&tags, before) {
+        return Ok(Some(GitSelector::Tag(tag.to_string())));
+    }
+
+    let head = resolve_selector(db_path, &GitSelector::DefaultBranch)?;
+    let walk = repo
+        .rev_walk([head])
+        .first_parent_only()
+        .all()
+        .map_err(|e| walk_error("Failed to walk the default branch", Box::new(e)))?;
+    for info in walk {
+        let info = info.map_err(|e| walk_error("Failed to walk the default branch", Box::new(e)))?;
+        let committed = info
+            .object()
+            .map_err(|e| walk_error("Failed to read a commit", Box::new(e)))?
+            .time()
+            .map_err(|e| walk_error("Failed to read a commit", Box::new(e)))?;
+        if committed.seconds < before {
+            return Ok(Some(GitSelector::Commit(info.id.to_string())));
+        }
+    }
+
+    Ok(None)
+}
+
+fn read_blob(db_path: &Path, commit_oid: ObjectId, path: &Path) -> Result<Option<Vec<u8>>> {
+    let repo = gix::open(db_path).map_err(|e| {
+        OpenRepoSnafu {
+            path: db_path.to_path_buf(),
+        }
+        .into_error(e)
+    })?;
+    let read_error = |e: Box<dyn std::error::Error + Send + Sync>| {
+        ReadFileSnafu {
+            path: path.to_path_buf(),
+        }
+        .into_error(e)
+    };
+
+    let tree = repo
+        .find_commit(commit_oid)
+        .map_err(|e| read_error(Box::new(e)))?
+        .tree()
+        .map_err(|e| read_error(Box::new(e)))?;
+    let Some(entry) = tree
+        .lookup_entry_by_path(path)
+        .map_err(|e| read_error(Box::new(e)))?
+    else {
+        return Ok(None);
+    };
+
+    let object = entry.object().map_err(|e| read_error(Box::new(e)))?;
+    Ok(Some(object.detach().data))
+}
+
+fn checkout_from_db(db_path: &Path, commit_oid: ObjectId, dest: &Path) -> Result<()> {
+    let repo = gix::open(db_path).map_err(|e| {
+        OpenRepoSnafu {
+            path: db_path.to_path_buf(),
+        }
+        .into_error(e)
+    })?;
+
+    // Get commit and tree
+    let commit = repo.find_commit(commit_oid).map_err(|e| {
+        CheckoutFromDbSnafu {
+            path: dest.to_path_buf(),
+        }
+        .into_error(Box::new(e))
+    })?;
+
+    let tree_id = commit.tree_id().map_err(|e| {
+        CheckoutFromDbSnafu {
+            path: dest.to_path_buf(),
+        }
+        .into_error(Box::new(e))
+    })?;
+
+    // Create index from tree
+    let mut index = repo.index_from_tree(&tree_id).map_err(|e| {
+        CheckoutFromDbSnafu {
+            path: dest.to_path_buf(),
+        }
+        .into_error(Box::new(e))
+    })?;
+
+    // Get checkout options (handles .gitattributes, filters, line endings)
+    let options = repo
+        .checkout_options(gix::worktree::stack::state::attributes::Source::IdMapping)
+        .map_err(|e| {
+            CheckoutFromDbSnafu {
+                path: dest.to_path_buf(),
+            }
+            .into_error(Box::new(e))
+        })?;
+
+    // Use gix native checkout
+    gix::worktree::state::checkout(
+        &mut index,
+        dest,
+        repo.objects.clone(),
+        &gix::progress::Discard,
+        &gix::progress::Discard,
+        cancel::current().as_atomic(),
+        options,
+    )
+    .map_err(|e| {
+        CheckoutFromDbSnafu {
+            path: dest.to_path_buf(),
+        }
+        .into_error(Box::new(e))
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cache::Cache, messages::MessageReporter};
+    use assert_matches::assert_matches;
+    use tempfile::TempDir;
+
+    fn test_git_client() -> (GitClient, TempDir) {
+        let (temp_dir, config) = crate::config::create_test_env();
+        let reporter = MessageReporter::null();
+        let cache = Cache::new(config.clone(), reporter.clone());
+        let git_client = GitClient::new(cache, reporter, &config);
+        (git_client, temp_dir)
+    }
+
+    mod http_config_overrides {
+        use super::*;
+
+        const REPO_URL: &str = "https://github.com/owner/repo.git";
+
+        fn overrides_to_strings(overrides: Vec<BString>) -> Vec<String> {
+            overrides
+                .into_iter()
+                .map(|override_value| String::from_utf8_lossy(override_value.as_ref()).into_owned())
+                .collect()
+        }
+
+        #[test]
+        fn includes_user_agent_and_timeout_settings() {
+            let (_temp_dir, config) = crate::config::create_test_env();
+            let overrides = overrides_to_strings(http_config_overrides(&config.http, REPO_URL));
+
+            assert!(overrides.iter().any(|o| o.starts_with("gitoxide.userAgent=")));
+            assert!(overrides.iter().any(|o| o.starts_with("http.userAgent=")));
+            assert!(
+                overrides
+                    .iter()
+                    .any(|o| o.starts_with("gitoxide.http.connectTimeout="))
+            );
+            assert!(overrides.iter().any(|o| o == "http.lowSpeedLimit=1"));
+        }
+
+        #[test]
+        fn includes_proxy_when_configured() {
+            let (_temp_dir, mut config) = crate::config::create_test_env();
+            config.http.proxy = Some("http://proxy.example:8080".to_string());
+            let overrides = overrides_to_strings(http_config_overrides(&config.http, REPO_URL));
+
+            assert!(
+                overrides
+                    .iter()
+                    .any(|o| o == "http.proxy=http://proxy.example:8080")
+            );
+        }
+
+        #[test]
+        fn proxy_rules_pick_the_proxy_for_the_host() {
+            let (_temp_dir, mut config) = crate::config::create_test_env();
+            config.http.proxy = Some("http://proxy.example:8080".to_string());
+            config.http.no_proxy = Some(".corp.example".to_string());
+            config.http.proxies =
+                [("github.com".to_string(), "http://egress.example:3128".to_string())].into();
+
+            let overrides = overrides_to_strings(http_config_overrides(&config.http, REPO_URL));
+            assert!(
+                overrides
+                    .iter()
+                    .any(|o| o == "http.proxy=http://egress.example:3128")
+            );
+
+            let overrides = overrides_to_strings(http_config_overrides(
+                &config.http,
+                "https://git.corp.example/tools/tool.git",
+            ));
+            assert!(!overrides.iter().any(|o| o.starts_with("http.proxy=")));
+            assert!(overrides.iter().any(|o| o == "gitoxide.http.noProxy=*"));
+        }
+
+        #[test]
+        fn includes_ca_bundle_when_configured() {
+            let (_temp_dir, mut config) = crate::config::create_test_env();
+            config.http.ca_bundle = Some(PathBuf::from("/etc/ssl/corp-ca.pem"));
+            let overrides = overrides_to_strings(http_config_overrides(&config.http, REPO_URL));
+
+            assert!(
+                overrides
+                    .iter()
+                    .any(|o| o == "http.sslCAInfo=/etc/ssl/corp-ca.pem")
+            );
+        }
+
+        #[test]
+        fn omits_proxy_when_not_configured() {
+            let (_temp_dir, config) = crate::config::create_test_env();
+            let overrides = overrides_to_strings(http_config_overrides(&config.http, REPO_URL));
+
+            assert!(!overrides.iter().any(|o| o.starts_with("http.proxy=")));
+        }
+    }
+
+    mod checkout_ref {
+        use super::*;
+
+        #[test]
+        fn checkout_default_branch() {
+            let (git_client, _temp) = test_git_client();
+            let url = "https://github.com/rust-lang/rustlings.git";
+
+            let (checkout_path, _commit_hash) =
+                git_client.checkout_ref(url, GitSelector::DefaultBranch).unwrap();
+            assert!(checkout_path.exists());
+            assert!(checkout_path.join(".cgx-ok").exists());
+        }
+
+        #[test]
+        fn checkout_specific_branch() {
+            let (git_client, _temp) = test_git_client();
+            let url = "https://github.com/rust-lang/rustlings.git";
+
+            let (checkout_path, _commit_hash) = git_client
+                .checkout_ref(url, GitSelector::Branch("main".to_string()))
+                .unwrap();
+            assert!(checkout_path.exists());
+            assert!(checkout_path.join("Cargo.toml").exists());
+        }
+
+        #[test]
+        fn checkout_specific_tag() {
+            let (git_client, _temp) = test_git_client();
+            let url = "https://github.com/rust-lang/rustlings.git";
+
+            let (checkout_path, commit_hash) = git_client
+                .checkout_ref(url, GitSelector::Tag("v6.0.0".to_string()))
+                .unwrap();
+            assert!(checkout_path.exists());
+
+            // I happen to know what the commit hash is for this tag
+            assert_eq!("28d2bb04326d7036514245d73f10fb72b9ed108c", &commit_hash);
+        }
+
+        /// Checkout a specific commit that I happen to know is advertised by the remote, because
+        /// this commit is associated with the v6.0.0 tag.
+        #[test]
+        fn checkout_specific_advertised_commit() {
+            let (git_client, _temp) = test_git_client();
+            let url = "https://github.com/rust-lang/rustlings.git";
+
+            // Known stable commit corresponding to tag v6.0.0
+            let commit = "28d2bb04326d7036514245d73f10fb72b9ed108c";
+
+            let (checkout_path, commit_hash) = git_client
+                .checkout_ref(url, GitSelector::Commit(commit.to_string()))
+                .unwrap();
+            assert!(checkout_path.exists());
+            assert!(checkout_path.join(".cgx-ok").exists());
+            assert_eq!(commit, &commit_hash);
+
+            // Try again with a fresh client and clean cache, with a short commit; expect the same
+            // result
+            drop(_temp);
+            let (git_client, _temp) = test_git_client();
+            let short_commit = &commit[..7];
+            let (checkout_path, commit_hash) = git_client
+                .checkout_ref(url, GitSelector::Commit(short_commit.to_string()))
+                .unwrap();
+            assert!(checkout_path.exists());
+            assert!(checkout_path.join(".cgx-ok").exists());
+            assert_eq!(commit, &commit_hash);
+        }
+
+        /// Checkout a specific commit that I happen to know just a regular commot that is NOT
+        /// adverstised by the remote.  This triggers fallback fetch logic and thus must be tested
+        /// separately from advertised commits.
+        #[test]
+        fn checkout_specific_non_advertised_commit() {
+            let (git_client, _temp) = test_git_client();
+            let url = "https://github.com/rust-lang/rustlings.git";
+
+            // This is a random commit from 2024-07-02 that I don't think is advertised
+            let commit = "6cf75d569bd0dd33a041e37c59cb75d28664bd7b";
+
+            let (checkout_path, commit_hash) = git_client
+                .checkout_ref(url, GitSelector::Commit(commit.to_string()))
+                .unwrap();
+            assert!(checkout_path.exists());
+            assert!(checkout_path.join(".cgx-ok").exists());
+            assert_eq!(commit, &commit_hash);
+
+            // Try again with a fresh client and clean cache, with a short commit; expect the same
+            // result
+            drop(_temp);
+            let (git_client, _temp) = test_git_client();
+            let short_commit = &commit[..7];
+            let (checkout_path, commit_hash) = git_client
+                .checkout_ref(url, GitSelector::Commit(short_commit.to_string()))
+                .unwrap();
+            assert!(checkout_path.exists());
+            assert!(checkout_path.join(".cgx-ok").exists());
+            assert_eq!(commit, &commit_hash);
+        }
+
+        #[test]
+        fn cache_reuse_same_commit() {
+            let (git_client, _temp) = test_git_client();
+            let url = "https://github.com/rust-lang/rustlings.git";
+            let commit = "28d2bb04326d7036514245d73f10fb72b9ed108c";
+
+            // First checkout
+            let (first_checkout_path, first_checkout_hash) = git_client
+                .checkout_ref(url, GitSelector::Commit(commit.to_string()))
+                .unwrap();
+
+            // Second checkout should hit cache
+            let (second_checkout_path, second_checkout_hash) = git_client
+                .checkout_ref(url, GitSelector::Commit(commit.to_string()))
+                .unwrap();
+
+            assert_eq!(commit, &first_checkout_hash);
+            assert_eq!(commit, &second_checkout_hash);
+
+            assert_eq!(first_checkout_path, second_checkout_path);
+        }
+
+        #[test]
+        fn nonexistent_branch() {
+            let (git_client, _temp) = test_git_client();
+            let url = "https://github.com/rust-lang/rustlings.git";
+
+            let result = git_client.checkout_ref(
+                url,
+                GitSelector::Branch("this-branch-does-not-exist-xyzzy".to_string()),
+            );
+            assert_matches!(
+                result,
+                Err(crate::error::Error::Git { source })
+                    if matches!(source.downcast_ref::<Error>(), Some(Error::FetchRef { .. }))
+            );
+        }
+
+        #[test]
+        fn nonexistent_tag() {
+            let (git_client, _temp) = test_git_client();
+            let url = "https://github.com/rust-lang/rustlings.git";
+
+            let result = git_client.checkout_ref(url, GitSelector::Tag("v999.999.999".to_string()));
+            assert_matches!(
+                result,
+                Err(crate::error::Error::Git { source })
+                    if matches!(source.downcast_ref::<Error>(), Some(Error::FetchRef { .. }))
+            );
+        }
+
+        #[test]
+        fn nonexistent_commit() {
+            let (git_client, _temp) = test_git_client();
+            let url = "https://github.com/rust-lang/rustlings.git";
+
+            let result = git_client.checkout_ref(
+                url,
+                GitSelector::Commit("0000000000000000000000000000000000000000".to_string()),
+            );
+            assert_matches!(
+                result,
+                Err(crate::error::Error::Git { source })
+                    if matches!(source.downcast_ref::<Error>(), Some(Error::FetchRef { .. }))
+            );
+        }
+    }
+
+    mod missing_repo_or_ref {
+        use super::*;
+
+        fn fetch_failure(message: &str) -> Box<dyn std::error::Error + Send + Sync> {
+            Box::new(
+                FetchRefSnafu {
+                    url: "https://git.example.com/repo.git",
+                }
+                .into_error(Box::new(std::io::Error::other(message.to_string()))),
+            )
+        }
+
+        #[test]
+        fn only_a_missing_repo_or_ref_counts() {
+            assert!(is_missing_repo_or_ref(
+                fetch_failure("Received HTTP status 404").as_ref()
+            ));
+            assert!(is_missing_repo_or_ref(
+                fetch_failure("ERROR: Repository not found.").as_ref()
+            ));
+
+            let unresolved: Box<dyn std::error::Error + Send + Sync> = Box::new(
+                ResolveSelectorSnafu {
+                    message: "Tag 'v9.9.9' not found",
+                }
+                .into_error(Box::new(std::io::Error::other("missing"))),
+            );
+            assert!(is_missing_repo_or_ref(unresolved.as_ref()));
+
+            assert!(!is_missing_repo_or_ref(
+                fetch_failure("Received HTTP status 503").as_ref()
+            ));
+            assert!(!is_missing_repo_or_ref(
+                fetch_failure("Received HTTP status 401").as_ref()
+            ));
+            assert!(!is_missing_repo_or_ref(
+                fetch_failure("Connection refused").as_ref()
+            ));
+        }
+    }
+
+    /// Integration tests exercising the git fetch retry logic against a local mock HTTP server.
+    ///
+    /// These live here rather than in `cgx/tests/integration/` because the functions under test
+    /// ([`fetch_ref`], [`is_retryable_error`]) and their gix error types are `pub(crate)` and
+    /// not part of cgx-core's public API.
+    mod integration {
+        use super::*;
+        use httpmock::prelude::*;
+        use std::time::Duration;
+
+        /// Returns an HTTP configuration with near-zero retry delays so retry behavior can be
+        /// exercised without slowing the test suite down.
+        fn fast_retry_config() -> HttpConfig {
+            HttpConfig {
+                retries: 2,
+                backoff_base: Duration::from_millis(1),
+                backoff_max: Duration::from_millis(10),
+                timeout: Duration::from_secs(30),
+                ..Default::default()
+            }
+        }
+
+        /// Returns an HTTP configuration that disables retries so one-shot request behavior can be
+        /// asserted deterministically.
+        fn no_retry_config() -> HttpConfig {
+            HttpConfig {
+                retries: 0,
+                backoff_base: Duration::from_millis(1),
+                backoff_max: Duration::from_millis(1),
+                timeout: Duration::from_secs(5),
+                ..Default::default()
+            }
+        }
+
+        /// Creates an empty bare repository to use as the destination object database for fetch
+        /// integration tests.
+        fn test_bare_repo() -> (TempDir, PathBuf) {
+            let temp_dir = TempDir::new().unwrap();
+            let repo_path = temp_dir.path().join("bare.git");
+            fs::create_dir_all(&repo_path).unwrap();
+            init_bare_repo(&repo_path).unwrap();
+            (temp_dir, repo_path)
+        }
+
+        #[test]
+        fn server_503_is_retried() {
+            let server = MockServer::start();
+            let mock = server.mock(|_when, then| {
+                then.status(503);
+            });
+
+            let (_temp, db_path) = test_bare_repo();
+            let config = fast_retry_config();
+            let result = fetch_ref(
+                &db_path,
+                &server.url("/repo.git"),
+                &[selector_refspec(&GitSelector::DefaultBranch)],
+                &config,
+                &GitConfig::default(),
+            );
+
+            assert_matches!(result, Err(Error::FetchRef { .. }));
+            mock.assert_calls(3);
+        }
+
+        #[test]
+        fn server_500_is_retried() {
+            let server = MockServer::start();
+            let mock = server.mock(|_when, then| {
+                then.status(500);
+            });
+
+            let (_temp, db_path) = test_bare_repo();
+            let config = fast_retry_config();
+            let result = fetch_ref(
+                &db_path,
+                &server.url("/repo.git"),
+                &[selector_refspec(&GitSelector::DefaultBranch)],
+                &config,
+                &GitConfig::default(),
+            );
+
+            assert_matches!(result, Err(Error::FetchRef { .. }));
+            mock.assert_calls(3);
+        }
+
+        #[test]
+        fn server_429_is_retried() {
+            let server = MockServer::start();
+            let mock = server.mock(|_when, then| {
+                then.status(429);
+            });
+
+            let (_temp, db_path) = test_bare_repo();
+            let config = fast_retry_config();
+            let result = fetch_ref(
+                &db_path,
+                &server.url("/repo.git"),
+                &[selector_refspec(&GitSelector::DefaultBranch)],
+                &config,
+                &GitConfig::default(),
+            );
+
+            assert_matches!(result, Err(Error::FetchRef { .. }));
+            mock.assert_calls(3);
+        }
+
+        #[test]
+        fn server_403_is_not_retried() {
+            let server = MockServer::start();
+            let mock = server.mock(|_when, then| {
+                then.status(403);
+            });
+
+            let (_temp, db_path) = test_bare_repo();
+            let config = fast_retry_config();
+            let result = fetch_ref(
+                &db_path,
+                &server.url("/repo.git"),
+                &[selector_refspec(&GitSelector::DefaultBranch)],
+                &config,
+                &GitConfig::default(),
+            );
+
+            assert_matches!(result, Err(Error::FetchRef { .. }));
+            mock.assert_calls(1);
+        }
+
+        #[test]
+        fn server_404_is_not_retried() {
+            let server = MockServer::start();
+            let mock = server.mock(|_when, then| {
+                then.status(404);
+            });
+
+            let (_temp, db_path) = test_bare_repo();
+            let config = fast_retry_config();
+            let result = fetch_ref(
+                &db_path,
+                &server.url("/repo.git"),
+                &[selector_refspec(&GitSelector::DefaultBranch)],
+                &config,
+                &GitConfig::default(),
+            );
+
+            assert_matches!(result, Err(Error::FetchRef { .. }));
+            mock.assert_calls(1);
+        }
+
+        #[test]
+        fn connection_timeout_is_retried() {
+            let server = MockServer::start();
+            let mock = server.mock(|_when, then| {
+                then.status(200).delay(Duration::from_secs(3));
+            });
+
+            let (_temp, db_path) = test_bare_repo();
+            let config = HttpConfig {
+                retries: 2,
+                backoff_base: Duration::from_millis(1),
+                backoff_max: Duration::from_millis(10),
+                timeout: Duration::from_secs(1),
+                ..Default::default()
+            };
+            let result = fetch_ref(
+                &db_path,
+                &server.url("/repo.git"),
+                &[selector_refspec(&GitSelector::DefaultBranch)],
+                &config,
+                &GitConfig::default(),
+            );
+
+            assert_matches!(result, Err(Error::FetchRef { .. }));
+            mock.assert_calls(3);
+        }
+
+        #[test]
+        fn user_agent_is_applied_to_git_http_requests() {
+            let server = MockServer::start();
+            let expected_ua = crate::http::user_agent();
+            let mock = server.mock(|when, then| {
+                when.method(GET)
+                    .path("/repo.git/info/refs")
+                    .query_param("service", "git-upload-pack")
+                    .header("User-Agent", expected_ua.as_str());
+                then.status(500);
+            });
+
+            let (_temp, db_path) = test_bare_repo();
+            let config = no_retry_config();
+            let result = fetch_ref(
+                &db_path,
+                &server.url("/repo.git"),
+                &[selector_refspec(&GitSelector::DefaultBranch)],
+                &config,
+                &GitConfig::default(),
+            );
+
+            assert_matches!(result, Err(Error::FetchRef { .. }));
+            mock.assert_calls(1);
+        }
+
+        #[test]
+        fn proxy_setting_is_used_for_git_http_requests() {
+            let server = MockServer::start();
+            let expected_ua = crate::http::user_agent();
+            let mock = server.mock(|when, then| {
+                when.method(GET)
+                    .host("example.invalid")
+                    .path("/repo.git/info/refs")
+                    .query_param("service", "git-upload-pack")
+                    .header("User-Agent", expected_ua.as_str());
+                then.status(502);
+            });
+
+            let (_temp, db_path) = test_bare_repo();
+            let config = HttpConfig {
+                proxy: Some(server.base_url()),
+                ..no_retry_config()
+            };
+
+            let result = fetch_ref(
+                &db_path,
+                "http://example.invalid/repo.git",
+                &[selector_refspec(&GitSelector::DefaultBranch)],
+                &config,
+                &GitConfig::default(),
+            );
+
+            assert_matches!(result, Err(Error::FetchRef { .. }));
+            mock.assert_calls(1);
+        }
+    }
+
+    mod auth {
+        use super::*;
+        use crate::config::GitHostAuth;
+
+        #[test]
+        fn ssh_urls_are_recognized() {
+            assert!(is_ssh_url("git@github.com:owner/repo.git"));
+            assert!(is_ssh_url("ssh://git@git.example.com/owner/repo.git"));
+            assert!(!is_ssh_url("https://github.com/owner/repo.git"));
+            assert!(!is_ssh_url("https://user@git.example.com/owner/repo.git"));
+            assert!(!is_ssh_url("/srv/git/repo.git"));
+        }
+
+        #[test]
+        fn configured_token_is_listed_as_attempted() {
+            let git_config = GitConfig {
+                hosts: [(
+                    "git.example.com".to_string(),
+                    GitHostAuth {
+                        token_env: "CGX_TEST_UNSET_GIT_TOKEN".to_string(),
+                        username: "x-access-token".to_string(),
+                    },
+                )]
+                .into(),
+            };
+            let temp_dir = TempDir::new().unwrap();
+
+            assert_eq!(
+                auth_methods(
+                    "https://git.example.com/owner/repo.git",
+                    temp_dir.path(),
+                    &git_config
+                ),
+                vec![
+                    "the token in $CGX_TEST_UNSET_GIT_TOKEN for git.example.com, but that environment \
+                     variable isn't set"
+                        .to_string()
+                ]
+            );
+            assert!(configured_auth("https://github.com/owner/repo.git", &git_config).is_none());
+            assert!(configured_auth("git@git.example.com:owner/repo.git", &git_config).is_none());
+
+            let methods = auth_methods("git@git.example.com:owner/repo.git", temp_dir.path(), &git_config);
+            assert!(methods[0].contains("SSH agent"));
+        }
+
+        #[test]
+        fn unauthorized_fetch_is_an_auth_failure() {
+            let unauthorized = FetchRefSnafu {
+                url: "https://git.example.com/repo.git",
+            }
+            .into_error(Box::new(std::io::Error::other("Received HTTP status 401")));
+            assert!(is_auth_failure(&unauthorized));
+
+            let not_found = FetchRefSnafu {
+                url: "https://git.example.com/repo.git",
+            }
+            .into_error(Box::new(std::io::Error::other("Received HTTP status 404")));
+            assert!(!is_auth_failure(&not_found));
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Git hooks that run pinned tools, for `cgx --install-git-hooks` and `cgx --git-hook <NAME>`.
+//!
+//! The commands for each git hook are listed under `[hooks.git]` in the config, each one written
+//! as it would follow `cgx` on the command line, such as `cargo-deny check`:
+//!
+//! ```toml
+//! [hooks.git]
+//! pre-commit = ["taplo-cli fmt --check", "typos-cli"]
+//! pre-push = ["cargo-deny check"]
+//! ```
+//!
+//! `--install-git-hooks` writes a script for each configured hook into the repository's hooks
+//! directory, which does nothing but run `cgx --git-hook <NAME>`.  That runs each of the hook's
+//! commands through cgx in turn, so the tool is the version pinned in `[tools]`, and stops at the
+//! first one that fails.  Since the scripts only name the hook, changing its commands or the
+//! versions of its tools takes effect without installing them again.
+
+use crate::{Result, error, runner::parse_wrapper};
+use snafu::{OptionExt, ResultExt};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The git hooks that cgx can be installed as.
+///
+/// These are the client-side hooks that git runs in a working tree; the hooks that git runs on a
+/// server, or only for `git am`, aren't included.
+pub const GIT_HOOKS: &[&str] = &[
+    "pre-commit",
+    "prepare-commit-msg",
+    "commit-msg",
+    "post-commit",
+    "pre-rebase",
+    "post-checkout",
+    "post-merge",
+    "pre-push",
+];
+
+/// A line in every script cgx installs, by which it knows a hook is its own to replace.
+const MARKER: &str = "# Installed by cgx --install-git-hooks";
+
+/// Check that every hook in `hooks` is a git hook cgx can be installed as, and that every command
+/// can be split into words.
+pub(crate) fn validate(hooks: &BTreeMap<String, Vec<String>>) -> Result<()> {
+    for (name, commands) in hooks {
+        let invalid = |message: String| {
+            error::InvalidConfigValueSnafu {
+                field: format!("hooks.git.{}", name),
+                message,
+            }
+            .build()
+        };
+
+        if !GIT_HOOKS.contains(&name.as_str()) {
+            return Err(invalid(format!(
+                "not a git hook cgx can be installed as; expected one of: {}",
+                GIT_HOOKS.join(", ")
+            )));
+        }
+        for command in commands {
+            parse_wrapper(command).map_err(|e| invalid(e.to_string()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The hooks directory of the git repository `cwd` is in, as git itself sees it, so that
+/// `core.hooksPath` and linked worktrees are taken into account.
+pub fn hooks_dir(cwd: &Path) -> Result<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--path-format=absolute", "--git-path", "hooks"])
+        .current_dir(cwd)
+        .output()
+        .map_err(|e| {
+            error::GitHookInstallSnafu {
+                path: cwd,
+                message: format!("unable to run git: {}", e),
+            }
+            .build()
+        })?;
+
+    if !output.status.success() {
+        return error::GitHookInstallSnafu {
+            path: cwd,
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+        .fail();
+    }
+
+    Ok(PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// Write a script for each hook in `hooks` into `hooks_dir`, running `cgx` by the path `cgx_exe`,
+/// and return the paths of the scripts.
+///
+/// A hook cgx installed before is replaced, but any other script already there is left alone, and
+/// that's an error, so that a hook set up some other way is never lost.  That's checked for every
+/// hook before any is written.
+pub fn install(
+    hooks_dir: &Path,
+    hooks: &BTreeMap<String, Vec<String>>,
+    cgx_exe: &Path,
+) -> Result<Vec<PathBuf>> {
+    let paths: Vec<PathBuf> = hooks.keys().map(|name| hooks_dir.join(name)).collect();
+
+    for path in &paths {
+        match std::fs::read_to_string(path) {
+            Ok(script) if !script.contains(MARKER) => {
+                return error::GitHookInstallSnafu {
+                    path,
+                    message: "a hook not installed by cgx is already there",
+                }
+                .fail();
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            // Not valid UTF-8, so not a script cgx wrote
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                return error::GitHookInstallSnafu {
+                    path,
+                    message: "a hook not installed by cgx is already there",
+                }
+                .fail();
+            }
+            Err(e) => return Err(e).context(error::IoSnafu { path }),
+        }
+    }
+
+    std::fs::create_dir_all(hooks_dir).context(error::IoSnafu { path: hooks_dir })?;
+    for (name, path) in hooks.keys().zip(&paths) {
+        std::fs::write(path, script(name, cgx_exe)).context(error::IoSnafu { path })?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+                .context(error::IoSnafu { path })?;
+        }
+    }
+
+    Ok(paths)
+}
+
+/// The script installed as the hook `name`.
+///
+/// Git for Windows runs hooks with its own `sh` too, so the same script does for every platform.
+fn script(name: &str, cgx_exe: &Path) -> String {
+    let cgx_exe = cgx_exe
+        .display()
+        .to_string()
+        .replace('\\', "/")
+        .replace('\'', r"'\''");
+    format!(
+        "#!/bin/sh\n{MARKER}; the commands it runs are under [hooks.git] in cgx.toml.\nexec '{cgx_exe}' --git-hook {name} \"$@\"\n"
+    )
+}
+
+/// Run each of the commands configured for the git hook `name` in order, through `cgx` by the
+/// path `cgx_exe`, stopping at the first one that fails.
+///
+/// `hook_args` are the arguments git gave the hook, such as the file holding the commit message
+/// for `commit-msg`, and are passed on to each command's tool after its own arguments.
+pub fn run(
+    hooks: &BTreeMap<String, Vec<String>>,
+    name: &str,
+    hook_args: &[String],
+    cgx_exe: &Path,
+) -> Result<()> {
+    let commands = hooks
+        .get(name)
+        .context(error::GitHookNotConfiguredSnafu { name })?;
+
+    for command in commands {
+        let words = parse_wrapper(command)?;
+        if words.is_empty() {
+            continue;
+        }
+
+        let failed = |message: String| {
+            error::GitHookFailedSnafu {
+                name,
+                command,
+                message,
+            }
+            .build()
+        };
+
+        tracing::debug!(hook = name, command, "Running git hook");
+        let status = Command::new(cgx_exe)
+            .args(&words)
+            .args(hook_args)
+            .env("CGX_HOOK", name)
+            .status()
+            .map_err(|e| failed(e.to_string()))?;
+
+        if !status.success() {
+            return Err(failed(format!("it exited with {}", status)));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn hooks(entries: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|&(name, commands)| {
+                (
+                    name.to_string(),
+                    commands.iter().map(|&command| command.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unknown_hook_is_rejected() {
+        validate(&hooks(&[("pre-commit", &["typos"])])).unwrap();
+        assert_matches!(
+            validate(&hooks(&[("pre-comit", &["typos"])])),
+            Err(error::Error::InvalidConfigValue { field, .. }) if field == "hooks.git.pre-comit"
+        );
+    }
+
+    #[test]
+    fn install_replaces_only_its_own_hooks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let hooks_dir = temp_dir.path().join("hooks");
+        let cgx_exe = Path::new("/opt/cgx/bin/cgx");
+        let configured = hooks(&[("pre-commit", &["typos"]), ("pre-push", &["cargo-deny check"])]);
+
+        let paths = install(&hooks_dir, &configured, cgx_exe).unwrap();
+        assert_eq!(
+            paths,
+            vec![hooks_dir.join("pre-commit"), hooks_dir.join("pre-push")]
+        );
+        let script = std::fs::read_to_string(&paths[0]).unwrap();
+        assert!(script.ends_with("exec '/opt/cgx/bin/cgx' --git-hook pre-commit \"$@\"\n"));
+
+        // Installing again replaces cgx's own scripts
+        install(&hooks_dir, &configured, cgx_exe).unwrap();
+
+        std::fs::write(&paths[1], "#!/bin/sh\nnpx lint-staged\n").unwrap();
+        assert_matches!(
+            install(&hooks_dir, &configured, cgx_exe),
+            Err(error::Error::GitHookInstall { path, .. }) if path == paths[1]
+        );
+        assert_eq!(
+            std::fs::read_to_string(&paths[1]).unwrap(),
+            "#!/bin/sh\nnpx lint-staged\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_stops_at_first_failure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = temp_dir.path().join("log");
+        // Stands in for cgx, logging the hook and the command it's given
+        let fake_cgx = temp_dir.path().join("cgx");
+        std::fs::write(
+            &fake_cgx,
+            format!(
+                "#!/bin/sh\necho \"$CGX_HOOK $*\" >> {}\n[ \"$1\" != fail ]\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&fake_cgx, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let configured = hooks(&[("pre-commit", &["typos --diff", "fail", "never-run"])]);
+        assert_matches!(
+            run(&configured, "pre-commit", &[], &fake_cgx),
+            Err(error::Error::GitHookFailed { command, .. }) if command == "fail"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "pre-commit typos --diff\npre-commit fail\n"
+        );
+
+        // The arguments git gives the hook are passed on to each tool
+        let configured = hooks(&[("commit-msg", &["committed --commit-file"])]);
+        run(
+            &configured,
+            "commit-msg",
+            &[".git/COMMIT_EDITMSG".to_string()],
+            &fake_cgx,
+        )
+        .unwrap();
+        assert!(
+            std::fs::read_to_string(&log)
+                .unwrap()
+                .ends_with("commit-msg committed --commit-file .git/COMMIT_EDITMSG\n")
+        );
+
+        assert_matches!(
+            run(&configured, "pre-push", &[], &fake_cgx),
+            Err(error::Error::GitHookNotConfigured { .. })
+        );
+    }
+}
@@ -1,6 +1,20 @@
 use crate::{Result, error};
 use snafu::ResultExt;
-use std::{fmt::Write, path::Path};
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+/// Number of hex digits kept from SHA-256 digests used as cache directory names.
+///
+/// Cache paths nest several hashed components, and on Windows the full path has to stay under
+/// `MAX_PATH` for the tools (cargo, rustc, linkers) that run inside them, so full 64-digit
+/// digests are too expensive.  16 digits is still far more than enough to avoid collisions.
+pub(crate) const SHORT_HASH_LEN: usize = 16;
+
+/// Longest path Windows accepts without the `\\?\` prefix.
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
 
 /// Format a byte slice as a lowercase hex string.
 ///
@@ -16,10 +30,195 @@ pub(crate) fn format_hex_lower(bytes: impl AsRef<[u8]>) -> String {
     s
 }
 
+/// Convert `path` to a form that file-system calls accept even if it is longer than `MAX_PATH`.
+///
+/// On Windows, absolute paths are rewritten in the verbatim `\\?\` form (`\\?\UNC\` for network
+/// shares), which lifts the length limit.  Windows does no normalization of verbatim paths, so the
+/// path is rebuilt from its components, with `.` dropped and `..` applied lexically.  Relative
+/// and already-verbatim paths, and all paths on other platforms, are returned unchanged.
+///
+/// Only pass the result to `std::fs`; external tools like cargo don't all understand verbatim
+/// paths.
+pub(crate) fn long_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        use std::path::{Component, Prefix};
+
+        if !path.has_root() {
+            return path.to_path_buf();
+        }
+
+        let mut components = path.components();
+        let mut long = match components.next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(letter) => PathBuf::from(format!(r"\\?\{}:\", char::from(letter))),
+                Prefix::UNC(server, share) => PathBuf::from(format!(
+                    r"\\?\UNC\{}\{}\",
+                    server.to_string_lossy(),
+                    share.to_string_lossy()
+                )),
+                Prefix::Verbatim(_)
+                | Prefix::VerbatimUNC(..)
+                | Prefix::VerbatimDisk(_)
+                | Prefix::DeviceNS(_) => return path.to_path_buf(),
+            },
+            _ => return path.to_path_buf(),
+        };
+
+        for component in components {
+            match component {
+                Component::Normal(part) => long.push(part),
+                Component::ParentDir => {
+                    long.pop();
+                }
+                Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+            }
+        }
+
+        long
+    }
+
+    #[cfg(not(windows))]
+    {
+        path.to_path_buf()
+    }
+}
+
+/// Resolve symlinks in `path`, even when the path (or its last few components) doesn't exist yet.
+///
+/// The longest existing prefix of the path is canonicalized, and the remaining components are
+/// appended unchanged.  If no prefix exists the path is returned as-is.
+///
+/// On Windows, [`std::fs::canonicalize`] always produces a verbatim `\\?\` path; that prefix is
+/// removed again whenever the path is short enough not to need it, so that the result can still be
+/// handed to cargo and shown to the user.
+pub(crate) fn canonicalize_lenient(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut missing = Vec::new();
+
+    loop {
+        if let Ok(canonical) = std::fs::canonicalize(existing) {
+            let mut resolved = simplify_verbatim(canonical);
+            resolved.extend(missing.iter().rev());
+            return resolved;
+        }
+
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Strip the verbatim prefix from a canonicalized Windows path when it isn't needed.
+#[cfg(windows)]
+fn simplify_verbatim(path: PathBuf) -> PathBuf {
+    let Some(s) = path.to_str() else {
+        return path;
+    };
+
+    let simplified = if let Some(rest) = s.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{rest}")
+    } else if let Some(rest) = s.strip_prefix(r"\\?\") {
+        // Only drive paths (`C:\...`) can drop the prefix; other verbatim paths have no
+        // non-verbatim equivalent.
+        if rest.as_bytes().get(1) != Some(&b':') {
+            return path;
+        }
+        rest.to_string()
+    } else {
+        return path;
+    };
+
+    if simplified.len() < MAX_PATH {
+        PathBuf::from(simplified)
+    } else {
+        path
+    }
+}
+
+#[cfg(not(windows))]
+fn simplify_verbatim(path: PathBuf) -> PathBuf {
+    path
+}
+
+/// Copy the executable at `src` to `dst`, creating `dst`'s parent directory and marking it
+/// executable.
+///
+/// If `src` and `dst` resolve to the same file (for example because `bin_dir` is a symlink into
+/// the cache), the copy is skipped, since copying a file onto itself would truncate it.
+pub(crate) fn install_executable(src: &Path, dst: &Path) -> Result<()> {
+    if let Some(parent) = dst.parent() {
+        std::fs::create_dir_all(long_path(parent)).with_context(|_| error::IoSnafu {
+            path: parent.to_path_buf(),
+        })?;
+    }
+
+    if !(dst.exists() && canonicalize_lenient(src) == canonicalize_lenient(dst)) {
+        std::fs::copy(long_path(src), long_path(dst)).with_context(|_| error::CopyBinarySnafu {
+            src: src.to_path_buf(),
+            dst: dst.to_path_buf(),
+        })?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dst, std::fs::Permissions::from_mode(0o755)).with_context(|_| {
+            error::IoSnafu {
+                path: dst.to_path_buf(),
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Remove the quarantine attribute macOS may put on a downloaded file, which makes Gatekeeper
+/// refuse to run it with a "cannot be opened" dialog.
+///
+/// This can't be done in safe Rust without another dependency, so the `xattr` tool that comes with
+/// macOS is used.  A file without the attribute, or one whose attribute can't be removed, is left
+/// as is; the worst that happens is that macOS asks about it when it runs.
+#[cfg(target_os = "macos")]
+#[cfg_attr(not(feature = "prebuilt-binaries"), allow(dead_code))]
+pub(crate) fn remove_quarantine(path: &Path) {
+    let status = std::process::Command::new("/usr/bin/xattr")
+        .args(["-d", "com.apple.quarantine"])
+        .arg(path)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {
+            tracing::debug!("Removed quarantine attribute from {}", path.display());
+        }
+        // The file wasn't quarantined
+        Ok(_) => {}
+        Err(e) => tracing::debug!("Failed to run xattr on {}: {}", path.display(), e),
+    }
+}
+
+/// Remove the quarantine attribute macOS may put on a downloaded file; other platforms have none.
+#[cfg(not(target_os = "macos"))]
+#[cfg_attr(not(feature = "prebuilt-binaries"), allow(dead_code))]
+pub(crate) fn remove_quarantine(_path: &Path) {}
+
 /// Copy source files from src to dst, respecting .gitignore patterns.
 ///
 /// Uses the `ignore` crate to walk the source tree while respecting gitignore rules,
 /// then copies each file to the destination, preserving directory structure.
+///
+/// Each file is copied with [`std::fs::copy`], which clones it rather than copying its contents
+/// wherever the filesystem can: with `clonefile` on APFS, `copy_file_range` on btrfs and XFS, and
+/// block cloning on Windows volumes that have it.  A clone shares the original's blocks until one
+/// of them is written, so copying even a large crate this way takes next to no time or space.  On
+/// any other filesystem, or between two filesystems (see [`same_filesystem`]), it falls back to
+/// copying the bytes by itself.
 pub(crate) fn copy_source_tree(src: &Path, dst: &Path) -> Result<()> {
     use ignore::WalkBuilder;
 
@@ -47,7 +246,7 @@ pub(crate) fn copy_source_tree(src: &Path, dst: &Path) -> Result<()> {
 
         let file_type = entry.file_type().unwrap();
         if file_type.is_dir() {
-            std::fs::create_dir_all(&dst_path)
+            std::fs::create_dir_all(long_path(&dst_path))
                 .map_err(|e| Box::new(e) as _)
                 .with_context(|_| error::CopySourceTreeSnafu {
                     src: src.to_path_buf(),
@@ -55,14 +254,14 @@ pub(crate) fn copy_source_tree(src: &Path, dst: &Path) -> Result<()> {
                 })?;
         } else if file_type.is_file() {
             if let Some(parent) = dst_path.parent() {
-                std::fs::create_dir_all(parent)
+                std::fs::create_dir_all(long_path(parent))
                     .map_err(|e| Box::new(e) as _)
                     .with_context(|_| error::CopySourceTreeSnafu {
                         src: src.to_path_buf(),
                         dst: dst.to_path_buf(),
                     })?;
             }
-            std::fs::copy(src_path, &dst_path)
+            std::fs::copy(long_path(src_path), long_path(&dst_path))
                 .map_err(|e| Box::new(e) as _)
                 .with_context(|_| error::CopySourceTreeSnafu {
                     src: src.to_path_buf(),
@@ -73,3 +272,151 @@ pub(crate) fn copy_source_tree(src: &Path, dst: &Path) -> Result<()> {
 
     Ok(())
 }
+
+/// Whether `a` and `b` are on the same filesystem, which files have to be for one to be cloned
+/// from the other, or `None` if that can't be told.
+///
+/// Neither path has to exist; each is compared by its closest existing ancestor.
+pub(crate) fn same_filesystem(a: &Path, b: &Path) -> Option<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let device = |path: &Path| {
+            path.ancestors()
+                .find_map(|ancestor| std::fs::metadata(ancestor).ok())
+                .map(|metadata| metadata.dev())
+        };
+        Some(device(a)? == device(b)?)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (a, b);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonicalize_lenient_keeps_missing_components() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(temp_dir.path()).unwrap();
+
+        let path = temp_dir.path().join("does").join("not").join("exist");
+        assert_eq!(
+            canonicalize_lenient(&path),
+            root.join("does").join("not").join("exist")
+        );
+    }
+
+    #[test]
+    fn canonicalize_lenient_without_existing_prefix_is_unchanged() {
+        let path = Path::new("no-such-relative-dir").join("child");
+        assert_eq!(canonicalize_lenient(&path), path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn canonicalize_lenient_resolves_symlinked_parent() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(temp_dir.path()).unwrap();
+        std::fs::create_dir(root.join("real")).unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+
+        assert_eq!(
+            canonicalize_lenient(&root.join("link").join("bins")),
+            root.join("real").join("bins")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn same_filesystem_compares_closest_existing_ancestors() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(temp_dir.path().join("cache")).unwrap();
+
+        assert_eq!(
+            same_filesystem(
+                &temp_dir.path().join("cache"),
+                &temp_dir.path().join("build").join("not-yet")
+            ),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn install_executable_creates_parent_dirs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("tool");
+        std::fs::write(&src, b"binary").unwrap();
+
+        let dst = temp_dir.path().join("a").join("b").join("tool");
+        install_executable(&src, &dst).unwrap();
+        assert_eq!(std::fs::read(&dst).unwrap(), b"binary");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&dst).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+
+    /// Copying a binary onto itself through a symlinked directory must not truncate it.
+    #[cfg(unix)]
+    #[test]
+    fn install_executable_onto_itself_via_symlink() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let real = temp_dir.path().join("real");
+        std::fs::create_dir(&real).unwrap();
+        std::fs::write(real.join("tool"), b"binary").unwrap();
+        std::os::unix::fs::symlink(&real, temp_dir.path().join("link")).unwrap();
+
+        install_executable(&real.join("tool"), &temp_dir.path().join("link").join("tool")).unwrap();
+        assert_eq!(std::fs::read(real.join("tool")).unwrap(), b"binary");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn long_path_is_identity_off_windows() {
+        let path = Path::new("/some/./deep/path");
+        assert_eq!(long_path(path), path);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_path_uses_verbatim_prefix() {
+        assert_eq!(
+            long_path(Path::new(r"C:\Users\me\.\cache/cgx\..\cgx\bins")),
+            PathBuf::from(r"\\?\C:\Users\me\cache\cgx\bins")
+        );
+        assert_eq!(
+            long_path(Path::new(r"\\server\share\cgx")),
+            PathBuf::from(r"\\?\UNC\server\share\cgx")
+        );
+        assert_eq!(
+            long_path(Path::new(r"relative\path")),
+            PathBuf::from(r"relative\path")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn simplify_verbatim_drops_unneeded_prefix() {
+        assert_eq!(
+            simplify_verbatim(PathBuf::from(r"\\?\C:\Users\me")),
+            PathBuf::from(r"C:\Users\me")
+        );
+        assert_eq!(
+            simplify_verbatim(PathBuf::from(r"\\?\UNC\server\share")),
+            PathBuf::from(r"\\server\share")
+        );
+
+        let long = format!(r"\\?\C:\{}", "a".repeat(MAX_PATH));
+        assert_eq!(simplify_verbatim(PathBuf::from(&long)), PathBuf::from(long));
+    }
+}
@@ -0,0 +1,207 @@
+//! Lifecycle hooks: commands from the config that cgx runs at points in getting and running a
+//! tool, such as to scan a binary before it runs or to log which tools are used.
+//!
+//! Each hook is a list of commands, split into words the same way as a wrapper command (see
+//! [`crate::runner::parse_wrapper`]) and run in order.  What the hook is about is passed in
+//! environment variables rather than arguments, so a command only has to read the ones it needs:
+//!
+//! - `CGX_HOOK`: the hook being run, e.g. `post_acquire`.
+//! - `CGX_CRATE`: the crate's name, if it's known; a crate from git or a local directory may not
+//!   have one until it's resolved.
+//! - `CGX_CRATE_VERSION`: the resolved version (`post_acquire`, other than for an installed
+//!   binary).
+//! - `CGX_BINARY`: the path of the binary (`post_acquire` and `pre_exec`).
+//! - `CGX_BINARY_SOURCE`: where the binary came from (`post_acquire`): the provider of a pre-built
+//!   binary, `source` if it was built, or `system` if it was already installed.
+//!
+//! A hook's output goes to stderr, so that it can't get mixed up with the binary path that
+//! `--no-exec` prints.  A command that fails stops the run, unless `on_failure` is `warn`.
+//!
+//! The `[hooks.git]` table is different: it holds the commands for git hooks, which cgx runs when
+//! git does rather than as part of getting a tool (see [`crate::git_hooks`]).
+
+use crate::{
+    Result, error,
+    messages::{HookMessage, MessageReporter},
+    runner::parse_wrapper,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    process::{Command, Stdio},
+};
+use strum::{Display, IntoStaticStr};
+
+/// The points at which hooks can run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, IntoStaticStr, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Hook {
+    /// Before the crate is resolved to a version.
+    PreResolve,
+    /// Once there's a binary for the crate, whether it was downloaded, built, or already installed.
+    PostAcquire,
+    /// Right before the binary is run; not at all with `--no-exec`.
+    PreExec,
+}
+
+/// What to do when a hook command fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum HookFailurePolicy {
+    /// Fail the run, so that e.g. a binary a scanner rejected is never run.
+    #[default]
+    Block,
+    /// Warn and carry on.
+    Warn,
+}
+
+/// The commands to run for each hook, from the `[hooks]` table of a config file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Commands run before the crate is resolved.
+    pub pre_resolve: Vec<String>,
+
+    /// Commands run once there's a binary for the crate.
+    pub post_acquire: Vec<String>,
+
+    /// Commands run right before the binary is run.
+    pub pre_exec: Vec<String>,
+
+    /// What to do when a command fails.
+    pub on_failure: HookFailurePolicy,
+
+    /// Commands run by `cgx --git-hook`, keyed by the name of the git hook, each written as it
+    /// would follow `cgx` on the command line.
+    pub git: BTreeMap<String, Vec<String>>,
+}
+
+impl HooksConfig {
+    /// The commands configured for `hook`.
+    pub fn commands(&self, hook: Hook) -> &[String] {
+        match hook {
+            Hook::PreResolve => &self.pre_resolve,
+            Hook::PostAcquire => &self.post_acquire,
+            Hook::PreExec => &self.pre_exec,
+        }
+    }
+
+    /// Check that every command can be split into words.
+    pub(crate) fn validate(&self) -> Result<()> {
+        for hook in [Hook::PreResolve, Hook::PostAcquire, Hook::PreExec] {
+            for command in self.commands(hook) {
+                parse_wrapper(command).map_err(|e| {
+                    error::InvalidConfigValueSnafu {
+                        field: format!("hooks.{}", hook),
+                        message: e.to_string(),
+                    }
+                    .build()
+                })?;
+            }
+        }
+        crate::git_hooks::validate(&self.git)
+    }
+}
+
+/// Run the commands configured for `hook`, in order, with `env` added to their environment.
+///
+/// Fails on the first command that fails, if the failure policy is to block; otherwise failures
+/// are logged as warnings.
+pub fn run(
+    hooks: &HooksConfig,
+    hook: Hook,
+    env: &[(&str, &OsStr)],
+    reporter: &MessageReporter,
+) -> Result<()> {
+    for command in hooks.commands(hook) {
+        reporter.report(|| HookMessage::running(hook, command));
+        let result = run_command(hook, command, env);
+        reporter.report(|| HookMessage::finished(hook, command, result.is_ok()));
+
+        match result {
+            Ok(()) => {}
+            Err(e) if hooks.on_failure == HookFailurePolicy::Warn => tracing::warn!("{}", e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+fn run_command(hook: Hook, command: &str, env: &[(&str, &OsStr)]) -> Result<()> {
+    let words = parse_wrapper(command)?;
+    let Some((program, args)) = words.split_first() else {
+        return Ok(());
+    };
+
+    let failed = |message: String| {
+        error::HookFailedSnafu {
+            hook: hook.to_string(),
+            command,
+            message,
+        }
+        .build()
+    };
+
+    tracing::debug!(%hook, command, "Running hook");
+    let status = Command::new(program)
+        .args(args)
+        .env("CGX_HOOK", <&'static str>::from(hook))
+        .envs(env.iter().copied())
+        .stdin(Stdio::null())
+        .stdout(std::io::stderr())
+        .status()
+        .map_err(|e| failed(e.to_string()))?;
+
+    if !status.success() {
+        return Err(failed(format!("it exited with {}", status)));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn invalid_command_is_rejected() {
+        let hooks = HooksConfig {
+            pre_exec: vec!["scan 'unterminated".to_string()],
+            ..Default::default()
+        };
+        assert_matches!(
+            hooks.validate(),
+            Err(error::Error::InvalidConfigValue { field, .. }) if field == "hooks.pre_exec"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_command_blocks_unless_warned() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = temp_dir.path().join("log");
+        let mut hooks = HooksConfig {
+            post_acquire: vec![
+                format!("sh -c 'echo \"$CGX_HOOK $CGX_BINARY\" >> {}'", log.display()),
+                "false".to_string(),
+            ],
+            ..Default::default()
+        };
+        let env = [("CGX_BINARY", OsStr::new("/bin/tool"))];
+
+        assert_matches!(
+            run(&hooks, Hook::PostAcquire, &env, &MessageReporter::null()),
+            Err(error::Error::HookFailed { hook, .. }) if hook == "post_acquire"
+        );
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "post_acquire /bin/tool\n");
+
+        hooks.on_failure = HookFailurePolicy::Warn;
+        run(&hooks, Hook::PostAcquire, &env, &MessageReporter::null()).unwrap();
+        run(&hooks, Hook::PreExec, &env, &MessageReporter::null()).unwrap();
+    }
+}
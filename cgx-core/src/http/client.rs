@@ -0,0 +1,1084 @@
+//! The [`HttpClient`], on top of `reqwest`.
+
+pub use reqwest::blocking::Response;
+
+use super::{Download, HeaderMap, backoff, user_agent};
+use crate::{
+    Result, cancel,
+    config::HttpConfig,
+    error, proxy_auth,
+    proxy_rules::{ProxyRules, Route},
+    singleflight::SingleFlight,
+};
+use backon::{BlockingRetryable, ExponentialBuilder};
+use bytes::Bytes;
+use reqwest::{Certificate, blocking::Client};
+use sha2::{Digest, Sha256};
+use snafu::ResultExt;
+use std::{
+    io::{ErrorKind, Read, Write},
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tame_index::external::http;
+use tempfile::NamedTempFile;
+
+/// The error of a request that couldn't be made, in [`error::Error::HttpRequest`] and the like.
+pub type RequestError = reqwest::Error;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How much of a download is read at a time, to hash it as it comes in.
+const DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The value of the first of `vars` that is set to a non-empty value.
+fn env_proxy(vars: &[&str]) -> Option<String> {
+    vars.iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+}
+
+fn invalid_proxy(proxy_url: &str, e: reqwest::Error) -> error::Error {
+    error::Error::HttpClientBuild {
+        message: format!("invalid proxy URL '{}': {}", proxy_url, e),
+    }
+}
+
+/// Read the root certificates in the PEM file at `path`, for [`HttpConfig::ca_bundle`].
+fn load_ca_bundle(path: &Path) -> Result<Vec<Certificate>> {
+    let pem = std::fs::read(path).context(error::IoSnafu { path })?;
+    let certs = Certificate::from_pem_bundle(&pem).map_err(|e| {
+        error::InvalidCaBundleSnafu {
+            path,
+            message: e.to_string(),
+        }
+        .build()
+    })?;
+    if certs.is_empty() {
+        return error::InvalidCaBundleSnafu {
+            path,
+            message: "it holds no PEM certificates",
+        }
+        .fail();
+    }
+
+    Ok(certs)
+}
+
+/// HTTP client wrapper with retry, user agent, proxy, and timeout support.
+///
+/// This provides a unified HTTP client for all cgx HTTP operations including:
+/// - Registry queries (sparse index)
+/// - Binary downloads from providers
+/// - API calls to GitHub/GitLab
+///
+/// Git operations use their own transport layer via `gix` and do not use this client directly.
+/// cgx mirrors `HttpConfig` settings into gix where possible: proxy, user agent, retry/backoff,
+/// and timeout (used as both connect timeout and stalled-transfer timeout threshold). See
+/// [`crate::git`] for details.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    config: HttpConfig,
+
+    /// Bytes downloaded by [`HttpClient::try_download`], shared with clones of this client.
+    downloaded: Arc<AtomicU64>,
+
+    /// Downloads by [`HttpClient::try_download`] under way, keyed by URL, shared with clones of
+    /// this client so that an asset asked for by several threads at once is only downloaded once.
+    downloads: Arc<SingleFlight<String, Option<Download>>>,
+}
+
+impl HttpClient {
+    /// Build a new [`HttpClient`] with the given configuration.
+    pub fn new(config: &HttpConfig) -> Result<Self> {
+        let mut builder = Client::builder()
+            .user_agent(user_agent())
+            .timeout(config.timeout)
+            .connect_timeout(CONNECT_TIMEOUT);
+
+        // reqwest honors the standard proxy environment variables by itself, but only with
+        // credentials embedded in their URLs, and without cgx's own `no_proxy` and per-host rules.
+        // So whenever there's a proxy, requests are routed to it here, which also turns off
+        // reqwest's own lookup.
+        let rules = ProxyRules::new(config);
+        let (https, http) = match config.proxy {
+            Some(ref proxy_url) => (Some(proxy_url.clone()), Some(proxy_url.clone())),
+            None => (
+                env_proxy(&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]),
+                env_proxy(&["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]),
+            ),
+        };
+        if https.is_some() || http.is_some() || rules.has_host_rules() {
+            let checked = |proxy_url: &str| -> Result<String> {
+                let with_credentials = proxy_auth::with_credentials(proxy_url, config);
+                reqwest::Proxy::all(&with_credentials).map_err(|e| invalid_proxy(proxy_url, e))?;
+                Ok(with_credentials)
+            };
+            let https = https.as_deref().map(checked).transpose()?;
+            let http = http.as_deref().map(checked).transpose()?;
+            let rules = rules.map_proxies(checked)?;
+
+            builder = builder.proxy(reqwest::Proxy::custom(move |url| {
+                match url.host_str().and_then(|host| rules.route(host)) {
+                    Some(Route::Direct) => None,
+                    Some(Route::Proxy(proxy_url)) => Some(proxy_url),
+                    None if url.scheme() == "https" => https.clone(),
+                    None => http.clone(),
+                }
+            }));
+        }
+
+        if let Some(ref ca_bundle) = config.ca_bundle {
+            builder = builder.tls_certs_only(load_ca_bundle(ca_bundle)?);
+        }
+
+        let client = builder.build().map_err(|e| error::Error::HttpClientBuild {
+            message: e.to_string(),
+        })?;
+
+        Ok(Self {
+            client,
+            config: config.clone(),
+            downloaded: Arc::new(AtomicU64::new(0)),
+            downloads: Arc::new(SingleFlight::new()),
+        })
+    }
+
+    /// A copy of this client that counts the bytes it downloads apart from this one, so that the
+    /// downloads of one operation can be told from those of others going on at the same time.
+    pub fn with_own_download_count(&self) -> Self {
+        Self {
+            downloaded: Arc::new(AtomicU64::new(0)),
+            ..self.clone()
+        }
+    }
+
+    /// How many bytes [`HttpClient::try_download`] has downloaded through this client and its
+    /// clones.
+    pub fn bytes_downloaded(&self) -> u64 {
+        self.downloaded.load(Ordering::Relaxed)
+    }
+
+    /// Send `request` once, without retrying, and read all of the response.
+    ///
+    /// This is for sparse index requests, which [`tame_index`] builds and parses with the types of
+    /// the `http` crate, and which the registry retries by its own policy.
+    pub fn send(&self, request: http::Request<()>) -> Result<http::Response<Vec<u8>>> {
+        cancel::check()?;
+
+        let url = request.uri().to_string();
+        let (parts, ()) = request.into_parts();
+        let response = self
+            .client
+            .request(parts.method, &url)
+            .version(parts.version)
+            .headers(parts.headers)
+            .send()
+            .with_context(|_| error::HttpRequestSnafu { url: url.clone() })?;
+
+        let status = response.status();
+        let version = response.version();
+        let headers = response.headers().clone();
+        let body = response
+            .bytes()
+            .with_context(|_| error::HttpRequestSnafu { url: url.clone() })?;
+
+        let mut parsed = http::Response::new(body.to_vec());
+        *parsed.status_mut() = status;
+        *parsed.version_mut() = version;
+        *parsed.headers_mut() = headers;
+        Ok(parsed)
+    }
+
+    /// Perform a GET request with retry on transient errors.
+    ///
+    /// Retries on certain errors that are considered retriable.
+    ///
+    /// Returns the response even if the status is non-success (except for retryable statuses);
+    /// callers must inspect the status and handle non-2xx as needed.
+    pub fn get(&self, url: &str) -> Result<Response> {
+        self.get_with_headers(url, &HeaderMap::new())
+    }
+
+    /// Perform a GET request with custom headers and retry on transient errors.
+    ///
+    /// Retries on 429 (rate limit), 5xx (server errors), and connection errors.
+    /// Returns the response even if the status is non-success (except for retryable statuses);
+    /// callers must inspect the status and handle non-2xx as needed.
+    pub fn get_with_headers(&self, url: &str, headers: &HeaderMap) -> Result<Response> {
+        let backoff = self.build_backoff();
+        let url_owned = url.to_string();
+        let headers = headers.clone();
+
+        let operation = || {
+            cancel::check()?;
+
+            let mut request = self.client.get(&url_owned);
+            for (key, value) in &headers {
+                request = request.header(key, value);
+            }
+
+            let response = request.send().with_context(|_| error::HttpRequestSnafu {
+                url: url_owned.clone(),
+            })?;
+
+            Self::classify_retryable_status(response, &url_owned)
+        };
+
+        operation
+            .retry(backoff)
+            .when(Self::is_retryable_error)
+            .notify(|err, dur| {
+                tracing::debug!("HTTP request failed, retrying in {:?}: {:?}", dur, err);
+            })
+            .call()
+    }
+
+    /// Perform a POST request with custom headers and retry on transient errors.
+    ///
+    /// Only for requests that are safe to repeat, like GraphQL queries, since a request that
+    /// failed may still have been processed.  Retries and status handling are as for
+    /// [`Self::get_with_headers`].
+    pub fn post_with_headers(&self, url: &str, headers: &HeaderMap, body: &str) -> Result<Response> {
+        let backoff = self.build_backoff();
+        let url_owned = url.to_string();
+        let headers = headers.clone();
+
+        let operation = || {
+            cancel::check()?;
+
+            let mut request = self.client.post(&url_owned).body(body.to_string());
+            for (key, value) in &headers {
+                request = request.header(key, value);
+            }
+
+            let response = request.send().with_context(|_| error::HttpRequestSnafu {
+                url: url_owned.clone(),
+            })?;
+
+            Self::classify_retryable_status(response, &url_owned)
+        };
+
+        operation
+            .retry(backoff)
+            .when(Self::is_retryable_error)
+            .notify(|err, dur| {
+                tracing::debug!("HTTP POST request failed, retrying in {:?}: {:?}", dur, err);
+            })
+            .call()
+    }
+
+    /// Perform a HEAD request with retry on transient errors.
+    ///
+    /// Retries on 429 (rate limit), 5xx (server errors), and connection errors.
+    /// Returns the response even if the status is non-success (except for retryable statuses);
+    /// callers must inspect the status and handle non-2xx as needed.
+    pub fn head(&self, url: &str) -> Result<Response> {
+        let backoff = self.build_backoff();
+        let url_owned = url.to_string();
+
+        let operation = || {
+            cancel::check()?;
+
+            let response = self
+                .client
+                .head(&url_owned)
+                .send()
+                .with_context(|_| error::HttpRequestSnafu {
+                    url: url_owned.clone(),
+                })?;
+
+            Self::classify_retryable_status(response, &url_owned)
+        };
+
+        operation
+            .retry(backoff)
+            .when(Self::is_retryable_error)
+            .notify(|err, dur| {
+                tracing::debug!("HTTP HEAD request failed, retrying in {:?}: {:?}", dur, err);
+            })
+            .call()
+    }
+
+    /// Attempt to download a file from the given URL with retry.
+    ///
+    /// Returns `Ok(Some(bytes))` on success, `Ok(None)` if the server returned 404
+    /// (resource does not exist), or `Err` for any other failure (network errors,
+    /// non-404 HTTP errors after retries).
+    ///
+    /// This is a convenience method that encapsulates the common pattern used by
+    /// all binary providers.
+    ///
+    /// The same URL asked for by another thread while it's being downloaded isn't downloaded
+    /// again; that thread gets the same bytes (see [`crate::singleflight`]).
+    pub fn try_download(&self, url: &str) -> Result<Option<Bytes>> {
+        let Some(download) = self.try_download_hashed(url)? else {
+            return Ok(None);
+        };
+        let bytes = std::fs::read(download.path()).context(error::IoSnafu {
+            path: download.path(),
+        })?;
+        Ok(Some(Bytes::from(bytes)))
+    }
+
+    /// Like [`Self::try_download`], but into a temporary file, which is hashed as it's written so
+    /// that however large the download is, only [`DOWNLOAD_CHUNK_SIZE`] of it is in memory at once.
+    pub fn try_download_hashed(&self, url: &str) -> Result<Option<Download>> {
+        self.try_download_with_progress(url, &|_, _| {})
+    }
+
+    /// Like [`Self::try_download_hashed`], calling `on_progress` with the bytes downloaded so far
+    /// and the total each time another whole percent of a download of known size has been read.
+    ///
+    /// A thread that gets the bytes of another's download of the same URL hears of no progress.
+    pub fn try_download_with_progress(
+        &self,
+        url: &str,
+        on_progress: &dyn Fn(u64, u64),
+    ) -> Result<Option<Download>> {
+        self.downloads.run(url.to_string(), || {
+            self.try_download_uncoalesced(url, on_progress)
+        })
+    }
+
+    fn try_download_uncoalesced(
+        &self,
+        url: &str,
+        on_progress: &dyn Fn(u64, u64),
+    ) -> Result<Option<Download>> {
+        let response = self.get(url)?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            return error::HttpStatusSnafu {
+                url: url.to_string(),
+                status: response.status().as_u16(),
+            }
+            .fail();
+        }
+
+        let total = response.content_length().filter(|total| *total > 0);
+        let mut body = self.download_body(response, url)?;
+        let mut file = NamedTempFile::new().with_context(|_| error::IoSnafu {
+            path: std::env::temp_dir(),
+        })?;
+        let mut hasher = Sha256::new();
+        let mut written = 0;
+        let mut percent = 0;
+        let mut chunk = vec![0; DOWNLOAD_CHUNK_SIZE];
+        let read = loop {
+            match body.read(&mut chunk) {
+                Ok(0) => break Ok(()),
+                Ok(len) => {
+                    hasher.update(&chunk[..len]);
+                    file.write_all(&chunk[..len])
+                        .context(error::IoSnafu { path: file.path() })?;
+                    written += len as u64;
+
+                    // Finishing is left unreported, so a download read in one go reports nothing
+                    if let Some(total) = total {
+                        let now = written.saturating_mul(100) / total;
+                        if now > percent && now < 100 {
+                            percent = now;
+                            on_progress(written, total);
+                        }
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        body.check()?;
+        read.with_context(|_| error::HttpBodyReadSnafu { url: url.to_string() })?;
+        self.downloaded.fetch_add(written, Ordering::Relaxed);
+
+        Ok(Some(Download::new(
+            file.into_temp_path(),
+            crate::helpers::format_hex_lower(hasher.finalize()),
+        )))
+    }
+
+    /// The body of `response`, a download from `url`, to read, as long as its `Content-Length`
+    /// doesn't say it's larger than [`HttpConfig::max_download_size`].
+    ///
+    /// A body with no `Content-Length`, or one that lied about it, stops being read once it goes
+    /// past the limit (see [`DownloadBody`]).
+    pub fn download_body(&self, response: Response, url: &str) -> Result<DownloadBody> {
+        let limit = self.config.max_download_size;
+        if let Some(size) = response.content_length().filter(|size| *size > limit) {
+            return error::DownloadTooLargeSnafu {
+                url,
+                size: Some(size),
+                limit,
+            }
+            .fail();
+        }
+
+        Ok(DownloadBody {
+            response,
+            url: url.to_string(),
+            limit,
+            read: 0,
+        })
+    }
+
+    /// Check if an error indicates a connection/timeout failure (vs a logical HTTP error).
+    ///
+    /// This is used by the GitLab provider to bail early when the server is unreachable,
+    /// rather than continuing to probe ~160 candidate URLs against a dead server.
+    pub fn is_connection_error(err: &error::Error) -> bool {
+        match err {
+            error::Error::HttpRequest { source, .. } => {
+                source.is_connect() || source.is_timeout() || source.is_request()
+            }
+            _ => false,
+        }
+    }
+
+    fn build_backoff(&self) -> ExponentialBuilder {
+        backoff(&self.config)
+    }
+
+    /// Convert retryable HTTP status codes into errors that trigger retry.
+    ///
+    /// Returns `Err(HttpStatus)` only for status codes that we consider retriable, so the
+    /// retry policy can act on them. `Ok(response)` does not imply success; it only
+    /// means the response is not retryable and should be handled by the caller.
+    fn classify_retryable_status(response: Response, url: &str) -> Result<Response> {
+        let status = response.status();
+
+        // 429 Too Many Requests - retryable
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return error::HttpStatusSnafu {
+                url: url.to_string(),
+                status: status.as_u16(),
+            }
+            .fail();
+        }
+
+        // 5xx Server Errors - retryable
+        if status.is_server_error() {
+            return error::HttpStatusSnafu {
+                url: url.to_string(),
+                status: status.as_u16(),
+            }
+            .fail();
+        }
+
+        // All other responses (including 4xx other than 429) are returned as-is
+        Ok(response)
+    }
+
+    fn is_retryable_error(err: &error::Error) -> bool {
+        match err {
+            error::Error::HttpStatus { status, .. } => {
+                *status == reqwest::StatusCode::TOO_MANY_REQUESTS.as_u16() || *status >= 500
+            }
+            error::Error::HttpRequest { source, .. } => {
+                source.is_connect() || source.is_timeout() || source.is_request()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The body of a download, which fails to read once more than its limit has been read from it.
+///
+/// Whatever the body is read by only sees an I/O error then, so call [`DownloadBody::check`]
+/// after reading it to get [`error::Error::DownloadTooLarge`] instead.
+#[derive(Debug)]
+pub struct DownloadBody {
+    response: Response,
+    url: String,
+    limit: u64,
+
+    /// How many bytes have been read so far.
+    read: u64,
+}
+
+impl DownloadBody {
+    /// Fail if reading the body went past the limit.
+    pub fn check(&self) -> Result<()> {
+        if self.read > self.limit {
+            return error::DownloadTooLargeSnafu {
+                url: &self.url,
+                size: None,
+                limit: self.limit,
+            }
+            .fail();
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for DownloadBody {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Reading one byte past the limit is how a body that goes over it is told from one that
+        // ends right at it
+        let allowed = self.limit.saturating_add(1).saturating_sub(self.read);
+        let len = buf.len().min(usize::try_from(allowed).unwrap_or(usize::MAX));
+        if len == 0 && !buf.is_empty() {
+            return Err(std::io::Error::other(format!(
+                "download of {} is larger than {} bytes",
+                self.url, self.limit
+            )));
+        }
+
+        let read = self.response.read(&mut buf[..len])?;
+        self.read += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn test_construction_with_defaults() {
+        let config = HttpConfig::default();
+        HttpClient::new(&config).unwrap();
+
+        // Verify user agent contains version and repo
+        let user_agent = format!(
+            "cgx/{} ({})",
+            env!("CARGO_PKG_VERSION"),
+            env!("CARGO_PKG_REPOSITORY")
+        );
+        assert!(user_agent.contains("cgx/"));
+        assert!(user_agent.contains("github.com"));
+    }
+
+    #[test]
+    fn test_construction_with_http_proxy() {
+        let config = HttpConfig {
+            proxy: Some("http://localhost:8080".to_string()),
+            ..Default::default()
+        };
+        HttpClient::new(&config).unwrap();
+    }
+
+    #[test]
+    fn test_construction_with_socks_proxy() {
+        let config = HttpConfig {
+            proxy: Some("socks5://localhost:1080".to_string()),
+            ..Default::default()
+        };
+        HttpClient::new(&config).unwrap();
+    }
+
+    #[test]
+    fn test_construction_with_invalid_proxy() {
+        let config = HttpConfig {
+            proxy: Some("://invalid-no-scheme".to_string()),
+            ..Default::default()
+        };
+        let result = HttpClient::new(&config);
+        assert_matches!(result, Err(error::Error::HttpClientBuild { .. }));
+    }
+
+    #[test]
+    fn test_construction_with_invalid_ca_bundle() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let ca_bundle = temp_dir.path().join("ca.pem");
+        std::fs::write(&ca_bundle, "not a certificate").unwrap();
+
+        let config = HttpConfig {
+            ca_bundle: Some(ca_bundle),
+            ..Default::default()
+        };
+        assert_matches!(
+            HttpClient::new(&config),
+            Err(error::Error::InvalidCaBundle { .. })
+        );
+    }
+
+    #[test]
+    fn test_is_connection_error() {
+        // HttpStatus is not a connection error
+        let status_err = error::Error::HttpStatus {
+            url: "http://example.com".to_string(),
+            status: 500,
+        };
+        assert!(!HttpClient::is_connection_error(&status_err));
+
+        // HttpClientBuild is not a connection error
+        let build_err = error::Error::HttpClientBuild {
+            message: "test".to_string(),
+        };
+        assert!(!HttpClient::is_connection_error(&build_err));
+    }
+
+    #[test]
+    fn test_construction_with_custom_timeout() {
+        let config = HttpConfig {
+            timeout: Duration::from_secs(120),
+            ..Default::default()
+        };
+        HttpClient::new(&config).unwrap();
+    }
+
+    #[test]
+    fn test_construction_with_zero_retries() {
+        let config = HttpConfig {
+            retries: 0,
+            ..Default::default()
+        };
+        HttpClient::new(&config).unwrap();
+    }
+
+    #[test]
+    fn test_construction_with_https_proxy() {
+        let config = HttpConfig {
+            proxy: Some("https://proxy.example.com:3128".to_string()),
+            ..Default::default()
+        };
+        HttpClient::new(&config).unwrap();
+    }
+
+    #[test]
+    fn test_construction_with_socks5h_proxy() {
+        let config = HttpConfig {
+            proxy: Some("socks5h://localhost:1080".to_string()),
+            ..Default::default()
+        };
+        HttpClient::new(&config).unwrap();
+    }
+
+    #[test]
+    fn test_is_connection_error_various_non_http_errors() {
+        let errors: Vec<error::Error> = vec![
+            error::Error::HttpStatus {
+                url: "http://example.com".to_string(),
+                status: 429,
+            },
+            error::Error::HttpStatus {
+                url: "http://example.com".to_string(),
+                status: 503,
+            },
+            error::Error::HttpClientBuild {
+                message: "bad config".to_string(),
+            },
+            error::Error::InvalidHttpTimeout {
+                value: "not-a-duration".to_string(),
+                source: humantime::parse_duration("not-a-duration").unwrap_err(),
+            },
+        ];
+        for err in &errors {
+            assert!(
+                !HttpClient::is_connection_error(err),
+                "Expected false for {:?}",
+                err
+            );
+        }
+    }
+
+    fn fast_retry_config() -> HttpConfig {
+        HttpConfig {
+            retries: 2,
+            backoff_base: Duration::from_millis(1),
+            backoff_max: Duration::from_millis(10),
+            ..Default::default()
+        }
+    }
+
+    mod classify_retryable_status_tests {
+        use super::*;
+        use httpmock::prelude::*;
+
+        #[test]
+        fn test_get_200_returned_directly() {
+            let server = MockServer::start();
+            let mock = server.mock(|when, then| {
+                when.method(GET).path("/ok");
+                then.status(200).body("success");
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let response = client.get(&server.url("/ok")).unwrap();
+            assert_eq!(response.status(), 200);
+            mock.assert_calls(1);
+        }
+
+        #[test]
+        fn test_get_404_returned_not_retried() {
+            let server = MockServer::start();
+            let mock = server.mock(|when, then| {
+                when.method(GET).path("/notfound");
+                then.status(404);
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let response = client.get(&server.url("/notfound")).unwrap();
+            assert_eq!(response.status(), 404);
+            mock.assert_calls(1);
+        }
+
+        #[test]
+        fn test_get_403_returned_not_retried() {
+            let server = MockServer::start();
+            let mock = server.mock(|when, then| {
+                when.method(GET).path("/forbidden");
+                then.status(403);
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let response = client.get(&server.url("/forbidden")).unwrap();
+            assert_eq!(response.status(), 403);
+            mock.assert_calls(1);
+        }
+
+        #[test]
+        fn test_get_429_triggers_retry() {
+            let server = MockServer::start();
+            let fail_mock = server.mock(|when, then| {
+                when.method(GET).path("/ratelimit");
+                then.status(429);
+            });
+
+            let config = HttpConfig {
+                retries: 1,
+                backoff_base: Duration::from_millis(1),
+                backoff_max: Duration::from_millis(10),
+                ..Default::default()
+            };
+            let client = HttpClient::new(&config).unwrap();
+            let result = client.get(&server.url("/ratelimit"));
+            assert!(result.is_err());
+            fail_mock.assert_calls(2);
+        }
+
+        #[test]
+        fn test_get_500_triggers_retry() {
+            let server = MockServer::start();
+            let fail_mock = server.mock(|when, then| {
+                when.method(GET).path("/error");
+                then.status(500);
+            });
+
+            let config = HttpConfig {
+                retries: 1,
+                backoff_base: Duration::from_millis(1),
+                backoff_max: Duration::from_millis(10),
+                ..Default::default()
+            };
+            let client = HttpClient::new(&config).unwrap();
+            let result = client.get(&server.url("/error"));
+            assert!(result.is_err());
+            fail_mock.assert_calls(2);
+        }
+
+        #[test]
+        fn test_get_503_triggers_retry() {
+            let server = MockServer::start();
+            let fail_mock = server.mock(|when, then| {
+                when.method(GET).path("/unavailable");
+                then.status(503);
+            });
+
+            let config = HttpConfig {
+                retries: 1,
+                backoff_base: Duration::from_millis(1),
+                backoff_max: Duration::from_millis(10),
+                ..Default::default()
+            };
+            let client = HttpClient::new(&config).unwrap();
+            let result = client.get(&server.url("/unavailable"));
+            assert!(result.is_err());
+            fail_mock.assert_calls(2);
+        }
+
+        #[test]
+        fn test_exhausted_retries_returns_error() {
+            let server = MockServer::start();
+            let mock = server.mock(|when, then| {
+                when.method(GET).path("/always-fail");
+                then.status(503);
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let result = client.get(&server.url("/always-fail"));
+            assert_matches!(result, Err(error::Error::HttpStatus { status: 503, .. }));
+            mock.assert_calls(3);
+        }
+
+        #[test]
+        fn test_zero_retries_no_retry_on_5xx() {
+            let server = MockServer::start();
+            let mock = server.mock(|when, then| {
+                when.method(GET).path("/once");
+                then.status(500);
+            });
+
+            let config = HttpConfig {
+                retries: 0,
+                backoff_base: Duration::from_millis(1),
+                backoff_max: Duration::from_millis(10),
+                ..Default::default()
+            };
+            let client = HttpClient::new(&config).unwrap();
+            let result = client.get(&server.url("/once"));
+            assert_matches!(result, Err(error::Error::HttpStatus { status: 500, .. }));
+            mock.assert_calls(1);
+        }
+    }
+
+    mod try_download_tests {
+        use super::*;
+        use httpmock::{HttpMockRequest, HttpMockResponse, prelude::*};
+
+        #[test]
+        fn test_try_download_200_returns_some_bytes() {
+            let server = MockServer::start();
+            server.mock(|when, then| {
+                when.method(GET).path("/binary");
+                then.status(200).body("file-content");
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let result = client.try_download(&server.url("/binary")).unwrap();
+            assert_eq!(result, Some(Bytes::from("file-content")));
+        }
+
+        #[test]
+        fn test_try_download_hashed_gives_sha256() {
+            let server = MockServer::start();
+            let content = vec![7u8; DOWNLOAD_CHUNK_SIZE * 2 + 10];
+            server.mock(|when, then| {
+                when.method(GET).path("/binary");
+                then.status(200).body(&content);
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let download = client
+                .try_download_hashed(&server.url("/binary"))
+                .unwrap()
+                .unwrap();
+            assert_eq!(std::fs::read(download.path()).unwrap(), content);
+            assert_eq!(
+                download.sha256,
+                crate::helpers::format_hex_lower(Sha256::digest(&content))
+            );
+        }
+
+        #[test]
+        fn test_try_download_with_progress_reports_percentages() {
+            let server = MockServer::start();
+            let content = vec![7u8; DOWNLOAD_CHUNK_SIZE * 4];
+            server.mock(|when, then| {
+                when.method(GET).path("/binary");
+                then.status(200).body(&content);
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let progress = std::sync::Mutex::new(Vec::new());
+            client
+                .try_download_with_progress(&server.url("/binary"), &|downloaded, total| {
+                    progress.lock().unwrap().push((downloaded, total));
+                })
+                .unwrap()
+                .unwrap();
+
+            let progress = progress.into_inner().unwrap();
+            assert!(!progress.is_empty());
+            assert!(progress.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert!(
+                progress
+                    .iter()
+                    .all(|(downloaded, total)| *total == content.len() as u64 && downloaded < total)
+            );
+        }
+
+        #[test]
+        fn test_try_download_counts_bytes() {
+            let server = MockServer::start();
+            server.mock(|when, then| {
+                when.method(GET).path("/binary");
+                then.status(200).body("file-content");
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let counted = client.with_own_download_count();
+            counted.try_download(&server.url("/binary")).unwrap();
+            counted.clone().try_download(&server.url("/binary")).unwrap();
+
+            assert_eq!(counted.bytes_downloaded(), 24);
+            assert_eq!(client.bytes_downloaded(), 0);
+        }
+
+        #[test]
+        fn test_try_download_over_max_size_is_refused() {
+            let server = MockServer::start();
+            server.mock(|when, then| {
+                when.method(GET).path("/binary");
+                then.status(200).body("file-content");
+            });
+
+            let at_limit = HttpClient::new(&HttpConfig {
+                max_download_size: 12,
+                ..fast_retry_config()
+            })
+            .unwrap();
+            let result = at_limit.try_download(&server.url("/binary")).unwrap();
+            assert_eq!(result, Some(Bytes::from("file-content")));
+
+            let over_limit = HttpClient::new(&HttpConfig {
+                max_download_size: 11,
+                ..fast_retry_config()
+            })
+            .unwrap();
+            let result = over_limit.try_download(&server.url("/binary"));
+            assert_matches!(
+                result,
+                Err(error::Error::DownloadTooLarge {
+                    size: Some(12),
+                    limit: 11,
+                    ..
+                })
+            );
+            assert_eq!(over_limit.bytes_downloaded(), 0);
+        }
+
+        #[test]
+        fn test_try_download_404_returns_none() {
+            let server = MockServer::start();
+            server.mock(|when, then| {
+                when.method(GET).path("/missing");
+                then.status(404);
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let result = client.try_download(&server.url("/missing")).unwrap();
+            assert_eq!(result, None);
+        }
+
+        #[test]
+        fn test_try_download_403_returns_error() {
+            let server = MockServer::start();
+            server.mock(|when, then| {
+                when.method(GET).path("/denied");
+                then.status(403);
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let result = client.try_download(&server.url("/denied"));
+            assert_matches!(result, Err(error::Error::HttpStatus { status: 403, .. }));
+        }
+
+        #[test]
+        fn test_try_download_retries_then_succeeds() {
+            let server = MockServer::start();
+            let call_count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let call_count_for_mock = Arc::clone(&call_count);
+
+            let mock = server.mock(|when, then| {
+                when.method(GET).path("/flaky");
+                then.respond_with(move |_req: &HttpMockRequest| {
+                    let attempt = call_count_for_mock.fetch_add(1, Ordering::SeqCst);
+                    if attempt == 0 {
+                        HttpMockResponse::builder().status(500).build()
+                    } else {
+                        HttpMockResponse::builder().status(200).body("recovered").build()
+                    }
+                });
+            });
+
+            let retry_config = fast_retry_config();
+            let retry_client = HttpClient::new(&retry_config).unwrap();
+            let result = retry_client.try_download(&server.url("/flaky")).unwrap();
+            assert_eq!(result, Some(Bytes::from("recovered")));
+            mock.assert_calls(2);
+        }
+    }
+
+    mod header_tests {
+        use super::*;
+        use crate::http::{ACCEPT, AUTHORIZATION, HeaderValue};
+        use httpmock::{Method::HEAD, prelude::*};
+
+        #[test]
+        fn test_user_agent_header_sent() {
+            let server = MockServer::start();
+            let expected_ua = format!(
+                "cgx/{} ({})",
+                env!("CARGO_PKG_VERSION"),
+                env!("CARGO_PKG_REPOSITORY")
+            );
+            let mock = server.mock(|when, then| {
+                when.method(GET)
+                    .path("/ua-check")
+                    .header("user-agent", &expected_ua);
+                then.status(200);
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            client.get(&server.url("/ua-check")).unwrap();
+            mock.assert();
+        }
+
+        #[test]
+        fn test_get_with_headers_sends_custom_headers() {
+            let server = MockServer::start();
+            let mock = server.mock(|when, then| {
+                when.method(GET)
+                    .path("/auth-check")
+                    .header("authorization", "Bearer token123")
+                    .header("accept", "application/json");
+                then.status(200);
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer token123"));
+            headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+            client
+                .get_with_headers(&server.url("/auth-check"), &headers)
+                .unwrap();
+            mock.assert();
+        }
+
+        #[test]
+        fn test_head_sends_head_request() {
+            let server = MockServer::start();
+            let mock = server.mock(|when, then| {
+                when.method(HEAD).path("/head-check");
+                then.status(200);
+            });
+
+            let client = HttpClient::new(&fast_retry_config()).unwrap();
+            let response = client.head(&server.url("/head-check")).unwrap();
+            assert_eq!(response.status(), 200);
+            mock.assert();
+        }
+
+        #[test]
+        fn test_head_retries_on_429() {
+            let server = MockServer::start();
+            let mock = server.mock(|when, then| {
+                when.method(HEAD).path("/head-ratelimit");
+                then.status(429);
+            });
+
+            let config = HttpConfig {
+                retries: 1,
+                backoff_base: Duration::from_millis(1),
+                backoff_max: Duration::from_millis(10),
+                ..Default::default()
+            };
+            let client = HttpClient::new(&config).unwrap();
+            let result = client.head(&server.url("/head-ratelimit"));
+            assert!(result.is_err());
+            mock.assert_calls(2);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! The stand-in for [`HttpClient`] without the `http` feature, whose requests all fail with
+//! [`crate::error::Error::FeatureDisabled`].
+
+use super::{Download, HeaderMap};
+use crate::{Result, config::HttpConfig, error};
+use bytes::Bytes;
+use std::{fmt, io::Read};
+use tame_index::external::http::{self, StatusCode};
+
+/// The error of a request that couldn't be made, which there never is without the `http` feature,
+/// since no request is ever made.
+#[derive(Debug)]
+pub struct RequestError {
+    _private: (),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HTTP is not available without the `http` feature")
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+/// The response to a request, which there never is without the `http` feature.
+#[derive(Debug)]
+pub enum Response {}
+
+impl Response {
+    pub fn status(&self) -> StatusCode {
+        match *self {}
+    }
+}
+
+/// The body of a download, which there never is without the `http` feature.
+#[derive(Debug)]
+pub enum DownloadBody {}
+
+impl DownloadBody {
+    pub fn check(&self) -> Result<()> {
+        match *self {}
+    }
+}
+
+impl Read for DownloadBody {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        match *self {}
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpClient {}
+
+impl HttpClient {
+    /// Without the `http` feature there's nothing to configure, so this always succeeds.
+    pub fn new(_config: &HttpConfig) -> Result<Self> {
+        Ok(Self {})
+    }
+
+    pub fn with_own_download_count(&self) -> Self {
+        self.clone()
+    }
+
+    /// Without the `http` feature nothing is ever downloaded.
+    pub fn bytes_downloaded(&self) -> u64 {
+        0
+    }
+
+    /// Without the `http` feature there's no HTTP, so this always fails.
+    pub fn send(&self, request: http::Request<()>) -> Result<http::Response<Vec<u8>>> {
+        disabled(&request.uri().to_string())
+    }
+
+    /// Without the `http` feature there's no HTTP, so this always fails.
+    pub fn get(&self, url: &str) -> Result<Response> {
+        disabled(url)
+    }
+
+    /// Without the `http` feature there's no HTTP, so this always fails.
+    pub fn get_with_headers(&self, url: &str, _headers: &HeaderMap) -> Result<Response> {
+        disabled(url)
+    }
+
+    /// Without the `http` feature there's no HTTP, so this always fails.
+    pub fn post_with_headers(&self, url: &str, _headers: &HeaderMap, _body: &str) -> Result<Response> {
+        disabled(url)
+    }
+
+    /// Without the `http` feature there's no HTTP, so this always fails.
+    pub fn head(&self, url: &str) -> Result<Response> {
+        disabled(url)
+    }
+
+    /// Without the `http` feature there's no HTTP, so this always fails.
+    pub fn try_download(&self, url: &str) -> Result<Option<Bytes>> {
+        disabled(url)
+    }
+
+    /// Without the `http` feature there's no HTTP, so this always fails.
+    pub fn try_download_hashed(&self, url: &str) -> Result<Option<Download>> {
+        disabled(url)
+    }
+
+    /// Without the `http` feature there's no HTTP, so this always fails.
+    pub fn try_download_with_progress(
+        &self,
+        url: &str,
+        _on_progress: &dyn Fn(u64, u64),
+    ) -> Result<Option<Download>> {
+        disabled(url)
+    }
+
+    pub fn download_body(&self, response: Response, _url: &str) -> Result<DownloadBody> {
+        match response {}
+    }
+
+    /// Without the `http` feature no request is ever made, so no error is a connection error.
+    pub fn is_connection_error(_err: &error::Error) -> bool {
+        false
+    }
+}
+
+fn disabled<T>(url: &str) -> Result<T> {
+    error::FeatureDisabledSnafu {
+        feature: "http",
+        what: format!("Downloading {}", url),
+    }
+    .fail()
+}
@@ -0,0 +1,314 @@
+//! Installing tools for good with `cgx --install`, and the manifest `--list-install-dir`,
+//! `--uninstall` and `--upgrade` use to manage them.
+//!
+//! An installed tool's binary is copied from the binary cache into [`Config::install_dir`], which
+//! defaults to `~/.cargo/bin` so that it's on the `PATH` wherever `cargo install` would be.  What
+//! was installed is recorded in `installs.json` in cgx's own [`Config::installs_dir`] rather than
+//! in the install directory, which is shared with other tools (an [`InstallManifest`]).  It's
+//! keyed by the name of the crate, and has the crate specs and build options each tool was
+//! installed with, so that `--upgrade` can get it again the same way.  Installs and uninstalls
+//! hold a lock on the manifest from reading it until it's written back, so that two at once don't
+//! lose each other's changes.  A file in the install directory that cgx didn't put there, such as
+//! one `cargo install` did, is never replaced; installing over it fails with
+//! [`Error::InstallConflict`].
+//!
+//! [`Error::InstallConflict`]: crate::error::Error::InstallConflict
+
+use crate::{
+    Result, builder::BuildOptions, config::Config, crate_resolver::ResolvedCrate, cratespec::CrateSpec, error,
+};
+use chrono::{DateTime, Utc};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tame_index::utils::flock::{FileLock, LockOptions};
+
+/// Name of the manifest of installed tools in [`Config::installs_dir`].
+const MANIFEST_FILE_NAME: &str = "installs.json";
+
+/// Name of the file in [`Config::installs_dir`] that's locked while the manifest is changed.
+const LOCK_FILE_NAME: &str = "installs.lock";
+
+/// The tools installed into an install directory, keyed by the name of their crate.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallManifest {
+    pub tools: BTreeMap<String, InstalledTool>,
+}
+
+/// One tool in an [`InstallManifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledTool {
+    /// The version that was installed.
+    pub version: Version,
+
+    /// The installed binary, in the install directory.
+    pub binary: PathBuf,
+
+    /// The crate specs the tool was installed from, as they were loaded from the command line,
+    /// so that `--upgrade` resolves them again the same way.
+    pub crate_specs: Vec<CrateSpec>,
+
+    /// The options the tool was built with.
+    pub build_options: BuildOptions,
+
+    /// When it was installed.
+    pub installed_at: DateTime<Utc>,
+}
+
+impl InstallManifest {
+    /// Load the manifest in `installs_dir`, which is empty if nothing has been installed.
+    pub fn load(installs_dir: &Path) -> Result<Self> {
+        let path = installs_dir.join(MANIFEST_FILE_NAME);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).context(error::JsonSnafu),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(error::Error::Io { path, source }),
+        }
+    }
+
+    /// Write the manifest into `installs_dir`, by way of a temporary file so that it's never left
+    /// half written.
+    fn save(&self, installs_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(installs_dir).context(error::IoSnafu { path: installs_dir })?;
+        let path = installs_dir.join(MANIFEST_FILE_NAME);
+        let json = serde_json::to_string_pretty(self).context(error::JsonSnafu)?;
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, json).context(error::IoSnafu {
+            path: temp_path.clone(),
+        })?;
+        std::fs::rename(&temp_path, &path).context(error::RenameFileSnafu {
+            src: temp_path.clone(),
+            dst: path.clone(),
+        })
+    }
+
+    /// Lock the manifest in `installs_dir` against changes by other processes, waiting for
+    /// whichever has it locked, until the lock returned is dropped.
+    fn lock(installs_dir: &Path) -> Result<FileLock> {
+        let path = installs_dir.join(LOCK_FILE_NAME);
+        let locked = match tame_index::Path::from_path(&path) {
+            Some(utf8_path) => LockOptions::new(utf8_path).exclusive(false).lock(|_| None),
+            None => Err(tame_index::Error::NonUtf8Path(path.clone())),
+        };
+        locked.context(error::InstallManifestLockSnafu { path })
+    }
+
+    /// The tool whose binary is at `binary`, if any.
+    fn owner_of(&self, binary: &Path) -> Option<&str> {
+        self.tools
+            .iter()
+            .find(|(_, tool)| tool.binary == binary)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Install the binary at `bin_path`, of the crate `crate_specs` resolved to, into
+/// [`Config::install_dir`], and record it in the manifest.
+///
+/// An earlier install of the same crate is replaced, and its binary removed if this one is named
+/// differently.
+pub fn install(
+    config: &Config,
+    resolved: &ResolvedCrate,
+    crate_specs: &[CrateSpec],
+    build_options: &BuildOptions,
+    bin_path: &Path,
+) -> Result<InstalledTool> {
+    let install_dir = &config.install_dir;
+    let _lock = InstallManifest::lock(&config.installs_dir)?;
+    let mut manifest = InstallManifest::load(&config.installs_dir)?;
+
+    let file_name = bin_path.file_name().ok_or_else(|| error::Error::Io {
+        path: bin_path.to_path_buf(),
+        source: std::io::Error::new(std::io::ErrorKind::InvalidInput, "binary path has no filename"),
+    })?;
+    let binary = install_dir.join(file_name);
+    match manifest.owner_of(&binary) {
+        Some(owner) if owner == resolved.name => {}
+        Some(owner) => {
+            return error::InstallConflictSnafu {
+                path: binary,
+                owner: owner.to_string(),
+            }
+            .fail();
+        }
+        None if binary.exists() => {
+            return error::InstallConflictSnafu {
+                path: binary,
+                owner: None::<String>,
+            }
+            .fail();
+        }
+        None => {}
+    }
+
+    crate::helpers::install_executable(bin_path, &binary)?;
+
+    let tool = InstalledTool {
+        version: resolved.version.clone(),
+        binary: binary.clone(),
+        crate_specs: crate_specs.to_vec(),
+        build_options: build_options.clone(),
+        installed_at: Utc::now(),
+    };
+    if let Some(previous) = manifest.tools.insert(resolved.name.clone(), tool.clone()) {
+        if previous.binary != binary {
+            remove_binary(&previous.binary)?;
+        }
+    }
+    manifest.save(&config.installs_dir)?;
+
+    Ok(tool)
+}
+
+/// Remove the tool `name` from [`Config::install_dir`], and return what was installed.
+pub fn uninstall(config: &Config, name: &str) -> Result<InstalledTool> {
+    let _lock = InstallManifest::lock(&config.installs_dir)?;
+    let mut manifest = InstallManifest::load(&config.installs_dir)?;
+    let tool = manifest
+        .tools
+        .remove(name)
+        .ok_or_else(|| error::NotInstalledSnafu { name }.build())?;
+
+    remove_binary(&tool.binary)?;
+    manifest.save(&config.installs_dir)?;
+
+    Ok(tool)
+}
+
+/// Remove an installed binary, which is fine if it's already gone.
+fn remove_binary(binary: &Path) -> Result<()> {
+    match std::fs::remove_file(binary) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(error::Error::Io {
+            path: binary.to_path_buf(),
+            source: e,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_resolver::ResolvedSource;
+    use assert_matches::assert_matches;
+
+    fn resolved(name: &str, version: &str) -> ResolvedCrate {
+        ResolvedCrate {
+            name: name.to_string(),
+            version: Version::parse(version).unwrap(),
+            source: ResolvedSource::CratesIo,
+        }
+    }
+
+    #[test]
+    fn tools_are_installed_upgraded_and_uninstalled() {
+        let (temp_dir, config) = crate::config::create_test_env();
+        let cached = temp_dir.path().join("cached");
+        std::fs::create_dir_all(&cached).unwrap();
+        let bin_path = cached.join("rg");
+        std::fs::write(&bin_path, "v14.0").unwrap();
+        let specs = [CrateSpec::CratesIo {
+            name: "ripgrep".to_string(),
+            version: None,
+        }];
+        let build_options = BuildOptions::default();
+
+        let tool = install(
+            &config,
+            &resolved("ripgrep", "14.0.0"),
+            &specs,
+            &build_options,
+            &bin_path,
+        )
+        .unwrap();
+        assert_eq!(tool.binary, config.install_dir.join("rg"));
+        assert_eq!(std::fs::read_to_string(&tool.binary).unwrap(), "v14.0");
+
+        // Installing again replaces the binary and the record
+        std::fs::write(&bin_path, "v14.1").unwrap();
+        install(
+            &config,
+            &resolved("ripgrep", "14.1.0"),
+            &specs,
+            &build_options,
+            &bin_path,
+        )
+        .unwrap();
+        let manifest = InstallManifest::load(&config.installs_dir).unwrap();
+        assert_eq!(manifest.tools.len(), 1);
+        assert_eq!(manifest.tools["ripgrep"].version, Version::new(14, 1, 0));
+        assert_eq!(manifest.tools["ripgrep"].crate_specs, specs);
+        assert_eq!(std::fs::read_to_string(&tool.binary).unwrap(), "v14.1");
+
+        // Neither another crate's binary of the same name nor one cgx didn't install is replaced
+        assert_matches!(
+            install(&config, &resolved("rg", "1.0.0"), &specs, &build_options, &bin_path),
+            Err(error::Error::InstallConflict { owner: Some(owner), .. }) if owner == "ripgrep"
+        );
+        let other = cached.join("fd");
+        std::fs::write(&other, "").unwrap();
+        std::fs::write(config.install_dir.join("fd"), "cargo install").unwrap();
+        assert_matches!(
+            install(
+                &config,
+                &resolved("fd-find", "10.0.0"),
+                &specs,
+                &build_options,
+                &other
+            ),
+            Err(error::Error::InstallConflict { owner: None, .. })
+        );
+
+        uninstall(&config, "ripgrep").unwrap();
+        assert!(!tool.binary.exists());
+        assert!(
+            InstallManifest::load(&config.installs_dir)
+                .unwrap()
+                .tools
+                .is_empty()
+        );
+        assert_matches!(
+            uninstall(&config, "ripgrep"),
+            Err(error::Error::NotInstalled { .. })
+        );
+    }
+
+    #[test]
+    fn concurrent_installs_are_all_recorded() {
+        let (temp_dir, config) = crate::config::create_test_env();
+        let cached = temp_dir.path().join("cached");
+        std::fs::create_dir_all(&cached).unwrap();
+
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let (config, cached) = (&config, &cached);
+                scope.spawn(move || {
+                    let name = format!("tool{i}");
+                    let bin_path = cached.join(&name);
+                    std::fs::write(&bin_path, &name).unwrap();
+                    let specs = [CrateSpec::CratesIo {
+                        name: name.clone(),
+                        version: None,
+                    }];
+                    install(
+                        config,
+                        &resolved(&name, "1.0.0"),
+                        &specs,
+                        &BuildOptions::default(),
+                        &bin_path,
+                    )
+                    .unwrap();
+                });
+            }
+        });
+
+        let manifest = InstallManifest::load(&config.installs_dir).unwrap();
+        assert_eq!(manifest.tools.len(), 8);
+        assert!(!config.install_dir.join(MANIFEST_FILE_NAME).exists());
+    }
+}
@@ -1,34 +1,103 @@
+pub mod as_of;
+pub mod assets;
+pub(crate) mod bin_dedup;
 pub mod bin_resolver;
+pub(crate) mod binary_format;
+pub mod build_diagnostics;
 pub mod builder;
 pub(crate) mod cache;
+pub(crate) mod cache_layout;
+pub mod cancel;
 pub mod cargo;
 pub mod cli;
+pub mod completion;
 pub mod config;
+pub mod config_edit;
 pub mod crate_resolver;
 pub mod cratespec;
+pub mod detach;
 pub mod downloader;
+pub mod ephemeral;
 pub mod error;
+pub mod freeze;
 pub mod git;
+pub mod git_hooks;
 pub(crate) mod helpers;
+pub mod hooks;
 pub mod http;
+pub mod installer;
 pub(crate) mod logging;
+pub(crate) mod memory_cache;
 pub mod messages;
+pub mod metrics;
+pub mod plan;
+pub(crate) mod platform;
+pub mod project_env;
+pub(crate) mod proxy_auth;
+pub(crate) mod proxy_rules;
 pub(crate) mod registry;
+pub mod release_notes;
 pub mod runner;
 pub(crate) mod sbom;
+pub mod shell;
+pub(crate) mod singleflight;
+pub mod source_file;
+pub mod source_provider;
+pub mod successors;
+pub mod system_binary;
+pub mod telemetry;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 #[cfg(test)]
 pub(crate) mod testdata;
+pub mod timeouts;
+pub mod tool_alias;
+pub mod tool_pin;
+pub mod trust;
+pub mod usage;
+pub mod vendor;
 
 use bin_resolver::BinaryResolver;
 use builder::{BuildOptions, CrateBuilder};
 use cache::Cache;
+use cancel::CancellationToken;
 use config::Config;
-use crate_resolver::CrateResolver;
-use cratespec::CrateSpec;
+use crate_resolver::{CrateResolver, ResolvedCrate, ResolvedSource};
+use cratespec::{CrateSpec, RegistrySource};
 use downloader::CrateDownloader;
 use error::Result;
+use hooks::Hook;
 use http::HttpClient;
-use std::sync::Arc;
+use snafu::{OptionExt, ResultExt};
+use source_provider::{SourceProvider, SourceProviders};
+use std::{ffi::OsStr, sync::Arc, time::Instant};
+use timeouts::{Budget, Phase};
+use tracing::field::Empty;
+
+/// The optional cargo features of this crate that were enabled when it was compiled.
+pub const ENABLED_FEATURES: &[&str] = &[
+    #[cfg(feature = "git")]
+    "git",
+    #[cfg(feature = "http")]
+    "http",
+    #[cfg(feature = "prebuilt-binaries")]
+    "prebuilt-binaries",
+    #[cfg(feature = "sbom")]
+    "sbom",
+    #[cfg(feature = "test-support")]
+    "test-support",
+];
+
+/// A binary got by [`Cgx::acquire`].
+struct Acquired {
+    path: std::path::PathBuf,
+
+    /// Where the binary came from, as given to the `post_acquire` hooks.
+    source: &'static str,
+
+    /// The crate the binary is of, unless it's an installed binary, which isn't resolved.
+    resolved: Option<ResolvedCrate>,
+}
 
 /// Instance of the engine that powers the `cgx` tool.
 ///
@@ -42,19 +111,71 @@ pub struct Cgx {
     bin_resolver: Arc<dyn BinaryResolver>,
     downloader: Arc<dyn CrateDownloader>,
     builder: Arc<dyn CrateBuilder>,
+    cache: Cache,
+    config: Config,
+    reporter: messages::MessageReporter,
+
+    /// When the run this is part of started, if the deadline in [`Config::timeouts`] counts from
+    /// then for every operation (see [`CgxBuilder::deadline_from`]), rather than from the start of
+    /// each one.
+    run_started: Option<Instant>,
 }
 
-impl Cgx {
-    /// Create a new instance from a loaded configuration.
+/// Builds a [`Cgx`] with extensions beyond what its [`Config`] can express, such as
+/// [`SourceProvider`]s.
+///
+/// Created with [`Cgx::builder`].
+#[derive(Debug)]
+pub struct CgxBuilder {
+    config: Config,
+    reporter: messages::MessageReporter,
+    source_providers: SourceProviders,
+    run_started: Option<Instant>,
+}
+
+impl CgxBuilder {
+    /// Serve crate specs that are URLs of `provider`'s scheme with it, in place of any provider
+    /// registered for the same scheme before.
+    pub fn source_provider(mut self, provider: impl SourceProvider) -> Self {
+        self.source_providers.register(Arc::new(provider));
+        self
+    }
+
+    /// Count the deadline in [`Config::timeouts`] from `started` for every operation, so that it
+    /// limits them all together, as it does a whole run of the cgx command line with `--batch`.
     ///
-    /// The config should be loaded using [`Config::load()`] with the CLI args.
-    pub fn new(config: Config, reporter: messages::MessageReporter) -> Result<Self> {
+    /// Otherwise the deadline counts from the start of each operation, such as each call of
+    /// [`Cgx::crate_to_bin`], so that an instance kept around doesn't run out of time.
+    pub fn deadline_from(mut self, started: Instant) -> Self {
+        self.run_started = Some(started);
+        self
+    }
+
+    /// Create the [`Cgx`] instance.
+    pub fn build(self) -> Result<Cgx> {
+        let Self {
+            config,
+            reporter,
+            source_providers,
+            run_started,
+        } = self;
+
         tracing::debug!("Using config: {:#?}", config);
 
+        if config.require_sbom && !cfg!(feature = "sbom") {
+            return error::FeatureDisabledSnafu {
+                feature: "sbom",
+                what: "`require_sbom`",
+            }
+            .fail();
+        }
+
+        cache_layout::prepare(&config)?;
+
         let http_client = HttpClient::new(&config.http)?;
 
         let cache = Cache::new(config.clone(), reporter.clone());
-        let git_client = git::GitClient::new(cache.clone(), reporter.clone(), config.http.clone());
+        let git_client = git::GitClient::new(cache.clone(), reporter.clone(), &config);
 
         let cargo_runner = Arc::new(cargo::find_cargo(reporter.clone())?);
 
@@ -64,6 +185,7 @@ impl Cgx {
             git_client.clone(),
             cargo_runner.clone(),
             http_client.clone(),
+            source_providers.clone(),
         ));
 
         let bin_resolver = Arc::new(bin_resolver::create_resolver(
@@ -78,19 +200,52 @@ impl Cgx {
             cache.clone(),
             git_client,
             http_client,
+            source_providers,
         ));
 
-        let builder = Arc::new(builder::create_builder(config, cache, cargo_runner));
+        let builder = Arc::new(builder::create_builder(
+            config.clone(),
+            cache.clone(),
+            cargo_runner,
+            reporter.clone(),
+        ));
 
-        Ok(Self {
+        Ok(Cgx {
             resolver,
             bin_resolver,
             downloader,
             builder,
+            cache,
+            config,
+            reporter,
+            run_started,
         })
     }
+}
+
+impl Cgx {
+    /// Create a new instance from a loaded configuration.
+    ///
+    /// The config should be loaded using [`Config::load()`] with the CLI args.
+    pub fn new(config: Config, reporter: messages::MessageReporter) -> Result<Self> {
+        Self::builder(config, reporter).build()
+    }
 
-    /// Run the cgx engine with the given crate spec and build options.
+    /// Start building an instance from a loaded configuration, to register extensions with before
+    /// it's created.
+    pub fn builder(config: Config, reporter: messages::MessageReporter) -> CgxBuilder {
+        CgxBuilder {
+            config,
+            reporter,
+            source_providers: SourceProviders::default(),
+            run_started: None,
+        }
+    }
+
+    /// Run the cgx engine with the given crate spec candidates and build options.
+    ///
+    /// `crate_specs` are the specs to try in order, as returned by
+    /// [`CrateSpec::load_candidates`]; the first one that the crate is found in is used.
     ///
     /// This is the main execution path that:
     /// - Resolves the crate spec to a concrete version
@@ -102,49 +257,302 @@ impl Cgx {
     /// This method does NOT execute the binary - that's left to the caller.
     pub fn crate_to_bin(
         &self,
-        crate_spec: &CrateSpec,
+        crate_specs: &[CrateSpec],
+        build_options: &BuildOptions,
+    ) -> Result<std::path::PathBuf> {
+        self.crate_to_bin_with_cancel(crate_specs, build_options, &CancellationToken::new())
+    }
+
+    /// Like [`Self::crate_to_bin`], but abandoned as soon as possible once `token` is cancelled.
+    ///
+    /// This is for programs embedding cgx, like GUIs and editor integrations, that need to abort
+    /// a long resolution, download or build.  Cancelling `token` from another thread makes this
+    /// fail with [`error::Error::Cancelled`] at the next point where it checks, killing the cargo
+    /// process if a build is running.  See [`cancel`] for details.
+    pub fn crate_to_bin_with_cancel(
+        &self,
+        crate_specs: &[CrateSpec],
+        build_options: &BuildOptions,
+        token: &CancellationToken,
+    ) -> Result<std::path::PathBuf> {
+        let result = cancel::with_token(token, || self.crate_to_bin_inner(crate_specs, build_options));
+
+        // Whatever an interrupted step failed with, the real reason is the cancellation
+        match result {
+            Err(e) if token.is_cancelled() && !matches!(e, error::Error::Cancelled) => {
+                tracing::debug!("Operation failed after being cancelled: {e}");
+                error::CancelledSnafu.fail()
+            }
+            result => result,
+        }
+    }
+
+    /// Like [`Self::crate_to_bin`], but following the acquisition plan at `plan_path` if it's for
+    /// this same invocation, and writing one there once the binary is got if it isn't (see
+    /// [`plan`]).
+    ///
+    /// With `--refresh=resolve` the plan isn't followed, and with `--refresh=binary` its binary
+    /// isn't used, but either way it's written afresh.
+    pub fn crate_to_bin_with_plan(
+        &self,
+        crate_specs: &[CrateSpec],
+        build_options: &BuildOptions,
+        plan_path: &std::path::Path,
+    ) -> Result<std::path::PathBuf> {
+        let invocation = plan::invocation_key(crate_specs, build_options, self.config.as_of)?;
+        let planned = if self.config.refresh.resolve {
+            None
+        } else {
+            plan::AcquisitionPlan::load(plan_path)?.filter(|plan| plan.invocation == invocation)
+        };
+
+        if let Some(plan) = &planned {
+            let binary_intact = !self.config.refresh.binary && plan.binary_is_intact();
+            tracing::info!(
+                "Following acquisition plan {} for {}@{}",
+                plan_path.display(),
+                plan.krate.name,
+                plan.krate.version
+            );
+            self.reporter
+                .report(|| messages::CrateResolutionMessage::plan_followed(plan_path, plan, binary_intact));
+
+            if binary_intact {
+                if self.config.require_sbom {
+                    self.verify_sbom(
+                        &plan.krate.name,
+                        &plan.krate.version.to_string(),
+                        &plan.binary,
+                        false,
+                    )?;
+                }
+                return Ok(plan.binary.clone());
+            }
+        }
+
+        let acquired = self.acquire(crate_specs, build_options, planned.map(|plan| plan.krate), true)?;
+        match acquired.resolved {
+            Some(resolved) if !matches!(resolved.source, ResolvedSource::LocalDir { .. }) => {
+                plan::AcquisitionPlan::new(
+                    invocation,
+                    resolved,
+                    self.config.as_of,
+                    &acquired.path,
+                    acquired.source,
+                )?
+                .save(plan_path)?;
+                tracing::info!("Wrote acquisition plan to {}", plan_path.display());
+            }
+            _ => tracing::info!("Not writing an acquisition plan for a local crate or installed binary"),
+        }
+
+        Ok(acquired.path)
+    }
+
+    fn crate_to_bin_inner(
+        &self,
+        crate_specs: &[CrateSpec],
         build_options: &BuildOptions,
     ) -> Result<std::path::PathBuf> {
-        tracing::debug!("Got crate spec: {:?}", crate_spec);
+        self.acquire(crate_specs, build_options, None, true)
+            .map(|acquired| acquired.path)
+    }
+
+    /// Get the binary of the crate in `crate_specs`, or of `planned` without resolving anything if
+    /// it's given, the crate an acquisition plan resolved to (see [`plan`]).
+    ///
+    /// An installed binary is only looked for if `system_binaries` is set, as well as
+    /// `prefer_system_binaries` in the config (see [`system_binary`]).
+    fn acquire(
+        &self,
+        crate_specs: &[CrateSpec],
+        build_options: &BuildOptions,
+        planned: Option<ResolvedCrate>,
+        system_binaries: bool,
+    ) -> Result<Acquired> {
+        tracing::debug!("Got crate specs: {:?}", crate_specs);
 
-        tracing::info!("Resolving crate...");
-        let resolved_crate = self.resolver.resolve(crate_spec)?;
+        // Each phase gets a span of its own so that where the time goes can be traced (see
+        // `telemetry`), in which the cache records whether it had what the phase needed
+        let span = tracing::info_span!("crate_to_bin", krate = Empty, version = Empty);
+        let _span = span.enter();
+
+        let spec_name = crate_specs.first().and_then(|spec| spec.name());
+        let crate_env = spec_name
+            .map(|name| ("CGX_CRATE", OsStr::new(name)))
+            .into_iter()
+            .collect::<Vec<_>>();
+        hooks::run(&self.config.hooks, Hook::PreResolve, &crate_env, &self.reporter)?;
+
+        // An installed binary is only as good as cgx's own when nothing about the build was
+        // customized, the same as for a pre-built binary, and it never has an SBOM.  It's also
+        // only ever for the host, whatever target the build is for.
+        if planned.is_none()
+            && system_binaries
+            && self.config.prefer_system_binaries
+            && !self.config.require_sbom
+            && build_options.target.is_none()
+            && !self.config.refresh.binary
+            && bin_resolver::is_disqualified(build_options, &self.config.prebuilt_binaries).is_none()
+        {
+            let known_bins = self.known_bins(spec_name.unwrap_or_default(), build_options);
+            if let Some(path) = crate_specs
+                .first()
+                .and_then(|spec| system_binary::find(&self.config, spec, &known_bins, &self.reporter))
+            {
+                tracing::info!("Using installed binary at: {}", path.display());
+                self.run_post_acquire_hooks(spec_name.unwrap_or_default(), None, &path, "system")?;
+                return Ok(Acquired {
+                    path,
+                    source: "system",
+                    resolved: None,
+                });
+            }
+        }
+
+        let mut budget = Budget::new(
+            &self.config.timeouts,
+            self.run_started.unwrap_or_else(Instant::now),
+        );
+        let resolved_crate = if let Some(resolved_crate) = planned {
+            tracing::info!("Using the crate resolved by the acquisition plan");
+            resolved_crate
+        } else {
+            tracing::info!("Resolving crate...");
+            budget.run(Phase::Resolve, || {
+                tracing::info_span!("resolve", cache = Empty).in_scope(|| self.resolve(crate_specs))
+            })?
+        };
 
         tracing::info!(
             "Resolved crate {}@{}",
             resolved_crate.name,
             resolved_crate.version
         );
+        let (name, version) = (resolved_crate.name.clone(), resolved_crate.version.to_string());
+        span.record("krate", name.as_str());
+        span.record("version", version.as_str());
 
-        let downloaded_crate = self.downloader.download(resolved_crate)?;
+        // Only ever a suggestion, so a history that can't be written doesn't stop the run
+        match usage::record(&self.config, &resolved_crate) {
+            Ok(Some(suggestion)) => self
+                .reporter
+                .report(|| messages::CrateResolutionMessage::pin_suggested(&suggestion)),
+            Ok(None) => {}
+            Err(e) => tracing::debug!("Failed to record usage of {}: {}", name, e),
+        }
+
+        cancel::check()?;
+        let downloaded_crate = budget.run(Phase::Download, || {
+            tracing::info_span!("download", krate = %name, version = %version, cache = Empty)
+                .in_scope(|| self.downloader.download(resolved_crate))
+        })?;
 
         tracing::debug!("Downloaded crate to cache: {:#?}", downloaded_crate);
 
         // Try to resolve a pre-built binary, now with access to the downloaded source
         tracing::debug!("Attempting to resolve pre-built binary");
-        if let Some(resolved_binary) = self.bin_resolver.resolve(&downloaded_crate, build_options)? {
+        cancel::check()?;
+        let prebuilt_span = tracing::info_span!("prebuilt", krate = %name, version = %version, cache = Empty, provider = Empty);
+        let resolved_binary = if self.config.require_sbom {
+            tracing::info!("An SBOM is required, so not looking for a pre-built binary");
+            self.reporter
+                .report(|| messages::SbomMessage::source_build_required(&name, &version));
+            None
+        } else {
+            budget.run(Phase::Download, || {
+                prebuilt_span.in_scope(|| self.bin_resolver.resolve(&downloaded_crate, build_options))
+            })?
+        };
+        let (bin_path, bin_source) = if let Some(resolved_binary) = resolved_binary {
+            let provider = <&'static str>::from(resolved_binary.provider);
+            prebuilt_span.record("provider", provider);
             tracing::info!(
                 "Found pre-built binary from {:?} at: {}",
                 resolved_binary.provider,
                 resolved_binary.path.display()
             );
-            return Ok(resolved_binary.path);
+            (resolved_binary.path, provider)
+        } else {
+            // No pre-built binary available, fall back to building from source
+            tracing::info!(
+                "Pre-built binary not found, excluded by config, or or disabled; building crate from source..."
+            );
+
+            cancel::check()?;
+            let bin_path = budget.run(Phase::Build, || {
+                tracing::info_span!("build", krate = %name, version = %version, cache = Empty)
+                    .in_scope(|| self.builder.build(&downloaded_crate, build_options))
+            })?;
+
+            tracing::info!("Built crate binary at: {}", bin_path.display());
+            if self.config.require_sbom {
+                let is_local = matches!(downloaded_crate.resolved.source, ResolvedSource::LocalDir { .. });
+                self.verify_sbom(&name, &version, &bin_path, is_local)?;
+            }
+            (bin_path, "source")
+        };
+
+        if let Some(assets) = self.config.tool_assets(&downloaded_crate.resolved.name) {
+            let assets_dir = assets::stage(
+                &downloaded_crate.resolved.name,
+                &downloaded_crate.crate_path,
+                &bin_path,
+                assets,
+                matches!(downloaded_crate.resolved.source, ResolvedSource::LocalDir { .. })
+                    || self.config.refresh.source,
+            )?;
+            tracing::info!("Staged assets at: {}", assets_dir.display());
         }
 
-        // No pre-built binary available, fall back to building from source
-        tracing::info!(
-            "Pre-built binary not found, excluded by config, or or disabled; building crate from source..."
-        );
+        self.run_post_acquire_hooks(&name, Some(version.as_str()), &bin_path, bin_source)?;
 
-        let bin_path = self.builder.build(&downloaded_crate, build_options)?;
+        // Only recorded once there's a binary to run, so that a version that failed to build or
+        // download is shown the notes for again next time.  It's only ever shown, so a record that
+        // can't be written doesn't stop the run either
+        match release_notes::record(
+            &self.config,
+            &downloaded_crate.resolved,
+            &downloaded_crate.crate_path,
+        ) {
+            Ok(Some(notes)) => self
+                .reporter
+                .report(|| messages::CrateResolutionMessage::release_notes(&notes)),
+            Ok(None) => {}
+            Err(e) => tracing::debug!("Failed to record the version of {} run: {}", name, e),
+        }
 
-        tracing::info!("Built crate binary at: {}", bin_path.display());
+        Ok(Acquired {
+            path: bin_path,
+            source: bin_source,
+            resolved: Some(downloaded_crate.resolved),
+        })
+    }
 
-        Ok(bin_path)
+    /// Run the `post_acquire` hooks for the binary of the crate `name` at `bin_path`, which came
+    /// from `source` (see [`hooks`]).
+    fn run_post_acquire_hooks(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        bin_path: &std::path::Path,
+        source: &str,
+    ) -> Result<()> {
+        let mut env = vec![
+            ("CGX_CRATE", OsStr::new(name)),
+            ("CGX_BINARY", bin_path.as_os_str()),
+            ("CGX_BINARY_SOURCE", OsStr::new(source)),
+        ];
+        if let Some(version) = version {
+            env.push(("CGX_CRATE_VERSION", OsStr::new(version)));
+        }
+        hooks::run(&self.config.hooks, Hook::PostAcquire, &env, &self.reporter)
     }
 
     /// List the available targets (binaries and examples) in a crate.
     ///
+    /// `crate_specs` are tried in order the same way as in [`Self::crate_to_bin`].
+    ///
     /// Returns a tuple of:
     /// - `String`: The crate name
     /// - `Option<Target>`: The default target if one is specified
@@ -153,7 +561,7 @@ impl Cgx {
     #[allow(clippy::type_complexity)]
     pub fn list_targets(
         &self,
-        crate_spec: &CrateSpec,
+        crate_specs: &[CrateSpec],
         build_options: &BuildOptions,
     ) -> Result<(
         String,
@@ -161,10 +569,366 @@ impl Cgx {
         Vec<cargo_metadata::Target>,
         Vec<cargo_metadata::Target>,
     )> {
-        let resolved_crate = self.resolver.resolve(crate_spec)?;
+        let resolved_crate = self.resolve(crate_specs)?;
         let crate_name = resolved_crate.name.clone();
         let downloaded_crate = self.downloader.download(resolved_crate)?;
         let (default, bins, examples) = self.builder.list_targets(&downloaded_crate, build_options)?;
         Ok((crate_name, default, bins, examples))
     }
+
+    /// Resolve and download a crate without building it, and return the path of its source.
+    ///
+    /// `crate_specs` are tried in order the same way as in [`Self::crate_to_bin`], but cargo is
+    /// never run.  The path returned is in cgx's source cache (or is the crate's own directory,
+    /// for a local crate), so it shouldn't be modified.  If `out_dir` is given, the source is
+    /// copied into a `<name>-<version>` directory in it, replacing any earlier copy, and the path
+    /// of the copy is returned instead.
+    pub fn crate_source(
+        &self,
+        crate_specs: &[CrateSpec],
+        out_dir: Option<&std::path::Path>,
+    ) -> Result<std::path::PathBuf> {
+        let resolved_crate = self.resolve(crate_specs)?;
+        let copy_name = format!("{}-{}", resolved_crate.name, resolved_crate.version);
+        let downloaded_crate = self.downloader.download(resolved_crate)?;
+
+        let Some(out_dir) = out_dir else {
+            return Ok(downloaded_crate.crate_path);
+        };
+
+        let dst = out_dir.join(copy_name);
+        if dst.exists() {
+            std::fs::remove_dir_all(helpers::long_path(&dst))
+                .with_context(|_| error::IoSnafu { path: dst.clone() })?;
+        }
+        helpers::copy_source_tree(&downloaded_crate.crate_path, &dst)?;
+
+        Ok(dst)
+    }
+
+    /// Copy the binary at `bin_path`, as returned by [`Self::crate_to_bin`], into `out_dir`,
+    /// creating it if need be, and return the path of the copy.
+    ///
+    /// The copy keeps the binary's file name, and replaces any file of that name already there.
+    pub fn export_binary(
+        bin_path: &std::path::Path,
+        out_dir: &std::path::Path,
+    ) -> Result<std::path::PathBuf> {
+        let file_name = bin_path.file_name().ok_or_else(|| error::Error::Io {
+            path: bin_path.to_path_buf(),
+            source: std::io::Error::new(std::io::ErrorKind::InvalidInput, "binary path has no filename"),
+        })?;
+        let dst = out_dir.join(file_name);
+        helpers::install_executable(bin_path, &dst)?;
+
+        Ok(dst)
+    }
+
+    /// Get the binary of a tool the same way as [`Self::crate_to_bin`], and freeze it to exactly
+    /// what it resolved to, for a [`freeze::FrozenManifest`].
+    ///
+    /// An installed binary (see [`system_binary`]) is never used, since what it was built from
+    /// isn't known.
+    pub fn freeze_tool(
+        &self,
+        crate_specs: &[CrateSpec],
+        build_options: &BuildOptions,
+    ) -> Result<freeze::FrozenTool> {
+        let acquired = self.acquire(crate_specs, build_options, None, false)?;
+        let resolved = acquired
+            .resolved
+            .expect("BUG: a binary acquired without installed binaries is always resolved");
+        freeze::FrozenTool::new(&resolved, &acquired.path)
+    }
+
+    /// Get the binary of a tool the same way as [`Self::crate_to_bin`], and install it into
+    /// [`Config::install_dir`] for good (see [`installer`]).
+    ///
+    /// The version recorded is the one the binary was got for.  An installed binary (see
+    /// [`system_binary`]) is never used, since there'd be no point in installing it again.
+    pub fn install(
+        &self,
+        crate_specs: &[CrateSpec],
+        build_options: &BuildOptions,
+    ) -> Result<(String, installer::InstalledTool)> {
+        let acquired = self.acquire(crate_specs, build_options, None, false)?;
+        let resolved = acquired
+            .resolved
+            .expect("BUG: a binary acquired without installed binaries is always resolved");
+        let tool = installer::install(
+            &self.config,
+            &resolved,
+            crate_specs,
+            build_options,
+            &acquired.path,
+        )?;
+        Ok((resolved.name, tool))
+    }
+
+    /// Get the binary of a specific earlier build, identified by its build key.
+    ///
+    /// This skips resolving, downloading and building entirely, and returns exactly the binary
+    /// that was cached under `build_key`, which is useful for comparing the behavior of
+    /// different builds of the same tool.  If `crate_spec` is given, the build must be of that
+    /// crate.
+    pub fn pinned_build(
+        &self,
+        build_key: &str,
+        crate_spec: Option<&CrateSpec>,
+    ) -> Result<std::path::PathBuf> {
+        let build = self
+            .cache
+            .find_build(build_key)?
+            .context(error::PinnedBuildNotFoundSnafu { build_key })?;
+
+        if let Some(expected) = crate_spec.map(crate_spec_name) {
+            if !expected.is_empty() && expected != build.name {
+                return error::PinnedBuildCrateMismatchSnafu {
+                    build_key,
+                    expected,
+                    actual: build.name,
+                }
+                .fail();
+            }
+        }
+
+        tracing::info!(
+            "Using pinned build {} of {}@{} at: {}",
+            build.build_key,
+            build.name,
+            build.version,
+            build.binary_path.display()
+        );
+        self.reporter
+            .report(|| messages::BuildCacheMessage::pinned_build(&build));
+
+        if self.config.require_sbom {
+            self.verify_sbom(&build.name, &build.version, &build.binary_path, false)?;
+        }
+
+        Ok(build.binary_path)
+    }
+
+    /// Check that the binary at `bin_path`, built from source, has the SBOM `require_sbom` calls
+    /// for.
+    ///
+    /// A crate from a local directory is built in place and never cached, so while its SBOM is
+    /// generated by the build, it isn't kept anywhere to check.
+    fn verify_sbom(
+        &self,
+        name: &str,
+        version: &str,
+        bin_path: &std::path::Path,
+        is_local: bool,
+    ) -> Result<()> {
+        if is_local {
+            self.reporter
+                .report(|| messages::SbomMessage::verified(name, version, None));
+            return Ok(());
+        }
+
+        let sbom_path = bin_path.with_file_name(cache::SBOM_FILE_NAME);
+        if !sbom_path.is_file() {
+            return error::SbomUnavailableSnafu {
+                name,
+                version,
+                sbom_path,
+            }
+            .fail();
+        }
+
+        self.reporter
+            .report(|| messages::SbomMessage::verified(name, version, Some(&sbom_path)));
+        Ok(())
+    }
+
+    /// The names of the binary targets of the crate `name` that are known without its source: the
+    /// one `build_options` asks for, or else those of the builds of it in the build cache.
+    fn known_bins(&self, name: &str, build_options: &BuildOptions) -> Vec<String> {
+        if let builder::BuildTarget::Bin(bin) = &build_options.build_target {
+            return vec![bin.clone()];
+        }
+
+        let builds = self.cache.list_builds().unwrap_or_else(|e| {
+            tracing::debug!("Failed to list cached builds: {}", e);
+            Vec::new()
+        });
+        builds
+            .iter()
+            .filter(|build| build.name == name)
+            .filter_map(|build| build.binary_path.file_name()?.to_str())
+            .map(|file_name| {
+                file_name
+                    .strip_suffix(std::env::consts::EXE_SUFFIX)
+                    .unwrap_or(file_name)
+                    .to_string()
+            })
+            .collect()
+    }
+
+    /// List the binaries that have been built from source and cached, with the build keys that
+    /// [`Self::pinned_build`] takes.
+    pub fn list_builds(&self) -> Result<Vec<builder::CachedBuild>> {
+        self.cache.list_builds()
+    }
+
+    /// Whether the crate can be found in any of `crate_specs`, tried the same way as in
+    /// [`Self::crate_to_bin`], without downloading or building it.
+    ///
+    /// Only a crate that definitely isn't in any of the sources is `false`; any other failure,
+    /// such as a network error, is returned as is.
+    pub fn crate_exists(&self, crate_specs: &[CrateSpec]) -> Result<bool> {
+        match self.resolve(crate_specs) {
+            Ok(_) => Ok(true),
+            Err(e) if is_not_found(&e) || matches!(e, error::Error::CrateNotFoundInAnySource { .. }) => {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Resolve the first of `crate_specs` that the crate can be found in, or its successor if it
+    /// has been renamed or replaced and `follow_successors` is on (see [`successors`]).
+    fn resolve(&self, crate_specs: &[CrateSpec]) -> Result<ResolvedCrate> {
+        let crate_specs = crate_specs
+            .iter()
+            .map(|crate_spec| match successors::follow(&self.config, crate_spec) {
+                Some(successor_spec) => {
+                    let (name, successor) = (crate_spec_name(crate_spec), crate_spec_name(&successor_spec));
+                    tracing::info!("{name} has been renamed or replaced, so running {successor} instead");
+                    self.reporter
+                        .report(|| messages::CrateResolutionMessage::successor_followed(&name, &successor));
+                    successor_spec
+                }
+                None => crate_spec.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let resolved = self.resolve_candidates(&crate_specs)?;
+        if resolved.source == ResolvedSource::CratesIo {
+            if let Some(successor) = successors::successor(&self.config, &resolved.name) {
+                self.reporter
+                    .report(|| messages::CrateResolutionMessage::superseded(&resolved.name, successor));
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// Resolve the first of `crate_specs` that the crate can be found in.
+    ///
+    /// Moving on to the next spec only happens when the crate definitely isn't in a source;
+    /// any other failure, such as a network error, is returned as is so that it isn't masked by
+    /// a same-named crate from a source further down the list.
+    fn resolve_candidates(&self, crate_specs: &[CrateSpec]) -> Result<ResolvedCrate> {
+        let Some((first, fallbacks)) = crate_specs.split_first() else {
+            return error::MissingCrateParameterSnafu.fail();
+        };
+
+        if fallbacks.is_empty() {
+            return self.resolver.resolve(first);
+        }
+
+        let mut not_found_in = Vec::new();
+        for crate_spec in crate_specs {
+            cancel::check()?;
+            match self.resolver.resolve(crate_spec) {
+                Ok(resolved) => {
+                    if !not_found_in.is_empty() {
+                        tracing::warn!(
+                            "Crate {} not found in {}; using {}@{} from {}",
+                            resolved.name,
+                            not_found_in.join(", "),
+                            resolved.name,
+                            resolved.version,
+                            describe_source(crate_spec)
+                        );
+                        self.reporter.report(|| {
+                            messages::CrateResolutionMessage::resolved_from_fallback_source(
+                                crate_spec, &resolved,
+                            )
+                        });
+                    }
+                    return Ok(resolved);
+                }
+                Err(e) if is_not_found(&e) => {
+                    tracing::debug!("Crate not found in {}: {e}", describe_source(crate_spec));
+                    self.reporter
+                        .report(|| messages::CrateResolutionMessage::not_found_in_source(crate_spec));
+                    not_found_in.push(describe_source(crate_spec));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        error::CrateNotFoundInAnySourceSnafu {
+            name: crate_spec_name(first),
+            sources: not_found_in,
+        }
+        .fail()
+    }
+}
+
+/// Whether `e` means the crate isn't in the source that was tried, as opposed to the source
+/// being unreachable.
+///
+/// A forge repository that doesn't exist surfaces as a git error, so a git failure counts as not
+/// found when the repository or the ref that was asked for is missing, but not when the host
+/// couldn't be reached.
+fn is_not_found(e: &error::Error) -> bool {
+    match e {
+        error::Error::Git { source } => git::is_missing_repo_or_ref(source.as_ref()),
+        _ => matches!(
+            e,
+            error::Error::CrateNotFoundInRegistry { .. }
+                | error::Error::NoMatchingVersion { .. }
+                | error::Error::CrateSuperseded { .. }
+                | error::Error::PackageNotFoundInWorkspace { .. }
+                | error::Error::VersionMismatch { .. }
+        ),
+    }
+}
+
+/// A short human-readable description of where `crate_spec` looks for the crate.
+fn describe_source(crate_spec: &CrateSpec) -> String {
+    match crate_spec {
+        CrateSpec::CratesIo { .. } => "crates.io".to_string(),
+        CrateSpec::Registry {
+            source: RegistrySource::Named(name),
+            ..
+        } => format!("registry '{name}'"),
+        CrateSpec::Registry {
+            source: RegistrySource::IndexUrl(url),
+            ..
+        } => format!("registry {url}"),
+        CrateSpec::Forge { forge, .. } => forge.repo_url(),
+        CrateSpec::Git { repo, .. } => repo.clone(),
+        CrateSpec::LocalDir { path, .. } => path.display().to_string(),
+        CrateSpec::Custom { url, .. } => url.to_string(),
+    }
+}
+
+/// The crate name of `crate_spec`, for error messages.
+fn crate_spec_name(crate_spec: &CrateSpec) -> String {
+    crate_spec.name().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    /// Every optional feature in `Cargo.toml` must be listed in [`super::ENABLED_FEATURES`], or
+    /// `--version` won't say whether it was enabled.
+    #[test]
+    fn enabled_features_lists_every_feature() {
+        let manifest: toml::Table = toml::from_str(include_str!("../Cargo.toml")).unwrap();
+        let features = manifest["features"].as_table().unwrap();
+        let source = include_str!("lib.rs");
+
+        for feature in features.keys().filter(|feature| *feature != "default") {
+            let entry = format!("#[cfg(feature = \"{feature}\")]\n    \"{feature}\",");
+            assert!(
+                source.contains(&entry),
+                "feature `{feature}` is missing from ENABLED_FEATURES"
+            );
+        }
+    }
 }
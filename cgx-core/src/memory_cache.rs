@@ -0,0 +1,112 @@
+//! A small in-memory LRU cache, which [`crate::cache::Cache`] keeps in front of the cache files
+//! it reads most often.
+//!
+//! A single run of cgx reads each cache file only once or twice, but an embedding that keeps a
+//! [`crate::Cgx`] around resolves the same specs over and over, and without this each of those
+//! would read and parse the same JSON file from disk again.
+
+use std::{collections::HashMap, hash::Hash, sync::Mutex};
+
+/// A cache of at most `capacity` values, which evicts the least recently used value to make
+/// room for a new one.
+///
+/// It's safe to share between threads; every operation takes a lock for as long as it takes to
+/// look up or store a value.
+#[derive(Debug)]
+pub(crate) struct MemoryCache<K, V> {
+    capacity: usize,
+    entries: Mutex<Entries<K, V>>,
+}
+
+#[derive(Debug)]
+struct Entries<K, V> {
+    /// Each value, along with the tick of the clock at which it was last used.
+    values: HashMap<K, (V, u64)>,
+
+    /// Advanced on every use of a value, so that the least recently used one has the lowest tick.
+    clock: u64,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> MemoryCache<K, V> {
+    /// Create an empty cache that holds at most `capacity` values; one that holds none never
+    /// stores anything.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries {
+                values: HashMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// A copy of the value for `key`, if there is one, marking it as the most recently used.
+    pub(crate) fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.clock += 1;
+        let clock = entries.clock;
+
+        let (value, last_used) = entries.values.get_mut(key)?;
+        *last_used = clock;
+        Some(value.clone())
+    }
+
+    /// Store `value` for `key`, replacing any value already there, and evict the least recently
+    /// used value if that makes too many.
+    pub(crate) fn insert(&self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.clock += 1;
+        let clock = entries.clock;
+        entries.values.insert(key, (value, clock));
+
+        if entries.values.len() > self.capacity {
+            // Eviction is a linear scan, which at the capacities used is cheaper than keeping the
+            // values in order of use
+            let oldest = entries
+                .values
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.values.remove(&oldest);
+            }
+        }
+    }
+
+    /// Forget the value for `key`, if there is one.
+    pub(crate) fn remove(&self, key: &K) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.values.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = MemoryCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+
+        // Using "a" leaves "b" as the least recently used
+        assert_eq!(cache.get(&"a"), Some(1));
+        cache.insert("c", 3);
+
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"c"), Some(3));
+
+        cache.remove(&"a");
+        assert_eq!(cache.get(&"a"), None);
+
+        let disabled = MemoryCache::new(0);
+        disabled.insert("a", 1);
+        assert_eq!(disabled.get(&"a"), None);
+    }
+}
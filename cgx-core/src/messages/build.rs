@@ -1,12 +1,28 @@
 use super::Message;
 use crate::builder::BuildOptions;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 /// Messages related to build operations.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "event", rename_all = "snake_case")]
 pub enum BuildMessage {
+    /// `cargo fetch` is about to download the crate's dependencies ahead of the build.
+    FetchStarted {
+        offline: bool,
+    },
+    /// `cargo fetch` failed, and will be retried after `delay`.
+    FetchRetrying {
+        attempt: usize,
+        delay: Duration,
+    },
+    FetchCompleted,
+    /// The build is to compile up to `crates` crates: the crate itself and every package in its
+    /// dependency graph, some of which, such as those only needed on other platforms, may not
+    /// actually be compiled.
+    Planned {
+        crates: usize,
+    },
     Started {
         options: BuildOptions,
     },
@@ -26,6 +42,22 @@ pub enum BuildMessage {
 }
 
 impl BuildMessage {
+    pub fn fetch_started(offline: bool) -> Self {
+        Self::FetchStarted { offline }
+    }
+
+    pub fn fetch_retrying(attempt: usize, delay: Duration) -> Self {
+        Self::FetchRetrying { attempt, delay }
+    }
+
+    pub fn fetch_completed() -> Self {
+        Self::FetchCompleted
+    }
+
+    pub fn planned(crates: usize) -> Self {
+        Self::Planned { crates }
+    }
+
     pub fn started(options: &BuildOptions) -> Self {
         Self::Started {
             options: options.clone(),
@@ -1,5 +1,8 @@
 use super::Message;
-use crate::{builder::BuildOptions, crate_resolver::ResolvedCrate};
+use crate::{
+    builder::{BuildOptions, BuildStats, CachedBuild},
+    crate_resolver::ResolvedCrate,
+};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -13,6 +16,7 @@ pub enum BuildCacheMessage {
         options: BuildOptions,
     },
     CacheHit {
+        build_key: String,
         binary_path: PathBuf,
         sbom_path: PathBuf,
     },
@@ -21,10 +25,23 @@ pub enum BuildCacheMessage {
         version: String,
     },
     CacheStored {
+        build_key: String,
         binary_path: PathBuf,
         sbom_path: PathBuf,
     },
     SkippingCacheLocalDir,
+    PinnedBuild {
+        name: String,
+        version: String,
+        build_key: String,
+        binary_path: PathBuf,
+    },
+    PreviousBuild {
+        name: String,
+        version: String,
+        stats: BuildStats,
+        prebuilt_binaries_disabled: bool,
+    },
 }
 
 impl BuildCacheMessage {
@@ -36,8 +53,9 @@ impl BuildCacheMessage {
         }
     }
 
-    pub fn cache_hit(binary_path: &std::path::Path, sbom_path: &std::path::Path) -> Self {
+    pub fn cache_hit(build_key: &str, binary_path: &std::path::Path, sbom_path: &std::path::Path) -> Self {
         Self::CacheHit {
+            build_key: build_key.to_string(),
             binary_path: binary_path.to_path_buf(),
             sbom_path: sbom_path.to_path_buf(),
         }
@@ -50,8 +68,9 @@ impl BuildCacheMessage {
         }
     }
 
-    pub fn cache_stored(binary_path: &std::path::Path, sbom_path: &std::path::Path) -> Self {
+    pub fn cache_stored(build_key: &str, binary_path: &std::path::Path, sbom_path: &std::path::Path) -> Self {
         Self::CacheStored {
+            build_key: build_key.to_string(),
             binary_path: binary_path.to_path_buf(),
             sbom_path: sbom_path.to_path_buf(),
         }
@@ -60,6 +79,29 @@ impl BuildCacheMessage {
     pub fn skipping_cache_local_dir() -> Self {
         Self::SkippingCacheLocalDir
     }
+
+    pub fn pinned_build(build: &CachedBuild) -> Self {
+        Self::PinnedBuild {
+            name: build.name.clone(),
+            version: build.version.clone(),
+            build_key: build.build_key.clone(),
+            binary_path: build.binary_path.clone(),
+        }
+    }
+
+    pub fn previous_build(
+        name: &str,
+        version: &str,
+        stats: &BuildStats,
+        prebuilt_binaries_disabled: bool,
+    ) -> Self {
+        Self::PreviousBuild {
+            name: name.to_string(),
+            version: version.to_string(),
+            stats: stats.clone(),
+            prebuilt_binaries_disabled,
+        }
+    }
 }
 
 impl From<BuildCacheMessage> for Message {
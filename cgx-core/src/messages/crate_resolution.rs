@@ -1,5 +1,12 @@
 use super::Message;
-use crate::{crate_resolver::ResolvedCrate, cratespec::CrateSpec};
+use crate::{
+    crate_resolver::ResolvedCrate,
+    cratespec::{ConfigDecision, CrateSpec},
+    plan::AcquisitionPlan,
+    release_notes::ReleaseNotes,
+    usage::PinSuggestion,
+};
+use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::{path::PathBuf, time::Duration};
 
@@ -15,9 +22,24 @@ pub enum CrateResolutionMessage {
         age_secs: u64,
         ttl_remaining_secs: u64,
     },
+    /// A cached resolution is being used rather than resolving the spec again, without a revision
+    /// of the index to tell whether it's still current, so a release made since it was cached
+    /// won't be seen until it expires or `--refresh` is used
+    UsingCachedResolution {
+        spec: CrateSpec,
+        resolved: ResolvedCrate,
+        age_secs: u64,
+    },
     CacheMiss {
         spec: CrateSpec,
     },
+    /// A cached resolution hasn't expired, but a newer version that satisfies the spec has been
+    /// seen locally since, so the spec is resolved again
+    CacheOutdated {
+        spec: CrateSpec,
+        cached_version: Version,
+        newer_version: Version,
+    },
     CacheStale {
         spec: CrateSpec,
         age_secs: u64,
@@ -35,6 +57,65 @@ pub enum CrateResolutionMessage {
         spec: CrateSpec,
         age_secs: u64,
     },
+    /// The spec couldn't be resolved and had no cached resolution to fall back on, so it's taken
+    /// to be the newest version that satisfies it of which a binary is cached
+    UsingCachedBinaryVersion {
+        spec: CrateSpec,
+        resolved: ResolvedCrate,
+    },
+    NotFoundInSource {
+        spec: CrateSpec,
+    },
+    ResolvedFromFallbackSource {
+        spec: CrateSpec,
+        resolved: ResolvedCrate,
+    },
+    /// A crate that isn't in `[tools]` has been run often enough that pinning it is suggested,
+    /// to the version that's been run most (see [`crate::usage`])
+    PinSuggested {
+        name: String,
+        version: Version,
+        runs: u32,
+    },
+    /// A crate resolved to a newer version than was last run here, and its changelog has entries
+    /// for the versions in between (see [`crate::release_notes`])
+    ReleaseNotes {
+        name: String,
+        previous_version: Version,
+        version: Version,
+        changelog: PathBuf,
+        notes: String,
+        truncated: bool,
+    },
+    /// The crate has been renamed or replaced by another (see [`crate::successors`])
+    Superseded {
+        name: String,
+        successor: String,
+    },
+    /// The crate has been renamed or replaced by another, which is resolved in its place since
+    /// `follow_successors` is on (see [`crate::successors`])
+    SuccessorFollowed {
+        name: String,
+        successor: String,
+    },
+    /// A setting in the config, such as an alias or a `[tools]` pin, changed what the crate named
+    /// on the command line resolves to (see [`CrateSpec::config_decisions`])
+    ConfigApplied {
+        /// Dotted key of the setting, like `aliases.rg`
+        key: String,
+        value: String,
+        /// The config file the setting is from
+        config_file: String,
+    },
+    /// An acquisition plan for the invocation was found, so the crate wasn't resolved (see
+    /// [`crate::plan`])
+    PlanFollowed {
+        plan: PathBuf,
+        resolved: ResolvedCrate,
+        binary: PathBuf,
+        /// Whether the binary was still there, unchanged, so that nothing else had to be done
+        binary_intact: bool,
+    },
 }
 
 impl CrateResolutionMessage {
@@ -50,6 +131,22 @@ impl CrateResolutionMessage {
         }
     }
 
+    pub fn using_cached_resolution(spec: &CrateSpec, resolved: &ResolvedCrate, age: Duration) -> Self {
+        Self::UsingCachedResolution {
+            spec: spec.clone(),
+            resolved: resolved.clone(),
+            age_secs: age.as_secs(),
+        }
+    }
+
+    pub fn cache_outdated(spec: &CrateSpec, cached_version: &Version, newer_version: &Version) -> Self {
+        Self::CacheOutdated {
+            spec: spec.clone(),
+            cached_version: cached_version.clone(),
+            newer_version: newer_version.clone(),
+        }
+    }
+
     pub fn cache_miss(spec: &CrateSpec) -> Self {
         Self::CacheMiss { spec: spec.clone() }
     }
@@ -83,6 +180,74 @@ impl CrateResolutionMessage {
             age_secs: age.as_secs(),
         }
     }
+
+    pub fn using_cached_binary_version(spec: &CrateSpec, resolved: &ResolvedCrate) -> Self {
+        Self::UsingCachedBinaryVersion {
+            spec: spec.clone(),
+            resolved: resolved.clone(),
+        }
+    }
+
+    pub fn not_found_in_source(spec: &CrateSpec) -> Self {
+        Self::NotFoundInSource { spec: spec.clone() }
+    }
+
+    pub fn resolved_from_fallback_source(spec: &CrateSpec, resolved: &ResolvedCrate) -> Self {
+        Self::ResolvedFromFallbackSource {
+            spec: spec.clone(),
+            resolved: resolved.clone(),
+        }
+    }
+
+    pub fn pin_suggested(suggestion: &PinSuggestion) -> Self {
+        Self::PinSuggested {
+            name: suggestion.name.clone(),
+            version: suggestion.version.clone(),
+            runs: suggestion.runs,
+        }
+    }
+
+    pub fn release_notes(notes: &ReleaseNotes) -> Self {
+        Self::ReleaseNotes {
+            name: notes.name.clone(),
+            previous_version: notes.previous_version.clone(),
+            version: notes.version.clone(),
+            changelog: notes.changelog.clone(),
+            notes: notes.notes.clone(),
+            truncated: notes.truncated,
+        }
+    }
+
+    pub fn superseded(name: &str, successor: &str) -> Self {
+        Self::Superseded {
+            name: name.to_string(),
+            successor: successor.to_string(),
+        }
+    }
+
+    pub fn successor_followed(name: &str, successor: &str) -> Self {
+        Self::SuccessorFollowed {
+            name: name.to_string(),
+            successor: successor.to_string(),
+        }
+    }
+
+    pub fn config_applied(decision: &ConfigDecision) -> Self {
+        Self::ConfigApplied {
+            key: decision.key.clone(),
+            value: decision.value.clone(),
+            config_file: decision.source.clone(),
+        }
+    }
+
+    pub fn plan_followed(path: &std::path::Path, plan: &AcquisitionPlan, binary_intact: bool) -> Self {
+        Self::PlanFollowed {
+            plan: path.to_path_buf(),
+            resolved: plan.krate.clone(),
+            binary: plan.binary.clone(),
+            binary_intact,
+        }
+    }
 }
 
 impl From<CrateResolutionMessage> for Message {
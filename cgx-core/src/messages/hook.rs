@@ -0,0 +1,41 @@
+use super::Message;
+use crate::hooks::Hook;
+use serde::{Deserialize, Serialize};
+
+/// Messages about the lifecycle hooks configured in `[hooks]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HookMessage {
+    /// A hook command is about to run
+    Running { hook: Hook, command: String },
+    /// A hook command has finished; if it failed, whether that stops the run depends on the
+    /// hooks' `on_failure` policy
+    Finished {
+        hook: Hook,
+        command: String,
+        success: bool,
+    },
+}
+
+impl HookMessage {
+    pub fn running(hook: Hook, command: &str) -> Self {
+        Self::Running {
+            hook,
+            command: command.to_string(),
+        }
+    }
+
+    pub fn finished(hook: Hook, command: &str, success: bool) -> Self {
+        Self::Finished {
+            hook,
+            command: command.to_string(),
+            success,
+        }
+    }
+}
+
+impl From<HookMessage> for Message {
+    fn from(msg: HookMessage) -> Self {
+        Message::Hook(msg)
+    }
+}
@@ -2,9 +2,13 @@ pub mod build;
 pub mod build_cache;
 pub mod crate_resolution;
 pub mod git;
+pub mod hook;
 pub mod prebuilt_binary;
+pub mod run_end;
 pub mod runner;
+pub mod sbom;
 pub mod source;
+pub mod system_binary;
 
 use serde::{Deserialize, Serialize};
 use std::sync::mpsc;
@@ -13,13 +17,29 @@ pub use build::BuildMessage;
 pub use build_cache::BuildCacheMessage;
 pub use crate_resolution::CrateResolutionMessage;
 pub use git::GitMessage;
+pub use hook::HookMessage;
 pub use prebuilt_binary::PrebuiltBinaryMessage;
+pub use run_end::RunEndMessage;
 pub use runner::RunnerMessage;
+pub use sbom::SbomMessage;
 pub use source::SourceMessage;
+pub use system_binary::SystemBinaryMessage;
 
 // Re-export GitSelector since it's used in GitMessage's public API
 pub use crate::git::GitSelector;
 
+// Re-export cargo's message type since it's used in BuildMessage's public API
+pub use cargo_metadata::Message as CargoMessage;
+
+/// Version of the schema of the JSON messages emitted with `--message-format json`.
+///
+/// Bumped whenever a change to [`Message`] or any of the types it contains would break a consumer
+/// of the JSON output.
+pub const MESSAGE_SCHEMA_VERSION: u32 = 1;
+
+/// Every message schema version this build of cgx is able to emit.
+pub const SUPPORTED_MESSAGE_SCHEMA_VERSIONS: &[u32] = &[MESSAGE_SCHEMA_VERSION];
+
 /// Top-level message enum representing all possible diagnostic messages from cgx.
 ///
 /// Each variant corresponds to a specific subsystem and wraps that subsystem's message type.
@@ -34,6 +54,11 @@ pub enum Message {
     Git(GitMessage),
     Build(BuildMessage),
     Runner(RunnerMessage),
+    SystemBinary(SystemBinaryMessage),
+    Sbom(SbomMessage),
+    Hook(HookMessage),
+    /// Always the last message, once the rest have been written out
+    RunEnd(RunEndMessage),
 }
 
 /// A reporter for diagnostic messages.
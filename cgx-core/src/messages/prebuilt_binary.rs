@@ -1,7 +1,11 @@
 use super::Message;
-use crate::{bin_resolver::ResolvedBinary, config::BinaryProvider, crate_resolver::ResolvedCrate};
+use crate::{
+    bin_resolver::{ProviderFailure, ResolvedBinary},
+    config::BinaryProvider,
+    crate_resolver::ResolvedCrate,
+};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 /// Messages related to prebuilt binary resolution and binary resolution cache operations.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,14 +27,55 @@ pub enum PrebuiltBinaryMessage {
         provider: BinaryProvider,
         reason: String,
     },
+    /// A provider failed with an error while looking for a binary
+    ProviderFailed { failure: ProviderFailure },
     /// Downloading a prebuilt binary from a provider
     DownloadingBinary { url: String, provider: BinaryProvider },
+    /// Another whole percent of a prebuilt binary of known size has been downloaded
+    DownloadProgress {
+        url: String,
+        downloaded: u64,
+        total: u64,
+        provider: BinaryProvider,
+    },
+    /// Using a release archive that was downloaded and extracted before, instead of downloading
+    /// it again
+    ReusingArchive {
+        url: String,
+        path: PathBuf,
+        provider: BinaryProvider,
+    },
+    /// Using the assets of a release as listed by an earlier run, instead of listing them again
+    ReusingReleaseListing {
+        repo: String,
+        version: String,
+        provider: BinaryProvider,
+    },
     /// Verifying checksum of downloaded binary
     VerifyingChecksum { expected: String },
     /// Checksum verification successful
     ChecksumVerified,
     /// Successfully resolved a prebuilt binary
     Resolved { binary: ResolvedBinary },
+    /// No binary for the host's native platform was found, so one that runs under emulation is
+    /// used instead
+    EmulatedBinary {
+        krate: ResolvedCrate,
+        platform: String,
+        native_platform: String,
+    },
+    /// What getting a pre-built binary came to, once it has been got
+    Summary {
+        krate: ResolvedCrate,
+        provider: BinaryProvider,
+        /// Bytes downloaded while looking for the binary, from every provider that was asked
+        bytes_downloaded: u64,
+        /// How long looking for the binary took, from the first provider asked
+        elapsed: Duration,
+        /// How long the last build of the crate from source took on this machine, if one was
+        /// recorded, which is roughly the time the binary saved
+        build_time_saved: Option<Duration>,
+    },
     /// Stored resolved binary information in cache
     CacheStored { path: PathBuf },
     /// No prebuilt binary found from any provider
@@ -74,6 +119,12 @@ impl PrebuiltBinaryMessage {
         }
     }
 
+    pub fn provider_failed(failure: &ProviderFailure) -> Self {
+        Self::ProviderFailed {
+            failure: failure.clone(),
+        }
+    }
+
     pub fn downloading_binary(url: impl Into<String>, provider: BinaryProvider) -> Self {
         Self::DownloadingBinary {
             url: url.into(),
@@ -81,6 +132,40 @@ impl PrebuiltBinaryMessage {
         }
     }
 
+    pub fn download_progress(
+        url: impl Into<String>,
+        downloaded: u64,
+        total: u64,
+        provider: BinaryProvider,
+    ) -> Self {
+        Self::DownloadProgress {
+            url: url.into(),
+            downloaded,
+            total,
+            provider,
+        }
+    }
+
+    pub fn reusing_archive(url: impl Into<String>, path: &std::path::Path, provider: BinaryProvider) -> Self {
+        Self::ReusingArchive {
+            url: url.into(),
+            path: path.to_path_buf(),
+            provider,
+        }
+    }
+
+    pub fn reusing_release_listing(
+        repo: impl Into<String>,
+        version: impl Into<String>,
+        provider: BinaryProvider,
+    ) -> Self {
+        Self::ReusingReleaseListing {
+            repo: repo.into(),
+            version: version.into(),
+            provider,
+        }
+    }
+
     pub fn verifying_checksum(expected: impl Into<String>) -> Self {
         Self::VerifyingChecksum {
             expected: expected.into(),
@@ -97,6 +182,30 @@ impl PrebuiltBinaryMessage {
         }
     }
 
+    pub fn emulated_binary(krate: &ResolvedCrate, platform: &str, native_platform: &str) -> Self {
+        Self::EmulatedBinary {
+            krate: krate.clone(),
+            platform: platform.to_string(),
+            native_platform: native_platform.to_string(),
+        }
+    }
+
+    pub fn summary(
+        krate: &ResolvedCrate,
+        provider: BinaryProvider,
+        bytes_downloaded: u64,
+        elapsed: Duration,
+        build_time_saved: Option<Duration>,
+    ) -> Self {
+        Self::Summary {
+            krate: krate.clone(),
+            provider,
+            bytes_downloaded,
+            elapsed,
+            build_time_saved,
+        }
+    }
+
     pub fn cache_stored(path: &std::path::Path) -> Self {
         Self::CacheStored {
             path: path.to_path_buf(),
@@ -0,0 +1,25 @@
+use super::Message;
+use serde::{Deserialize, Serialize};
+
+/// The last message of a run, sent once every other message has been, so that a consumer of the
+/// JSON output can tell that the stream is complete rather than cut short.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunEndMessage {
+    /// Whether cgx did what it was asked to; if not, it's about to exit with an error
+    pub success: bool,
+    /// Whether cgx is about to hand over to the tool, in which case anything after this on stdout
+    /// is the tool's own output
+    pub execs_tool: bool,
+}
+
+impl RunEndMessage {
+    pub fn new(success: bool, execs_tool: bool) -> Self {
+        Self { success, execs_tool }
+    }
+}
+
+impl From<RunEndMessage> for Message {
+    fn from(msg: RunEndMessage) -> Self {
+        Message::RunEnd(msg)
+    }
+}
@@ -1,6 +1,6 @@
 use super::Message;
 use serde::{Deserialize, Serialize};
-use std::{ffi::OsString, path::PathBuf};
+use std::{ffi::OsString, path::PathBuf, time::Duration};
 
 /// Messages related to binary execution.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +11,26 @@ pub enum RunnerMessage {
         args: Vec<String>,
         no_exec: bool,
     },
+    /// The tool is about to be run with a subcommand or flag that its `feature_hints` say needs
+    /// features the binary wasn't built with.
+    MissingFeatures {
+        krate: String,
+        arg: String,
+        features: Vec<String>,
+    },
+    /// The executed binary ran past its `--max-runtime` limit and was killed.
+    RuntimeLimitExceeded {
+        binary_path: PathBuf,
+        #[serde(with = "humantime_serde")]
+        max_runtime: Duration,
+    },
+    /// The executed binary was killed by `signal` while running under a `--max-memory` limit,
+    /// most likely for running out of memory under it.
+    MemoryLimitExceeded {
+        binary_path: PathBuf,
+        max_memory: u64,
+        signal: i32,
+    },
 }
 
 impl RunnerMessage {
@@ -21,6 +41,29 @@ impl RunnerMessage {
             no_exec,
         }
     }
+
+    pub fn missing_features(krate: &str, arg: &str, features: &[String]) -> Self {
+        Self::MissingFeatures {
+            krate: krate.to_string(),
+            arg: arg.to_string(),
+            features: features.to_vec(),
+        }
+    }
+
+    pub fn runtime_limit_exceeded(binary_path: &std::path::Path, max_runtime: Duration) -> Self {
+        Self::RuntimeLimitExceeded {
+            binary_path: binary_path.to_path_buf(),
+            max_runtime,
+        }
+    }
+
+    pub fn memory_limit_exceeded(binary_path: &std::path::Path, max_memory: u64, signal: i32) -> Self {
+        Self::MemoryLimitExceeded {
+            binary_path: binary_path.to_path_buf(),
+            max_memory,
+            signal,
+        }
+    }
 }
 
 impl From<RunnerMessage> for Message {
@@ -0,0 +1,42 @@
+use super::Message;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Messages about the SBOM of the binary being run, when one is required (see `require_sbom`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SbomMessage {
+    /// Pre-built and already installed binaries have no SBOM, so they aren't considered and the
+    /// crate is built from source
+    SourceBuildRequired { name: String, version: String },
+    /// The binary to be run has an SBOM.  A crate built from a local directory isn't cached, so
+    /// its SBOM is generated during the build but not kept, and there is no path
+    Verified {
+        name: String,
+        version: String,
+        sbom_path: Option<PathBuf>,
+    },
+}
+
+impl SbomMessage {
+    pub fn source_build_required(name: &str, version: &str) -> Self {
+        Self::SourceBuildRequired {
+            name: name.to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    pub fn verified(name: &str, version: &str, sbom_path: Option<&Path>) -> Self {
+        Self::Verified {
+            name: name.to_string(),
+            version: version.to_string(),
+            sbom_path: sbom_path.map(Path::to_path_buf),
+        }
+    }
+}
+
+impl From<SbomMessage> for Message {
+    fn from(msg: SbomMessage) -> Self {
+        Message::Sbom(msg)
+    }
+}
@@ -0,0 +1,53 @@
+use super::Message;
+use crate::system_binary::SystemBinarySource;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Messages related to reusing binaries already installed on the system (see
+/// `prefer_system_binaries`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SystemBinaryMessage {
+    /// An installed binary satisfies the version requirement, and is run instead of cgx's own
+    Reused {
+        name: String,
+        path: PathBuf,
+        source: SystemBinarySource,
+        version: String,
+    },
+    /// An installed binary was found but can't be used, because it reports a version that doesn't
+    /// satisfy the requirement, or none at all
+    Rejected {
+        name: String,
+        path: PathBuf,
+        source: SystemBinarySource,
+        version: Option<String>,
+    },
+}
+
+impl SystemBinaryMessage {
+    pub fn reused(name: &str, path: &Path, source: SystemBinarySource, version: &Version) -> Self {
+        Self::Reused {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            source,
+            version: version.to_string(),
+        }
+    }
+
+    pub fn rejected(name: &str, path: &Path, source: SystemBinarySource, version: Option<&Version>) -> Self {
+        Self::Rejected {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            source,
+            version: version.map(ToString::to_string),
+        }
+    }
+}
+
+impl From<SystemBinaryMessage> for Message {
+    fn from(msg: SystemBinaryMessage) -> Self {
+        Message::SystemBinary(msg)
+    }
+}
@@ -0,0 +1,407 @@
+//! Usage metrics counted on this machine, for `collect_metrics`, `--show-metrics` and
+//! `--export-metrics`.
+//!
+//! Collecting them is strictly opt-in: nothing is counted unless `collect_metrics = true` is set
+//! in a trusted config file.  A [`Recorder`] tallies the [`Message`]s of one run: cache hits and
+//! misses at each layer, how each pre-built binary provider fared, and how many builds from source
+//! there were.  When the run is over, its counts are added to the totals in `metrics.json` in
+//! [`Config::cache_dir`].
+//!
+//! Only counts are kept, with no crate names, versions, paths or URLs, and cgx never sends them
+//! anywhere.  `--export-metrics` writes them to a file, along with the cgx version and the
+//! platform, for an organization to gather from its machines through whatever it already uses.
+//!
+//! Two runs that finish at the same moment can each miss the other's counts, so the totals are
+//! close rather than exact.
+
+use crate::{
+    Result,
+    config::Config,
+    error,
+    helpers::long_path,
+    messages::{
+        BuildCacheMessage, BuildMessage, CrateResolutionMessage, Message, PrebuiltBinaryMessage,
+        SourceMessage, SystemBinaryMessage,
+    },
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// Name of the file in [`Config::cache_dir`] that the metrics are kept in.
+const METRICS_FILE_NAME: &str = "metrics.json";
+
+/// Counts of what cgx did on this machine.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metrics {
+    /// When the first run was counted, if any has been.
+    pub since: Option<DateTime<Utc>>,
+
+    /// How many runs were counted.
+    pub runs: u64,
+
+    /// How many of them failed.
+    pub failed_runs: u64,
+
+    /// Lookups of what a crate spec resolves to in the resolution cache.
+    pub resolution_cache: CacheCounts,
+
+    /// Lookups of a crate's source in the source cache.
+    pub source_cache: CacheCounts,
+
+    /// Lookups of a crate's pre-built binary in the cache of those.
+    pub prebuilt_binary_cache: CacheCounts,
+
+    /// Lookups of a build of a crate in the build cache.
+    pub build_cache: CacheCounts,
+
+    /// How each pre-built binary provider fared, by name.
+    pub providers: BTreeMap<String, ProviderCounts>,
+
+    /// How many builds from source were started.
+    pub builds_started: u64,
+
+    /// How many builds from source produced a binary.
+    pub builds_completed: u64,
+
+    /// How many times an installed binary was run instead of cgx's own.
+    pub system_binaries_reused: u64,
+}
+
+/// Hits and misses of one cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheCounts {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheCounts {
+    /// The percentage of lookups that were hits, if there were any lookups.
+    pub fn hit_rate(&self) -> Option<u64> {
+        let lookups = self.hits + self.misses;
+        (lookups > 0).then(|| self.hits * 100 / lookups)
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+    }
+}
+
+/// How one pre-built binary provider fared.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderCounts {
+    /// How many times it was asked for a binary.
+    pub checked: u64,
+
+    /// How many times the binary used came from it.
+    pub found: u64,
+
+    /// How many times it had no binary for the crate.
+    pub no_binary: u64,
+
+    /// How many times it failed with an error.
+    pub failed: u64,
+}
+
+impl ProviderCounts {
+    fn add(&mut self, other: &Self) {
+        self.checked += other.checked;
+        self.found += other.found;
+        self.no_binary += other.no_binary;
+        self.failed += other.failed;
+    }
+}
+
+impl Metrics {
+    /// Count what `msg` says happened.
+    pub fn observe(&mut self, msg: &Message) {
+        match msg {
+            Message::CrateResolution(CrateResolutionMessage::CacheHit { .. }) => {
+                self.resolution_cache.hits += 1;
+            }
+            Message::CrateResolution(CrateResolutionMessage::CacheMiss { .. }) => {
+                self.resolution_cache.misses += 1;
+            }
+            Message::Source(SourceMessage::CacheHit { .. }) => self.source_cache.hits += 1,
+            Message::Source(SourceMessage::CacheMiss { .. }) => self.source_cache.misses += 1,
+            Message::PrebuiltBinary(msg) => match msg {
+                PrebuiltBinaryMessage::CacheHit { .. } => self.prebuilt_binary_cache.hits += 1,
+                PrebuiltBinaryMessage::CacheMiss { .. } => self.prebuilt_binary_cache.misses += 1,
+                PrebuiltBinaryMessage::CheckingProvider { provider, .. } => {
+                    self.provider(provider.to_string()).checked += 1;
+                }
+                PrebuiltBinaryMessage::Summary { provider, .. } => {
+                    self.provider(provider.to_string()).found += 1;
+                }
+                PrebuiltBinaryMessage::ProviderHasNoBinary { provider, .. } => {
+                    self.provider(provider.to_string()).no_binary += 1;
+                }
+                PrebuiltBinaryMessage::ProviderFailed { failure } => {
+                    self.provider(failure.provider.to_string()).failed += 1;
+                }
+                _ => {}
+            },
+            Message::BuildCache(BuildCacheMessage::CacheHit { .. }) => self.build_cache.hits += 1,
+            Message::BuildCache(BuildCacheMessage::CacheMiss { .. }) => self.build_cache.misses += 1,
+            Message::Build(BuildMessage::Started { .. }) => self.builds_started += 1,
+            Message::Build(BuildMessage::Completed { .. }) => self.builds_completed += 1,
+            Message::SystemBinary(SystemBinaryMessage::Reused { .. }) => self.system_binaries_reused += 1,
+            _ => {}
+        }
+    }
+
+    fn provider(&mut self, name: String) -> &mut ProviderCounts {
+        self.providers.entry(name).or_default()
+    }
+
+    /// Add the counts of `other` to these.
+    fn add(&mut self, other: &Self) {
+        self.since = match (self.since, other.since) {
+            (Some(since), Some(other)) => Some(since.min(other)),
+            (since, other) => since.or(other),
+        };
+        self.runs += other.runs;
+        self.failed_runs += other.failed_runs;
+        self.resolution_cache.add(&other.resolution_cache);
+        self.source_cache.add(&other.source_cache);
+        self.prebuilt_binary_cache.add(&other.prebuilt_binary_cache);
+        self.build_cache.add(&other.build_cache);
+        for (name, counts) in &other.providers {
+            self.provider(name.clone()).add(counts);
+        }
+        self.builds_started += other.builds_started;
+        self.builds_completed += other.builds_completed;
+        self.system_binaries_reused += other.system_binaries_reused;
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(since) = self.since else {
+            return writeln!(
+                f,
+                "No metrics have been collected; set collect_metrics = true in the config to collect them"
+            );
+        };
+
+        let cache = |counts: &CacheCounts| {
+            let mut line = format!("{} hits, {} misses", counts.hits, counts.misses);
+            if let Some(rate) = counts.hit_rate() {
+                line.push_str(&format!(" ({}% hit)", rate));
+            }
+            line
+        };
+
+        writeln!(
+            f,
+            "collected since:        {}",
+            since.format("%Y-%m-%d %H:%M:%S UTC")
+        )?;
+        writeln!(
+            f,
+            "runs:                   {} ({} failed)",
+            self.runs, self.failed_runs
+        )?;
+        writeln!(f, "resolution cache:       {}", cache(&self.resolution_cache))?;
+        writeln!(f, "source cache:           {}", cache(&self.source_cache))?;
+        writeln!(
+            f,
+            "pre-built binary cache: {}",
+            cache(&self.prebuilt_binary_cache)
+        )?;
+        writeln!(f, "build cache:            {}", cache(&self.build_cache))?;
+        writeln!(
+            f,
+            "builds from source:     {} ({} failed)",
+            self.builds_started,
+            self.builds_started.saturating_sub(self.builds_completed)
+        )?;
+        writeln!(f, "installed binaries run: {}", self.system_binaries_reused)?;
+
+        if !self.providers.is_empty() {
+            writeln!(f, "providers:")?;
+            for (name, counts) in &self.providers {
+                writeln!(
+                    f,
+                    "  {}: checked {}, found {}, no binary {}, failed {}",
+                    name, counts.checked, counts.found, counts.no_binary, counts.failed
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// What `--export-metrics` writes: the metrics, along with what they came from.
+#[derive(Debug, Serialize)]
+struct Export<'a> {
+    cgx_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    exported_at: DateTime<Utc>,
+    metrics: &'a Metrics,
+}
+
+/// Tallies the messages of one run, to add them to the metrics when it's over.
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    run: Metrics,
+}
+
+impl Recorder {
+    /// A recorder for a run with `config`, or `None` unless [`Config::collect_metrics`] is set.
+    pub fn new(config: &Config) -> Option<Self> {
+        config.collect_metrics.then(|| Self {
+            path: config.cache_dir.join(METRICS_FILE_NAME),
+            run: Metrics::default(),
+        })
+    }
+
+    /// Count what `msg` says happened in the run.
+    pub fn observe(&mut self, msg: &Message) {
+        self.run.observe(msg);
+    }
+
+    /// Add the run, which `succeeded` or not, to the metrics.
+    ///
+    /// A run that didn't report doing anything, like `--list-installed`, isn't counted.
+    pub fn finish(mut self, succeeded: bool) -> Result<()> {
+        if self.run == Metrics::default() {
+            return Ok(());
+        }
+
+        self.run.since = Some(Utc::now());
+        self.run.runs = 1;
+        self.run.failed_runs = u64::from(!succeeded);
+
+        let mut metrics = load_from(&self.path);
+        metrics.add(&self.run);
+        save(&self.path, &metrics)
+    }
+}
+
+/// The metrics collected on this machine.
+pub fn load(config: &Config) -> Metrics {
+    load_from(&config.cache_dir.join(METRICS_FILE_NAME))
+}
+
+/// Write the metrics collected on this machine to `path` as JSON, for `--export-metrics`.
+pub fn export(config: &Config, path: &Path) -> Result<()> {
+    let metrics = load(config);
+    let export = Export {
+        cgx_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        exported_at: Utc::now(),
+        metrics: &metrics,
+    };
+
+    let mut json = serde_json::to_string_pretty(&export).context(error::JsonSnafu)?;
+    json.push('\n');
+    fs::write(long_path(path), json).context(error::IoSnafu { path })
+}
+
+/// Read the metrics from `path`, starting afresh if there are none or they can't be read.
+fn load_from(path: &Path) -> Metrics {
+    let Ok(json) = fs::read_to_string(long_path(path)) else {
+        return Metrics::default();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        tracing::debug!(path = %path.display(), "Ignoring invalid metrics: {}", e);
+        Metrics::default()
+    })
+}
+
+/// Write `metrics` to `path`, replacing it atomically so that a cgx running at the same time never
+/// reads it half written.
+fn save(path: &Path, metrics: &Metrics) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(long_path(parent)).context(error::IoSnafu { path: parent })?;
+    }
+
+    let json = serde_json::to_string_pretty(metrics).context(error::JsonSnafu)?;
+    let temp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+
+    fs::write(long_path(&temp_path), json).context(error::IoSnafu { path: &temp_path })?;
+    fs::rename(long_path(&temp_path), long_path(path)).context(error::RenameFileSnafu {
+        src: &temp_path,
+        dst: path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::BinaryProvider,
+        crate_resolver::{ResolvedCrate, ResolvedSource},
+    };
+    use semver::Version;
+
+    fn krate() -> ResolvedCrate {
+        ResolvedCrate {
+            name: "tool".to_string(),
+            version: Version::parse("1.0.0").unwrap(),
+            source: ResolvedSource::CratesIo,
+        }
+    }
+
+    #[test]
+    fn runs_are_added_to_the_totals() {
+        let (_temp_dir, mut config) = crate::config::create_test_env();
+        assert!(Recorder::new(&config).is_none());
+        config.collect_metrics = true;
+
+        let mut recorder = Recorder::new(&config).unwrap();
+        recorder.observe(&SourceMessage::cache_miss(&krate()).into());
+        recorder.observe(
+            &PrebuiltBinaryMessage::checking_provider(&krate(), BinaryProvider::GithubReleases).into(),
+        );
+        recorder.observe(
+            &PrebuiltBinaryMessage::provider_has_no_binary(BinaryProvider::GithubReleases, "none").into(),
+        );
+        recorder.observe(&BuildMessage::completed(Path::new("/tmp/tool")).into());
+        recorder.finish(true).unwrap();
+
+        let mut recorder = Recorder::new(&config).unwrap();
+        recorder.observe(&SourceMessage::cache_hit(Path::new("/tmp/source")).into());
+        recorder.finish(false).unwrap();
+
+        // Nothing happened in this one, so it isn't counted
+        Recorder::new(&config).unwrap().finish(true).unwrap();
+
+        let metrics = load(&config);
+        assert!(metrics.since.is_some());
+        assert_eq!(metrics.runs, 2);
+        assert_eq!(metrics.failed_runs, 1);
+        assert_eq!(metrics.source_cache, CacheCounts { hits: 1, misses: 1 });
+        assert_eq!(metrics.source_cache.hit_rate(), Some(50));
+        assert_eq!(metrics.builds_completed, 1);
+        assert_eq!(
+            metrics.providers["github-releases"],
+            ProviderCounts {
+                checked: 1,
+                no_binary: 1,
+                ..ProviderCounts::default()
+            }
+        );
+
+        let export_path = config.cache_dir.join("export.json");
+        export(&config, &export_path).unwrap();
+        let exported: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&export_path).unwrap()).unwrap();
+        assert_eq!(exported["metrics"]["runs"], 2);
+        assert_eq!(exported["os"], std::env::consts::OS);
+    }
+}
@@ -0,0 +1,211 @@
+//! Acquisition plans, for `--plan`: what one run resolved a crate to and got, so that later runs
+//! of the same invocation can skip straight to it.
+//!
+//! A CI pipeline runs the same cgx invocation over and over, and each run otherwise asks the
+//! registry or forge what the crate resolves to before it even looks in the cache.  After a
+//! successful run with `--plan`, an [`AcquisitionPlan`] is written to the file given, recording the
+//! invocation it's for, the exact crate it resolved to, and the path and SHA256 of the binary.  A
+//! later run of the same invocation that finds the binary still there with that digest uses it
+//! without resolving, downloading, or looking for a pre-built binary at all.  If the binary is gone
+//! or was changed, the crate the plan resolved to is got again without resolving it, and the plan
+//! is updated with the new binary.
+//!
+//! A run with `--as-of` is a different invocation from one without, or with another date, and its
+//! plan records the date the crate was resolved as of (see [`crate::as_of`]).
+//!
+//! A plan for a different invocation is replaced, and nothing is planned for a crate from a local
+//! directory, whose source can change between runs, or for an installed binary.
+
+use crate::{
+    Result, builder::BuildOptions, cache::Cache, crate_resolver::ResolvedCrate, cratespec::CrateSpec, error,
+    helpers::long_path,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use snafu::ResultExt;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// What one run of cgx resolved a crate to and got.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AcquisitionPlan {
+    /// Identifies the invocation the plan is for (see [`invocation_key`]).
+    pub invocation: String,
+
+    /// The exact crate the invocation resolved to.
+    pub krate: ResolvedCrate,
+
+    /// The date the crate was resolved as of, if it was resolved as of one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<NaiveDate>,
+
+    /// Where the binary that was got is, in cgx's cache.
+    pub binary: PathBuf,
+
+    /// Where the binary came from: the pre-built binary provider, or `source` if it was built.
+    pub binary_source: String,
+
+    /// The SHA256 of the binary.
+    pub sha256: String,
+}
+
+impl AcquisitionPlan {
+    /// Plan the invocation `invocation`, for which `krate` resolved, as of `as_of` if given, to the
+    /// binary at `binary` from `binary_source`.
+    pub fn new(
+        invocation: String,
+        krate: ResolvedCrate,
+        as_of: Option<NaiveDate>,
+        binary: &Path,
+        binary_source: &str,
+    ) -> Result<Self> {
+        Ok(Self {
+            invocation,
+            krate,
+            as_of,
+            binary: binary.to_path_buf(),
+            binary_source: binary_source.to_string(),
+            sha256: sha256_file(binary)?,
+        })
+    }
+
+    /// Read the plan at `path`, or `None` if there's no file there.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let json = match fs::read_to_string(long_path(path)) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context(error::IoSnafu { path }),
+        };
+
+        serde_json::from_str(&json).map(Some).map_err(|e| {
+            error::InvalidAcquisitionPlanSnafu {
+                path,
+                message: e.to_string(),
+            }
+            .build()
+        })
+    }
+
+    /// Write the plan to `path`, replacing it atomically so that a run reading it at the same time
+    /// never sees it half written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(long_path(parent)).context(error::IoSnafu { path: parent })?;
+        }
+
+        let mut json = serde_json::to_string_pretty(self).context(error::JsonSnafu)?;
+        json.push('\n');
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(long_path(&temp_path), json).context(error::IoSnafu { path: &temp_path })?;
+        fs::rename(long_path(&temp_path), long_path(path)).context(error::RenameFileSnafu {
+            src: &temp_path,
+            dst: path,
+        })
+    }
+
+    /// Whether the binary is still where the plan says, with the digest it says.
+    pub fn binary_is_intact(&self) -> bool {
+        sha256_file(&self.binary).is_ok_and(|sha256| sha256 == self.sha256)
+    }
+}
+
+/// A key identifying an invocation of cgx for the crate in `crate_specs`, built with
+/// `build_options` and resolved as of `as_of` if given.
+///
+/// Only the build options that change the binary count, the same ones that are part of the key a
+/// build is cached under, so that a plan still applies with, say, a different number of jobs.
+pub fn invocation_key(
+    crate_specs: &[CrateSpec],
+    build_options: &BuildOptions,
+    as_of: Option<NaiveDate>,
+) -> Result<String> {
+    let specs = serde_json::to_string(crate_specs).context(error::JsonSnafu)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(specs.as_bytes());
+    hasher.update(Cache::compute_build_hash(build_options).as_bytes());
+    if let Some(date) = as_of {
+        hasher.update(format!("as-of {date}").as_bytes());
+    }
+    Ok(crate::helpers::format_hex_lower(hasher.finalize()))
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let data = fs::read(long_path(path)).context(error::IoSnafu { path })?;
+    Ok(crate::helpers::format_hex_lower(Sha256::digest(&data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crate_resolver::ResolvedSource;
+    use assert_matches::assert_matches;
+    use semver::Version;
+
+    #[test]
+    fn plan_round_trips_and_checks_its_binary() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let binary = temp_dir.path().join("tool");
+        fs::write(&binary, b"binary").unwrap();
+        let krate = ResolvedCrate {
+            name: "tool".to_string(),
+            version: Version::parse("1.2.3").unwrap(),
+            source: ResolvedSource::CratesIo,
+        };
+
+        let plan = AcquisitionPlan::new(
+            "key".to_string(),
+            krate,
+            NaiveDate::from_ymd_opt(2024, 6, 1),
+            &binary,
+            "github-releases",
+        )
+        .unwrap();
+        let path = temp_dir.path().join("ci/plan.json");
+        assert_eq!(AcquisitionPlan::load(&path).unwrap(), None);
+        plan.save(&path).unwrap();
+        assert_eq!(AcquisitionPlan::load(&path).unwrap(), Some(plan.clone()));
+        assert!(plan.binary_is_intact());
+
+        fs::write(&binary, b"replaced").unwrap();
+        assert!(!plan.binary_is_intact());
+        fs::remove_file(&binary).unwrap();
+        assert!(!plan.binary_is_intact());
+
+        fs::write(&path, "{\"tools\": {}}").unwrap();
+        assert_matches!(
+            AcquisitionPlan::load(&path),
+            Err(error::Error::InvalidAcquisitionPlan { .. })
+        );
+    }
+
+    #[test]
+    fn invocation_key_ignores_options_that_dont_change_the_binary() {
+        let specs = [CrateSpec::CratesIo {
+            name: "tool".to_string(),
+            version: None,
+        }];
+        let options = BuildOptions::default();
+        let key = invocation_key(&specs, &options, None).unwrap();
+
+        let faster = BuildOptions {
+            jobs: Some(4),
+            ..BuildOptions::default()
+        };
+        assert_eq!(invocation_key(&specs, &faster, None).unwrap(), key);
+
+        let featured = BuildOptions {
+            features: vec!["extra".to_string()],
+            ..BuildOptions::default()
+        };
+        assert_ne!(invocation_key(&specs, &featured, None).unwrap(), key);
+        assert_ne!(
+            invocation_key(&specs, &options, NaiveDate::from_ymd_opt(2024, 6, 1)).unwrap(),
+            key
+        );
+    }
+}
@@ -0,0 +1,518 @@
+//! Detection of the platform cgx is really running on, as opposed to the one it was built for.
+//!
+//! The target triple cgx was compiled for isn't always the best one to get pre-built binaries
+//! for.  An x86_64 build of cgx runs fine on Windows on ARM64 and on Apple Silicon Macs, under
+//! the emulation those systems provide, but the tools it runs would be better off as native
+//! ARM64 binaries.  So the host's native architecture is detected at runtime, binaries for it are
+//! preferred, and binaries for architectures the host can emulate are only used as a fallback.
+//!
+//! Detection avoids any platform APIs that would need `unsafe` code:
+//!
+//! - On Windows, a 32-bit process under WOW64 has the native architecture in the
+//!   `PROCESSOR_ARCHITEW6432` environment variable.  Otherwise the system-wide
+//!   `PROCESSOR_ARCHITECTURE` is read from the registry, since the one in the environment of an
+//!   emulated x86_64 process on ARM64 claims to be `AMD64`.
+//! - On macOS, `sysctl.proc_translated` is 1 in a process running under Rosetta 2.
+//!
+//! If detection fails, the architecture cgx was built for is assumed to be native.
+//!
+//! Building a container image for another platform is the exception: the binaries are for the
+//! image, not the host, so see [`container_target`].
+//!
+//! On Linux, the C library matters as well.  A static musl binary runs anywhere, but a glibc one
+//! needs glibc, and at least the version it was linked against, so the host's glibc version is
+//! detected too (see [`host_glibc_version`]).
+
+use crate::config::LibcPreference;
+use std::{fmt, sync::OnceLock};
+use tracing::*;
+
+/// A version of glibc, such as 2.35.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct GlibcVersion {
+    major: u32,
+    minor: u32,
+}
+
+impl GlibcVersion {
+    /// Parse a version like `2.35`; anything after the minor version, as in `2.35.1`, is ignored.
+    pub(crate) fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.trim().split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        Some(Self { major, minor })
+    }
+}
+
+impl fmt::Display for GlibcVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// The target triples to look for pre-built binaries for on this host when none are configured,
+/// in order of preference.
+///
+/// The first is always the host's native platform.
+pub(crate) fn host_targets() -> Vec<String> {
+    static HOST_TARGETS: OnceLock<Vec<String>> = OnceLock::new();
+
+    HOST_TARGETS
+        .get_or_init(|| {
+            let native_arch = native_arch();
+            debug!(
+                build_target = build_context::TARGET,
+                native_arch = ?native_arch,
+                "Detected host architecture"
+            );
+            targets_for(build_context::TARGET, native_arch)
+        })
+        .clone()
+}
+
+/// The version of glibc on this host, or `None` if it has none or it can't be detected.
+pub(crate) fn host_glibc_version() -> Option<GlibcVersion> {
+    static HOST_GLIBC_VERSION: OnceLock<Option<GlibcVersion>> = OnceLock::new();
+
+    *HOST_GLIBC_VERSION.get_or_init(|| {
+        let version = detect_glibc_version();
+        debug!(glibc = ?version.map(|version| version.to_string()), "Detected host glibc version");
+        version
+    })
+}
+
+/// `targets` with Linux targets for the other C library added, ordered by the `libc` preference.
+///
+/// A musl binary is always a fallback for a glibc one, since it has no dependency on the C library
+/// of the host, but a glibc binary is only one for a musl one if the host `has_glibc`.
+pub(crate) fn with_libc_preference(targets: &[String], libc: LibcPreference, has_glibc: bool) -> Vec<String> {
+    let mut preferred = Vec::new();
+
+    for target in targets {
+        let candidates = match libc_flavors(target) {
+            Some((gnu, musl)) => {
+                let gnu_first = match libc {
+                    LibcPreference::Auto => target == &gnu,
+                    LibcPreference::Gnu => true,
+                    LibcPreference::Musl => false,
+                };
+                let gnu = (has_glibc || target == &gnu).then_some(gnu);
+                if gnu_first {
+                    gnu.into_iter().chain([musl]).collect()
+                } else {
+                    [musl].into_iter().chain(gnu).collect()
+                }
+            }
+            None => vec![target.clone()],
+        };
+
+        for candidate in candidates {
+            if !preferred.contains(&candidate) {
+                preferred.push(candidate);
+            }
+        }
+    }
+
+    preferred
+}
+
+/// Whether this host can run binaries linked against glibc.
+pub(crate) fn has_glibc() -> bool {
+    build_context::TARGET.contains("-linux-gnu") || host_glibc_version().is_some()
+}
+
+/// The glibc and musl versions of a Linux target, or `None` if it isn't a Linux target for one of
+/// them.
+fn libc_flavors(target: &str) -> Option<(String, String)> {
+    if target.contains("-linux-gnu") {
+        Some((
+            target.to_string(),
+            target.replacen("-linux-gnu", "-linux-musl", 1),
+        ))
+    } else if target.contains("-linux-musl") {
+        Some((
+            target.replacen("-linux-musl", "-linux-gnu", 1),
+            target.to_string(),
+        ))
+    } else {
+        None
+    }
+}
+
+/// The target triple of the platform docker buildx is building an image for, when that isn't the
+/// host's native platform.
+///
+/// `platform` is the value of `TARGETPLATFORM` (such as `linux/arm64`), which buildx passes to a
+/// build stage that declares it with `ARG TARGETPLATFORM`.  A build stage that runs on the build
+/// host with `--platform=$BUILDPLATFORM`, to cross-compile rather than run under emulation, gets it
+/// for the image's platform while cgx itself runs natively, so without this the tools cgx put in
+/// the image would be for the wrong architecture.  `TARGETARCH` and `TARGETOS` alone aren't taken
+/// as a sign of that, since they're generic enough names for other builds to set.
+///
+/// This is only asked when cgx is turned on to do it, and the binary isn't going to be run on the
+/// host (see [`crate::config::Config::container_target`]).
+pub(crate) fn container_target(platform: Option<&str>) -> Option<String> {
+    let platform = platform.filter(|platform| !platform.is_empty())?;
+
+    let Some(target) = docker_platform_target(platform, build_context::TARGET) else {
+        warn!(
+            "Ignoring TARGETPLATFORM {}, which isn't a platform cgx knows the target triple for",
+            platform
+        );
+        return None;
+    };
+
+    if host_targets().first() == Some(&target) {
+        return None;
+    }
+
+    debug!(
+        platform,
+        target, "Targeting the platform of the container image being built"
+    );
+    Some(target)
+}
+
+/// The target triple for a docker platform, `os/arch[/variant]`, if it's one cgx knows.
+///
+/// On Linux, the C library is the one `build_target` uses, on the assumption that the tools should
+/// be built the same way cgx was.
+fn docker_platform_target(platform: &str, build_target: &str) -> Option<String> {
+    let mut parts = platform.split('/');
+    let (os, arch, variant) = (parts.next()?, parts.next()?, parts.next());
+
+    match os {
+        "linux" => {
+            let libc = if build_target.contains("-musl") {
+                "musl"
+            } else {
+                "gnu"
+            };
+            let target = match (arch, variant) {
+                ("amd64", _) => format!("x86_64-unknown-linux-{}", libc),
+                ("arm64", _) => format!("aarch64-unknown-linux-{}", libc),
+                ("arm", Some("v6")) => format!("arm-unknown-linux-{}eabihf", libc),
+                ("arm", _) => format!("armv7-unknown-linux-{}eabihf", libc),
+                ("386", _) => format!("i686-unknown-linux-{}", libc),
+                ("riscv64", _) => format!("riscv64gc-unknown-linux-{}", libc),
+                ("ppc64le", _) => format!("powerpc64le-unknown-linux-{}", libc),
+                ("s390x", _) => format!("s390x-unknown-linux-{}", libc),
+                _ => return None,
+            };
+            Some(target)
+        }
+        "windows" => match arch {
+            "amd64" => Some("x86_64-pc-windows-msvc".to_string()),
+            "arm64" => Some("aarch64-pc-windows-msvc".to_string()),
+            _ => None,
+        },
+        "freebsd" => match arch {
+            "amd64" => Some("x86_64-unknown-freebsd".to_string()),
+            "arm64" => Some("aarch64-unknown-freebsd".to_string()),
+            "riscv64" => Some("riscv64gc-unknown-freebsd".to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The target triples a host with the `native_arch` architecture can run binaries for, given that
+/// it runs cgx built for `build_target`, with native binaries first.
+///
+/// `native_arch` is `None` if it isn't known, in which case it's assumed to be the architecture of
+/// `build_target`.
+fn targets_for(build_target: &str, native_arch: Option<&str>) -> Vec<String> {
+    let native_arch = native_arch.unwrap_or_else(|| arch_of(build_target));
+
+    let mut targets = vec![with_arch(build_target, native_arch)];
+    if !targets.iter().any(|target| target == build_target) {
+        targets.push(build_target.to_string());
+    }
+    for arch in emulated_archs(build_target, native_arch) {
+        let target = with_arch(build_target, arch);
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+
+    targets
+}
+
+/// The architectures that a host with the `native_arch` architecture can run binaries for under
+/// emulation, besides whatever cgx itself was built for, in order of preference.
+///
+/// Only emulation that is built into the OS counts.  Rosetta 2 is an optional install on macOS,
+/// so x86_64 binaries are only used on an ARM64 Mac if cgx itself is one and is therefore
+/// evidently running under it.
+fn emulated_archs(build_target: &str, native_arch: &str) -> &'static [&'static str] {
+    if !build_target.contains("-windows-") {
+        return &[];
+    }
+
+    match native_arch {
+        "aarch64" => &["x86_64", "i686"],
+        "x86_64" => &["i686"],
+        _ => &[],
+    }
+}
+
+/// The architecture part of a target triple.
+fn arch_of(target: &str) -> &str {
+    target.split_once('-').map_or(target, |(arch, _)| arch)
+}
+
+/// `target` with its architecture replaced by `arch`.
+fn with_arch(target: &str, arch: &str) -> String {
+    match target.split_once('-') {
+        Some((_, rest)) => format!("{}-{}", arch, rest),
+        None => arch.to_string(),
+    }
+}
+
+/// The native architecture of the host, as it appears in target triples, if it can be detected.
+#[cfg(windows)]
+fn native_arch() -> Option<&'static str> {
+    if let Ok(arch) = std::env::var("PROCESSOR_ARCHITEW6432") {
+        return windows_arch(&arch);
+    }
+
+    let output = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKLM\SYSTEM\CurrentControlSet\Control\Session Manager\Environment",
+            "/v",
+            "PROCESSOR_ARCHITECTURE",
+        ])
+        .output()
+        .inspect_err(|e| debug!("Failed to query the processor architecture: {}", e))
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    // The value is the last field of the line that names it
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.trim_start().starts_with("PROCESSOR_ARCHITECTURE"))
+        .and_then(|line| line.split_whitespace().last())
+        .and_then(windows_arch)
+}
+
+/// The architecture in a target triple for an architecture name as Windows reports it.
+#[cfg(windows)]
+fn windows_arch(arch: &str) -> Option<&'static str> {
+    match arch.trim().to_ascii_uppercase().as_str() {
+        "ARM64" => Some("aarch64"),
+        "AMD64" => Some("x86_64"),
+        "X86" => Some("i686"),
+        _ => None,
+    }
+}
+
+/// The native architecture of the host, as it appears in target triples, if it can be detected.
+#[cfg(target_os = "macos")]
+fn native_arch() -> Option<&'static str> {
+    let output = std::process::Command::new("sysctl")
+        .args(["-n", "sysctl.proc_translated"])
+        .output()
+        .inspect_err(|e| debug!("Failed to check for Rosetta translation: {}", e))
+        .ok()?;
+
+    // On Intel Macs the sysctl doesn't exist at all, so anything but 1 means cgx is native
+    if output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "1" {
+        Some("aarch64")
+    } else {
+        None
+    }
+}
+
+/// The native architecture of the host, as it appears in target triples, if it can be detected.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn native_arch() -> Option<&'static str> {
+    None
+}
+
+/// The version of glibc on the host, as reported by `getconf GNU_LIBC_VERSION` (`glibc 2.35`) or
+/// else the first line of `ldd --version` (`ldd (GNU libc) 2.35`).
+///
+/// Neither exists on a musl system, or reports a glibc version there.
+#[cfg(target_os = "linux")]
+fn detect_glibc_version() -> Option<GlibcVersion> {
+    let run = |program: &str, args: &[&str]| {
+        std::process::Command::new(program)
+            .args(args)
+            .output()
+            .inspect_err(|e| debug!("Failed to run {}: {}", program, e))
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+
+    if let Some(version) = run("getconf", &["GNU_LIBC_VERSION"])
+        .and_then(|output| output.trim().strip_prefix("glibc ").and_then(GlibcVersion::parse))
+    {
+        return Some(version);
+    }
+
+    run("ldd", &["--version"])
+        .filter(|output| output.contains("GNU libc") || output.contains("GLIBC"))
+        .and_then(|output| {
+            output
+                .lines()
+                .next()
+                .and_then(|line| line.split_whitespace().last())
+                .and_then(GlibcVersion::parse)
+        })
+}
+
+/// The version of glibc on the host; only Linux has one.
+#[cfg(not(target_os = "linux"))]
+fn detect_glibc_version() -> Option<GlibcVersion> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn native_build_prefers_its_own_target() {
+        assert_eq!(
+            targets_for("x86_64-unknown-linux-gnu", None),
+            vec!["x86_64-unknown-linux-gnu"]
+        );
+        assert_eq!(
+            targets_for("aarch64-apple-darwin", Some("aarch64")),
+            vec!["aarch64-apple-darwin"]
+        );
+    }
+
+    #[test]
+    fn emulated_windows_build_prefers_arm64() {
+        assert_eq!(
+            targets_for("x86_64-pc-windows-msvc", Some("aarch64")),
+            vec![
+                "aarch64-pc-windows-msvc",
+                "x86_64-pc-windows-msvc",
+                "i686-pc-windows-msvc"
+            ]
+        );
+    }
+
+    #[test]
+    fn native_windows_builds_fall_back_to_emulated_archs() {
+        assert_eq!(
+            targets_for("aarch64-pc-windows-msvc", None),
+            vec![
+                "aarch64-pc-windows-msvc",
+                "x86_64-pc-windows-msvc",
+                "i686-pc-windows-msvc"
+            ]
+        );
+        assert_eq!(
+            targets_for("i686-pc-windows-msvc", Some("x86_64")),
+            vec!["x86_64-pc-windows-msvc", "i686-pc-windows-msvc"]
+        );
+    }
+
+    #[test]
+    fn rosetta_build_prefers_arm64() {
+        assert_eq!(
+            targets_for("x86_64-apple-darwin", Some("aarch64")),
+            vec!["aarch64-apple-darwin", "x86_64-apple-darwin"]
+        );
+    }
+
+    #[test]
+    fn container_target_needs_a_foreign_target_platform() {
+        assert_eq!(container_target(None), None);
+        assert_eq!(container_target(Some("")), None);
+        assert_eq!(container_target(Some("plan9/amd64")), None);
+
+        let host = host_targets().first().cloned();
+        for platform in ["linux/amd64", "linux/arm64"] {
+            let target = docker_platform_target(platform, build_context::TARGET);
+            let expected = target.filter(|target| Some(target) != host.as_ref());
+            assert_eq!(container_target(Some(platform)), expected);
+        }
+    }
+
+    #[test]
+    fn maps_docker_platforms_to_targets() {
+        assert_eq!(
+            docker_platform_target("linux/arm64", "x86_64-unknown-linux-gnu").as_deref(),
+            Some("aarch64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            docker_platform_target("linux/amd64", "aarch64-unknown-linux-musl").as_deref(),
+            Some("x86_64-unknown-linux-musl")
+        );
+        assert_eq!(
+            docker_platform_target("linux/arm/v7", "x86_64-unknown-linux-gnu").as_deref(),
+            Some("armv7-unknown-linux-gnueabihf")
+        );
+        assert_eq!(
+            docker_platform_target("linux/arm/v6", "x86_64-unknown-linux-musl").as_deref(),
+            Some("arm-unknown-linux-musleabihf")
+        );
+        assert_eq!(
+            docker_platform_target("windows/amd64", "x86_64-unknown-linux-gnu").as_deref(),
+            Some("x86_64-pc-windows-msvc")
+        );
+        assert_eq!(
+            docker_platform_target("linux/mips64le", "x86_64-unknown-linux-gnu"),
+            None
+        );
+        assert_eq!(
+            docker_platform_target("freebsd/arm64", "x86_64-unknown-linux-gnu").as_deref(),
+            Some("aarch64-unknown-freebsd")
+        );
+        assert_eq!(docker_platform_target("linux", "x86_64-unknown-linux-gnu"), None);
+    }
+
+    #[test]
+    fn parses_glibc_versions() {
+        let version = GlibcVersion::parse("2.35").unwrap();
+        assert_eq!(version.to_string(), "2.35");
+        assert!(GlibcVersion::parse("2.9").unwrap() < GlibcVersion::parse("2.17.1").unwrap());
+        assert_eq!(GlibcVersion::parse("PRIVATE"), None);
+        assert_eq!(GlibcVersion::parse("2"), None);
+    }
+
+    #[test]
+    fn libc_preference_orders_linux_targets() {
+        let targets = |targets: &[&str]| targets.iter().map(|&t| t.to_string()).collect::<Vec<_>>();
+        let gnu = targets(&["x86_64-unknown-linux-gnu"]);
+        let musl = targets(&["x86_64-unknown-linux-musl"]);
+
+        assert_eq!(
+            with_libc_preference(&gnu, LibcPreference::Auto, true),
+            targets(&["x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"])
+        );
+        assert_eq!(
+            with_libc_preference(&gnu, LibcPreference::Musl, true),
+            targets(&["x86_64-unknown-linux-musl", "x86_64-unknown-linux-gnu"])
+        );
+        assert_eq!(with_libc_preference(&musl, LibcPreference::Auto, false), musl);
+        assert_eq!(with_libc_preference(&musl, LibcPreference::Gnu, false), musl);
+        assert_eq!(
+            with_libc_preference(
+                &targets(&["armv7-unknown-linux-musleabihf"]),
+                LibcPreference::Gnu,
+                true
+            ),
+            targets(&["armv7-unknown-linux-gnueabihf", "armv7-unknown-linux-musleabihf"])
+        );
+
+        let windows = targets(&["aarch64-pc-windows-msvc", "x86_64-pc-windows-msvc"]);
+        assert_eq!(
+            with_libc_preference(&windows, LibcPreference::Musl, true),
+            windows
+        );
+    }
+
+    #[test]
+    fn host_targets_include_build_target() {
+        assert!(host_targets().contains(&build_context::TARGET.to_string()));
+    }
+}
@@ -0,0 +1,91 @@
+//! Project-local tool environments, kept in a `.cgx` directory at the root of a project.
+//!
+//! Much like a Python virtualenv in `.venv`, a project environment gives a project a toolset of
+//! its own: when cgx runs anywhere inside a project that has one, the `.cgx` directory is used as
+//! the app dir (see `--app-dir`), so the binaries, caches, and user config cgx uses are all kept
+//! there rather than in the user's global directories.
+//!
+//! A `.cgx` directory in a cloned repository could hold binaries that cgx would then run, so like
+//! a `cgx.toml`, a project environment is only used once the project directory has been trusted
+//! with `cgx --trust <DIR>`.  `cgx --init-env` creates an environment and trusts it in one step.
+
+use crate::{Result, error, trust::TrustStore};
+use snafu::ResultExt;
+use std::path::{Path, PathBuf};
+
+/// Name of the directory at the root of a project that holds its environment.
+pub const ENV_DIR_NAME: &str = ".cgx";
+
+/// Contents of the `.gitignore` written into a new environment, since everything in it is
+/// specific to the machine it was created on.
+const GITIGNORE: &str = "# Created by `cgx --init-env`; cgx's cache for this project.\n*\n";
+
+/// The environment of the project that `cwd` is in, if there is one.
+///
+/// The closest `.cgx` directory in `cwd` or its ancestors is used.  If the project it belongs to
+/// isn't trusted, it's ignored with a warning rather than used.
+pub fn find(cwd: &Path, trust_store: &TrustStore) -> Option<PathBuf> {
+    let project_dir = cwd.ancestors().find(|dir| dir.join(ENV_DIR_NAME).is_dir())?;
+
+    if !trust_store.is_trusted(project_dir) {
+        tracing::warn!(
+            "Ignoring untrusted project environment in {}; run `cgx --trust {}` to use it.",
+            project_dir.join(ENV_DIR_NAME).display(),
+            project_dir.display()
+        );
+        return None;
+    }
+
+    Some(project_dir.join(ENV_DIR_NAME))
+}
+
+/// Create an environment for the project in `project_dir`, and trust the project.
+///
+/// Returns the environment's directory.  Initializing a project that already has an environment
+/// leaves it as it is, other than trusting the project.
+pub fn init(project_dir: &Path, trust_store: &mut TrustStore) -> Result<PathBuf> {
+    let env_dir = project_dir.join(ENV_DIR_NAME);
+    std::fs::create_dir_all(&env_dir).context(error::IoSnafu { path: &env_dir })?;
+
+    let gitignore = env_dir.join(".gitignore");
+    if !gitignore.exists() {
+        std::fs::write(&gitignore, GITIGNORE).context(error::IoSnafu { path: &gitignore })?;
+    }
+
+    trust_store.trust(project_dir)?;
+
+    Ok(env_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_trusted_env_in_ancestor() {
+        let project = tempfile::tempdir().unwrap();
+        let config = tempfile::tempdir().unwrap();
+        let mut trust_store = TrustStore::load(config.path()).unwrap();
+        let subdir = project.path().join("src/bin");
+        std::fs::create_dir_all(&subdir).unwrap();
+
+        assert_eq!(find(&subdir, &trust_store), None);
+
+        let env_dir = init(project.path(), &mut trust_store).unwrap();
+        assert_eq!(env_dir, project.path().join(ENV_DIR_NAME));
+        assert!(env_dir.join(".gitignore").is_file());
+
+        assert_eq!(find(&subdir, &trust_store), Some(env_dir));
+    }
+
+    #[test]
+    fn untrusted_env_is_ignored() {
+        let project = tempfile::tempdir().unwrap();
+        let config = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(project.path().join(ENV_DIR_NAME)).unwrap();
+
+        let trust_store = TrustStore::load(config.path()).unwrap();
+
+        assert_eq!(find(project.path(), &trust_store), None);
+    }
+}
@@ -1,11 +1,23 @@
-use crate::{Result, config::HttpConfig, cratespec::RegistrySource, error, http::HttpClient};
+use crate::{
+    Result,
+    config::{EndpointsConfig, HttpConfig},
+    cratespec::RegistrySource,
+    error,
+    git::GitClient,
+    http::HttpClient,
+};
 use backon::{BlockingRetryable, ExponentialBuilder};
 use semver::Version;
+use serde::Deserialize;
 use snafu::ResultExt;
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 use tame_index::{
     Error as TameIndexError, HttpError as TameHttpError, IndexKrate, IndexLocation, IndexUrl, KrateName,
     SparseIndex,
-    index::RemoteSparseIndex,
+    index::{IndexConfig, cache::ValidCacheEntry},
     utils::flock::{FileLock, LockOptions},
 };
 
@@ -13,6 +25,8 @@ use tame_index::{
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum DownloadUrlLookup {
     Url(String),
+    /// The `.crate` file is already on disk, in a local registry.
+    LocalFile(PathBuf),
     CrateNotFound,
     VersionNotFound,
     UrlUnavailable,
@@ -25,31 +39,94 @@ pub(crate) struct RegistryVersionInfo {
     pub(crate) yanked: bool,
 }
 
-/// Shared registry client for all sparse-index operations.
+/// Shared registry client for all registry operations.
 ///
-/// This centralizes all tame-index usage (index URL resolution, lock acquisition,
-/// sparse index fetch behavior, and retry policy).
-pub(crate) struct RegistryClient {
-    remote_index: RemoteSparseIndex,
-    lock: FileLock,
-    http_config: HttpConfig,
+/// Registries are remote sparse indexes (`sparse+` URLs), remote git indexes (any other URL, as
+/// with cargo), or, for air-gapped and vendored setups, a local registry directory given as a
+/// `file://` index URL.  Both kinds of remote index are reached with the same HTTP settings,
+/// proxy and CA bundle included.
+pub(crate) enum RegistryClient {
+    Sparse(SparseRegistry),
+    Git(GitRegistry),
+    Local(LocalRegistry),
 }
 
 impl RegistryClient {
     /// Build a registry client for crates.io (None) or a custom registry source.
+    ///
+    /// If `endpoints` overrides the crates.io index, that index is used in place of crates.io.
     pub(crate) fn new(
         source: Option<&RegistrySource>,
+        endpoints: &EndpointsConfig,
         http_client: &HttpClient,
         http: &HttpConfig,
+        git_client: &GitClient,
     ) -> Result<Self> {
         // Resolve IndexUrl based on source type.
-        let index_url = resolve_index_url(source).context(error::RegistrySnafu)?;
+        let index_url =
+            resolve_index_url(source, endpoints.crates_io_index.as_ref()).context(error::RegistrySnafu)?;
+
+        if let Some(local) = LocalRegistry::at(index_url.as_str()) {
+            return Ok(Self::Local(local));
+        }
+        if !index_url.is_sparse() {
+            return Ok(Self::Git(GitRegistry::new(index_url.as_str(), git_client)));
+        }
+        SparseRegistry::new(index_url, http_client, http).map(Self::Sparse)
+    }
+
+    /// Whether this is a local registry, which is available even when offline.
+    pub(crate) fn is_local(&self) -> bool {
+        matches!(self, Self::Local(_))
+    }
+
+    /// Fetch available versions for a crate from the index.
+    ///
+    /// Returns `Ok(None)` when the crate is not present in the selected registry.
+    pub(crate) fn crate_versions(
+        &self,
+        name: &str,
+        offline: bool,
+    ) -> Result<Option<Vec<RegistryVersionInfo>>> {
+        match self {
+            Self::Sparse(sparse) => sparse.crate_versions(name, offline),
+            Self::Git(git) => git.crate_versions(name, offline),
+            Self::Local(local) => local.crate_versions(name),
+        }
+    }
+
+    /// Find where to get the `.crate` file of an exact crate version.
+    pub(crate) fn crate_download_url(
+        &self,
+        name: &str,
+        version: &Version,
+        offline: bool,
+    ) -> Result<DownloadUrlLookup> {
+        match self {
+            Self::Sparse(sparse) => sparse.crate_download_url(name, version, offline),
+            Self::Git(git) => git.crate_download_url(name, version, offline),
+            Self::Local(local) => local.crate_file(name, version),
+        }
+    }
+}
+
+/// Client for a remote sparse index.
+///
+/// This centralizes all tame-index usage (lock acquisition, sparse index fetch behavior, and
+/// retry policy).  Requests to the index go through [`HttpClient`], like every other request cgx
+/// makes.
+pub(crate) struct SparseRegistry {
+    index: SparseIndex,
+    lock: FileLock,
+    http_client: HttpClient,
+    http_config: HttpConfig,
+}
 
+impl SparseRegistry {
+    fn new(index_url: IndexUrl<'_>, http_client: &HttpClient, http: &HttpConfig) -> Result<Self> {
         // Use the sparse index for this registry and connect to it remotely.
-        // NOTE: We currently assume remote registries only.
         let index_location = IndexLocation::new(index_url);
-        let sparse_index = SparseIndex::new(index_location).context(error::RegistrySnafu)?;
-        let remote_index = RemoteSparseIndex::new(sparse_index, http_client.inner().clone());
+        let index = SparseIndex::new(index_location).context(error::RegistrySnafu)?;
 
         // Use the same cache lock as cargo itself to maximize cache hits and compatibility.
         // The tradeoff is potential contention if cargo is simultaneously reading/updating
@@ -61,20 +138,14 @@ impl RegistryClient {
             .context(error::RegistrySnafu)?;
 
         Ok(Self {
-            remote_index,
+            index,
             lock,
+            http_client: http_client.clone(),
             http_config: http.clone(),
         })
     }
 
-    /// Fetch available versions for a crate from the sparse index.
-    ///
-    /// Returns `Ok(None)` when the crate is not present in the selected registry.
-    pub(crate) fn crate_versions(
-        &self,
-        name: &str,
-        offline: bool,
-    ) -> Result<Option<Vec<RegistryVersionInfo>>> {
+    fn crate_versions(&self, name: &str, offline: bool) -> Result<Option<Vec<RegistryVersionInfo>>> {
         let Some(krate) = self.fetch_krate(name, offline)? else {
             return Ok(None);
         };
@@ -92,12 +163,7 @@ impl RegistryClient {
     }
 
     /// Fetch a direct tarball download URL for an exact crate version.
-    pub(crate) fn crate_download_url(
-        &self,
-        name: &str,
-        version: &Version,
-        offline: bool,
-    ) -> Result<DownloadUrlLookup> {
+    fn crate_download_url(&self, name: &str, version: &Version, offline: bool) -> Result<DownloadUrlLookup> {
         let Some(krate) = self.fetch_krate(name, offline)? else {
             return Ok(DownloadUrlLookup::CrateNotFound);
         };
@@ -112,11 +178,9 @@ impl RegistryClient {
         };
 
         // Get the index config to construct the download URL.
-        let index_config = self
-            .remote_index
-            .index
-            .index_config()
-            .context(error::RegistrySnafu)?;
+        let Some(index_config) = self.index_config(offline)? else {
+            return Ok(DownloadUrlLookup::UrlUnavailable);
+        };
 
         // Get download URL for this exact version.
         let Some(download_url) = index_version.download_url(&index_config) else {
@@ -126,40 +190,352 @@ impl RegistryClient {
         Ok(DownloadUrlLookup::Url(download_url))
     }
 
+    /// Read the index's `config.json`, which describes where crate tarballs are downloaded from.
+    ///
+    /// Cargo caches this file when it first uses a registry, but for a registry cargo has never
+    /// seen (such as a private or mock index) there is no cached copy, so it is fetched directly.
+    /// Returns `Ok(None)` if the index does not serve a config.
+    fn index_config(&self, offline: bool) -> Result<Option<IndexConfig>> {
+        match self.index.index_config() {
+            Ok(config) => Ok(Some(config)),
+            Err(TameIndexError::IoPath(e, _)) if e.kind() == std::io::ErrorKind::NotFound && !offline => {
+                let url = format!("{}config.json", self.index.url());
+                let Some(bytes) = self.http_client.try_download(&url)? else {
+                    return Ok(None);
+                };
+                serde_json::from_slice(&bytes).context(error::JsonSnafu).map(Some)
+            }
+            Err(e) => Err(e).context(error::RegistrySnafu),
+        }
+    }
+
     fn fetch_krate(&self, name: &str, offline: bool) -> Result<Option<IndexKrate>> {
         // In offline mode, use cached_krate which only queries the local cache.
         // Otherwise, use krate which may perform network I/O and can trigger retries.
+        let krate_name = KrateName::try_from(name).context(error::RegistrySnafu)?;
         if offline {
-            let krate_name = KrateName::try_from(name).context(error::RegistrySnafu)?;
             return self
-                .remote_index
+                .index
                 .cached_krate(krate_name, &self.lock)
                 .context(error::RegistrySnafu);
         }
 
         let operation = || {
-            let krate_name = KrateName::try_from(name)?;
-            self.remote_index.krate(krate_name, true, &self.lock)
+            let request = self
+                .index
+                .make_remote_request(krate_name, None, &self.lock)
+                .context(error::RegistrySnafu)?;
+            let response = self.http_client.send(request)?;
+            self.index
+                .parse_remote_response(krate_name, response, true, &self.lock)
+                .context(error::RegistrySnafu)
+        };
+
+        run_with_retry(&self.http_config, name, operation)
+    }
+}
+
+/// A cargo local registry: a directory of `.crate` files, with an index of them in `index/`, as
+/// made by `cargo local-registry` for air-gapped and vendored setups.
+///
+/// It's read straight from disk, so it needs no network access even when not offline.
+pub(crate) struct LocalRegistry {
+    root: PathBuf,
+}
+
+/// The parts of a line of an index file in a local registry or a git index that cgx needs; each
+/// line describes one version of the crate.
+#[derive(Debug, Deserialize)]
+struct IndexEntry {
+    name: String,
+    vers: String,
+    #[serde(default)]
+    yanked: bool,
+}
+
+impl IndexEntry {
+    /// The entries in the contents of an index file.
+    fn parse_all(contents: &str) -> Result<Vec<Self>> {
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).context(error::JsonSnafu))
+            .collect()
+    }
+
+    /// The versions described by `entries`.
+    fn versions(entries: Vec<Self>) -> Vec<RegistryVersionInfo> {
+        entries
+            .into_iter()
+            .map(|entry| RegistryVersionInfo {
+                version: entry.vers,
+                yanked: entry.yanked,
+            })
+            .collect()
+    }
+
+    /// The entry among `entries` for exactly `version`.
+    fn find<'a>(entries: &'a [Self], version: &Version) -> Option<&'a Self> {
+        entries.iter().find(|entry| {
+            Version::parse(&entry.vers)
+                .ok()
+                .is_some_and(|ver| &ver == version)
+        })
+    }
+}
+
+impl LocalRegistry {
+    /// The local registry that `index_url` points to, if it's the `file://` URL of one.
+    fn at(index_url: &str) -> Option<Self> {
+        let url = url::Url::parse(index_url)
+            .ok()
+            .filter(|url| url.scheme() == "file")?;
+        let root = url.to_file_path().ok()?;
+        root.join("index").is_dir().then_some(Self { root })
+    }
+
+    fn entries(&self, name: &str) -> Result<Option<Vec<IndexEntry>>> {
+        let path = self.root.join("index").join(index_file_path(name));
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context(error::IoSnafu { path }),
+        };
+
+        IndexEntry::parse_all(&contents).map(Some)
+    }
+
+    fn crate_versions(&self, name: &str) -> Result<Option<Vec<RegistryVersionInfo>>> {
+        Ok(self.entries(name)?.map(IndexEntry::versions))
+    }
+
+    /// The `.crate` file of an exact crate version, which sits at the root of the registry named
+    /// after the crate as the index spells it.
+    fn crate_file(&self, name: &str, version: &Version) -> Result<DownloadUrlLookup> {
+        let Some(entries) = self.entries(name)? else {
+            return Ok(DownloadUrlLookup::CrateNotFound);
+        };
+
+        let Some(entry) = IndexEntry::find(&entries, version) else {
+            return Ok(DownloadUrlLookup::VersionNotFound);
+        };
+
+        let path = self.root.join(format!("{}-{}.crate", entry.name, entry.vers));
+        if !path.is_file() {
+            return Ok(DownloadUrlLookup::UrlUnavailable);
+        }
+
+        Ok(DownloadUrlLookup::LocalFile(path))
+    }
+}
+
+/// Client for a remote git index, the protocol cargo used for every registry before sparse
+/// indexes, and still uses for an index URL without the `sparse+` prefix.
+///
+/// The index is fetched into cgx's cache of git repositories, as a bare repository, by the same
+/// [`GitClient`] that gets crates from git, so it's reached with the same HTTP and authentication
+/// settings.  Its files are read straight out of the repository, without checking it out.
+pub(crate) struct GitRegistry {
+    url: String,
+    git_client: GitClient,
+
+    /// The commit of the index that's being read, fetched the first time it's needed, so that
+    /// looking up a crate and then where to download it from fetches the index only once.
+    commit: OnceLock<String>,
+}
+
+impl GitRegistry {
+    fn new(url: &str, git_client: &GitClient) -> Self {
+        Self {
+            url: url.to_string(),
+            git_client: git_client.clone(),
+            commit: OnceLock::new(),
+        }
+    }
+
+    /// The contents of the file at `path` in the index, or `None` if there's no such file or,
+    /// when `offline`, the index has never been fetched.
+    fn read(&self, path: &Path, offline: bool) -> Result<Option<Vec<u8>>> {
+        let commit = match self.commit.get() {
+            Some(commit) => commit,
+            None => match self.git_client.fetch_default_branch(&self.url, offline)? {
+                Some(commit) => self.commit.get_or_init(|| commit),
+                None => return Ok(None),
+            },
+        };
+
+        self.git_client.read_file(&self.url, commit, path)
+    }
+
+    fn entries(&self, name: &str, offline: bool) -> Result<Option<Vec<IndexEntry>>> {
+        let Some(contents) = self.read(&index_file_path(name), offline)? else {
+            return Ok(None);
+        };
+
+        IndexEntry::parse_all(&String::from_utf8_lossy(&contents)).map(Some)
+    }
+
+    fn crate_versions(&self, name: &str, offline: bool) -> Result<Option<Vec<RegistryVersionInfo>>> {
+        Ok(self.entries(name, offline)?.map(IndexEntry::versions))
+    }
+
+    fn crate_download_url(&self, name: &str, version: &Version, offline: bool) -> Result<DownloadUrlLookup> {
+        let Some(entries) = self.entries(name, offline)? else {
+            return Ok(DownloadUrlLookup::CrateNotFound);
         };
+        let Some(entry) = IndexEntry::find(&entries, version) else {
+            return Ok(DownloadUrlLookup::VersionNotFound);
+        };
+
+        // The index's `config.json` says where crate tarballs are downloaded from
+        let Some(config) = self.read(Path::new("config.json"), offline)? else {
+            return Ok(DownloadUrlLookup::UrlUnavailable);
+        };
+        let config: IndexConfig = serde_json::from_slice(&config).context(error::JsonSnafu)?;
+        let krate_name = KrateName::try_from(entry.name.as_str()).context(error::RegistrySnafu)?;
+
+        Ok(DownloadUrlLookup::Url(
+            config.download_url(krate_name, &entry.vers),
+        ))
+    }
+}
+
+/// The path of the index file of the crate `name`, relative to the root of the index, in the
+/// layout cargo uses for registry indexes: `1/a`, `2/ab`, `3/a/abc`, and `ab/cd/abcd...`.
+fn index_file_path(name: &str) -> PathBuf {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => ["1", &name].iter().collect(),
+        2 => ["2", &name].iter().collect(),
+        3 => ["3", &name[..1], &name].iter().collect(),
+        _ => [&name[..2], &name[2..4], &name].iter().collect(),
+    }
+}
+
+/// Names of the crates starting with `prefix` (which must be lowercase) that crates.io, or the
+/// index that replaces it, has entries for on disk: in cargo's cache of the sparse index, or in a
+/// local registry.
+///
+/// This never touches the network, so it only knows about crates that have been used before on
+/// this machine.
+pub(crate) fn cached_crate_names(endpoints: &EndpointsConfig, prefix: &str) -> Vec<String> {
+    let Ok(index_url) = resolve_index_url(None, endpoints.crates_io_index.as_ref()) else {
+        return Vec::new();
+    };
+
+    let index_dir = match LocalRegistry::at(index_url.as_str()) {
+        Some(local) => local.root.join("index"),
+        None => match sparse_cache_dir(index_url) {
+            Some(cache_dir) => cache_dir,
+            None => return Vec::new(),
+        },
+    };
+
+    names_in_index_dir(&index_dir, prefix)
+}
 
-        run_with_retry(&self.http_config, name, operation).context(error::RegistrySnafu)
+/// The revision of the crate `name` in cargo's cache of the sparse index of `source` (crates.io if
+/// `None`): the `ETag` or `Last-Modified` the index served the crate's entry with when it was last
+/// fetched.
+///
+/// This is a single read of a small file, without taking cargo's lock, so it's cheap enough to
+/// check whether the index has changed since a crate was resolved.  It's `None` for a git index or
+/// a local registry, or if the crate isn't in the cache.
+pub(crate) fn index_revision(
+    source: Option<&RegistrySource>,
+    endpoints: &EndpointsConfig,
+    name: &str,
+) -> Option<String> {
+    let index_url = resolve_index_url(source, endpoints.crates_io_index.as_ref()).ok()?;
+    if LocalRegistry::at(index_url.as_str()).is_some() || !index_url.is_sparse() {
+        return None;
+    }
+
+    let index = SparseIndex::new(IndexLocation::new(index_url)).ok()?;
+    let cache_path = index.cache().cache_path(KrateName::try_from(name).ok()?);
+    let contents = std::fs::read(cache_path).ok()?;
+    ValidCacheEntry::read(&contents)
+        .ok()
+        .map(|entry| entry.revision.to_string())
+}
+
+/// The directory cargo caches the entries of a sparse index in.
+fn sparse_cache_dir(index_url: IndexUrl<'_>) -> Option<PathBuf> {
+    let index = SparseIndex::new(IndexLocation::new(index_url)).ok()?;
+
+    // The cache has the same layout as the index, so a four-letter name is three levels down
+    let probe = index.cache().cache_path(KrateName::try_from("abcd").ok()?);
+    probe
+        .ancestors()
+        .nth(3)
+        .map(|dir| dir.as_std_path().to_path_buf())
+}
+
+/// Names of the files in an index laid out as [`index_file_path`] describes that start with
+/// `prefix`, only looking in the directories such names could be in.
+fn names_in_index_dir(index_dir: &Path, prefix: &str) -> Vec<String> {
+    if prefix.is_empty() || !prefix.is_ascii() {
+        return Vec::new();
+    }
+
+    fn subdirs(dir: &Path) -> impl Iterator<Item = (String, PathBuf)> {
+        std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.path()))
+    }
+
+    // Whether a directory named for the characters of a name from `offset` on can hold names
+    // starting with `prefix`
+    let could_match = |dir_name: &str, offset: usize| {
+        let part = prefix.get(offset..).unwrap_or("");
+        part.starts_with(dir_name) || dir_name.starts_with(part)
+    };
+
+    let mut dirs = vec![index_dir.join("1"), index_dir.join("2")];
+    dirs.extend(
+        subdirs(&index_dir.join("3"))
+            .filter(|(name, _)| could_match(name, 0))
+            .map(|(_, path)| path),
+    );
+    for (_, first) in subdirs(index_dir).filter(|(name, _)| name.len() == 2 && could_match(name, 0)) {
+        dirs.extend(
+            subdirs(&first)
+                .filter(|(name, _)| could_match(name, 2))
+                .map(|(_, path)| path),
+        );
     }
+
+    let mut names: Vec<String> = dirs
+        .iter()
+        .flat_map(|dir| std::fs::read_dir(dir).into_iter().flatten().flatten())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort();
+    names
 }
 
-/// Resolve an index URL for crates.io or a custom registry source.
-fn resolve_index_url(source: Option<&RegistrySource>) -> std::result::Result<IndexUrl<'_>, TameIndexError> {
-    match source {
-        None => IndexUrl::crates_io(
+/// Resolve an index URL for crates.io (or its configured replacement) or a custom registry source.
+fn resolve_index_url<'a>(
+    source: Option<&'a RegistrySource>,
+    crates_io_index: Option<&'a url::Url>,
+) -> std::result::Result<IndexUrl<'a>, TameIndexError> {
+    match (source, crates_io_index) {
+        (None, Some(url)) | (Some(RegistrySource::IndexUrl(url)), _) => Ok(IndexUrl::from(url.as_str())),
+        (None, None) => IndexUrl::crates_io(
             None, // config_root: search standard locations
             None, // cargo_home: use $CARGO_HOME
             None, // cargo_version: auto-detect version
         ),
-        Some(RegistrySource::Named(registry_name)) => IndexUrl::for_registry_name(
+        (Some(RegistrySource::Named(registry_name)), _) => IndexUrl::for_registry_name(
             None, // config_root: search standard locations
             None, // cargo_home: use $CARGO_HOME
             registry_name,
         ),
-        Some(RegistrySource::IndexUrl(url)) => Ok(IndexUrl::from(url.as_str())),
     }
 }
 
@@ -171,17 +547,13 @@ fn build_registry_backoff(http: &HttpConfig) -> ExponentialBuilder {
         .with_jitter()
 }
 
-fn run_with_retry<T, F>(
-    http: &HttpConfig,
-    crate_name: &str,
-    operation: F,
-) -> std::result::Result<T, TameIndexError>
+fn run_with_retry<T, F>(http: &HttpConfig, crate_name: &str, operation: F) -> Result<T>
 where
-    F: FnMut() -> std::result::Result<T, TameIndexError>,
+    F: FnMut() -> Result<T>,
 {
     operation
         .retry(build_registry_backoff(http))
-        .when(is_retryable_tame_error)
+        .when(is_retryable_registry_error)
         .notify(|err, dur| {
             tracing::debug!(
                 "Sparse index request for crate '{}' failed, retrying in {:?}: {:?}",
@@ -193,26 +565,31 @@ where
         .call()
 }
 
-fn is_retryable_tame_error(err: &TameIndexError) -> bool {
+fn is_retryable_registry_error(err: &error::Error) -> bool {
     match err {
-        TameIndexError::Http(TameHttpError::Reqwest(source)) => {
-            source.is_connect() || source.is_timeout() || source.is_request()
-        }
-        TameIndexError::Http(TameHttpError::StatusCode { code, .. }) => {
-            code.as_u16() == 429 || code.is_server_error()
-        }
-        _ => false,
+        error::Error::Registry {
+            source: TameIndexError::Http(TameHttpError::StatusCode { code, .. }),
+        } => code.as_u16() == 429 || code.is_server_error(),
+        err => HttpClient::is_connection_error(err),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::StatusCode;
     use std::{
+        path::Path,
         sync::atomic::{AtomicUsize, Ordering},
         time::Duration,
     };
 
+    fn status_error(code: StatusCode, msg: &'static str) -> error::Error {
+        error::Error::Registry {
+            source: TameIndexError::Http(TameHttpError::StatusCode { code, msg }),
+        }
+    }
+
     fn fast_http_config(retries: usize) -> HttpConfig {
         HttpConfig {
             retries,
@@ -222,25 +599,101 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_index_file_path() {
+        assert_eq!(index_file_path("a"), Path::new("1/a"));
+        assert_eq!(index_file_path("cc"), Path::new("2/cc"));
+        assert_eq!(index_file_path("Syn"), Path::new("3/s/syn"));
+        assert_eq!(index_file_path("ripgrep"), Path::new("ri/pg/ripgrep"));
+    }
+
+    #[test]
+    fn test_names_in_index_dir() {
+        let index = tempfile::tempdir().unwrap();
+        for name in [
+            "rg",
+            "rip",
+            "ripgrep",
+            "ripunzip",
+            "rust-script",
+            "just",
+            "sd",
+            "ri",
+        ] {
+            let path = index.path().join(index_file_path(name));
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        assert_eq!(
+            names_in_index_dir(index.path(), "ri"),
+            vec!["ri", "rip", "ripgrep", "ripunzip"]
+        );
+        assert_eq!(names_in_index_dir(index.path(), "ripg"), vec!["ripgrep"]);
+        assert_eq!(names_in_index_dir(index.path(), "r").len(), 6);
+        assert_eq!(names_in_index_dir(index.path(), ""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_local_registry() {
+        let root = tempfile::tempdir().unwrap();
+        let index_dir = root.path().join("index/ri/pg");
+        std::fs::create_dir_all(&index_dir).unwrap();
+        std::fs::write(
+            index_dir.join("ripgrep"),
+            concat!(
+                r#"{"name":"ripgrep","vers":"14.0.0","deps":[],"cksum":"00","features":{},"yanked":true}"#,
+                "\n",
+                r#"{"name":"ripgrep","vers":"14.1.0","deps":[],"cksum":"00","features":{},"yanked":false}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+        std::fs::write(root.path().join("ripgrep-14.1.0.crate"), b"").unwrap();
+
+        let index_url = url::Url::from_directory_path(root.path()).unwrap();
+        let registry = LocalRegistry::at(index_url.as_str()).unwrap();
+
+        assert_eq!(
+            registry.crate_versions("ripgrep").unwrap(),
+            Some(vec![
+                RegistryVersionInfo {
+                    version: "14.0.0".to_string(),
+                    yanked: true,
+                },
+                RegistryVersionInfo {
+                    version: "14.1.0".to_string(),
+                    yanked: false,
+                },
+            ])
+        );
+        assert_eq!(registry.crate_versions("serde").unwrap(), None);
+        assert_eq!(
+            registry.crate_file("ripgrep", &Version::new(14, 1, 0)).unwrap(),
+            DownloadUrlLookup::LocalFile(root.path().join("ripgrep-14.1.0.crate"))
+        );
+        assert_eq!(
+            registry.crate_file("ripgrep", &Version::new(14, 0, 0)).unwrap(),
+            DownloadUrlLookup::UrlUnavailable
+        );
+        assert_eq!(
+            registry.crate_file("ripgrep", &Version::new(13, 0, 0)).unwrap(),
+            DownloadUrlLookup::VersionNotFound
+        );
+
+        assert!(LocalRegistry::at("https://index.crates.io/").is_none());
+    }
+
     #[test]
     fn test_retry_classifier_for_status_codes() {
-        let rate_limited = TameIndexError::Http(TameHttpError::StatusCode {
-            code: tame_index::external::http::StatusCode::TOO_MANY_REQUESTS,
-            msg: "rate limited",
-        });
-        assert!(is_retryable_tame_error(&rate_limited));
+        let rate_limited = status_error(StatusCode::TOO_MANY_REQUESTS, "rate limited");
+        assert!(is_retryable_registry_error(&rate_limited));
 
-        let server_error = TameIndexError::Http(TameHttpError::StatusCode {
-            code: tame_index::external::http::StatusCode::SERVICE_UNAVAILABLE,
-            msg: "service unavailable",
-        });
-        assert!(is_retryable_tame_error(&server_error));
+        let server_error = status_error(StatusCode::SERVICE_UNAVAILABLE, "service unavailable");
+        assert!(is_retryable_registry_error(&server_error));
 
-        let unauthorized = TameIndexError::Http(TameHttpError::StatusCode {
-            code: tame_index::external::http::StatusCode::UNAUTHORIZED,
-            msg: "unauthorized",
-        });
-        assert!(!is_retryable_tame_error(&unauthorized));
+        let unauthorized = status_error(StatusCode::UNAUTHORIZED, "unauthorized");
+        assert!(!is_retryable_registry_error(&unauthorized));
     }
 
     #[test]
@@ -250,10 +703,7 @@ mod tests {
         let result = run_with_retry(&fast_http_config(2), "serde", || {
             let attempt = attempts.fetch_add(1, Ordering::SeqCst);
             if attempt == 0 {
-                Err(TameIndexError::Http(TameHttpError::StatusCode {
-                    code: tame_index::external::http::StatusCode::TOO_MANY_REQUESTS,
-                    msg: "rate limited",
-                }))
+                Err(status_error(StatusCode::TOO_MANY_REQUESTS, "rate limited"))
             } else {
                 Ok("ok")
             }
@@ -269,10 +719,7 @@ mod tests {
 
         let result = run_with_retry(&fast_http_config(3), "serde", || {
             attempts.fetch_add(1, Ordering::SeqCst);
-            Err::<(), _>(TameIndexError::Http(TameHttpError::StatusCode {
-                code: tame_index::external::http::StatusCode::UNAUTHORIZED,
-                msg: "unauthorized",
-            }))
+            Err::<(), _>(status_error(StatusCode::UNAUTHORIZED, "unauthorized"))
         });
 
         assert!(result.is_err());
@@ -285,10 +732,10 @@ mod tests {
 
         let result = run_with_retry(&fast_http_config(2), "serde", || {
             attempts.fetch_add(1, Ordering::SeqCst);
-            Err::<(), _>(TameIndexError::Http(TameHttpError::StatusCode {
-                code: tame_index::external::http::StatusCode::SERVICE_UNAVAILABLE,
-                msg: "service unavailable",
-            }))
+            Err::<(), _>(status_error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "service unavailable",
+            ))
         });
 
         assert!(result.is_err());
@@ -301,13 +748,94 @@ mod tests {
 
         let result = run_with_retry(&fast_http_config(0), "serde", || {
             attempts.fetch_add(1, Ordering::SeqCst);
-            Err::<(), _>(TameIndexError::Http(TameHttpError::StatusCode {
-                code: tame_index::external::http::StatusCode::SERVICE_UNAVAILABLE,
-                msg: "service unavailable",
-            }))
+            Err::<(), _>(status_error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "service unavailable",
+            ))
         });
 
         assert!(result.is_err());
         assert_eq!(attempts.load(Ordering::SeqCst), 1);
     }
+
+    /// Requests for index files go through [`HttpClient`], and their responses through tame-index's
+    /// `parse_remote_response` and [`is_retryable_registry_error`], just as when tame-index made
+    /// them itself.
+    mod sparse {
+        use super::*;
+        use httpmock::{Method::GET, MockServer};
+
+        const ENTRY: &str = concat!(
+            r#"{"name":"cgx-sparse","vers":"1.0.0","deps":[],"features":{},"#,
+            r#""cksum":"0000000000000000000000000000000000000000000000000000000000000000"}"#,
+        );
+
+        fn registry(server: &MockServer, http: &HttpConfig) -> SparseRegistry {
+            let index_url = format!("sparse+{}/", server.base_url());
+            SparseRegistry::new(
+                IndexUrl::from(index_url.as_str()),
+                &HttpClient::new(http).unwrap(),
+                http,
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn not_modified_index_file_is_read_from_the_cache() {
+            let server = MockServer::start();
+            let fetched = server.mock(|when, then| {
+                when.method(GET)
+                    .path("/cg/x-/cgx-sparse")
+                    .header_missing("if-none-match");
+                then.status(200).header("etag", "\"v1\"").body(ENTRY);
+            });
+            let not_modified = server.mock(|when, then| {
+                when.method(GET)
+                    .path("/cg/x-/cgx-sparse")
+                    .header("if-none-match", "\"v1\"");
+                then.status(304);
+            });
+            let registry = registry(&server, &fast_http_config(0));
+
+            for _ in 0..2 {
+                let versions = registry.crate_versions("cgx-sparse", false).unwrap().unwrap();
+                assert_eq!(versions[0].version, "1.0.0");
+            }
+            fetched.assert_calls(1);
+            not_modified.assert_calls(1);
+        }
+
+        #[test]
+        fn missing_crate_is_none() {
+            let server = MockServer::start();
+            let missing = server.mock(|when, then| {
+                when.method(GET).path("/cg/x-/cgx-sparse");
+                then.status(404);
+            });
+            let registry = registry(&server, &fast_http_config(2));
+
+            assert_eq!(registry.crate_versions("cgx-sparse", false).unwrap(), None);
+            missing.assert_calls(1);
+        }
+
+        #[test]
+        fn server_error_is_retried() {
+            let server = MockServer::start();
+            let unavailable = server.mock(|when, then| {
+                when.method(GET).path("/cg/x-/cgx-sparse");
+                then.status(503);
+            });
+            let registry = registry(&server, &fast_http_config(2));
+
+            let err = registry.crate_versions("cgx-sparse", false).unwrap_err();
+            assert!(is_retryable_registry_error(&err), "{err:?}");
+            assert!(matches!(
+                err,
+                error::Error::Registry {
+                    source: TameIndexError::Http(TameHttpError::StatusCode { code, .. }),
+                } if code == StatusCode::SERVICE_UNAVAILABLE
+            ));
+            unavailable.assert_calls(3);
+        }
+    }
 }
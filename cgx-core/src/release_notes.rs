@@ -0,0 +1,318 @@
+//! Showing what changed in a tool since it was last run here, for `--release-notes`.
+//!
+//! With `release_notes` enabled, the version each crate resolved to is recorded in
+//! `last-run.json` in [`Config::cache_dir`].  When a crate resolves to a newer version than the one
+//! recorded, the entries for the versions in between are taken from the changelog in the crate's
+//! source, so that a breaking change to a tool's command line doesn't go unnoticed.  Only the
+//! first [`MAX_NOTES_LINES`] lines are kept; the changelog itself has the rest.
+//!
+//! Changelogs aren't in any one format, so an entry is taken to be everything under a Markdown
+//! heading with a version in it, like `## [1.2.0] - 2024-01-01` or `# v1.2.0`, up to the next
+//! heading at the same level or above.
+
+use crate::{
+    Result,
+    config::Config,
+    crate_resolver::{ResolvedCrate, ResolvedSource},
+    error,
+    helpers::long_path,
+};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The most lines of release notes shown for one run.
+pub const MAX_NOTES_LINES: usize = 40;
+
+/// The names of the files looked for in the root of a crate's source, matched case-insensitively,
+/// in order of preference.
+const CHANGELOG_FILE_NAMES: &[&str] = &[
+    "CHANGELOG.md",
+    "CHANGELOG",
+    "CHANGES.md",
+    "CHANGES",
+    "HISTORY.md",
+    "RELEASES.md",
+];
+
+/// Name of the file in [`Config::cache_dir`] that the version last run of each crate is kept in.
+const LAST_RUN_FILE_NAME: &str = "last-run.json";
+
+/// What changed in a crate since the version last run here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseNotes {
+    pub name: String,
+
+    /// The version that was last run.
+    pub previous_version: Version,
+
+    /// The version about to be run.
+    pub version: Version,
+
+    /// The changelog the notes are from.
+    pub changelog: PathBuf,
+
+    /// The entries for the versions after `previous_version` up to `version`, newest first as
+    /// changelogs usually are, cut short after [`MAX_NOTES_LINES`].
+    pub notes: String,
+
+    /// Whether `notes` was cut short.
+    pub truncated: bool,
+}
+
+/// The contents of `last-run.json`: the version last run of each crate, by name.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LastRuns {
+    crates: BTreeMap<String, Version>,
+}
+
+/// Record that `resolved`, whose source is in `source_dir`, is about to be run, and return the
+/// release notes since the version last run if it's newer.
+///
+/// This is only called once the binary has been acquired, so that a version that failed to build
+/// or download isn't taken to have been run.
+///
+/// Nothing is recorded unless [`Config::release_notes`] is set, and crates from a local directory
+/// never are, since their versions aren't releases.
+pub fn record(config: &Config, resolved: &ResolvedCrate, source_dir: &Path) -> Result<Option<ReleaseNotes>> {
+    if !config.release_notes || matches!(resolved.source, ResolvedSource::LocalDir { .. }) {
+        return Ok(None);
+    }
+
+    let path = config.cache_dir.join(LAST_RUN_FILE_NAME);
+    let mut last_runs = load(&path);
+    let previous_version = last_runs
+        .crates
+        .insert(resolved.name.clone(), resolved.version.clone());
+    if previous_version.as_ref() != Some(&resolved.version) {
+        save(&path, &last_runs)?;
+    }
+
+    let Some(previous_version) = previous_version.filter(|previous| *previous < resolved.version) else {
+        return Ok(None);
+    };
+    let Some(changelog) = find_changelog(source_dir) else {
+        tracing::debug!("{} {} has no changelog", resolved.name, resolved.version);
+        return Ok(None);
+    };
+    let contents = fs::read_to_string(long_path(&changelog)).context(error::IoSnafu { path: &changelog })?;
+
+    let Some((notes, truncated)) = entries_between(&contents, &previous_version, &resolved.version) else {
+        return Ok(None);
+    };
+
+    Ok(Some(ReleaseNotes {
+        name: resolved.name.clone(),
+        previous_version,
+        version: resolved.version.clone(),
+        changelog,
+        notes,
+        truncated,
+    }))
+}
+
+/// The changelog in the root of the crate's source in `source_dir`, if it has one.
+fn find_changelog(source_dir: &Path) -> Option<PathBuf> {
+    let files: Vec<PathBuf> = fs::read_dir(long_path(source_dir))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+
+    CHANGELOG_FILE_NAMES.iter().find_map(|name| {
+        files
+            .iter()
+            .find(|path| {
+                path.file_name()
+                    .and_then(|file_name| file_name.to_str())
+                    .is_some_and(|file_name| file_name.eq_ignore_ascii_case(name))
+            })
+            .cloned()
+    })
+}
+
+/// The changelog entries in `contents` for versions after `previous` up to and including
+/// `current`, cut short after [`MAX_NOTES_LINES`], along with whether they were.
+///
+/// Returns `None` if there are no such entries.
+fn entries_between(contents: &str, previous: &Version, current: &Version) -> Option<(String, bool)> {
+    let mut lines = Vec::new();
+    // The level of the heading of the entry being taken, if one is
+    let mut taking: Option<usize> = None;
+
+    for line in contents.lines() {
+        if let Some(level) = heading_level(line) {
+            if taking.is_some_and(|taking| level <= taking) {
+                taking = None;
+            }
+            if taking.is_none() {
+                taking = heading_version(line)
+                    .filter(|version| previous < version && version <= current)
+                    .map(|_| level);
+            }
+        }
+
+        if taking.is_some() {
+            lines.push(line.trim_end());
+        }
+    }
+
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    if lines.is_empty() {
+        return None;
+    }
+
+    let truncated = lines.len() > MAX_NOTES_LINES;
+    lines.truncate(MAX_NOTES_LINES);
+    Some((lines.join("\n"), truncated))
+}
+
+/// The level of the Markdown heading on `line`, if it's one.
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    (level > 0 && (rest.is_empty() || rest.starts_with(' '))).then_some(level)
+}
+
+/// The first version in the heading on `line`, with or without a `v` in front of it.
+fn heading_version(line: &str) -> Option<Version> {
+    line.trim_start_matches('#')
+        .split(|c: char| c.is_whitespace() || matches!(c, '[' | ']' | '(' | ')' | ',' | ':'))
+        .find_map(|word| Version::parse(word.strip_prefix('v').unwrap_or(word)).ok())
+}
+
+/// Read the versions last run from `path`, starting afresh if there's no record or it can't be
+/// read.
+fn load(path: &Path) -> LastRuns {
+    let Ok(json) = fs::read_to_string(long_path(path)) else {
+        return LastRuns::default();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        tracing::debug!(path = %path.display(), "Ignoring invalid record of the versions last run: {}", e);
+        LastRuns::default()
+    })
+}
+
+/// Write `last_runs` to `path`, replacing it atomically so that a cgx running at the same time
+/// never reads it half written.
+fn save(path: &Path, last_runs: &LastRuns) -> Result<()> {
+    let json = serde_json::to_string_pretty(last_runs).context(error::JsonSnafu)?;
+    let temp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+
+    fs::write(long_path(&temp_path), json).context(error::IoSnafu { path: &temp_path })?;
+    fs::rename(long_path(&temp_path), long_path(path)).context(error::RenameFileSnafu {
+        src: &temp_path,
+        dst: path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANGELOG: &str = "# Changelog
+
+## [Unreleased]
+
+- Something not out yet
+
+## [2.0.0] - 2024-03-01
+
+### Breaking
+
+- `--output` is now `--out`
+
+## [1.2.0] - 2024-02-01
+
+- Added `--color`
+
+## v1.1.0
+
+- Fixed a crash
+
+## 1.0.0
+
+- First release
+";
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn takes_the_entries_after_the_previous_version() {
+        let (notes, truncated) = entries_between(CHANGELOG, &version("1.1.0"), &version("2.0.0")).unwrap();
+
+        assert_eq!(
+            notes,
+            "## [2.0.0] - 2024-03-01\n\n### Breaking\n\n- `--output` is now `--out`\n\n## [1.2.0] - \
+             2024-02-01\n\n- Added `--color`"
+        );
+        assert!(!truncated);
+
+        assert_eq!(
+            entries_between(CHANGELOG, &version("1.0.0"), &version("1.1.0"))
+                .unwrap()
+                .0,
+            "## v1.1.0\n\n- Fixed a crash"
+        );
+        assert_eq!(
+            entries_between(CHANGELOG, &version("2.0.0"), &version("2.1.0")),
+            None
+        );
+    }
+
+    #[test]
+    fn long_notes_are_cut_short() {
+        let mut changelog = "## 2.0.0\n".to_string();
+        for i in 0..MAX_NOTES_LINES * 2 {
+            changelog.push_str(&format!("- Change {}\n", i));
+        }
+
+        let (notes, truncated) = entries_between(&changelog, &version("1.0.0"), &version("2.0.0")).unwrap();
+        assert_eq!(notes.lines().count(), MAX_NOTES_LINES);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn notes_are_only_for_a_newer_version_than_last_run() {
+        let (_temp_dir, mut config) = crate::config::create_test_env();
+        fs::create_dir_all(&config.cache_dir).unwrap();
+        config.release_notes = true;
+        let source_dir = tempfile::tempdir().unwrap();
+        fs::write(source_dir.path().join("changelog.md"), CHANGELOG).unwrap();
+        let resolved = |version: &str| ResolvedCrate {
+            name: "tool".to_string(),
+            version: self::version(version),
+            source: ResolvedSource::CratesIo,
+        };
+
+        assert_eq!(
+            record(&config, &resolved("1.2.0"), source_dir.path()).unwrap(),
+            None
+        );
+        assert_eq!(
+            record(&config, &resolved("1.2.0"), source_dir.path()).unwrap(),
+            None
+        );
+        assert_eq!(
+            record(&config, &resolved("1.1.0"), source_dir.path()).unwrap(),
+            None
+        );
+
+        let notes = record(&config, &resolved("2.0.0"), source_dir.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(notes.previous_version, version("1.1.0"));
+        assert_eq!(notes.changelog, source_dir.path().join("changelog.md"));
+        assert!(notes.notes.starts_with("## [2.0.0]"));
+    }
+}
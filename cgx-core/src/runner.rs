@@ -9,13 +9,276 @@
 //!
 //! The `run()` function never returns on success - it either replaces the process (Unix)
 //! or exits with the child's exit code (Windows/other).
+//!
+//! The tool can be given a different `argv[0]` than the path it was run from, for multi-call
+//! binaries that pick what to do based on the name they were invoked as (see [`tool_command`]),
+//! and extra environment variables, such as the one pointing a tool at its staged assets.
+//!
+//! The tool can also be run under a wrapper command, such as `valgrind` or `strace`, which is
+//! run in its place with the tool's path and arguments appended (see [`load_wrapper`]).  The
+//! wrapper then takes the tool's place in everything above: it is what cgx replaces itself
+//! with, or spawns and waits for.
+//!
+//! A WebAssembly (WASI) binary, such as one built for `wasm32-wasip1`, can't be run directly, so
+//! it's run under a WebAssembly runtime the same way (see [`wasm_runtime`]), after any wrapper.
+//!
+//! When [`RunLimits`] are requested, [`run_with_limits`] is used instead.  Enforcing a runtime
+//! limit requires cgx to stay alive as the parent of the tool, and so does telling the user that
+//! a memory limit is what the tool died of, so in that case the tool is always spawned as a child
+//! process and its exit code is returned to the caller.
 
-#[cfg(windows)]
-use crate::error;
-use crate::error::{Error, Result};
-#[cfg(windows)]
-use snafu::ResultExt;
-use std::{ffi::OsString, path::Path, process::Command};
+use crate::{
+    binary_format,
+    builder::{BuildOptions, missing_required_features},
+    cli::CliArgs,
+    config::Config,
+    error::{self, Error, Result},
+    messages::{MessageReporter, RunnerMessage},
+};
+use snafu::{OptionExt, ResultExt};
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    time::{Duration, Instant},
+};
+
+/// Exit code returned by [`run_with_limits`] when the tool is killed for exceeding its runtime
+/// limit.  This matches the convention of `timeout(1)`.
+pub const RUNTIME_EXCEEDED_EXIT_CODE: i32 = 124;
+
+/// How often a child with a runtime limit is polled for completion.
+const RUNTIME_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The WebAssembly runtimes looked for on the `PATH` to run WASI binaries with, most preferred
+/// first, along with the arguments that have them run a module with access to the current
+/// directory.
+const WASM_RUNTIMES: &[(&str, &[&str])] =
+    &[("wasmtime", &["run", "--dir=."]), ("wasmer", &["run", "--dir=."])];
+
+/// The crate cgx gets a WebAssembly runtime from, when there's none configured or on the `PATH`.
+/// It's the first of [`WASM_RUNTIMES`].
+pub const WASM_RUNTIME_CRATE: &str = "wasmtime-cli";
+
+/// Resource limits applied to the executed tool.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunLimits {
+    /// Wall-clock time after which the tool is killed.
+    pub max_runtime: Option<Duration>,
+
+    /// Maximum virtual memory the tool may use, in bytes.
+    pub max_memory: Option<u64>,
+}
+
+impl RunLimits {
+    /// Load the run limits from the command line arguments.
+    pub fn load(args: &CliArgs) -> Result<Self> {
+        let max_runtime = args
+            .max_runtime
+            .as_ref()
+            .map(|value| {
+                humantime::parse_duration(value)
+                    .context(error::InvalidMaxRuntimeSnafu { value: value.clone() })
+            })
+            .transpose()?;
+        let max_memory = args
+            .max_memory
+            .as_ref()
+            .map(|value| {
+                parse_byte_size(value).context(error::InvalidMaxMemorySnafu { value: value.clone() })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            max_runtime,
+            max_memory,
+        })
+    }
+
+    /// Returns true if no limits are set, in which case [`run`] should be used.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_runtime.is_none() && self.max_memory.is_none()
+    }
+}
+
+/// Load the wrapper command to run the tool `tool` under, split into the program and its
+/// arguments, from `--wrapper` or else the tool's config.
+///
+/// Returns an empty list if the tool isn't wrapped, which `--wrapper ""` forces even if the
+/// tool's config sets a wrapper.
+pub fn load_wrapper(args: &CliArgs, config: &Config, tool: Option<&str>) -> Result<Vec<OsString>> {
+    let wrapper = args
+        .wrapper
+        .as_deref()
+        .or_else(|| tool.and_then(|tool| config.tool_wrapper(tool)));
+
+    match wrapper {
+        Some(wrapper) => parse_wrapper(wrapper),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The WebAssembly runtime to run the binary at `bin_path` under, split into the program and its
+/// arguments, or an empty list if it's a native binary.
+///
+/// The runtime is `wasm_runtime` from the config, or else the first of `wasmtime` and `wasmer` on
+/// the `PATH`.  Failing those, `acquire` is called to get a `wasmtime` binary from
+/// [`WASM_RUNTIME_CRATE`] the same way as any other tool, and fails with
+/// [`Error::NoWasmRuntime`] if it can't.
+pub fn wasm_runtime(
+    config: &Config,
+    bin_path: &Path,
+    acquire: impl FnOnce() -> Result<PathBuf>,
+) -> Result<Vec<OsString>> {
+    if !binary_format::is_wasm(bin_path) {
+        return Ok(Vec::new());
+    }
+    if let Some(runtime) = &config.wasm_runtime {
+        return parse_wrapper(runtime);
+    }
+
+    let found = WASM_RUNTIMES
+        .iter()
+        .find_map(|(name, args)| find_on_path(name).map(|program| (program, *args)));
+    let (program, args) = if let Some(found) = found {
+        found
+    } else {
+        let wasmtime = acquire().map_err(|e| Error::NoWasmRuntime {
+            path: bin_path.to_path_buf(),
+            runtime_crate: WASM_RUNTIME_CRATE.to_string(),
+            source: Box::new(e),
+        })?;
+        (wasmtime, WASM_RUNTIMES[0].1)
+    };
+
+    Ok(std::iter::once(program.into_os_string())
+        .chain(args.iter().map(OsString::from))
+        .collect())
+}
+
+/// The program `name` in the first directory on the `PATH` that has it.
+fn find_on_path(name: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    let path = std::env::var_os("PATH")?;
+
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Split a wrapper command line into words with [`split_words`].
+pub(crate) fn parse_wrapper(wrapper: &str) -> Result<Vec<OsString>> {
+    let words =
+        split_words(wrapper).map_err(|message| error::InvalidWrapperSnafu { wrapper, message }.build())?;
+    Ok(words.into_iter().map(OsString::from).collect())
+}
+
+/// `word` as a word of a command line that [`split_words`] splits back into the same word, quoted
+/// only if it has to be.
+pub(crate) fn quote_word(word: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "/._-+:@,%=".contains(c);
+    if !word.is_empty() && word.chars().all(is_plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+/// Split a command line into words the way a POSIX shell would, without expanding anything:
+/// words are separated by whitespace, quotes group words that contain it, and a backslash escapes
+/// the next character outside of single quotes.
+///
+/// If the line can't be split, the error says why.
+pub(crate) fn split_words(line: &str) -> std::result::Result<Vec<String>, &'static str> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\\' => {
+                let escaped = chars.next().ok_or("it ends with a backslash")?;
+                word.push(escaped);
+                in_word = true;
+            }
+            '\'' => {
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("a single quote is not closed"),
+                    }
+                }
+                in_word = true;
+            }
+            '"' => {
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            // Leaves the quote unclosed, which is reported below
+                            None => {}
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("a double quote is not closed"),
+                    }
+                }
+                in_word = true;
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// The cargo subcommand the binary at `bin_path` implements, if it's named like one: `nextest`
+/// for `cargo-nextest`.
+///
+/// This is how cargo itself finds subcommands, so it holds whichever crate the binary came from.
+pub fn cargo_subcommand(bin_path: &Path) -> Option<String> {
+    let name = bin_path.file_stem()?.to_str()?;
+    name.strip_prefix("cargo-")
+        .filter(|subcommand| !subcommand.is_empty())
+        .map(str::to_string)
+}
+
+/// The arguments to run a cargo subcommand with, given the ones meant for it.
+///
+/// Cargo runs `cargo foo --bar` as `cargo-foo foo --bar`, and subcommands parse their arguments
+/// expecting that, so the subcommand's name goes first.  Arguments that already start with it,
+/// as when a script calls the binary the way cargo does, are left as they are.
+pub fn cargo_subcommand_args(subcommand: &str, args: &[OsString]) -> Vec<OsString> {
+    if args.first().is_some_and(|first| first == subcommand) {
+        return args.to_vec();
+    }
+
+    std::iter::once(OsString::from(subcommand))
+        .chain(args.iter().cloned())
+        .collect()
+}
 
 /// Run a binary, replacing or waiting for it depending on platform.
 ///
@@ -31,40 +294,108 @@ use std::{ffi::OsString, path::Path, process::Command};
 /// # Arguments
 ///
 /// * `bin_path` - Path to the binary to execute
+/// * `argv0` - Name to run the binary as, if not its path
+/// * `env` - Environment variables to set for the binary, on top of those cgx was run with
+/// * `wrapper` - Command to run the binary under, if not empty (see [`load_wrapper`])
 /// * `args` - Arguments to pass to the binary
 ///
 /// # Returns
 ///
 /// Only returns `Err` if the binary cannot be launched. On success, this function
 /// either replaces the current process or exits, and thus never returns.
-pub fn run(bin_path: &Path, args: &[OsString]) -> Result<()> {
+pub fn run(
+    bin_path: &Path,
+    argv0: Option<&OsStr>,
+    env: &[(OsString, OsString)],
+    wrapper: &[OsString],
+    args: &[OsString],
+) -> Result<()> {
+    check_wrapper(argv0, wrapper)?;
+    let cmd = tool_command(bin_path, argv0, env, wrapper, args);
+
     #[cfg(unix)]
     {
-        exec_replace(bin_path, args)
+        exec_replace(cmd, launched_program(bin_path, wrapper))
     }
 
     #[cfg(windows)]
     {
-        spawn_and_wait_windows(bin_path, args)
+        spawn_and_wait_windows(cmd, launched_program(bin_path, wrapper))
     }
 
     #[cfg(not(any(unix, windows)))]
     {
-        spawn_and_wait_fallback(bin_path, args)
+        spawn_and_wait_fallback(cmd, launched_program(bin_path, wrapper))
+    }
+}
+
+/// Check that the tool can be run under `wrapper` as `argv0`.
+///
+/// A wrapper runs the tool itself, so on Unix there's no way to give the tool a different
+/// `argv[0]`.  On other platforms `argv0` is just another argument, which a wrapper passes on.
+fn check_wrapper(argv0: Option<&OsStr>, wrapper: &[OsString]) -> Result<()> {
+    if cfg!(unix) && argv0.is_some() && !wrapper.is_empty() {
+        return error::Argv0WithWrapperSnafu.fail();
     }
+
+    Ok(())
+}
+
+/// The program that is actually launched to run the binary at `bin_path`: the wrapper, if there
+/// is one.  Failures to launch it are reported against this path.
+fn launched_program<'a>(bin_path: &'a Path, wrapper: &'a [OsString]) -> &'a Path {
+    wrapper.first().map_or(bin_path, Path::new)
+}
+
+/// Build the command that runs the binary at `bin_path` with `args` and the variables in `env`
+/// added to its environment, as `argv0` if given, under `wrapper` if it isn't empty.
+///
+/// On Unix `argv0` is passed as the process's `argv[0]`.  Windows has no `argv[0]` separate from
+/// the command line, so there it is instead prefixed to the arguments, which is how multi-call
+/// binaries such as `busybox` and `uutils` are told which tool to act as on that platform.
+fn tool_command(
+    bin_path: &Path,
+    argv0: Option<&OsStr>,
+    env: &[(OsString, OsString)],
+    wrapper: &[OsString],
+    args: &[OsString],
+) -> Command {
+    let mut cmd = match wrapper.split_first() {
+        Some((program, wrapper_args)) => {
+            let mut cmd = Command::new(program);
+            cmd.args(wrapper_args).arg(bin_path);
+            cmd
+        }
+        None => Command::new(bin_path),
+    };
+    cmd.envs(env.iter().map(|(key, value)| (key, value)));
+
+    if let Some(argv0) = argv0 {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+
+            cmd.arg0(argv0);
+        }
+
+        #[cfg(not(unix))]
+        cmd.arg(argv0);
+    }
+
+    cmd.args(args);
+    cmd
 }
 
 /// Unix implementation: Replace current process with the target binary.
 ///
-/// Uses the `exec()` system call to replace the current process image with the new binary.
-/// This means cgx's process ID stays the same, but it becomes the target binary.
-/// Signals are handled naturally because the target binary receives them directly.
+/// Uses the `exec()` system call to replace the current process image with the new binary,
+/// `program` being what `cmd` launches.  This means cgx's process ID stays the same, but it
+/// becomes the target binary.  Signals are handled naturally because the target binary receives
+/// them directly.
 #[cfg(unix)]
-fn exec_replace(bin_path: &Path, args: &[OsString]) -> Result<()> {
+fn exec_replace(mut cmd: Command, program: &Path) -> Result<()> {
     use std::os::unix::process::CommandExt;
 
-    let mut cmd = Command::new(bin_path);
-    cmd.args(args);
     // Environment and current directory are inherited by default
 
     // exec() replaces the current process and never returns on success.
@@ -73,7 +404,7 @@ fn exec_replace(bin_path: &Path, args: &[OsString]) -> Result<()> {
 
     // Only reachable if exec() failed
     Err(err).map_err(|source| Error::ExecFailed {
-        path: bin_path.to_owned(),
+        path: program.to_owned(),
         source,
     })
 }
@@ -89,7 +420,7 @@ fn exec_replace(bin_path: &Path, args: &[OsString]) -> Result<()> {
 /// Both the parent (cgx) and child receive Ctrl-C events. The parent ignores them,
 /// allowing the child to handle signals as it sees fit.
 #[cfg(windows)]
-fn spawn_and_wait_windows(bin_path: &Path, args: &[OsString]) -> Result<()> {
+fn spawn_and_wait_windows(mut cmd: Command, program: &Path) -> Result<()> {
     // Install handler that ignores Ctrl-C in parent process.
     // The child will receive and handle Ctrl-C directly from the Windows console.
     ctrlc::set_handler(|| {
@@ -99,13 +430,10 @@ fn spawn_and_wait_windows(bin_path: &Path, args: &[OsString]) -> Result<()> {
     .context(error::ConsoleHandlerFailedSnafu)?;
 
     // Spawn the child process
-    let mut child = Command::new(bin_path)
-        .args(args)
-        .spawn()
-        .map_err(|source| Error::SpawnFailed {
-            path: bin_path.to_owned(),
-            source,
-        })?;
+    let mut child = cmd.spawn().map_err(|source| Error::SpawnFailed {
+        path: program.to_owned(),
+        source,
+    })?;
 
     // Wait for the child to complete
     let status = child.wait().map_err(|source| Error::WaitFailed { source })?;
@@ -123,15 +451,12 @@ fn spawn_and_wait_windows(bin_path: &Path, args: &[OsString]) -> Result<()> {
 /// On most POSIX-like systems, the default signal handling will likely be reasonable,
 /// but Ctrl-C behavior may not be optimal.
 #[cfg(not(any(unix, windows)))]
-fn spawn_and_wait_fallback(bin_path: &Path, args: &[OsString]) -> Result<()> {
+fn spawn_and_wait_fallback(mut cmd: Command, program: &Path) -> Result<()> {
     // Spawn the child process
-    let mut child = Command::new(bin_path)
-        .args(args)
-        .spawn()
-        .map_err(|source| Error::SpawnFailed {
-            path: bin_path.to_owned(),
-            source,
-        })?;
+    let mut child = cmd.spawn().map_err(|source| Error::SpawnFailed {
+        path: program.to_owned(),
+        source,
+    })?;
 
     // Wait for the child to complete
     let status = child.wait().map_err(|source| Error::WaitFailed { source })?;
@@ -143,14 +468,250 @@ fn spawn_and_wait_fallback(bin_path: &Path, args: &[OsString]) -> Result<()> {
     std::process::exit(exit_code)
 }
 
+/// The first of `args` that `hints` (a tool's `feature_hints`) says needs features that a build
+/// with `options` doesn't enable, along with those features, sorted.
+///
+/// A flag matches with or without a value attached, so `--format=json` matches a hint for
+/// `--format`.  This is checked before the tool runs, since on Unix cgx becomes the tool and never
+/// sees how it exits.
+///
+/// The features are checked the way the builder checks a target's `required-features` (see
+/// [`missing_required_features`]), except that the package's `[features]` table isn't known here,
+/// since the binary may not have been built from source, so only the features asked for count.
+pub fn missing_features<'a>(
+    hints: &'a HashMap<String, Vec<String>>,
+    args: &'a [OsString],
+    options: &BuildOptions,
+) -> Option<(&'a str, Vec<String>)> {
+    args.iter()
+        .filter_map(|arg| arg.to_str())
+        .map(|arg| arg.split_once('=').map_or(arg, |(flag, _)| flag))
+        .find_map(|arg| {
+            let missing = missing_required_features(&BTreeMap::new(), hints.get(arg)?, options);
+            (!missing.is_empty()).then_some((arg, missing))
+        })
+}
+
+/// Run a binary as a child process subject to the given [`RunLimits`], and wait for it to exit,
+/// returning its exit code.
+///
+/// If it exceeds `max_runtime` it is killed, a [`RunnerMessage::RuntimeLimitExceeded`] is
+/// reported, and [`RUNTIME_EXCEEDED_EXIT_CODE`] is returned.  If it's killed by a signal while it
+/// has a `max_memory`, as a tool that can't allocate memory under the limit usually is by
+/// aborting, a [`RunnerMessage::MemoryLimitExceeded`] is reported.  This never replaces the cgx
+/// process, even without any limit, so it's also how a tool is run that cgx has to clean up after
+/// once it exits, as `--ephemeral` does.
+///
+/// Unlike [`run`], this function does not exit the process itself, so that the caller can flush
+/// any pending messages before exiting with the returned code.
+pub fn run_with_limits(
+    bin_path: &Path,
+    argv0: Option<&OsStr>,
+    env: &[(OsString, OsString)],
+    wrapper: &[OsString],
+    args: &[OsString],
+    limits: &RunLimits,
+    reporter: &MessageReporter,
+) -> Result<i32> {
+    check_wrapper(argv0, wrapper)?;
+    let mut cmd = limited_command(bin_path, argv0, env, wrapper, args, limits)?;
+
+    #[cfg(windows)]
+    ctrlc::set_handler(|| {}).context(error::ConsoleHandlerFailedSnafu)?;
+
+    let mut child = cmd.spawn().map_err(|source| Error::SpawnFailed {
+        path: launched_program(bin_path, wrapper).to_owned(),
+        source,
+    })?;
+    let started = Instant::now();
+
+    let status = match limits.max_runtime {
+        None => child.wait().map_err(|source| Error::WaitFailed { source })?,
+        Some(max_runtime) => loop {
+            if let Some(status) = child.try_wait().map_err(|source| Error::WaitFailed { source })? {
+                break status;
+            }
+
+            let elapsed = started.elapsed();
+            if elapsed >= max_runtime {
+                child.kill().with_context(|_| error::KillFailedSnafu {
+                    path: bin_path.to_owned(),
+                })?;
+                child.wait().map_err(|source| Error::WaitFailed { source })?;
+
+                tracing::warn!(
+                    "{} exceeded its maximum runtime of {} and was killed",
+                    bin_path.display(),
+                    humantime::format_duration(max_runtime)
+                );
+                reporter.report(|| RunnerMessage::runtime_limit_exceeded(bin_path, max_runtime));
+                return Ok(RUNTIME_EXCEEDED_EXIT_CODE);
+            }
+
+            std::thread::sleep(RUNTIME_POLL_INTERVAL.min(max_runtime - elapsed));
+        },
+    };
+
+    if let Some((max_memory, signal)) = limits.max_memory.zip(killed_by(&status)) {
+        tracing::warn!(
+            "{} was killed by signal {} while limited to {} bytes of memory, which it most likely ran out of",
+            bin_path.display(),
+            signal,
+            max_memory
+        );
+        reporter.report(|| RunnerMessage::memory_limit_exceeded(bin_path, max_memory, signal));
+    }
+
+    Ok(status.code().unwrap_or(1))
+}
+
+/// The signal that killed the process that exited with `status`, if one did.
+#[cfg(unix)]
+fn killed_by(status: &ExitStatus) -> Option<i32> {
+    status.signal()
+}
+
+/// Processes can't be killed by a signal where there are none.
+#[cfg(not(unix))]
+fn killed_by(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Start the binary in the background, subject to the memory limit in `limits`, and return its
+/// process ID without waiting for it (see [`crate::detach`]).
+///
+/// The tool gets nothing on its standard input, writes its output to `stdout` and `stderr`, and
+/// runs in a process group of its own (on Windows, detached from cgx's console), so that a Ctrl-C
+/// in the terminal cgx was run from doesn't reach it.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn spawn_detached(
+    bin_path: &Path,
+    argv0: Option<&OsStr>,
+    env: &[(OsString, OsString)],
+    wrapper: &[OsString],
+    args: &[OsString],
+    limits: &RunLimits,
+    stdout: std::fs::File,
+    stderr: std::fs::File,
+) -> Result<u32> {
+    check_wrapper(argv0, wrapper)?;
+    let mut cmd = limited_command(bin_path, argv0, env, wrapper, args, limits)?;
+    cmd.stdin(Stdio::null()).stdout(stdout).stderr(stderr);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        cmd.process_group(0);
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        cmd.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+
+    let child = cmd.spawn().map_err(|source| Error::SpawnFailed {
+        path: launched_program(bin_path, wrapper).to_owned(),
+        source,
+    })?;
+
+    Ok(child.id())
+}
+
+/// Build the command that launches the tool with its memory limit (if any) applied.
+///
+/// `std` offers no safe way to call `setrlimit` between fork and exec, so on Unix the limit is
+/// applied by `/bin/sh` via `ulimit` (see [`ulimit_memory_flag`]), after which the shell `exec`s
+/// the tool so the limit is inherited without leaving an extra process behind.  A POSIX shell's
+/// `exec` can't set the tool's `argv[0]`, so that can't be combined with a memory limit.
+///
+/// On Windows a process's memory can only be limited by putting it in a job object, which takes
+/// Win32 calls that can't be made without unsafe code, so a memory limit is refused there with
+/// [`Error::RunLimitUnsupported`].
+fn limited_command(
+    bin_path: &Path,
+    argv0: Option<&OsStr>,
+    env: &[(OsString, OsString)],
+    wrapper: &[OsString],
+    args: &[OsString],
+    limits: &RunLimits,
+) -> Result<Command> {
+    match limits.max_memory {
+        None => Ok(tool_command(bin_path, argv0, env, wrapper, args)),
+        #[cfg(unix)]
+        Some(_) if argv0.is_some() => error::Argv0WithMemoryLimitSnafu.fail(),
+        #[cfg(unix)]
+        Some(max_memory) => {
+            let mut cmd = Command::new("/bin/sh");
+            cmd.arg("-c")
+                .arg(format!(
+                    "ulimit {} {} && exec \"$0\" \"$@\"",
+                    ulimit_memory_flag(std::env::consts::OS),
+                    max_memory.div_ceil(1024)
+                ))
+                .args(wrapper)
+                .arg(bin_path)
+                .args(args)
+                .envs(env.iter().map(|(key, value)| (key, value)));
+            Ok(cmd)
+        }
+        #[cfg(not(unix))]
+        Some(_) => error::RunLimitUnsupportedSnafu { limit: "max-memory" }.fail(),
+    }
+}
+
+/// The `ulimit` flag of `/bin/sh` on `os` that limits a process's memory, in KiB.
+///
+/// That's the address space limit, `-v`, everywhere but OpenBSD, which has no such limit; there
+/// the data segment limit, `-d`, is the closest, since that's where heap allocations go.
+#[cfg(unix)]
+fn ulimit_memory_flag(os: &str) -> &'static str {
+    match os {
+        "openbsd" => "-d",
+        _ => "-v",
+    }
+}
+
+/// Parse a human-readable byte size such as `512M`, `2GiB`, or `1048576`.
+///
+/// Suffixes are binary multiples (K = 1024).  Returns [`None`] for malformed or zero sizes.
+pub(crate) fn parse_byte_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (digits, suffix) = value.split_at(split);
+    let count: u64 = digits.parse().ok()?;
+
+    let unit = suffix.trim().to_ascii_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+    let multiplier: u64 = match unit {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+
+    count.checked_mul(multiplier).filter(|bytes| *bytes > 0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::messages::Message;
+    use assert_matches::assert_matches;
 
     #[test]
     fn test_run_nonexistent_binary() {
         // Attempting to run a nonexistent binary should return an error
-        let result = run(Path::new("/nonexistent/binary"), &[]);
+        let result = run(Path::new("/nonexistent/binary"), None, &[], &[], &[]);
         assert!(result.is_err());
     }
 
@@ -159,6 +720,388 @@ mod tests {
         // This test would need a test binary to actually execute,
         // which would be better handled in integration tests
         // For now, just verify the function signature is correct
-        let _: fn(&Path, &[OsString]) -> Result<()> = run;
+        type Run = fn(&Path, Option<&OsStr>, &[(OsString, OsString)], &[OsString], &[OsString]) -> Result<()>;
+        let _: Run = run;
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("1024"), Some(1024));
+        assert_eq!(parse_byte_size("1B"), Some(1));
+        assert_eq!(parse_byte_size("4k"), Some(4096));
+        assert_eq!(parse_byte_size("512M"), Some(512 << 20));
+        assert_eq!(parse_byte_size("2GiB"), Some(2 << 30));
+        assert_eq!(parse_byte_size("1 T"), Some(1 << 40));
+        assert_eq!(parse_byte_size("0"), None);
+        assert_eq!(parse_byte_size("12X"), None);
+        assert_eq!(parse_byte_size("M"), None);
+        assert_eq!(parse_byte_size("99999999999T"), None);
+    }
+
+    #[test]
+    fn test_run_limits_load() {
+        let args = CliArgs::parse_from_test_args(["--max-runtime", "90s", "--max-memory", "1G", "ripgrep"]);
+        let limits = RunLimits::load(&args).unwrap();
+        assert_eq!(limits.max_runtime, Some(Duration::from_secs(90)));
+        assert_eq!(limits.max_memory, Some(1 << 30));
+        assert!(!limits.is_unlimited());
+
+        let args = CliArgs::parse_from_test_args(["ripgrep"]);
+        assert!(RunLimits::load(&args).unwrap().is_unlimited());
+    }
+
+    #[test]
+    fn test_run_limits_load_invalid() {
+        let args = CliArgs::parse_from_test_args(["--max-runtime", "forever", "ripgrep"]);
+        assert_matches!(RunLimits::load(&args), Err(Error::InvalidMaxRuntime { .. }));
+
+        let args = CliArgs::parse_from_test_args(["--max-memory", "lots", "ripgrep"]);
+        assert_matches!(RunLimits::load(&args), Err(Error::InvalidMaxMemory { .. }));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_limits_returns_exit_code() {
+        let limits = RunLimits {
+            max_runtime: Some(Duration::from_secs(30)),
+            max_memory: Some(1 << 30),
+        };
+        let code = run_with_limits(
+            Path::new("/bin/sh"),
+            None,
+            &[],
+            &[],
+            &["-c".into(), "exit 3".into()],
+            &limits,
+            &MessageReporter::null(),
+        )
+        .unwrap();
+        assert_eq!(code, 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_limits_kills_on_timeout() {
+        let (tx, rx) = std::sync::mpsc::sync_channel(10);
+        let limits = RunLimits {
+            max_runtime: Some(Duration::from_millis(200)),
+            max_memory: None,
+        };
+
+        let started = Instant::now();
+        let code = run_with_limits(
+            Path::new("/bin/sh"),
+            None,
+            &[],
+            &[],
+            &["-c".into(), "sleep 30".into()],
+            &limits,
+            &MessageReporter::channel(tx),
+        )
+        .unwrap();
+
+        assert_eq!(code, RUNTIME_EXCEEDED_EXIT_CODE);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_matches!(
+            rx.try_recv().unwrap(),
+            Message::Runner(RunnerMessage::RuntimeLimitExceeded { max_runtime, .. })
+                if max_runtime == Duration::from_millis(200)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_limits_reports_death_under_memory_limit() {
+        let (tx, rx) = std::sync::mpsc::sync_channel(10);
+        let limits = RunLimits {
+            max_runtime: None,
+            max_memory: Some(1 << 30),
+        };
+
+        // What a tool does when an allocation fails under the limit
+        let code = run_with_limits(
+            Path::new("/bin/sh"),
+            None,
+            &[],
+            &[],
+            &["-c".into(), "kill -ABRT $$".into()],
+            &limits,
+            &MessageReporter::channel(tx),
+        )
+        .unwrap();
+
+        assert_eq!(code, 1);
+        assert_matches!(
+            rx.try_recv().unwrap(),
+            Message::Runner(RunnerMessage::MemoryLimitExceeded { max_memory, signal, .. })
+                if max_memory == 1 << 30 && signal == 6
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_limits_reports_nothing_for_a_normal_exit_or_no_memory_limit() {
+        let (tx, rx) = std::sync::mpsc::sync_channel(10);
+        let reporter = MessageReporter::channel(tx);
+
+        let code = run_with_limits(
+            Path::new("/bin/sh"),
+            None,
+            &[],
+            &[],
+            &["-c".into(), "exit 3".into()],
+            &RunLimits {
+                max_runtime: None,
+                max_memory: Some(1 << 30),
+            },
+            &reporter,
+        )
+        .unwrap();
+        assert_eq!(code, 3);
+
+        run_with_limits(
+            Path::new("/bin/sh"),
+            None,
+            &[],
+            &[],
+            &["-c".into(), "kill -ABRT $$".into()],
+            &RunLimits::default(),
+            &reporter,
+        )
+        .unwrap();
+
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_run_with_limits_sets_argv0() {
+        let limits = RunLimits {
+            max_runtime: Some(Duration::from_secs(30)),
+            max_memory: None,
+        };
+        let code = run_with_limits(
+            Path::new("/bin/sh"),
+            Some(OsStr::new("multicall")),
+            &[],
+            &[],
+            &[
+                "-c".into(),
+                r#"tr '\0' ' ' < /proc/$$/cmdline | grep -q '^multicall -c'"#.into(),
+            ],
+            &limits,
+            &MessageReporter::null(),
+        )
+        .unwrap();
+        assert_eq!(code, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_argv0_with_memory_limit_is_refused() {
+        let limits = RunLimits {
+            max_runtime: None,
+            max_memory: Some(1 << 30),
+        };
+        let result = run_with_limits(
+            Path::new("/bin/sh"),
+            Some(OsStr::new("multicall")),
+            &[],
+            &[],
+            &[],
+            &limits,
+            &MessageReporter::null(),
+        );
+        assert_matches!(result, Err(Error::Argv0WithMemoryLimit));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ulimit_memory_flag_per_os() {
+        assert_eq!(ulimit_memory_flag("linux"), "-v");
+        assert_eq!(ulimit_memory_flag("freebsd"), "-v");
+        assert_eq!(ulimit_memory_flag("netbsd"), "-v");
+        assert_eq!(ulimit_memory_flag("illumos"), "-v");
+        assert_eq!(ulimit_memory_flag("openbsd"), "-d");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_limits_sets_env() {
+        for max_memory in [None, Some(1 << 30)] {
+            let limits = RunLimits {
+                max_runtime: Some(Duration::from_secs(30)),
+                max_memory,
+            };
+            let code = run_with_limits(
+                Path::new("/bin/sh"),
+                None,
+                &[("CGX_TEST_ASSETS_DIR".into(), "/tmp/assets".into())],
+                &[],
+                &["-c".into(), r#"test "$CGX_TEST_ASSETS_DIR" = /tmp/assets"#.into()],
+                &limits,
+                &MessageReporter::null(),
+            )
+            .unwrap();
+            assert_eq!(code, 0);
+        }
+    }
+
+    #[test]
+    fn test_missing_features() {
+        let hints = HashMap::from([
+            ("--format".to_string(), vec!["json".to_string()]),
+            ("serve".to_string(), vec!["server".to_string(), "tls".to_string()]),
+        ]);
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        let options = |features: &[&str]| BuildOptions {
+            features: features.iter().copied().map(String::from).collect(),
+            ..BuildOptions::default()
+        };
+
+        let run_args = args(&["check", "--format=json"]);
+        assert_eq!(
+            missing_features(&hints, &run_args, &options(&[])),
+            Some(("--format", vec!["json".to_string()]))
+        );
+        assert_eq!(missing_features(&hints, &run_args, &options(&["json"])), None);
+
+        let run_args = args(&["serve", "--port", "8080"]);
+        assert_eq!(
+            missing_features(&hints, &run_args, &options(&["server"])),
+            Some(("serve", vec!["tls".to_string()]))
+        );
+        assert_eq!(
+            missing_features(
+                &hints,
+                &run_args,
+                &BuildOptions {
+                    all_features: true,
+                    ..options(&[])
+                }
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cargo_subcommand() {
+        assert_eq!(
+            cargo_subcommand(Path::new("/bin/cargo-nextest")).as_deref(),
+            Some("nextest")
+        );
+        assert_eq!(
+            cargo_subcommand(Path::new("cargo-deny.exe")).as_deref(),
+            Some("deny")
+        );
+        assert_eq!(cargo_subcommand(Path::new("/bin/cargo-")), None);
+        assert_eq!(cargo_subcommand(Path::new("/bin/rg")), None);
+
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(
+            cargo_subcommand_args("nextest", &args(&["run", "--all"])),
+            args(&["nextest", "run", "--all"])
+        );
+        assert_eq!(
+            cargo_subcommand_args("nextest", &args(&["nextest", "run"])),
+            args(&["nextest", "run"])
+        );
+        assert_eq!(cargo_subcommand_args("deny", &[]), args(&["deny"]));
+    }
+
+    #[test]
+    fn test_parse_wrapper() {
+        let words = |wrapper| {
+            parse_wrapper(wrapper)
+                .unwrap()
+                .into_iter()
+                .map(|word| word.into_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            words("valgrind --leak-check=full"),
+            ["valgrind", "--leak-check=full"]
+        );
+        assert_eq!(words("  time  "), ["time"]);
+        assert_eq!(words(""), Vec::<String>::new());
+        assert_eq!(
+            words(r#"hyperfine --prepare 'rm -f out' "--export-json=my runs.json""#),
+            [
+                "hyperfine",
+                "--prepare",
+                "rm -f out",
+                "--export-json=my runs.json"
+            ]
+        );
+        assert_eq!(
+            words(r#"strace -o trace\ log.txt "a\"b" 'c\d' """#),
+            ["strace", "-o", "trace log.txt", "a\"b", "c\\d", ""]
+        );
+    }
+
+    #[test]
+    fn test_parse_wrapper_invalid() {
+        for wrapper in ["valgrind 'unclosed", r#"strace "unclosed"#, "time \\"] {
+            assert_matches!(parse_wrapper(wrapper), Err(Error::InvalidWrapper { .. }));
+        }
+    }
+
+    #[test]
+    fn test_wasm_runtime() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let native = temp_dir.path().join("native");
+        let wasm = temp_dir.path().join("tool.wasm");
+        std::fs::write(&native, b"#!/bin/sh\n").unwrap();
+        std::fs::write(&wasm, b"\0asm\x01\0\0\0").unwrap();
+        let unreachable = || -> Result<PathBuf> { panic!("no runtime should have been acquired") };
+
+        let mut config = Config::default();
+        assert!(wasm_runtime(&config, &native, unreachable).unwrap().is_empty());
+
+        config.wasm_runtime = Some("wasmtime run --dir=/data".to_string());
+        assert_eq!(
+            wasm_runtime(&config, &wasm, unreachable).unwrap(),
+            ["wasmtime", "run", "--dir=/data"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_limits_under_wrapper() {
+        for max_memory in [None, Some(1 << 30)] {
+            let limits = RunLimits {
+                max_runtime: Some(Duration::from_secs(30)),
+                max_memory,
+            };
+
+            // The wrapper gets the tool's path and arguments after its own, and runs the tool
+            let code = run_with_limits(
+                Path::new("/bin/sh"),
+                None,
+                &[],
+                &["/bin/sh".into(), "-c".into(), r#"exec "$0" "$@""#.into()],
+                &["-c".into(), "exit 5".into()],
+                &limits,
+                &MessageReporter::null(),
+            )
+            .unwrap();
+            assert_eq!(code, 5);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_argv0_with_wrapper_is_refused() {
+        let result = run_with_limits(
+            Path::new("/bin/sh"),
+            Some(OsStr::new("multicall")),
+            &[],
+            &["time".into()],
+            &[],
+            &RunLimits::default(),
+            &MessageReporter::null(),
+        );
+        assert_matches!(result, Err(Error::Argv0WithWrapper));
     }
 }
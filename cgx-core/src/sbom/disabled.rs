@@ -0,0 +1,13 @@
+//! The stand-in for generating SBOMs without the `sbom` feature.
+
+use super::BuildSbom;
+use crate::{Result, builder::BuildOptions, crate_resolver::ResolvedCrate};
+
+/// Without the `sbom` feature, builds have no SBOM.
+pub(crate) fn for_build(
+    _metadata: &cargo_metadata::Metadata,
+    _resolved: &ResolvedCrate,
+    _options: &BuildOptions,
+) -> Result<Option<BuildSbom>> {
+    Ok(None)
+}
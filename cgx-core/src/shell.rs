@@ -0,0 +1,177 @@
+//! Subshells with a project's tools on `PATH`, for `cgx --shell`.
+//!
+//! The binary of every tool in the `[tools]` table is got as usual, and linked into a directory
+//! of its own under [`Config::build_dir`].  The user's shell is then started with that directory
+//! at the front of `PATH`, so that the tools can be run by name, in the versions the config calls
+//! for, without being installed anywhere.  [`SHELL_ENV_VAR`] is set in the shell too, to the
+//! directory, so that prompts and scripts can tell they're running in one.
+//!
+//! The directory is removed when the [`ShellDir`] is dropped, which cgx does once the shell has
+//! exited.  Directories left behind by a cgx that was killed first are removed by a later
+//! `--shell`, as with `--ephemeral`.
+
+use crate::{
+    Result,
+    config::Config,
+    ephemeral::{LEFTOVER_AGE, remove_leftovers},
+    error,
+    helpers::long_path,
+};
+use snafu::{OptionExt, ResultExt};
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The variable set in the shell started by `--shell`, to the directory of the tools' binaries.
+pub const SHELL_ENV_VAR: &str = "CGX_SHELL";
+
+/// The prefix of the name of every shell's directory, by which leftovers are recognized.
+const SHELL_DIR_PREFIX: &str = "cgx-shell-";
+
+/// The directory of the binaries put on `PATH` for a single shell, which is removed along with
+/// everything in it when this is dropped.
+#[derive(Debug)]
+pub struct ShellDir {
+    dir: tempfile::TempDir,
+}
+
+impl ShellDir {
+    /// Create a directory for a shell, with a link in it named for each binary in `binaries` to
+    /// the binary at its path.
+    ///
+    /// Only the first of several binaries with the same name is linked, since only one of them
+    /// could be found on `PATH` anyway; the rest are logged and left out.
+    pub fn create(config: &Config, binaries: &[(OsString, PathBuf)]) -> Result<Self> {
+        let parent = config.build_dir.clone();
+        fs::create_dir_all(long_path(&parent)).with_context(|_| error::IoSnafu { path: &parent })?;
+        remove_leftovers(&parent, SHELL_DIR_PREFIX, LEFTOVER_AGE);
+
+        let dir = tempfile::Builder::new()
+            .prefix(SHELL_DIR_PREFIX)
+            .tempdir_in(&parent)
+            .with_context(|_| error::TempDirCreationSnafu { parent: &parent })?;
+        tracing::debug!(dir = %dir.path().display(), "Created shell directory");
+
+        let mut linked: BTreeMap<OsString, &Path> = BTreeMap::new();
+        for (name, bin_path) in binaries {
+            if let Some(first) = linked.get(name) {
+                tracing::warn!(
+                    "Not putting {} on PATH as {}, since {} already is",
+                    bin_path.display(),
+                    name.to_string_lossy(),
+                    first.display()
+                );
+                continue;
+            }
+
+            link_binary(bin_path, &dir.path().join(name))?;
+            linked.insert(name.clone(), bin_path.as_path());
+        }
+
+        Ok(Self { dir })
+    }
+
+    /// The path of the directory.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// The variables to add to the shell's environment: `PATH`, with this directory in front of
+    /// the `PATH` cgx was run with, and [`SHELL_ENV_VAR`].
+    pub fn env(&self) -> Result<Vec<(OsString, OsString)>> {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        Ok(vec![
+            (OsString::from("PATH"), prepend_path(self.path(), &path)?),
+            (OsString::from(SHELL_ENV_VAR), self.path().as_os_str().to_owned()),
+        ])
+    }
+}
+
+/// The shell to start: the user's login shell from `SHELL` on Unix, or the command interpreter
+/// from `COMSPEC` on Windows, falling back to the system's default if that isn't set.
+pub fn program() -> PathBuf {
+    let (var, default) = if cfg!(windows) {
+        ("COMSPEC", "cmd.exe")
+    } else {
+        ("SHELL", "/bin/sh")
+    };
+
+    std::env::var_os(var)
+        .filter(|program| !program.is_empty())
+        .map_or_else(|| PathBuf::from(default), PathBuf::from)
+}
+
+/// `path` with `dir` in front of the directories already in it.
+fn prepend_path(dir: &Path, path: &OsStr) -> Result<OsString> {
+    let dirs = std::iter::once(dir.to_path_buf()).chain(std::env::split_paths(path));
+    // The only way joining can fail is a separator in `dir`, which the error is about
+    std::env::join_paths(dirs)
+        .ok()
+        .context(error::InvalidShellDirSnafu { path: dir })
+}
+
+/// Put the binary at `bin_path` at `link`.
+///
+/// On Unix this is a symlink.  Windows only lets privileged users make those, so there the binary
+/// is hard linked instead, or copied if it's on another volume.
+fn link_binary(bin_path: &Path, link: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(bin_path, link).with_context(|_| error::IoSnafu { path: link })
+    }
+
+    #[cfg(not(unix))]
+    {
+        match fs::hard_link(long_path(bin_path), long_path(link)) {
+            Ok(()) => Ok(()),
+            Err(_) => crate::helpers::install_executable(bin_path, link),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_dir_links_binaries_and_is_removed_on_drop() {
+        let (temp_dir, config) = crate::config::create_test_env();
+        let first = temp_dir.path().join("first").join("taplo");
+        let second = temp_dir.path().join("second").join("taplo");
+        for bin_path in [&first, &second] {
+            fs::create_dir_all(bin_path.parent().unwrap()).unwrap();
+            fs::write(bin_path, "binary").unwrap();
+        }
+
+        let shell_dir = ShellDir::create(
+            &config,
+            &[
+                (OsString::from("taplo"), first.clone()),
+                (OsString::from("taplo"), second),
+                (OsString::from("toml"), first),
+            ],
+        )
+        .unwrap();
+        let path = shell_dir.path().to_path_buf();
+        assert!(path.starts_with(&config.build_dir));
+
+        let mut names = fs::read_dir(&path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["taplo", "toml"]);
+        assert_eq!(fs::read_to_string(path.join("taplo")).unwrap(), "binary");
+
+        let env = shell_dir.env().unwrap();
+        let path_var = &env.iter().find(|(key, _)| key == "PATH").unwrap().1;
+        assert_eq!(std::env::split_paths(path_var).next().unwrap(), path);
+        assert!(env.contains(&(OsString::from(SHELL_ENV_VAR), path.clone().into_os_string())));
+
+        drop(shell_dir);
+        assert!(!path.exists());
+    }
+}
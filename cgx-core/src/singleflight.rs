@@ -0,0 +1,197 @@
+//! Coalescing identical work that's asked for at the same time within one process, which
+//! [`crate::cache::Cache`] and [`crate::http::HttpClient`] put in front of resolving, downloading
+//! and building.
+//!
+//! When several threads of an embedding, or the parallel parts of cgx itself, want the same crate
+//! or asset at once, each would otherwise miss the cache, and then download or build the same
+//! thing side by side, only for all but one of the results to be thrown away.  With a
+//! [`SingleFlight`] in front, the first caller for a key does the work, and any others that ask
+//! for the same key meanwhile wait for it and get a copy of its result.
+//!
+//! Errors generally can't be copied, so if the first caller fails, each waiting caller does the
+//! work itself instead, the same as it would have without this.  A caller that's waiting still
+//! notices being cancelled (see [`crate::cancel`]).
+
+use crate::Result;
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+/// How often a caller waiting for another's result checks whether it's been cancelled.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Work in progress, keyed by what it's for.
+///
+/// It's safe to share between threads.  The work for one key must not ask for the same key of the
+/// same [`SingleFlight`] again, or it waits on itself forever.
+#[derive(Debug)]
+pub(crate) struct SingleFlight<K, V> {
+    calls: Mutex<HashMap<K, Arc<Call<V>>>>,
+}
+
+/// One piece of work in progress, which callers for the same key wait on.
+#[derive(Debug)]
+struct Call<V> {
+    outcome: Mutex<Outcome<V>>,
+    done: Condvar,
+}
+
+/// How the work of a [`Call`] went.
+#[derive(Debug)]
+enum Outcome<V> {
+    Pending,
+    Done(V),
+    Failed,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> SingleFlight<K, V> {
+    pub(crate) fn new() -> Self {
+        Self {
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Do `work` for `key`, unless it's already being done, in which case wait for that and return
+    /// a copy of its result.
+    pub(crate) fn run(&self, key: K, work: impl FnOnce() -> Result<V>) -> Result<V> {
+        let (call, leader) = {
+            let mut calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(call) = calls.get(&key) {
+                (call.clone(), false)
+            } else {
+                let call = Arc::new(Call {
+                    outcome: Mutex::new(Outcome::Pending),
+                    done: Condvar::new(),
+                });
+                calls.insert(key.clone(), call.clone());
+                (call, true)
+            }
+        };
+
+        if !leader {
+            return match Self::wait(&call)? {
+                Some(value) => {
+                    tracing::debug!("Reusing the result of identical work done at the same time");
+                    Ok(value)
+                }
+                None => work(),
+            };
+        }
+
+        // Whatever happens to the work, even a panic, the key is freed and the waiters woken
+        let mut finish = Finish {
+            flight: self,
+            key: Some(key),
+            call: &call,
+            value: None,
+        };
+        let result = work();
+        finish.value = result.as_ref().ok().cloned();
+        drop(finish);
+
+        result
+    }
+
+    /// Wait for the work of `call` to be over, and return its result, or `None` if it failed.
+    fn wait(call: &Call<V>) -> Result<Option<V>> {
+        let mut outcome = call.outcome.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            match &*outcome {
+                Outcome::Pending => {}
+                Outcome::Done(value) => return Ok(Some(value.clone())),
+                Outcome::Failed => return Ok(None),
+            }
+
+            crate::cancel::check()?;
+            outcome = call
+                .done
+                .wait_timeout(outcome, CANCEL_CHECK_INTERVAL)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+/// Ends the work for a key when dropped: stores its result for the waiters, wakes them, and
+/// frees the key for whoever asks for it next.
+struct Finish<'a, K: Eq + Hash, V> {
+    flight: &'a SingleFlight<K, V>,
+    key: Option<K>,
+    call: &'a Call<V>,
+    value: Option<V>,
+}
+
+impl<K: Eq + Hash, V> Drop for Finish<'_, K, V> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let mut calls = self.flight.calls.lock().unwrap_or_else(|e| e.into_inner());
+            calls.remove(&key);
+        }
+
+        let mut outcome = self.call.outcome.lock().unwrap_or_else(|e| e.into_inner());
+        *outcome = self.value.take().map_or(Outcome::Failed, Outcome::Done);
+        self.call.done.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error;
+    use std::sync::{
+        Barrier,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    #[test]
+    fn concurrent_callers_share_one_result() {
+        let flight = SingleFlight::new();
+        let runs = AtomicUsize::new(0);
+        let started = Barrier::new(4);
+
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        started.wait();
+                        flight.run("crate", || {
+                            runs.fetch_add(1, Ordering::SeqCst);
+                            std::thread::sleep(Duration::from_millis(200));
+                            Ok(42)
+                        })
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        assert!(results.iter().all(|result| matches!(result, Ok(42))));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // Once it's over, the work is done again for the next caller
+        assert_eq!(flight.run("crate", || Ok(43)).unwrap(), 43);
+    }
+
+    #[test]
+    fn waiters_do_the_work_themselves_if_it_failed() {
+        let flight = SingleFlight::new();
+        let leading = Barrier::new(2);
+
+        std::thread::scope(|scope| {
+            let leader = scope.spawn(|| {
+                flight.run("crate", || {
+                    leading.wait();
+                    std::thread::sleep(Duration::from_millis(200));
+                    error::MissingCrateParameterSnafu.fail()
+                })
+            });
+
+            leading.wait();
+            assert_eq!(flight.run("crate", || Ok(1)).unwrap(), 1);
+            assert!(leader.join().unwrap().is_err());
+        });
+    }
+}
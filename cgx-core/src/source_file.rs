@@ -0,0 +1,164 @@
+//! Running a file from a crate's source, for `cgx --run-source-file <PATH>`.
+//!
+//! Some crates ship helper scripts meant to be run from their source tree, alongside or instead
+//! of their binary.  The crate is resolved and downloaded as for any other run, and the file is
+//! run where it is in the source cache, with [`SOURCE_DIR_ENV`] pointing at the root of the
+//! source so that the script can find whatever else it needs there.
+//!
+//! A file is run by an interpreter picked by its extension: one configured under
+//! `[script_interpreters]`, or else one of [`DEFAULT_INTERPRETERS`].  A file with any other
+//! extension, or none, is run directly, which on Unix relies on it being executable.
+
+use crate::{Result, config::Config, error, runner};
+use snafu::ResultExt;
+use std::{
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
+};
+
+/// The environment variable set to the root of the crate's source for the file that's run.
+pub const SOURCE_DIR_ENV: &str = "CRATE_SOURCE_DIR";
+
+/// The interpreters for common kinds of script, keyed by file extension.
+pub const DEFAULT_INTERPRETERS: &[(&str, &str)] = &[
+    ("sh", "sh"),
+    ("bash", "bash"),
+    ("py", "python3"),
+    ("pl", "perl"),
+    ("rb", "ruby"),
+    ("js", "node"),
+    ("ps1", "pwsh -NoProfile -File"),
+];
+
+/// The file `path` in the crate's source in `source_dir`.
+///
+/// `path` has to be relative, and stay within the source, symlinks included, since the source
+/// is whatever the crate's authors published and it's only the crate's own files that are meant
+/// to be run.
+pub fn locate(source_dir: &Path, path: &Path) -> Result<PathBuf> {
+    let invalid = |message: &str| error::InvalidSourceFilePathSnafu { path, message }.fail();
+
+    if path.as_os_str().is_empty() {
+        return invalid("it's empty");
+    }
+    if path.components().any(|component| {
+        matches!(
+            component,
+            Component::Prefix(_) | Component::RootDir | Component::ParentDir
+        )
+    }) {
+        return invalid("it must be relative, and not use `..`");
+    }
+
+    let file = source_dir.join(path);
+    if !file.is_file() {
+        return error::SourceFileNotFoundSnafu { path, source_dir }.fail();
+    }
+
+    let canonical_file = file.canonicalize().context(error::IoSnafu { path: &file })?;
+    let canonical_source_dir = source_dir
+        .canonicalize()
+        .context(error::IoSnafu { path: source_dir })?;
+    if !canonical_file.starts_with(&canonical_source_dir) {
+        return invalid("it's a symlink to a file outside of the source");
+    }
+
+    Ok(file)
+}
+
+/// The interpreter to run `file` with, split into the program and its arguments, or an empty
+/// list if `file` is to be run directly.
+pub fn interpreter(config: &Config, file: &Path) -> Result<Vec<OsString>> {
+    let Some(extension) = file.extension().and_then(|extension| extension.to_str()) else {
+        return Ok(Vec::new());
+    };
+
+    let configured = config.script_interpreters.get(extension).map(String::as_str);
+    let default = || {
+        DEFAULT_INTERPRETERS
+            .iter()
+            .find(|(default_extension, _)| default_extension.eq_ignore_ascii_case(extension))
+            .map(|(_, interpreter)| *interpreter)
+    };
+
+    match configured.or_else(default) {
+        Some(interpreter) => runner::parse_wrapper(interpreter),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The environment variables to set for a file run from the crate's source in `source_dir`.
+pub fn env(source_dir: &Path) -> Vec<(OsString, OsString)> {
+    vec![(
+        OsString::from(SOURCE_DIR_ENV),
+        source_dir.as_os_str().to_os_string(),
+    )]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn locates_files_within_the_source_only() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("tool-1.0.0");
+        std::fs::create_dir_all(source_dir.join("scripts")).unwrap();
+        std::fs::write(source_dir.join("scripts/setup.sh"), "echo hi\n").unwrap();
+        std::fs::write(temp_dir.path().join("secret.sh"), "echo secret\n").unwrap();
+
+        assert_eq!(
+            locate(&source_dir, Path::new("scripts/setup.sh")).unwrap(),
+            source_dir.join("scripts/setup.sh")
+        );
+        assert_matches!(
+            locate(&source_dir, Path::new("scripts/missing.sh")),
+            Err(error::Error::SourceFileNotFound { .. })
+        );
+        assert_matches!(
+            locate(&source_dir, Path::new("scripts")),
+            Err(error::Error::SourceFileNotFound { .. })
+        );
+        assert_matches!(
+            locate(&source_dir, Path::new("../secret.sh")),
+            Err(error::Error::InvalidSourceFilePath { .. })
+        );
+        assert_matches!(
+            locate(&source_dir, &temp_dir.path().join("secret.sh")),
+            Err(error::Error::InvalidSourceFilePath { .. })
+        );
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(temp_dir.path().join("secret.sh"), source_dir.join("link.sh"))
+                .unwrap();
+            assert_matches!(
+                locate(&source_dir, Path::new("link.sh")),
+                Err(error::Error::InvalidSourceFilePath { .. })
+            );
+        }
+    }
+
+    #[test]
+    fn configured_interpreters_take_precedence() {
+        let mut config = Config::default();
+        config
+            .script_interpreters
+            .insert("py".to_string(), "uv run --script".to_string());
+        config.script_interpreters.insert("sh".to_string(), String::new());
+
+        assert_eq!(
+            interpreter(&config, Path::new("scripts/gen.py")).unwrap(),
+            ["uv", "run", "--script"]
+        );
+        assert!(
+            interpreter(&config, Path::new("scripts/setup.sh"))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(interpreter(&config, Path::new("release.rb")).unwrap(), ["ruby"]);
+        assert_eq!(interpreter(&config, Path::new("BUILD.PL")).unwrap(), ["perl"]);
+        assert!(interpreter(&config, Path::new("scripts/run")).unwrap().is_empty());
+    }
+}
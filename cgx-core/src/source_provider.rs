@@ -0,0 +1,276 @@
+//! Crate sources beyond the registries, git repositories, forges and local directories cgx knows
+//! itself, such as an organization's internal artifact API or snapshots of a monorepo.
+//!
+//! A [`SourceProvider`] serves the crate specs that are URLs of its scheme, like
+//! `mysrc://team/tool`, given as the crate on the command line or as what an alias stands for.
+//! Providers are registered with [`crate::CgxBuilder::source_provider`]; a URL of a scheme that no
+//! provider is registered for fails with [`Error::UnknownSourceScheme`].
+//!
+//! A provider resolves a URL to an exact crate, along with a revision that pins down exactly what
+//! its source is, the way a commit does for git, and then puts that source in a directory for cgx
+//! to build from.  The source is cached by URL and revision like any other, so a provider is only
+//! asked for it once, and the binary built from it is cached the same way too.
+//!
+//! [`Error::UnknownSourceScheme`]: crate::error::Error::UnknownSourceScheme
+
+use crate::{
+    Result,
+    crate_resolver::{ResolvedCrate, ResolvedSource},
+    error,
+};
+use semver::{Version, VersionReq};
+use std::{collections::HashMap, fmt, path::Path, sync::Arc};
+use url::Url;
+
+/// The URL schemes of sources cgx handles itself, so that a crate spec that's a URL of one of them
+/// is never taken to be for a provider.
+const BUILTIN_SCHEMES: &[&str] = &["http", "https", "git", "ssh", "file", "sparse", "registry"];
+
+/// An error from a [`SourceProvider`], which cgx reports along with the URL it was for.
+pub type ProviderError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A crate as a [`SourceProvider`] resolved it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProvidedCrate {
+    /// The exact name of the crate.
+    pub name: String,
+
+    /// The exact version of the crate.
+    pub version: Version,
+
+    /// What identifies exactly this source of the crate, such as a snapshot ID or a digest, which
+    /// the source and the binary built from it are cached by.
+    pub revision: String,
+}
+
+/// A source of crates reached through URLs of a scheme of its own.
+pub trait SourceProvider: fmt::Debug + Send + Sync + 'static {
+    /// The URL scheme the provider serves, without the `://`, like `mysrc`.
+    fn scheme(&self) -> &str;
+
+    /// Resolve the crate at `url` to an exact version, the newest that satisfies `version` if
+    /// there's a choice of them.
+    fn resolve(
+        &self,
+        url: &Url,
+        version: Option<&VersionReq>,
+    ) -> std::result::Result<ProvidedCrate, ProviderError>;
+
+    /// Put the source of `krate`, as resolved from `url`, into `dest_dir`, with its `Cargo.toml`
+    /// at the top.
+    fn download(
+        &self,
+        url: &Url,
+        krate: &ProvidedCrate,
+        dest_dir: &Path,
+    ) -> std::result::Result<(), ProviderError>;
+}
+
+/// The registered [`SourceProvider`]s, by scheme.
+#[derive(Clone, Debug, Default)]
+pub struct SourceProviders {
+    providers: HashMap<String, Arc<dyn SourceProvider>>,
+}
+
+impl SourceProviders {
+    /// Register `provider` for its scheme, in place of any provider already registered for it.
+    pub fn register(&mut self, provider: Arc<dyn SourceProvider>) {
+        self.providers
+            .insert(provider.scheme().to_ascii_lowercase(), provider);
+    }
+
+    /// Resolve the crate at `url` with the provider for its scheme, checking that the version it
+    /// resolved to satisfies `version`.
+    pub(crate) fn resolve(&self, url: &Url, version: Option<&VersionReq>) -> Result<ResolvedCrate> {
+        let provided = self
+            .provider(url)?
+            .resolve(url, version)
+            .map_err(|source| provider_failed(url, source))?;
+
+        if let Some(req) = version {
+            if !req.matches(&provided.version) {
+                return error::VersionMismatchSnafu {
+                    requirement: req.to_string(),
+                    found: provided.version,
+                }
+                .fail();
+            }
+        }
+
+        Ok(ResolvedCrate {
+            name: provided.name,
+            version: provided.version,
+            source: ResolvedSource::Custom {
+                url: url.clone(),
+                revision: provided.revision,
+            },
+        })
+    }
+
+    /// Put the source of `krate`, resolved from `url` at `revision`, into `dest_dir` with the
+    /// provider for the URL's scheme.
+    pub(crate) fn download(
+        &self,
+        url: &Url,
+        revision: &str,
+        krate: &ResolvedCrate,
+        dest_dir: &Path,
+    ) -> Result<()> {
+        let provided = ProvidedCrate {
+            name: krate.name.clone(),
+            version: krate.version.clone(),
+            revision: revision.to_string(),
+        };
+
+        self.provider(url)?
+            .download(url, &provided, dest_dir)
+            .map_err(|source| provider_failed(url, source))
+    }
+
+    fn provider(&self, url: &Url) -> Result<&Arc<dyn SourceProvider>> {
+        self.providers.get(url.scheme()).ok_or_else(|| {
+            error::UnknownSourceSchemeSnafu {
+                scheme: url.scheme(),
+                url: url.to_string(),
+            }
+            .build()
+        })
+    }
+}
+
+fn provider_failed(url: &Url, source: ProviderError) -> error::Error {
+    error::Error::SourceProviderFailed {
+        url: url.to_string(),
+        source,
+    }
+}
+
+/// The URL `spec` is, if it's a crate spec for a source provider: a URL of a scheme cgx doesn't
+/// handle itself.
+pub(crate) fn provider_url(spec: &str) -> Option<Url> {
+    let (scheme, rest) = spec.split_once("://")?;
+    if rest.is_empty() || BUILTIN_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) {
+        return None;
+    }
+
+    Url::parse(spec).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    /// A provider with a single crate, at `test://tools/hello`.
+    #[derive(Debug)]
+    struct TestProvider;
+
+    impl SourceProvider for TestProvider {
+        fn scheme(&self) -> &str {
+            "test"
+        }
+
+        fn resolve(
+            &self,
+            url: &Url,
+            _version: Option<&VersionReq>,
+        ) -> std::result::Result<ProvidedCrate, ProviderError> {
+            if url.path() != "/hello" {
+                return Err(format!("nothing at {}", url.path()).into());
+            }
+
+            Ok(ProvidedCrate {
+                name: "hello".to_string(),
+                version: Version::new(1, 2, 3),
+                revision: "snapshot-42".to_string(),
+            })
+        }
+
+        fn download(
+            &self,
+            _url: &Url,
+            krate: &ProvidedCrate,
+            dest_dir: &Path,
+        ) -> std::result::Result<(), ProviderError> {
+            std::fs::create_dir_all(dest_dir)?;
+            std::fs::write(
+                dest_dir.join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"{}\"\n",
+                    krate.name, krate.version
+                ),
+            )?;
+            Ok(())
+        }
+    }
+
+    fn providers() -> SourceProviders {
+        let mut providers = SourceProviders::default();
+        providers.register(Arc::new(TestProvider));
+        providers
+    }
+
+    #[test]
+    fn only_urls_of_unknown_schemes_are_for_providers() {
+        assert_eq!(
+            provider_url("test://tools/hello").map(String::from),
+            Some("test://tools/hello".to_string())
+        );
+        for spec in [
+            "ripgrep",
+            "https://github.com/o/r",
+            "git://example.com/r",
+            "test://",
+        ] {
+            assert_eq!(provider_url(spec), None, "{spec}");
+        }
+    }
+
+    #[test]
+    fn crates_are_resolved_and_downloaded_by_their_scheme_provider() {
+        let providers = providers();
+        let url = Url::parse("test://tools/hello").unwrap();
+
+        let resolved = providers
+            .resolve(&url, Some(&VersionReq::parse("1.2").unwrap()))
+            .unwrap();
+        assert_eq!(
+            resolved,
+            ResolvedCrate {
+                name: "hello".to_string(),
+                version: Version::new(1, 2, 3),
+                source: ResolvedSource::Custom {
+                    url: url.clone(),
+                    revision: "snapshot-42".to_string(),
+                },
+            }
+        );
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        providers
+            .download(&url, "snapshot-42", &resolved, temp_dir.path())
+            .unwrap();
+        assert!(temp_dir.path().join("Cargo.toml").is_file());
+    }
+
+    #[test]
+    fn failures_name_the_url() {
+        let providers = providers();
+
+        assert_matches!(
+            providers.resolve(
+                &Url::parse("test://tools/hello").unwrap(),
+                Some(&VersionReq::parse("2").unwrap())
+            ),
+            Err(error::Error::VersionMismatch { .. })
+        );
+        assert_matches!(
+            providers.resolve(&Url::parse("test://tools/missing").unwrap(), None),
+            Err(error::Error::SourceProviderFailed { url, .. }) if url == "test://tools/missing"
+        );
+        assert_matches!(
+            providers.resolve(&Url::parse("other://tools/hello").unwrap(), None),
+            Err(error::Error::UnknownSourceScheme { scheme, .. }) if scheme == "other"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Crates on crates.io that have been renamed or replaced by another, so that running one can
+//! point at its successor, or run the successor in its place with `follow_successors`.
+//!
+//! The registry index has no crate descriptions or deprecation notices to go by, so which crates
+//! have moved on comes from a curated table, [`CURATED`], that the `[successors]` config table
+//! adds to or overrides.  An empty successor there means the crate has none, for a crate the table
+//! is wrong about, or one that is still wanted as it is.
+//!
+//! A crate with a successor gets a note saying so whenever it's resolved.  One whose every
+//! matching version is yanked, which is what a crate that has moved on often leaves behind, fails
+//! with [`Error::CrateSuperseded`] naming the successor rather than with a bare "no matching
+//! version".
+//!
+//! [`Error::CrateSuperseded`]: crate::error::Error::CrateSuperseded
+
+use crate::{config::Config, cratespec::CrateSpec};
+
+/// Crates that have been renamed or replaced, and the crate that took their place.
+const CURATED: &[(&str, &str)] = &[
+    ("cargo-watch", "bacon"),
+    ("failure", "anyhow"),
+    ("rustc-serialize", "serde"),
+    ("structopt", "clap"),
+    ("tokio-core", "tokio"),
+];
+
+/// The crate that took the place of the crates.io crate `name`, if it has been renamed or
+/// replaced.
+pub fn successor<'a>(config: &'a Config, name: &str) -> Option<&'a str> {
+    match config.successors.get(name) {
+        Some(successor) => Some(successor.as_str()).filter(|successor| !successor.is_empty()),
+        None => CURATED
+            .iter()
+            .find(|(crate_name, _)| *crate_name == name)
+            .map(|(_, successor)| *successor),
+    }
+}
+
+/// The spec to resolve in place of `spec`, if `follow_successors` is on and `spec` is for a
+/// crates.io crate with a successor.
+///
+/// The version requirement, if any, is dropped, since it was for the versions of the crate that
+/// was replaced.
+pub(crate) fn follow(config: &Config, spec: &CrateSpec) -> Option<CrateSpec> {
+    if !config.follow_successors {
+        return None;
+    }
+
+    let CrateSpec::CratesIo { name, .. } = spec else {
+        return None;
+    };
+
+    successor(config, name).map(|successor| CrateSpec::CratesIo {
+        name: successor.to_string(),
+        version: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_overrides_the_curated_table() {
+        let mut config = Config::default();
+        assert_eq!(successor(&config, "structopt"), Some("clap"));
+        assert_eq!(successor(&config, "ripgrep"), None);
+
+        config.successors.insert("structopt".to_string(), String::new());
+        config
+            .successors
+            .insert("old-tool".to_string(), "new-tool".to_string());
+        assert_eq!(successor(&config, "structopt"), None);
+        assert_eq!(successor(&config, "old-tool"), Some("new-tool"));
+    }
+
+    #[test]
+    fn successors_are_only_followed_when_asked_to() {
+        let spec = CrateSpec::CratesIo {
+            name: "structopt".to_string(),
+            version: Some("0.3".parse().unwrap()),
+        };
+        let mut config = Config::default();
+        assert_eq!(follow(&config, &spec), None);
+
+        config.follow_successors = true;
+        assert_eq!(
+            follow(&config, &spec),
+            Some(CrateSpec::CratesIo {
+                name: "clap".to_string(),
+                version: None,
+            })
+        );
+    }
+}
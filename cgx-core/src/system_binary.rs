@@ -0,0 +1,237 @@
+//! Reuse of a tool's binary that is already installed on the system, rather than getting one of
+//! cgx's own.
+//!
+//! With [`Config::prefer_system_binaries`] set, a crate from a registry whose version requirement
+//! is satisfied by a binary the user already has is run from there, skipping resolution,
+//! download, and build entirely.  Two places are looked in:
+//!
+//! - The binaries `cargo install` recorded in `$CARGO_HOME/.crates2.json`, which says which
+//!   binaries each installed crate has.
+//! - An executable on `PATH` named after one of the crate's binary targets, which is how most
+//!   tools installed by other means (such as Homebrew) can be found.  The names of a crate's
+//!   binary targets aren't known without its source, so the ones used are those that are known
+//!   some other way: the `--bin` asked for, the binaries `cargo install` recorded for the crate,
+//!   and the binaries of the crate cgx has built before.  The crate's name alone is never looked
+//!   for, since plenty of crates (such as `ripgrep`, whose binary is `rg`) have no binary of that
+//!   name, and whatever is on `PATH` by that name is then some other tool.
+//!
+//! Either way the version the binary actually reports with `--version` is what is checked against
+//! the requirement, since a record of what was installed doesn't prove it's still what's there.
+
+use crate::{
+    config::Config,
+    cratespec::CrateSpec,
+    messages::{MessageReporter, SystemBinaryMessage},
+};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use strum::Display;
+use tracing::*;
+
+/// Where a system binary was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SystemBinarySource {
+    /// Installed with `cargo install`.
+    CargoInstall,
+    /// Found on `PATH`.
+    Path,
+}
+
+/// The parts of `.crates2.json` that say which binaries `cargo install` installed.
+#[derive(Debug, Default, Deserialize)]
+struct CargoInstalls {
+    #[serde(default)]
+    installs: HashMap<String, CargoInstall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoInstall {
+    #[serde(default)]
+    bins: Vec<String>,
+}
+
+/// Find an installed binary of the crate `spec` names that satisfies its version requirement.
+///
+/// `known_bins` are the names of the crate's binary targets that are known without its source,
+/// such as those of earlier builds of it, which are looked for on `PATH` along with any that
+/// `cargo install` recorded.
+///
+/// Only crates from registries are considered, since the version of a crate from git or a local
+/// directory isn't something a binary can be checked against.
+pub(crate) fn find(
+    config: &Config,
+    spec: &CrateSpec,
+    known_bins: &[String],
+    reporter: &MessageReporter,
+) -> Option<PathBuf> {
+    let (name, version_req) = match spec {
+        CrateSpec::CratesIo { name, version } | CrateSpec::Registry { name, version, .. } => {
+            (name.as_str(), version.clone().unwrap_or(VersionReq::STAR))
+        }
+        _ => return None,
+    };
+
+    // A tool whose binary is pinned to a digest, or that needs assets staged from its source,
+    // needs cgx's own copy
+    if config.tool_prebuilt_sha256(name).is_some() || config.tool_assets(name).is_some() {
+        debug!(
+            "Not looking for a system binary of {}, as its config needs cgx's own",
+            name
+        );
+        return None;
+    }
+
+    let cargo_installed = cargo_home().and_then(|cargo_home| cargo_installed_bin(&cargo_home, name));
+    let mut bin_names: Vec<&str> = Vec::new();
+    for bin_name in cargo_installed
+        .iter()
+        .filter_map(|path| path.file_name().and_then(|name| name.to_str()))
+        .chain(known_bins.iter().map(String::as_str))
+    {
+        if !bin_names.contains(&bin_name) {
+            bin_names.push(bin_name);
+        }
+    }
+    if bin_names.is_empty() {
+        debug!(
+            "None of the binaries of {} are known, so not looking on PATH",
+            name
+        );
+    }
+
+    let mut candidates: Vec<(PathBuf, SystemBinarySource)> = cargo_installed
+        .clone()
+        .into_iter()
+        .map(|path| (path, SystemBinarySource::CargoInstall))
+        .collect();
+    for bin_name in bin_names {
+        if let Ok(path) = which::which(bin_name) {
+            if !candidates.iter().any(|(candidate, _)| *candidate == path) {
+                candidates.push((path, SystemBinarySource::Path));
+            }
+        }
+    }
+
+    for (path, source) in candidates {
+        let Some(version) = reported_version(&path) else {
+            reporter.report(|| SystemBinaryMessage::rejected(name, &path, source, None));
+            continue;
+        };
+
+        if version_req.matches(&version) {
+            reporter.report(|| SystemBinaryMessage::reused(name, &path, source, &version));
+            return Some(path);
+        }
+        reporter.report(|| SystemBinaryMessage::rejected(name, &path, source, Some(&version)));
+    }
+
+    None
+}
+
+/// `$CARGO_HOME`, which defaults to `~/.cargo`.
+fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home::cargo_home().ok())
+}
+
+/// The binary `cargo install` installed for the crate `name` from a registry, according to
+/// `.crates2.json` in `cargo_home`.
+///
+/// Only a crate with exactly one binary is of any use, since which of several is the tool isn't
+/// known without its source.
+fn cargo_installed_bin(cargo_home: &Path, name: &str) -> Option<PathBuf> {
+    let contents = std::fs::read_to_string(cargo_home.join(".crates2.json")).ok()?;
+    let installs: CargoInstalls = serde_json::from_str(&contents)
+        .inspect_err(|e| debug!("Failed to parse .crates2.json in {}: {}", cargo_home.display(), e))
+        .ok()?;
+
+    // Keys are the package ID, e.g. `ripgrep 14.1.0 (registry+https://github.com/...)`
+    let install = installs.installs.iter().find_map(|(package_id, install)| {
+        let mut parts = package_id.splitn(3, ' ');
+        let is_match = parts.next() == Some(name)
+            && parts.next().is_some()
+            && parts
+                .next()
+                .is_some_and(|source| source.starts_with("(registry+") || source.starts_with("(sparse+"));
+        is_match.then_some(install)
+    })?;
+
+    match install.bins.as_slice() {
+        [bin] => Some(cargo_home.join("bin").join(bin)),
+        _ => None,
+    }
+}
+
+/// The version the binary at `path` reports with `--version`, if it runs and reports one.
+fn reported_version(path: &Path) -> Option<Version> {
+    let output = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .inspect_err(|e| debug!("Failed to run {} --version: {}", path.display(), e))
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The first thing that looks like a semver version in the output of `--version`, such as the
+/// `14.1.0` in `ripgrep 14.1.0 (rev 1234abcd)`.
+fn parse_version(output: &str) -> Option<Version> {
+    output
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')')
+        .map(|word| word.strip_prefix('v').unwrap_or(word))
+        .find_map(|word| Version::parse(word).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_version_output() {
+        assert_eq!(
+            parse_version("ripgrep 14.1.0 (rev e50df40a19)\n\nfeatures:+pcre2"),
+            Some(Version::new(14, 1, 0))
+        );
+        assert_eq!(parse_version("just v1.36.0"), Some(Version::new(1, 36, 0)));
+        assert_eq!(
+            parse_version("cargo-deny 0.16.2-beta.1"),
+            Version::parse("0.16.2-beta.1").ok()
+        );
+        assert_eq!(parse_version("tool version unknown"), None);
+    }
+
+    #[test]
+    fn finds_single_cargo_installed_bin() {
+        let cargo_home = tempfile::tempdir().unwrap();
+        std::fs::write(
+            cargo_home.path().join(".crates2.json"),
+            r#"{"installs": {
+                "ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)": {"bins": ["rg"]},
+                "coreutils 0.0.28 (registry+https://github.com/rust-lang/crates.io-index)": {"bins": ["ls", "cat"]},
+                "local-tool 0.1.0 (path+file:///src/local-tool)": {"bins": ["local-tool"]}
+            }}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            cargo_installed_bin(cargo_home.path(), "ripgrep"),
+            Some(cargo_home.path().join("bin").join("rg"))
+        );
+        assert_eq!(cargo_installed_bin(cargo_home.path(), "coreutils"), None);
+        assert_eq!(cargo_installed_bin(cargo_home.path(), "local-tool"), None);
+        assert_eq!(cargo_installed_bin(cargo_home.path(), "rip"), None);
+    }
+}
@@ -0,0 +1,346 @@
+//! Export of traces of what cgx spent its time on to an OpenTelemetry collector.
+//!
+//! cgx wraps each phase of getting a tool in a `tracing` span: resolving the crate, downloading
+//! its source, looking for a pre-built binary with each provider, building it, and running it.
+//! The spans carry the crate, its version, whether the phase was served from the cache, and the
+//! provider.  Frontends collect them as [`SpanRecord`]s and, if an OTLP endpoint is configured,
+//! send them with [`OtlpExporter::export`] before cgx exits or hands the process over to the tool.
+//!
+//! Export is opt-in, and configured with the standard OpenTelemetry environment variables:
+//!
+//! - `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`, or `OTEL_EXPORTER_OTLP_ENDPOINT` with `/v1/traces`
+//!   appended, is where traces are sent.  Nothing is exported unless one of them is set.
+//! - `OTEL_EXPORTER_OTLP_HEADERS` (or `OTEL_EXPORTER_OTLP_TRACES_HEADERS`) adds `key=value`
+//!   headers, separated by commas, for collectors that need authentication.
+//! - `OTEL_EXPORTER_OTLP_TIMEOUT` (or `OTEL_EXPORTER_OTLP_TRACES_TIMEOUT`) is the export timeout in
+//!   milliseconds, 10 seconds by default.
+//! - `OTEL_SERVICE_NAME` is the service name traces are reported under, `cgx` by default.
+//! - `OTEL_SDK_DISABLED=true` turns export off again.
+//!
+//! Only the `http/json` OTLP protocol is supported; any other `OTEL_EXPORTER_OTLP_PROTOCOL` turns
+//! export off with a warning.
+
+use crate::{
+    Result,
+    config::HttpConfig,
+    error,
+    http::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, HttpClient},
+};
+use snafu::ResultExt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::*;
+
+/// Export timeout used when `OTEL_EXPORTER_OTLP_TIMEOUT` isn't set, as the spec prescribes.
+const DEFAULT_EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Service name used when `OTEL_SERVICE_NAME` isn't set.
+const DEFAULT_SERVICE_NAME: &str = "cgx";
+
+/// The id of a trace, shared by all of the spans in it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceId([u8; 16]);
+
+impl TraceId {
+    pub fn random() -> Self {
+        Self(*uuid::Uuid::new_v4().as_bytes())
+    }
+}
+
+/// The id of a span, unique within its trace.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpanId([u8; 8]);
+
+impl SpanId {
+    pub fn random() -> Self {
+        let bytes = uuid::Uuid::new_v4();
+        let mut id = [0; 8];
+        id.copy_from_slice(&bytes.as_bytes()[..8]);
+        Self(id)
+    }
+}
+
+/// A finished span, ready to be exported.
+#[derive(Clone, Debug)]
+pub struct SpanRecord {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub parent_span_id: Option<SpanId>,
+    pub name: String,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    /// The span's fields that were given values, in the order they were recorded.
+    pub attributes: Vec<(String, String)>,
+}
+
+/// Sends spans to an OTLP collector over HTTP, as configured by the environment.
+#[derive(Clone, Debug)]
+pub struct OtlpExporter {
+    endpoint: String,
+    headers: HeaderMap,
+    service_name: String,
+    http_client: HttpClient,
+}
+
+impl OtlpExporter {
+    /// The exporter configured by the `OTEL_*` environment variables, or `None` if export isn't
+    /// enabled.
+    pub fn from_env() -> Option<Self> {
+        Self::from_vars(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    }
+
+    /// Like [`Self::from_env`], but reading the variables with `var`.
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        if var("OTEL_SDK_DISABLED").is_some_and(|value| value.eq_ignore_ascii_case("true")) {
+            return None;
+        }
+
+        let endpoint = var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").or_else(|| {
+            var("OTEL_EXPORTER_OTLP_ENDPOINT")
+                .map(|endpoint| format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        })?;
+
+        let protocol =
+            var("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL").or_else(|| var("OTEL_EXPORTER_OTLP_PROTOCOL"));
+        if let Some(protocol) = protocol.filter(|protocol| protocol != "http/json") {
+            warn!(
+                "OTLP protocol '{}' is not supported, only 'http/json' is; not exporting traces",
+                protocol
+            );
+            return None;
+        }
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let extra_headers =
+            var("OTEL_EXPORTER_OTLP_TRACES_HEADERS").or_else(|| var("OTEL_EXPORTER_OTLP_HEADERS"));
+        for (name, value) in parse_headers(extra_headers.as_deref().unwrap_or_default()) {
+            if let (Ok(name), Ok(value)) = (HeaderName::try_from(name.as_str()), HeaderValue::try_from(value))
+            {
+                headers.insert(name, value);
+            } else {
+                warn!("Ignoring invalid OTLP export header '{}'", name);
+            }
+        }
+
+        let timeout = var("OTEL_EXPORTER_OTLP_TRACES_TIMEOUT")
+            .or_else(|| var("OTEL_EXPORTER_OTLP_TIMEOUT"))
+            .and_then(|millis| millis.trim().parse().ok())
+            .map_or(DEFAULT_EXPORT_TIMEOUT, Duration::from_millis);
+
+        // Export happens as cgx is finishing, so an unreachable collector must not hold it up
+        let http_config = HttpConfig {
+            timeout,
+            retries: 1,
+            ..HttpConfig::default()
+        };
+        let http_client = HttpClient::new(&http_config)
+            .inspect_err(|e| warn!("Failed to create the HTTP client for exporting traces: {}", e))
+            .ok()?;
+
+        Some(Self {
+            endpoint,
+            headers,
+            service_name: var("OTEL_SERVICE_NAME").unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string()),
+            http_client,
+        })
+    }
+
+    /// Send `spans` to the collector.
+    pub fn export(&self, spans: &[SpanRecord]) -> Result<()> {
+        if spans.is_empty() {
+            return Ok(());
+        }
+
+        let body = serde_json::to_string(&self.request_body(spans)).context(error::JsonSnafu)?;
+        let response = self
+            .http_client
+            .post_with_headers(&self.endpoint, &self.headers, &body)?;
+
+        if !response.status().is_success() {
+            return error::HttpStatusSnafu {
+                url: self.endpoint.clone(),
+                status: response.status().as_u16(),
+            }
+            .fail();
+        }
+
+        debug!("Exported {} spans to {}", spans.len(), self.endpoint);
+        Ok(())
+    }
+
+    /// An OTLP `ExportTraceServiceRequest` for `spans`, in its JSON encoding.
+    fn request_body(&self, spans: &[SpanRecord]) -> serde_json::Value {
+        let spans = spans
+            .iter()
+            .map(|span| {
+                let mut json = serde_json::json!({
+                    "traceId": crate::helpers::format_hex_lower(span.trace_id.0),
+                    "spanId": crate::helpers::format_hex_lower(span.span_id.0),
+                    "name": span.name,
+                    // SPAN_KIND_INTERNAL
+                    "kind": 1,
+                    "startTimeUnixNano": unix_nanos(span.start),
+                    "endTimeUnixNano": unix_nanos(span.end),
+                    "attributes": span
+                        .attributes
+                        .iter()
+                        .map(|(key, value)| attribute(key, value))
+                        .collect::<Vec<_>>(),
+                });
+                if let Some(parent) = span.parent_span_id {
+                    json["parentSpanId"] = crate::helpers::format_hex_lower(parent.0).into();
+                }
+                json
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        attribute("service.name", &self.service_name),
+                        attribute("service.version", env!("CARGO_PKG_VERSION")),
+                    ],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "cgx", "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+}
+
+/// An OTLP string attribute.
+fn attribute(key: &str, value: &str) -> serde_json::Value {
+    serde_json::json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// `time` as nanoseconds since the Unix epoch, as a string, since JSON numbers can't hold them
+/// precisely.
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+/// Parse the `key=value,key=value` list of `OTEL_EXPORTER_OTLP_HEADERS`, whose values may be
+/// percent-encoded.
+fn parse_headers(headers: &str) -> Vec<(String, String)> {
+    headers
+        .split(',')
+        .filter_map(|header| header.split_once('='))
+        .map(|(name, value)| {
+            let value = url::form_urlencoded::parse(format!("v={}", value.trim()).as_bytes())
+                .next()
+                .map(|(_, value)| value.into_owned())
+                .unwrap_or_default();
+            (name.trim().to_string(), value)
+        })
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn exporter(vars: &[(&str, &str)]) -> Option<OtlpExporter> {
+        let vars = vars
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        OtlpExporter::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn export_is_opt_in() {
+        assert!(exporter(&[]).is_none());
+        assert!(exporter(&[("OTEL_SERVICE_NAME", "ci")]).is_none());
+    }
+
+    #[test]
+    fn endpoint_from_env() {
+        let generic = exporter(&[("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318/")]).unwrap();
+        assert_eq!(generic.endpoint, "http://collector:4318/v1/traces");
+        assert_eq!(generic.service_name, "cgx");
+
+        let traces = exporter(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318"),
+            ("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "http://traces:4318/custom"),
+        ])
+        .unwrap();
+        assert_eq!(traces.endpoint, "http://traces:4318/custom");
+    }
+
+    #[test]
+    fn unsupported_protocol_or_disabled_sdk_turns_export_off() {
+        assert!(
+            exporter(&[
+                ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4317"),
+                ("OTEL_EXPORTER_OTLP_PROTOCOL", "grpc"),
+            ])
+            .is_none()
+        );
+        assert!(
+            exporter(&[
+                ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318"),
+                ("OTEL_SDK_DISABLED", "true"),
+            ])
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn headers_are_parsed_and_decoded() {
+        assert_eq!(
+            parse_headers("api-key=secret, Authorization=Basic%20dXNlcg%3D%3D,bogus"),
+            vec![
+                ("api-key".to_string(), "secret".to_string()),
+                ("Authorization".to_string(), "Basic dXNlcg==".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn spans_are_exported_as_otlp_json() {
+        let server = httpmock::MockServer::start();
+        let endpoint = server.url("/v1/traces");
+        let otlp = exporter(&[
+            ("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", &endpoint),
+            ("OTEL_EXPORTER_OTLP_HEADERS", "x-api-key=secret"),
+        ])
+        .unwrap();
+
+        let trace_id = TraceId::random();
+        let parent = SpanId::random();
+        let spans = [SpanRecord {
+            trace_id,
+            span_id: SpanId::random(),
+            parent_span_id: Some(parent),
+            name: "download".to_string(),
+            start: UNIX_EPOCH + Duration::from_secs(1),
+            end: UNIX_EPOCH + Duration::from_secs(2),
+            attributes: vec![("cache".to_string(), "hit".to_string())],
+        }];
+
+        let mock = server.mock(|when, then| {
+            when.method(httpmock::Method::POST)
+                .path("/v1/traces")
+                .header("x-api-key", "secret")
+                .body_includes(r#""name":"download""#)
+                .body_includes(r#""startTimeUnixNano":"1000000000""#)
+                .body_includes(format!(
+                    r#""parentSpanId":"{}""#,
+                    crate::helpers::format_hex_lower(parent.0)
+                ))
+                .body_includes(r#"{"key":"cache","value":{"stringValue":"hit"}}"#);
+            then.status(200).body("{}");
+        });
+
+        otlp.export(&spans).unwrap();
+        mock.assert();
+    }
+}
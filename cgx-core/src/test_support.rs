@@ -0,0 +1,859 @@
+//! Mock network services for writing deterministic integration tests against cgx.
+//!
+//! Everything here runs on local [`httpmock`] servers: a sparse registry index standing in for
+//! crates.io, and GitHub and GitLab release hosts serving fixture release assets.  Point a
+//! [`Config`](crate::config::Config) at them by replacing its
+//! [`endpoints`](crate::config::Config::endpoints) with [`MockServices::endpoints`], and the
+//! whole resolve/download/pre-built binary pipeline runs without touching the real network.
+//!
+//! This module is available to downstream crates with the `test-support` feature enabled.
+//!
+//! The helpers panic on failure, since they are only meant to be called from tests.
+
+use crate::config::{EndpointsConfig, QuickinstallIndex};
+use flate2::{Compression, write::GzEncoder};
+use httpmock::{Method::HEAD, Mock, MockServer, prelude::*};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, path::Path};
+use url::Url;
+
+/// A local sparse registry index that crates can be published to.
+pub struct MockRegistry {
+    server: MockServer,
+
+    /// Index lines published so far for each crate, along with the id of the mock serving them.
+    index_entries: HashMap<String, (Vec<String>, Option<usize>)>,
+}
+
+impl MockRegistry {
+    pub fn start() -> Self {
+        let server = MockServer::start();
+        let config = serde_json::json!({ "dl": server.url("/crates") });
+        server.mock(|when, then| {
+            when.method(GET).path("/index/config.json");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(config.to_string());
+        });
+
+        Self {
+            server,
+            index_entries: HashMap::new(),
+        }
+    }
+
+    /// URL of the sparse index, including the `sparse+` prefix.
+    pub fn index_url(&self) -> Url {
+        Url::parse(&format!("sparse+{}/index/", self.server.base_url())).expect("BUG: mock URL is valid")
+    }
+
+    /// Base URL of the mock's stand-in for the crates.io web API.
+    ///
+    /// Nothing is mocked there, so every request to it is a 404.
+    pub fn api_url(&self) -> Url {
+        Url::parse(&self.server.base_url()).expect("BUG: mock URL is valid")
+    }
+
+    /// Publish the crate source in `source_dir` as version `version` of crate `name`.
+    ///
+    /// The directory is packaged as-is into a `.crate` tarball, so it should contain a
+    /// `Cargo.toml` whose package name and version match.
+    pub fn publish(&mut self, name: &str, version: &str, source_dir: &Path) {
+        let tarball = crate_tarball(name, version, source_dir);
+        let cksum = crate::helpers::format_hex_lower(Sha256::digest(&tarball));
+
+        self.server.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/crates/{name}/{version}/download"));
+            then.status(200).body(&tarball);
+        });
+
+        let entry = serde_json::json!({
+            "name": name,
+            "vers": version,
+            "deps": [],
+            "cksum": cksum,
+            "features": {},
+            "yanked": false,
+        });
+
+        let (lines, _) = self.index_entries.entry(name.to_string()).or_default();
+        lines.push(entry.to_string());
+        self.serve_index_file(name);
+    }
+
+    /// Mark version `version` of crate `name`, which must have been published, as yanked.
+    pub fn yank(&mut self, name: &str, version: &str) {
+        let (lines, _) = self
+            .index_entries
+            .get_mut(name)
+            .expect("BUG: only published crates can be yanked");
+        for line in lines.iter_mut() {
+            let mut entry: serde_json::Value = serde_json::from_str(line).expect("BUG: index entry is JSON");
+            if entry["vers"] == version {
+                entry["yanked"] = serde_json::Value::Bool(true);
+                *line = entry.to_string();
+            }
+        }
+        self.serve_index_file(name);
+    }
+
+    /// Serve the index file for crate `name`, listing every version published so far.
+    fn serve_index_file(&mut self, name: &str) {
+        let (lines, mock_id) = self
+            .index_entries
+            .get_mut(name)
+            .expect("BUG: index file is only served for published crates");
+
+        // Mocks can't be updated in place, so replace the index file for this crate
+        if let Some(id) = mock_id.take() {
+            Mock::new(id, &self.server).delete();
+        }
+        let body = lines.join("\n");
+        let mock = self.server.mock(|when, then| {
+            when.method(GET).path(format!("/index/{}", index_path(name)));
+            then.status(200).body(body);
+        });
+        *mock_id = Some(mock.id);
+    }
+}
+
+/// A local stand-in for the GitHub REST API and release asset downloads.
+pub struct MockGithub {
+    server: MockServer,
+}
+
+impl MockGithub {
+    pub fn start() -> Self {
+        Self {
+            server: MockServer::start(),
+        }
+    }
+
+    /// Base URL to use as [`EndpointsConfig::github_api`].
+    pub fn api_url(&self) -> Url {
+        Url::parse(&self.server.base_url()).expect("BUG: mock URL is valid")
+    }
+
+    /// Publish a release of `owner/repo` at `tag` with the given `(filename, contents)` assets.
+    ///
+    /// A `.sha256` checksum file is published alongside each asset.
+    pub fn add_release(&self, owner: &str, repo: &str, tag: &str, assets: &[(&str, Vec<u8>)]) {
+        let mut listing = Vec::new();
+        for (filename, contents) in assets {
+            let path = format!("/downloads/{owner}/{repo}/{tag}/{filename}");
+            serve_with_checksum(&self.server, &path, contents);
+            listing.push(serde_json::json!({
+                "name": filename,
+                "browser_download_url": self.server.url(&path),
+            }));
+        }
+
+        let release = serde_json::json!({ "assets": listing });
+        self.server.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/repos/{owner}/{repo}/releases/tags/{tag}"));
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(release.to_string());
+        });
+    }
+
+    /// Reject every release lookup for `owner/repo` with a 403, the way the GitHub API answers
+    /// anonymous requests once the unauthenticated rate limit is exhausted.
+    pub fn deny_releases(&self, owner: &str, repo: &str) {
+        self.server.mock(|when, then| {
+            when.method(GET)
+                .path_includes(format!("/repos/{owner}/{repo}/releases/"));
+            then.status(403)
+                .header("content-type", "application/json")
+                .body(r#"{"message": "API rate limit exceeded"}"#);
+        });
+    }
+}
+
+/// A local stand-in for `gitlab.com` release asset downloads and its generic package registry.
+pub struct MockGitlab {
+    server: MockServer,
+}
+
+impl MockGitlab {
+    pub fn start() -> Self {
+        Self {
+            server: MockServer::start(),
+        }
+    }
+
+    /// Base URL to use as [`EndpointsConfig::gitlab`].
+    pub fn base_url(&self) -> Url {
+        Url::parse(&self.server.base_url()).expect("BUG: mock URL is valid")
+    }
+
+    /// Publish a release of `owner/repo` at `tag` with the given `(filename, contents)` assets.
+    ///
+    /// A `.sha256` checksum file is published alongside each asset.
+    pub fn add_release(&self, owner: &str, repo: &str, tag: &str, assets: &[(&str, Vec<u8>)]) {
+        for (filename, contents) in assets {
+            let path = format!("/{owner}/{repo}/-/releases/{tag}/downloads/binaries/{filename}");
+            self.server.mock(|when, then| {
+                when.method(HEAD).path(&path);
+                then.status(200);
+            });
+            serve_with_checksum(&self.server, &path, contents);
+        }
+    }
+
+    /// Publish a generic package `name` at `version` in the package registry of `owner/repo`,
+    /// with the given `(filename, contents)` files.
+    ///
+    /// The API reports the SHA256 of each file, as GitLab does; no `.sha256` files are published.
+    pub fn add_generic_package(
+        &self,
+        owner: &str,
+        repo: &str,
+        name: &str,
+        version: &str,
+        files: &[(&str, Vec<u8>)],
+    ) {
+        const PROJECT_ID: u64 = 42;
+        const PACKAGE_ID: u64 = 7;
+
+        // Whether the slash in the project path arrives encoded depends on the HTTP stack
+        for project_path in [format!("{owner}%2F{repo}"), format!("{owner}/{repo}")] {
+            self.server.mock(|when, then| {
+                when.method(GET).path(format!("/api/v4/projects/{project_path}"));
+                then.status(200)
+                    .body(serde_json::json!({ "id": PROJECT_ID }).to_string());
+            });
+        }
+
+        let package = serde_json::json!([{ "id": PACKAGE_ID, "name": name, "version": version }]);
+        self.server.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/api/v4/projects/{PROJECT_ID}/packages"))
+                .query_param("package_type", "generic")
+                .query_param("package_name", name);
+            then.status(200).body(package.to_string());
+        });
+
+        let listing = files
+            .iter()
+            .map(|(filename, contents)| {
+                serde_json::json!({
+                    "file_name": filename,
+                    "file_sha256": crate::helpers::format_hex_lower(Sha256::digest(contents)),
+                })
+            })
+            .collect::<Vec<_>>();
+        self.server.mock(|when, then| {
+            when.method(GET).path(format!(
+                "/api/v4/projects/{PROJECT_ID}/packages/{PACKAGE_ID}/package_files"
+            ));
+            then.status(200)
+                .body(serde_json::Value::Array(listing).to_string());
+        });
+
+        for (filename, contents) in files {
+            self.server.mock(|when, then| {
+                when.method(GET).path(format!(
+                    "/api/v4/projects/{PROJECT_ID}/packages/generic/{name}/{version}/{filename}"
+                ));
+                then.status(200).body(contents);
+            });
+        }
+    }
+}
+
+/// All of the mock services, together with the endpoints that route cgx to them.
+pub struct MockServices {
+    pub registry: MockRegistry,
+    pub github: MockGithub,
+    pub gitlab: MockGitlab,
+}
+
+impl MockServices {
+    pub fn start() -> Self {
+        Self {
+            registry: MockRegistry::start(),
+            github: MockGithub::start(),
+            gitlab: MockGitlab::start(),
+        }
+    }
+
+    /// Endpoints that send every request cgx makes to one of the mock services.
+    ///
+    /// Quickinstall has no dedicated mock; its endpoint points at the GitHub mock, where every
+    /// download is a 404 unless a test explicitly mocks it.
+    pub fn endpoints(&self) -> EndpointsConfig {
+        EndpointsConfig {
+            crates_io_index: Some(self.registry.index_url()),
+            crates_io_api: self.registry.api_url(),
+            github_api: self.github.api_url(),
+            gitlab: self.gitlab.base_url(),
+            quickinstall: vec![QuickinstallIndex::new(
+                self.github
+                    .api_url()
+                    .join("quickinstall")
+                    .expect("BUG: mock URL is valid"),
+            )],
+        }
+    }
+}
+
+/// Build a `.tar.gz` release asset containing a single executable named `binary_name`.
+///
+/// The platform's executable suffix is appended to the name, matching what the pre-built binary
+/// providers look for when extracting the archive.
+pub fn release_archive(binary_name: &str, contents: &[u8]) -> Vec<u8> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o755);
+    header.set_cksum();
+
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    builder
+        .append_data(
+            &mut header,
+            format!("{binary_name}{}", std::env::consts::EXE_SUFFIX),
+            contents,
+        )
+        .expect("failed to append binary to release archive");
+    builder
+        .into_inner()
+        .and_then(GzEncoder::finish)
+        .expect("failed to finish release archive")
+}
+
+/// Package `source_dir` as a `.crate` tarball, with everything nested under `{name}-{version}/`.
+fn crate_tarball(name: &str, version: &str, source_dir: &Path) -> Vec<u8> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    builder
+        .append_dir_all(format!("{name}-{version}"), source_dir)
+        .unwrap_or_else(|e| panic!("failed to package {}: {e}", source_dir.display()));
+    builder
+        .into_inner()
+        .and_then(GzEncoder::finish)
+        .expect("failed to finish crate tarball")
+}
+
+/// Serve `contents` at `path`, with its SHA-256 checksum at `{path}.sha256`.
+fn serve_with_checksum(server: &MockServer, path: &str, contents: &[u8]) {
+    let checksum = crate::helpers::format_hex_lower(Sha256::digest(contents));
+    server.mock(|when, then| {
+        when.method(GET).path(path);
+        then.status(200).body(contents);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path(format!("{path}.sha256"));
+        then.status(200).body(checksum);
+    });
+}
+
+/// Path of a crate's file within a sparse index, per cargo's index layout.
+fn index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{name}"),
+        2 => format!("2/{name}"),
+        3 => format!("3/{}/{name}", &name[..1]),
+        _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
+    }
+}
+
+#[cfg(all(test, feature = "prebuilt-binaries"))]
+mod tests {
+    use super::*;
+    use crate::{
+        Cgx,
+        bin_resolver::{ProviderFailure, ProviderFailureKind},
+        builder::BuildOptions,
+        config::{BinaryProvider, UsePrebuiltBinaries},
+        cratespec::CrateSpec,
+        messages::{Message, MessageReporter, PrebuiltBinaryMessage},
+    };
+    use assert_matches::assert_matches;
+    use std::io::Read;
+
+    /// Stands in for a tool's binary: the start of this test executable, so that it passes the
+    /// check that downloaded binaries are executables for the host.
+    fn fake_binary() -> Vec<u8> {
+        let mut header = Vec::new();
+        std::fs::File::open(std::env::current_exe().unwrap())
+            .and_then(|file| file.take(4096).read_to_end(&mut header))
+            .unwrap();
+        header
+    }
+
+    /// Write a minimal binary crate named `name` whose manifest points at `repository`.
+    fn write_crate(dir: &Path, name: &str, version: &str, repository: &str) {
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"{name}\"\nversion = \"{version}\"\nedition = \"2024\"\nrepository = \
+                 \"{repository}\"\n"
+            ),
+        )
+        .unwrap();
+        std::fs::write(dir.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+    }
+
+    fn cgx_for(services: &MockServices, providers: Vec<BinaryProvider>) -> (tempfile::TempDir, Cgx) {
+        cgx_reporting_to(services, providers, MessageReporter::null())
+    }
+
+    fn cgx_reporting_to(
+        services: &MockServices,
+        providers: Vec<BinaryProvider>,
+        reporter: MessageReporter,
+    ) -> (tempfile::TempDir, Cgx) {
+        crate::logging::init_test_logging();
+
+        let (temp_dir, mut config) = crate::config::create_test_env();
+        config.endpoints = services.endpoints();
+        config.prebuilt_binaries.use_prebuilt_binaries = UsePrebuiltBinaries::Always;
+        config.prebuilt_binaries.binary_providers = providers;
+        config.prebuilt_binaries.targets = vec!["x86_64-unknown-linux-gnu".to_string()];
+
+        let cgx = Cgx::new(config, reporter).unwrap();
+        (temp_dir, cgx)
+    }
+
+    fn spec(name: &str) -> CrateSpec {
+        CrateSpec::CratesIo {
+            name: name.to_string(),
+            version: None,
+        }
+    }
+
+    #[test]
+    fn index_path_layout() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("ab"), "2/ab");
+        assert_eq!(index_path("abc"), "3/a/abc");
+        assert_eq!(index_path("Serde"), "se/rd/serde");
+    }
+
+    #[test]
+    fn registry_serves_every_published_version() {
+        let mut services = MockServices::start();
+        let src = tempfile::tempdir().unwrap();
+        for version in ["0.1.0", "0.2.0"] {
+            write_crate(
+                src.path(),
+                "mocktool",
+                version,
+                "https://github.com/mock/mocktool",
+            );
+            services.registry.publish("mocktool", version, src.path());
+        }
+        services.github.add_release(
+            "mock",
+            "mocktool",
+            "v0.2.0",
+            &[(
+                "mocktool-x86_64-unknown-linux-gnu.tar.gz",
+                release_archive("mocktool", &fake_binary()),
+            )],
+        );
+
+        let (_temp_dir, cgx) = cgx_for(&services, vec![BinaryProvider::GithubReleases]);
+        let bin = cgx
+            .crate_to_bin(&[spec("mocktool")], &BuildOptions::default())
+            .unwrap();
+
+        assert!(bin.to_string_lossy().contains("mocktool-0.2.0"));
+        assert_eq!(std::fs::read(bin).unwrap(), fake_binary());
+    }
+
+    #[test]
+    fn plain_tag_is_used_when_v_prefixed_release_has_no_binary() {
+        let mut services = MockServices::start();
+        let src = tempfile::tempdir().unwrap();
+        write_crate(
+            src.path(),
+            "mocktool",
+            "1.0.0",
+            "https://github.com/mock/mocktool",
+        );
+        services.registry.publish("mocktool", "1.0.0", src.path());
+        services.github.add_release(
+            "mock",
+            "mocktool",
+            "v1.0.0",
+            &[("mocktool-1.0.0-source.tar.gz", b"source".to_vec())],
+        );
+        services.github.add_release(
+            "mock",
+            "mocktool",
+            "1.0.0",
+            &[(
+                "mocktool-x86_64-unknown-linux-gnu.tar.gz",
+                release_archive("mocktool", &fake_binary()),
+            )],
+        );
+
+        let (_temp_dir, cgx) = cgx_for(&services, vec![BinaryProvider::GithubReleases]);
+        let bin = cgx
+            .crate_to_bin(&[spec("mocktool")], &BuildOptions::default())
+            .unwrap();
+
+        assert_eq!(std::fs::read(bin).unwrap(), fake_binary());
+    }
+
+    #[test]
+    fn prebuilt_binary_summary_is_reported() {
+        let mut services = MockServices::start();
+        let src = tempfile::tempdir().unwrap();
+        write_crate(
+            src.path(),
+            "mocktool",
+            "1.0.0",
+            "https://github.com/mock/mocktool",
+        );
+        services.registry.publish("mocktool", "1.0.0", src.path());
+        let archive = release_archive("mocktool", &fake_binary());
+        let archive_len = archive.len() as u64;
+        services.github.add_release(
+            "mock",
+            "mocktool",
+            "v1.0.0",
+            &[("mocktool-x86_64-unknown-linux-gnu.tar.gz", archive)],
+        );
+
+        let (tx, rx) = std::sync::mpsc::sync_channel(1000);
+        let (_temp_dir, cgx) = cgx_reporting_to(
+            &services,
+            vec![BinaryProvider::GithubReleases],
+            MessageReporter::channel(tx),
+        );
+        cgx.crate_to_bin(&[spec("mocktool")], &BuildOptions::default())
+            .unwrap();
+        drop(cgx);
+
+        let summary = rx.into_iter().find_map(|message| match message {
+            Message::PrebuiltBinary(PrebuiltBinaryMessage::Summary {
+                provider,
+                bytes_downloaded,
+                build_time_saved,
+                ..
+            }) => Some((provider, bytes_downloaded, build_time_saved)),
+            _ => None,
+        });
+        // The archive's checksum file is downloaded too
+        let (provider, bytes_downloaded, build_time_saved) = summary.unwrap();
+        assert_eq!(provider, BinaryProvider::GithubReleases);
+        assert!(bytes_downloaded >= archive_len);
+        assert_eq!(build_time_saved, None);
+    }
+
+    #[test]
+    fn prebuilt_binary_from_mock_gitlab() {
+        let mut services = MockServices::start();
+        let src = tempfile::tempdir().unwrap();
+        write_crate(src.path(), "labtool", "1.0.0", "https://gitlab.com/mock/labtool");
+        services.registry.publish("labtool", "1.0.0", src.path());
+        services.gitlab.add_release(
+            "mock",
+            "labtool",
+            "v1.0.0",
+            &[(
+                "labtool-x86_64-unknown-linux-gnu.tar.gz",
+                release_archive("labtool", &fake_binary()),
+            )],
+        );
+
+        let (_temp_dir, cgx) = cgx_for(&services, vec![BinaryProvider::GitlabReleases]);
+        let bin = cgx
+            .crate_to_bin(&[spec("labtool")], &BuildOptions::default())
+            .unwrap();
+
+        assert_eq!(std::fs::read(bin).unwrap(), fake_binary());
+    }
+
+    #[test]
+    fn prebuilt_binary_from_mock_gitlab_package() {
+        let mut services = MockServices::start();
+        let src = tempfile::tempdir().unwrap();
+        write_crate(src.path(), "pkgtool", "1.0.0", "https://gitlab.com/mock/pkgtool");
+        services.registry.publish("pkgtool", "1.0.0", src.path());
+        services.gitlab.add_generic_package(
+            "mock",
+            "pkgtool",
+            "pkgtool",
+            "1.0.0",
+            &[(
+                "pkgtool-x86_64-unknown-linux-gnu.tar.gz",
+                release_archive("pkgtool", &fake_binary()),
+            )],
+        );
+
+        let (_temp_dir, cgx) = cgx_for(&services, vec![BinaryProvider::GitlabReleases]);
+        let bin = cgx
+            .crate_to_bin(&[spec("pkgtool")], &BuildOptions::default())
+            .unwrap();
+
+        assert_eq!(std::fs::read(bin).unwrap(), fake_binary());
+    }
+
+    #[test]
+    fn unpublished_crate_is_not_found() {
+        let services = MockServices::start();
+
+        let (_temp_dir, cgx) = cgx_for(&services, vec![BinaryProvider::GithubReleases]);
+        let result = cgx.crate_to_bin(&[spec("nonexistent")], &BuildOptions::default());
+
+        assert_matches!(result, Err(crate::error::Error::CrateNotFoundInRegistry { .. }));
+    }
+
+    #[test]
+    fn falls_back_to_next_source_when_not_found() {
+        let mut services = MockServices::start();
+        let src = tempfile::tempdir().unwrap();
+        write_crate(
+            src.path(),
+            "mocktool",
+            "0.2.0",
+            "https://github.com/mock/mocktool",
+        );
+        services.registry.publish("mocktool", "0.2.0", src.path());
+        services.github.add_release(
+            "mock",
+            "mocktool",
+            "v0.2.0",
+            &[(
+                "mocktool-x86_64-unknown-linux-gnu.tar.gz",
+                release_archive("mocktool", &fake_binary()),
+            )],
+        );
+
+        let (_temp_dir, cgx) = cgx_for(&services, vec![BinaryProvider::GithubReleases]);
+        let bin = cgx
+            .crate_to_bin(&[spec("nonexistent"), spec("mocktool")], &BuildOptions::default())
+            .unwrap();
+
+        assert!(bin.to_string_lossy().contains("mocktool-0.2.0"));
+    }
+
+    #[test]
+    fn not_found_in_any_fallback_source() {
+        let services = MockServices::start();
+
+        let (_temp_dir, cgx) = cgx_for(&services, vec![BinaryProvider::GithubReleases]);
+        let result = cgx.crate_to_bin(
+            &[spec("nonexistent"), spec("also-nonexistent")],
+            &BuildOptions::default(),
+        );
+
+        assert_matches!(
+            result,
+            Err(crate::error::Error::CrateNotFoundInAnySource { name, sources })
+                if name == "nonexistent" && sources.len() == 2
+        );
+    }
+
+    #[test]
+    fn missing_release_fails_when_prebuilt_required() {
+        let mut services = MockServices::start();
+        let src = tempfile::tempdir().unwrap();
+        write_crate(
+            src.path(),
+            "norelease",
+            "1.0.0",
+            "https://github.com/mock/norelease",
+        );
+        services.registry.publish("norelease", "1.0.0", src.path());
+
+        let (_temp_dir, cgx) = cgx_for(&services, vec![BinaryProvider::GithubReleases]);
+        let result = cgx.crate_to_bin(&[spec("norelease")], &BuildOptions::default());
+
+        assert_matches!(
+            result,
+            Err(crate::error::Error::PrebuiltBinaryRequired { failures, .. }) if failures.is_empty()
+        );
+    }
+
+    #[test]
+    fn yanked_crate_points_at_its_successor() {
+        let mut services = MockServices::start();
+        for (name, version) in [("oldtool", "1.0.0"), ("newtool", "2.0.0")] {
+            let src = tempfile::tempdir().unwrap();
+            write_crate(src.path(), name, version, "https://github.com/mock/tool");
+            services.registry.publish(name, version, src.path());
+        }
+        services.registry.yank("oldtool", "1.0.0");
+
+        crate::logging::init_test_logging();
+        let (_temp_dir, mut config) = crate::config::create_test_env();
+        config.endpoints = services.endpoints();
+        config
+            .successors
+            .insert("oldtool".to_string(), "newtool".to_string());
+
+        let cgx = Cgx::new(config.clone(), MessageReporter::null()).unwrap();
+        assert_matches!(
+            cgx.crate_source(&[spec("oldtool")], None),
+            Err(crate::error::Error::CrateSuperseded { name, successor })
+                if name == "oldtool" && successor == "newtool"
+        );
+
+        config.follow_successors = true;
+        let cgx = Cgx::new(config, MessageReporter::null()).unwrap();
+        let source = cgx.crate_source(&[spec("oldtool")], None).unwrap();
+        assert!(source.ends_with(Path::new("newtool").join("2.0.0")));
+    }
+
+    #[test]
+    fn denied_release_lookup_is_reported_as_auth_required() {
+        let mut services = MockServices::start();
+        let src = tempfile::tempdir().unwrap();
+        write_crate(
+            src.path(),
+            "authtool",
+            "1.0.0",
+            "https://github.com/mock/authtool",
+        );
+        services.registry.publish("authtool", "1.0.0", src.path());
+        services.github.deny_releases("mock", "authtool");
+
+        let (_temp_dir, cgx) = cgx_for(&services, vec![BinaryProvider::GithubReleases]);
+        let result = cgx.crate_to_bin(&[spec("authtool")], &BuildOptions::default());
+
+        assert_matches!(
+            result,
+            Err(crate::error::Error::PrebuiltBinaryRequired { failures, .. })
+                if matches!(
+                    failures.as_slice(),
+                    [ProviderFailure { provider: BinaryProvider::GithubReleases, kind: ProviderFailureKind::AuthRequired, .. }]
+                )
+        );
+    }
+
+    /// Snapshots of the messages reported over whole runs, in the order they're reported.
+    ///
+    /// Tools reading `--message-format json` depend on these streams, so a change to them should
+    /// be a decision rather than a side effect of a refactor.  What varies from run to run (temp
+    /// paths, ports, hashes, sizes and timings) is replaced before comparing.
+    ///
+    /// They're only compared on x86-64 Linux, since the platform shows up in the messages, such as
+    /// in the target a pre-built binary is looked for, and paths there don't need escaping in JSON.
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    mod message_snapshots {
+        use super::*;
+        use std::sync::mpsc::{Receiver, sync_channel};
+
+        const FILTERS: &[(&str, &str)] = &[
+            (r"http://127\.0\.0\.1:\d+", "[MOCK]"),
+            (r"\.tmp[0-9A-Za-z]{6}", ".tmp[RANDOM]"),
+            (r"[0-9a-f]{16,}", "[HASH]"),
+            (
+                r#""elapsed":\{"secs":\d+,"nanos":\d+\}"#,
+                r#""elapsed":"[DURATION]""#,
+            ),
+            (r#""bytes_downloaded":\d+"#, r#""bytes_downloaded":"[BYTES]""#),
+            (r#""(age_secs|ttl_remaining_secs)":\d+"#, r#""${1}":"[SECS]""#),
+        ];
+
+        /// The messages reported so far, one JSON object per line, with `root` replaced.
+        fn drain(rx: &Receiver<Message>, root: &Path) -> String {
+            rx.try_iter()
+                .map(|message| serde_json::to_string(&message).unwrap())
+                .collect::<Vec<_>>()
+                .join("\n")
+                .replace(root.to_str().unwrap(), "[ROOT]")
+        }
+
+        fn assert_stream(name: &str, stream: &str) {
+            let mut settings = insta::Settings::clone_current();
+            for (pattern, replacement) in FILTERS {
+                settings.add_filter(pattern, *replacement);
+            }
+            settings.bind(|| insta::assert_snapshot!(name, stream));
+        }
+
+        /// `mocktool` 1.0.0, with a release on the mock GitHub holding a binary for the target.
+        fn services_with_release() -> (MockServices, tempfile::TempDir) {
+            let mut services = MockServices::start();
+            let src = tempfile::tempdir().unwrap();
+            write_crate(
+                src.path(),
+                "mocktool",
+                "1.0.0",
+                "https://github.com/mock/mocktool",
+            );
+            services.registry.publish("mocktool", "1.0.0", src.path());
+            services.github.add_release(
+                "mock",
+                "mocktool",
+                "v1.0.0",
+                &[(
+                    "mocktool-x86_64-unknown-linux-gnu.tar.gz",
+                    release_archive("mocktool", &fake_binary()),
+                )],
+            );
+            (services, src)
+        }
+
+        #[test]
+        fn prebuilt_from_github_release() {
+            let (services, _src) = services_with_release();
+            let (tx, rx) = sync_channel(1000);
+            let (temp_dir, cgx) = cgx_reporting_to(
+                &services,
+                vec![BinaryProvider::GithubReleases],
+                MessageReporter::channel(tx),
+            );
+
+            cgx.crate_to_bin(&[spec("mocktool")], &BuildOptions::default())
+                .unwrap();
+
+            assert_stream("prebuilt_from_github_release", &drain(&rx, temp_dir.path()));
+        }
+
+        #[test]
+        fn prebuilt_from_cache() {
+            let (services, _src) = services_with_release();
+            let (tx, rx) = sync_channel(1000);
+            let (temp_dir, cgx) = cgx_reporting_to(
+                &services,
+                vec![BinaryProvider::GithubReleases],
+                MessageReporter::channel(tx),
+            );
+
+            cgx.crate_to_bin(&[spec("mocktool")], &BuildOptions::default())
+                .unwrap();
+            // Only the second run, which finds everything cached, is snapshotted
+            rx.try_iter().for_each(drop);
+            cgx.crate_to_bin(&[spec("mocktool")], &BuildOptions::default())
+                .unwrap();
+
+            assert_stream("prebuilt_from_cache", &drain(&rx, temp_dir.path()));
+        }
+
+        #[test]
+        fn prebuilt_required_but_missing() {
+            let mut services = MockServices::start();
+            let src = tempfile::tempdir().unwrap();
+            write_crate(
+                src.path(),
+                "norelease",
+                "1.0.0",
+                "https://github.com/mock/norelease",
+            );
+            services.registry.publish("norelease", "1.0.0", src.path());
+            let (tx, rx) = sync_channel(1000);
+            let (temp_dir, cgx) = cgx_reporting_to(
+                &services,
+                vec![BinaryProvider::GithubReleases],
+                MessageReporter::channel(tx),
+            );
+
+            let error = cgx
+                .crate_to_bin(&[spec("norelease")], &BuildOptions::default())
+                .unwrap_err();
+
+            let stream = format!("{}\nerror: {error}", drain(&rx, temp_dir.path()));
+            assert_stream("prebuilt_required_but_missing", &stream);
+        }
+    }
+}
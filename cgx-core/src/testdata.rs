@@ -51,6 +51,7 @@ impl CrateTestCase {
             Self::timestamp(),
             Self::workspace_all_libs(),
             Self::workspace_multiple_bin_crates(),
+            Self::workspace_single_bin_crate(),
         ]
     }
 
@@ -98,6 +99,10 @@ impl CrateTestCase {
         Self::load("workspace-multiple-bin-crates")
     }
 
+    pub(crate) fn workspace_single_bin_crate() -> Self {
+        Self::load("workspace-single-bin-crate")
+    }
+
     /// Load a test case from the filesystem, by name
     fn load(name: &'static str) -> Self {
         const TESTDATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/crates");
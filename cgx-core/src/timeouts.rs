@@ -0,0 +1,267 @@
+//! Time limits on the phases of getting a binary, and on a run as a whole, for CI steps with a
+//! hard time budget.
+//!
+//! Resolving a crate, downloading it (its source, and a pre-built binary if there is one) and
+//! building it can each be given a timeout in the `[timeouts]` config (see [`PhaseTimeouts`]), and
+//! a run a deadline with `deadline` there or `--deadline`, which counts from the start of each
+//! operation of a [`crate::Cgx`], or for all of them from the time given to
+//! [`crate::CgxBuilder::deadline_from`].  The time left for a phase is however much of its timeout earlier steps of the
+//! same phase haven't used, or what's left until the deadline, whichever is less.
+//!
+//! A phase that runs out of time is cancelled the same way as with a [`CancellationToken`] (see
+//! [`crate::cancel`]), so a cargo build in progress is killed and any temporary state removed, and
+//! then fails with [`Error::PhaseTimedOut`] or [`Error::DeadlineExceeded`], naming the phase.
+//!
+//! [`Error::PhaseTimedOut`]: crate::error::Error::PhaseTimedOut
+//! [`Error::DeadlineExceeded`]: crate::error::Error::DeadlineExceeded
+
+use crate::{
+    Result,
+    cancel::{self, CancellationToken},
+    config::PhaseTimeouts,
+    error,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+use strum::Display;
+
+/// How often a phase with a time limit checks whether the operation it's part of was cancelled
+/// from outside, so that the cancellation reaches the phase too.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A phase of getting a binary that can be given a timeout of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Phase {
+    /// Resolving the crate spec to an exact version and source.
+    Resolve,
+
+    /// Downloading the crate's source, and looking for and downloading a pre-built binary.
+    Download,
+
+    /// Building the crate from source.
+    Build,
+}
+
+/// What limits the time a phase has left.
+#[derive(Debug, Clone, Copy)]
+enum Limit {
+    /// The phase's own timeout, of which this much is left.
+    Phase(Duration),
+
+    /// The deadline, which is this much away.
+    Deadline(Duration),
+}
+
+impl Limit {
+    fn remaining(self) -> Duration {
+        match self {
+            Self::Phase(remaining) | Self::Deadline(remaining) => remaining,
+        }
+    }
+}
+
+/// The time the phases of getting one binary have left.
+pub(crate) struct Budget<'a> {
+    timeouts: &'a PhaseTimeouts,
+
+    /// When the operation, or the run it is part of, started, which the deadline counts from.
+    started: Instant,
+
+    /// The time spent so far in each phase, since a phase can take more than one step.
+    spent: HashMap<Phase, Duration>,
+}
+
+impl<'a> Budget<'a> {
+    pub(crate) fn new(timeouts: &'a PhaseTimeouts, started: Instant) -> Self {
+        Self {
+            timeouts,
+            started,
+            spent: HashMap::new(),
+        }
+    }
+
+    /// Run `f` as a step of `phase`, cancelling it if it runs out of time.
+    pub(crate) fn run<T>(&mut self, phase: Phase, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let Some(limit) = self.limit(phase) else {
+            return f();
+        };
+        if limit.remaining().is_zero() {
+            return Err(self.timed_out(phase));
+        }
+
+        let started = Instant::now();
+        let (result, timed_out) = watch(limit.remaining(), f);
+        *self.spent.entry(phase).or_default() += started.elapsed();
+
+        match result {
+            Err(e) if timed_out => {
+                tracing::debug!("The {phase} phase failed after running out of time: {e}");
+                Err(self.timed_out(phase))
+            }
+            result => result,
+        }
+    }
+
+    /// The tighter of the limits on `phase`, if it has any.
+    fn limit(&self, phase: Phase) -> Option<Limit> {
+        let spent = self.spent.get(&phase).copied().unwrap_or_default();
+        let phase_limit = self
+            .timeouts
+            .for_phase(phase)
+            .map(|timeout| Limit::Phase(timeout.saturating_sub(spent)));
+        let deadline_limit = self
+            .timeouts
+            .deadline
+            .map(|deadline| Limit::Deadline(deadline.saturating_sub(self.started.elapsed())));
+
+        match (phase_limit, deadline_limit) {
+            (Some(phase_limit), Some(deadline_limit)) => {
+                if deadline_limit.remaining() < phase_limit.remaining() {
+                    Some(deadline_limit)
+                } else {
+                    Some(phase_limit)
+                }
+            }
+            (phase_limit, deadline_limit) => phase_limit.or(deadline_limit),
+        }
+    }
+
+    /// The error for `phase` having run out of time, blaming the deadline if it's passed.
+    fn timed_out(&self, phase: Phase) -> error::Error {
+        match self.timeouts.deadline {
+            Some(deadline) if self.started.elapsed() >= deadline => {
+                error::DeadlineExceededSnafu { phase, deadline }.build()
+            }
+            _ => error::PhaseTimedOutSnafu {
+                phase,
+                timeout: self.timeouts.for_phase(phase).unwrap_or_default(),
+            }
+            .build(),
+        }
+    }
+}
+
+/// Run `f`, cancelling it once `limit` has passed, and return what it returned along with whether
+/// it was cancelled for running out of time.
+///
+/// `f` runs on this thread with a token of its own, which is cancelled from a watcher thread, as
+/// it also is if the current token is cancelled while `f` runs.
+fn watch<T>(limit: Duration, f: impl FnOnce() -> T) -> (T, bool) {
+    let outer = cancel::current();
+    let token = CancellationToken::new();
+
+    std::thread::scope(|scope| {
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let watcher = {
+            let (outer, token) = (&outer, &token);
+            scope.spawn(move || {
+                let started = Instant::now();
+                loop {
+                    let remaining = limit.saturating_sub(started.elapsed());
+                    if remaining.is_zero() {
+                        token.cancel();
+                        return true;
+                    }
+
+                    match done_rx.recv_timeout(remaining.min(POLL_INTERVAL)) {
+                        Err(RecvTimeoutError::Timeout) if outer.is_cancelled() => token.cancel(),
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Ok(()) | Err(RecvTimeoutError::Disconnected) => return false,
+                    }
+                }
+            })
+        };
+
+        let result = cancel::with_token(&token, f);
+        drop(done_tx);
+        let timed_out = watcher
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        (result, timed_out)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    /// A step that runs until it's cancelled, as a cargo build that's killed does.
+    fn until_cancelled() -> Result<()> {
+        loop {
+            cancel::check()?;
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn steps_within_their_limits_are_left_alone() {
+        let timeouts = PhaseTimeouts {
+            resolve: Some(Duration::from_secs(60)),
+            deadline: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let mut budget = Budget::new(&timeouts, Instant::now());
+
+        assert_eq!(budget.run(Phase::Resolve, || Ok(1)).unwrap(), 1);
+        assert_matches!(
+            budget.run(Phase::Build, || error::CancelledSnafu.fail::<()>()),
+            Err(error::Error::Cancelled)
+        );
+    }
+
+    #[test]
+    fn a_phase_past_its_timeout_is_cancelled() {
+        let timeouts = PhaseTimeouts {
+            build: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let mut budget = Budget::new(&timeouts, Instant::now());
+
+        assert_matches!(
+            budget.run(Phase::Build, until_cancelled),
+            Err(error::Error::PhaseTimedOut {
+                phase: Phase::Build,
+                ..
+            })
+        );
+
+        // The phase's timeout is used up, so a later step of it fails straight away
+        assert_matches!(
+            budget.run(Phase::Build, || Ok(())),
+            Err(error::Error::PhaseTimedOut { .. })
+        );
+        assert!(budget.run(Phase::Download, || Ok(())).is_ok());
+    }
+
+    #[test]
+    fn the_deadline_is_blamed_once_it_passes() {
+        let timeouts = PhaseTimeouts {
+            download: Some(Duration::from_secs(60)),
+            deadline: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let mut budget = Budget::new(&timeouts, Instant::now());
+
+        assert_matches!(
+            budget.run(Phase::Download, until_cancelled),
+            Err(error::Error::DeadlineExceeded {
+                phase: Phase::Download,
+                ..
+            })
+        );
+        assert_matches!(
+            budget.run(Phase::Resolve, || Ok(())),
+            Err(error::Error::DeadlineExceeded {
+                phase: Phase::Resolve,
+                ..
+            })
+        );
+    }
+}
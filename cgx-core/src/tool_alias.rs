@@ -0,0 +1,138 @@
+//! Adding and removing aliases in a config file, for `cgx --alias <ALIAS>=<CRATE>` and
+//! `cgx --unalias <ALIAS>`.
+//!
+//! The alias's entry in the `[aliases]` table is edited in place with [`config_edit`], so
+//! everything else in the file is left as it was.  These only edit the file; checking that the
+//! crate an alias stands for exists is up to the caller, since that needs the whole config.
+
+use crate::{Result, config_edit, error};
+use std::path::Path;
+use toml_edit::Item;
+
+/// Split an `<ALIAS>=<CRATE>` argument into the alias and the name of the crate it stands for.
+///
+/// The crate is a name alone, since that's all an alias is replaced with; a version or source
+/// for it belongs in its entry under `[tools]`.
+pub fn parse(spec: &str) -> Result<(String, String)> {
+    let Some((alias, target)) = spec
+        .split_once('=')
+        .map(|(alias, target)| (alias.trim(), target.trim()))
+        .filter(|(alias, target)| is_name(alias) && is_name(target) && alias != target)
+    else {
+        return error::InvalidAliasSnafu { spec }.fail();
+    };
+
+    Ok((alias.to_string(), target.to_string()))
+}
+
+/// Whether `name` could be the name of a crate.
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Alias `alias` to the crate `target` in the config file at `path`, creating the file if need
+/// be, and return the crate it was aliased to before, if any.
+pub fn add(path: &Path, alias: &str, target: &str) -> Result<Option<String>> {
+    config_edit::edit(path, |document| {
+        let aliases = config_edit::table_mut(document, path, "aliases")?;
+
+        if let Some(item) = aliases.get_mut(alias) {
+            let previous = item.as_str().map(str::to_string);
+            let mut value = toml_edit::Value::from(target);
+            if let Some(old) = item.as_value() {
+                *value.decor_mut() = old.decor().clone();
+            }
+            *item = Item::Value(value);
+            Ok(previous)
+        } else {
+            aliases.insert(alias, toml_edit::value(target));
+            Ok(None)
+        }
+    })
+}
+
+/// Remove the alias `alias` from the config file at `path`, and return the crate it stood for,
+/// if it was there.
+pub fn remove(path: &Path, alias: &str) -> Result<Option<String>> {
+    config_edit::edit(path, |document| {
+        let Some(aliases) = document.get_mut("aliases").and_then(Item::as_table_like_mut) else {
+            return Ok(None);
+        };
+
+        Ok(aliases
+            .remove(alias)
+            .map(|item| item.as_str().unwrap_or_default().to_string()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn parses_alias_and_crate() {
+        assert_eq!(
+            parse("rg=ripgrep").unwrap(),
+            ("rg".to_string(), "ripgrep".to_string())
+        );
+        assert_eq!(
+            parse("deny = cargo-deny").unwrap(),
+            ("deny".to_string(), "cargo-deny".to_string())
+        );
+        assert_matches!(parse("rg"), Err(error::Error::InvalidAlias { .. }));
+        assert_matches!(parse("=ripgrep"), Err(error::Error::InvalidAlias { .. }));
+        assert_matches!(parse("rg=ripgrep@14"), Err(error::Error::InvalidAlias { .. }));
+        assert_matches!(parse("rg=rg"), Err(error::Error::InvalidAlias { .. }));
+    }
+
+    #[test]
+    fn add_and_remove_preserve_the_rest_of_the_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("cgx.toml");
+        std::fs::write(
+            &path,
+            r#"# Team tools
+[tools]
+ripgrep = "14"
+
+[aliases]
+rg = "ripgrep"  # muscle memory
+taplo = "taplo-cli"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            add(&path, "rg", "ripgrep-all").unwrap(),
+            Some("ripgrep".to_string())
+        );
+        assert_eq!(add(&path, "deny", "cargo-deny").unwrap(), None);
+        assert_eq!(remove(&path, "taplo").unwrap(), Some("taplo-cli".to_string()));
+        assert_eq!(remove(&path, "taplo").unwrap(), None);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"# Team tools
+[tools]
+ripgrep = "14"
+
+[aliases]
+rg = "ripgrep-all"  # muscle memory
+deny = "cargo-deny"
+"#
+        );
+    }
+
+    #[test]
+    fn remove_leaves_missing_file_missing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("cgx.toml");
+
+        assert_eq!(remove(&path, "rg").unwrap(), None);
+        assert!(!path.exists());
+    }
+}
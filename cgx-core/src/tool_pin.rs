@@ -0,0 +1,236 @@
+//! Pinning a tool's version in a config file, for `cgx --pin <TOOL>@<VERSION>`.
+//!
+//! The tool's entry in the `[tools]` table is edited in place with [`config_edit`], so everything
+//! else in the file, comments and formatting included, is left as it was.
+
+use crate::{Result, cli::CliArgs, config::Config, config_edit, cratespec, error};
+use std::path::{Path, PathBuf};
+use toml_edit::Item;
+
+/// Split a `<TOOL>@<VERSION>` argument into the tool's name and its version requirement.
+///
+/// The requirement is returned as it was written, since that's what goes in the config file; it's
+/// only parsed to check that it's valid.
+pub fn parse(spec: &str) -> Result<(String, String)> {
+    let Some((name, version)) = spec
+        .split_once('@')
+        .filter(|(name, version)| !name.is_empty() && !version.is_empty())
+    else {
+        return error::InvalidToolPinSnafu { spec }.fail();
+    };
+
+    cratespec::parse_version_req(version)?;
+
+    Ok((name.to_string(), version.trim().to_string()))
+}
+
+/// The config file `cgx --pin` writes to, when run in `cwd`.  The other commands that edit the
+/// config, like `--alias`, write to the same one.
+///
+/// That's the file given with `--config-file`, or with `--global` the user config file.
+/// Otherwise it's the closest `cgx.toml` in `cwd` or its ancestors, which is the one that takes
+/// precedence there, or a new one in `cwd` if there isn't one.
+pub fn config_file(cwd: &Path, args: &CliArgs) -> Result<PathBuf> {
+    if let Some(config_file) = &args.config_file {
+        return Ok(config_file.clone());
+    }
+
+    if args.global {
+        return Ok(Config::user_config_dir(args)?.join("cgx.toml"));
+    }
+
+    Ok(cwd
+        .ancestors()
+        .map(|dir| dir.join("cgx.toml"))
+        .find(|config_file| config_file.is_file())
+        .unwrap_or_else(|| cwd.join("cgx.toml")))
+}
+
+/// Pin the tool `name` to `version` in the config file at `path`, creating the file if need be.
+///
+/// A tool that was only given a version has it replaced.  One with a detailed entry keeps its
+/// other settings and only has its `version` set; if it comes from git or a local directory,
+/// there's no version to pin, so that's an error.
+pub fn pin(path: &Path, name: &str, version: &str) -> Result<()> {
+    config_edit::edit(path, |document| {
+        let tools = config_edit::table_mut(document, path, "tools")?;
+
+        match tools.get_mut(name) {
+            Some(item) => match item.as_table_like_mut() {
+                Some(entry) => {
+                    if let Some(source) = ["git", "path"].into_iter().find(|key| entry.contains_key(key)) {
+                        return error::ConfigFileEditSnafu {
+                            path,
+                            message: format!(
+                                "tool '{name}' is built from its `{source}`, which has no version to pin"
+                            ),
+                        }
+                        .fail();
+                    }
+                    match entry.get_mut("version") {
+                        Some(item) => set_version(item, version),
+                        None => {
+                            entry.insert("version", toml_edit::value(version));
+                        }
+                    }
+                }
+                None => set_version(item, version),
+            },
+            None => {
+                tools.insert(name, toml_edit::value(version));
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Remove the version the tool `name` is pinned to in the config file at `path`, and return
+/// whether it was pinned there.
+///
+/// A tool that was only given a version has its entry removed.  One with a detailed entry keeps
+/// its other settings and only loses its `version`, unless that was all there was to it.
+pub fn unpin(path: &Path, name: &str) -> Result<bool> {
+    config_edit::edit(path, |document| {
+        let Some(tools) = document.get_mut("tools").and_then(Item::as_table_like_mut) else {
+            return Ok(false);
+        };
+
+        let Some(item) = tools.get_mut(name) else {
+            return Ok(false);
+        };
+        let Some(entry) = item.as_table_like_mut() else {
+            tools.remove(name);
+            return Ok(true);
+        };
+
+        let unpinned = entry.remove("version").is_some();
+        if entry.is_empty() {
+            tools.remove(name);
+        }
+        Ok(unpinned)
+    })
+}
+
+/// Replace the value of `item` with `version`, keeping the whitespace and any comment around it.
+fn set_version(item: &mut Item, version: &str) {
+    let mut value = toml_edit::Value::from(version);
+    if let Some(old) = item.as_value() {
+        *value.decor_mut() = old.decor().clone();
+    }
+    *item = Item::Value(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn parses_tool_and_version() {
+        assert_eq!(
+            parse("ripgrep@14.1").unwrap(),
+            ("ripgrep".to_string(), "14.1".to_string())
+        );
+        assert_matches!(parse("ripgrep"), Err(error::Error::InvalidToolPin { .. }));
+        assert_matches!(parse("@14"), Err(error::Error::InvalidToolPin { .. }));
+        assert_matches!(
+            parse("ripgrep@latest"),
+            Err(error::Error::InvalidVersionReq { .. })
+        );
+    }
+
+    #[test]
+    fn pin_preserves_the_rest_of_the_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("cgx.toml");
+        std::fs::write(
+            &path,
+            r#"# Team tools
+offline = false
+
+[tools]
+ripgrep = "13"  # keep in sync with CI
+just = { version = "1.36", features = ["cli"] }
+
+[tools.taplo-cli]
+version = "0.9"
+registry = "internal"
+"#,
+        )
+        .unwrap();
+
+        pin(&path, "ripgrep", "=14.1.0").unwrap();
+        pin(&path, "just", "1.38").unwrap();
+        pin(&path, "taplo-cli", "0.10").unwrap();
+        pin(&path, "cargo-deny", "0.16").unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"# Team tools
+offline = false
+
+[tools]
+ripgrep = "=14.1.0"  # keep in sync with CI
+just = { version = "1.38", features = ["cli"] }
+cargo-deny = "0.16"
+
+[tools.taplo-cli]
+version = "0.10"
+registry = "internal"
+"#
+        );
+    }
+
+    #[test]
+    fn pin_creates_file_and_refuses_git_tools() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config/cgx.toml");
+
+        pin(&path, "ripgrep", "14").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[tools]\nripgrep = \"14\"\n"
+        );
+
+        std::fs::write(
+            &path,
+            "[tools]\nmy-tool = { git = \"https://github.com/me/my-tool\" }\n",
+        )
+        .unwrap();
+        assert_matches!(
+            pin(&path, "my-tool", "1"),
+            Err(error::Error::ConfigFileEdit { .. })
+        );
+    }
+
+    #[test]
+    fn unpin_keeps_other_settings() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("cgx.toml");
+        std::fs::write(
+            &path,
+            r#"[tools]
+ripgrep = "14"  # fast grep
+just = { version = "1.38", features = ["cli"] }
+taplo-cli = { version = "0.10" }
+my-tool = { git = "https://github.com/me/my-tool" }
+"#,
+        )
+        .unwrap();
+
+        assert!(unpin(&path, "ripgrep").unwrap());
+        assert!(unpin(&path, "just").unwrap());
+        assert!(unpin(&path, "taplo-cli").unwrap());
+        assert!(!unpin(&path, "my-tool").unwrap());
+        assert!(!unpin(&path, "cargo-deny").unwrap());
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"[tools]
+just = { features = ["cli"] }
+my-tool = { git = "https://github.com/me/my-tool" }
+"#
+        );
+    }
+}
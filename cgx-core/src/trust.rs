@@ -0,0 +1,157 @@
+//! Tracking of the directories whose `cgx.toml` files the user has approved.
+//!
+//! cgx merges every `cgx.toml` between the filesystem root and the current directory, so without
+//! a trust model merely running cgx inside a freshly cloned repository would let that repository
+//! change which tools, versions, and sources cgx uses.  Config files in directories that have not
+//! been approved with `cgx --trust <DIR>` are instead handled according to the
+//! [`UntrustedConfigPolicy`](crate::config::UntrustedConfigPolicy).
+//!
+//! Approved directories are recorded in `trusted.toml` in the user config directory.
+
+use crate::{Result, error, helpers};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+/// Name of the file in the user config directory that holds the trusted directories.
+const TRUST_FILE_NAME: &str = "trusted.toml";
+
+/// On-disk representation of the trust store.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct TrustFile {
+    directories: BTreeSet<PathBuf>,
+}
+
+/// The set of directories whose `cgx.toml` files are trusted.
+///
+/// Trust is granted per directory, not per subtree: trusting `~/src` does not implicitly trust
+/// a repository later cloned into `~/src/foo`.
+#[derive(Debug, Clone)]
+pub struct TrustStore {
+    path: PathBuf,
+    directories: BTreeSet<PathBuf>,
+}
+
+impl TrustStore {
+    /// Load the trust store kept in `config_dir`.  If there is none yet, nothing is trusted.
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let path = config_dir.join(TRUST_FILE_NAME);
+
+        let directories = match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                toml::from_str::<TrustFile>(&contents)
+                    .with_context(|_| error::TrustStoreParseSnafu { path: path.clone() })?
+                    .directories
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(e) => return Err(e).context(error::IoSnafu { path }),
+        };
+
+        Ok(Self { path, directories })
+    }
+
+    /// Path of the file backing this trust store.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether config files in `dir` are trusted.
+    pub fn is_trusted(&self, dir: &Path) -> bool {
+        self.directories.contains(&helpers::canonicalize_lenient(dir))
+    }
+
+    /// Trust the config files in `dir` and persist the change.
+    ///
+    /// Returns the canonical path that was recorded.  Trusting a directory that is already
+    /// trusted is not an error.
+    pub fn trust(&mut self, dir: &Path) -> Result<PathBuf> {
+        // Unlike `is_trusted`, the directory must exist, so a typo doesn't silently record a
+        // path that will never match anything.
+        std::fs::metadata(dir).context(error::IoSnafu { path: dir })?;
+        let dir = helpers::canonicalize_lenient(dir);
+
+        if self.directories.insert(dir.clone()) {
+            self.save()?;
+        }
+
+        Ok(dir)
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).context(error::IoSnafu { path: parent })?;
+        }
+
+        let contents = toml::to_string(&TrustFile {
+            directories: self.directories.clone(),
+        })
+        .context(error::TrustStoreSerializeSnafu)?;
+
+        std::fs::write(&self.path, contents).context(error::IoSnafu { path: &self.path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn test_missing_store_trusts_nothing() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let store = TrustStore::load(config_dir.path()).unwrap();
+
+        assert!(!store.is_trusted(config_dir.path()));
+    }
+
+    #[test]
+    fn test_trust_persists() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+
+        let mut store = TrustStore::load(config_dir.path()).unwrap();
+        store.trust(project.path()).unwrap();
+        assert!(store.is_trusted(project.path()));
+
+        let reloaded = TrustStore::load(config_dir.path()).unwrap();
+        assert!(reloaded.is_trusted(project.path()));
+        assert!(!reloaded.is_trusted(&project.path().join("subdir")));
+        assert!(!reloaded.is_trusted(config_dir.path()));
+    }
+
+    #[test]
+    fn test_trust_is_idempotent() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+
+        let mut store = TrustStore::load(config_dir.path()).unwrap();
+        let first = store.trust(project.path()).unwrap();
+        let second = store.trust(project.path()).unwrap();
+        assert_eq!(first, second);
+
+        let reloaded = TrustStore::load(config_dir.path()).unwrap();
+        assert_eq!(reloaded.directories.len(), 1);
+    }
+
+    #[test]
+    fn test_trust_nonexistent_dir_fails() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let mut store = TrustStore::load(config_dir.path()).unwrap();
+
+        let result = store.trust(&config_dir.path().join("does-not-exist"));
+        assert_matches!(result, Err(error::Error::Io { .. }));
+    }
+
+    #[test]
+    fn test_invalid_store_fails() {
+        let config_dir = tempfile::tempdir().unwrap();
+        std::fs::write(config_dir.path().join(TRUST_FILE_NAME), "directories = 5").unwrap();
+
+        let result = TrustStore::load(config_dir.path());
+        assert_matches!(result, Err(error::Error::TrustStoreParse { .. }));
+    }
+}
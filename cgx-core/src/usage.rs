@@ -0,0 +1,199 @@
+//! A record of the crates run without a pin, for suggesting which ones are worth adding to
+//! `[tools]`.
+//!
+//! Each time a crate from a registry is run that isn't in `[tools]`, the version it resolved to is
+//! counted in `usage.json` in [`Config::cache_dir`].  Once a crate has been run that way
+//! [`SUGGEST_AFTER_RUNS`] times, cgx suggests pinning it to the version run most often, just the
+//! once.  Nothing but crate names, versions, counts and when each crate was last run is kept, it
+//! never leaves this machine, and a crate that hasn't been run for [`FORGET_AFTER`] is forgotten.
+//! Nothing is recorded at all unless `suggest_pins = true`, since recording is a read and a write
+//! of the file on every run, which isn't locked against other runs at the same time.
+
+use crate::{
+    Result,
+    config::Config,
+    crate_resolver::{ResolvedCrate, ResolvedSource},
+    error,
+    helpers::long_path,
+};
+use chrono::{DateTime, Utc};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::{collections::BTreeMap, fs, path::Path, time::Duration};
+
+/// How many times a crate is run without a pin before pinning it is suggested.
+pub const SUGGEST_AFTER_RUNS: u32 = 5;
+
+/// How long after it was last run a crate is forgotten.
+pub const FORGET_AFTER: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Name of the file in [`Config::cache_dir`] that the runs are recorded in.
+const USAGE_FILE_NAME: &str = "usage.json";
+
+/// A suggestion to pin a crate that keeps being run without one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinSuggestion {
+    pub name: String,
+
+    /// The version that's been run most often.
+    pub version: Version,
+
+    /// How many times the crate has been run.
+    pub runs: u32,
+}
+
+/// The contents of `usage.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageHistory {
+    crates: BTreeMap<String, CrateUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CrateUsage {
+    /// How many times each version has been run.
+    runs: BTreeMap<String, u32>,
+
+    last_run: DateTime<Utc>,
+
+    /// Whether pinning the crate has been suggested already.
+    suggested: bool,
+}
+
+impl CrateUsage {
+    /// The version run most often, the newest of them if that's a tie.
+    fn most_used_version(&self) -> Option<Version> {
+        self.runs
+            .iter()
+            .filter_map(|(version, count)| Version::parse(version).ok().map(|version| (*count, version)))
+            .max()
+            .map(|(_, version)| version)
+    }
+}
+
+/// Record a run of `resolved`, and return a suggestion to pin it if one is due.
+///
+/// Only crates from a registry that aren't in [`Config::tools`] are recorded, since those from
+/// git or a local directory can't be pinned to a version the same way.
+pub fn record(config: &Config, resolved: &ResolvedCrate) -> Result<Option<PinSuggestion>> {
+    if !config.suggest_pins
+        || config.tools.contains_key(&resolved.name)
+        || !matches!(
+            resolved.source,
+            ResolvedSource::CratesIo | ResolvedSource::Registry { .. }
+        )
+    {
+        return Ok(None);
+    }
+
+    let path = config.cache_dir.join(USAGE_FILE_NAME);
+    let mut history = load(&path);
+    let now = Utc::now();
+    history.crates.retain(|_, usage| {
+        now.signed_duration_since(usage.last_run)
+            .to_std()
+            .unwrap_or_default()
+            < FORGET_AFTER
+    });
+
+    let usage = history
+        .crates
+        .entry(resolved.name.clone())
+        .or_insert_with(|| CrateUsage {
+            runs: BTreeMap::new(),
+            last_run: now,
+            suggested: false,
+        });
+    *usage.runs.entry(resolved.version.to_string()).or_default() += 1;
+    usage.last_run = now;
+
+    let runs = usage.runs.values().sum();
+    let suggestion = if !usage.suggested && runs >= SUGGEST_AFTER_RUNS {
+        usage.suggested = true;
+        usage.most_used_version().map(|version| PinSuggestion {
+            name: resolved.name.clone(),
+            version,
+            runs,
+        })
+    } else {
+        None
+    };
+
+    save(&path, &history)?;
+
+    Ok(suggestion)
+}
+
+/// Read the history at `path`, starting afresh if there's none or it can't be read.
+fn load(path: &Path) -> UsageHistory {
+    let Ok(json) = fs::read_to_string(long_path(path)) else {
+        return UsageHistory::default();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        tracing::debug!(path = %path.display(), "Ignoring invalid usage history: {}", e);
+        UsageHistory::default()
+    })
+}
+
+/// Write `history` to `path`, replacing it atomically so that a cgx running at the same time never
+/// reads it half written.
+///
+/// Two runs that finish at the same time can still each miss the other's run, which only delays a
+/// suggestion.
+fn save(path: &Path, history: &UsageHistory) -> Result<()> {
+    let json = serde_json::to_string_pretty(history).context(error::JsonSnafu)?;
+    let temp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+
+    fs::write(long_path(&temp_path), json).context(error::IoSnafu { path: &temp_path })?;
+    fs::rename(long_path(&temp_path), long_path(path)).context(error::RenameFileSnafu {
+        src: &temp_path,
+        dst: path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ToolConfig;
+
+    fn resolved(name: &str, version: &str) -> ResolvedCrate {
+        ResolvedCrate {
+            name: name.to_string(),
+            version: Version::parse(version).unwrap(),
+            source: ResolvedSource::CratesIo,
+        }
+    }
+
+    #[test]
+    fn suggests_most_used_version_once() {
+        let (_temp_dir, mut config) = crate::config::create_test_env();
+        fs::create_dir_all(&config.cache_dir).unwrap();
+        config.suggest_pins = true;
+
+        for version in ["14.1.0", "14.1.1", "14.1.1", "14.1.0"] {
+            assert_eq!(record(&config, &resolved("ripgrep", version)).unwrap(), None);
+        }
+        assert_eq!(
+            record(&config, &resolved("ripgrep", "14.1.1")).unwrap(),
+            Some(PinSuggestion {
+                name: "ripgrep".to_string(),
+                version: Version::parse("14.1.1").unwrap(),
+                runs: 5,
+            })
+        );
+        assert_eq!(record(&config, &resolved("ripgrep", "14.1.1")).unwrap(), None);
+
+        // Pinned tools aren't counted, and nothing is with suggestions turned off
+        config
+            .tools
+            .insert("taplo-cli".to_string(), ToolConfig::Version("0.9".to_string()));
+        for _ in 0..SUGGEST_AFTER_RUNS {
+            assert_eq!(record(&config, &resolved("taplo-cli", "0.9.3")).unwrap(), None);
+        }
+        config.suggest_pins = false;
+        record(&config, &resolved("typos-cli", "1.0.0")).unwrap();
+
+        let history = load(&config.cache_dir.join(USAGE_FILE_NAME));
+        assert_eq!(history.crates.keys().collect::<Vec<_>>(), vec!["ripgrep"]);
+    }
+}
@@ -0,0 +1,223 @@
+//! Vendoring the dependencies of crates built from source, so that they can be built again
+//! offline.
+//!
+//! With `vendor_dependencies` enabled (`--vendor`), the first time a crate version is built while
+//! online, `cargo vendor` copies every dependency it needs into [`Config::cache_dir`], under
+//! `vendor/<crate>-<version>/<source hash>/`.  From then on every build of that crate version,
+//! online or `--offline`, is pointed at those copies with the cargo config `cargo vendor` prints
+//! (see [`crate::builder::BuildOptions::vendor_config`]), so building it again with other
+//! features or another profile doesn't depend on cargo's registry cache still having them.
+//!
+//! The dependencies are the ones the crate's `Cargo.lock` names, and they're kept for
+//! `vendor_cache_timeout` after a build last used them.  Those that have gone unused for longer
+//! are removed whenever another crate's dependencies are vendored.
+
+use crate::{
+    Result, builder::BuildOptions, cargo::CargoRunner, config::Config, downloader::DownloadedCrate, error,
+    helpers::long_path,
+};
+use snafu::ResultExt;
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tracing::*;
+
+/// Name of the directory in [`Config::cache_dir`] that vendored dependencies are kept in.
+pub(crate) const VENDOR_DIR_NAME: &str = "vendor";
+
+/// Name of the directory, in the vendor directory of a crate, that holds the dependencies.
+const SOURCES_DIR_NAME: &str = "sources";
+
+/// Name of the file, in the vendor directory of a crate, that holds the cargo config to build
+/// with the vendored dependencies.
+///
+/// It's written once they've all been vendored, so a directory without one was never finished.
+/// Its modification time is when the dependencies were last used.
+const CONFIG_FILE_NAME: &str = "cargo-config.toml";
+
+/// The cargo config that has `krate` built with the vendored dependencies in `vendor_dir`,
+/// vendoring them first if that hasn't been done yet.
+///
+/// Returns `None` if they haven't been vendored and can't be now, because of `--offline` or
+/// because `cargo vendor` failed.  The failure is only warned about, since the crate can still be
+/// built the way it would have been without vendoring.
+pub(crate) fn vendor_config(
+    cargo_runner: &dyn CargoRunner,
+    config: &Config,
+    vendor_dir: &Path,
+    krate: &DownloadedCrate,
+    options: &BuildOptions,
+) -> Result<Option<PathBuf>> {
+    let config_path = vendor_dir.join(CONFIG_FILE_NAME);
+    if config_path.is_file() && (options.offline || !config.refresh.source) {
+        mark_used(&config_path);
+        return Ok(Some(config_path));
+    }
+    if options.offline {
+        debug!(
+            "The dependencies of {} {} haven't been vendored, and can't be offline",
+            krate.resolved.name, krate.resolved.version
+        );
+        return Ok(None);
+    }
+
+    match vendor(cargo_runner, vendor_dir, krate, options) {
+        Ok(()) => {}
+        Err(e @ error::Error::Cancelled) => {
+            let _ = fs::remove_dir_all(long_path(vendor_dir));
+            return Err(e);
+        }
+        Err(e) => {
+            warn!("{}; building without vendored dependencies", e);
+            let _ = fs::remove_dir_all(long_path(vendor_dir));
+            return Ok(None);
+        }
+    }
+
+    prune(
+        &config.cache_dir.join(VENDOR_DIR_NAME),
+        config.vendor_cache_timeout,
+        vendor_dir,
+    );
+
+    Ok(Some(config_path))
+}
+
+/// Vendor the dependencies of `krate` into `vendor_dir`, replacing whatever is there.
+fn vendor(
+    cargo_runner: &dyn CargoRunner,
+    vendor_dir: &Path,
+    krate: &DownloadedCrate,
+    options: &BuildOptions,
+) -> Result<()> {
+    if vendor_dir.exists() {
+        fs::remove_dir_all(long_path(vendor_dir)).context(error::IoSnafu { path: vendor_dir })?;
+    }
+    fs::create_dir_all(long_path(vendor_dir)).context(error::IoSnafu { path: vendor_dir })?;
+
+    info!(
+        "Vendoring the dependencies of {} {} into {}",
+        krate.resolved.name,
+        krate.resolved.version,
+        vendor_dir.display()
+    );
+    let cargo_config = cargo_runner.vendor(&krate.crate_path, &vendor_dir.join(SOURCES_DIR_NAME), options)?;
+
+    let config_path = vendor_dir.join(CONFIG_FILE_NAME);
+    fs::write(long_path(&config_path), cargo_config).context(error::IoSnafu { path: &config_path })
+}
+
+/// Record that the vendored dependencies whose cargo config is at `config_path` were just used.
+fn mark_used(config_path: &Path) {
+    if let Err(e) = File::options()
+        .write(true)
+        .open(long_path(config_path))
+        .and_then(|file| file.set_modified(SystemTime::now()))
+    {
+        debug!(
+            "Failed to record the use of vendored dependencies in {}: {}",
+            config_path.display(),
+            e
+        );
+    }
+}
+
+/// Remove the vendored dependencies in `root`, other than those in `keep`, that haven't been used
+/// for `max_age`.
+///
+/// Failures are only logged; whatever can't be removed now is tried again next time.
+fn prune(root: &Path, max_age: Duration, keep: &Path) {
+    let Ok(crate_dirs) = fs::read_dir(long_path(root)) else {
+        return;
+    };
+
+    for crate_dir in crate_dirs.flatten().map(|entry| entry.path()) {
+        let Ok(vendor_dirs) = fs::read_dir(long_path(&crate_dir)) else {
+            continue;
+        };
+
+        for vendor_dir in vendor_dirs.flatten().map(|entry| entry.path()) {
+            let unused_for = last_used(&vendor_dir).and_then(|time| time.elapsed().ok());
+            if vendor_dir == keep || unused_for.is_none_or(|unused_for| unused_for <= max_age) {
+                continue;
+            }
+
+            debug!(
+                "Removing vendored dependencies in {}, unused for over {:?}",
+                vendor_dir.display(),
+                max_age
+            );
+            if let Err(e) = fs::remove_dir_all(long_path(&vendor_dir)) {
+                warn!(
+                    "Failed to remove vendored dependencies in {}: {}",
+                    vendor_dir.display(),
+                    e
+                );
+            }
+        }
+
+        // Only succeeds once every version of the crate is gone
+        let _ = fs::remove_dir(long_path(&crate_dir));
+    }
+}
+
+/// When the vendored dependencies in `vendor_dir` were last used, or for those that were never
+/// finished, when vendoring them started.
+fn last_used(vendor_dir: &Path) -> Option<SystemTime> {
+    fs::metadata(long_path(&vendor_dir.join(CONFIG_FILE_NAME)))
+        .or_else(|_| fs::metadata(long_path(vendor_dir)))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A finished vendor directory for `name` in `root`, last used `age` ago.
+    fn vendored(root: &Path, name: &str, age: Duration) -> PathBuf {
+        let vendor_dir = root.join(format!("{}-1.0.0", name)).join("0123456789abcdef");
+        fs::create_dir_all(vendor_dir.join(SOURCES_DIR_NAME).join("serde")).unwrap();
+        let config_path = vendor_dir.join(CONFIG_FILE_NAME);
+        fs::write(&config_path, "[source.crates-io]\n").unwrap();
+        File::options()
+            .write(true)
+            .open(&config_path)
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+        vendor_dir
+    }
+
+    #[test]
+    fn prunes_only_unused_vendor_dirs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let max_age = Duration::from_secs(24 * 60 * 60);
+
+        let recent = vendored(root, "recent", Duration::from_secs(60));
+        let stale = vendored(root, "stale", 2 * max_age);
+        let kept = vendored(root, "kept", 2 * max_age);
+
+        prune(root, max_age, &kept);
+
+        assert!(recent.join(CONFIG_FILE_NAME).exists());
+        assert!(kept.join(CONFIG_FILE_NAME).exists());
+        assert!(!stale.exists());
+        assert!(!root.join("stale-1.0.0").exists());
+    }
+
+    #[test]
+    fn using_vendored_dependencies_marks_them_used() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let max_age = Duration::from_secs(24 * 60 * 60);
+        let vendor_dir = vendored(temp_dir.path(), "tool", 2 * max_age);
+
+        mark_used(&vendor_dir.join(CONFIG_FILE_NAME));
+        prune(temp_dir.path(), max_age, Path::new(""));
+
+        assert!(vendor_dir.join(CONFIG_FILE_NAME).exists());
+    }
+}
@@ -1,11 +1,15 @@
-use vergen_gix::{Emitter, GixBuilder};
+use vergen_gix::{BuildBuilder, Emitter, GixBuilder};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Generate the git version information which will be available at compile time in env vars
+    // Generate the git and build information which will be available at compile time in env vars
     // that are used to construct the `--version` output of the binary.
+    let build = BuildBuilder::default().build_date(true).build()?;
     let gix = GixBuilder::default().sha(true).commit_date(true).build()?;
 
-    Emitter::default().add_instructions(&gix)?.emit()?;
+    Emitter::default()
+        .add_instructions(&build)?
+        .add_instructions(&gix)?
+        .emit()?;
 
     Ok(())
 }
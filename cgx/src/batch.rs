@@ -0,0 +1,408 @@
+//! Getting the binaries of many tools in one run, for `cgx --batch`.
+//!
+//! A batch file lists one tool per line, as the arguments that would follow `cgx` on the command
+//! line.  The whole file is parsed and checked up front, so that a typo on the last line doesn't
+//! surface only after everything before it has been built.  The tools are then resolved and built
+//! one after the other with a single [`Cgx`] instance, and a summary of the outcome for each one is
+//! printed at the end.
+//!
+//! A batch file can also be a manifest written by `cgx --freeze`, which is read as a line for each
+//! tool in it that gets exactly the version it was frozen at.  The entries for `--freeze` itself,
+//! one per tool in the `[tools]` table, are made here too, as are those for `--shell` and `--emit`.
+
+use cgx_core::{
+    Cgx,
+    builder::BuildOptions,
+    cli::{CliArgs, EmitFormat},
+    config::Config,
+    cratespec::CrateSpec,
+    error::{self, Result},
+    freeze::{FrozenManifest, FrozenTool},
+};
+use snafu::ResultExt;
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fmt::Write as _,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// One tool to get, from one line of a batch file.
+#[derive(Debug)]
+pub(crate) struct BatchEntry {
+    /// The line of the batch file, trimmed, to identify the tool in the summary.
+    line: String,
+    crate_specs: Vec<CrateSpec>,
+    build_options: BuildOptions,
+}
+
+/// The outcome of getting one tool in a batch.
+#[derive(Debug)]
+pub(crate) struct BatchResult<'a> {
+    entry: &'a BatchEntry,
+    result: Result<PathBuf>,
+}
+
+impl BatchResult<'_> {
+    pub(crate) fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Read and check the batch file at `path`, or stdin if `path` is `-`.
+///
+/// `jobs` is the `--jobs` given to `cgx --batch` itself, which lines that don't give their own
+/// are built with.
+pub(crate) fn load(
+    path: &Path,
+    config: &Config,
+    verbose: u8,
+    jobs: Option<usize>,
+) -> Result<Vec<BatchEntry>> {
+    let contents = if path == Path::new("-") {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .context(error::IoSnafu { path })?;
+        contents
+    } else {
+        std::fs::read_to_string(path).context(error::IoSnafu { path })?
+    };
+
+    if FrozenManifest::is_manifest(path, &contents) {
+        // A manifest has no lines to speak of, so an entry is numbered by its place in it
+        return FrozenManifest::parse(path, &contents)?
+            .batch_lines()
+            .iter()
+            .enumerate()
+            .map(|(index, line)| {
+                parse_entry(line, config, verbose, jobs)
+                    .map_err(Box::new)
+                    .context(error::InvalidBatchEntrySnafu {
+                        path,
+                        line_number: index + 1,
+                    })
+            })
+            .collect();
+    }
+
+    contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            parse_entry(line, config, verbose, jobs)
+                .map_err(Box::new)
+                .context(error::InvalidBatchEntrySnafu { path, line_number })
+        })
+        .collect()
+}
+
+/// An entry for each tool in the `[tools]` table, in order of name, for `--freeze`, `--shell` and
+/// `--emit`, built with `jobs` as in [`load`].
+pub(crate) fn tools(config: &Config, verbose: u8, jobs: Option<usize>) -> Result<Vec<BatchEntry>> {
+    let mut names: Vec<&String> = config.tools.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| parse_entry(name, config, verbose, jobs))
+        .collect()
+}
+
+fn parse_entry(line: &str, config: &Config, verbose: u8, jobs: Option<usize>) -> Result<BatchEntry> {
+    let args = CliArgs::parse_batch_line(line)?;
+    let crate_specs = CrateSpec::load_candidates(config, &args)?;
+
+    let tool = crate_specs.first().and_then(|crate_spec| crate_spec.name());
+    let mut build_options = BuildOptions::load(config, &args.build_options.for_tool(config, tool), verbose)?;
+    build_options.jobs = build_options.jobs.or(jobs);
+
+    Ok(BatchEntry {
+        line: line.to_string(),
+        crate_specs,
+        build_options,
+    })
+}
+
+/// Get the binary of every tool in the batch, carrying on past any failures.
+pub(crate) fn run<'a>(cgx: &Cgx, entries: &'a [BatchEntry]) -> Vec<BatchResult<'a>> {
+    entries
+        .iter()
+        .map(|entry| BatchResult {
+            entry,
+            result: cgx.crate_to_bin(&entry.crate_specs, &entry.build_options),
+        })
+        .collect()
+}
+
+/// Get the binary of every tool in `entries`, as made by [`tools`], and freeze each one to exactly
+/// what it resolved to, as of the date in `config` if there is one.
+///
+/// Unlike a batch, this stops at the first tool that fails, since a manifest missing a tool
+/// would be no use.
+pub(crate) fn freeze(cgx: &Cgx, config: &Config, entries: &[BatchEntry]) -> Result<FrozenManifest> {
+    let tools = entries
+        .iter()
+        .map(|entry| {
+            let tool = cgx.freeze_tool(&entry.crate_specs, &entry.build_options)?;
+            Ok((entry.line.clone(), tool))
+        })
+        .collect::<Result<BTreeMap<String, FrozenTool>>>()?;
+
+    Ok(FrozenManifest {
+        as_of: config.as_of,
+        tools,
+    })
+}
+
+/// Get the binary of every tool in `entries`, as made by [`tools`], for `--shell`, with the name
+/// each one should be on `PATH` as: the tool's `argv0` if it has one, or else the binary's own.
+///
+/// As with [`freeze`], this stops at the first tool that fails.
+pub(crate) fn shell_binaries(
+    cgx: &Cgx,
+    config: &Config,
+    entries: &[BatchEntry],
+) -> Result<Vec<(OsString, PathBuf)>> {
+    entries
+        .iter()
+        .map(|entry| {
+            let bin_path = cgx.crate_to_bin(&entry.crate_specs, &entry.build_options)?;
+            let name = match config.tool_argv0(&entry.line) {
+                Some(argv0) => OsString::from(format!("{}{}", argv0, std::env::consts::EXE_SUFFIX)),
+                None => bin_path.file_name().unwrap_or_default().to_owned(),
+            };
+            Ok((name, bin_path))
+        })
+        .collect()
+}
+
+/// Get the binary of every tool in `entries`, for `--emit`, keyed by the name of the variable its
+/// path goes in: the tool's name, or else its binary's, in upper case with anything but letters
+/// and digits as `_`.
+///
+/// As with [`freeze`], this stops at the first tool that fails.
+pub(crate) fn emit_variables(cgx: &Cgx, entries: &[BatchEntry]) -> Result<BTreeMap<String, PathBuf>> {
+    entries
+        .iter()
+        .map(|entry| {
+            let bin_path = cgx.crate_to_bin(&entry.crate_specs, &entry.build_options)?;
+            let name = match entry.crate_specs.first().and_then(|crate_spec| crate_spec.name()) {
+                Some(name) => name.to_string(),
+                None => bin_path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+            };
+            Ok((variable_name(&name), bin_path))
+        })
+        .collect()
+}
+
+/// Write the variables from [`emit_variables`] in `format`.
+pub(crate) fn format_variables(variables: &BTreeMap<String, PathBuf>, format: EmitFormat) -> String {
+    match format {
+        EmitFormat::ShellEnv => variables.iter().fold(String::new(), |mut exports, (name, path)| {
+            let _ = writeln!(
+                exports,
+                "export {}={}",
+                name,
+                shell_quote(&path.to_string_lossy())
+            );
+            exports
+        }),
+        EmitFormat::Json => {
+            let variables: BTreeMap<&str, String> = variables
+                .iter()
+                .map(|(name, path)| (name.as_str(), path.to_string_lossy().into_owned()))
+                .collect();
+            let mut json =
+                serde_json::to_string_pretty(&variables).expect("BUG: variables are always valid JSON");
+            json.push('\n');
+            json
+        }
+    }
+}
+
+/// The name of the variable for the tool `name`, like `TAPLO_CLI` for `taplo-cli`.
+fn variable_name(name: &str) -> String {
+    let variable: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if variable.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", variable)
+    } else {
+        variable
+    }
+}
+
+/// `value` as a word of a POSIX shell command, quoted only if it has to be.
+fn shell_quote(value: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "/._-+:@,%=".contains(c);
+    if !value.is_empty() && value.chars().all(is_plain) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// Print the outcome for each tool, and fail if any of them failed.
+pub(crate) fn summarize(results: &[BatchResult<'_>]) -> Result<()> {
+    let width = results
+        .iter()
+        .map(|result| result.entry.line.len())
+        .max()
+        .unwrap_or_default();
+
+    for result in results {
+        match &result.result {
+            Ok(bin_path) => println!("ok      {:width$}  {}", result.entry.line, bin_path.display()),
+            Err(e) => {
+                let report = snafu::Report::from_error(e).to_string();
+                let report = report.trim_end().replace('\n', " ");
+                println!("FAILED  {:width$}  [{}] {}", result.entry.line, e.code(), report);
+            }
+        }
+    }
+
+    let failed = results.iter().filter(|result| !result.is_ok()).count();
+    if failed > 0 {
+        return error::BatchFailedSnafu {
+            failed,
+            total: results.len(),
+        }
+        .fail();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn load_str(contents: &str) -> Result<Vec<BatchEntry>> {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("tools.txt");
+        std::fs::write(&path, contents).unwrap();
+        load(&path, &Config::default(), 0, Some(2))
+    }
+
+    #[test]
+    fn skips_blank_lines_and_comments() {
+        let entries = load_str("# Linters\ncargo-deny@0.16\n\n  --features schema taplo-cli  \n").unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].line, "cargo-deny@0.16");
+        assert_eq!(entries[1].line, "--features schema taplo-cli");
+        assert_eq!(entries[1].build_options.features, vec!["schema".to_string()]);
+    }
+
+    #[test]
+    fn lines_without_jobs_get_the_batch_jobs() {
+        let entries = load_str(
+            "ripgrep
+-j 8 taplo-cli
+",
+        )
+        .unwrap();
+
+        assert_eq!(entries[0].build_options.jobs, Some(2));
+        assert_eq!(entries[1].build_options.jobs, Some(8));
+    }
+
+    #[test]
+    fn quoted_arguments_keep_their_spaces() {
+        let entries = load_str("--features 'schema lsp' taplo-cli\n").unwrap();
+
+        assert_eq!(
+            entries[0].build_options.features,
+            vec!["schema".to_string(), "lsp".to_string()]
+        );
+
+        assert_matches!(
+            load_str("--features 'schema taplo-cli\n"),
+            Err(error::Error::InvalidBatchEntry { line_number: 1, source, .. })
+                if matches!(*source, error::Error::InvalidBatchArgs { .. })
+        );
+    }
+
+    #[test]
+    fn invalid_line_is_reported_with_its_number() {
+        let result = load_str("ripgrep\n\n--no-such-flag ripgrep\n");
+
+        assert_matches!(
+            result,
+            Err(error::Error::InvalidBatchEntry { line_number: 3, source, .. })
+                if matches!(*source, error::Error::InvalidBatchArgs { .. })
+        );
+    }
+
+    #[test]
+    fn frozen_manifest_is_read_as_exact_versions() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("tools.toml");
+        std::fs::write(
+            &path,
+            r#"[tools.ripgrep]
+version = "14.1.1"
+sha256 = "0000000000000000000000000000000000000000000000000000000000000000"
+
+[tools.taplo-cli]
+version = "0.9.3"
+registry = "internal"
+sha256 = "0000000000000000000000000000000000000000000000000000000000000000"
+"#,
+        )
+        .unwrap();
+
+        let entries = load(&path, &Config::default(), 0, None).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].line, "ripgrep@=14.1.1");
+        assert_eq!(entries[1].line, "--registry internal taplo-cli@=0.9.3");
+    }
+
+    #[test]
+    fn variables_are_named_after_tools() {
+        assert_eq!(variable_name("ripgrep"), "RIPGREP");
+        assert_eq!(variable_name("taplo-cli"), "TAPLO_CLI");
+        assert_eq!(variable_name("7z.rs"), "_7Z_RS");
+    }
+
+    #[test]
+    fn variables_are_formatted_for_shells_and_json() {
+        let variables = BTreeMap::from([
+            ("RIPGREP".to_string(), PathBuf::from("/cache/bins/rg")),
+            (
+                "TAPLO_CLI".to_string(),
+                PathBuf::from("/home/o'brien/my bins/taplo"),
+            ),
+        ]);
+
+        assert_eq!(
+            format_variables(&variables, EmitFormat::ShellEnv),
+            "export RIPGREP=/cache/bins/rg\nexport TAPLO_CLI='/home/o'\\''brien/my bins/taplo'\n"
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&format_variables(&variables, EmitFormat::Json))
+                .unwrap(),
+            serde_json::json!({
+                "RIPGREP": "/cache/bins/rg",
+                "TAPLO_CLI": "/home/o'brien/my bins/taplo",
+            })
+        );
+    }
+}
@@ -1,14 +1,42 @@
+mod batch;
 pub mod logging;
+mod status;
+mod telemetry;
 
 use cgx_core::{
-    builder::BuildOptions,
-    cli::{CliArgs, MessageFormat},
-    config::Config,
+    assets,
+    builder::{BuildOptions, CachedBuild},
+    cli::{CliArgs, EmitFormat, FreezeFormat, MessageFormat},
+    config::{Config, ConfigSetting, ToolConfig},
     cratespec::CrateSpec,
-    error,
-    messages::{Message, MessageReporter},
+    error, git_hooks,
+    hooks::{self, Hook},
+    installer::{self, InstallManifest},
+    messages::{
+        BuildCacheMessage, CrateResolutionMessage, Message, MessageReporter, PrebuiltBinaryMessage,
+        RunEndMessage, RunnerMessage,
+    },
+    project_env,
+    runner::RunLimits,
+    shell::{self, ShellDir},
+    source_file, tool_alias, tool_pin,
+    trust::TrustStore,
+};
+use snafu::{OptionExt, ResultExt};
+use status::StatusLine;
+#[cfg(unix)]
+use std::os::fd::AsFd;
+#[cfg(windows)]
+use std::os::windows::io::AsHandle;
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    io::Write,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
-use std::io::Write;
 use tracing::*;
 
 // Re-export key types from cgx-core for convenience
@@ -31,9 +59,62 @@ pub use snafu::Report as SnafuReport;
 
 /// Main entry point for the `cgx` engine.
 ///
-/// Meant to be called from `main.rs` or other frontends.
-#[snafu::report]
-pub fn cgx_main() -> Result<()> {
+/// Meant to be called from `main.rs` or other frontends.  Failures are reported on stderr along
+/// with their error code, and turned into a failing exit code.
+pub fn cgx_main() -> ExitCode {
+    let result = run();
+    telemetry::flush();
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let code = e.code();
+            let report = SnafuReport::from_error(e).to_string();
+            eprintln!("Error [{code}]: {}", report.trim_end());
+            eprintln!();
+            eprintln!("For more information about this error, try `cgx --explain {code}`.");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Print the version of cgx itself.
+///
+/// In JSON mode this is a single object on stdout with the build metadata that tooling driving
+/// cgx programmatically needs to check compatibility; otherwise it's a human-readable line on
+/// stderr.
+fn print_version(json: bool) {
+    let version = env!("CARGO_PKG_VERSION");
+    let git_sha = vergen_value(option_env!("VERGEN_GIT_SHA"));
+    let git_commit_date = vergen_value(option_env!("VERGEN_GIT_COMMIT_DATE"));
+
+    if json {
+        let info = serde_json::json!({
+            "name": env!("CARGO_PKG_NAME"),
+            "version": version,
+            "git_sha": git_sha,
+            "git_commit_date": git_commit_date,
+            "build_date": vergen_value(option_env!("VERGEN_BUILD_DATE")),
+            "features": cgx_core::ENABLED_FEATURES,
+            "message_schema_versions": messages::SUPPORTED_MESSAGE_SCHEMA_VERSIONS,
+        });
+        println!("{info}");
+        return;
+    }
+
+    match (git_sha, git_commit_date) {
+        (Some(sha), Some(date)) => eprintln!("cgx {} ({} {})", version, sha, date),
+        _ => eprintln!("cgx {}", version),
+    }
+}
+
+/// A value emitted by vergen, unless it's the placeholder used when the information isn't
+/// available (for example when building outside of a git checkout).
+fn vergen_value(value: Option<&'static str>) -> Option<&'static str> {
+    value.filter(|value| *value != "VERGEN_IDEMPOTENT_OUTPUT")
+}
+
+fn run() -> Result<()> {
     let args = CliArgs::parse_from_cli_args();
 
     // Initialize tracing early, before any other operations
@@ -41,50 +122,265 @@ pub fn cgx_main() -> Result<()> {
 
     if let Some(version_arg) = &args.version {
         if version_arg.is_empty() {
-            let version = env!("CARGO_PKG_VERSION");
-
-            match (
-                option_env!("VERGEN_GIT_SHA"),
-                option_env!("VERGEN_GIT_COMMIT_DATE"),
-            ) {
-                (Some(sha), Some(date))
-                    if sha != "VERGEN_IDEMPOTENT_OUTPUT" && date != "VERGEN_IDEMPOTENT_OUTPUT" =>
-                {
-                    eprintln!("cgx {} ({} {})", version, sha, date);
-                }
-                _ => {
-                    eprintln!("cgx {}", version);
-                }
-            }
+            print_version(matches!(args.message_format, Some(MessageFormat::Json)));
             return Ok(());
         }
     }
 
-    let config = Config::load(&args)?;
+    if let Some(code) = &args.explain {
+        return explain(code);
+    }
+
+    if let Some(dir) = &args.trust {
+        return trust(dir, &args);
+    }
+
+    if let Some(spec) = &args.pin {
+        return pin(spec, &args);
+    }
+
+    if let Some(name) = &args.unpin {
+        return unpin(name, &args);
+    }
+
+    if let Some(spec) = &args.alias {
+        return alias(spec, &args);
+    }
+
+    if let Some(alias) = &args.unalias {
+        return unalias(alias, &args);
+    }
+
+    if args.init_env {
+        return init_env(&args);
+    }
+
+    if args.show_config {
+        return show_config(&args);
+    }
+
+    let mut config = Config::load(&args)?;
 
     // Apply log level from config file if appropriate
     logging::apply_config(&config, &args);
 
-    let crate_spec = CrateSpec::load(&config, &args)?;
-    let build_options = BuildOptions::load(&config, &args.build_options, args.verbose)?;
+    if let Some(partial) = &args.complete_crate {
+        for name in cgx_core::completion::crate_names(&config, partial) {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+
+    if args.list_aliases {
+        for (alias, target) in config.aliases.iter().collect::<BTreeMap<_, _>>() {
+            println!("{}={}", alias, target);
+        }
+        return Ok(());
+    }
+
+    if args.list_pins {
+        for (name, version) in pinned_versions(&config) {
+            println!("{}@{}", name, version);
+        }
+        return Ok(());
+    }
+
+    if args.ps {
+        for (tool, running) in cgx_core::detach::list(&config)? {
+            println!(
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                tool.name,
+                tool.pid,
+                if running { "running" } else { "exited" },
+                tool.started_at.format("%Y-%m-%dT%H:%M:%SZ"),
+                tool.stdout_log.display(),
+                tool.stderr_log.display()
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(name) = &args.stop {
+        let (tool, was_running) = cgx_core::detach::stop(&config, name)?;
+        if was_running {
+            eprintln!("Stopped {} (pid {})", tool.name, tool.pid);
+        } else {
+            eprintln!("{} (pid {}) had already exited", tool.name, tool.pid);
+        }
+        return Ok(());
+    }
+
+    if args.list_install_dir {
+        for (name, tool) in InstallManifest::load(&config.installs_dir)?.tools {
+            println!("{}@{} {}", name, tool.version, tool.binary.display());
+        }
+        return Ok(());
+    }
+
+    if let Some(name) = &args.uninstall {
+        let name = config.aliases.get(name).unwrap_or(name);
+        let tool = installer::uninstall(&config, name)?;
+        eprintln!(
+            "Uninstalled {}@{} from {}",
+            name,
+            tool.version,
+            tool.binary.display()
+        );
+        return Ok(());
+    }
+
+    // An upgrade is to whatever the installed tools resolve to now, not when they were last run
+    if args.upgrade {
+        config.refresh.resolve = true;
+    }
+
+    if args.show_metrics {
+        print!("{}", cgx_core::metrics::load(&config));
+        return Ok(());
+    }
+
+    if let Some(path) = &args.export_metrics {
+        cgx_core::metrics::export(&config, path)?;
+        eprintln!("Exported metrics to {}", path.display());
+        return Ok(());
+    }
+
+    if args.install_git_hooks {
+        return install_git_hooks(&config);
+    }
+
+    if let Some(name) = &args.git_hook {
+        let cgx_exe = std::env::current_exe().context(error::IoSnafu { path: "cgx" })?;
+        // What git passed the hook is taken for a crate spec and its arguments
+        let hook_args = args
+            .crate_spec
+            .iter()
+            .chain(&args.args)
+            .cloned()
+            .collect::<Vec<_>>();
+        return git_hooks::run(&config.hooks.git, name, &hook_args, &cgx_exe);
+    }
+
+    // A pinned build is run without resolving anything, so a crate spec is only needed to check
+    // the build against, if one was given; likewise `--list-installed` only uses it to pick the
+    // builds to show.  A batch brings its own crate specs, and `--freeze` and `--shell` take them from
+    // `[tools]`, as does `--emit` unless it's given a batch.  `--upgrade` takes them from what
+    // was installed.
+    let crate_specs = if args.upgrade
+        || (args.list_installed && args.crate_spec.is_none())
+        || args.batch.is_some()
+        || args.freeze.is_some()
+        || args.shell
+        || args.emit.is_some()
+        || (args.pin_build.is_some() && args.crate_spec.is_none())
+    {
+        Vec::new()
+    } else {
+        CrateSpec::load_candidates(&config, &args)?
+    };
+    let batch = args
+        .batch
+        .as_deref()
+        .map(|path| batch::load(path, &config, args.verbose, args.build_options.jobs))
+        .transpose()?;
+    let (batch, emit) = match args.emit {
+        Some(format) => {
+            let entries = match batch {
+                Some(entries) => entries,
+                None => batch::tools(&config, args.verbose, args.build_options.jobs)?,
+            };
+            (None, Some((format, entries)))
+        }
+        None => (batch, None),
+    };
+    let freeze = args
+        .freeze
+        .map(|format| {
+            batch::tools(&config, args.verbose, args.build_options.jobs).map(|entries| (format, entries))
+        })
+        .transpose()?;
+    let shell = args
+        .shell
+        .then(|| batch::tools(&config, args.verbose, args.build_options.jobs))
+        .transpose()?;
+    if shell.as_ref().is_some_and(|entries| entries.is_empty()) {
+        eprintln!("No tools are configured under [tools]; not starting a shell");
+        return Ok(());
+    }
+    // The name the tool is configured under, if it has one
+    let tool_name = crate_specs.first().and_then(|crate_spec| crate_spec.name());
+
+    let target_build_options = BuildOptions::load_per_target(
+        &config,
+        &args.build_options.for_tool(&config, tool_name),
+        args.verbose,
+    )?;
+    if target_build_options.len() > 1 && !args.no_exec {
+        return error::MultipleTargetsNeedNoExecSnafu {
+            targets: args.build_options.target.join(", "),
+        }
+        .fail();
+    }
+    if target_build_options.len() > 1 && args.plan.is_some() {
+        return error::MultipleTargetsNotSupportedSnafu {
+            targets: args.build_options.target.join(", "),
+        }
+        .fail();
+    }
+    let build_options = &target_build_options[0];
+    let run_limits = RunLimits::load(&args)?;
+
+    // Extract arguments to pass to the binary, before anything is built so that a bad
+    // `--args-file` fails fast
+    let binary_args = CrateSpec::get_binary_args(&args)?;
+
+    // The command to run the tool under, if any: `--wrapper` or else the tool's config
+    let wrapper = cgx_core::runner::load_wrapper(&args, &config, tool_name)?;
 
     const MESSAGE_CHANNEL_SIZE: usize = 100;
 
     // Set up a channel reporter to run in a separate thread.
     // This thread handles:
-    // 1. CargoStderrChunk messages: echoed to stderr
+    // 1. CargoStderrChunk messages: echoed to stderr, or buffered by the status line if enabled
     // 2. All messages in JSON mode: serialized to stdout
+    // 3. In interactive `--quiet` mode, the single-line status display
     let json_mode = matches!(args.message_format, Some(MessageFormat::Json));
+    let verbose = args.verbose > 0;
+    let mut status = StatusLine::is_wanted(&args).then(StatusLine::new);
+    let mut metrics = cgx_core::metrics::Recorder::new(&config);
     let (tx, rx) = std::sync::mpsc::sync_channel(MESSAGE_CHANNEL_SIZE);
     let reporter_thread = std::thread::spawn(move || {
         debug!("Starting message reporter thread");
         for msg in rx {
-            // Handle CargoStderrChunk by echoing to stderr
-            if let Message::Build(messages::BuildMessage::CargoStderr { ref bytes }) = msg {
+            if let Some(metrics) = &mut metrics {
+                metrics.observe(&msg);
+            }
+
+            if let Some(status) = &mut status {
+                status.handle(&msg);
+            } else if let Message::Build(messages::BuildMessage::CargoStderr { ref bytes }) = msg {
+                // Handle CargoStderrChunk by echoing to stderr
                 let _ = std::io::stderr().write_all(bytes);
                 let _ = std::io::stderr().flush();
             }
 
+            // Hints go above the status line, if there is one, rather than through the middle of it
+            if let Some(hint) = build_hint(&msg)
+                .or_else(|| emulation_hint(&msg))
+                .or_else(|| cached_resolution_hint(&msg))
+                .or_else(|| cached_binary_version_hint(&msg))
+                .or_else(|| pin_hint(&msg))
+                .or_else(|| successor_hint(&msg))
+                .or_else(|| release_notes_hint(&msg))
+                .or_else(|| feature_hint(&msg))
+                .or_else(|| config_hint(&msg).filter(|_| verbose))
+            {
+                match &mut status {
+                    Some(status) => status.note(&hint),
+                    None => eprintln!("note: {}", hint),
+                }
+            }
+
             // In JSON mode, serialize all messages to stdout
             if json_mode {
                 match serde_json::to_string(&msg) {
@@ -94,63 +390,1289 @@ pub fn cgx_main() -> Result<()> {
             }
         }
         debug!("Message reporter thread exiting");
+        (status, metrics)
     });
+    let reporter_thread = ReporterThread::new(reporter_thread, json_mode);
     let reporter = MessageReporter::channel(tx);
 
-    let cgx = cgx_core::Cgx::new(config, reporter.clone())?;
+    // Say which aliases and `[tools]` settings changed what was asked for, and where they're from
+    if !crate_specs.is_empty() {
+        for decision in CrateSpec::config_decisions(&config, &args) {
+            reporter.report(|| CrateResolutionMessage::config_applied(&decision));
+        }
+    }
+
+    // An ephemeral run stages its binary in a directory of its own, removed when this is dropped
+    let run_dir = args
+        .ephemeral
+        .then(|| cgx_core::ephemeral::RunDir::create(&mut config))
+        .transpose()?;
+
+    // The deadline counts from here for every crate, so that it covers a whole batch
+    let cgx = cgx_core::Cgx::builder(config.clone(), reporter.clone())
+        .deadline_from(Instant::now())
+        .build()?;
+    let session = Session {
+        cgx,
+        reporter,
+        reporter_thread,
+    };
 
     if args.list_targets {
-        let (crate_name, default, bins, examples) = cgx.list_targets(&crate_spec, &build_options)?;
+        return list_targets(session, &crate_specs, build_options);
+    }
 
-        // Ensure there are executable targets
-        if bins.is_empty() && examples.is_empty() {
-            return error::NoPackageBinariesSnafu { krate: crate_name }.fail();
-        }
+    if args.fetch_source {
+        return fetch_source(session, &crate_specs, args.out_dir.as_deref());
+    }
+
+    if let Some(path) = &args.run_source_file {
+        return run_source_file(session, &config, &crate_specs, path, &binary_args);
+    }
+
+    if let Some(entries) = batch {
+        return run_batch(session, &entries);
+    }
+
+    if let Some((format, entries)) = freeze {
+        return freeze_tools(session, &config, &entries, format);
+    }
+
+    if let Some((format, entries)) = emit {
+        return emit_tools(session, &entries, format);
+    }
+
+    if let Some(entries) = shell {
+        return run_shell(session, &config, &entries, &run_limits);
+    }
+
+    if args.list_installed {
+        return list_installed(session, tool_name, verbose);
+    }
+
+    if args.install {
+        return install(session, &crate_specs, build_options);
+    }
 
-        println!(
-            "default_run: {}",
-            default
-                .map(|target| target.name)
-                .as_deref()
-                .unwrap_or("<not set>")
+    if args.upgrade {
+        return upgrade(session, &config, args.crate_spec.as_deref());
+    }
+
+    if args.pin_build.is_none() && target_build_options.len() > 1 {
+        return build_targets(
+            session,
+            &crate_specs,
+            &target_build_options,
+            args.out_dir.as_deref(),
         );
-        // Print bins with default indication
-        for bin in bins {
-            println!("bin: {}", bin.name);
-        }
+    }
 
-        // Print examples
-        for example in examples {
-            println!("example: {}", example.name);
-        }
+    run_tool(
+        session,
+        &args,
+        &config,
+        &crate_specs,
+        build_options,
+        binary_args,
+        wrapper,
+        &run_limits,
+        run_dir,
+    )
+}
 
-        return Ok(());
+/// Print the binary and example targets of the crate.
+fn list_targets(session: Session, crate_specs: &[CrateSpec], build_options: &BuildOptions) -> Result<()> {
+    let targets = session.cgx.list_targets(crate_specs, build_options);
+    session.finish(targets.is_ok());
+    let (crate_name, default, bins, examples) = targets?;
+
+    // Ensure there are executable targets
+    if bins.is_empty() && examples.is_empty() {
+        return error::NoPackageBinariesSnafu { krate: crate_name }.fail();
+    }
+
+    println!(
+        "default_run: {}",
+        default
+            .map(|target| target.name)
+            .as_deref()
+            .unwrap_or("<not set>")
+    );
+    // Print bins with default indication
+    for bin in bins {
+        println!("bin: {}", bin.name);
+    }
+
+    // Print examples
+    for example in examples {
+        println!("example: {}", example.name);
     }
 
-    let bin_path = cgx.crate_to_bin(&crate_spec, &build_options)?;
+    Ok(())
+}
+
+/// Fetch the crate's source, into `out_dir` if one is given, and print where it is.
+fn fetch_source(session: Session, crate_specs: &[CrateSpec], out_dir: Option<&Path>) -> Result<()> {
+    let source_path = session.cgx.crate_source(crate_specs, out_dir);
+    session.finish(source_path.is_ok());
+
+    println!("{}", source_path?.display());
+    Ok(())
+}
+
+/// Fetch the crate's source and run the file `path` from it, with `args`.
+///
+/// Like running the binary, this only returns if the file couldn't be run.
+fn run_source_file(
+    session: Session,
+    config: &Config,
+    crate_specs: &[CrateSpec],
+    path: &Path,
+    args: &[OsString],
+) -> Result<()> {
+    let source_dir = session.cgx.crate_source(crate_specs, None);
+    session.finish_before_exec(source_dir.is_ok());
+    let source_dir = source_dir?;
+
+    let file = source_file::locate(&source_dir, path)?;
+    let interpreter = source_file::interpreter(config, &file)?;
+    debug!(
+        "Running {} from the crate's source{}",
+        file.display(),
+        interpreter
+            .first()
+            .map(|program| format!(" with {}", program.to_string_lossy()))
+            .unwrap_or_default()
+    );
+
+    telemetry::flush();
+    cgx_core::runner::run(&file, None, &source_file::env(&source_dir), &interpreter, args)
+}
+
+/// Get the binary of every tool in the batch `entries`, and summarize how that went.
+fn run_batch(session: Session, entries: &[batch::BatchEntry]) -> Result<()> {
+    let results = batch::run(&session.cgx, entries);
+    session.finish(results.iter().all(|result| result.is_ok()));
+    batch::summarize(&results)
+}
+
+/// Print the manifest of what every tool in `entries` resolves to now, in `format`.
+fn freeze_tools(
+    session: Session,
+    config: &Config,
+    entries: &[batch::BatchEntry],
+    format: FreezeFormat,
+) -> Result<()> {
+    let manifest = batch::freeze(&session.cgx, config, entries);
+    session.finish(manifest.is_ok());
+
+    print!("{}", manifest?.to_string(format));
+    Ok(())
+}
+
+/// Get the binary of every tool in `entries`, and print the variables pointing at them in
+/// `format`.
+fn emit_tools(session: Session, entries: &[batch::BatchEntry], format: EmitFormat) -> Result<()> {
+    let variables = batch::emit_variables(&session.cgx, entries);
+    session.finish(variables.is_ok());
 
-    // Extract arguments to pass to the binary
-    let binary_args = CrateSpec::get_binary_args(&args);
+    print!("{}", batch::format_variables(&variables?, format));
+    Ok(())
+}
+
+/// Get the binary of every tool in `entries`, and run the user's shell with them all on `PATH`,
+/// exiting with its exit code once it exits.
+///
+/// cgx has to outlive the shell to remove the directory of binaries afterwards, so the reporter is
+/// kept running until then.
+fn run_shell(
+    session: Session,
+    config: &Config,
+    entries: &[batch::BatchEntry],
+    run_limits: &RunLimits,
+) -> Result<()> {
+    let started = batch::shell_binaries(&session.cgx, config, entries).and_then(|binaries| {
+        let shell_dir = ShellDir::create(config, &binaries)?;
+        let env = shell_dir.env()?;
+        let program = shell::program();
+        eprintln!(
+            "Starting {} with the tools in [tools] on PATH; exit it to return",
+            program.display()
+        );
+
+        let _span = info_span!("shell").entered();
+        let exit_code =
+            cgx_core::runner::run_with_limits(&program, None, &env, &[], &[], run_limits, &session.reporter)?;
+        Ok((shell_dir, exit_code))
+    });
+    session.finish(started.is_ok());
+
+    // Exiting skips destructors, so the shell's directory has to be removed first
+    let (shell_dir, exit_code) = started?;
+    drop(shell_dir);
+    telemetry::flush();
+    #[allow(clippy::exit)]
+    std::process::exit(exit_code);
+}
+
+/// Print the cached builds, only those of the tool `name` if one is given.
+fn list_installed(session: Session, name: Option<&str>, verbose: bool) -> Result<()> {
+    let builds = session.cgx.list_builds();
+    session.finish(builds.is_ok());
+
+    print_builds(builds?, name, verbose);
+    Ok(())
+}
+
+/// Install the tool's binary to the install directory.
+fn install(session: Session, crate_specs: &[CrateSpec], build_options: &BuildOptions) -> Result<()> {
+    let installed = session.cgx.install(crate_specs, build_options);
+    session.finish(installed.is_ok());
+
+    let (name, tool) = installed?;
+    eprintln!("Installed {}@{} to {}", name, tool.version, tool.binary.display());
+    Ok(())
+}
+
+/// Get the binary of the tool for each of several targets, and print where they are.
+fn build_targets(
+    session: Session,
+    crate_specs: &[CrateSpec],
+    target_build_options: &[BuildOptions],
+    out_dir: Option<&Path>,
+) -> Result<()> {
+    let results = build_for_targets(&session.cgx, crate_specs, target_build_options);
+    session.finish(results.iter().all(|result| result.is_ok()));
+    print_target_binaries(target_build_options, results, out_dir)
+}
+
+/// Get the tool's binary and run it with `binary_args`, or with `--no-exec` print where it is.
+///
+/// This only returns once the tool is done if cgx has to outlive it; otherwise cgx is replaced by
+/// the tool, and this only returns if it couldn't be run.
+#[allow(clippy::too_many_arguments)]
+fn run_tool(
+    session: Session,
+    args: &CliArgs,
+    config: &Config,
+    crate_specs: &[CrateSpec],
+    build_options: &BuildOptions,
+    binary_args: Vec<OsString>,
+    wrapper: Vec<OsString>,
+    run_limits: &RunLimits,
+    run_dir: Option<cgx_core::ephemeral::RunDir>,
+) -> Result<()> {
+    let tool_name = crate_specs.first().and_then(|crate_spec| crate_spec.name());
+
+    // The name to run the binary as, if not its own: `--argv0` or else the tool's config
+    let argv0 = args
+        .argv0
+        .as_deref()
+        .or_else(|| tool_name.and_then(|name| config.tool_argv0(name)))
+        .map(OsString::from);
+
+    let bin_path = match &args.pin_build {
+        Some(build_key) => session.cgx.pinned_build(build_key, crate_specs.first()),
+        None => match &args.plan {
+            Some(plan) => session
+                .cgx
+                .crate_to_bin_with_plan(crate_specs, build_options, plan),
+            None => session.cgx.crate_to_bin(crate_specs, build_options),
+        },
+    }?;
+
+    // With `--out-dir` the binary is also copied there, and that copy is what `--no-exec` prints
+    let exported_path = args
+        .out_dir
+        .as_deref()
+        .map(|out_dir| cgx_core::Cgx::export_binary(&bin_path, out_dir))
+        .transpose()?;
+
+    // Cargo subcommands are run the way cargo runs them: with their own name as the first
+    // argument, and the cargo of the toolchain they were built with first on the `PATH`
+    let cargo_subcommand = cgx_core::runner::cargo_subcommand(&bin_path);
+    let binary_args = match &cargo_subcommand {
+        Some(subcommand) => cgx_core::runner::cargo_subcommand_args(subcommand, &binary_args),
+        None => binary_args,
+    };
+
+    // A WebAssembly binary is run under a runtime, after any wrapper, and one is built or
+    // downloaded like any other tool if none is configured or on the PATH
+    let wrapper = if args.no_exec {
+        wrapper
+    } else {
+        let runtime = cgx_core::runner::wasm_runtime(config, &bin_path, || {
+            let runtime_spec = CrateSpec::CratesIo {
+                name: cgx_core::runner::WASM_RUNTIME_CRATE.to_string(),
+                version: None,
+            };
+            let runtime_options = BuildOptions {
+                locked: build_options.locked,
+                offline: build_options.offline,
+                ..BuildOptions::default()
+            };
+            session.cgx.crate_to_bin(&[runtime_spec], &runtime_options)
+        })?;
+        wrapper.into_iter().chain(runtime).collect()
+    };
 
     // Report the execution plan
-    reporter.report(|| messages::RunnerMessage::execution_plan(&bin_path, &binary_args, args.no_exec));
+    session
+        .reporter
+        .report(|| RunnerMessage::execution_plan(&bin_path, &binary_args, args.no_exec));
+
+    // Running the tool with a subcommand or flag that needs features it was built without would
+    // only fail once cgx has handed over to it, so the hint has to come first
+    if let Some(name) = tool_name.filter(|_| !args.no_exec) {
+        if let Some((arg, features)) = config
+            .tool_feature_hints(name)
+            .and_then(|hints| cgx_core::runner::missing_features(hints, &binary_args, build_options))
+        {
+            session
+                .reporter
+                .report(|| RunnerMessage::missing_features(name, arg, &features));
+        }
+    }
+
+    if !args.no_exec {
+        let hook_env = tool_name
+            .map(|name| ("CGX_CRATE", OsStr::new(name)))
+            .into_iter()
+            .chain([("CGX_BINARY", bin_path.as_os_str())])
+            .collect::<Vec<_>>();
+        hooks::run(&config.hooks, Hook::PreExec, &hook_env, &session.reporter)?;
+    }
 
-    // Drop everything that can report messages, once all senders are dropped then the reporter
-    // thread will exit cleanly.
-    drop(reporter);
-    drop(cgx);
+    // Point a tool with staged assets at them
+    let mut tool_env = tool_name
+        .and_then(|name| config.tool_assets(name))
+        .map(|assets| (assets, assets::staged_dir(&bin_path, assets)))
+        .filter(|(_, assets_dir)| assets_dir.is_dir())
+        .map(|(assets, assets_dir)| (OsString::from(assets.env_var()), assets_dir.into_os_string()))
+        .into_iter()
+        .collect::<Vec<_>>();
+    if cargo_subcommand.is_some() {
+        tool_env.extend(cgx_core::cargo::subcommand_env(
+            build_options.toolchain.as_deref(),
+        ));
+    }
 
-    // Wait for reporter thread to finish
-    debug!("Waiting for reporter thread to finish");
-    let _ = reporter_thread.join();
+    // A detached tool is left running in the background, named after its crate so that `--stop`
+    // can find it
+    if args.detach {
+        let name = tool_name.map_or_else(
+            || {
+                bin_path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            },
+            str::to_string,
+        );
+        let started = info_span!("run", krate = tool_name, detached = true).in_scope(|| {
+            cgx_core::detach::start(
+                config,
+                &name,
+                &bin_path,
+                argv0.as_deref(),
+                &tool_env,
+                &wrapper,
+                &binary_args,
+                run_limits,
+            )
+        });
+        session.finish(started.is_ok());
+
+        let tool = started?;
+        eprintln!("Started {} in the background (pid {})", tool.name, tool.pid);
+        eprintln!("  stdout: {}", tool.stdout_log.display());
+        eprintln!("  stderr: {}", tool.stderr_log.display());
+        eprintln!("Stop it with `cgx --stop {}`", tool.name);
+        return Ok(());
+    }
+
+    // With a limit cgx has to outlive the tool, so run it before finishing the session so that
+    // the limit being hit is still reported.  An ephemeral run has to outlive it too, to remove
+    // the binary afterwards.
+    let limited = run_limits.max_runtime.is_some() || run_limits.max_memory.is_some();
+    if !args.no_exec && (args.ephemeral || limited) {
+        let exit_code = info_span!("run", krate = tool_name, limited = true).in_scope(|| {
+            cgx_core::runner::run_with_limits(
+                &bin_path,
+                argv0.as_deref(),
+                &tool_env,
+                &wrapper,
+                &binary_args,
+                run_limits,
+                &session.reporter,
+            )
+        });
+        session.finish(true);
+
+        // Exiting skips destructors, so the run's directory has to be removed first
+        drop(run_dir);
+        let exit_code = exit_code?;
+        telemetry::flush();
+        #[allow(clippy::exit)]
+        std::process::exit(exit_code);
+    }
 
     if args.no_exec {
+        session.finish(true);
+
         // Print path to stdout for scripting (e.g., binary=$(cgx --no-exec tool))
-        println!("{}", bin_path.display());
+        println!("{}", exported_path.as_ref().unwrap_or(&bin_path).display());
         return Ok(());
     }
 
+    session.finish_before_exec(true);
+
     // Run the binary - this function never returns on success
-    // It either replaces the process (Unix) or exits with the child's code (Windows)
-    cgx_core::runner::run(&bin_path, &binary_args)
+    // It either replaces the process (Unix) or exits with the child's code (Windows).  So the trace
+    // has to be sent first, and can only record that the tool was started.
+    info_span!("run", krate = tool_name, limited = false).in_scope(|| {});
+    telemetry::flush();
+    cgx_core::runner::run(&bin_path, argv0.as_deref(), &tool_env, &wrapper, &binary_args)
+}
+
+/// Get the binary of the tool for each of several targets, building them concurrently.
+///
+/// The results are in the same order as `build_options`.
+fn build_for_targets(
+    cgx: &cgx_core::Cgx,
+    crate_specs: &[CrateSpec],
+    build_options: &[BuildOptions],
+) -> Vec<Result<PathBuf>> {
+    std::thread::scope(|scope| {
+        let handles = build_options
+            .iter()
+            .map(|options| {
+                // Keep each target's spans part of this run's trace
+                let span = Span::current();
+                scope.spawn(move || span.in_scope(|| cgx.crate_to_bin(crate_specs, options)))
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
+/// Print the path of the binary for each target that was built, copying them into a subdirectory
+/// of `out_dir` per target first if it's given, and fail if any target failed.
+fn print_target_binaries(
+    build_options: &[BuildOptions],
+    results: Vec<Result<PathBuf>>,
+    out_dir: Option<&Path>,
+) -> Result<()> {
+    let mut first_error = None;
+    for (options, result) in build_options.iter().zip(results) {
+        let target = options.target.as_deref().unwrap_or_default();
+        let result = result.and_then(|bin_path| match out_dir {
+            Some(out_dir) => cgx_core::Cgx::export_binary(&bin_path, &out_dir.join(target)),
+            None => Ok(bin_path),
+        });
+
+        match result {
+            Ok(bin_path) => println!("{}", bin_path.display()),
+            Err(e) => {
+                error!("Failed to get a binary for {}: {}", target, e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Print the cached builds of the crate `name`, or of all crates: one line for each, or with
+/// `verbose`, everything that was recorded about it.
+fn print_builds(builds: Vec<CachedBuild>, name: Option<&str>, verbose: bool) {
+    let builds = builds
+        .into_iter()
+        .filter(|build| name.is_none_or(|name| build.name == name))
+        .collect::<Vec<_>>();
+
+    if builds.is_empty() {
+        eprintln!("No cached builds of {}", name.unwrap_or("any crate"));
+        return;
+    }
+
+    if !verbose {
+        for build in builds {
+            println!(
+                "{}@{} {} {}",
+                build.name,
+                build.version,
+                build.build_key,
+                build.binary_path.display()
+            );
+        }
+        return;
+    }
+
+    for (index, build) in builds.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        println!("{}@{} (build {})", build.name, build.version, build.build_key);
+        println!("  binary:       {}", build.binary_path.display());
+        match &build.stats {
+            Some(stats) => {
+                println!(
+                    "  built at:     {}",
+                    stats.built_at.format("%Y-%m-%d %H:%M:%S UTC")
+                );
+                println!("  build time:   {}", stats.build_time());
+                println!("  binary size:  {} bytes", stats.binary_size);
+                println!("  dependencies: {}", stats.dependency_count);
+            }
+            None => println!("  (no build stats recorded)"),
+        }
+    }
+}
+
+/// Builds that previously took at least this long get a hint before they start again.
+const SLOW_BUILD_HINT_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// A hint for the user about the build that's about to start, if `msg` calls for one.
+fn build_hint(msg: &Message) -> Option<String> {
+    let Message::BuildCache(BuildCacheMessage::PreviousBuild {
+        name,
+        stats,
+        prebuilt_binaries_disabled,
+        ..
+    }) = msg
+    else {
+        return None;
+    };
+
+    if stats.duration < SLOW_BUILD_HINT_THRESHOLD {
+        return None;
+    }
+
+    let mut hint = format!("building {} took ~{} on this machine", name, stats.build_time());
+    if *prebuilt_binaries_disabled {
+        hint.push_str("; a prebuilt binary may be available with --prebuilt-binary auto");
+    }
+    Some(hint)
+}
+
+/// A hint that the binary about to be used isn't native to this machine, if `msg` is about one.
+fn emulation_hint(msg: &Message) -> Option<String> {
+    let Message::PrebuiltBinary(PrebuiltBinaryMessage::EmulatedBinary {
+        krate,
+        platform,
+        native_platform,
+    }) = msg
+    else {
+        return None;
+    };
+
+    Some(format!(
+        "no {} binary of {} is available; using the {} binary, which runs under emulation",
+        native_platform, krate.name, platform
+    ))
+}
+
+/// A note that a cached resolution that may be out of date is being used, if `msg` is about one.
+///
+/// That's only reported when there's no revision of the index to check the resolution against,
+/// since otherwise a newer release in the local copy of the index would have been found.
+///
+/// Not for a crate pinned to an exact version, since re-resolving it couldn't find anything newer.
+fn cached_resolution_hint(msg: &Message) -> Option<String> {
+    let Message::CrateResolution(CrateResolutionMessage::UsingCachedResolution {
+        spec,
+        resolved,
+        age_secs,
+    }) = msg
+    else {
+        return None;
+    };
+
+    if spec.is_exact_version() {
+        return None;
+    }
+
+    let age = match age_secs / 60 {
+        0 => "less than a minute ago".to_string(),
+        1 => "a minute ago".to_string(),
+        minutes @ 2..120 => format!("{} minutes ago", minutes),
+        minutes => format!("{} hours ago", minutes / 60),
+    };
+    Some(format!(
+        "using cached resolution of {} to {} from {} (use --refresh=resolve to re-resolve)",
+        resolved.name, resolved.version, age
+    ))
+}
+
+/// A note that a crate couldn't be resolved, and a version of it with a cached binary is being
+/// used instead, if `msg` is about one.
+fn cached_binary_version_hint(msg: &Message) -> Option<String> {
+    let Message::CrateResolution(CrateResolutionMessage::UsingCachedBinaryVersion { resolved, .. }) = msg
+    else {
+        return None;
+    };
+
+    Some(format!(
+        "couldn't resolve {}, so using {}, the newest version that matches of which a binary is cached",
+        resolved.name, resolved.version
+    ))
+}
+
+/// A suggestion to pin a crate that keeps being run without one, if `msg` makes one.
+fn pin_hint(msg: &Message) -> Option<String> {
+    let Message::CrateResolution(CrateResolutionMessage::PinSuggested { name, version, runs }) = msg else {
+        return None;
+    };
+
+    Some(format!(
+        "{} has been run {} times without a pin; `cgx --pin {}@{}` adds it to [tools] (set suggest_pins = false to stop these)",
+        name, runs, name, version
+    ))
+}
+
+/// A note naming the config setting that changed what the crate asked for resolves to, and the file
+/// it's in, if `msg` is about one.
+fn config_hint(msg: &Message) -> Option<String> {
+    let Message::CrateResolution(CrateResolutionMessage::ConfigApplied {
+        key,
+        value,
+        config_file,
+    }) = msg
+    else {
+        return None;
+    };
+
+    Some(format!("using {} = \"{}\" from {}", key, value, config_file))
+}
+
+/// A note that a crate has been renamed or replaced, if `msg` is about one.
+fn successor_hint(msg: &Message) -> Option<String> {
+    match msg {
+        Message::CrateResolution(CrateResolutionMessage::Superseded { name, successor }) => Some(format!(
+            "{} has been renamed or replaced by {}; consider running that instead (set follow_successors = true \
+             to have cgx do so)",
+            name, successor
+        )),
+        Message::CrateResolution(CrateResolutionMessage::SuccessorFollowed { name, successor }) => {
+            Some(format!(
+                "{} has been renamed or replaced by {}, so running that instead",
+                name, successor
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// The changelog entries of a crate that's newer than the version last run here, if `msg` has
+/// them, indented under a line saying where they're from.
+fn release_notes_hint(msg: &Message) -> Option<String> {
+    let Message::CrateResolution(CrateResolutionMessage::ReleaseNotes {
+        name,
+        previous_version,
+        version,
+        changelog,
+        notes,
+        truncated,
+    }) = msg
+    else {
+        return None;
+    };
+
+    let mut hint = format!(
+        "{} {} is newer than {}, the version last run here; from its changelog:",
+        name, version, previous_version
+    );
+    for line in notes.lines() {
+        hint.push('\n');
+        if !line.is_empty() {
+            hint.push_str("    ");
+            hint.push_str(line);
+        }
+    }
+    if *truncated {
+        hint.push_str(&format!("\n    ... (the rest is in {})", changelog.display()));
+    }
+
+    Some(hint)
+}
+
+/// A hint that the tool is being run with a subcommand or flag its binary lacks the features for,
+/// if `msg` is about one.
+fn feature_hint(msg: &Message) -> Option<String> {
+    let Message::Runner(RunnerMessage::MissingFeatures { krate, arg, features }) = msg else {
+        return None;
+    };
+
+    Some(format!(
+        "{} needs {} built with --features {}, which this binary wasn't; add them to `features` in its [tools] entry",
+        arg,
+        krate,
+        features.join(",")
+    ))
+}
+
+/// Print the extended explanation for an error code.
+fn explain(code: &str) -> Result<()> {
+    let explanation = Error::explain(code).context(error::UnknownErrorCodeSnafu { code })?;
+    println!("{} ({})", explanation.code, explanation.name);
+    println!();
+    println!("{}", explanation.text);
+    Ok(())
+}
+
+/// Record a directory's config file as trusted.
+fn trust(dir: &Path, args: &CliArgs) -> Result<()> {
+    let mut trust_store = TrustStore::load(&Config::user_config_dir(args)?)?;
+    let dir = trust_store.trust(dir)?;
+    eprintln!(
+        "Trusted config files in {} (recorded in {})",
+        dir.display(),
+        trust_store.path().display()
+    );
+    Ok(())
+}
+
+/// Pin a tool to a version in the config file that applies in the current directory.
+fn pin(spec: &str, args: &CliArgs) -> Result<()> {
+    let (name, version) = tool_pin::parse(spec)?;
+    let config_file = edited_config_file(args)?;
+    tool_pin::pin(&config_file, &name, &version)?;
+    eprintln!("Pinned {} to {} in {}", name, version, config_file.display());
+
+    note_untrusted(&config_file, args, "tool pins")
+}
+
+/// Unpin a tool in the config file that applies in the current directory.
+fn unpin(name: &str, args: &CliArgs) -> Result<()> {
+    let config_file = edited_config_file(args)?;
+    if tool_pin::unpin(&config_file, name)? {
+        eprintln!("Unpinned {} in {}", name, config_file.display());
+    } else {
+        eprintln!("{} isn't pinned in {}", name, config_file.display());
+    }
+    Ok(())
+}
+
+/// Add an alias to the config file that applies in the current directory, if the crate it stands
+/// for can be found.
+fn alias(spec: &str, args: &CliArgs) -> Result<()> {
+    let (alias, target) = tool_alias::parse(spec)?;
+    let config_file = edited_config_file(args)?;
+
+    // Crates are looked for without any aliases, since an alias is only ever replaced with the
+    // crate it stands for, never with what another alias stands for
+    let mut config = Config::load(args)?;
+    config.aliases.clear();
+    let cgx = cgx_core::Cgx::new(config.clone(), MessageReporter::null())?;
+    let crate_exists = |name: &str| {
+        let mut args = args.clone();
+        args.crate_spec = Some(name.to_string());
+        args.args.clear();
+        cgx.crate_exists(&CrateSpec::load_candidates(&config, &args)?)
+    };
+
+    match crate_exists(&target) {
+        Ok(true) => {}
+        Ok(false) => return error::AliasTargetNotFoundSnafu { alias, target }.fail(),
+        Err(e) => warn!("Unable to check that crate {} exists: {}", target, e),
+    }
+    if let Ok(true) = crate_exists(&alias) {
+        warn!(
+            "There's a crate named {}, which `cgx {}` will no longer run; it will run {} instead",
+            alias, alias, target
+        );
+    }
+
+    match tool_alias::add(&config_file, &alias, &target)? {
+        Some(previous) if previous != target => eprintln!(
+            "Aliased {} to {} in {}, in place of {}",
+            alias,
+            target,
+            config_file.display(),
+            previous
+        ),
+        _ => eprintln!("Aliased {} to {} in {}", alias, target, config_file.display()),
+    }
+
+    note_untrusted(&config_file, args, "aliases")
+}
+
+/// Remove an alias from the config file that applies in the current directory.
+fn unalias(alias: &str, args: &CliArgs) -> Result<()> {
+    let config_file = edited_config_file(args)?;
+    match tool_alias::remove(&config_file, alias)? {
+        Some(target) => eprintln!(
+            "Removed alias {} for {} from {}",
+            alias,
+            target,
+            config_file.display()
+        ),
+        None => eprintln!("{} isn't an alias in {}", alias, config_file.display()),
+    }
+    Ok(())
+}
+
+/// The config file `--pin`, `--alias` and the like edit in the current directory.
+fn edited_config_file(args: &CliArgs) -> Result<PathBuf> {
+    let cwd = std::env::current_dir().context(error::IoSnafu { path: "." })?;
+    tool_pin::config_file(&cwd, args)
+}
+
+/// Point out that the `what` just added to `config_file` have no effect until its directory is
+/// trusted, if it isn't yet.
+fn note_untrusted(config_file: &Path, args: &CliArgs, what: &str) -> Result<()> {
+    // Only a project's config file needs trusting
+    if !args.global && args.config_file.is_none() {
+        let trust_store = TrustStore::load(&Config::user_config_dir(args)?)?;
+        if let Some(dir) = config_file.parent().filter(|dir| !trust_store.is_trusted(dir)) {
+            eprintln!(
+                "{} isn't trusted yet; run `cgx --trust {}` for the {} in it to be used",
+                dir.display(),
+                dir.display(),
+                what
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The tools pinned to a version in `config`, and their version requirements, sorted by name.
+fn pinned_versions(config: &Config) -> BTreeMap<&str, &str> {
+    config
+        .tools
+        .iter()
+        .filter_map(|(name, tool)| {
+            let version = match tool {
+                ToolConfig::Version(version) => Some(version),
+                ToolConfig::Detailed { version, .. } => version.as_ref(),
+            };
+            version.map(|version| (name.as_str(), version.as_str()))
+        })
+        .collect()
+}
+
+/// Create a project environment in the current directory.
+fn init_env(args: &CliArgs) -> Result<()> {
+    let cwd = std::env::current_dir().context(error::IoSnafu { path: "." })?;
+    let mut trust_store = TrustStore::load(&Config::user_config_dir(args)?)?;
+    let env_dir = project_env::init(&cwd, &mut trust_store)?;
+    eprintln!(
+        "Created project environment in {}; cgx run in {} or below will use it",
+        env_dir.display(),
+        cwd.display()
+    );
+    Ok(())
+}
+
+/// Install the newest version of every tool installed with `--install`, or only of the crate
+/// `name`, each from what it was installed from and with the same build options.
+fn upgrade(session: Session, config: &Config, name: Option<&str>) -> Result<()> {
+    let manifest = InstallManifest::load(&config.installs_dir)?;
+    let tools = match name {
+        Some(name) => {
+            let name = config.aliases.get(name).map_or(name, String::as_str);
+            let tool = manifest
+                .tools
+                .get(name)
+                .context(error::NotInstalledSnafu { name })?;
+            vec![(name, tool)]
+        }
+        None => manifest
+            .tools
+            .iter()
+            .map(|(name, tool)| (name.as_str(), tool))
+            .collect(),
+    };
+
+    for (name, tool) in tools {
+        let (_, upgraded) = session.cgx.install(&tool.crate_specs, &tool.build_options)?;
+        if upgraded.version == tool.version {
+            eprintln!("{} is up to date at {}", name, tool.version);
+        } else {
+            eprintln!("Upgraded {} from {} to {}", name, tool.version, upgraded.version);
+        }
+    }
+
+    session.finish(true);
+    Ok(())
+}
+
+/// Install the git hooks configured under `[hooks.git]` in the repository the current directory is
+/// in.
+fn install_git_hooks(config: &Config) -> Result<()> {
+    if config.hooks.git.is_empty() {
+        eprintln!("No git hooks are configured under [hooks.git]; nothing to install");
+        return Ok(());
+    }
+
+    let cwd = std::env::current_dir().context(error::IoSnafu { path: "." })?;
+    let cgx_exe = std::env::current_exe().context(error::IoSnafu { path: "cgx" })?;
+    for path in git_hooks::install(&git_hooks::hooks_dir(&cwd)?, &config.hooks.git, &cgx_exe)? {
+        eprintln!("Installed git hook {}", path.display());
+    }
+    Ok(())
+}
+
+/// Print the settings of the config in effect in the current directory, or how they differ from
+/// those in the `--compare` directory.
+fn show_config(args: &CliArgs) -> Result<()> {
+    let cwd = std::env::current_dir().context(error::IoSnafu { path: "." })?;
+    let settings = Config::settings_in_dir(&cwd, args)?;
+
+    let Some(other_dir) = &args.compare else {
+        for setting in &settings {
+            println!("{}", format_setting(setting));
+        }
+        return Ok(());
+    };
+
+    let other_dir = std::fs::canonicalize(other_dir).context(error::IoSnafu { path: other_dir })?;
+    let other_settings = Config::settings_in_dir(&other_dir, args)?;
+
+    println!("--- {}", cwd.display());
+    println!("+++ {}", other_dir.display());
+    for line in config_diff(&settings, &other_settings) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn format_setting(setting: &ConfigSetting) -> String {
+    format!("{} = {}  # {}", setting.key, setting.value, setting.source)
+}
+
+/// The lines of a diff from the settings `ours` to `theirs`, in order of key.
+///
+/// Only settings whose value differs, or that only one side has, are included; the same value
+/// coming from different config files is no difference.
+fn config_diff(ours: &[ConfigSetting], theirs: &[ConfigSetting]) -> Vec<String> {
+    let mut keys: BTreeMap<&str, (Option<&ConfigSetting>, Option<&ConfigSetting>)> = BTreeMap::new();
+    for setting in ours {
+        keys.entry(setting.key.as_str()).or_default().0 = Some(setting);
+    }
+    for setting in theirs {
+        keys.entry(setting.key.as_str()).or_default().1 = Some(setting);
+    }
+
+    let mut lines = Vec::new();
+    for (ours, theirs) in keys.into_values() {
+        if ours.map(|setting| &setting.value) == theirs.map(|setting| &setting.value) {
+            continue;
+        }
+        lines.extend(ours.map(|setting| format!("-{}", format_setting(setting))));
+        lines.extend(theirs.map(|setting| format!("+{}", format_setting(setting))));
+    }
+    lines
+}
+
+/// What reports the messages of a run: cgx itself and the reporter it was built with, and the
+/// thread that handles what they report.
+///
+/// Every mode ends its session with [`Self::finish`] once it's done with cgx, which drops both
+/// senders before waiting for the [`ReporterThread`], as it never exits otherwise.  A session
+/// dropped on an early return does the same, as a failed run, since its fields are dropped in the
+/// order they're declared.
+struct Session {
+    cgx: cgx_core::Cgx,
+    reporter: MessageReporter,
+    reporter_thread: ReporterThread,
+}
+
+impl Session {
+    /// Finish the reporter thread with [`ReporterThread::finish`], once cgx and the reporter are
+    /// dropped.
+    fn finish(self, succeeded: bool) {
+        self.into_reporter_thread().finish(succeeded);
+    }
+
+    /// Like [`Self::finish`], when cgx is about to hand over to the tool (or a file from the
+    /// crate's source) if `succeeded`.
+    fn finish_before_exec(self, succeeded: bool) {
+        self.into_reporter_thread().finish_before_exec(succeeded);
+    }
+
+    fn into_reporter_thread(self) -> ReporterThread {
+        let Self {
+            cgx,
+            reporter,
+            reporter_thread,
+        } = self;
+        drop(reporter);
+        drop(cgx);
+        reporter_thread
+    }
+}
+
+/// The thread that handles the messages reported during a run.
+///
+/// It has to be finished before cgx execs the tool or exits, or any messages still queued for it
+/// are lost, so if it isn't finished explicitly it is when it's dropped, as for a failed run.  All
+/// [`MessageReporter`] clones must have been dropped by then, otherwise the thread never exits;
+/// declaring this before the reporter ensures that for an early return.
+struct ReporterThread {
+    handle: Option<JoinHandle<(Option<StatusLine>, Option<cgx_core::metrics::Recorder>)>>,
+    json_mode: bool,
+}
+
+impl ReporterThread {
+    fn new(
+        handle: JoinHandle<(Option<StatusLine>, Option<cgx_core::metrics::Recorder>)>,
+        json_mode: bool,
+    ) -> Self {
+        Self {
+            handle: Some(handle),
+            json_mode,
+        }
+    }
+
+    /// Wait for the thread to drain the messages, end the message stream, add the run to the
+    /// metrics if they're being collected, and tear down the status line if one was being
+    /// displayed.
+    fn finish(mut self, succeeded: bool) {
+        self.end(succeeded, false);
+    }
+
+    /// Like [`Self::finish`], when cgx is about to hand over to the tool (or a file from the
+    /// crate's source) if `succeeded`.
+    fn finish_before_exec(mut self, succeeded: bool) {
+        self.end(succeeded, succeeded);
+    }
+
+    fn end(&mut self, succeeded: bool, execs_tool: bool) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+
+        debug!("Waiting for reporter thread to finish");
+        let (status, metrics) = handle.join().unwrap_or((None, None));
+
+        if let Some(metrics) = metrics {
+            if let Err(e) = metrics.finish(succeeded) {
+                debug!("Failed to record metrics: {}", e);
+            }
+        }
+
+        // Only written once every other message has been, so it's always the last
+        if self.json_mode {
+            let msg = Message::from(RunEndMessage::new(succeeded, execs_tool));
+            match serde_json::to_string(&msg) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("Failed to serialize message: {}", e),
+            }
+            sync_stdout();
+        }
+
+        if let Some(status) = status {
+            status.finish(succeeded);
+        }
+    }
+}
+
+impl Drop for ReporterThread {
+    fn drop(&mut self) {
+        self.end(false, false);
+    }
+}
+
+/// Flush stdout, and if it's redirected to a file, sync that to disk.
+///
+/// Exec-ing the tool replaces cgx without running any destructors, and a consumer may read the
+/// file as soon as cgx (or the tool) exits, so the messages have to be all the way out first.
+fn sync_stdout() {
+    let _ = std::io::stdout().flush();
+
+    if let Ok(file) = stdout_file() {
+        if file.metadata().is_ok_and(|metadata| metadata.is_file()) {
+            let _ = file.sync_all();
+        }
+    }
+}
+
+/// A handle to whatever stdout is, as a [`std::fs::File`].
+#[cfg(unix)]
+fn stdout_file() -> std::io::Result<std::fs::File> {
+    let fd = std::io::stdout().as_fd().try_clone_to_owned()?;
+    Ok(std::fs::File::from(fd))
+}
+
+/// A handle to whatever stdout is, as a [`std::fs::File`].
+#[cfg(windows)]
+fn stdout_file() -> std::io::Result<std::fs::File> {
+    let handle = std::io::stdout().as_handle().try_clone_to_owned()?;
+    Ok(std::fs::File::from(handle))
+}
+
+/// A handle to whatever stdout is, as a [`std::fs::File`].
+#[cfg(not(any(unix, windows)))]
+fn stdout_file() -> std::io::Result<std::fs::File> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgx_core::{
+        builder::BuildStats,
+        crate_resolver::{ResolvedCrate, ResolvedSource},
+    };
+
+    fn previous_build(duration: Duration, prebuilt_binaries_disabled: bool) -> Message {
+        let stats = BuildStats {
+            built_at: "2026-01-01T00:00:00Z".parse().unwrap(),
+            duration,
+            binary_size: 1024,
+            dependency_count: 42,
+        };
+        BuildCacheMessage::previous_build("ripgrep", "14.1.1", &stats, prebuilt_binaries_disabled).into()
+    }
+
+    #[test]
+    fn test_config_diff_shows_only_differing_values() {
+        let setting = |key: &str, value: &str, source: &str| ConfigSetting {
+            key: key.to_string(),
+            value: value.to_string(),
+            source: source.to_string(),
+        };
+        let ours = [
+            setting("locked", "true", "defaults"),
+            setting("offline", "false", "defaults"),
+            setting("resolve_cache_timeout", "\"1h\"", "/home/me/.config/cgx/cgx.toml"),
+            setting("tools.ripgrep", "\"14\"", "/work/cgx.toml"),
+        ];
+        let theirs = [
+            setting("locked", "true", "/ci/cgx.toml"),
+            setting("offline", "true", "/ci/cgx.toml"),
+            setting("resolve_cache_timeout", "\"1h\"", "defaults"),
+        ];
+
+        assert_eq!(
+            config_diff(&ours, &theirs),
+            vec![
+                "-offline = false  # defaults",
+                "+offline = true  # /ci/cgx.toml",
+                "-tools.ripgrep = \"14\"  # /work/cgx.toml",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fast_builds_get_no_hint() {
+        assert_eq!(build_hint(&previous_build(Duration::from_secs(5), true)), None);
+    }
+
+    #[test]
+    fn test_slow_build_hint() {
+        assert_eq!(
+            build_hint(&previous_build(Duration::from_secs(125), false)).as_deref(),
+            Some("building ripgrep took ~2m 5s on this machine")
+        );
+        assert_eq!(
+            build_hint(&previous_build(Duration::from_secs(125), true)).as_deref(),
+            Some(
+                "building ripgrep took ~2m 5s on this machine; a prebuilt binary may be available with \
+                 --prebuilt-binary auto"
+            )
+        );
+    }
+
+    #[test]
+    fn test_cached_resolution_hint() {
+        let resolved = ResolvedCrate {
+            name: "ripgrep".to_string(),
+            version: "14.1.1".parse().unwrap(),
+            source: ResolvedSource::CratesIo,
+        };
+        let hint = |version: &str, age_secs: u64| {
+            let spec = CrateSpec::CratesIo {
+                name: "ripgrep".to_string(),
+                version: Some(version.parse().unwrap()),
+            };
+            let age = Duration::from_secs(age_secs);
+            cached_resolution_hint(
+                &CrateResolutionMessage::using_cached_resolution(&spec, &resolved, age).into(),
+            )
+        };
+
+        assert_eq!(
+            hint("14", 25 * 60).as_deref(),
+            Some(
+                "using cached resolution of ripgrep to 14.1.1 from 25 minutes ago (use --refresh=resolve to re-resolve)"
+            )
+        );
+        assert_eq!(hint("=14.1.1", 25 * 60), None);
+    }
+
+    #[test]
+    fn test_pin_hint() {
+        let suggestion = cgx_core::usage::PinSuggestion {
+            name: "ripgrep".to_string(),
+            version: "14.1.1".parse().unwrap(),
+            runs: 5,
+        };
+        assert_eq!(
+            pin_hint(&CrateResolutionMessage::pin_suggested(&suggestion).into()).as_deref(),
+            Some(
+                "ripgrep has been run 5 times without a pin; `cgx --pin ripgrep@14.1.1` adds it to [tools] \
+                 (set suggest_pins = false to stop these)"
+            )
+        );
+    }
+
+    #[test]
+    fn test_config_hint() {
+        let decision = cgx_core::cratespec::ConfigDecision {
+            key: "aliases.rg".to_string(),
+            value: "ripgrep".to_string(),
+            source: "/home/me/.config/cgx/cgx.toml".to_string(),
+        };
+        assert_eq!(
+            config_hint(&CrateResolutionMessage::config_applied(&decision).into()).as_deref(),
+            Some("using aliases.rg = \"ripgrep\" from /home/me/.config/cgx/cgx.toml")
+        );
+    }
+
+    #[test]
+    fn test_successor_hint() {
+        assert_eq!(
+            successor_hint(&CrateResolutionMessage::superseded("structopt", "clap").into()).as_deref(),
+            Some(
+                "structopt has been renamed or replaced by clap; consider running that instead (set \
+                 follow_successors = true to have cgx do so)"
+            )
+        );
+        assert_eq!(
+            successor_hint(&CrateResolutionMessage::successor_followed("structopt", "clap").into())
+                .as_deref(),
+            Some("structopt has been renamed or replaced by clap, so running that instead")
+        );
+    }
+
+    #[test]
+    fn test_release_notes_hint() {
+        let notes = cgx_core::release_notes::ReleaseNotes {
+            name: "tool".to_string(),
+            previous_version: "1.1.0".parse().unwrap(),
+            version: "2.0.0".parse().unwrap(),
+            changelog: PathBuf::from("/cache/tool-2.0.0/CHANGELOG.md"),
+            notes: "## 2.0.0\n\n- `--output` is now `--out`".to_string(),
+            truncated: true,
+        };
+        assert_eq!(
+            release_notes_hint(&CrateResolutionMessage::release_notes(&notes).into()).as_deref(),
+            Some(
+                "tool 2.0.0 is newer than 1.1.0, the version last run here; from its changelog:\n    ## \
+                 2.0.0\n\n    - `--output` is now `--out`\n    ... (the rest is in \
+                 /cache/tool-2.0.0/CHANGELOG.md)"
+            )
+        );
+    }
 }
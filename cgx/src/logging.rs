@@ -40,6 +40,11 @@ static RELOAD_HANDLE: OnceLock<ReloadHandle> = OnceLock::new();
 ///
 /// This allows for fine-grained control of logging output without recompiling.
 ///
+/// # Traces
+///
+/// If an OTLP endpoint is configured in the environment, cgx's spans are also collected for export
+/// as traces; see [`crate::telemetry`].
+///
 /// # Panics
 ///
 /// This function will panic if called more than once in the same process, as the
@@ -69,7 +74,8 @@ pub(crate) fn init(args: &CliArgs) {
             }
         });
 
-    // Wrap the filter in a reload layer so we can update it later based on config
+    // Wrap the filter in a reload layer so we can update it later based on config.  It only
+    // applies to the log output, since the spans exported as traces are wanted at any verbosity.
     let (filter, reload_handle) = reload::Layer::new(filter);
 
     // Store the reload handle for later use by apply_config()
@@ -83,25 +89,27 @@ pub(crate) fn init(args: &CliArgs) {
         // Simple format for default (non-verbose) mode: just the message, with color if TTY
         // This is meant to not even look very "loggy", and just prints log messages, one per line.
         tracing_subscriber::registry()
-            .with(filter)
             .with(
                 fmt::layer()
                     .with_target(false)
                     .with_level(true)
                     .with_ansi(use_ansi)
-                    .without_time(),
+                    .without_time()
+                    .with_filter(filter),
             )
+            .with(crate::telemetry::layer())
             .init();
     } else {
         // Structured format for verbose modes: timestamp, target, level, message
         tracing_subscriber::registry()
-            .with(filter)
             .with(
                 fmt::layer()
                     .with_target(true)
                     .with_level(true)
-                    .with_ansi(use_ansi),
+                    .with_ansi(use_ansi)
+                    .with_filter(filter),
             )
+            .with(crate::telemetry::layer())
             .init();
     }
 }
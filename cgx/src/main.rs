@@ -1,5 +1,5 @@
 use cgx::cgx_main;
 
-fn main() -> cgx::SnafuReport<cgx::Error> {
+fn main() -> std::process::ExitCode {
     cgx_main()
 }
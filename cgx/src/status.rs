@@ -0,0 +1,356 @@
+//! Compact single-line status rendering for interactive `--quiet` runs.
+//!
+//! Instead of echoing cargo's output and leaving a trail of log lines, the [`StatusLine`] keeps
+//! one continuously-updated line on stderr summarizing where cgx is in its pipeline, e.g.
+//! `resolving → downloading → building [crate 3/57]`.  It is driven entirely by the
+//! [`Message`] stream that cgx-core already emits.
+//!
+//! Cargo's stderr is buffered rather than displayed, and is only dumped if the run fails, so that
+//! build errors are never swallowed.
+
+use cgx_core::{
+    cli::CliArgs,
+    messages::{
+        BuildMessage, CargoMessage, CrateResolutionMessage, GitMessage, Message, PrebuiltBinaryMessage,
+        RunnerMessage, SourceMessage,
+    },
+};
+use std::{
+    collections::HashSet,
+    io::{IsTerminal, Write},
+};
+
+/// Width to truncate the status line to when the terminal width is not known.
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+
+/// A stage of the cgx pipeline, as presented in the status line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    Resolving,
+    Downloading,
+    Building,
+}
+
+impl Phase {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Resolving => "resolving",
+            Self::Downloading => "downloading",
+            Self::Building => "building",
+        }
+    }
+}
+
+/// Renders the message stream as a single, continuously-updated line on stderr.
+#[derive(Debug, Default)]
+pub(crate) struct StatusLine {
+    /// Phases entered so far, in order.
+    phases: Vec<Phase>,
+
+    /// Detail about the current phase, shown after its label.
+    detail: Option<String>,
+
+    /// Packages cargo has reported compiler artifacts of so far.
+    compiled: HashSet<String>,
+
+    /// Number of crates the build could compile at most, once it's known.
+    planned: Option<usize>,
+
+    /// Raw cargo stderr, held back unless the run fails.
+    cargo_stderr: Vec<u8>,
+
+    /// Whether anything has been drawn, and therefore needs to be cleared on finish.
+    drawn: bool,
+}
+
+impl StatusLine {
+    /// Whether the compact status line should replace the normal output for this invocation.
+    ///
+    /// It is only used when the user asked for `--quiet` output on an interactive terminal; with
+    /// `--verbose`, structured messages, or a non-TTY stderr, the plain log output is kept.
+    pub(crate) fn is_wanted(args: &CliArgs) -> bool {
+        args.quiet && args.verbose == 0 && args.message_format.is_none() && std::io::stderr().is_terminal()
+    }
+
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the status from a message and redraw the line if it changed.
+    pub(crate) fn handle(&mut self, msg: &Message) {
+        match msg {
+            Message::Build(BuildMessage::CargoStderr { bytes }) => {
+                self.cargo_stderr.extend_from_slice(bytes);
+                return;
+            }
+            Message::Runner(RunnerMessage::ExecutionPlan { .. }) => {
+                // The tool is about to take over the terminal
+                self.clear();
+                return;
+            }
+            _ => {}
+        }
+
+        if self.apply(msg) {
+            self.draw();
+        }
+    }
+
+    /// Print a note for the user on a line of its own above the status line.
+    pub(crate) fn note(&mut self, note: &str) {
+        let drawn = self.drawn;
+        self.clear();
+        eprintln!("note: {note}");
+        if drawn {
+            self.draw();
+        }
+    }
+
+    /// Clear the status line, dumping the buffered cargo output if the run failed.
+    pub(crate) fn finish(mut self, succeeded: bool) {
+        self.clear();
+        if !succeeded {
+            let mut stderr = std::io::stderr().lock();
+            let _ = stderr.write_all(&self.cargo_stderr);
+            let _ = stderr.flush();
+        }
+    }
+
+    /// Apply a message to the status state, returning true if the rendered line changed.
+    fn apply(&mut self, msg: &Message) -> bool {
+        match msg {
+            Message::CrateResolution(
+                CrateResolutionMessage::CacheLookup { .. } | CrateResolutionMessage::Resolving { .. },
+            ) => self.enter(Phase::Resolving, None),
+            Message::CrateResolution(CrateResolutionMessage::Resolved { resolved }) => self.enter(
+                Phase::Resolving,
+                Some(format!("{} {}", resolved.name, resolved.version)),
+            ),
+            Message::PrebuiltBinary(PrebuiltBinaryMessage::CheckingProvider { provider, .. }) => {
+                self.enter(Phase::Downloading, Some(format!("checking {provider}")))
+            }
+            Message::PrebuiltBinary(PrebuiltBinaryMessage::DownloadingBinary { provider, .. }) => self.enter(
+                Phase::Downloading,
+                Some(format!("prebuilt binary from {provider}")),
+            ),
+            Message::PrebuiltBinary(PrebuiltBinaryMessage::DownloadProgress {
+                downloaded,
+                total,
+                provider,
+                ..
+            }) => self.enter(
+                Phase::Downloading,
+                Some(format!(
+                    "prebuilt binary from {provider} ({}%)",
+                    downloaded.saturating_mul(100) / (*total).max(1)
+                )),
+            ),
+            Message::Source(SourceMessage::Downloading { name, version, .. }) => {
+                self.enter(Phase::Downloading, Some(format!("{name} {version}")))
+            }
+            Message::Git(GitMessage::FetchingRepo { url, .. }) => {
+                self.enter(Phase::Downloading, Some(url.clone()))
+            }
+            Message::Git(GitMessage::CheckingOut { commit, .. }) => self.enter(
+                Phase::Downloading,
+                Some(format!("checkout {}", commit.get(..12).unwrap_or(commit))),
+            ),
+            Message::Build(BuildMessage::FetchStarted { .. }) => {
+                self.enter(Phase::Downloading, Some("dependencies".to_string()))
+            }
+            Message::Build(BuildMessage::FetchRetrying { attempt, .. }) => self.enter(
+                Phase::Downloading,
+                Some(format!("dependencies (retry {attempt})")),
+            ),
+            Message::Build(BuildMessage::Planned { crates }) => {
+                self.planned = Some(*crates);
+                false
+            }
+            Message::Build(BuildMessage::Started { .. }) => {
+                self.compiled.clear();
+                self.enter(Phase::Building, None)
+            }
+            Message::Build(BuildMessage::CargoMessage {
+                message: CargoMessage::CompilerArtifact(artifact),
+            }) => {
+                self.compiled.insert(artifact.package_id.repr.clone());
+                let compiled = self.compiled.len();
+                // The plan counts packages that may never be compiled, but it's no total once exceeded
+                let detail = match self.planned {
+                    Some(planned) if compiled <= planned => format!("[crate {compiled}/{planned}]"),
+                    _ => format!("[crate {compiled}]"),
+                };
+                self.enter(Phase::Building, Some(detail))
+            }
+            _ => false,
+        }
+    }
+
+    /// Move to `phase` (if not already there) with the given detail.
+    fn enter(&mut self, phase: Phase, detail: Option<String>) -> bool {
+        let changed_phase = self.phases.last() != Some(&phase);
+        if changed_phase {
+            self.phases.push(phase);
+        }
+        let changed_detail = self.detail != detail;
+        self.detail = detail;
+        changed_phase || changed_detail
+    }
+
+    fn render(&self) -> String {
+        let mut line = self
+            .phases
+            .iter()
+            .map(|phase| phase.label())
+            .collect::<Vec<_>>()
+            .join(" → ");
+        if let Some(detail) = &self.detail {
+            line.push(' ');
+            line.push_str(detail);
+        }
+        line
+    }
+
+    fn draw(&mut self) {
+        let width = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_TERMINAL_WIDTH);
+        let line: String = self.render().chars().take(width.saturating_sub(1)).collect();
+
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{line}");
+        let _ = stderr.flush();
+        self.drawn = true;
+    }
+
+    fn clear(&mut self) {
+        if self.drawn {
+            let mut stderr = std::io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+            self.drawn = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgx_core::config::BinaryProvider;
+    use std::path::Path;
+
+    fn stderr_chunk(bytes: &[u8]) -> Message {
+        BuildMessage::cargo_stderr(bytes.to_vec()).into()
+    }
+
+    #[test]
+    fn test_phases_accumulate() {
+        let mut status = StatusLine::new();
+
+        status.enter(Phase::Resolving, None);
+        status.enter(Phase::Downloading, Some("ripgrep 14.1.1".to_string()));
+        assert_eq!(status.render(), "resolving → downloading ripgrep 14.1.1");
+
+        status.enter(Phase::Building, Some("[crate 3]".to_string()));
+        assert_eq!(status.render(), "resolving → downloading → building [crate 3]");
+    }
+
+    #[test]
+    fn test_repeated_phase_is_not_duplicated() {
+        let mut status = StatusLine::new();
+        assert!(status.enter(Phase::Resolving, None));
+        assert!(!status.enter(Phase::Resolving, None));
+        assert!(status.enter(Phase::Resolving, Some("ripgrep 14.1.1".to_string())));
+        assert_eq!(status.render(), "resolving ripgrep 14.1.1");
+    }
+
+    #[test]
+    fn test_irrelevant_messages_ignored() {
+        let mut status = StatusLine::new();
+        assert!(!status.apply(&RunnerMessage::execution_plan(Path::new("/tmp/foo"), &[], false).into()));
+        assert!(!status.apply(&BuildMessage::completed(Path::new("/tmp/foo")).into()));
+        assert!(status.phases.is_empty());
+    }
+
+    #[test]
+    fn test_fetch_reported_as_downloading() {
+        let mut status = StatusLine::new();
+        assert!(status.apply(&BuildMessage::fetch_started(false).into()));
+        assert_eq!(status.render(), "downloading dependencies");
+
+        assert!(status.apply(&BuildMessage::fetch_retrying(1, std::time::Duration::from_secs(1)).into()));
+        assert_eq!(status.render(), "downloading dependencies (retry 1)");
+    }
+
+    #[test]
+    fn test_download_percentage_shown() {
+        let mut status = StatusLine::new();
+        let progress = PrebuiltBinaryMessage::download_progress(
+            "https://example.com/rg.tar.gz",
+            42 * 1024,
+            100 * 1024,
+            BinaryProvider::GithubReleases,
+        );
+        assert!(status.apply(&progress.into()));
+        assert_eq!(
+            status.render(),
+            "downloading prebuilt binary from github-releases (42%)"
+        );
+    }
+
+    #[test]
+    fn test_compiled_crates_counted_against_plan() {
+        let artifact = |package_id: &str| -> Message {
+            let message = serde_json::from_value(serde_json::json!({
+                "reason": "compiler-artifact",
+                "package_id": package_id,
+                "manifest_path": "/src/Cargo.toml",
+                "target": {
+                    "name": "foo",
+                    "kind": ["lib"],
+                    "crate_types": ["lib"],
+                    "src_path": "/src/lib.rs",
+                    "edition": "2021",
+                    "doctest": true,
+                    "test": true,
+                },
+                "profile": {
+                    "opt_level": "3",
+                    "debuginfo": 0,
+                    "debug_assertions": false,
+                    "overflow_checks": false,
+                    "test": false,
+                },
+                "features": [],
+                "filenames": [],
+                "executable": null,
+                "fresh": false,
+            }))
+            .unwrap();
+            BuildMessage::cargo_message(message).into()
+        };
+
+        let mut status = StatusLine::new();
+        status.apply(&BuildMessage::planned(2).into());
+        status.apply(&BuildMessage::started(&Default::default()).into());
+        status.apply(&artifact("foo 1.0.0"));
+        assert_eq!(status.render(), "building [crate 1/2]");
+
+        // A build script and the library it's for are the same crate
+        assert!(!status.apply(&artifact("foo 1.0.0")));
+        status.apply(&artifact("bar 1.0.0"));
+        assert_eq!(status.render(), "building [crate 2/2]");
+        status.apply(&artifact("baz 1.0.0"));
+        assert_eq!(status.render(), "building [crate 3]");
+    }
+
+    #[test]
+    fn test_cargo_stderr_is_buffered() {
+        let mut status = StatusLine::new();
+        status.handle(&stderr_chunk(b"error[E0425]: "));
+        status.handle(&stderr_chunk(b"cannot find value"));
+        assert_eq!(status.cargo_stderr, b"error[E0425]: cannot find value");
+        assert!(!status.drawn);
+    }
+}
@@ -0,0 +1,197 @@
+//! Collection of cgx's spans for export to an OpenTelemetry collector.
+//!
+//! When [`cgx_core::telemetry::OtlpExporter::from_env`] finds an OTLP endpoint configured, the
+//! [`layer`] installed by [`crate::logging::init`] records every span cgx creates, and [`flush`]
+//! sends those that have finished.  All spans of one cgx run belong to the same trace.
+
+use cgx_core::telemetry::{OtlpExporter, SpanId, SpanRecord, TraceId};
+use std::{
+    fmt::Debug,
+    sync::{Mutex, OnceLock},
+    time::SystemTime,
+};
+use tracing::{
+    Subscriber,
+    field::{Field, Visit},
+    span,
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+/// Where the spans of this run are sent, and the finished spans that haven't been yet.
+struct Collector {
+    exporter: OtlpExporter,
+    trace_id: TraceId,
+    finished: Mutex<Vec<SpanRecord>>,
+}
+
+/// The collector for this run, if traces are being exported.
+static COLLECTOR: OnceLock<Collector> = OnceLock::new();
+
+/// A [`Layer`] that collects cgx's spans, if exporting them is configured.
+///
+/// Only spans from cgx itself are collected, not those of the libraries it uses.
+pub(crate) fn layer<S>() -> Option<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let exporter = OtlpExporter::from_env()?;
+    let _ = COLLECTOR.set(Collector {
+        exporter,
+        trace_id: TraceId::random(),
+        finished: Mutex::new(Vec::new()),
+    });
+
+    Some(
+        SpanCollector.with_filter(tracing_subscriber::filter::filter_fn(|metadata| {
+            metadata.is_span() && metadata.target().starts_with("cgx")
+        })),
+    )
+}
+
+/// Send the spans that have finished so far, if traces are being exported.
+///
+/// Called before cgx exits or replaces itself with the tool, since spans that are still open at
+/// that point are lost.  A collector that can't be reached only gets a warning, since the trace
+/// is not worth failing the run over.
+pub(crate) fn flush() {
+    let Some(collector) = COLLECTOR.get() else {
+        return;
+    };
+
+    let spans = std::mem::take(&mut *collector.finished.lock().unwrap_or_else(|e| e.into_inner()));
+    if let Err(e) = collector.exporter.export(&spans) {
+        tracing::warn!("Failed to export traces: {}", e);
+    }
+}
+
+/// What is known about a span that hasn't finished yet, kept in its extensions.
+struct OpenSpan {
+    span_id: SpanId,
+    parent_span_id: Option<SpanId>,
+    start: SystemTime,
+    attributes: Vec<(String, String)>,
+}
+
+struct SpanCollector;
+
+impl<S> Layer<S> for SpanCollector
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let parent_span_id = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<OpenSpan>().map(|open| open.span_id));
+        let mut open = OpenSpan {
+            span_id: SpanId::random(),
+            parent_span_id,
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+        };
+        attrs.record(&mut AttributeVisitor(&mut open.attributes));
+
+        span.extensions_mut().insert(open);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(open) = span.extensions_mut().get_mut::<OpenSpan>() {
+                values.record(&mut AttributeVisitor(&mut open.attributes));
+            }
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let (Some(collector), Some(span)) = (COLLECTOR.get(), ctx.span(&id)) else {
+            return;
+        };
+        let Some(open) = span.extensions_mut().remove::<OpenSpan>() else {
+            return;
+        };
+
+        collector
+            .finished
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(SpanRecord {
+                trace_id: collector.trace_id,
+                span_id: open.span_id,
+                parent_span_id: open.parent_span_id,
+                name: span.name().to_string(),
+                start: open.start,
+                end: SystemTime::now(),
+                attributes: open.attributes,
+            });
+    }
+}
+
+/// Records the fields of a span as attributes, replacing any earlier value of the same field.
+struct AttributeVisitor<'a>(&'a mut Vec<(String, String)>);
+
+impl AttributeVisitor<'_> {
+    fn set(&mut self, field: &Field, value: String) {
+        match self.0.iter_mut().find(|(name, _)| name == field.name()) {
+            Some((_, existing)) => *existing = value,
+            None => self.0.push((field.name().to_string(), value)),
+        }
+    }
+}
+
+impl Visit for AttributeVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.set(field, format!("{:?}", value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn fields_are_recorded_as_attributes() {
+        let attributes = std::sync::Arc::new(Mutex::new(Vec::new()));
+
+        /// Captures the attributes of the spans that close, without a collector to export to.
+        struct Capture(std::sync::Arc<Mutex<Vec<(String, String)>>>);
+
+        impl<S> Layer<S> for Capture
+        where
+            S: Subscriber + for<'a> LookupSpan<'a>,
+        {
+            fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+                let span = ctx.span(&id).unwrap();
+                if let Some(open) = span.extensions().get::<OpenSpan>() {
+                    self.0.lock().unwrap().extend(open.attributes.clone());
+                }
+            }
+        }
+
+        // Layers see `on_close` in the order they were added, so `Capture` goes first, while the
+        // span still has its extensions
+        let subscriber = tracing_subscriber::registry()
+            .with(Capture(attributes.clone()))
+            .with(SpanCollector);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("download", krate = "ripgrep", cache = tracing::field::Empty);
+            span.record("cache", "miss");
+            span.record("cache", "hit");
+        });
+
+        assert_eq!(
+            *attributes.lock().unwrap(),
+            vec![
+                ("krate".to_string(), "ripgrep".to_string()),
+                ("cache".to_string(), "hit".to_string()),
+            ]
+        );
+    }
+}
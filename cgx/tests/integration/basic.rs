@@ -37,3 +37,37 @@ fn test_version_output() {
         .stdout(predicates::str::is_empty())
         .stderr(predicates::str::is_match(r"cgx \d+\.\d+\.\d+ \([0-9a-f]{7} \d{4}-\d{2}-\d{2}\)\n").unwrap());
 }
+
+/// With `--message-format json`, `--version` prints the build metadata as a single JSON object on
+/// stdout instead.
+#[test]
+fn test_version_output_json() {
+    let mut cgx = Cgx::find();
+
+    let output = cgx
+        .cmd
+        .args(["--version", "--message-format", "json"])
+        .assert()
+        .success()
+        .stderr(predicates::str::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+
+    let info: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(info["name"], "cgx");
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(
+        info["git_sha"]
+            .as_str()
+            .unwrap()
+            .chars()
+            .all(|c| c.is_ascii_hexdigit())
+    );
+    assert!(info["build_date"].is_string());
+    assert!(info["features"].is_array());
+    assert_eq!(
+        info["message_schema_versions"],
+        serde_json::json!(cgx::messages::SUPPORTED_MESSAGE_SCHEMA_VERSIONS)
+    );
+}
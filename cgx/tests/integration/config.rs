@@ -26,6 +26,8 @@ eza = "=0.20.0"
         )
         .unwrap();
 
+    cgx.trust_dir(cgx.test_fs().cwd.path());
+
     cgx.cmd
         .arg("--no-exec")
         .arg("eza")
@@ -53,6 +55,8 @@ eza = "=0.20.0"
         )
         .unwrap();
 
+    cgx.trust_dir(cgx.test_fs().cwd.path());
+
     // CLI specifies different version - should override config
     cgx.cmd
         .arg("--no-exec")
@@ -99,6 +103,8 @@ eza = "=0.23.1"
         )
         .unwrap();
 
+    cgx.trust_dir(cgx.test_fs().cwd.path());
+
     cgx.cmd
         .arg("--no-exec")
         .arg("eza")
@@ -147,6 +153,9 @@ ripgrep = "13.0"
         )
         .unwrap();
 
+    cgx.trust_dir(cgx.test_fs().cwd.path());
+    cgx.trust_dir(project_dir.path());
+
     // Run from subdir - project config should win (it's closer than root)
     cgx.cmd
         .current_dir(subdir.path())
@@ -179,6 +188,8 @@ ripgrep = "=15.1.0"
         )
         .unwrap();
 
+    cgx.trust_dir(cgx.test_fs().cwd.path());
+
     // Use alias 'rg' instead of full name 'ripgrep'
     cgx.cmd
         .arg("--no-exec")
@@ -207,6 +218,8 @@ cargo-binstall = { git = "https://github.com/cargo-bins/cargo-binstall.git", tag
         )
         .unwrap();
 
+    cgx.trust_dir(cgx.test_fs().cwd.path());
+
     cgx.cmd
         .arg("--no-exec")
         .arg("cargo-binstall")
@@ -214,3 +227,30 @@ cargo-binstall = { git = "https://github.com/cargo-bins/cargo-binstall.git", tag
         .success()
         .stdout(predicates::str::contains("cargo-binstall-1.14.0"));
 }
+
+/// Test that version pins in an untrusted cwd config are not honored.
+///
+/// Until the directory is trusted with `cgx --trust`, a cgx.toml found in the directory hierarchy
+/// may only change settings that can't affect which code gets run, so the pin is ignored.
+#[test]
+fn untrusted_config_pins_ignored() {
+    let mut cgx = Cgx::with_test_fs();
+
+    cgx.test_fs()
+        .cwd
+        .child("cgx.toml")
+        .write_str(
+            r#"
+[tools]
+eza = "=0.20.0"
+"#,
+        )
+        .unwrap();
+
+    cgx.cmd
+        .arg("--no-exec")
+        .arg("eza")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("eza-0.20.0").not());
+}
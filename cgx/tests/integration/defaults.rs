@@ -2,7 +2,8 @@
 
 use crate::utils::{Cgx, CommandExt};
 use cgx::messages::{
-    BuildCacheMessage, CrateResolutionMessage, Message, PrebuiltBinaryMessage, RunnerMessage, SourceMessage,
+    BuildCacheMessage, CrateResolutionMessage, Message, PrebuiltBinaryMessage, RunEndMessage, RunnerMessage,
+    SourceMessage,
 };
 use predicates::prelude::*;
 
@@ -108,6 +109,16 @@ fn messages_with_prebuilt_binary() {
         )),
         "Expected CrateResolution::CacheMiss on first run"
     );
+    assert!(
+        matches!(
+            messages.last(),
+            Some(Message::RunEnd(RunEndMessage {
+                success: true,
+                execs_tool: true
+            }))
+        ),
+        "Expected RunEnd as the last message, before exec-ing the tool"
+    );
 
     // Second invocation should hit all caches
     let mut cgx = cgx.reset();
@@ -141,6 +152,16 @@ fn messages_with_prebuilt_binary() {
         )),
         "Expected RunnerMessage::ExecutionPlan"
     );
+    assert!(
+        matches!(
+            messages.last(),
+            Some(Message::RunEnd(RunEndMessage {
+                success: true,
+                execs_tool: false
+            }))
+        ),
+        "Expected RunEnd as the last message"
+    );
 }
 
 /// Test message reporting for a crate WITHOUT pre-built binaries (uses default settings).
@@ -215,3 +236,135 @@ fn messages_without_prebuilt_binary() {
         "Expected BuildCache::CacheHit on second run"
     );
 }
+
+/// Test that a build from source can be listed with `--list-installed` and run again with
+/// `--pin-build`, without any resolution.
+///
+/// ```sh
+/// cgx cargo-expand@=1.0.88 --version
+/// cgx --list-installed
+/// cgx --pin-build <KEY> cargo-expand --version
+/// ```
+#[test]
+fn pin_build_runs_cached_build() {
+    let mut cgx = Cgx::with_test_fs();
+
+    let (assert, messages) = cgx
+        .cmd
+        .with_json_messages()
+        .arg("cargo-expand@=1.0.88")
+        .arg("--version")
+        .assert_with_messages();
+
+    assert.success();
+
+    let build_key = messages
+        .iter()
+        .find_map(|m| match m {
+            Message::BuildCache(BuildCacheMessage::CacheStored { build_key, .. }) => Some(build_key.clone()),
+            _ => None,
+        })
+        .expect("Expected BuildCache::CacheStored with the build key");
+
+    let mut cgx = cgx.reset();
+    cgx.cmd
+        .arg("--list-installed")
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(format!(
+            "cargo-expand@1.0.88 {build_key} "
+        )));
+
+    let mut cgx = cgx.reset();
+    let (assert, messages) = cgx
+        .cmd
+        .with_json_messages()
+        .arg("--pin-build")
+        .arg(&build_key)
+        .arg("cargo-expand")
+        .arg("--version")
+        .assert_with_messages();
+
+    assert
+        .success()
+        .stdout(predicates::str::starts_with("cargo-expand"));
+
+    assert!(
+        messages
+            .iter()
+            .any(|m| matches!(m, Message::BuildCache(BuildCacheMessage::PinnedBuild { .. }))),
+        "Expected BuildCache::PinnedBuild"
+    );
+    assert!(
+        !messages.iter().any(|m| matches!(m, Message::CrateResolution(_))),
+        "Should not resolve the crate when running a pinned build"
+    );
+
+    // A pinned build of a different crate is refused
+    let mut cgx = cgx.reset();
+    cgx.cmd
+        .arg("--pin-build")
+        .arg(&build_key)
+        .arg("ripgrep")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("CGX0071"));
+}
+
+/// Test that `--fetch-source` downloads a crate's source and prints where it is, without building
+/// it, and copies it into `--out-dir` when that is given.
+///
+/// ```sh
+/// cgx --fetch-source cargo-expand@=1.0.88
+/// cgx --fetch-source --out-dir <DIR> cargo-expand@=1.0.88
+/// ```
+#[test]
+fn fetch_source_does_not_build() {
+    let mut cgx = Cgx::with_test_fs();
+
+    let (assert, messages) = cgx
+        .cmd
+        .with_json_messages()
+        .arg("--fetch-source")
+        .arg("cargo-expand@=1.0.88")
+        .assert_with_messages();
+
+    assert.success();
+    assert!(
+        messages
+            .iter()
+            .any(|m| matches!(m, Message::Source(SourceMessage::Downloaded { .. }))),
+        "Expected Source::Downloaded"
+    );
+    assert!(
+        !messages.iter().any(|m| matches!(m, Message::Build(_))),
+        "Should not build the crate"
+    );
+
+    let mut cgx = cgx.reset();
+    let output = cgx
+        .cmd
+        .arg("--fetch-source")
+        .arg("cargo-expand@=1.0.88")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let source_path = std::path::PathBuf::from(String::from_utf8(output.stdout).unwrap().trim());
+    assert!(source_path.starts_with(cgx.test_fs_app_root().path()));
+    assert!(source_path.join("Cargo.toml").is_file());
+
+    let out_dir = assert_fs::TempDir::new().unwrap();
+    let mut cgx = cgx.reset();
+    cgx.cmd
+        .arg("--fetch-source")
+        .arg("--out-dir")
+        .arg(out_dir.path())
+        .arg("cargo-expand@=1.0.88")
+        .assert()
+        .success()
+        .stdout(predicates::ord::eq(format!(
+            "{}\n",
+            out_dir.path().join("cargo-expand-1.0.88").display()
+        )));
+    assert!(out_dir.path().join("cargo-expand-1.0.88/Cargo.toml").is_file());
+}
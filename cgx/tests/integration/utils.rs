@@ -87,6 +87,19 @@ impl Cgx {
         self.test_fs().app_root.child("config")
     }
 
+    /// Trust the config file in `dir`, the same way a user would with `cgx --trust`.
+    ///
+    /// Directory config files are otherwise only partially honored.
+    pub(crate) fn trust_dir(&self, dir: &std::path::Path) {
+        let mut cmd = cargo_bin_cmd!("cgx");
+        cmd.arg("--app-dir")
+            .arg(self.test_fs_app_root().path())
+            .arg("--trust")
+            .arg(dir)
+            .assert()
+            .success();
+    }
+
     fn set_test_env(&mut self, test_fs: TestFs) {
         self.cmd
             .arg("--system-config-dir")